@@ -350,6 +350,56 @@ fn userdata_call_method(c: &mut Criterion) {
     });
 }
 
+// Methods are resolved by the generated `__index` (with the method lookup cache) when the userdata
+// has fields
+fn userdata_call_method_with_fields(c: &mut Criterion) {
+    struct UserData(i64);
+    impl LuaUserData for UserData {
+        fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("value", |_, this| Ok(this.0));
+        }
+
+        fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("add", |_, this, i: i64| Ok(this.0 + i));
+        }
+    }
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(UserData(123)).unwrap();
+    let method = lua
+        .load("function(ud, i) return ud:add(i) end")
+        .eval::<LuaFunction>()
+        .unwrap();
+    let field = lua
+        .load("function(ud) return ud.value end")
+        .eval::<LuaFunction>()
+        .unwrap();
+    let i = AtomicUsize::new(0);
+
+    c.bench_function("userdata [call method with fields]", |b| {
+        b.iter_batched(
+            || {
+                collect_gc_twice(&lua);
+                i.fetch_add(1, Ordering::Relaxed)
+            },
+            |i| {
+                assert_eq!(method.call::<usize>((&ud, i)).unwrap(), 123 + i);
+            },
+            BatchSize::SmallInput,
+        );
+    });
+
+    c.bench_function("userdata [get field]", |b| {
+        b.iter_batched(
+            || collect_gc_twice(&lua),
+            |_| {
+                assert_eq!(field.call::<i64>(&ud).unwrap(), 123);
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
 fn userdata_async_call_method(c: &mut Criterion) {
     struct UserData(i64);
     impl LuaUserData for UserData {
@@ -413,6 +463,7 @@ criterion_group! {
         userdata_create,
         userdata_call_index,
         userdata_call_method,
+        userdata_call_method_with_fields,
         userdata_async_call_method,
 }
 
@@ -56,3 +56,16 @@ pub fn probe_lua() {
 
     lua.unwrap_or_else(|err| panic!("cannot find Lua{ver} using `pkg-config`: {err}"));
 }
+
+// Returns the error propagation mechanism used by the system Lua.
+//
+// It cannot be detected, so the default can be overridden using the `LUA_UNWIND_MODE` env variable.
+pub fn unwind_mode() -> String {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    let default = if cfg!(feature = "luajit") && target_os == "windows" {
+        "external"
+    } else {
+        "longjmp"
+    };
+    super::requested_unwind_mode().unwrap_or_else(|| default.to_string())
+}
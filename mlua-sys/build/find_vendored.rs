@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 
+use std::env;
+
 pub fn probe_lua() {
     #[cfg(feature = "lua54")]
     let artifacts = lua_src::Build::new().build(lua_src::Lua54);
@@ -23,7 +25,30 @@ pub fn probe_lua() {
         .enable_codegen(cfg!(feature = "luau-codegen"))
         .set_max_cstack_size(1000000)
         .set_vector_size(if cfg!(feature = "luau-vector4") { 4 } else { 3 })
+        .use_longjmp(unwind_mode() == "longjmp")
         .build();
 
     artifacts.print_cargo_metadata();
 }
+
+// Returns the error propagation mechanism used by the vendored Lua.
+//
+// Only Luau can be built with either of the mechanisms, for other versions the requested mode must
+// match the one used by the vendored sources.
+pub fn unwind_mode() -> String {
+    let target = env::var("TARGET").unwrap();
+
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52", feature = "lua51"))]
+    let supported = if target.ends_with("emscripten") { ["cxx"] } else { ["longjmp"] };
+    // LuaJIT uses the system unwinder on Windows and the internal one elsewhere
+    #[cfg(feature = "luajit")]
+    let supported = if target.contains("windows") { ["external"] } else { ["longjmp"] };
+    #[cfg(feature = "luau")]
+    let supported = ["cxx", "longjmp"];
+
+    match super::requested_unwind_mode() {
+        None => supported[0].to_string(),
+        Some(mode) if supported.contains(&mode.as_str()) => mode,
+        Some(mode) => panic!("unwind mode `{mode}` is not supported by the vendored Lua for `{target}`"),
+    }
+}
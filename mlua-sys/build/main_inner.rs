@@ -18,6 +18,8 @@ fn main() {
     compile_error!("`vendored` and `module` features are mutually exclusive");
 
     println!("cargo:rerun-if-changed=build");
+    println!("cargo:rerun-if-env-changed=LUA_UNWIND_MODE");
+    println!("cargo:rustc-env=MLUA_UNWIND_MODE={}", find::unwind_mode());
 
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
     if target_os == "windows" && cfg!(feature = "module") {
@@ -33,3 +35,13 @@ fn main() {
     #[cfg(not(feature = "module"))]
     find::probe_lua();
 }
+
+// Returns the error propagation mechanism requested using the `LUA_UNWIND_MODE` env variable
+fn requested_unwind_mode() -> Option<String> {
+    let mode = env::var("LUA_UNWIND_MODE").unwrap_or_default();
+    match mode.as_str() {
+        "" => None,
+        "longjmp" | "cxx" | "external" => Some(mode),
+        _ => panic!("invalid `LUA_UNWIND_MODE` value `{mode}`, expected `longjmp`, `cxx` or `external`"),
+    }
+}
@@ -34,6 +34,10 @@ pub const LUA_MAX_UPVALUES: c_int = 60;
 #[doc(hidden)]
 pub const LUA_MAX_UPVALUES: c_int = 200;
 
+// Error propagation mechanism of the linked Lua library (`longjmp`, `cxx` or `external`)
+#[doc(hidden)]
+pub const LUA_UNWIND_MODE: &str = env!("MLUA_UNWIND_MODE");
+
 // I believe `luaL_traceback` < 5.4 requires this much free stack to not error.
 // 5.4 uses `luaL_Buffer`
 #[doc(hidden)]
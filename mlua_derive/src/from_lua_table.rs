@@ -0,0 +1,102 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, LitStr};
+
+pub fn from_lua_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_from_lua_table(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_from_lua_table(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.span(),
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        _ => return Err(Error::new(input.span(), "only structs are supported")),
+    };
+    let ident = &input.ident;
+    let members = fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let keys = (members.iter())
+        .map(|ident| LitStr::new(&ident.to_string(), ident.span()))
+        .collect::<Vec<_>>();
+    let positions = 1..=members.len();
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    where_clause
+        .predicates
+        .push(parse_quote!(Self: ::std::default::Default));
+    for field in fields {
+        let ty = &field.ty;
+        where_clause.predicates.push(parse_quote!(#ty: ::mlua::FromLua));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::mlua::FromLuaTable for #ident #ty_generics #where_clause {
+            fn from_lua_table(table: ::mlua::Table, lua: &::mlua::Lua) -> ::mlua::Result<Self> {
+                let bad_argument = |name: &str, cause: ::mlua::Error| ::mlua::Error::BadArgument {
+                    to: None,
+                    pos: 1,
+                    name: Some(name.to_string()),
+                    cause: ::std::sync::Arc::new(cause),
+                };
+                let mut this = <Self as ::std::default::Default>::default();
+                for pair in table.pairs::<::mlua::Value, ::mlua::Value>() {
+                    let (key, value) = pair?;
+                    let name = match &key {
+                        ::mlua::Value::String(name) => name.to_string_lossy(),
+                        key => {
+                            let cause = ::mlua::Error::runtime(format!("unexpected {} key", key.type_name()));
+                            return Err(::mlua::Error::BadArgument { to: None, pos: 1, name: None, cause: ::std::sync::Arc::new(cause) });
+                        }
+                    };
+                    match &*name {
+                        #(
+                            #keys => {
+                                this.#members = ::mlua::FromLua::from_lua(value, lua)
+                                    .map_err(|err| bad_argument(&name, err))?;
+                            }
+                        )*
+                        _ => return Err(bad_argument(&name, ::mlua::Error::runtime("unknown argument"))),
+                    }
+                }
+                Ok(this)
+            }
+
+            fn from_lua_positional(args: ::mlua::MultiValue, lua: &::mlua::Lua) -> ::mlua::Result<Self> {
+                let mut this = <Self as ::std::default::Default>::default();
+                let mut args = args.into_iter();
+                #(
+                    match args.next() {
+                        None | Some(::mlua::Value::Nil) => {}
+                        Some(value) => {
+                            this.#members = ::mlua::FromLua::from_lua(value, lua).map_err(|err| {
+                                ::mlua::Error::BadArgument {
+                                    to: None,
+                                    pos: #positions,
+                                    name: Some(#keys.to_string()),
+                                    cause: ::std::sync::Arc::new(err),
+                                }
+                            })?;
+                        }
+                    }
+                )*
+                Ok(this)
+            }
+        }
+    })
+}
@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+use std::{env, fs};
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span};
+use quote::quote;
+use syn::{parse_macro_input, Error, LitStr};
+
+pub fn include_lua(input: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(input as LitStr);
+    match expand(&path) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(path: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    // Paths are resolved relative to the crate root (as the calling file is unknown on stable)
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let full_path = PathBuf::from(manifest_dir).join(path.value());
+    let source = fs::read(&full_path).map_err(|err| {
+        Error::new(
+            path.span(),
+            format!("cannot read `{}`: {err}", full_path.display()),
+        )
+    })?;
+
+    let name = format!("@{}", path.value());
+    let bytecode = compile(&source, &name).map_err(|err| Error::new(path.span(), err))?;
+    let bytecode = Literal::byte_string(&bytecode);
+    let full_path = LitStr::new(&full_path.to_string_lossy(), Span::call_site());
+
+    Ok(quote! {{
+        use ::mlua::{AsChunk, ChunkMode};
+        use ::std::borrow::Cow;
+        use ::std::io::Result as IoResult;
+
+        // Recompile when the script is changed
+        const _: &[u8] = ::std::include_bytes!(#full_path);
+
+        struct CompiledChunk(&'static [u8]);
+
+        impl AsChunk<'static> for CompiledChunk {
+            fn name(&self) -> Option<::std::string::String> {
+                Some(#name.to_string())
+            }
+
+            fn mode(&self) -> Option<ChunkMode> {
+                Some(ChunkMode::Binary)
+            }
+
+            fn source(self) -> IoResult<Cow<'static, [u8]>> {
+                Ok(Cow::Borrowed(self.0))
+            }
+        }
+
+        CompiledChunk(#bytecode)
+    }})
+}
+
+/// Compiles Lua source to stripped bytecode using Luau compiler.
+#[cfg(feature = "luau")]
+fn compile(source: &[u8], _name: &str) -> Result<Vec<u8>, String> {
+    let mut options = ffi::lua_CompileOptions::default();
+    options.debugLevel = 0;
+    let bytecode = unsafe { ffi::luau_compile(source, options) };
+    // Luau compiler returns an error message prefixed with zero byte
+    match bytecode.split_first() {
+        Some((0, msg)) => Err(String::from_utf8_lossy(msg).into_owned()),
+        _ => Ok(bytecode),
+    }
+}
+
+/// Compiles Lua source to stripped bytecode by loading it into a fresh Lua state and dumping
+/// the main function.
+#[cfg(not(feature = "luau"))]
+fn compile(source: &[u8], name: &str) -> Result<Vec<u8>, String> {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::slice;
+
+    unsafe extern "C-unwind" fn writer(
+        _state: *mut ffi::lua_State,
+        buf: *const c_void,
+        buf_len: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        let data = &mut *(data as *mut Vec<u8>);
+        data.extend_from_slice(slice::from_raw_parts(buf as *const u8, buf_len));
+        0
+    }
+
+    let name = CString::new(name).map_err(|err| err.to_string())?;
+    unsafe {
+        let state = ffi::luaL_newstate();
+        if state.is_null() {
+            return Err("cannot create Lua state".to_string());
+        }
+        let status = ffi::luaL_loadbufferx(
+            state,
+            source.as_ptr() as *const c_char,
+            source.len(),
+            name.as_ptr(),
+            c"t".as_ptr(),
+        );
+        let result = if status == ffi::LUA_OK {
+            let mut bytecode = Vec::new();
+            ffi::lua_dump(state, writer, &mut bytecode as *mut Vec<u8> as *mut c_void, 1);
+            Ok(bytecode)
+        } else {
+            let mut len = 0;
+            let msg = ffi::lua_tolstring(state, -1, &mut len);
+            Err(String::from_utf8_lossy(slice::from_raw_parts(msg as *const u8, len)).into_owned())
+        };
+        ffi::lua_close(state);
+        result
+    }
+}
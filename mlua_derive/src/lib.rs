@@ -74,6 +74,40 @@ pub fn lua_module(attr: TokenStream, item: TokenStream) -> TokenStream {
     wrapped.into()
 }
 
+#[proc_macro_attribute]
+pub fn lua_plugin(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = ModuleAttributes::default();
+    if !attr.is_empty() {
+        let args_parser = syn::meta::parser(|meta| args.parse(meta));
+        parse_macro_input!(attr with args_parser);
+    }
+    if args.skip_memory_check {
+        let err = syn::Error::new(
+            Span::call_site(),
+            "`skip_memory_check` is supported only in module mode",
+        );
+        return err.to_compile_error().into();
+    }
+
+    let func = parse_macro_input!(item as ItemFn);
+    let func_name = &func.sig.ident;
+    let plugin_name = args.name.unwrap_or_else(|| func_name.clone());
+    let ext_entrypoint_name = Ident::new(&format!("luaopen_{plugin_name}"), Span::call_site());
+
+    let wrapped = quote! {
+        mlua::require_plugin_feature!();
+
+        #func
+
+        #[no_mangle]
+        pub unsafe extern "C-unwind" fn #ext_entrypoint_name(state: *mut mlua::lua_State) -> ::std::os::raw::c_int {
+            mlua::Lua::entrypoint1(state, #func_name)
+        }
+    };
+
+    wrapped.into()
+}
+
 #[cfg(feature = "macros")]
 fn to_ident(tt: &TokenTree) -> TokenStream2 {
     let s: TokenStream = tt.clone().into();
@@ -151,9 +185,59 @@ pub fn from_lua(input: TokenStream) -> TokenStream {
     from_lua::from_lua(input)
 }
 
+#[cfg(feature = "macros")]
+#[proc_macro_derive(IntoLuaMulti, attributes(mlua))]
+pub fn into_lua_multi(input: TokenStream) -> TokenStream {
+    multi::into_lua_multi(input)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro_derive(FromLuaMulti, attributes(mlua))]
+pub fn from_lua_multi(input: TokenStream) -> TokenStream {
+    multi::from_lua_multi(input)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro_derive(FromLuaTable)]
+pub fn from_lua_table(input: TokenStream) -> TokenStream {
+    from_lua_table::from_lua_table(input)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro_derive(LuaTable, attributes(lua))]
+pub fn lua_table(input: TokenStream) -> TokenStream {
+    lua_table::lua_table(input)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro_attribute]
+pub fn lua_trait(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let err = syn::Error::new(Span::call_site(), "`lua_trait` does not accept arguments");
+        return err.to_compile_error().into();
+    }
+    lua_trait::lua_trait(item)
+}
+
+#[cfg(feature = "macros")]
+#[proc_macro]
+pub fn include_lua(input: TokenStream) -> TokenStream {
+    include_lua::include_lua(input)
+}
+
 #[cfg(feature = "macros")]
 mod chunk;
 #[cfg(feature = "macros")]
 mod from_lua;
 #[cfg(feature = "macros")]
+mod from_lua_table;
+#[cfg(feature = "macros")]
+mod include_lua;
+#[cfg(feature = "macros")]
+mod lua_table;
+#[cfg(feature = "macros")]
+mod lua_trait;
+#[cfg(feature = "macros")]
+mod multi;
+#[cfg(feature = "macros")]
 mod token;
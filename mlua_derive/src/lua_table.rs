@@ -0,0 +1,211 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, LitStr, Type};
+
+pub fn lua_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_lua_table(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    // Stored under its key
+    Key,
+    // Stored under its key, missing (nil) values are replaced with `Default::default()`
+    Default,
+    // Not stored, always `Default::default()` when converting from Lua
+    Skip,
+    // Fields of the (table) value are stored in the parent table
+    Flatten,
+}
+
+/// A struct field to convert.
+struct Field {
+    ident: Ident,
+    ty: Type,
+    key: LitStr,
+    mode: Mode,
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.clone().unwrap();
+        let mut rename = None;
+        let mut modes = Vec::new();
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?);
+                } else if meta.path.is_ident("default") {
+                    modes.push(Mode::Default);
+                } else if meta.path.is_ident("skip") {
+                    modes.push(Mode::Skip);
+                } else if meta.path.is_ident("flatten") {
+                    modes.push(Mode::Flatten);
+                } else {
+                    return Err(meta.error(
+                        "unsupported `lua` attribute, expected `rename`, `default`, `skip` or `flatten`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+
+        let mode = match modes[..] {
+            [] => Mode::Key,
+            [mode] => mode,
+            _ => return Err(Error::new(field.span(), "conflicting `lua` attributes")),
+        };
+        if rename.is_some() && matches!(mode, Mode::Skip | Mode::Flatten) {
+            return Err(Error::new(
+                field.span(),
+                "`rename` cannot be used with `skip` or `flatten`",
+            ));
+        }
+        let key = rename.unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+
+        Ok(Field {
+            ident,
+            ty: field.ty.clone(),
+            key,
+            mode,
+        })
+    }
+}
+
+fn expand_lua_table(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.span(),
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        _ => return Err(Error::new(input.span(), "only structs are supported")),
+    };
+    let fields = fields.iter().map(Field::parse).collect::<syn::Result<Vec<_>>>()?;
+    let ident = &input.ident;
+    let ident_str = ident.to_string();
+
+    // Bounds are required only for generic structs
+    let mut into_generics = input.generics.clone();
+    let mut from_generics = input.generics.clone();
+    if !input.generics.params.is_empty() {
+        let into_where = into_generics.make_where_clause();
+        let from_where = from_generics.make_where_clause();
+        for field in &fields {
+            let ty = &field.ty;
+            match field.mode {
+                Mode::Key | Mode::Flatten => {
+                    into_where.predicates.push(parse_quote!(#ty: ::mlua::IntoLua));
+                    from_where.predicates.push(parse_quote!(#ty: ::mlua::FromLua));
+                }
+                Mode::Default => {
+                    into_where.predicates.push(parse_quote!(#ty: ::mlua::IntoLua));
+                    from_where
+                        .predicates
+                        .push(parse_quote!(#ty: ::mlua::FromLua + ::std::default::Default));
+                }
+                Mode::Skip => {
+                    from_where
+                        .predicates
+                        .push(parse_quote!(#ty: ::std::default::Default));
+                }
+            }
+        }
+    }
+
+    let set_fields = fields.iter().map(|field| {
+        let Field { ident, key, .. } = field;
+        match field.mode {
+            Mode::Key | Mode::Default => quote! {
+                table.raw_set(#key, self.#ident)?;
+            },
+            Mode::Skip => quote! {},
+            Mode::Flatten => quote! {
+                match ::mlua::IntoLua::into_lua(self.#ident, lua)? {
+                    ::mlua::Value::Table(t) => {
+                        for pair in t.pairs::<::mlua::Value, ::mlua::Value>() {
+                            let (key, value) = pair?;
+                            table.raw_set(key, value)?;
+                        }
+                    }
+                    ::mlua::Value::Nil => {}
+                    value => {
+                        return Err(::mlua::Error::ToLuaConversionError {
+                            from: #ident_str.to_string(),
+                            to: "table",
+                            message: Some(format!(
+                                "cannot flatten field `{}` of type {}",
+                                stringify!(#ident),
+                                value.type_name(),
+                            )),
+                        })
+                    }
+                }
+            },
+        }
+    });
+
+    let get_fields = fields.iter().map(|field| {
+        let Field { ident, key, .. } = field;
+        let context = quote! {
+            |err| ::mlua::ErrorContext::push_context(err, format!("field `{}`", #key))
+        };
+        let value = match field.mode {
+            Mode::Key => quote! {
+                ::mlua::FromLua::from_lua(table.get::<::mlua::Value>(#key)?, lua).map_err(#context)?
+            },
+            Mode::Default => quote! {
+                match table.get::<::mlua::Value>(#key)? {
+                    ::mlua::Value::Nil => ::std::default::Default::default(),
+                    value => ::mlua::FromLua::from_lua(value, lua).map_err(#context)?,
+                }
+            },
+            Mode::Skip => quote! { ::std::default::Default::default() },
+            Mode::Flatten => quote! {
+                ::mlua::FromLua::from_lua(::mlua::Value::Table(table.clone()), lua)?
+            },
+        };
+        quote! { #ident: #value }
+    });
+
+    let (into_impl_generics, ty_generics, into_where_clause) = into_generics.split_for_impl();
+    let (from_impl_generics, _, from_where_clause) = from_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #into_impl_generics ::mlua::IntoLua for #ident #ty_generics #into_where_clause {
+            fn into_lua(self, lua: &::mlua::Lua) -> ::mlua::Result<::mlua::Value> {
+                let table = lua.create_table()?;
+                #(#set_fields)*
+                Ok(::mlua::Value::Table(table))
+            }
+        }
+
+        impl #from_impl_generics ::mlua::FromLua for #ident #ty_generics #from_where_clause {
+            fn from_lua(value: ::mlua::Value, lua: &::mlua::Lua) -> ::mlua::Result<Self> {
+                let table = match value {
+                    ::mlua::Value::Table(table) => table,
+                    _ => {
+                        return Err(::mlua::Error::FromLuaConversionError {
+                            from: value.type_name(),
+                            to: #ident_str.to_string(),
+                            message: Some("expected table".to_string()),
+                        })
+                    }
+                };
+                Ok(Self {
+                    #(#get_fields),*
+                })
+            }
+        }
+    })
+}
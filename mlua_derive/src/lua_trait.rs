@@ -0,0 +1,159 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Error, FnArg, GenericParam, ItemTrait, Pat, PathArguments, ReturnType, TraitItem,
+    TraitItemFn, Type,
+};
+
+pub fn lua_trait(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemTrait);
+    match expand(&item) {
+        Ok(impl_tokens) => quote!(#item #impl_tokens).into(),
+        Err(err) => {
+            let err = err.to_compile_error();
+            quote!(#item #err).into()
+        }
+    }
+}
+
+fn expand(item: &ItemTrait) -> syn::Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "generic traits are not supported by `lua_trait`",
+        ));
+    }
+
+    let trait_ident = &item.ident;
+    let struct_ident = format_ident!("__MluaLuaTrait{}", trait_ident);
+    let trait_name = format!("dyn {trait_ident}");
+
+    let mut methods = Vec::new();
+    let mut required = Vec::new();
+    for trait_item in &item.items {
+        match trait_item {
+            TraitItem::Fn(func) => {
+                if func.default.is_none() {
+                    required.push(func.sig.ident.to_string());
+                }
+                methods.push(expand_method(func)?);
+            }
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "only methods are supported by `lua_trait`",
+                ))
+            }
+        }
+    }
+
+    Ok(quote! {
+        const _: () = {
+            #[doc(hidden)]
+            struct #struct_ident(::mlua::Table);
+
+            impl #trait_ident for #struct_ident {
+                #(#methods)*
+            }
+
+            impl ::mlua::LuaTrait for dyn #trait_ident {
+                fn from_lua_table(table: ::mlua::Table) -> ::mlua::Result<::std::boxed::Box<Self>> {
+                    #(
+                        if !table.contains_key(#required)? {
+                            return Err(::mlua::Error::FromLuaConversionError {
+                                from: "table",
+                                to: #trait_name.to_string(),
+                                message: Some(::std::format!("missing method `{}`", #required)),
+                            });
+                        }
+                    )*
+                    Ok(::std::boxed::Box::new(#struct_ident(table)))
+                }
+            }
+        };
+    })
+}
+
+fn expand_method(func: &TraitItemFn) -> syn::Result<TokenStream2> {
+    let sig = &func.sig;
+    if sig.receiver().is_none() {
+        return Err(Error::new(
+            sig.span(),
+            "`lua_trait` methods must have a `self` receiver",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            "async methods are not supported by `lua_trait`",
+        ));
+    }
+    if let Some(param) = (sig.generics.params.iter()).find(|p| !matches!(p, GenericParam::Lifetime(_))) {
+        return Err(Error::new(
+            param.span(),
+            "generic methods are not supported by `lua_trait`",
+        ));
+    }
+
+    // Replace argument patterns with plain identifiers, keeping the original patterns to be used by
+    // the default implementation
+    let mut new_sig = sig.clone();
+    let mut arg_idents = Vec::new();
+    let mut arg_bindings = Vec::new();
+    for (i, arg) in new_sig.inputs.iter_mut().enumerate() {
+        if let FnArg::Typed(arg) = arg {
+            let ident = Ident::new(&format!("__arg{i}"), Span::call_site());
+            let (pat, ty) = (&arg.pat, &arg.ty);
+            arg_bindings.push(quote!(let #pat: #ty = #ident;));
+            *arg.pat = Pat::Verbatim(quote!(#ident));
+            arg_idents.push(ident);
+        }
+    }
+
+    let name = sig.ident.to_string();
+    let call = quote! {
+        ::mlua::ObjectLike::call_method(&self.0, #name, (#(#arg_idents,)*))
+    };
+    let body = match &sig.output {
+        // Lua errors are converted to the error type of the returned `Result`
+        ReturnType::Type(_, ty) if is_result_type(ty) => quote!(#call.map_err(::core::convert::Into::into)),
+        _ => {
+            quote! {
+                match #call {
+                    Ok(value) => value,
+                    Err(err) => ::std::panic!("error calling Lua method `{}`: {}", #name, err),
+                }
+            }
+        }
+    };
+
+    let body = match &func.default {
+        Some(default) => quote! {
+            if self.0.contains_key(#name).unwrap_or(false) {
+                #body
+            } else {
+                #(#arg_bindings)*
+                #default
+            }
+        },
+        None => body,
+    };
+
+    Ok(quote! {
+        #new_sig {
+            #body
+        }
+    })
+}
+
+/// Checks if `ty` looks like `Result<T, ...>`.
+fn is_result_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => (path.path.segments.last())
+            .map(|seg| seg.ident == "Result" && matches!(seg.arguments, PathArguments::AngleBracketed(_)))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
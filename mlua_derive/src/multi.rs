@@ -0,0 +1,219 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
+use quote::{format_ident, quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Index, LitStr, Member, Type};
+
+pub fn into_lua_multi(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_into_lua_multi(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+pub fn from_lua_multi(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_from_lua_multi(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// A struct field to convert.
+struct Field {
+    member: Member,
+    ty: Type,
+    // Key in the table representation
+    key: TokenStream2,
+}
+
+struct Struct {
+    input: DeriveInput,
+    fields: Vec<Field>,
+    // Struct has named fields
+    named: bool,
+    // Struct has no fields at all (`struct Unit;`)
+    unit: bool,
+    // Convert the struct to a single table instead of multiple values
+    as_table: bool,
+}
+
+impl Struct {
+    fn parse(input: DeriveInput) -> syn::Result<Self> {
+        let mut as_table = false;
+        for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("mlua")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("table") {
+                    as_table = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported `mlua` attribute, expected `table`"))
+                }
+            })?;
+        }
+
+        let data = match &input.data {
+            Data::Struct(data) => data,
+            _ => return Err(Error::new(input.span(), "only structs are supported")),
+        };
+        let named = matches!(data.fields, Fields::Named(_));
+        let unit = matches!(data.fields, Fields::Unit);
+        let fields = (data.fields.iter().enumerate())
+            .map(|(i, field)| match &field.ident {
+                Some(ident) => Field {
+                    member: Member::Named(ident.clone()),
+                    ty: field.ty.clone(),
+                    key: LitStr::new(&ident.to_string(), ident.span()).to_token_stream(),
+                },
+                None => {
+                    // Lua sequences start from 1
+                    let key = i + 1;
+                    Field {
+                        member: Member::Unnamed(Index::from(i)),
+                        ty: field.ty.clone(),
+                        key: quote!(#key),
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Struct {
+            input,
+            fields,
+            named,
+            unit,
+            as_table,
+        })
+    }
+
+    // Builds `Self` from the bindings `__field0`, `__field1`, ...
+    fn construct(&self) -> TokenStream2 {
+        let bindings = self.bindings();
+        if self.unit {
+            quote!(Self)
+        } else if self.named {
+            let members = self.fields.iter().map(|f| &f.member);
+            quote!(Self { #(#members: #bindings),* })
+        } else {
+            quote!(Self(#(#bindings),*))
+        }
+    }
+
+    fn bindings(&self) -> Vec<Ident> {
+        (0..self.fields.len())
+            .map(|i| format_ident!("__field{}", i))
+            .collect()
+    }
+
+    // Tuple type of all fields used to convert the struct to (or from) multiple values
+    fn tuple_type(&self) -> TokenStream2 {
+        let types = self.fields.iter().map(|f| &f.ty);
+        quote!((#(#types,)*))
+    }
+}
+
+fn expand_into_lua_multi(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let st = Struct::parse(input)?;
+    let ident = &st.input.ident;
+    let members = st.fields.iter().map(|f| &f.member).collect::<Vec<_>>();
+
+    let mut generics = st.input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    let body = if st.as_table {
+        for field in &st.fields {
+            let ty = &field.ty;
+            where_clause.predicates.push(parse_quote!(#ty: ::mlua::IntoLua));
+        }
+        let keys = st.fields.iter().map(|f| &f.key);
+        let len = st.fields.len();
+        let (narr, nrec) = if st.named { (0, len) } else { (len, 0) };
+        quote! {
+            let table = lua.create_table_with_capacity(#narr, #nrec)?;
+            #( table.raw_set(#keys, self.#members)?; )*
+            ::mlua::IntoLuaMulti::into_lua_multi(table, lua)
+        }
+    } else {
+        let tuple_type = st.tuple_type();
+        where_clause
+            .predicates
+            .push(parse_quote!(#tuple_type: ::mlua::IntoLuaMulti));
+        quote! {
+            ::mlua::IntoLuaMulti::into_lua_multi((#(self.#members,)*), lua)
+        }
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::mlua::IntoLuaMulti for #ident #ty_generics #where_clause {
+            #[inline]
+            fn into_lua_multi(self, lua: &::mlua::Lua) -> ::mlua::Result<::mlua::MultiValue> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_from_lua_multi(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let st = Struct::parse(input)?;
+    let ident = &st.input.ident;
+    let ident_str = ident.to_string();
+    let bindings = st.bindings();
+    let construct = st.construct();
+
+    let mut generics = st.input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    let methods = if st.as_table {
+        for field in &st.fields {
+            let ty = &field.ty;
+            where_clause.predicates.push(parse_quote!(#ty: ::mlua::FromLua));
+        }
+        let keys = st.fields.iter().map(|f| &f.key);
+        let types = st.fields.iter().map(|f| &f.ty);
+        quote! {
+            fn from_lua_multi(mut values: ::mlua::MultiValue, _: &::mlua::Lua) -> ::mlua::Result<Self> {
+                match values.pop_front().unwrap_or(::mlua::Value::Nil) {
+                    ::mlua::Value::Table(table) => {
+                        #( let #bindings: #types = table.get(#keys)?; )*
+                        Ok(#construct)
+                    }
+                    value => Err(::mlua::Error::FromLuaConversionError {
+                        from: value.type_name(),
+                        to: #ident_str.to_string(),
+                        message: Some("expected table".to_string()),
+                    }),
+                }
+            }
+        }
+    } else {
+        let tuple_type = st.tuple_type();
+        where_clause
+            .predicates
+            .push(parse_quote!(#tuple_type: ::mlua::FromLuaMulti));
+        quote! {
+            #[inline]
+            fn from_lua_multi(values: ::mlua::MultiValue, lua: &::mlua::Lua) -> ::mlua::Result<Self> {
+                let (#(#bindings,)*) = <#tuple_type as ::mlua::FromLuaMulti>::from_lua_multi(values, lua)?;
+                Ok(#construct)
+            }
+
+            #[inline]
+            fn from_lua_args(
+                args: ::mlua::MultiValue,
+                i: usize,
+                to: Option<&str>,
+                lua: &::mlua::Lua,
+            ) -> ::mlua::Result<Self> {
+                let (#(#bindings,)*) = <#tuple_type as ::mlua::FromLuaMulti>::from_lua_args(args, i, to, lua)?;
+                Ok(#construct)
+            }
+        }
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::mlua::FromLuaMulti for #ident #ty_generics #where_clause {
+            #methods
+        }
+    })
+}
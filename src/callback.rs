@@ -0,0 +1,235 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+#[cfg(feature = "async")]
+use std::future::Future;
+
+use crate::error::Result;
+use crate::function::Function;
+use crate::state::Lua;
+use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Value};
+
+macro_rules! impl_function_wrapper {
+    ($name:ident<$($param:ident),+>) => {
+        impl<$($param),+> $name<$($param),+> {
+            /// Returns a reference to the wrapped function.
+            #[inline]
+            pub fn function(&self) -> &Function {
+                &self.func
+            }
+
+            /// Returns the wrapped function.
+            #[inline]
+            pub fn into_function(self) -> Function {
+                self.func
+            }
+        }
+
+        impl<$($param),+> From<Function> for $name<$($param),+> {
+            #[inline]
+            fn from(func: Function) -> Self {
+                $name {
+                    func,
+                    _marker: PhantomData,
+                }
+            }
+        }
+
+        impl<$($param),+> Clone for $name<$($param),+> {
+            #[inline]
+            fn clone(&self) -> Self {
+                Self::from(self.func.clone())
+            }
+        }
+
+        impl<$($param),+> fmt::Debug for $name<$($param),+> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_tuple(stringify!($name)).field(&self.func).finish()
+            }
+        }
+
+        impl<$($param),+> PartialEq for $name<$($param),+> {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                self.func == other.func
+            }
+        }
+
+        impl<$($param),+> FromLua for $name<$($param),+> {
+            #[inline]
+            fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+                Function::from_lua(value, lua).map(Self::from)
+            }
+        }
+
+        impl<$($param),+> IntoLua for $name<$($param),+> {
+            #[inline]
+            fn into_lua(self, _: &Lua) -> Result<Value> {
+                Ok(Value::Function(self.func))
+            }
+        }
+    };
+}
+
+/// A Lua function called for its side effects with arguments of type `A`.
+///
+/// Rust functions can accept `Callback` instead of a bare [`Function`] to document the expected
+/// signature. Any values returned by the Lua function are ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Callback, Lua, Result};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let for_each = lua.create_function(|_, (items, cb): (Vec<String>, Callback<(usize, String)>)| {
+///     for (i, item) in items.into_iter().enumerate() {
+///         cb.call((i + 1, item))?;
+///     }
+///     Ok(())
+/// })?;
+/// lua.globals().set("for_each", for_each)?;
+///
+/// lua.load(r#"
+///     local out = {}
+///     for_each({"a", "b"}, function(i, item) out[i] = item end)
+///     assert(out[1] == "a" and out[2] == "b")
+/// "#).exec()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Callback<A> {
+    func: Function,
+    _marker: PhantomData<fn(A)>,
+}
+
+impl<A: IntoLuaMulti> Callback<A> {
+    /// Calls the function with the given arguments, discarding the results.
+    pub fn call(&self, args: A) -> Result<()> {
+        self.func.call(args)
+    }
+}
+
+impl_function_wrapper!(Callback<A>);
+
+/// A Lua function that tests values of type `A`.
+///
+/// The result of the function is interpreted using Lua truthiness: everything except `nil` and
+/// `false` passes the test.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Predicate, Result};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let filter = lua.create_function(|_, (items, pred): (Vec<i64>, Predicate<i64>)| {
+///     let mut out = Vec::new();
+///     for item in items {
+///         if pred.test(item)? {
+///             out.push(item);
+///         }
+///     }
+///     Ok(out)
+/// })?;
+/// lua.globals().set("filter", filter)?;
+///
+/// let even: Vec<i64> = lua.load("filter({1, 2, 3, 4}, function(n) return n % 2 == 0 end)").eval()?;
+/// assert_eq!(even, vec![2, 4]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Predicate<A> {
+    func: Function,
+    _marker: PhantomData<fn(A)>,
+}
+
+impl<A: IntoLuaMulti> Predicate<A> {
+    /// Calls the function with the given arguments and returns `true` if the result is truthy.
+    pub fn test(&self, args: A) -> Result<bool> {
+        let result: Value = self.func.call(args)?;
+        Ok(!matches!(result, Value::Nil | Value::Boolean(false)))
+    }
+}
+
+impl_function_wrapper!(Predicate<A>);
+
+/// A Lua function that maps values of type `A` to values of type `R`.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Mapper, Result};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let map = lua.create_function(|_, (items, mapper): (Vec<i64>, Mapper<i64, String>)| {
+///     items.into_iter().map(|item| mapper.map(item)).collect::<Result<Vec<_>>>()
+/// })?;
+/// lua.globals().set("map", map)?;
+///
+/// let names: Vec<String> = lua.load("map({1, 2}, function(n) return 'item' .. n end)").eval()?;
+/// assert_eq!(names, vec!["item1", "item2"]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Mapper<A, R> {
+    func: Function,
+    _marker: PhantomData<fn(A) -> R>,
+}
+
+impl<A: IntoLuaMulti, R: FromLuaMulti> Mapper<A, R> {
+    /// Calls the function with the given arguments and converts the result to `R`.
+    pub fn map(&self, args: A) -> Result<R> {
+        self.func.call(args)
+    }
+}
+
+impl_function_wrapper!(Mapper<A, R>);
+
+/// A Lua function with argument types `A` and return types `R` fixed at the type level.
+///
+/// Unlike [`Function`], the types don't need to be specified on every call, which is convenient
+/// for storing Lua callbacks in Rust structs. Arguments can be a single value or a tuple of values
+/// (any type implementing [`IntoLuaMulti`]), and so can the results.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result, TypedFunction};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// struct Handlers {
+///     on_add: TypedFunction<(i64, i64), i64>,
+/// }
+///
+/// let handlers = Handlers {
+///     on_add: lua.load("function(a, b) return a + b end").eval()?,
+/// };
+/// assert_eq!(handlers.on_add.call((1, 2))?, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedFunction<A, R> {
+    func: Function,
+    _marker: PhantomData<fn(A) -> R>,
+}
+
+impl<A: IntoLuaMulti, R: FromLuaMulti> TypedFunction<A, R> {
+    /// Calls the function with the given arguments and converts the results to `R`.
+    pub fn call(&self, args: A) -> Result<R> {
+        self.func.call(args)
+    }
+
+    /// Asynchronously calls the function with the given arguments.
+    ///
+    /// See [`Function::call_async`] for details.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn call_async(&self, args: A) -> impl Future<Output = Result<R>> {
+        self.func.call_async(args)
+    }
+}
+
+impl_function_wrapper!(TypedFunction<A, R>);
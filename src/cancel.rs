@@ -0,0 +1,168 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "async")]
+use {
+    parking_lot::Mutex,
+    std::future::{poll_fn, Future},
+    std::task::{Poll, Waker},
+};
+
+use crate::error::{Error, Result};
+use crate::userdata::{UserData, UserDataMethods};
+
+#[cfg(feature = "async")]
+use crate::{types::BoxFuture, util::Waiters};
+
+struct State {
+    cancelled: AtomicBool,
+    // Tasks waiting for cancellation
+    #[cfg(feature = "async")]
+    waiters: Mutex<Waiters>,
+}
+
+/// A token used by the host to request cooperative cancellation of Lua scripts.
+///
+/// The token can be passed to Lua as userdata, where scripts can query it using the following
+/// methods:
+/// - `cancelled()` returns `true` if cancellation was requested
+/// - `check()` raises a [`Error::Cancelled`] error if cancellation was requested
+/// - `wait()` waits until cancellation is requested (requires `feature = "async"`)
+///
+/// When the token is set for a Lua state using [`Lua::set_cancellation_token`], async Rust
+/// functions called from Lua (including `wait()`) observe it automatically and fail with
+/// [`Error::Cancelled`] once the cancellation is requested. Pure Lua code is not interrupted,
+/// long running loops should check the token periodically.
+///
+/// Cloned instances share the same state. The token is thread-safe, so it can be cancelled from
+/// any thread.
+///
+/// [`Lua::set_cancellation_token`]: crate::Lua::set_cancellation_token
+#[derive(Clone)]
+pub struct CancellationToken(Arc<State>);
+
+impl CancellationToken {
+    /// Creates a new token that is not cancelled.
+    pub fn new() -> Self {
+        CancellationToken(Arc::new(State {
+            cancelled: AtomicBool::new(false),
+            #[cfg(feature = "async")]
+            waiters: Mutex::new(Waiters::default()),
+        }))
+    }
+
+    /// Requests cancellation, waking up all tasks waiting for it.
+    ///
+    /// Calling this method more than once has no effect.
+    pub fn cancel(&self) {
+        #[cfg(not(feature = "async"))]
+        self.0.cancelled.store(true, Ordering::Release);
+        #[cfg(feature = "async")]
+        if !self.0.cancelled.swap(true, Ordering::AcqRel) {
+            let wakers = self.0.waiters.lock().take_all();
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+
+    /// Returns `true` if cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns [`Error::Cancelled`] if cancellation was requested.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Returns a future that completes when cancellation is requested.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn cancelled(&self) -> impl Future<Output = ()> + Send + 'static {
+        let guard = WaiterGuard {
+            state: self.0.clone(),
+            id: None,
+        };
+        async move {
+            let mut guard = guard;
+            poll_fn(|cx| {
+                if guard.state.cancelled.load(Ordering::Acquire) {
+                    return Poll::Ready(());
+                }
+                let mut waiters = guard.state.waiters.lock();
+                // Check again while holding the lock to not miss a wake up
+                if guard.state.cancelled.load(Ordering::Acquire) {
+                    return Poll::Ready(());
+                }
+                waiters.register(&mut guard.id, cx.waker());
+                Poll::Pending
+            })
+            .await
+        }
+    }
+
+    // Fails the future with `Error::Cancelled` when cancellation is requested
+    #[cfg(feature = "async")]
+    pub(crate) fn guard<'a, R: 'a>(&self, fut: BoxFuture<'a, Result<R>>) -> BoxFuture<'a, Result<R>> {
+        use futures_util::future::{self, Either};
+
+        let cancelled = self.cancelled();
+        Box::pin(async move {
+            futures_util::pin_mut!(cancelled);
+            match future::select(cancelled, fut).await {
+                Either::Left(_) => Err(Error::Cancelled),
+                Either::Right((res, _)) => res,
+            }
+        })
+    }
+}
+
+// Removes the waker of a dropped `cancelled` future
+#[cfg(feature = "async")]
+struct WaiterGuard {
+    state: Arc<State>,
+    id: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl Drop for WaiterGuard {
+    fn drop(&mut self) {
+        if self.id.is_some() {
+            self.state.waiters.lock().remove(self.id);
+        }
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl UserData for CancellationToken {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("cancelled", |_, this, ()| Ok(this.is_cancelled()));
+        methods.add_method("check", |_, this, ()| this.check());
+        #[cfg(feature = "async")]
+        methods.add_async_method("wait", |_, this, ()| {
+            let cancelled = this.cancelled();
+            async move {
+                cancelled.await;
+                Ok(())
+            }
+        });
+    }
+}
@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::{Lua, RawLua};
+use crate::table::Table;
+use crate::util::{check_stack, StackGuard};
+
+// Registry key of the table that maps environments to the sets of granted capabilities
+const GRANTS_KEY: &str = "__mlua_capabilities";
+
+/// A named permission required to call a Rust function from Lua.
+///
+/// Capabilities are hierarchical, with levels separated by dots. Granting a capability also grants
+/// all capabilities nested under it, eg. granting `fs` allows `fs.read` and `fs.write`.
+/// The special `*` capability grants everything.
+///
+/// See [`Lua::create_function_with_capabilities`] for details.
+///
+/// [`Lua::create_function_with_capabilities`]: crate::Lua::create_function_with_capabilities
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Capability(Cow<'static, str>);
+
+impl Capability {
+    /// Read access to the filesystem.
+    pub const FS_READ: Capability = Capability::new("fs.read");
+    /// Write access to the filesystem.
+    pub const FS_WRITE: Capability = Capability::new("fs.write");
+    /// Network access.
+    pub const NET: Capability = Capability::new("net");
+    /// Spawning and controlling processes.
+    pub const PROCESS: Capability = Capability::new("process");
+    /// Every capability.
+    pub const ALL: Capability = Capability::new("*");
+
+    /// Creates a new capability with the given name.
+    pub const fn new(name: &'static str) -> Self {
+        Capability(Cow::Borrowed(name))
+    }
+
+    /// Returns the capability name.
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if granting this capability allows `other`.
+    pub fn grants(&self, other: &Capability) -> bool {
+        let (name, other) = (self.name(), other.name());
+        name == "*"
+            || name == other
+            || (other.starts_with(name) && other.as_bytes().get(name.len()) == Some(&b'.'))
+    }
+}
+
+impl From<&'static str> for Capability {
+    fn from(name: &'static str) -> Self {
+        Capability(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for Capability {
+    fn from(name: String) -> Self {
+        Capability(Cow::Owned(name))
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+pub(crate) fn grant(lua: &Lua, env: &Table, caps: impl IntoIterator<Item = Capability>) -> Result<()> {
+    let grants = match lua.named_registry_value::<Option<Table>>(GRANTS_KEY)? {
+        Some(grants) => grants,
+        None => {
+            // Environments are weak keys, so granting does not keep them alive
+            let grants = lua.create_table()?;
+            let mt = lua.create_table()?;
+            mt.raw_set("__mode", "k")?;
+            grants.set_metatable(Some(mt));
+            lua.set_named_registry_value(GRANTS_KEY, &grants)?;
+            grants
+        }
+    };
+    let set = match grants.raw_get::<Option<Table>>(env)? {
+        Some(set) => set,
+        None => {
+            let set = lua.create_table()?;
+            grants.raw_set(env, &set)?;
+            set
+        }
+    };
+    for cap in caps {
+        set.raw_set(cap.name(), true)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn revoke(lua: &Lua, env: &Table, caps: impl IntoIterator<Item = Capability>) -> Result<()> {
+    if let Some(set) = granted_set(lua, env)? {
+        for cap in caps {
+            set.raw_set(cap.name(), false)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_granted(lua: &Lua, env: &Table, cap: &Capability) -> Result<bool> {
+    let Some(set) = granted_set(lua, env)? else {
+        return Ok(false);
+    };
+    if set.raw_get::<bool>("*")? {
+        return Ok(true);
+    }
+    // Check the capability itself and all its parents
+    let mut name = cap.name();
+    loop {
+        if set.raw_get::<bool>(name)? {
+            return Ok(true);
+        }
+        match name.rfind('.') {
+            Some(i) => name = &name[..i],
+            None => return Ok(false),
+        }
+    }
+}
+
+// Grants all capabilities of the `from` environment to the `to` environment
+pub(crate) fn inherit(lua: &Lua, from: &Table, to: &Table) -> Result<()> {
+    let Some(set) = granted_set(lua, from)? else {
+        return Ok(());
+    };
+    let mut caps = Vec::new();
+    set.for_each(|name: String, granted: bool| {
+        if granted {
+            caps.push(Capability::from(name));
+        }
+        Ok(())
+    })?;
+    grant(lua, to, caps)
+}
+
+fn granted_set(lua: &Lua, env: &Table) -> Result<Option<Table>> {
+    match lua.named_registry_value::<Option<Table>>(GRANTS_KEY)? {
+        Some(grants) => grants.raw_get(env),
+        None => Ok(None),
+    }
+}
+
+// Checks that the environment of the nearest Lua function calling the current Rust callback has
+// all the required capabilities.
+//
+// Calls without a Lua caller (eg. directly from Rust or as a coroutine body) and callers without
+// an environment are not trusted.
+pub(crate) unsafe fn check_caller(rawlua: &RawLua, required: &[Capability]) -> Result<()> {
+    if required.is_empty() {
+        return Ok(());
+    }
+    let env = caller_function(rawlua)?.and_then(|f| f.environment());
+    for cap in required {
+        let granted = match env {
+            Some(ref env) => is_granted(rawlua.lua(), env, cap)?,
+            None => false,
+        };
+        if !granted {
+            return Err(Error::PermissionDenied {
+                capability: cap.name().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Returns the first Lua (non C) function in the call stack, skipping the running callback
+pub(crate) unsafe fn caller_function(rawlua: &RawLua) -> Result<Option<Function>> {
+    let state = rawlua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 1)?;
+
+    let mut level = 1;
+    loop {
+        let mut ar: ffi::lua_Debug = mem::zeroed();
+        #[cfg(not(feature = "luau"))]
+        {
+            if ffi::lua_getstack(state, level, &mut ar) == 0 {
+                return Ok(None);
+            }
+            ffi::lua_getinfo(state, cstr!("f"), &mut ar);
+        }
+        #[cfg(feature = "luau")]
+        if ffi::lua_getinfo(state, level, cstr!("f"), &mut ar) == 0 {
+            return Ok(None);
+        }
+        if ffi::lua_iscfunction(state, -1) == 0 {
+            return Ok(Some(Function(rawlua.pop_ref())));
+        }
+        ffi::lua_pop(state, 1);
+        level += 1;
+    }
+}
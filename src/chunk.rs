@@ -3,10 +3,14 @@ use std::collections::HashMap;
 use std::ffi::CString;
 use std::io::Result as IoResult;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::string::String as StdString;
+use std::sync::Arc;
 
+use crate::capability::Capability;
 use crate::error::{Error, Result};
 use crate::function::Function;
+use crate::imports::ImportAllowlist;
 use crate::state::{Lua, WeakLua};
 use crate::table::Table;
 use crate::value::{FromLuaMulti, IntoLuaMulti};
@@ -94,6 +98,121 @@ impl AsChunk<'static> for PathBuf {
     }
 }
 
+// Sources behind smart pointers are borrowed if passed by reference, and copied otherwise
+macro_rules! impl_shared_chunk {
+    ($($ty:ty),*) => {
+        $(
+            impl AsChunk<'static> for $ty {
+                fn source(self) -> IoResult<Cow<'static, [u8]>> {
+                    Ok(Cow::Owned(AsRef::<[u8]>::as_ref(&*self).to_vec()))
+                }
+            }
+
+            impl<'a> AsChunk<'a> for &'a $ty {
+                fn source(self) -> IoResult<Cow<'a, [u8]>> {
+                    Ok(Cow::Borrowed(AsRef::<[u8]>::as_ref(&**self)))
+                }
+            }
+        )*
+    };
+}
+
+impl_shared_chunk!(Arc<str>, Arc<[u8]>, Rc<str>, Rc<[u8]>, Box<str>, Box<[u8]>);
+
+/// A source of named chunks, such as an asset store or an archive.
+///
+/// The chunk source is retrieved lazily, when the chunk returned by [`ChunkProvider::chunk`] is
+/// passed to [`Lua::load`]. Sources can be borrowed from the provider (e.g. from memory-mapped
+/// files or a shared cache) without copying.
+///
+/// # Examples
+///
+/// ```
+/// # use std::borrow::Cow;
+/// # use std::collections::HashMap;
+/// # use std::io;
+/// # use mlua::{ChunkProvider, Lua, Result};
+/// # fn main() -> Result<()> {
+/// struct Assets(HashMap<&'static str, &'static str>);
+///
+/// impl ChunkProvider for Assets {
+///     fn source(&self, name: &str) -> io::Result<Cow<'_, [u8]>> {
+///         let source = self.0.get(name).ok_or(io::ErrorKind::NotFound)?;
+///         Ok(Cow::Borrowed(source.as_bytes()))
+///     }
+/// }
+///
+/// let lua = Lua::new();
+/// let assets = Assets(HashMap::from([("main.lua", "return 1 + 2")]));
+/// assert_eq!(lua.load(assets.chunk("main.lua")).eval::<i32>()?, 3);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lua::load`]: crate::Lua::load
+pub trait ChunkProvider {
+    /// Returns source of the chunk with the given name (can be text or binary).
+    fn source(&self, name: &str) -> IoResult<Cow<'_, [u8]>>;
+
+    /// Returns name of the chunk as seen by Lua (e.g. in error messages).
+    ///
+    /// Default: the name prefixed with `@`.
+    fn chunk_name(&self, name: &str) -> StdString {
+        format!("@{name}")
+    }
+
+    /// Returns optional mode (text or binary) of the chunk with the given name.
+    fn mode(&self, name: &str) -> Option<ChunkMode> {
+        let _name = name; // suppress warning
+        None
+    }
+
+    /// Returns optional [environment] of the chunk with the given name.
+    ///
+    /// [environment]: https://www.lua.org/manual/5.4/manual.html#2.2
+    fn environment(&self, name: &str, lua: &Lua) -> Result<Option<Table>> {
+        let _ = (name, lua); // suppress warning
+        Ok(None)
+    }
+
+    /// Returns the chunk with the given name, which can be passed to [`Lua::load`].
+    ///
+    /// [`Lua::load`]: crate::Lua::load
+    fn chunk(&self, name: impl Into<StdString>) -> ProvidedChunk<'_, Self>
+    where
+        Self: Sized,
+    {
+        ProvidedChunk {
+            provider: self,
+            name: name.into(),
+        }
+    }
+}
+
+/// A chunk returned by [`ChunkProvider::chunk`].
+pub struct ProvidedChunk<'a, P: ?Sized> {
+    provider: &'a P,
+    name: StdString,
+}
+
+impl<'a, P: ChunkProvider + ?Sized> AsChunk<'a> for ProvidedChunk<'a, P> {
+    fn name(&self) -> Option<StdString> {
+        Some(self.provider.chunk_name(&self.name))
+    }
+
+    fn environment(&self, lua: &Lua) -> Result<Option<Table>> {
+        self.provider.environment(&self.name, lua)
+    }
+
+    fn mode(&self) -> Option<ChunkMode> {
+        self.provider.mode(&self.name)
+    }
+
+    fn source(self) -> IoResult<Cow<'a, [u8]>> {
+        self.provider.source(&self.name)
+    }
+}
+
 /// Returned from [`Lua::load`] and is used to finalize loading and executing Lua main chunks.
 ///
 /// [`Lua::load`]: crate::Lua::load
@@ -104,10 +223,50 @@ pub struct Chunk<'a> {
     pub(crate) env: Result<Option<Table>>,
     pub(crate) mode: Option<ChunkMode>,
     pub(crate) source: IoResult<Cow<'a, [u8]>>,
+    pub(crate) capabilities: Option<Vec<Capability>>,
+    pub(crate) import_allowlist: Option<Vec<StdString>>,
+    // Whether `env` is the environment of the Lua function that called the running Rust callback
+    pub(crate) env_inherited: bool,
     #[cfg(feature = "luau")]
     pub(crate) compiler: Option<Compiler>,
 }
 
+/// Result of [`Chunk::try_into_function_incremental`].
+pub enum IncrementalChunk<'a> {
+    /// The chunk source is complete and was loaded into a function.
+    Complete(Function),
+    /// The chunk source is incomplete (e.g. an unfinished block), so more input is required.
+    Incomplete(PartialChunk<'a>),
+}
+
+/// A chunk with incomplete source code, see [`Chunk::try_into_function_incremental`].
+#[must_use = "`PartialChunk`s do nothing unless more input is appended"]
+pub struct PartialChunk<'a>(Box<Chunk<'a>>);
+
+impl<'a> PartialChunk<'a> {
+    /// Appends a line of source code and tries to load the chunk again.
+    ///
+    /// A newline is inserted before the appended input.
+    pub fn append(mut self, input: impl AsRef<[u8]>) -> Result<IncrementalChunk<'a>> {
+        if let Ok(source) = &mut self.0.source {
+            let source = source.to_mut();
+            source.push(b'\n');
+            source.extend_from_slice(input.as_ref());
+        }
+        self.0.try_into_function_incremental()
+    }
+
+    /// Returns the source code accumulated so far.
+    pub fn source(&self) -> &[u8] {
+        self.0.source.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the underlying chunk (e.g. to discard the incomplete input with an error).
+    pub fn into_chunk(self) -> Chunk<'a> {
+        *self.0
+    }
+}
+
 /// Represents chunk mode (text or binary).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ChunkMode {
@@ -324,6 +483,94 @@ impl<'a> Chunk<'a> {
     /// useful.
     pub fn set_environment(mut self, env: Table) -> Self {
         self.env = Ok(Some(env));
+        self.env_inherited = false;
+        self
+    }
+
+    /// Sets whether the chunk inherits the environment of the calling Lua code.
+    ///
+    /// When a chunk is loaded from inside a Rust callback called by Lua code that runs in a custom
+    /// environment (e.g. a sandbox), the chunk by default runs in the same environment and
+    /// therefore has the same [capabilities] as its caller. This prevents dynamically loaded code
+    /// from escaping the restricted environment of the code that loaded it. An environment set
+    /// using [`set_environment`] always takes precedence.
+    ///
+    /// Disabling inheritance makes the chunk run in the global environment instead. Files loaded
+    /// by `require`, `loadfile` and `dofile` are never bound to the caller environment, as loaded
+    /// modules are shared by all callers.
+    ///
+    /// Default: **true**
+    ///
+    /// [capabilities]: #method.set_capabilities
+    /// [`set_environment`]: #method.set_environment
+    pub fn set_inherit_environment(mut self, enabled: bool) -> Self {
+        if !enabled && self.env_inherited {
+            self.env = Ok(None);
+            self.env_inherited = false;
+        }
+        self
+    }
+
+    /// Grants capabilities to the loaded chunk.
+    ///
+    /// Unless an environment is set using [`set_environment`], the chunk runs in a new
+    /// environment that reads and writes the globals (or the [inherited] environment), so the
+    /// capabilities are granted only to this chunk (and functions defined in it). Otherwise the
+    /// capabilities are granted to the given environment.
+    ///
+    /// See [`Lua::create_function_with_capabilities`] for details.
+    ///
+    /// [`set_environment`]: #method.set_environment
+    /// [inherited]: #method.set_inherit_environment
+    pub fn set_capabilities(mut self, caps: impl IntoIterator<Item = impl Into<Capability>>) -> Self {
+        (self.capabilities.get_or_insert_with(Vec::new)).extend(caps.into_iter().map(Into::into));
+        self
+    }
+
+    /// Restricts global variables and modules the chunk can reference.
+    ///
+    /// Before loading, the chunk source is scanned for names of global variables (including
+    /// assignments) and modules passed to `require` as string literals. If any of them is not in
+    /// the allow-list, the chunk is rejected with [`Error::ImportNotAllowed`] without being
+    /// executed. Calling `require` with a non-literal module name is rejected unless `require`
+    /// itself is allow-listed (which allows any module). For Luau, the scan understands type
+    /// annotations and runs before compiling the chunk.
+    ///
+    /// Unless an environment is set using [`set_environment`], the chunk also runs in a new
+    /// environment which enforces the allow-list at runtime. This covers binary chunks, which
+    /// cannot be scanned. Note that allow-listing functions that give access to the global
+    /// environment (such as `load`, `getfenv` or `_G`) defeats the allow-list.
+    ///
+    /// Calling this method multiple times extends the allow-list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Error, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let allowlist = ["string", "table"];
+    ///
+    /// let chunk = lua.load("return string.upper('ok')");
+    /// assert_eq!(chunk.set_import_allowlist(allowlist).eval::<String>()?, "OK");
+    ///
+    /// let chunk = lua.load("local x = 1\nos.exit()");
+    /// match chunk.set_import_allowlist(allowlist).exec() {
+    ///     Err(Error::ImportNotAllowed { name, line }) => {
+    ///         assert_eq!(name, "os");
+    ///         assert_eq!(line, Some(2));
+    ///     }
+    ///     r => panic!("unexpected result: {r:?}"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::ImportNotAllowed`]: crate::Error::ImportNotAllowed
+    /// [`set_environment`]: #method.set_environment
+    pub fn set_import_allowlist(mut self, names: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let allowlist = self.import_allowlist.get_or_insert_with(Vec::new);
+        allowlist.extend(names.into_iter().map(|name| name.as_ref().to_string()));
         self
     }
 
@@ -432,11 +679,95 @@ impl<'a> Chunk<'a> {
         self.into_function()?.call_async(args).await
     }
 
+    /// Loads this chunk into a function, detecting incomplete source code.
+    ///
+    /// This is intended for REPLs and notebook integrations that read source code line by line.
+    /// Like [`eval`], the source is first loaded as an expression (so the function returns its
+    /// value), then as a block. If the source is incomplete, for example an unfinished `function`
+    /// block, [`IncrementalChunk::Incomplete`] is returned, which keeps the chunk options and
+    /// accumulates further input appended with [`PartialChunk::append`].
+    ///
+    /// Other errors (including syntax errors that cannot be fixed by appending more input) are
+    /// returned as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{IncrementalChunk, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let mut lines = ["function add(a, b)", "  return a + b", "end", "add(1, 2)"].into_iter();
+    ///
+    /// let mut results = Vec::new();
+    /// let mut state = lua.load(lines.next().unwrap()).try_into_function_incremental()?;
+    /// loop {
+    ///     state = match state {
+    ///         IncrementalChunk::Complete(func) => {
+    ///             results.push(func.call::<Option<i64>>(())?);
+    ///             match lines.next() {
+    ///                 Some(line) => lua.load(line).try_into_function_incremental()?,
+    ///                 None => break,
+    ///             }
+    ///         }
+    ///         IncrementalChunk::Incomplete(partial) => partial.append(lines.next().unwrap())?,
+    ///     };
+    /// }
+    /// assert_eq!(results, [None, Some(3)]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`eval`]: #method.eval
+    pub fn try_into_function_incremental(self) -> Result<IncrementalChunk<'a>> {
+        let source = match &self.source {
+            Ok(source) if self.detect_mode() == ChunkMode::Text => source,
+            _ => return self.into_function().map(IncrementalChunk::Complete),
+        };
+
+        let is_incomplete = |err: &Error| {
+            matches!(
+                err,
+                Error::SyntaxError {
+                    incomplete_input: true,
+                    ..
+                }
+            )
+        };
+
+        // An unfinished expression (e.g. `1 +`) is incomplete only when loaded as an expression
+        let expr_incomplete = match self.with_source(Self::expression_source(source)).into_function() {
+            Ok(func) => return Ok(IncrementalChunk::Complete(func)),
+            Err(err) => is_incomplete(&err),
+        };
+        match self.with_source(source.to_vec()).into_function() {
+            Ok(func) => Ok(IncrementalChunk::Complete(func)),
+            Err(err) if expr_incomplete || is_incomplete(&err) => {
+                Ok(IncrementalChunk::Incomplete(PartialChunk(Box::new(self))))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     /// Load this chunk into a regular `Function`.
     ///
     /// This simply compiles the chunk without actually executing it.
     #[cfg_attr(not(feature = "luau"), allow(unused_mut))]
     pub fn into_function(mut self) -> Result<Function> {
+        let allowlist = self.import_allowlist.as_deref().map(ImportAllowlist::new);
+        if let (Some(allowlist), Ok(source)) = (&allowlist, &self.source) {
+            if self.detect_mode() == ChunkMode::Text {
+                if let Err(err) = allowlist.check_source(source) {
+                    if let Error::SafetyError(_) = err {
+                        // The source cannot be scanned, report syntax errors from the compiler
+                        let name = Self::convert_name(self.name.clone())?;
+                        let lua = self.lua.lock();
+                        lua.load_chunk(Some(&name), None, Some(ChunkMode::Text), source)?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
         #[cfg(feature = "luau")]
         if self.compiler.is_some() {
             // We don't need to compile source if no compiler set
@@ -444,9 +775,38 @@ impl<'a> Chunk<'a> {
         }
 
         let name = Self::convert_name(self.name)?;
+        let mut env = self.env?;
+        let inherited_env = match self.env_inherited {
+            true => env.take(),
+            false => None,
+        };
+        if let (Some(allowlist), None) = (allowlist, &env) {
+            env = Some(allowlist.create_env(self.lua.lock().lua(), inherited_env.as_ref())?);
+        }
+        if let Some(caps) = self.capabilities {
+            let lua = self.lua.lock();
+            let lua = lua.lua();
+            let env = match env {
+                Some(ref env) => env,
+                None => {
+                    let base = inherited_env.clone().unwrap_or_else(|| lua.globals());
+                    let mt = lua.create_table_from([("__index", &base), ("__newindex", &base)])?;
+                    let proxy = lua.create_table()?;
+                    proxy.set_metatable(Some(mt));
+                    env.insert(proxy)
+                }
+            };
+            lua.grant_capabilities(env, caps)?;
+        }
+        match (inherited_env, &env) {
+            // The new environment wraps the inherited one, so it keeps the caller capabilities
+            (Some(base), Some(env)) => crate::capability::inherit(self.lua.lock().lua(), &base, env)?,
+            (base, None) => env = base,
+            _ => {}
+        }
         self.lua
             .lock()
-            .load_chunk(Some(&name), self.env?.as_ref(), self.mode, self.source?.as_ref())
+            .load_chunk(Some(&name), env.as_ref(), self.mode, self.source?.as_ref())
     }
 
     /// Compiles the chunk and changes mode to binary.
@@ -535,6 +895,22 @@ impl<'a> Chunk<'a> {
         self.lua.lock().load_chunk(Some(&name), env, None, &source)
     }
 
+    // Returns a copy of the chunk with a different source
+    fn with_source(&self, source: Vec<u8>) -> Chunk<'static> {
+        Chunk {
+            lua: self.lua.clone(),
+            name: self.name.clone(),
+            env: self.env.clone(),
+            mode: self.mode,
+            source: Ok(Cow::Owned(source)),
+            capabilities: self.capabilities.clone(),
+            import_allowlist: self.import_allowlist.clone(),
+            env_inherited: self.env_inherited,
+            #[cfg(feature = "luau")]
+            compiler: self.compiler.clone(),
+        }
+    }
+
     fn detect_mode(&self) -> ChunkMode {
         match (self.mode, &self.source) {
             (Some(mode), _) => mode,
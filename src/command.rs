@@ -0,0 +1,414 @@
+use std::collections::{vec_deque, VecDeque};
+use std::fmt;
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use crate::error::{Error, Result};
+use crate::types::{Integer, Number};
+use crate::userdata::{UserData, UserDataMethods};
+use crate::value::{MultiValue, Value};
+
+/// Type of a command argument in a [`CommandSchema`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CommandArgType {
+    /// A boolean value.
+    Boolean,
+    /// An integer. Floats with integral values are accepted too.
+    Integer,
+    /// A number. Integers are converted to floats.
+    Number,
+    /// A string (must be valid UTF-8).
+    String,
+}
+
+impl CommandArgType {
+    const fn name(self) -> &'static str {
+        match self {
+            CommandArgType::Boolean => "boolean",
+            CommandArgType::Integer => "integer",
+            CommandArgType::Number => "number",
+            CommandArgType::String => "string",
+        }
+    }
+}
+
+/// A decoded command argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandArg {
+    /// A boolean value.
+    Boolean(bool),
+    /// An integer value.
+    Integer(Integer),
+    /// A number value.
+    Number(Number),
+    /// A string value.
+    String(StdString),
+}
+
+impl CommandArg {
+    /// Returns the boolean value if this is a boolean argument.
+    pub fn as_boolean(&self) -> Option<bool> {
+        match *self {
+            CommandArg::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the integer value if this is an integer argument.
+    pub fn as_integer(&self) -> Option<Integer> {
+        match *self {
+            CommandArg::Integer(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Returns the number value if this is a number or an integer argument.
+    pub fn as_number(&self) -> Option<Number> {
+        match *self {
+            CommandArg::Number(n) => Some(n),
+            CommandArg::Integer(i) => Some(i as Number),
+            _ => None,
+        }
+    }
+
+    /// Returns the string value if this is a string argument.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            CommandArg::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// A set of commands accepted by a [`CommandBuffer`].
+///
+/// Each command has a name and a list of typed arguments. Commands pushed from Lua are validated
+/// against the schema, so the host can decode them without further checks.
+#[derive(Clone, Debug, Default)]
+pub struct CommandSchema {
+    commands: Vec<(Arc<str>, Vec<CommandArgType>)>,
+    ids: FxHashMap<Arc<str>, usize>,
+}
+
+impl CommandSchema {
+    /// Creates a new empty schema.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a command with the given argument types.
+    ///
+    /// Re-adding a command with the same name replaces its arguments.
+    #[must_use]
+    pub fn command(mut self, name: &str, args: impl IntoIterator<Item = CommandArgType>) -> Self {
+        let args = args.into_iter().collect();
+        match self.ids.get(name) {
+            Some(&id) => self.commands[id].1 = args,
+            None => {
+                let name: Arc<str> = Arc::from(name);
+                self.ids.insert(name.clone(), self.commands.len());
+                self.commands.push((name, args));
+            }
+        }
+        self
+    }
+
+    /// Returns the id of a command (in order of adding to the schema).
+    pub fn command_id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+}
+
+/// Behavior of a [`CommandBuffer`] when pushing a command to a full buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Raise a Lua error.
+    ///
+    /// This is the default behavior.
+    #[default]
+    Error,
+    /// Discard the new command.
+    DropNewest,
+    /// Discard the oldest command in the buffer to make room for the new one.
+    DropOldest,
+}
+
+/// A command decoded from a [`CommandBuffer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    id: usize,
+    name: Arc<str>,
+    args: Vec<CommandArg>,
+}
+
+impl Command {
+    /// Returns the command id in the schema.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Returns the command name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the command arguments.
+    ///
+    /// The arguments match the types declared in the schema.
+    pub fn args(&self) -> &[CommandArg] {
+        &self.args
+    }
+
+    /// Consumes the command, returning its arguments.
+    pub fn into_args(self) -> Vec<CommandArg> {
+        self.args
+    }
+}
+
+/// Trait for types that can be decoded from a [`Command`].
+///
+/// Used by [`CommandBuffer::drain_as`] to convert commands into host types (e.g. an enum of
+/// drawing operations).
+pub trait FromCommand: Sized {
+    /// Performs the conversion.
+    fn from_command(command: Command) -> Result<Self>;
+}
+
+impl FromCommand for Command {
+    #[inline]
+    fn from_command(command: Command) -> Result<Self> {
+        Ok(command)
+    }
+}
+
+struct BufferState {
+    commands: VecDeque<Command>,
+    dropped: u64,
+}
+
+/// An append-only buffer of commands queued by Lua scripts and drained by the host.
+///
+/// Instead of calling a Rust function for every small operation (e.g. drawing or audio commands),
+/// scripts push commands into the buffer using `buffer:push(name, ...)` and the host processes
+/// them in batches, typically once per frame. Commands are validated against a [`CommandSchema`]
+/// and stored as plain Rust values, so no Lua references are kept.
+///
+/// Available Lua methods:
+/// - `push(name, ...)` appends a command, returns `false` if it was dropped due to overflow
+/// - `len()` returns the number of queued commands
+/// - `clear()` discards all queued commands
+///
+/// Cloned instances share the same buffer, so the host keeps a clone while the buffer itself is
+/// passed to Lua as userdata.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{CommandArgType, CommandBuffer, CommandSchema, Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let schema = CommandSchema::new()
+///     .command("line", [CommandArgType::Number; 4])
+///     .command("text", [CommandArgType::String]);
+/// let buffer = CommandBuffer::new(schema);
+/// lua.globals().set("draw", buffer.clone())?;
+///
+/// lua.load(r#"
+///     draw:push("line", 0, 0, 10, 10)
+///     draw:push("text", "hello")
+/// "#).exec()?;
+///
+/// let names: Vec<_> = buffer.drain().map(|cmd| cmd.name().to_string()).collect();
+/// assert_eq!(names, ["line", "text"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CommandBuffer {
+    schema: Arc<CommandSchema>,
+    capacity: Option<usize>,
+    overflow: OverflowPolicy,
+    state: Arc<Mutex<BufferState>>,
+}
+
+impl CommandBuffer {
+    /// Creates a new unbounded command buffer with the given schema.
+    pub fn new(schema: CommandSchema) -> Self {
+        CommandBuffer {
+            schema: Arc::new(schema),
+            capacity: None,
+            overflow: OverflowPolicy::Error,
+            state: Arc::new(Mutex::new(BufferState {
+                commands: VecDeque::new(),
+                dropped: 0,
+            })),
+        }
+    }
+
+    /// Limits the number of queued commands, applying the overflow policy when the buffer is full.
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize, overflow: OverflowPolicy) -> Self {
+        self.capacity = Some(capacity);
+        self.overflow = overflow;
+        self
+    }
+
+    /// Returns the number of queued commands.
+    pub fn len(&self) -> usize {
+        self.state.lock().commands.len()
+    }
+
+    /// Returns `true` if there are no queued commands.
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().commands.is_empty()
+    }
+
+    /// Returns the total number of commands dropped due to overflow.
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().dropped
+    }
+
+    /// Discards all queued commands.
+    pub fn clear(&self) {
+        self.state.lock().commands.clear();
+    }
+
+    /// Takes all queued commands, returning an iterator over them in order of pushing.
+    ///
+    /// Commands pushed while iterating are kept for the next drain.
+    pub fn drain(&self) -> CommandDrain {
+        let commands = std::mem::take(&mut self.state.lock().commands);
+        CommandDrain(commands.into_iter())
+    }
+
+    /// Takes all queued commands, decoding them into type `T`.
+    pub fn drain_as<T: FromCommand>(&self) -> impl Iterator<Item = Result<T>> {
+        self.drain().map(T::from_command)
+    }
+
+    fn push(&self, args: MultiValue) -> Result<bool> {
+        let mut args = args.into_iter();
+        let name = match args.next() {
+            Some(Value::String(name)) => name.to_str()?.to_owned(),
+            value => {
+                let type_name = value.as_ref().map(|v| v.type_name()).unwrap_or("no value");
+                return Err(Error::runtime(format!(
+                    "bad argument #1 to 'push' (string expected, got {type_name})"
+                )));
+            }
+        };
+        let id = (self.schema.command_id(&name))
+            .ok_or_else(|| Error::runtime(format!("unknown command '{name}'")))?;
+        let (name, types) = &self.schema.commands[id];
+        if args.len() != types.len() {
+            return Err(Error::runtime(format!(
+                "command '{name}' expects {} arguments, got {}",
+                types.len(),
+                args.len()
+            )));
+        }
+        let args = (args.zip(types).enumerate())
+            .map(|(i, (value, &ty))| decode_arg(value, ty).map_err(|cause| bad_argument(name, i + 1, cause)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut state = self.state.lock();
+        if self.capacity.is_some_and(|cap| state.commands.len() >= cap) {
+            match self.overflow {
+                OverflowPolicy::Error => {
+                    return Err(Error::runtime("command buffer is full"));
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(false);
+                }
+                OverflowPolicy::DropOldest => {
+                    state.dropped += 1;
+                    if state.commands.pop_front().is_none() {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        state.commands.push_back(Command {
+            id,
+            name: name.clone(),
+            args,
+        });
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for CommandBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CommandBuffer")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .field("overflow", &self.overflow)
+            .finish()
+    }
+}
+
+impl UserData for CommandBuffer {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("push", |_, this, args: MultiValue| this.push(args));
+        methods.add_method("len", |_, this, ()| Ok(this.len()));
+        methods.add_method("clear", |_, this, ()| {
+            this.clear();
+            Ok(())
+        });
+    }
+}
+
+/// An iterator over commands taken from a [`CommandBuffer`].
+///
+/// This struct is created by the [`CommandBuffer::drain`] method.
+pub struct CommandDrain(vec_deque::IntoIter<Command>);
+
+impl Iterator for CommandDrain {
+    type Item = Command;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for CommandDrain {}
+
+fn decode_arg(value: Value, ty: CommandArgType) -> Result<CommandArg> {
+    let arg = match (ty, &value) {
+        (CommandArgType::Boolean, &Value::Boolean(b)) => Some(CommandArg::Boolean(b)),
+        (CommandArgType::Integer, &Value::Integer(i)) => Some(CommandArg::Integer(i)),
+        (CommandArgType::Integer, &Value::Number(n)) if (n as Integer) as Number == n => {
+            Some(CommandArg::Integer(n as Integer))
+        }
+        (CommandArgType::Number, &Value::Integer(i)) => Some(CommandArg::Number(i as Number)),
+        (CommandArgType::Number, &Value::Number(n)) => Some(CommandArg::Number(n)),
+        (CommandArgType::String, Value::String(s)) => Some(CommandArg::String(s.to_str()?.to_owned())),
+        _ => None,
+    };
+    arg.ok_or_else(|| Error::FromLuaConversionError {
+        from: value.type_name(),
+        to: ty.name().to_string(),
+        message: None,
+    })
+}
+
+fn bad_argument(command: &str, pos: usize, cause: Error) -> Error {
+    Error::BadArgument {
+        to: Some(format!("command '{command}'")),
+        pos,
+        name: None,
+        cause: Arc::new(cause),
+    }
+}
@@ -2,22 +2,25 @@ use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::{CStr, CString};
 use std::hash::{BuildHasher, Hash};
+use std::mem;
 use std::os::raw::c_int;
 use std::string::String as StdString;
-use std::{slice, str};
+use std::time::Duration;
+use std::{ptr, slice, str};
 
 use bstr::{BStr, BString};
 use num_traits::cast;
 
 use crate::error::{Error, Result};
 use crate::function::Function;
-use crate::state::{Lua, RawLua};
+use crate::state::{DurationFormat, IntegerOverflow, Lua, RawLua};
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
-use crate::traits::ShortTypeName as _;
-use crate::types::{LightUserData, MaybeSend, RegistryKey};
+use crate::traits::{ShortTypeName as _, ToLuaString};
+use crate::types::{Integer, LightUserData, MaybeSend, RegistryKey};
 use crate::userdata::{AnyUserData, UserData};
+use crate::util::{check_stack, StackGuard};
 use crate::value::{FromLua, IntoLua, Nil, Value};
 
 impl IntoLua for Value {
@@ -25,6 +28,11 @@ impl IntoLua for Value {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(self)
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        lua.push_value(&self)
+    }
 }
 
 impl IntoLua for &Value {
@@ -51,6 +59,12 @@ impl IntoLua for String {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::String(self))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        lua.push_ref(&self.0);
+        Ok(())
+    }
 }
 
 impl IntoLua for &String {
@@ -95,6 +109,12 @@ impl IntoLua for Table {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Table(self))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        lua.push_ref(&self.0);
+        Ok(())
+    }
 }
 
 impl IntoLua for &Table {
@@ -129,6 +149,12 @@ impl IntoLua for Function {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Function(self))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        lua.push_ref(&self.0);
+        Ok(())
+    }
 }
 
 impl IntoLua for &Function {
@@ -163,6 +189,12 @@ impl IntoLua for Thread {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::Thread(self))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        lua.push_ref(&self.0);
+        Ok(())
+    }
 }
 
 impl IntoLua for &Thread {
@@ -197,6 +229,12 @@ impl IntoLua for AnyUserData {
     fn into_lua(self, _: &Lua) -> Result<Value> {
         Ok(Value::UserData(self))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        lua.push_ref(&self.0);
+        Ok(())
+    }
 }
 
 impl IntoLua for &AnyUserData {
@@ -469,6 +507,11 @@ impl IntoLua for Cow<'_, str> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.as_bytes())?))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        push_bytes_into_stack(&*self, lua)
+    }
 }
 
 impl IntoLua for Box<str> {
@@ -537,6 +580,11 @@ impl IntoLua for Cow<'_, CStr> {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self.to_bytes())?))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        push_bytes_into_stack(BStr::new(self.to_bytes()), lua)
+    }
 }
 
 impl IntoLua for BString {
@@ -593,6 +641,77 @@ impl IntoLua for &BStr {
     fn into_lua(self, lua: &Lua) -> Result<Value> {
         Ok(Value::String(lua.create_string(self)?))
     }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        push_bytes_into_stack(self, lua)
+    }
+}
+
+/// Byte strings are converted to Lua strings (unlike slices, which are converted to tables).
+impl IntoLua for Cow<'_, [u8]> {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        Ok(Value::String(lua.create_string(&*self)?))
+    }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        push_bytes_into_stack(BStr::new(&*self), lua)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl IntoLua for bytes::Bytes {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        Ok(Value::String(lua.create_string(&self)?))
+    }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        push_bytes_into_stack(self, lua)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl FromLua for bytes::Bytes {
+    #[inline]
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        // `Vec<u8>` is converted to `Bytes` without copying
+        Ok(Vec::from(BString::from_lua(value, lua)?).into())
+    }
+
+    #[inline]
+    unsafe fn from_stack(idx: c_int, lua: &RawLua) -> Result<Self> {
+        Ok(Vec::from(BString::from_stack(idx, lua)?).into())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl IntoLua for bytes::BytesMut {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        Ok(Value::String(lua.create_string(&self)?))
+    }
+
+    #[inline]
+    unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
+        push_bytes_into_stack(self, lua)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl FromLua for bytes::BytesMut {
+    #[inline]
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        Ok(BString::from_lua(value, lua)?.as_slice().into())
+    }
+
+    #[inline]
+    unsafe fn from_stack(idx: c_int, lua: &RawLua) -> Result<Self> {
+        Ok(BString::from_stack(idx, lua)?.as_slice().into())
+    }
 }
 
 #[inline]
@@ -602,6 +721,8 @@ where
 {
     let bytes = this.as_ref();
     if lua.unlikely_memory_error() && bytes.len() < (1 << 30) {
+        #[cfg(feature = "metrics")]
+        lua.update_metrics(|metrics| metrics.record_string(bytes));
         // Fast path: push directly into the Lua stack.
         ffi::lua_pushlstring(lua.state(), bytes.as_ptr() as *const _, bytes.len());
         return Ok(());
@@ -614,23 +735,33 @@ macro_rules! lua_convert_int {
     ($x:ty) => {
         impl IntoLua for $x {
             #[inline]
-            fn into_lua(self, _: &Lua) -> Result<Value> {
-                cast(self)
-                    .map(Value::Integer)
-                    .or_else(|| cast(self).map(Value::Number))
-                    // This is impossible error because conversion to Number never fails
-                    .ok_or_else(|| Error::ToLuaConversionError {
+            fn into_lua(self, lua: &Lua) -> Result<Value> {
+                if let Some(i) = cast(self) {
+                    return Ok(Value::Integer(i));
+                }
+                match lua.lock().integer_overflow() {
+                    IntegerOverflow::AsFloat => Ok(Value::Number(self as ffi::lua_Number)),
+                    IntegerOverflow::WrapToNegative => Ok(Value::Integer(self as Integer)),
+                    IntegerOverflow::LosslessAsString => {
+                        lua.create_string(self.to_string()).map(Value::String)
+                    }
+                    IntegerOverflow::Error => Err(Error::ToLuaConversionError {
                         from: stringify!($x).to_string(),
-                        to: "number",
+                        to: "integer",
                         message: Some("out of range".to_owned()),
-                    })
+                    }),
+                }
             }
 
             #[inline]
             unsafe fn push_into_stack(self, lua: &RawLua) -> Result<()> {
                 match cast(self) {
                     Some(i) => ffi::lua_pushinteger(lua.state(), i),
-                    None => ffi::lua_pushnumber(lua.state(), self as ffi::lua_Number),
+                    None => match lua.integer_overflow() {
+                        IntegerOverflow::AsFloat => ffi::lua_pushnumber(lua.state(), self as ffi::lua_Number),
+                        IntegerOverflow::WrapToNegative => ffi::lua_pushinteger(lua.state(), self as Integer),
+                        _ => lua.push_value(&self.into_lua(lua.lua())?)?,
+                    },
                 }
                 Ok(())
             }
@@ -640,8 +771,17 @@ macro_rules! lua_convert_int {
             #[inline]
             fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
                 let ty = value.type_name();
+                if let Value::String(ref s) = value {
+                    if let Some(x) = parse_int_exact(s) {
+                        return Ok(x);
+                    }
+                }
                 (match value {
-                    Value::Integer(i) => cast(i),
+                    Value::Integer(i) => cast(i).or_else(|| {
+                        // Wrap negative integers back to the unsigned type of the same size
+                        let wrap = lua.lock().integer_overflow() == IntegerOverflow::WrapToNegative;
+                        (wrap && mem::size_of::<$x>() == mem::size_of::<Integer>()).then_some(i as $x)
+                    }),
                     Value::Number(n) => cast(n),
                     _ => {
                         if let Some(i) = lua.coerce_integer(value.clone())? {
@@ -674,11 +814,9 @@ macro_rules! lua_convert_int {
                     let mut ok = 0;
                     let i = ffi::lua_tointegerx(state, idx, &mut ok);
                     if ok != 0 {
-                        return cast(i).ok_or_else(|| Error::FromLuaConversionError {
-                            from: "integer",
-                            to: stringify!($x).to_string(),
-                            message: Some("out of range".to_owned()),
-                        });
+                        if let Some(x) = cast(i) {
+                            return Ok(x);
+                        }
                     }
                 }
                 // Fallback to default
@@ -688,6 +826,11 @@ macro_rules! lua_convert_int {
     };
 }
 
+// Parses a string with decimal integer exactly, without going through floating point conversion
+fn parse_int_exact<T: str::FromStr>(s: &String) -> Option<T> {
+    s.to_str().ok()?.trim().parse().ok()
+}
+
 lua_convert_int!(i8);
 lua_convert_int!(u8);
 lua_convert_int!(i16);
@@ -759,6 +902,103 @@ macro_rules! lua_convert_float {
 lua_convert_float!(f32);
 lua_convert_float!(f64);
 
+impl IntoLua for Duration {
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        match lua.lock().duration_format() {
+            DurationFormat::Seconds => Ok(Value::Number(self.as_secs_f64())),
+            DurationFormat::Millis => self.as_millis().into_lua(lua),
+            DurationFormat::String => format_duration(self).into_lua(lua),
+        }
+    }
+}
+
+impl FromLua for Duration {
+    fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+        let ty = value.type_name();
+        let conv_err = |message: &str| Error::FromLuaConversionError {
+            from: ty,
+            to: "Duration".to_string(),
+            message: Some(message.to_string()),
+        };
+        let number = match value {
+            Value::Integer(i) => i as f64,
+            Value::Number(n) => n,
+            Value::String(ref s) => match s.to_str().ok().and_then(|s| parse_duration(&s)) {
+                Some(duration) => return Ok(duration),
+                None => lua
+                    .coerce_number(value)?
+                    .ok_or_else(|| conv_err("invalid duration string"))?,
+            },
+            _ => return Err(conv_err("expected number or string")),
+        };
+        let secs = match lua.lock().duration_format() {
+            DurationFormat::Millis => number / 1000.0,
+            _ => number,
+        };
+        Duration::try_from_secs_f64(secs).map_err(|_| conv_err("negative or out of range duration"))
+    }
+}
+
+// Duration units (with their length in nanoseconds), from the largest one
+const DURATION_UNITS: [(&str, u128); 7] = [
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+// Formats duration as a sequence of numbers with unit suffixes, e.g. "1h30m" or "1s250ms"
+fn format_duration(duration: Duration) -> StdString {
+    let mut nanos = duration.as_nanos();
+    if nanos == 0 {
+        return "0s".to_string();
+    }
+    let mut s = StdString::new();
+    for (unit, len) in DURATION_UNITS {
+        if nanos >= len {
+            s.push_str(&format!("{}{unit}", nanos / len));
+            nanos %= len;
+        }
+    }
+    s
+}
+
+// Parses a sequence of (possibly fractional) numbers with unit suffixes, e.g. "1h 30m" or "1.5s".
+// Returns `None` if the string is not a valid duration (including a number without unit).
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let num_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let unit_len = rest[num_len..]
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len() - num_len);
+        let (number, unit) = (&rest[..num_len], &rest[num_len..num_len + unit_len]);
+        let len = match unit {
+            "min" => 60_000_000_000,
+            "µs" => 1_000,
+            _ => DURATION_UNITS.iter().find(|(u, _)| *u == unit)?.1,
+        };
+        let part = match number.parse::<u64>() {
+            Ok(n) => {
+                let nanos = (n as u128).checked_mul(len)?;
+                let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+                Duration::new(secs, (nanos % 1_000_000_000) as u32)
+            }
+            Err(_) => Duration::try_from_secs_f64(number.parse::<f64>().ok()? * len as f64 / 1e9).ok()?,
+        };
+        total = total.checked_add(part)?;
+        rest = rest[num_len + unit_len..].trim_start();
+    }
+    Some(total)
+}
+
 impl<T> IntoLua for &[T]
 where
     T: IntoLua + Clone,
@@ -979,3 +1219,81 @@ impl<T: FromLua> FromLua for Option<T> {
         }
     }
 }
+
+impl ToLuaString for Value {
+    fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+        if let Value::String(s) = self {
+            return Ok(s.clone());
+        }
+        let lua = lua.lock();
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 4)?;
+
+            lua.push_value(self)?;
+            protect_lua!(state, 1, 1, fn(state) {
+                ffi::luaL_tolstring(state, -1, ptr::null_mut());
+            })?;
+            Ok(String(lua.pop_ref()))
+        }
+    }
+}
+
+macro_rules! impl_to_lua_string_object {
+    ($($t:ident),*) => {
+        $(
+            impl ToLuaString for $t {
+                #[inline]
+                fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+                    Value::$t(self.clone()).to_lua_string(lua)
+                }
+            }
+        )*
+    };
+}
+
+impl_to_lua_string_object!(String, Table, Function, Thread);
+
+impl ToLuaString for AnyUserData {
+    #[inline]
+    fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+        Value::UserData(self.clone()).to_lua_string(lua)
+    }
+}
+
+macro_rules! impl_to_lua_string_primitive {
+    ($($t:ty),*) => {
+        $(
+            impl ToLuaString for $t {
+                #[inline]
+                fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+                    (*self).into_lua(lua)?.to_lua_string(lua)
+                }
+            }
+        )*
+    };
+}
+
+impl_to_lua_string_primitive!(bool, i8, u8, i16, u16, i32, u32, i64, u64, i128, u128, isize, usize, f32, f64);
+
+impl ToLuaString for str {
+    #[inline]
+    fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+        lua.create_string(self)
+    }
+}
+
+impl ToLuaString for StdString {
+    #[inline]
+    fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+        lua.create_string(self)
+    }
+}
+
+impl ToLuaString for Error {
+    #[inline]
+    fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+        lua.create_string(self.to_string())
+    }
+}
@@ -0,0 +1,223 @@
+use std::cell::Cell;
+use std::fmt;
+
+use crate::error::Result;
+use crate::function::Function;
+use crate::state::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::{ClockCallback, MaybeSend};
+use crate::value::{MultiValue, Value};
+
+/// Options to make Lua scripts behave deterministically, see [`Lua::set_deterministic`].
+///
+/// This is useful for replays and lockstep simulations, where the same script must produce
+/// identical results across runs and machines.
+///
+/// [`Lua::set_deterministic`]: crate::Lua::set_deterministic
+#[non_exhaustive]
+pub struct DeterministicOptions {
+    /// Seed for `math.random`.
+    ///
+    /// If set, `math.randomseed` is called with this value.
+    ///
+    /// Default: **0**
+    pub random_seed: Option<i64>,
+
+    /// Hide memory addresses of Lua values.
+    ///
+    /// If enabled, `tostring` and `print` use a sequential id (in order of appearance) instead of
+    /// the value address for tables, functions, threads and userdata without the `__tostring`
+    /// metamethod, e.g. `table: #1`. Errors with such values as error objects are reported without
+    /// addresses too.
+    ///
+    /// Default: **true**
+    pub hide_addresses: bool,
+
+    time: Option<ClockCallback<i64>>,
+    clock: Option<ClockCallback<f64>>,
+}
+
+impl fmt::Debug for DeterministicOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeterministicOptions")
+            .field("random_seed", &self.random_seed)
+            .field("hide_addresses", &self.hide_addresses)
+            .field("time", &self.time.is_some())
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
+
+impl Default for DeterministicOptions {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl DeterministicOptions {
+    /// Returns a new instance of [`DeterministicOptions`] with default parameters.
+    pub const fn new() -> Self {
+        DeterministicOptions {
+            random_seed: Some(0),
+            hide_addresses: true,
+            time: None,
+            clock: None,
+        }
+    }
+
+    /// Sets [`random_seed`] option.
+    ///
+    /// [`random_seed`]: #structfield.random_seed
+    #[must_use]
+    pub const fn random_seed(mut self, seed: Option<i64>) -> Self {
+        self.random_seed = seed;
+        self
+    }
+
+    /// Sets [`hide_addresses`] option.
+    ///
+    /// [`hide_addresses`]: #structfield.hide_addresses
+    #[must_use]
+    pub const fn hide_addresses(mut self, enabled: bool) -> Self {
+        self.hide_addresses = enabled;
+        self
+    }
+
+    /// Sets a function that returns the current time (in seconds) for `os.time` and `os.date`.
+    ///
+    /// `os.time` called with a table argument is not affected.
+    #[must_use]
+    pub fn time<F>(mut self, func: F) -> Self
+    where
+        F: Fn() -> i64 + MaybeSend + 'static,
+    {
+        self.time = Some(Box::new(func));
+        self
+    }
+
+    /// Sets a function that returns the CPU time (in seconds) for `os.clock`.
+    #[must_use]
+    pub fn clock<F>(mut self, func: F) -> Self
+    where
+        F: Fn() -> f64 + MaybeSend + 'static,
+    {
+        self.clock = Some(Box::new(func));
+        self
+    }
+}
+
+pub(crate) fn apply(lua: &Lua, options: DeterministicOptions) -> Result<()> {
+    let globals = lua.globals();
+
+    if let Some(seed) = options.random_seed {
+        if let Some(randomseed) = (globals.get::<Option<Table>>("math")?)
+            .map(|math| math.get::<Option<Function>>("randomseed"))
+            .transpose()?
+            .flatten()
+        {
+            #[cfg(any(feature = "lua54", feature = "lua53"))]
+            randomseed.call::<()>(seed)?;
+            #[cfg(not(any(feature = "lua54", feature = "lua53")))]
+            randomseed.call::<()>(seed as f64)?;
+        }
+    }
+
+    if let Some(os) = globals.get::<Option<Table>>("os")? {
+        if let Some(time) = options.time {
+            let now = lua.create_function(move |_, ()| Ok(time()))?;
+
+            let os_time = os.get::<Function>("time")?;
+            let now2 = now.clone();
+            let new_time = lua.create_function(move |_, t: Option<Table>| match t {
+                Some(t) => os_time.call::<Value>(t),
+                None => now2.call::<Value>(()),
+            })?;
+            os.set("time", new_time)?;
+
+            let os_date = os.get::<Function>("date")?;
+            let new_date = lua.create_function(move |_, (format, t): (Value, Option<Value>)| {
+                let t = match t {
+                    Some(t) => t,
+                    None => now.call::<Value>(())?,
+                };
+                os_date.call::<MultiValue>((format, t))
+            })?;
+            os.set("date", new_date)?;
+        }
+
+        if let Some(clock) = options.clock {
+            os.set("clock", lua.create_function(move |_, ()| Ok(clock()))?)?;
+        }
+    }
+
+    if options.hide_addresses {
+        hide_addresses(lua, &globals)?;
+    }
+
+    Ok(())
+}
+
+// Replaces `tostring` and `print` functions with versions that use sequential ids instead of
+// addresses
+fn hide_addresses(lua: &Lua, globals: &Table) -> Result<()> {
+    let tostring = globals.get::<Function>("tostring")?;
+
+    // Weak table to map values to their ids
+    let ids = lua.create_table()?;
+    let ids_mt = lua.create_table()?;
+    ids_mt.raw_set("__mode", "k")?;
+    ids.set_metatable(Some(ids_mt));
+    let next_id = Cell::new(0u64);
+
+    let new_tostring = lua.create_function(move |lua, value: Value| {
+        let s = tostring.call::<String>(&value)?;
+        if !has_address(&value) {
+            return Ok(s);
+        }
+        let id = match ids.raw_get::<Option<u64>>(&value)? {
+            Some(id) => id,
+            None => {
+                next_id.set(next_id.get() + 1);
+                ids.raw_set(&value, next_id.get())?;
+                next_id.get()
+            }
+        };
+        // Keep the type name (or `__name`) prefix
+        let s = s.to_string_lossy();
+        let prefix = s
+            .rsplit_once(": ")
+            .map(|(prefix, _)| prefix)
+            .unwrap_or(value.type_name());
+        lua.create_string(format!("{prefix}: #{id}"))
+    })?;
+
+    if let Some(print) = globals.get::<Option<Function>>("print")? {
+        let tostring = new_tostring.clone();
+        let new_print = lua.create_function(move |_, args: MultiValue| {
+            let args = (args.into_iter())
+                .map(|arg| tostring.call::<Value>(arg))
+                .collect::<Result<MultiValue>>()?;
+            print.call::<()>(args)
+        })?;
+        globals.set("print", new_print)?;
+    }
+    globals.set("tostring", new_tostring)?;
+
+    lua.set_hide_addresses(true);
+    Ok(())
+}
+
+// Checks if the default string representation of a value contains its address
+fn has_address(value: &Value) -> bool {
+    let has_tostring = |mt: Option<Table>| {
+        mt.map(|mt| mt.contains_key("__tostring").unwrap_or(false))
+            .unwrap_or(false)
+    };
+    match value {
+        Value::Table(t) => !has_tostring(t.metatable()),
+        Value::UserData(ud) => !has_tostring(ud.metatable().ok().map(|mt| mt.0)),
+        Value::Function(_) | Value::Thread(_) | Value::LightUserData(_) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+
+/// Text encoding of Lua strings, used by [`String::decode`] and [`Lua::create_string_encoded`].
+///
+/// Lua strings are byte strings without any encoding attached, so data read from legacy files
+/// often needs to be converted to (or from) UTF-8 explicitly. Conversions are strict: malformed
+/// input or characters that cannot be represented in the target encoding cause an error instead
+/// of being silently replaced.
+///
+/// Requires `feature = "encoding"`
+///
+/// [`String::decode`]: crate::String::decode
+/// [`Lua::create_string_encoded`]: crate::Lua::create_string_encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// UTF-8
+    Utf8,
+    /// UTF-16, little endian (without byte order mark)
+    Utf16Le,
+    /// UTF-16, big endian (without byte order mark)
+    Utf16Be,
+    /// ISO-8859-1, mapping each byte to the Unicode code point with the same value
+    Latin1,
+    /// Windows-1252 (often mislabeled as Latin-1)
+    Windows1252,
+    /// Shift-JIS
+    ShiftJis,
+    /// Any other encoding supported by [`encoding_rs`].
+    Other(&'static encoding_rs::Encoding),
+}
+
+impl Encoding {
+    /// Returns the encoding for the given label (such as `"utf-16le"`, `"latin1"` or
+    /// `"shift_jis"`), or `None` if the label is unknown.
+    ///
+    /// Labels are matched according to the [WHATWG Encoding Standard], except that `latin1` and
+    /// `iso-8859-1` refer to [`Encoding::Latin1`] rather than Windows-1252.
+    ///
+    /// [WHATWG Encoding Standard]: https://encoding.spec.whatwg.org/#names-and-labels
+    pub fn for_label(label: &str) -> Option<Self> {
+        let label = label.trim();
+        if ["latin1", "iso-8859-1", "iso8859-1", "l1"]
+            .iter()
+            .any(|l| label.eq_ignore_ascii_case(l))
+        {
+            return Some(Encoding::Latin1);
+        }
+        encoding_rs::Encoding::for_label(label.as_bytes()).map(Encoding::from)
+    }
+
+    /// Returns the canonical name of the encoding.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Latin1 => "ISO-8859-1",
+            _ => self.to_encoding_rs().map_or("UTF-8", |enc| enc.name()),
+        }
+    }
+
+    // Returns the `encoding_rs` counterpart (except for Latin-1, which has no exact one)
+    fn to_encoding_rs(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            Encoding::Utf8 => Some(encoding_rs::UTF_8),
+            Encoding::Utf16Le => Some(encoding_rs::UTF_16LE),
+            Encoding::Utf16Be => Some(encoding_rs::UTF_16BE),
+            Encoding::Latin1 => None,
+            Encoding::Windows1252 => Some(encoding_rs::WINDOWS_1252),
+            Encoding::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            Encoding::Other(enc) => Some(enc),
+        }
+    }
+
+    // Converts bytes in this encoding to a UTF-8 string
+    pub(crate) fn decode(self, bytes: &[u8]) -> Result<StdString> {
+        let decoded = match self {
+            Encoding::Latin1 => Some(bytes.iter().map(|&b| b as char).collect()),
+            _ => {
+                let enc = self.to_encoding_rs().unwrap_or(encoding_rs::UTF_8);
+                enc.decode_without_bom_handling_and_without_replacement(bytes)
+                    .map(Cow::into_owned)
+            }
+        };
+        decoded.ok_or_else(|| Error::FromLuaConversionError {
+            from: "string",
+            to: "String".to_string(),
+            message: Some(format!("invalid {} sequence", self.name())),
+        })
+    }
+
+    // Converts a UTF-8 string to bytes in this encoding
+    pub(crate) fn encode(self, s: &str) -> Result<Cow<'_, [u8]>> {
+        // `Other` can refer to one of the encodings handled below
+        let this = match self {
+            Encoding::Other(enc) => Encoding::from(enc),
+            this => this,
+        };
+        let enc = match this {
+            Encoding::Utf8 => return Ok(Cow::Borrowed(s.as_bytes())),
+            // `encoding_rs` can only decode UTF-16
+            Encoding::Utf16Le => {
+                return Ok(Cow::Owned(s.encode_utf16().flat_map(u16::to_le_bytes).collect()));
+            }
+            Encoding::Utf16Be => {
+                return Ok(Cow::Owned(s.encode_utf16().flat_map(u16::to_be_bytes).collect()));
+            }
+            Encoding::Latin1 => {
+                let bytes = s
+                    .chars()
+                    .map(|c| u8::try_from(c).map_err(|_| self.unmappable_error(c)));
+                return bytes.collect::<Result<Vec<_>>>().map(Cow::Owned);
+            }
+            Encoding::Windows1252 => encoding_rs::WINDOWS_1252,
+            Encoding::ShiftJis => encoding_rs::SHIFT_JIS,
+            Encoding::Other(enc) => enc,
+        };
+        if enc.output_encoding() != enc {
+            return Err(Error::ToLuaConversionError {
+                from: "&str".to_string(),
+                to: "string",
+                message: Some(format!("encoding to {} is not supported", enc.name())),
+            });
+        }
+
+        let (bytes, _, had_errors) = enc.encode(s);
+        if had_errors {
+            // Find the first character that cannot be encoded to report it
+            let mut buf = [0; 4];
+            let c = s.chars().find(|c| enc.encode(c.encode_utf8(&mut buf)).2);
+            return Err(self.unmappable_error(c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+        }
+        Ok(bytes)
+    }
+
+    fn unmappable_error(self, c: char) -> Error {
+        Error::ToLuaConversionError {
+            from: "&str".to_string(),
+            to: "string",
+            message: Some(format!("character {c:?} cannot be encoded in {}", self.name())),
+        }
+    }
+}
+
+impl From<&'static encoding_rs::Encoding> for Encoding {
+    fn from(enc: &'static encoding_rs::Encoding) -> Self {
+        match enc {
+            enc if enc == encoding_rs::UTF_8 => Encoding::Utf8,
+            enc if enc == encoding_rs::UTF_16LE => Encoding::Utf16Le,
+            enc if enc == encoding_rs::UTF_16BE => Encoding::Utf16Be,
+            enc if enc == encoding_rs::WINDOWS_1252 => Encoding::Windows1252,
+            enc if enc == encoding_rs::SHIFT_JIS => Encoding::ShiftJis,
+            enc => Encoding::Other(enc),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
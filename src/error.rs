@@ -174,6 +174,56 @@ pub enum Error {
     /// This error can occur only when a Rust panic resumed previously was recovered
     /// and returned again.
     PreviouslyResumedPanic,
+    /// A Rust function was called from Lua code lacking a required capability.
+    ///
+    /// See [`Lua::create_function_with_capabilities`] for details.
+    ///
+    /// [`Lua::create_function_with_capabilities`]: crate::Lua::create_function_with_capabilities
+    PermissionDenied {
+        /// Name of the missing capability.
+        capability: StdString,
+    },
+    /// A chunk references a global variable or module outside of its import allow-list.
+    ///
+    /// See [`Chunk::set_import_allowlist`] for details.
+    ///
+    /// [`Chunk::set_import_allowlist`]: crate::Chunk::set_import_allowlist
+    ImportNotAllowed {
+        /// Name of the global variable or module.
+        name: StdString,
+        /// Line in the chunk source where the name is referenced (if known).
+        line: Option<usize>,
+    },
+    /// A Lua number is outside of the range accepted by the Rust code.
+    ///
+    /// Returned by the range checked conversions such as [`Value::try_into_u8`] and [`Ranged`].
+    ///
+    /// [`Value::try_into_u8`]: crate::Value::try_into_u8
+    /// [`Ranged`]: crate::Ranged
+    OutOfRange {
+        /// The original value, formatted as a Lua number.
+        value: StdString,
+        /// Minimum accepted value (inclusive).
+        min: i128,
+        /// Maximum accepted value (inclusive).
+        max: i128,
+        /// Label describing the value (e.g. the argument or field name), if provided.
+        label: Option<StdString>,
+    },
+    /// An operation was cancelled using a [`CancellationToken`].
+    ///
+    /// [`CancellationToken`]: crate::CancellationToken
+    Cancelled,
+    /// A module was required while it was still being loaded.
+    ///
+    /// The chain contains the names of the modules being loaded, starting and ending with the
+    /// module that was required recursively.
+    #[cfg(feature = "luau")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    CyclicRequire {
+        /// Chain of required modules that form the cycle.
+        chain: Vec<StdString>,
+    },
     /// Serialization error.
     #[cfg(feature = "serialize")]
     #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
@@ -301,6 +351,25 @@ impl fmt::Display for Error {
             Error::PreviouslyResumedPanic => {
                 write!(fmt, "previously resumed panic returned again")
             }
+            Error::PermissionDenied { capability } => {
+                write!(fmt, "permission denied: missing capability `{capability}`")
+            }
+            Error::ImportNotAllowed { name, line } => {
+                write!(fmt, "import of `{name}` is not allowed")?;
+                if let Some(line) = line {
+                    write!(fmt, " (line {line})")?;
+                }
+                Ok(())
+            }
+            Error::OutOfRange { value, min, max, label } => {
+                let label = label.as_deref().unwrap_or("value");
+                write!(fmt, "{label} {value} is out of range [{min}, {max}]")
+            }
+            Error::Cancelled => write!(fmt, "operation cancelled"),
+            #[cfg(feature = "luau")]
+            Error::CyclicRequire { chain } => {
+                write!(fmt, "cyclic require detected: {}", chain.join(" -> "))
+            }
             #[cfg(feature = "serialize")]
             Error::SerializeError(err) => {
                 write!(fmt, "serialize error: {err}")
@@ -351,14 +420,17 @@ impl Error {
     }
 
     /// Attempts to downcast the external error object to a concrete type by reference.
+    ///
+    /// Errors converted from `anyhow::Error` or `eyre::Report` are downcast to the first matching
+    /// error in their chain.
     pub fn downcast_ref<T>(&self) -> Option<&T>
     where
         T: StdError + 'static,
     {
         match self {
-            Error::ExternalError(err) => err.downcast_ref(),
+            Error::ExternalError(err) => downcast_external(err),
             Error::WithContext { cause, .. } => match cause.as_ref() {
-                Error::ExternalError(err) => err.downcast_ref(),
+                Error::ExternalError(err) => downcast_external(err),
                 _ => None,
             },
             _ => None,
@@ -395,6 +467,62 @@ impl Error {
     }
 }
 
+/// Information about a failed conversion of Lua values in a Rust callback.
+///
+/// See [`Lua::on_conversion_error`].
+///
+/// [`Lua::on_conversion_error`]: crate::Lua::on_conversion_error
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ConversionErrorInfo {
+    /// Name of the called function or method (if known).
+    pub function: Option<StdString>,
+    /// Position of the argument (usually starts from 1).
+    ///
+    /// `None` if the error was not caused by an argument conversion.
+    pub arg_index: Option<usize>,
+    /// Name of the Lua type of the value.
+    pub actual: &'static str,
+    /// Name of the expected Rust type.
+    pub expected: StdString,
+    /// Additional message with details.
+    pub message: Option<StdString>,
+    /// Location (`source:line`) of the innermost Lua function in the call stack (if available).
+    pub location: Option<StdString>,
+}
+
+impl ConversionErrorInfo {
+    // Extracts information from the error returned by a callback
+    pub(crate) fn from_error(err: &Error) -> Option<Self> {
+        match err {
+            Error::BadArgument { to, pos, cause, .. } => {
+                let mut info = Self::from_error(cause)?;
+                info.function = info.function.or_else(|| to.clone());
+                info.arg_index = info.arg_index.or(Some(*pos));
+                Some(info)
+            }
+            Error::FromLuaConversionError { from, to, message } => Some(ConversionErrorInfo {
+                function: None,
+                arg_index: None,
+                actual: from,
+                expected: to.clone(),
+                message: message.clone(),
+                location: None,
+            }),
+            Error::OutOfRange { min, max, .. } => Some(ConversionErrorInfo {
+                function: None,
+                arg_index: None,
+                actual: "number",
+                expected: format!("integer in range [{min}, {max}]"),
+                message: Some(err.to_string()),
+                location: None,
+            }),
+            Error::WithContext { cause, .. } => Self::from_error(cause),
+            _ => None,
+        }
+    }
+}
+
 /// Trait for converting [`std::error::Error`] into Lua [`Error`].
 pub trait ExternalError {
     fn into_lua_err(self) -> Error;
@@ -423,11 +551,19 @@ where
 /// Provides the `context` method for [`Error`] and `Result<T, Error>`.
 pub trait ErrorContext: Sealed {
     /// Wraps the error value with additional context.
+    ///
+    /// If the error already has a context, the context message is replaced.
     fn context<C: fmt::Display>(self, context: C) -> Self;
 
     /// Wrap the error value with additional context that is evaluated lazily
     /// only once an error does occur.
     fn with_context<C: fmt::Display>(self, f: impl FnOnce(&Error) -> C) -> Self;
+
+    /// Wraps the error value with additional context, keeping the existing context messages.
+    ///
+    /// The messages are displayed from the outermost to the innermost, both in Rust and in Lua
+    /// error strings.
+    fn push_context<C: fmt::Display>(self, context: C) -> Self;
 }
 
 impl ErrorContext for Error {
@@ -452,6 +588,13 @@ impl ErrorContext for Error {
             },
         }
     }
+
+    fn push_context<C: fmt::Display>(self, context: C) -> Self {
+        Error::WithContext {
+            context: context.to_string(),
+            cause: Arc::new(self),
+        }
+    }
 }
 
 impl<T> ErrorContext for StdResult<T, Error> {
@@ -462,6 +605,10 @@ impl<T> ErrorContext for StdResult<T, Error> {
     fn with_context<C: fmt::Display>(self, f: impl FnOnce(&Error) -> C) -> Self {
         self.map_err(|err| err.with_context(f))
     }
+
+    fn push_context<C: fmt::Display>(self, context: C) -> Self {
+        self.map_err(|err| err.push_context(context))
+    }
 }
 
 impl From<AddrParseError> for Error {
@@ -496,6 +643,89 @@ impl serde::de::Error for Error {
     }
 }
 
+#[cfg(feature = "anyhow")]
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => err,
+            Err(err) => Error::ExternalError(Arc::new(Report::Anyhow(err))),
+        }
+    }
+}
+
+#[cfg(feature = "eyre")]
+impl From<eyre::Report> for Error {
+    fn from(err: eyre::Report) -> Self {
+        match err.downcast::<Error>() {
+            Ok(err) => err,
+            Err(err) => Error::ExternalError(Arc::new(Report::Eyre(err))),
+        }
+    }
+}
+
+// Keeps `anyhow`/`eyre` error reports (with their context chains and backtraces) as is.
+//
+// Display shows the whole context chain, and Debug shows the report including backtrace.
+#[cfg(any(feature = "anyhow", feature = "eyre"))]
+enum Report {
+    #[cfg(feature = "anyhow")]
+    Anyhow(anyhow::Error),
+    #[cfg(feature = "eyre")]
+    Eyre(eyre::Report),
+}
+
+#[cfg(any(feature = "anyhow", feature = "eyre"))]
+impl Report {
+    fn chain(&self) -> Box<dyn Iterator<Item = &(dyn StdError + 'static)> + '_> {
+        match self {
+            #[cfg(feature = "anyhow")]
+            Report::Anyhow(err) => Box::new(err.chain()),
+            #[cfg(feature = "eyre")]
+            Report::Eyre(err) => Box::new(err.chain()),
+        }
+    }
+}
+
+#[cfg(any(feature = "anyhow", feature = "eyre"))]
+impl fmt::Display for Report {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "anyhow")]
+            Report::Anyhow(err) => write!(fmt, "{err:#}"),
+            #[cfg(feature = "eyre")]
+            Report::Eyre(err) => write!(fmt, "{err:#}"),
+        }
+    }
+}
+
+#[cfg(any(feature = "anyhow", feature = "eyre"))]
+impl fmt::Debug for Report {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "anyhow")]
+            Report::Anyhow(err) => fmt::Debug::fmt(err, fmt),
+            #[cfg(feature = "eyre")]
+            Report::Eyre(err) => fmt::Debug::fmt(err, fmt),
+        }
+    }
+}
+
+#[cfg(any(feature = "anyhow", feature = "eyre"))]
+impl StdError for Report {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        // Display already includes the whole chain
+        None
+    }
+}
+
+fn downcast_external<T: StdError + 'static>(err: &Arc<dyn StdError + Send + Sync>) -> Option<&T> {
+    #[cfg(any(feature = "anyhow", feature = "eyre"))]
+    if let Some(report) = err.downcast_ref::<Report>() {
+        return report.chain().find_map(|err| err.downcast_ref::<T>());
+    }
+    err.downcast_ref()
+}
+
 struct Chain<'a> {
     root: &'a Error,
     current: Option<&'a (dyn StdError + 'static)>,
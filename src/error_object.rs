@@ -0,0 +1,254 @@
+use std::backtrace::Backtrace;
+use std::sync::{Arc, Weak};
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::userdata::{MetaMethod, UserData, UserDataFields, UserDataMethods};
+use crate::value::Value;
+
+/// Options for [`Lua::install_error_objects`].
+///
+/// [`Lua::install_error_objects`]: crate::Lua::install_error_objects
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct ErrorObjectOptions {
+    /// Capture Rust backtraces when Rust callbacks raise errors.
+    ///
+    /// The backtrace is captured at the point where the error is raised into Lua and is
+    /// available as [`ErrorObject::backtrace`]. Capturing backtraces is relatively expensive.
+    ///
+    /// Default: **false**
+    pub capture_backtrace: bool,
+}
+
+impl Default for ErrorObjectOptions {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl ErrorObjectOptions {
+    /// Returns a new instance of [`ErrorObjectOptions`] with default parameters.
+    pub const fn new() -> Self {
+        ErrorObjectOptions {
+            capture_backtrace: false,
+        }
+    }
+
+    /// Sets [`capture_backtrace`] option.
+    ///
+    /// [`capture_backtrace`]: #structfield.capture_backtrace
+    #[must_use]
+    pub const fn capture_backtrace(mut self, enabled: bool) -> Self {
+        self.capture_backtrace = enabled;
+        self
+    }
+}
+
+/// An error raised by Rust code, as seen by Lua error handlers.
+///
+/// When installed by [`Lua::install_error_objects`], the `pcall` and `xpcall` functions pass
+/// errors raised by Rust callbacks as `ErrorObject` userdata instead of opaque values. In Lua, the
+/// object has the following fields:
+///
+/// - `kind`: name of the [`Error`] variant in snake case (e.g. `"runtime_error"`).
+/// - `message`: error message (without the Lua traceback).
+/// - `chain`: array of messages of the error and its causes.
+/// - `cause`: error object of the cause (for errors wrapping other errors), or `nil`.
+/// - `traceback`: Lua traceback captured when the error was raised, or `nil`.
+/// - `backtrace`: Rust backtrace (if captured), or `nil`.
+///
+/// Converting the object to a string returns the full error description.
+///
+/// [`Lua::install_error_objects`]: crate::Lua::install_error_objects
+#[derive(Clone, Debug)]
+pub struct ErrorObject {
+    error: Error,
+    backtrace: Option<Arc<Backtrace>>,
+}
+
+impl ErrorObject {
+    /// Returns the underlying error.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Returns the Rust backtrace captured when the error was raised into Lua.
+    ///
+    /// Backtraces are captured only if enabled by [`ErrorObjectOptions::capture_backtrace`].
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_deref()
+    }
+
+    /// Consumes the object, returning the underlying error.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+
+    // Returns the error returned by the Rust code (skipping `CallbackError` layers)
+    fn root(&self) -> &Error {
+        let mut error = &self.error;
+        while let Error::CallbackError { cause, .. } = error {
+            error = cause;
+        }
+        error
+    }
+
+    // Returns the Lua traceback of the innermost `CallbackError`
+    fn traceback(&self) -> Option<&str> {
+        let (mut error, mut traceback) = (&self.error, None);
+        while let Error::CallbackError { cause, traceback: tb } = error {
+            error = cause;
+            traceback = Some(tb.as_str());
+        }
+        traceback
+    }
+
+    fn cause(&self) -> Option<ErrorObject> {
+        match self.root() {
+            Error::BadArgument { cause, .. } | Error::WithContext { cause, .. } => Some(ErrorObject {
+                error: (**cause).clone(),
+                backtrace: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl UserData for ErrorObject {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("kind", |_, this| Ok(kind(this.root())));
+        fields.add_field_method_get("message", |_, this| Ok(this.root().to_string()));
+        fields.add_field_method_get("chain", |_, this| {
+            let chain = this.root().chain().map(|err| err.to_string());
+            Ok(chain.collect::<Vec<_>>())
+        });
+        fields.add_field_method_get("cause", |_, this| Ok(this.cause()));
+        fields.add_field_method_get("traceback", |_, this| Ok(this.traceback().map(str::to_owned)));
+        fields.add_field_method_get("backtrace", |_, this| {
+            Ok(this.backtrace().map(|bt| bt.to_string()))
+        });
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.error.to_string()));
+    }
+}
+
+// Returns name of the error variant in snake case
+fn kind(error: &Error) -> &'static str {
+    match error {
+        Error::SyntaxError { .. } => "syntax_error",
+        Error::RuntimeError(_) => "runtime_error",
+        Error::MemoryError(_) => "memory_error",
+        #[cfg(any(feature = "lua53", feature = "lua52", doc))]
+        Error::GarbageCollectorError(_) => "garbage_collector_error",
+        Error::SafetyError(_) => "safety_error",
+        Error::MemoryLimitNotAvailable => "memory_limit_not_available",
+        Error::RecursiveMutCallback => "recursive_mut_callback",
+        Error::CallbackDestructed => "callback_destructed",
+        Error::StackError => "stack_error",
+        Error::BindError => "bind_error",
+        Error::BadArgument { .. } => "bad_argument",
+        Error::ToLuaConversionError { .. } => "to_lua_conversion_error",
+        Error::FromLuaConversionError { .. } => "from_lua_conversion_error",
+        Error::CoroutineUnresumable => "coroutine_unresumable",
+        Error::UserDataTypeMismatch => "userdata_type_mismatch",
+        Error::UserDataDestructed => "userdata_destructed",
+        Error::UserDataBorrowError => "userdata_borrow_error",
+        Error::UserDataBorrowMutError => "userdata_borrow_mut_error",
+        Error::MetaMethodRestricted(_) => "metamethod_restricted",
+        Error::MetaMethodTypeError { .. } => "metamethod_type_error",
+        Error::MismatchedRegistryKey => "mismatched_registry_key",
+        Error::CallbackError { .. } => "callback_error",
+        Error::PreviouslyResumedPanic => "previously_resumed_panic",
+        Error::PermissionDenied { .. } => "permission_denied",
+        Error::ImportNotAllowed { .. } => "import_not_allowed",
+        Error::OutOfRange { .. } => "out_of_range",
+        Error::Cancelled => "cancelled",
+        #[cfg(feature = "luau")]
+        Error::CyclicRequire { .. } => "cyclic_require",
+        #[cfg(feature = "serialize")]
+        Error::SerializeError(_) => "serialize_error",
+        #[cfg(feature = "serialize")]
+        Error::DeserializeError(_) => "deserialize_error",
+        Error::ExternalError(_) => "external_error",
+        Error::WithContext { .. } => "with_context",
+    }
+}
+
+// Registry flag set when `pcall` and `xpcall` are wrapped to convert errors
+const INSTALLED_KEY: &str = "__mlua_error_objects_installed";
+
+// Replaces `pcall` and `xpcall` functions with versions that pass Rust errors as `ErrorObject`s.
+//
+// The wrappers are Lua functions, so (unlike Rust functions) they can yield across.
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    if lua.named_registry_value::<bool>(INSTALLED_KEY)? {
+        return Ok(());
+    }
+
+    let globals = lua.globals();
+    let convert = lua.create_function(|lua, value: Value| match value {
+        Value::Error(error) => {
+            let backtrace = lua.lock().error_backtrace(&error);
+            let object = ErrorObject {
+                error: *error,
+                backtrace,
+            };
+            lua.create_userdata(object).map(Value::UserData)
+        }
+        value => Ok(value),
+    })?;
+    let (pcall, xpcall) = lua
+        .load(
+            r#"
+            local pcall, xpcall, convert = ...
+            local function finish(ok, ...)
+                if ok then
+                    return true, ...
+                end
+                return false, convert((...))
+            end
+            return function(f, ...)
+                return finish(pcall(f, ...))
+            end, function(f, msgh, ...)
+                return finish(xpcall(f, function(err)
+                    return msgh(convert(err))
+                end, ...))
+            end
+            "#,
+        )
+        .set_name("=__mlua_error_objects")
+        .call::<(Function, Function)>((
+            globals.get::<Value>("pcall")?,
+            globals.get::<Value>("xpcall")?,
+            convert,
+        ))?;
+    globals.set("pcall", pcall)?;
+    globals.set("xpcall", xpcall)?;
+    lua.set_named_registry_value(INSTALLED_KEY, true)
+}
+
+// Rust backtraces of errors raised into Lua, identified by the `CallbackError` cause pointer
+pub(crate) type ErrorBacktraces = Vec<(Weak<Error>, Arc<Backtrace>)>;
+
+// Captures a Rust backtrace for the error being raised into Lua
+pub(crate) fn capture_backtrace(backtraces: &mut ErrorBacktraces, cause: &Arc<Error>) {
+    backtraces.retain(|(error, _)| error.strong_count() > 0);
+    backtraces.push((Arc::downgrade(cause), Arc::new(Backtrace::force_capture())));
+}
+
+// Finds the Rust backtrace of the innermost `CallbackError` layer
+pub(crate) fn find_backtrace(backtraces: &ErrorBacktraces, mut error: &Error) -> Option<Arc<Backtrace>> {
+    let mut found = None;
+    while let Error::CallbackError { cause, .. } = error {
+        let ptr = Arc::as_ptr(cause);
+        if let Some((_, backtrace)) = backtraces.iter().find(|(err, _)| err.as_ptr() == ptr) {
+            found = Some(backtrace.clone());
+        }
+        error = cause;
+    }
+    found
+}
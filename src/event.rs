@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::string::String as StdString;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::types::MaybeSend;
+use crate::userdata::{UserData, UserDataMethods};
+use crate::value::{FromLuaMulti, IntoLuaMulti, MultiValue, Value};
+
+#[cfg(feature = "async")]
+use std::future::Future;
+
+/// Identifier of an event handler subscription, returned by [`EventBus::on`] and similar methods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// An event emitter shared between Lua and Rust.
+///
+/// Handlers can be subscribed from Rust (using typed closures) and from Lua, and events can be
+/// emitted from both sides. Handlers are called in the order of subscription. An error in one
+/// handler does not prevent other handlers from running: errors are collected and returned to
+/// the emitter.
+///
+/// The bus is a userdata, so it can be passed to Lua, where it has the following methods:
+///
+/// - `bus:on(event, handler)` subscribes a function and returns the subscription id
+/// - `bus:once(event, handler)` subscribes a function to be called only once
+/// - `bus:off(id)` unsubscribes a handler, returns `true` if the handler was found
+/// - `bus:emit(event, ...)` calls the handlers, returns `true` or `false` and a table of errors
+/// - `bus:emit_async(event, ...)` same as `emit`, but handlers can yield to async Rust code
+///   (requires `feature = "async"`)
+///
+/// Cloned instances share the same handlers. A bus must be used with a single Lua state.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{EventBus, Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let bus = EventBus::new();
+/// bus.on(&lua, "damage", |_, (target, amount): (String, u32)| {
+///     println!("{target} took {amount} damage");
+///     Ok(())
+/// })?;
+/// lua.globals().set("bus", bus.clone())?;
+/// lua.load(r#"
+///     bus:on("heal", function(target, amount) print(target .. " healed by " .. amount) end)
+///     bus:emit("damage", "orc", 10)
+/// "#).exec()?;
+/// let errors = bus.emit(&lua, "heal", ("orc", 5))?;
+/// assert!(errors.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct EventBus(Arc<Mutex<BusState>>);
+
+#[derive(Default)]
+struct BusState {
+    next_id: u64,
+    handlers: HashMap<StdString, Vec<Subscription>>,
+}
+
+struct Subscription {
+    id: u64,
+    func: Function,
+    once: bool,
+}
+
+impl EventBus {
+    /// Creates a new event bus without handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes a Rust handler to the `event`.
+    ///
+    /// Event arguments are converted to the handler arguments type `A`.
+    pub fn on<A, F>(&self, lua: &Lua, event: &str, handler: F) -> Result<SubscriptionId>
+    where
+        A: FromLuaMulti,
+        F: Fn(&Lua, A) -> Result<()> + MaybeSend + 'static,
+    {
+        Ok(self.subscribe(event, lua.create_function(handler)?, false))
+    }
+
+    /// Subscribes an async Rust handler to the `event`.
+    ///
+    /// Async handlers can be called only by [`EventBus::emit_async`] (or `bus:emit_async()` in
+    /// Lua).
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn on_async<A, F, FR>(&self, lua: &Lua, event: &str, handler: F) -> Result<SubscriptionId>
+    where
+        A: FromLuaMulti,
+        F: Fn(Lua, A) -> FR + MaybeSend + 'static,
+        FR: Future<Output = Result<()>> + MaybeSend + 'static,
+    {
+        Ok(self.subscribe(event, lua.create_async_function(handler)?, false))
+    }
+
+    /// Subscribes a function to the `event`.
+    pub fn on_function(&self, event: &str, func: Function) -> SubscriptionId {
+        self.subscribe(event, func, false)
+    }
+
+    /// Unsubscribes a handler.
+    ///
+    /// Returns `true` if the handler was subscribed.
+    pub fn off(&self, id: SubscriptionId) -> bool {
+        let mut state = self.0.lock();
+        for handlers in state.handlers.values_mut() {
+            if let Some(pos) = handlers.iter().position(|sub| sub.id == id.0) {
+                handlers.remove(pos);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the number of handlers subscribed to the `event`.
+    pub fn handler_count(&self, event: &str) -> usize {
+        self.0.lock().handlers.get(event).map(|h| h.len()).unwrap_or(0)
+    }
+
+    /// Emits the `event`, calling all subscribed handlers with the provided arguments.
+    ///
+    /// Returns errors raised by the handlers (an empty vector if all handlers succeeded).
+    pub fn emit(&self, lua: &Lua, event: &str, args: impl IntoLuaMulti) -> Result<Vec<Error>> {
+        let args = args.into_lua_multi(lua)?;
+        let errors = (self.take_handlers(event).into_iter())
+            .filter_map(|func| func.call::<()>(args.clone()).err())
+            .collect();
+        Ok(errors)
+    }
+
+    /// Emits the `event`, calling all subscribed handlers (including async ones) with the
+    /// provided arguments.
+    ///
+    /// Handlers are called sequentially. Returns errors raised by the handlers.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub async fn emit_async(&self, lua: &Lua, event: &str, args: impl IntoLuaMulti) -> Result<Vec<Error>> {
+        let args = args.into_lua_multi(lua)?;
+        let mut errors = Vec::new();
+        for func in self.take_handlers(event) {
+            if let Err(err) = func.call_async::<()>(args.clone()).await {
+                errors.push(err);
+            }
+        }
+        Ok(errors)
+    }
+
+    fn subscribe(&self, event: &str, func: Function, once: bool) -> SubscriptionId {
+        let mut state = self.0.lock();
+        state.next_id += 1;
+        let id = state.next_id;
+        let handlers = state.handlers.entry(event.to_string()).or_default();
+        handlers.push(Subscription { id, func, once });
+        SubscriptionId(id)
+    }
+
+    // Returns handlers to call for the event, removing one-time handlers.
+    // Handlers are called without holding the lock, so they can subscribe or unsubscribe.
+    fn take_handlers(&self, event: &str) -> Vec<Function> {
+        let mut state = self.0.lock();
+        let Some(handlers) = state.handlers.get_mut(event) else {
+            return Vec::new();
+        };
+        let funcs = handlers.iter().map(|sub| sub.func.clone()).collect();
+        handlers.retain(|sub| !sub.once);
+        funcs
+    }
+}
+
+// Converts handler errors to Lua `(true)` or `(false, errors)` results
+fn emit_result(lua: &Lua, errors: Vec<Error>) -> Result<(bool, Value)> {
+    if errors.is_empty() {
+        return Ok((true, Value::Nil));
+    }
+    let errors = lua.create_sequence_from(errors.into_iter().map(|err| Value::Error(Box::new(err))))?;
+    Ok((false, Value::Table(errors)))
+}
+
+impl UserData for EventBus {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("on", |_, this, (event, func): (StdString, Function)| {
+            Ok(this.subscribe(&event, func, false).0)
+        });
+        methods.add_method("once", |_, this, (event, func): (StdString, Function)| {
+            Ok(this.subscribe(&event, func, true).0)
+        });
+        methods.add_method("off", |_, this, id: u64| Ok(this.off(SubscriptionId(id))));
+        methods.add_method("emit", |lua, this, (event, args): (StdString, MultiValue)| {
+            emit_result(lua, this.emit(lua, &event, args)?)
+        });
+        #[cfg(feature = "async")]
+        methods.add_async_method(
+            "emit_async",
+            |lua, this, (event, args): (StdString, MultiValue)| {
+                let this = this.clone();
+                async move { emit_result(&lua, this.emit_async(&lua, &event, args).await?) }
+            },
+        );
+    }
+}
@@ -3,6 +3,8 @@ use std::os::raw::{c_int, c_void};
 use std::{mem, ptr, slice};
 
 use crate::error::{Error, Result};
+use crate::memoize::MemoizeOptions;
+use crate::memory::MemorySourceGuard;
 use crate::state::Lua;
 use crate::table::Table;
 use crate::traits::{LuaNativeFn, LuaNativeFnMut};
@@ -16,7 +18,10 @@ use crate::value::{FromLuaMulti, IntoLua, IntoLuaMulti, Value};
 use {
     crate::traits::LuaNativeAsyncFn,
     crate::types::AsyncCallback,
-    std::future::{self, Future},
+    crate::value::FromLua,
+    futures_util::future::{self, Either},
+    std::future::Future,
+    std::time::Instant,
 };
 
 /// Handle to an internal Lua function.
@@ -60,6 +65,30 @@ pub struct CoverageInfo {
     pub hits: Vec<i32>,
 }
 
+/// Handling of upvalues when a function is cloned into another Lua state.
+///
+/// Used by [`Function::deep_clone_to`]. The `_ENV` upvalue (Lua 5.2+) is always bound to the
+/// globals table of the target state.
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpvaluePolicy {
+    /// Fail if the function has any upvalues.
+    Error,
+    /// Copy upvalue values to the target state using serde.
+    ///
+    /// Only serializable values (nil, booleans, numbers, strings and tables of them) are supported.
+    /// Copied upvalues are no longer shared with other functions.
+    ///
+    /// Requires `feature = "serialize"`
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    Copy,
+    /// Bind each upvalue to the global variable with the same name in the target state.
+    Globals,
+}
+
 impl Function {
     /// Calls the function, passing `args` as function arguments.
     ///
@@ -102,14 +131,58 @@ impl Function {
     /// # }
     /// ```
     pub fn call<R: FromLuaMulti>(&self, args: impl IntoLuaMulti) -> Result<R> {
+        self.pcall(args, None)
+    }
+
+    /// Calls the function like [`Function::call`], using `handler` as the message handler.
+    ///
+    /// This has `xpcall` semantics: if the function raises an error, `handler` is called with the
+    /// original error object (before the stack is unwound) and the value it returns becomes the
+    /// error. Rust errors are passed as [`Error`] values, so a Rust handler can enrich them (and
+    /// Lua handlers can use `tostring` on them). Other returned values are converted to strings.
+    ///
+    /// The handler replaces the default one, so the Lua traceback is not appended to Lua errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Error, ErrorContext, Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let handler = lua.create_function(|_, err: Error| Ok(err.context("in plugin")))?;
+    /// let plugin: Function = lua.load("function() error('boom', 0) end").eval()?;
+    ///
+    /// let err = plugin.call_with_handler::<()>((), handler).unwrap_err();
+    /// assert_eq!(err.to_string(), "in plugin\nruntime error: boom");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_with_handler<R: FromLuaMulti>(
+        &self,
+        args: impl IntoLuaMulti,
+        handler: Function,
+    ) -> Result<R> {
+        self.pcall(args, Some(&handler))
+    }
+
+    // Calls the function in protected mode with the message handler (or the default one)
+    fn pcall<R: FromLuaMulti>(&self, args: impl IntoLuaMulti, handler: Option<&Function>) -> Result<R> {
         let lua = self.0.lua.lock();
         let state = lua.state();
         unsafe {
             let _sg = StackGuard::new(state);
             check_stack(state, 2)?;
+            // Attribute memory allocated during the call to the function source (if enabled)
+            let _source_guard = MemorySourceGuard::new(state, || {
+                let info = self.info();
+                info.source.filter(|_| info.what != "C")
+            });
 
             // Push error handler
-            lua.push_error_traceback();
+            match handler {
+                Some(handler) => lua.push_ref(&handler.0),
+                None => lua.push_error_traceback(),
+            }
             let stack_start = ffi::lua_gettop(state);
             // Push function and the arguments
             lua.push_ref(&self.0);
@@ -170,6 +243,106 @@ impl Function {
         async move { thread_res?.await }
     }
 
+    /// Returns a future that, when polled, calls `self` like [`Function::call_async`], using
+    /// `handler` as the message handler.
+    ///
+    /// See [`Function::call_with_handler`] for the handler semantics. The function is called using
+    /// `xpcall` inside the coroutine, so on Lua 5.1 it cannot yield (other versions support
+    /// yielding across `xpcall`).
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn call_async_with_handler<R>(
+        &self,
+        args: impl IntoLuaMulti,
+        handler: Function,
+    ) -> impl Future<Output = Result<R>>
+    where
+        R: FromLuaMulti,
+    {
+        let call = self.xpcall_wrapper(handler).map(|func| func.call_async(args));
+        async move { call?.await }
+    }
+
+    // Returns a Lua function calling `self` using `xpcall` with the message handler.
+    //
+    // Unlike `lua_pcall` with a message handler, it can be called from a coroutine that yields.
+    #[cfg(feature = "async")]
+    fn xpcall_wrapper(&self, handler: Function) -> Result<Function> {
+        // Registry key of the cached function creating the wrappers
+        const WRAPPER_KEY: &str = "__mlua_xpcall_wrapper";
+
+        let lua = self.0.lua.lock();
+        let lua = lua.lua();
+        let make_wrapper = match lua.named_registry_value::<Option<Function>>(WRAPPER_KEY)? {
+            Some(make_wrapper) => make_wrapper,
+            None => {
+                // Errors are rethrown as Rust errors, so the coroutine traceback is not attached
+                let to_error = lua.create_function(|lua, value: Value| Error::from_lua(value, lua))?;
+                let make_wrapper = lua
+                    .load(
+                        r##"
+                        local to_error = ...
+                        local select, xpcall, error = select, xpcall, error
+                        local unpack = unpack or table.unpack
+                        local function finish(ok, ...)
+                            if ok then
+                                return ...
+                            end
+                            error(to_error((...)), 0)
+                        end
+                        return function(f, handler)
+                            return function(...)
+                                local n, args = select("#", ...), {...}
+                                return finish(xpcall(function()
+                                    return f(unpack(args, 1, n))
+                                end, handler))
+                            end
+                        end
+                        "##,
+                    )
+                    .set_name("=__mlua_xpcall_wrapper")
+                    .set_inherit_environment(false)
+                    .call::<Function>(to_error)?;
+                lua.set_named_registry_value(WRAPPER_KEY, &make_wrapper)?;
+                make_wrapper
+            }
+        };
+        make_wrapper.call((self, handler))
+    }
+
+    /// Returns a future that, when polled, calls `self` like [`Function::call_async`] but fails if
+    /// the call does not complete before `deadline`.
+    ///
+    /// The deadline is tracked by the time driver set using [`Lua::set_time_driver`]. If the
+    /// deadline is reached, the call is abandoned and a runtime error is returned.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`Lua::set_time_driver`]: crate::Lua::set_time_driver
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn call_async_with_deadline<R>(
+        &self,
+        deadline: Instant,
+        args: impl IntoLuaMulti,
+    ) -> impl Future<Output = Result<R>>
+    where
+        R: FromLuaMulti,
+    {
+        let sleep = (self.0.lua.lock().time_driver()).map(|driver| driver.sleep_until(deadline));
+        let call = self.call_async(args);
+        async move {
+            let sleep = sleep?;
+            futures_util::pin_mut!(call);
+            match future::select(call, sleep).await {
+                Either::Left((res, _)) => res,
+                Either::Right(_) => Err(Error::runtime("deadline exceeded")),
+            }
+        }
+    }
+
     /// Returns a function that, when called, calls `self`, passing `args` as the first set of
     /// arguments.
     ///
@@ -256,6 +429,38 @@ impl Function {
         .call((self, args_wrapper))
     }
 
+    /// Returns a function that caches results of this function.
+    ///
+    /// The returned function can be called from Lua and Rust. Calls with structurally equal
+    /// arguments (see [`Value::hash`]) return the cached results instead of calling this function
+    /// again. The cache is bounded and evicts the least recently used results first.
+    ///
+    /// Only pure functions whose results depend on the arguments alone should be memoized.
+    /// Arguments must be hashable, otherwise the call returns an error. Errors are not cached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Function, Lua, MemoizeOptions, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// lua.load("calls = 0").exec()?;
+    /// let sum: Function = lua.load("function(t) calls = calls + 1; return t[1] + t[2] end").eval()?;
+    /// let sum = sum.memoized(MemoizeOptions::new().capacity(100))?;
+    ///
+    /// lua.globals().set("sum", sum)?;
+    /// lua.load("assert(sum({1, 2}) == 3 and sum({1, 2}) == 3)").exec()?;
+    /// assert_eq!(lua.globals().get::<u32>("calls")?, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Value::hash`]: crate::Value::hash
+    pub fn memoized(&self, options: MemoizeOptions) -> Result<Function> {
+        let lua = self.0.lua.lock();
+        crate::memoize::memoize(lua.lua(), self.clone(), options)
+    }
+
     /// Returns the environment of the Lua function.
     ///
     /// By default Lua functions shares a global environment.
@@ -423,6 +628,86 @@ impl Function {
         data
     }
 
+    /// Creates a copy of the Lua function in another Lua state.
+    ///
+    /// The function is dumped to bytecode and loaded in the target state, then its upvalues are
+    /// set according to the given [`UpvaluePolicy`]. This allows moving small callbacks between
+    /// independent Lua states (e.g. workers of a pool).
+    ///
+    /// Returns an error for Rust/C functions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Function, Lua, Result, UpvaluePolicy};
+    /// # fn main() -> Result<()> {
+    /// let lua1 = Lua::new();
+    /// let lua2 = Lua::new();
+    /// lua2.globals().set("factor", 10)?;
+    ///
+    /// let func: Function = lua1.load("local factor = 2; return function(x) return x * factor end").eval()?;
+    /// let func2 = func.deep_clone_to(&lua2, UpvaluePolicy::Globals)?;
+    /// assert_eq!(func2.call::<i64>(3)?, 30);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn deep_clone_to(&self, lua: &Lua, policy: UpvaluePolicy) -> Result<Function> {
+        let mut upvalues = Vec::new();
+        {
+            let src = self.0.lua.lock();
+            let state = src.state();
+            unsafe {
+                let _sg = StackGuard::new(state);
+                check_stack(state, 2)?;
+
+                src.push_ref(&self.0);
+                if ffi::lua_iscfunction(state, -1) != 0 {
+                    return Err(Error::runtime(
+                        "cannot clone a Rust/C function into another Lua state",
+                    ));
+                }
+                for i in 1.. {
+                    let name = ffi::lua_getupvalue(state, -1, i);
+                    if name.is_null() {
+                        break;
+                    }
+                    let name = ptr_to_lossy_str(name).unwrap_or_default().into_owned();
+                    upvalues.push((name, src.pop_value()));
+                }
+            }
+        }
+
+        let func = lua.load(self.dump(false)).into_function()?;
+        let globals = lua.globals();
+        let dst = lua.lock();
+        let state = dst.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            dst.push_ref(&func.0);
+            #[cfg_attr(not(feature = "serialize"), allow(unused_variables))]
+            for (i, (name, value)) in (1..).zip(upvalues) {
+                let value = match policy {
+                    _ if name == "_ENV" => Value::Table(globals.clone()),
+                    UpvaluePolicy::Error => {
+                        return Err(Error::runtime(format!(
+                            "cannot clone function with upvalue '{name}' into another Lua state"
+                        )))
+                    }
+                    #[cfg(feature = "serialize")]
+                    UpvaluePolicy::Copy => crate::serde::LuaSerdeExt::to_value(lua, &value)?,
+                    UpvaluePolicy::Globals => globals.get(&*name)?,
+                };
+                dst.push_value(&value)?;
+                ffi::lua_setupvalue(state, -2, i);
+            }
+        }
+        Ok(func)
+    }
+
     /// Retrieves recorded coverage information about this Lua function including inner calls.
     ///
     /// This function takes a callback as an argument and calls it providing [`CoverageInfo`]
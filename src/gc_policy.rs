@@ -0,0 +1,58 @@
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::value::{MultiValue, Value};
+
+/// Decision made by a `collectgarbage` policy, see [`Lua::set_collectgarbage_policy`].
+///
+/// [`Lua::set_collectgarbage_policy`]: crate::Lua::set_collectgarbage_policy
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum GCAction {
+    /// Calls the original `collectgarbage` function with the same arguments.
+    Allow,
+    /// Raises an error in the calling script.
+    Deny,
+    /// Returns the given values to the script without calling the original function.
+    ///
+    /// Returning an empty [`MultiValue`] silently ignores the call.
+    Return(MultiValue),
+}
+
+// Registry flag set when the `collectgarbage` function is wrapped to use the policy
+const INSTALLED_KEY: &str = "__mlua_collectgarbage_policy_installed";
+
+// Replaces the `collectgarbage` function with a version that consults the policy set for the Lua
+// state
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    if lua.named_registry_value::<bool>(INSTALLED_KEY)? {
+        return Ok(());
+    }
+    let globals = lua.globals();
+    let Some(collectgarbage) = globals.get::<Option<Function>>("collectgarbage")? else {
+        return Ok(());
+    };
+
+    let new_collectgarbage = lua.create_function(move |lua, args: MultiValue| {
+        let Some(policy) = lua.lock().collectgarbage_policy() else {
+            return collectgarbage.call::<MultiValue>(args);
+        };
+        let option = match args.front() {
+            None | Some(Value::Nil) => "collect".to_string(),
+            Some(Value::String(s)) => s.to_string_lossy(),
+            // Let the original function report the invalid argument
+            Some(_) => return collectgarbage.call::<MultiValue>(args),
+        };
+        let rest = args.iter().skip(1).cloned().collect::<MultiValue>();
+        match policy(lua, &option, &rest)? {
+            GCAction::Allow => collectgarbage.call::<MultiValue>(args),
+            GCAction::Deny => Err(Error::runtime(format!(
+                "collectgarbage option '{option}' is not allowed"
+            ))),
+            GCAction::Return(values) => Ok(values),
+        }
+    })?;
+
+    globals.set("collectgarbage", new_collectgarbage)?;
+    lua.set_named_registry_value(INSTALLED_KEY, true)
+}
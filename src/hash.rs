@@ -0,0 +1,288 @@
+use std::hash::Hasher;
+use std::os::raw::c_void;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::table::Table;
+use crate::value::Value;
+
+/// Hash algorithm used by [`Value::hash`].
+///
+/// All algorithms produce the same result regardless of the platform, Lua hash seed or table
+/// iteration order.
+///
+/// [`Value::hash`]: crate::Value::hash
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    /// 64-bit [FNV-1a](https://en.wikipedia.org/wiki/Fowler–Noll–Vo_hash_function) hash.
+    #[default]
+    Fnv1a64,
+}
+
+/// Options for hashing Lua values using [`Value::hash_with`].
+///
+/// [`Value::hash_with`]: crate::Value::hash_with
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct HashOptions {
+    /// If true, tables and userdata with a `__hash` metamethod are hashed by hashing the value
+    /// returned from the metamethod.
+    ///
+    /// A metamethod that returns the value itself (directly or through other values with
+    /// `__hash` metamethods) makes hashing fail with an error.
+    ///
+    /// Without this option userdata cannot be hashed and tables are always hashed structurally.
+    ///
+    /// Default: **false**
+    pub use_metamethods: bool,
+}
+
+impl Default for HashOptions {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl HashOptions {
+    /// Returns a new instance of [`HashOptions`] with default parameters.
+    pub const fn new() -> Self {
+        HashOptions {
+            use_metamethods: false,
+        }
+    }
+
+    /// Sets [`use_metamethods`] option.
+    ///
+    /// [`use_metamethods`]: #structfield.use_metamethods
+    #[must_use]
+    pub const fn use_metamethods(mut self, enabled: bool) -> Self {
+        self.use_metamethods = enabled;
+        self
+    }
+}
+
+// Type tags to distinguish values of different types with the same binary representation
+const TAG_NIL: u8 = 0;
+const TAG_BOOLEAN: u8 = 1;
+const TAG_INTEGER: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_TABLE: u8 = 5;
+const TAG_TABLE_ENTRY: u8 = 6;
+const TAG_CYCLE: u8 = 7;
+const TAG_NULL: u8 = 8;
+#[cfg(feature = "luau")]
+const TAG_VECTOR: u8 = 9;
+
+struct Fnv1a64(u64);
+
+// Integers are always written in little-endian order (and `usize` as `u64`) to make hashes
+// independent of the platform.
+impl Hasher for Fnv1a64 {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+impl HashAlgorithm {
+    fn hasher(self) -> impl Hasher {
+        match self {
+            HashAlgorithm::Fnv1a64 => Fnv1a64(0xcbf29ce484222325),
+        }
+    }
+}
+
+pub(crate) fn hash_value(value: &Value, algorithm: HashAlgorithm, options: HashOptions) -> Result<u64> {
+    let mut ctx = HashContext {
+        algorithm,
+        options,
+        visiting: Vec::new(),
+        metamethods: Vec::new(),
+    };
+    ctx.hash(value)
+}
+
+// Hashes a sequence of values (e.g. function arguments), taking their order and count into account
+pub(crate) fn hash_values<'a>(
+    values: impl ExactSizeIterator<Item = &'a Value>,
+    algorithm: HashAlgorithm,
+    options: HashOptions,
+) -> Result<u64> {
+    let mut ctx = HashContext {
+        algorithm,
+        options,
+        visiting: Vec::new(),
+        metamethods: Vec::new(),
+    };
+    let mut hasher = algorithm.hasher();
+    hasher.write_usize(values.len());
+    for value in values {
+        hasher.write_u64(ctx.hash(value)?);
+    }
+    Ok(hasher.finish())
+}
+
+struct HashContext {
+    algorithm: HashAlgorithm,
+    options: HashOptions,
+    // Tables that are currently being hashed (to detect cycles)
+    visiting: Vec<*const c_void>,
+    // Values whose `__hash` metamethod is currently being called
+    metamethods: Vec<*const c_void>,
+}
+
+// Maximum nesting of `__hash` metamethods (each returning a value with its own `__hash`)
+const MAX_METAMETHOD_DEPTH: usize = 64;
+
+impl HashContext {
+    fn hash(&mut self, value: &Value) -> Result<u64> {
+        let mut hasher = self.algorithm.hasher();
+        match value {
+            Value::Nil => hasher.write_u8(TAG_NIL),
+            Value::Boolean(b) => {
+                hasher.write_u8(TAG_BOOLEAN);
+                hasher.write_u8(*b as u8);
+            }
+            &Value::Integer(i) => {
+                hasher.write_u8(TAG_INTEGER);
+                #[allow(clippy::useless_conversion)]
+                hasher.write_i64(i.into());
+            }
+            &Value::Number(n) => {
+                // Numbers with integer representation are hashed as integers
+                if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+                    hasher.write_u8(TAG_INTEGER);
+                    hasher.write_i64(n as i64);
+                } else {
+                    hasher.write_u8(TAG_NUMBER);
+                    let n = if n.is_nan() { f64::NAN } else { n };
+                    hasher.write_u64(n.to_bits());
+                }
+            }
+            #[cfg(feature = "luau")]
+            Value::Vector(v) => {
+                hasher.write_u8(TAG_VECTOR);
+                for c in v.0 {
+                    hasher.write_u32(c.to_bits());
+                }
+            }
+            Value::String(s) => {
+                let bytes = s.as_bytes();
+                hasher.write_u8(TAG_STRING);
+                hasher.write_usize(bytes.len());
+                hasher.write(&bytes);
+            }
+            Value::LightUserData(ud) if ud.0.is_null() => hasher.write_u8(TAG_NULL),
+            Value::Table(t) => {
+                if let Some(hash) = self.hash_metamethod(value, t.metatable())? {
+                    return Ok(hash);
+                }
+                self.hash_table(t, &mut hasher)?;
+            }
+            Value::UserData(ud) if self.options.use_metamethods => {
+                let metatable = ud.metatable().ok().map(|mt| mt.0);
+                match self.hash_metamethod(value, metatable)? {
+                    Some(hash) => return Ok(hash),
+                    None => return Err(unsupported(value, Some("userdata has no `__hash` metamethod"))),
+                }
+            }
+            _ => return Err(unsupported(value, None)),
+        }
+        Ok(hasher.finish())
+    }
+
+    fn hash_table(&mut self, table: &Table, hasher: &mut impl Hasher) -> Result<()> {
+        let ptr = table.to_pointer();
+        if let Some(pos) = self.visiting.iter().rposition(|&p| p == ptr) {
+            // Cycle: hash the distance to the referenced table
+            hasher.write_u8(TAG_CYCLE);
+            hasher.write_usize(self.visiting.len() - pos);
+            return Ok(());
+        }
+
+        self.visiting.push(ptr);
+        // Combine the entries in an order independent way
+        let (mut count, mut sum) = (0usize, 0u64);
+        let res = table.for_each::<Value, Value>(|key, value| {
+            let mut entry_hasher = self.algorithm.hasher();
+            entry_hasher.write_u8(TAG_TABLE_ENTRY);
+            entry_hasher.write_u64(self.hash(&key)?);
+            entry_hasher.write_u64(self.hash(&value)?);
+            sum = sum.wrapping_add(entry_hasher.finish());
+            count += 1;
+            Ok(())
+        });
+        self.visiting.pop();
+        res?;
+
+        hasher.write_u8(TAG_TABLE);
+        hasher.write_usize(count);
+        hasher.write_u64(sum);
+        Ok(())
+    }
+
+    fn hash_metamethod(&mut self, value: &Value, metatable: Option<Table>) -> Result<Option<u64>> {
+        if !self.options.use_metamethods {
+            return Ok(None);
+        }
+        match metatable.map(|mt| mt.raw_get::<Option<Function>>("__hash")) {
+            Some(Ok(Some(func))) => {
+                // The returned value is hashed recursively, so `__hash` returning the value itself
+                // (directly or indirectly) would never terminate
+                let ptr = value.to_pointer();
+                if self.metamethods.contains(&ptr) {
+                    return Err(unsupported(value, Some("recursive `__hash` metamethod")));
+                }
+                if self.metamethods.len() >= MAX_METAMETHOD_DEPTH {
+                    return Err(unsupported(value, Some("too many nested `__hash` metamethods")));
+                }
+                self.metamethods.push(ptr);
+                let res = func
+                    .call::<Value>(value)
+                    .and_then(|hash_value| self.hash(&hash_value));
+                self.metamethods.pop();
+                Ok(Some(res?))
+            }
+            Some(Err(err)) => Err(err),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn unsupported(value: &Value, message: Option<&str>) -> Error {
+    Error::FromLuaConversionError {
+        from: value.type_name(),
+        to: "hash".to_string(),
+        message: Some(message.unwrap_or("cannot hash value of this type").to_string()),
+    }
+}
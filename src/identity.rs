@@ -0,0 +1,105 @@
+use std::os::raw::c_void;
+
+use crate::error::Result;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::Number;
+use crate::value::Value;
+
+// Name of the (weak) registry table mapping objects to their generations and back
+const IDENTITIES_KEY: &str = "__mlua_identities";
+
+// Key of the last assigned generation in the identities table
+const GENERATION_KEY: &str = "generation";
+
+/// A hashable token identifying a Lua object without keeping it alive.
+///
+/// Identities are created by [`Lua::value_identity`] and can be used as keys of Rust collections
+/// to track Lua objects (tables, functions, threads and userdata). Unlike the object pointer
+/// alone, an identity also contains a generation number, so an object allocated at the address of
+/// a collected one gets a different identity.
+///
+/// Use [`Lua::resolve_identity`] to get the object back or to detect that it has been collected.
+///
+/// [`Lua::value_identity`]: crate::Lua::value_identity
+/// [`Lua::resolve_identity`]: crate::Lua::resolve_identity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ValueIdentity {
+    ptr: usize,
+    generation: u64,
+}
+
+impl ValueIdentity {
+    /// Returns the pointer of the object (as returned by [`Value::to_pointer`]).
+    ///
+    /// [`Value::to_pointer`]: crate::Value::to_pointer
+    pub fn to_pointer(&self) -> *const c_void {
+        self.ptr as *const c_void
+    }
+
+    /// Returns the generation number of the identity.
+    ///
+    /// Generations are assigned in increasing order by the Lua state, starting from 1.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+// Returns the identity of the object, assigning a new generation on the first call
+pub(crate) fn value_identity(lua: &Lua, value: &Value) -> Result<Option<ValueIdentity>> {
+    if !is_object(value) {
+        return Ok(None);
+    }
+    let identities = match lua.named_registry_value::<Option<Table>>(IDENTITIES_KEY)? {
+        Some(identities) => identities,
+        None => {
+            // Entries are removed when the object (either key or value) is collected
+            let identities = lua.create_table()?;
+            let mt = lua.create_table()?;
+            mt.raw_set("__mode", "kv")?;
+            identities.set_metatable(Some(mt));
+            lua.set_named_registry_value(IDENTITIES_KEY, &identities)?;
+            identities
+        }
+    };
+
+    let generation = match identities.raw_get::<Option<Number>>(value)? {
+        Some(generation) => generation,
+        None => {
+            let generation = identities
+                .raw_get::<Option<Number>>(GENERATION_KEY)?
+                .unwrap_or(0.0)
+                + 1.0;
+            identities.raw_set(GENERATION_KEY, generation)?;
+            identities.raw_set(value, generation)?;
+            identities.raw_set(generation, value)?;
+            generation
+        }
+    };
+    Ok(Some(ValueIdentity {
+        ptr: value.to_pointer() as usize,
+        generation: generation as u64,
+    }))
+}
+
+// Returns the object with the given identity, if it has not been collected
+pub(crate) fn resolve_identity(lua: &Lua, id: &ValueIdentity) -> Result<Option<Value>> {
+    let Some(identities) = lua.named_registry_value::<Option<Table>>(IDENTITIES_KEY)? else {
+        return Ok(None);
+    };
+    let value = identities.raw_get::<Value>(id.generation as Number)?;
+    if is_object(&value) && value.to_pointer() as usize == id.ptr {
+        return Ok(Some(value));
+    }
+    Ok(None)
+}
+
+// Checks that the value is a collectable object with its own identity
+fn is_object(value: &Value) -> bool {
+    match value {
+        Value::Table(_) | Value::Function(_) | Value::Thread(_) | Value::UserData(_) => true,
+        #[cfg(feature = "luau")]
+        Value::Buffer(_) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,984 @@
+use std::collections::HashSet;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::XRc;
+use crate::value::{MultiValue, Value};
+
+// A global variable or module referenced by a chunk
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Import {
+    Global(StdString, usize),
+    Module(StdString, usize),
+    // `require` called with a non-literal module name
+    DynamicRequire(usize),
+}
+
+// Set of names a chunk is allowed to import
+pub(crate) struct ImportAllowlist(HashSet<StdString>);
+
+impl ImportAllowlist {
+    pub(crate) fn new(names: &[StdString]) -> Self {
+        ImportAllowlist(names.iter().cloned().collect())
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+
+    // Checks the chunk source statically, before it's loaded
+    pub(crate) fn check_source(&self, source: &[u8]) -> Result<()> {
+        let imports = scan(source)
+            .map_err(|err| Error::SafetyError(format!("unable to verify imports of the chunk: {err}")))?;
+        for import in imports {
+            let (name, line) = match import {
+                Import::Global(name, line) if !self.allows(&name) => (name, line),
+                Import::Module(name, line) if !self.allows(&name) && !self.allows("require") => (name, line),
+                Import::DynamicRequire(line) if !self.allows("require") => ("require".into(), line),
+                _ => continue,
+            };
+            return Err(Error::ImportNotAllowed {
+                name,
+                line: Some(line),
+            });
+        }
+        Ok(())
+    }
+
+    // Creates a chunk environment that allows only access to the allow-listed globals (or fields
+    // of the `base` environment, if provided)
+    pub(crate) fn create_env(self, lua: &Lua, base: Option<&Table>) -> Result<Table> {
+        let allowlist = XRc::new(self);
+        let (globals, require) = match base {
+            Some(base) => (base.clone(), base.get::<Value>("require")?),
+            None => {
+                let globals = lua.globals();
+                let require = globals.raw_get::<Value>("require")?;
+                (globals, require)
+            }
+        };
+
+        let require = match require {
+            Value::Function(require) if !allowlist.allows("require") => {
+                let allowlist = allowlist.clone();
+                let checked = lua.create_function(move |_, args: MultiValue| {
+                    match args.front() {
+                        Some(Value::String(name)) if allowlist.allows(&name.to_string_lossy()) => {}
+                        Some(Value::String(name)) => {
+                            let name = name.to_string_lossy();
+                            return Err(Error::ImportNotAllowed { name, line: None });
+                        }
+                        _ => {
+                            return Err(Error::ImportNotAllowed {
+                                name: "require".into(),
+                                line: None,
+                            })
+                        }
+                    }
+                    require.call::<MultiValue>(args)
+                })?;
+                Some(checked)
+            }
+            _ => None,
+        };
+
+        let (allowlist2, globals2) = (allowlist.clone(), globals.clone());
+        let index = lua.create_function(move |_, (_, key): (Table, Value)| {
+            if let Value::String(ref name) = key {
+                if allowlist.allows(&name.to_string_lossy()) {
+                    return globals.get::<Value>(key);
+                }
+                if let (Some(require), true) = (&require, name == "require") {
+                    return Ok(Value::Function(require.clone()));
+                }
+            }
+            Err(not_allowed(&key))
+        })?;
+        let newindex = lua.create_function(move |_, (_, key, value): (Table, Value, Value)| {
+            if let Value::String(ref name) = key {
+                if allowlist2.allows(&name.to_string_lossy()) {
+                    return globals2.set(key, value);
+                }
+            }
+            Err(not_allowed(&key))
+        })?;
+
+        let env = lua.create_table()?;
+        let mt = lua.create_table_from([("__index", index), ("__newindex", newindex)])?;
+        env.set_metatable(Some(mt));
+        Ok(env)
+    }
+}
+
+fn not_allowed(key: &Value) -> Error {
+    let name = match key {
+        Value::String(name) => name.to_string_lossy(),
+        key => key.to_string().unwrap_or_else(|_| key.type_name().to_string()),
+    };
+    Error::ImportNotAllowed { name, line: None }
+}
+
+//
+// Source scanner
+//
+// A minimal Lua (and Luau) parser that tracks local variable scopes to find free names, i.e.
+// global variables, referenced by a chunk. It does not validate the source: syntax errors are
+// reported by the Lua compiler.
+//
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Name(StdString),
+    Str(Option<StdString>),
+    Num,
+    Op(&'static str),
+    Eof,
+}
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local", "nil",
+    "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+// Sorted by length, longest first
+const OPS: &[&str] = &[
+    "...", "..=", "//=", "..", "==", "~=", "<=", ">=", "//", "::", "<<", ">>", "->", "+=", "-=", "*=", "/=",
+    "%=", "^=", "+", "-", "*", "/", "%", "^", "#", "&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[",
+    "]", ";", ":", ",", ".", "@", "?",
+];
+
+const LUAU: bool = cfg!(feature = "luau");
+
+fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+    // Brace depth of each (nested) interpolated string expression
+    interp: Vec<usize>,
+    toks: Vec<(Tok, usize)>,
+}
+
+impl Lexer<'_> {
+    fn peek(&self, n: usize) -> u8 {
+        self.src.get(self.pos + n).copied().unwrap_or(0)
+    }
+
+    fn bump(&mut self) -> u8 {
+        let c = self.peek(0);
+        if c == b'\n' {
+            self.line += 1;
+        }
+        self.pos += 1;
+        c
+    }
+
+    fn push(&mut self, tok: Tok) {
+        self.toks.push((tok, self.line));
+    }
+
+    // Returns level of a long bracket (`[==[`) starting at the current position
+    fn long_bracket(&self) -> Option<usize> {
+        let mut n = 1;
+        while self.peek(n) == b'=' {
+            n += 1;
+        }
+        (self.peek(0) == b'[' && self.peek(n) == b'[').then_some(n - 1)
+    }
+
+    fn skip_long_string(&mut self, level: usize) -> std::result::Result<StdString, StdString> {
+        self.pos += level + 2;
+        let start = self.pos;
+        loop {
+            if self.pos >= self.src.len() {
+                return Err("unfinished long string".into());
+            }
+            if self.peek(0) == b']'
+                && (1..=level).all(|i| self.peek(i) == b'=')
+                && self.peek(level + 1) == b']'
+            {
+                let s = StdString::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+                self.pos += level + 2;
+                return Ok(s);
+            }
+            self.bump();
+        }
+    }
+
+    // Reads a quoted string, returning its contents if it has no escape sequences
+    fn quoted_string(&mut self, quote: u8) -> std::result::Result<Option<StdString>, StdString> {
+        self.bump();
+        let start = self.pos;
+        let mut plain = true;
+        loop {
+            match self.peek(0) {
+                0 if self.pos >= self.src.len() => return Err("unfinished string".into()),
+                b'\\' => {
+                    plain = false;
+                    self.bump();
+                    self.bump();
+                }
+                c if c == quote => break,
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+        let s = StdString::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+        self.bump();
+        Ok(plain.then_some(s))
+    }
+
+    // Reads a segment of an interpolated string up to the closing backtick or `{`
+    fn interp_segment(&mut self) -> std::result::Result<(), StdString> {
+        loop {
+            match self.peek(0) {
+                0 if self.pos >= self.src.len() => return Err("unfinished string".into()),
+                b'\\' => {
+                    self.bump();
+                    self.bump();
+                }
+                b'`' => {
+                    self.bump();
+                    self.push(Tok::Str(None));
+                    self.push(Tok::Op(")"));
+                    return Ok(());
+                }
+                b'{' => {
+                    self.bump();
+                    self.push(Tok::Str(None));
+                    self.push(Tok::Op(".."));
+                    self.push(Tok::Op("("));
+                    self.interp.push(0);
+                    return Ok(());
+                }
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn run(mut self) -> std::result::Result<Vec<(Tok, usize)>, StdString> {
+        // Skip shebang line
+        if self.src.starts_with(b"#") {
+            while !matches!(self.peek(0), b'\n' | 0) {
+                self.bump();
+            }
+        }
+        while self.pos < self.src.len() {
+            let c = self.peek(0);
+            match c {
+                b'\n' | b'\r' | b' ' | b'\t' | b'\x0b' | b'\x0c' => {
+                    self.bump();
+                }
+                b'-' if self.peek(1) == b'-' => {
+                    self.pos += 2;
+                    match self.long_bracket() {
+                        Some(level) => {
+                            self.skip_long_string(level)?;
+                        }
+                        None => {
+                            while !matches!(self.peek(0), b'\n' | 0) {
+                                self.bump();
+                            }
+                        }
+                    }
+                }
+                b'[' if self.long_bracket().is_some() => {
+                    let level = self.long_bracket().unwrap();
+                    let s = self.skip_long_string(level)?;
+                    self.push(Tok::Str(Some(s)));
+                }
+                b'"' | b'\'' => {
+                    let s = self.quoted_string(c)?;
+                    self.push(Tok::Str(s));
+                }
+                b'`' if LUAU => {
+                    self.bump();
+                    self.push(Tok::Op("("));
+                    self.interp_segment()?;
+                }
+                b'{' if !self.interp.is_empty() => {
+                    *self.interp.last_mut().unwrap() += 1;
+                    self.bump();
+                    self.push(Tok::Op("{"));
+                }
+                b'}' if self.interp.last() == Some(&0) => {
+                    self.interp.pop();
+                    self.bump();
+                    self.push(Tok::Op(")"));
+                    self.push(Tok::Op(".."));
+                    self.interp_segment()?;
+                }
+                b'}' if !self.interp.is_empty() => {
+                    *self.interp.last_mut().unwrap() -= 1;
+                    self.bump();
+                    self.push(Tok::Op("}"));
+                }
+                b'0'..=b'9' => self.number(),
+                b'.' if self.peek(1).is_ascii_digit() => self.number(),
+                c if c.is_ascii_alphabetic() || c == b'_' || c >= 0x80 => {
+                    let start = self.pos;
+                    while matches!(self.peek(0), c if c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80) {
+                        self.bump();
+                    }
+                    let name = StdString::from_utf8_lossy(&self.src[start..self.pos]).into_owned();
+                    self.push(Tok::Name(name));
+                }
+                _ => {
+                    let rest = &self.src[self.pos..];
+                    let op = (OPS.iter().find(|op| rest.starts_with(op.as_bytes())))
+                        .ok_or_else(|| format!("unexpected symbol near '{}'", c as char))?;
+                    self.pos += op.len();
+                    self.push(Tok::Op(op));
+                }
+            }
+        }
+        self.push(Tok::Eof);
+        Ok(self.toks)
+    }
+
+    fn number(&mut self) {
+        let mut prev = 0;
+        while matches!(self.peek(0), c if c.is_ascii_alphanumeric() || c == b'_' || c == b'.')
+            || (matches!(self.peek(0), b'+' | b'-') && matches!(prev, b'e' | b'E' | b'p' | b'P'))
+        {
+            prev = self.bump();
+        }
+        self.push(Tok::Num);
+    }
+}
+
+struct Parser {
+    toks: Vec<(Tok, usize)>,
+    pos: usize,
+    scopes: Vec<Vec<StdString>>,
+    imports: Vec<Import>,
+}
+
+type ParseResult = std::result::Result<(), StdString>;
+
+impl Parser {
+    fn tok(&self) -> &Tok {
+        &self.toks[self.pos].0
+    }
+
+    fn tok_at(&self, n: usize) -> &Tok {
+        let i = (self.pos + n).min(self.toks.len() - 1);
+        &self.toks[i].0
+    }
+
+    fn line(&self) -> usize {
+        self.toks[self.pos].1
+    }
+
+    fn advance(&mut self) {
+        if self.pos < self.toks.len() - 1 {
+            self.pos += 1;
+        }
+    }
+
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self.tok(), Tok::Op(o) if *o == op)
+    }
+
+    fn is_kw(&self, kw: &str) -> bool {
+        matches!(self.tok(), Tok::Name(n) if n == kw)
+    }
+
+    fn check_op(&mut self, op: &str) -> bool {
+        let found = self.is_op(op);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn check_kw(&mut self, kw: &str) -> bool {
+        let found = self.is_kw(kw);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_op(&mut self, op: &str) -> ParseResult {
+        match self.check_op(op) {
+            true => Ok(()),
+            false => Err(self.unexpected(op)),
+        }
+    }
+
+    fn expect_kw(&mut self, kw: &str) -> ParseResult {
+        match self.check_kw(kw) {
+            true => Ok(()),
+            false => Err(self.unexpected(kw)),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> StdString {
+        format!(
+            "'{expected}' expected near {:?} (line {})",
+            self.tok(),
+            self.line()
+        )
+    }
+
+    fn name(&mut self) -> std::result::Result<StdString, StdString> {
+        match self.tok().clone() {
+            Tok::Name(name) if !is_keyword(&name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("<name>")),
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .any(|scope| scope.iter().any(|n| n == name))
+    }
+
+    fn declare(&mut self, name: StdString) {
+        self.scopes.last_mut().expect("scope").push(name);
+    }
+
+    fn block_end(&self) -> bool {
+        matches!(self.tok(), Tok::Eof) || ["end", "else", "elseif", "until"].iter().any(|kw| self.is_kw(kw))
+    }
+
+    // Parses a block in a new scope
+    fn block(&mut self) -> ParseResult {
+        self.scopes.push(Vec::new());
+        self.statements()?;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn statements(&mut self) -> ParseResult {
+        while !self.block_end() {
+            self.statement()?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> ParseResult {
+        let line = self.line();
+        match self.tok().clone() {
+            Tok::Op(";") => self.advance(),
+            Tok::Op("::") => {
+                self.advance();
+                self.name()?;
+                self.expect_op("::")?;
+            }
+            Tok::Op("@") if LUAU => {
+                // Function attribute
+                self.advance();
+                self.name()?;
+            }
+            Tok::Name(kw) if is_keyword(&kw) => {
+                self.advance();
+                match &*kw {
+                    "if" => {
+                        self.expr()?;
+                        self.expect_kw("then")?;
+                        self.block()?;
+                        while self.check_kw("elseif") {
+                            self.expr()?;
+                            self.expect_kw("then")?;
+                            self.block()?;
+                        }
+                        if self.check_kw("else") {
+                            self.block()?;
+                        }
+                        self.expect_kw("end")?;
+                    }
+                    "while" => {
+                        self.expr()?;
+                        self.expect_kw("do")?;
+                        self.block()?;
+                        self.expect_kw("end")?;
+                    }
+                    "do" => {
+                        self.block()?;
+                        self.expect_kw("end")?;
+                    }
+                    "for" => self.for_stat()?,
+                    "repeat" => {
+                        // The `until` condition can see locals of the loop body
+                        self.scopes.push(Vec::new());
+                        self.statements()?;
+                        self.expect_kw("until")?;
+                        self.expr()?;
+                        self.scopes.pop();
+                    }
+                    "function" => {
+                        let name = self.name()?;
+                        self.reference(name, line);
+                        let mut method = false;
+                        loop {
+                            if self.check_op(".") {
+                                self.name()?;
+                            } else if self.check_op(":") {
+                                self.name()?;
+                                method = true;
+                                break;
+                            } else {
+                                break;
+                            }
+                        }
+                        self.func_body(method)?;
+                    }
+                    "local" => self.local_stat()?,
+                    "return" => {
+                        if !self.block_end() && !self.is_op(";") {
+                            self.expr_list()?;
+                        }
+                    }
+                    "break" => {}
+                    _ => return Err(format!("unexpected '{kw}' (line {line})")),
+                }
+            }
+            Tok::Name(name) if name == "goto" && !LUAU && matches!(self.tok_at(1), Tok::Name(_)) => {
+                self.advance();
+                self.name()?;
+            }
+            Tok::Name(name) if LUAU && name == "continue" && !self.continues_expr(1) => self.advance(),
+            Tok::Name(name) if LUAU && name == "type" && matches!(self.tok_at(1), Tok::Name(_)) => {
+                self.advance();
+                self.type_stat()?;
+            }
+            Tok::Name(name)
+                if LUAU && name == "export" && matches!(self.tok_at(1), Tok::Name(n) if n == "type") =>
+            {
+                self.pos += 2;
+                self.type_stat()?;
+            }
+            _ => {
+                // Function call or assignment
+                self.suffixed_expr()?;
+                if self.is_op("=") || self.is_op(",") {
+                    while self.check_op(",") {
+                        self.suffixed_expr()?;
+                    }
+                    self.expect_op("=")?;
+                    self.expr_list()?;
+                } else if let Tok::Op(op) = *self.tok() {
+                    if LUAU && op.len() > 1 && op.ends_with('=') && !matches!(op, "==" | "~=" | "<=" | ">=") {
+                        // Compound assignment
+                        self.advance();
+                        self.expr()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Checks if the token at offset `n` continues an expression started by a name
+    fn continues_expr(&self, n: usize) -> bool {
+        match self.tok_at(n) {
+            Tok::Op(op) => matches!(*op, "(" | "." | "[" | ":" | "=" | "," | "{") || op.ends_with('='),
+            Tok::Str(_) => true,
+            _ => false,
+        }
+    }
+
+    fn for_stat(&mut self) -> ParseResult {
+        let mut names = vec![self.name()?];
+        self.skip_annotation()?;
+        if self.check_op("=") {
+            self.expr()?;
+            self.expect_op(",")?;
+            self.expr()?;
+            if self.check_op(",") {
+                self.expr()?;
+            }
+        } else {
+            while self.check_op(",") {
+                names.push(self.name()?);
+                self.skip_annotation()?;
+            }
+            self.expect_kw("in")?;
+            self.expr_list()?;
+        }
+        self.expect_kw("do")?;
+        self.scopes.push(names);
+        self.block()?;
+        self.scopes.pop();
+        self.expect_kw("end")
+    }
+
+    fn local_stat(&mut self) -> ParseResult {
+        if self.check_kw("function") {
+            let name = self.name()?;
+            self.declare(name);
+            return self.func_body(false);
+        }
+        let mut names = Vec::new();
+        loop {
+            names.push(self.name()?);
+            self.skip_annotation()?;
+            if !LUAU && self.check_op("<") {
+                // Variable attribute
+                self.name()?;
+                self.expect_op(">")?;
+            }
+            if !self.check_op(",") {
+                break;
+            }
+        }
+        if self.check_op("=") {
+            self.expr_list()?;
+        }
+        // Locals are visible only after the statement
+        for name in names {
+            self.declare(name);
+        }
+        Ok(())
+    }
+
+    fn func_body(&mut self, method: bool) -> ParseResult {
+        if LUAU && self.is_op("<") {
+            self.skip_angles()?;
+        }
+        self.expect_op("(")?;
+        let mut params = Vec::new();
+        if method {
+            params.push("self".into());
+        }
+        while !self.check_op(")") {
+            if self.check_op("...") {
+                if LUAU && self.check_op(":") {
+                    self.skip_type()?;
+                }
+            } else {
+                params.push(self.name()?);
+                self.skip_annotation()?;
+            }
+            if !self.check_op(",") {
+                self.expect_op(")")?;
+                break;
+            }
+        }
+        self.skip_annotation()?;
+        self.scopes.push(params);
+        self.statements()?;
+        self.scopes.pop();
+        self.expect_kw("end")
+    }
+
+    fn expr_list(&mut self) -> ParseResult {
+        self.expr()?;
+        while self.check_op(",") {
+            self.expr()?;
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self) -> ParseResult {
+        loop {
+            while self.check_kw("not") || self.check_op("-") || self.check_op("#") || self.check_op("~") {}
+            self.simple_expr()?;
+            if LUAU && self.check_op("::") {
+                self.skip_type()?;
+            }
+            let binop = match self.tok() {
+                Tok::Name(kw) => kw == "and" || kw == "or",
+                Tok::Op(op) => matches!(
+                    *op,
+                    "+" | "-"
+                        | "*"
+                        | "/"
+                        | "//"
+                        | "%"
+                        | "^"
+                        | ".."
+                        | "=="
+                        | "~="
+                        | "<"
+                        | "<="
+                        | ">"
+                        | ">="
+                        | "&"
+                        | "|"
+                        | "~"
+                        | "<<"
+                        | ">>"
+                ),
+                _ => false,
+            };
+            if !binop {
+                return Ok(());
+            }
+            self.advance();
+        }
+    }
+
+    fn simple_expr(&mut self) -> ParseResult {
+        match self.tok() {
+            Tok::Num | Tok::Str(_) => self.advance(),
+            Tok::Op("...") => self.advance(),
+            Tok::Op("{") => return self.table(),
+            Tok::Name(kw) if matches!(&**kw, "nil" | "true" | "false") => self.advance(),
+            Tok::Name(kw) if kw == "function" => {
+                self.advance();
+                return self.func_body(false);
+            }
+            Tok::Name(kw) if LUAU && kw == "if" => {
+                self.advance();
+                self.expr()?;
+                self.expect_kw("then")?;
+                self.expr()?;
+                while self.check_kw("elseif") {
+                    self.expr()?;
+                    self.expect_kw("then")?;
+                    self.expr()?;
+                }
+                self.expect_kw("else")?;
+                return self.expr();
+            }
+            _ => return self.suffixed_expr(),
+        }
+        Ok(())
+    }
+
+    fn suffixed_expr(&mut self) -> ParseResult {
+        let line = self.line();
+        if self.check_op("(") {
+            self.expr()?;
+            self.expect_op(")")?;
+        } else {
+            let name = self.name()?;
+            if name == "require" && !self.is_local(&name) {
+                // Literal module name: `require "mod"` or `require("mod")`
+                let module = match (self.tok_at(0), self.tok_at(1), self.tok_at(2)) {
+                    (Tok::Str(Some(m)), _, _) => Some(m.clone()),
+                    (Tok::Op("("), Tok::Str(Some(m)), Tok::Op(")")) => Some(m.clone()),
+                    _ => None,
+                };
+                match module {
+                    Some(module) => self.imports.push(Import::Module(module, line)),
+                    None => self.imports.push(Import::DynamicRequire(line)),
+                }
+            } else {
+                self.reference(name, line);
+            }
+        }
+        loop {
+            match self.tok() {
+                Tok::Op(".") => {
+                    self.advance();
+                    self.name()?;
+                }
+                Tok::Op("[") => {
+                    self.advance();
+                    self.expr()?;
+                    self.expect_op("]")?;
+                }
+                Tok::Op(":") => {
+                    self.advance();
+                    self.name()?;
+                    self.call_args()?;
+                }
+                Tok::Op("(") | Tok::Op("{") | Tok::Str(_) => self.call_args()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn call_args(&mut self) -> ParseResult {
+        match self.tok() {
+            Tok::Str(_) => {
+                self.advance();
+                Ok(())
+            }
+            Tok::Op("{") => self.table(),
+            _ => {
+                self.expect_op("(")?;
+                if !self.check_op(")") {
+                    self.expr_list()?;
+                    self.expect_op(")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn table(&mut self) -> ParseResult {
+        self.expect_op("{")?;
+        while !self.check_op("}") {
+            if self.check_op("[") {
+                self.expr()?;
+                self.expect_op("]")?;
+                self.expect_op("=")?;
+            } else if matches!(self.tok(), Tok::Name(n) if !is_keyword(n))
+                && matches!(self.tok_at(1), Tok::Op("="))
+            {
+                self.pos += 2;
+            }
+            self.expr()?;
+            if !self.check_op(",") && !self.check_op(";") {
+                self.expect_op("}")?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn reference(&mut self, name: StdString, line: usize) {
+        if !self.is_local(&name) {
+            self.imports.push(Import::Global(name, line));
+        }
+    }
+
+    //
+    // Luau type annotations (never evaluated, so names in them are not references)
+    //
+
+    fn skip_annotation(&mut self) -> ParseResult {
+        if LUAU && self.check_op(":") {
+            self.skip_type()?;
+        }
+        Ok(())
+    }
+
+    fn type_stat(&mut self) -> ParseResult {
+        if self.check_kw("function") {
+            // User-defined type function, runs only during type checking
+            self.name()?;
+            let scopes = std::mem::replace(&mut self.scopes, vec![Vec::new()]);
+            let imports = self.imports.len();
+            let res = self.func_body(false);
+            self.imports.truncate(imports);
+            self.scopes = scopes;
+            return res;
+        }
+        self.name()?;
+        if self.is_op("<") {
+            self.skip_angles()?;
+        }
+        self.expect_op("=")?;
+        self.skip_type()
+    }
+
+    fn skip_type(&mut self) -> ParseResult {
+        self.check_op("|");
+        self.check_op("&");
+        loop {
+            self.skip_simple_type()?;
+            while self.check_op("?") {}
+            if !self.check_op("|") && !self.check_op("&") {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_simple_type(&mut self) -> ParseResult {
+        match self.tok().clone() {
+            Tok::Name(name) if name == "typeof" => {
+                self.advance();
+                self.skip_balanced()
+            }
+            Tok::Name(_) => {
+                self.advance();
+                while self.check_op(".") {
+                    self.advance();
+                }
+                if self.is_op("<") {
+                    self.skip_angles()?;
+                }
+                Ok(())
+            }
+            Tok::Str(_) => {
+                self.advance();
+                Ok(())
+            }
+            Tok::Op("{") => self.skip_balanced(),
+            Tok::Op("(") => {
+                self.skip_balanced()?;
+                if self.check_op("->") {
+                    self.skip_type()?;
+                }
+                Ok(())
+            }
+            Tok::Op("<") => {
+                self.skip_angles()?;
+                self.skip_simple_type()
+            }
+            Tok::Op("...") => {
+                self.advance();
+                if matches!(self.tok(), Tok::Name(_)) {
+                    self.skip_simple_type()?;
+                }
+                Ok(())
+            }
+            _ => Err(self.unexpected("<type>")),
+        }
+    }
+
+    // Skips a group of tokens enclosed in `()`, `{}` or `[]`
+    fn skip_balanced(&mut self) -> ParseResult {
+        let mut depth = 0usize;
+        loop {
+            match self.tok() {
+                Tok::Op("(" | "{" | "[") => depth += 1,
+                Tok::Op(")" | "}" | "]") => depth = depth.saturating_sub(1),
+                Tok::Eof => return Err("unfinished type".into()),
+                _ => {}
+            }
+            self.advance();
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // Skips a group of tokens enclosed in `<>`
+    fn skip_angles(&mut self) -> ParseResult {
+        let mut depth = 0isize;
+        loop {
+            match self.tok() {
+                Tok::Op("<") => depth += 1,
+                Tok::Op(">") => depth -= 1,
+                Tok::Op(">>") => depth -= 2,
+                Tok::Eof => return Err("unfinished type".into()),
+                _ => {}
+            }
+            self.advance();
+            if depth <= 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Returns global variables and modules referenced by the chunk source
+pub(crate) fn scan(source: &[u8]) -> std::result::Result<Vec<Import>, StdString> {
+    let lexer = Lexer {
+        src: source,
+        pos: 0,
+        line: 1,
+        interp: Vec::new(),
+        toks: Vec::new(),
+    };
+    let mut parser = Parser {
+        toks: lexer.run()?,
+        pos: 0,
+        scopes: vec![Vec::new()],
+        imports: Vec::new(),
+    };
+    parser.statements()?;
+    if parser.tok() != &Tok::Eof {
+        return Err(parser.unexpected("<eof>"));
+    }
+    Ok(parser.imports)
+}
@@ -0,0 +1,125 @@
+use std::cell::Cell;
+
+use crate::error::Result;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::value::Value;
+
+/// Isolated view of the global environment, see [`Lua::run_isolated`].
+///
+/// [`Lua::run_isolated`]: crate::Lua::run_isolated
+pub struct IsolatedGlobals {
+    env: Table,
+    globals: Table,
+    // Assigned (non-nil) values
+    writes: Table,
+    // Keys assigned to nil
+    deleted: Table,
+    aborted: Cell<bool>,
+}
+
+impl IsolatedGlobals {
+    /// Returns the environment table to run chunks in.
+    ///
+    /// Reading from the environment falls through to the real globals, while assignments are
+    /// recorded separately until the changes are committed. Accessing `_G` returns the
+    /// environment itself.
+    pub fn env(&self) -> &Table {
+        &self.env
+    }
+
+    /// Marks the changes to be rolled back, even if the closure returns successfully.
+    ///
+    /// This is useful for dry-run evaluation of scripts.
+    pub fn abort(&self) {
+        self.aborted.set(true);
+    }
+
+    /// Returns `true` if [`abort`] was called.
+    ///
+    /// [`abort`]: #method.abort
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.get()
+    }
+
+    // Commits (or discards) the recorded changes and detaches the environment, so that
+    // functions defined in the isolated environment use the real globals from now on.
+    fn finish(self, commit: bool) -> Result<()> {
+        let lua = self.env.0.lua.lock().lua().clone();
+        let mt = lua.create_table()?;
+        mt.raw_set("__index", &self.globals)?;
+        mt.raw_set("__newindex", &self.globals)?;
+        self.env.set_metatable(Some(mt));
+
+        if commit {
+            for pair in self.deleted.pairs::<Value, Value>() {
+                let (key, _) = pair?;
+                self.globals.set(key, Value::Nil)?;
+            }
+            for pair in self.writes.pairs::<Value, Value>() {
+                let (key, mut value) = pair?;
+                if matches!(&value, Value::Table(t) if *t == self.env) {
+                    value = Value::Table(self.globals.clone());
+                }
+                self.globals.set(key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn run_isolated<R>(lua: &Lua, f: impl FnOnce(&IsolatedGlobals) -> Result<R>) -> Result<R> {
+    let globals = lua.globals();
+    let writes = lua.create_table()?;
+    let deleted = lua.create_table()?;
+
+    let index = {
+        let (globals, writes, deleted) = (globals.clone(), writes.clone(), deleted.clone());
+        lua.create_function(move |_, (env, key): (Table, Value)| {
+            if deleted.raw_get::<bool>(&key)? {
+                return Ok(Value::Nil);
+            }
+            match writes.raw_get::<Value>(&key)? {
+                Value::Nil => {}
+                value => return Ok(value),
+            }
+            match globals.get::<Value>(&key)? {
+                // Keep `_G` pointing to the isolated environment
+                Value::Table(t) if t == globals => Ok(Value::Table(env)),
+                value => Ok(value),
+            }
+        })?
+    };
+    let newindex = {
+        let (writes, deleted) = (writes.clone(), deleted.clone());
+        lua.create_function(move |_, (_, key, value): (Table, Value, Value)| {
+            if value.is_nil() {
+                writes.raw_set(&key, Value::Nil)?;
+                deleted.raw_set(key, true)
+            } else {
+                deleted.raw_set(&key, Value::Nil)?;
+                writes.raw_set(key, value)
+            }
+        })?
+    };
+
+    let env = lua.create_table()?;
+    let mt = lua.create_table()?;
+    mt.raw_set("__index", index)?;
+    mt.raw_set("__newindex", newindex)?;
+    env.set_metatable(Some(mt));
+
+    let isolated = IsolatedGlobals {
+        env,
+        globals,
+        writes,
+        deleted,
+        aborted: Cell::new(false),
+    };
+    let result = f(&isolated);
+    let commit = result.is_ok() && !isolated.is_aborted();
+    let finished = isolated.finish(commit);
+    let result = result?;
+    finished?;
+    Ok(result)
+}
@@ -0,0 +1,755 @@
+use std::fmt::Write as _;
+use std::os::raw::c_void;
+use std::str;
+use std::string::String as StdString;
+
+use rustc_hash::FxHashSet;
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::Value;
+
+#[cfg(feature = "serde_json")]
+use crate::value::NumberPolicy;
+
+// Maximum nesting depth of arrays and objects when encoding or decoding JSON
+const MAX_DEPTH: usize = 128;
+
+/// A struct with options to change the behavior of the built-in JSON encoder and decoder.
+///
+/// Used by [`Table::to_json_string`] and [`Lua::parse_json_with`].
+///
+/// [`Table::to_json_string`]: crate::Table::to_json_string
+/// [`Lua::parse_json_with`]: crate::Lua::parse_json_with
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct JsonOptions {
+    /// If true, encoding an unsupported value (such as function or userdata) will cause an error.
+    /// Otherwise such values are skipped in objects and encoded as `null` in arrays.
+    ///
+    /// Default: **true**
+    pub deny_unsupported_types: bool,
+
+    /// If true, object keys are sorted before encoding to make output deterministic.
+    ///
+    /// Default: **false**
+    pub sort_keys: bool,
+
+    /// If true, empty Lua tables are encoded as `[]` instead of `{}`.
+    ///
+    /// Default: **false**
+    pub encode_empty_tables_as_array: bool,
+
+    /// If true, Lua floats with integral values are encoded with a fractional part (e.g. `1.0`),
+    /// so the integer/float distinction is kept after decoding.
+    /// Otherwise they are encoded as integers (e.g. `1`).
+    ///
+    /// Default: **false**
+    pub explicit_floats: bool,
+
+    /// If true, JSON numbers without fractional part or exponent are decoded as Lua integers.
+    /// Otherwise all numbers are decoded as Lua floats.
+    ///
+    /// Default: **true**
+    pub decode_integers: bool,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonOptions {
+    /// Returns a new instance of [`JsonOptions`] with default parameters.
+    pub const fn new() -> Self {
+        JsonOptions {
+            deny_unsupported_types: true,
+            sort_keys: false,
+            encode_empty_tables_as_array: false,
+            explicit_floats: false,
+            decode_integers: true,
+        }
+    }
+
+    /// Sets [`deny_unsupported_types`] option.
+    ///
+    /// [`deny_unsupported_types`]: #structfield.deny_unsupported_types
+    #[must_use]
+    pub const fn deny_unsupported_types(mut self, enabled: bool) -> Self {
+        self.deny_unsupported_types = enabled;
+        self
+    }
+
+    /// Sets [`sort_keys`] option.
+    ///
+    /// [`sort_keys`]: #structfield.sort_keys
+    #[must_use]
+    pub const fn sort_keys(mut self, enabled: bool) -> Self {
+        self.sort_keys = enabled;
+        self
+    }
+
+    /// Sets [`encode_empty_tables_as_array`] option.
+    ///
+    /// [`encode_empty_tables_as_array`]: #structfield.encode_empty_tables_as_array
+    #[must_use]
+    pub const fn encode_empty_tables_as_array(mut self, enabled: bool) -> Self {
+        self.encode_empty_tables_as_array = enabled;
+        self
+    }
+
+    /// Sets [`explicit_floats`] option.
+    ///
+    /// [`explicit_floats`]: #structfield.explicit_floats
+    #[must_use]
+    pub const fn explicit_floats(mut self, enabled: bool) -> Self {
+        self.explicit_floats = enabled;
+        self
+    }
+
+    /// Sets [`decode_integers`] option.
+    ///
+    /// [`decode_integers`]: #structfield.decode_integers
+    #[must_use]
+    pub const fn decode_integers(mut self, enabled: bool) -> Self {
+        self.decode_integers = enabled;
+        self
+    }
+}
+
+//
+// Encoder
+//
+
+pub(crate) fn encode_table(table: &Table, options: JsonOptions) -> Result<StdString> {
+    let mut encoder = Encoder {
+        out: StdString::new(),
+        options,
+        visited: FxHashSet::default(),
+        depth: 0,
+    };
+    encoder.encode_table(table)?;
+    Ok(encoder.out)
+}
+
+// Returns the table entries and whether the table is encoded as a JSON array.
+//
+// Sequences and empty tables with the array metatable are arrays, their entries are sorted by
+// index.
+fn table_entries(table: &Table) -> Result<(Vec<(Value, Value)>, bool)> {
+    let len = table.raw_len();
+    let mut pairs = Vec::new();
+    let mut is_sequence = true;
+    table.for_each(|key: Value, value: Value| {
+        is_sequence = is_sequence && matches!(key, Value::Integer(i) if i >= 1 && i as usize <= len);
+        pairs.push((key, value));
+        Ok(())
+    })?;
+    is_sequence = is_sequence && len > 0 && pairs.len() == len;
+
+    #[cfg(feature = "serialize")]
+    let is_array = is_sequence || (pairs.is_empty() && table.is_array());
+    #[cfg(not(feature = "serialize"))]
+    let is_array = is_sequence;
+
+    if is_array {
+        pairs.sort_unstable_by_key(|(key, _)| key.as_integer());
+    }
+    Ok((pairs, is_array))
+}
+
+struct Encoder {
+    out: StdString,
+    options: JsonOptions,
+    visited: FxHashSet<*const c_void>,
+    depth: usize,
+}
+
+impl Encoder {
+    // Returns `false` if the value is unsupported and was skipped
+    fn encode_value(&mut self, value: &Value) -> Result<bool> {
+        match value {
+            Value::Nil => self.out.push_str("null"),
+            value if value.is_null() => self.out.push_str("null"),
+            Value::Boolean(b) => self.out.push_str(if *b { "true" } else { "false" }),
+            Value::Integer(i) => write!(self.out, "{i}").unwrap(),
+            Value::Number(n) => self.encode_number(*n)?,
+            Value::String(s) => self.encode_str(&s.as_bytes())?,
+            Value::Table(t) => self.encode_table(t)?,
+            _ if self.options.deny_unsupported_types => {
+                return Err(Error::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "JSON".to_string(),
+                    message: Some("unsupported value type".to_string()),
+                })
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn encode_number(&mut self, n: f64) -> Result<()> {
+        if !n.is_finite() {
+            return Err(Error::FromLuaConversionError {
+                from: "number",
+                to: "JSON".to_string(),
+                message: Some(format!("cannot encode {n}")),
+            });
+        }
+        let start = self.out.len();
+        write!(self.out, "{n}").unwrap();
+        if self.options.explicit_floats && !self.out[start..].contains(['.', 'e']) {
+            self.out.push_str(".0");
+        }
+        Ok(())
+    }
+
+    fn encode_str(&mut self, bytes: &[u8]) -> Result<()> {
+        let s = str::from_utf8(bytes).map_err(|err| Error::FromLuaConversionError {
+            from: "string",
+            to: "JSON".to_string(),
+            message: Some(err.to_string()),
+        })?;
+
+        self.out.push('"');
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            let escape = match c {
+                '"' => "\\\"",
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                '\u{08}' => "\\b",
+                '\u{0c}' => "\\f",
+                c if (c as u32) < 0x20 => "",
+                _ => continue,
+            };
+            self.out.push_str(&s[start..i]);
+            if escape.is_empty() {
+                write!(self.out, "\\u{:04x}", c as u32).unwrap();
+            } else {
+                self.out.push_str(escape);
+            }
+            start = i + 1;
+        }
+        self.out.push_str(&s[start..]);
+        self.out.push('"');
+        Ok(())
+    }
+
+    fn encode_table(&mut self, table: &Table) -> Result<()> {
+        let ptr = table.to_pointer();
+        if !self.visited.insert(ptr) {
+            return Err(Error::FromLuaConversionError {
+                from: "table",
+                to: "JSON".to_string(),
+                message: Some("recursive table detected".to_string()),
+            });
+        }
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(Error::FromLuaConversionError {
+                from: "table",
+                to: "JSON".to_string(),
+                message: Some("recursion limit exceeded".to_string()),
+            });
+        }
+
+        let (pairs, is_array) = table_entries(table)?;
+        if is_array || (pairs.is_empty() && self.options.encode_empty_tables_as_array) {
+            self.out.push('[');
+            for (i, (_, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                if !self.encode_value(value)? {
+                    self.out.push_str("null");
+                }
+            }
+            self.out.push(']');
+        } else {
+            let mut keys = Vec::with_capacity(pairs.len());
+            let mut seen = FxHashSet::default();
+            for (key, _) in &pairs {
+                let key = self.object_key(key)?;
+                // Different Lua keys (e.g. `1` and `"1"`) can produce the same object key
+                if let Some(key) = &key {
+                    if !seen.insert(key.clone()) {
+                        return Err(Error::FromLuaConversionError {
+                            from: "table",
+                            to: "JSON".to_string(),
+                            message: Some(format!("duplicate object key '{key}'")),
+                        });
+                    }
+                }
+                keys.push(key);
+            }
+            let mut entries = keys
+                .into_iter()
+                .zip(pairs.iter().map(|(_, v)| v))
+                .collect::<Vec<_>>();
+            if self.options.sort_keys {
+                entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            }
+
+            self.out.push('{');
+            let mut first = true;
+            for (key, value) in entries {
+                let Some(key) = key else { continue };
+                let rollback = self.out.len();
+                if !first {
+                    self.out.push(',');
+                }
+                self.encode_str(key.as_bytes())?;
+                self.out.push(':');
+                if self.encode_value(value)? {
+                    first = false;
+                } else {
+                    self.out.truncate(rollback);
+                }
+            }
+            self.out.push('}');
+        }
+
+        self.depth -= 1;
+        self.visited.remove(&ptr);
+        Ok(())
+    }
+
+    // Converts table key to a JSON object key.
+    // Returns `None` if the key is unsupported and should be skipped.
+    fn object_key(&self, key: &Value) -> Result<Option<StdString>> {
+        match key {
+            Value::String(s) => Ok(Some(s.to_str()?.to_owned())),
+            Value::Integer(i) => Ok(Some(i.to_string())),
+            Value::Number(n) if n.is_finite() => Ok(Some(n.to_string())),
+            _ if self.options.deny_unsupported_types => Err(Error::FromLuaConversionError {
+                from: key.type_name(),
+                to: "JSON".to_string(),
+                message: Some("unsupported object key type".to_string()),
+            }),
+            _ => Ok(None),
+        }
+    }
+}
+
+//
+// Decoder
+//
+
+pub(crate) fn decode(lua: &Lua, input: &[u8], options: JsonOptions) -> Result<Value> {
+    let mut decoder = Decoder {
+        lua,
+        input,
+        pos: 0,
+        depth: 0,
+        options,
+    };
+    decoder.skip_whitespace();
+    let value = decoder.decode_value()?;
+    decoder.skip_whitespace();
+    if decoder.pos < input.len() {
+        return Err(decoder.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    lua: &'a Lua,
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+    options: JsonOptions,
+}
+
+impl Decoder<'_> {
+    fn error(&self, msg: &str) -> Error {
+        Error::runtime(format!("JSON decode error: {msg} at position {}", self.pos))
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &[u8], value: Value) -> Result<Value> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn decode_value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some(b'n') => self.expect_literal(b"null", Value::NULL),
+            Some(b't') => self.expect_literal(b"true", Value::Boolean(true)),
+            Some(b'f') => self.expect_literal(b"false", Value::Boolean(false)),
+            Some(b'"') => {
+                let bytes = self.decode_string()?;
+                Ok(Value::String(self.lua.create_string(bytes)?))
+            }
+            Some(b'[') => self.decode_array(),
+            Some(b'{') => self.decode_object(),
+            Some(b'-' | b'0'..=b'9') => self.decode_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        Ok(())
+    }
+
+    fn decode_array(&mut self) -> Result<Value> {
+        self.enter()?;
+        let table = self.lua.create_table()?;
+        #[cfg(feature = "serialize")]
+        table.set_metatable(Some(crate::LuaSerdeExt::array_metatable(self.lua)));
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(Value::Table(table));
+        }
+        let mut i = 1;
+        loop {
+            self.skip_whitespace();
+            let value = self.decode_value()?;
+            table.raw_set(i, value)?;
+            i += 1;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => break,
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+        self.pos += 1;
+        self.depth -= 1;
+        Ok(Value::Table(table))
+    }
+
+    fn decode_object(&mut self) -> Result<Value> {
+        self.enter()?;
+        let table = self.lua.create_table()?;
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(Value::Table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected string key"));
+            }
+            let key = self.decode_string()?;
+            let key = self.lua.create_string(key)?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("expected ':'"));
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let value = self.decode_value()?;
+            table.raw_set(key, value)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => break,
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+        self.pos += 1;
+        self.depth -= 1;
+        Ok(Value::Table(table))
+    }
+
+    fn decode_number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.peek() == Some(b'.') {
+            is_float = true;
+            self.pos += 1;
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+        }
+
+        // SAFETY: the slice contains only ASCII characters
+        let s = unsafe { str::from_utf8_unchecked(&self.input[start..self.pos]) };
+        if !is_float && self.options.decode_integers {
+            if let Ok(i) = s.parse::<Integer>() {
+                return Ok(Value::Integer(i));
+            }
+        }
+        s.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn decode_string(&mut self) -> Result<Vec<u8>> {
+        self.pos += 1; // Skip opening quote
+        let mut buf = Vec::new();
+        loop {
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == b'"' || c == b'\\' || c < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            buf.extend_from_slice(&self.input[start..self.pos]);
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = self.peek().ok_or_else(|| self.error("unexpected end of input"))?;
+                    self.pos += 1;
+                    match c {
+                        b'"' => buf.push(b'"'),
+                        b'\\' => buf.push(b'\\'),
+                        b'/' => buf.push(b'/'),
+                        b'b' => buf.push(0x08),
+                        b'f' => buf.push(0x0c),
+                        b'n' => buf.push(b'\n'),
+                        b'r' => buf.push(b'\r'),
+                        b't' => buf.push(b'\t'),
+                        b'u' => {
+                            let c = self.decode_unicode_escape()?;
+                            let mut tmp = [0; 4];
+                            buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                Some(_) => return Err(self.error("control character in string")),
+                None => return Err(self.error("unexpected end of input")),
+            }
+        }
+        if str::from_utf8(&buf).is_err() {
+            return Err(self.error("invalid UTF-8 in string"));
+        }
+        Ok(buf)
+    }
+
+    fn decode_hex4(&mut self) -> Result<u32> {
+        let hex = self.input.get(self.pos..self.pos + 4);
+        // `from_str_radix` accepts a leading sign, so check the digits first
+        let code = hex
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn decode_unicode_escape(&mut self) -> Result<char> {
+        let code = self.decode_hex4()?;
+        let code = match code {
+            0xD800..=0xDBFF => {
+                // Surrogate pair
+                if !self.input[self.pos..].starts_with(b"\\u") {
+                    return Err(self.error("unpaired surrogate"));
+                }
+                self.pos += 2;
+                let low = self.decode_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("unpaired surrogate"));
+                }
+                0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(self.error("unpaired surrogate")),
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+}
+
+//
+// Conversion between Lua values and `serde_json::Value`
+//
+
+#[cfg(feature = "serde_json")]
+pub(crate) fn from_json_value(lua: &Lua, value: &serde_json::Value, policy: NumberPolicy) -> Result<Value> {
+    from_json_value_inner(lua, value, policy, 0)
+}
+
+#[cfg(feature = "serde_json")]
+fn from_json_value_inner(
+    lua: &Lua,
+    value: &serde_json::Value,
+    policy: NumberPolicy,
+    depth: usize,
+) -> Result<Value> {
+    use serde_json::Value as JsonValue;
+
+    if depth > MAX_DEPTH {
+        return Err(Error::ToLuaConversionError {
+            from: "serde_json::Value".to_string(),
+            to: "table",
+            message: Some("recursion limit exceeded".to_string()),
+        });
+    }
+
+    Ok(match value {
+        JsonValue::Null => Value::NULL,
+        JsonValue::Bool(b) => Value::Boolean(*b),
+        JsonValue::Number(n) => {
+            let value = match n.as_i64().and_then(|i| Integer::try_from(i).ok()) {
+                Some(i) => Value::Integer(i),
+                None => Value::Number(n.as_f64().ok_or_else(|| Error::ToLuaConversionError {
+                    from: "serde_json::Number".to_string(),
+                    to: "number",
+                    message: Some(format!("cannot represent {n}")),
+                })?),
+            };
+            value.canonicalize_numbers(policy)
+        }
+        JsonValue::String(s) => Value::String(lua.create_string(s)?),
+        JsonValue::Array(array) => {
+            let table = lua.create_table_with_capacity(array.len(), 0)?;
+            #[cfg(feature = "serialize")]
+            table.set_metatable(Some(crate::LuaSerdeExt::array_metatable(lua)));
+            for (i, value) in array.iter().enumerate() {
+                table.raw_set(i + 1, from_json_value_inner(lua, value, policy, depth + 1)?)?;
+            }
+            Value::Table(table)
+        }
+        JsonValue::Object(object) => {
+            let table = lua.create_table_with_capacity(0, object.len())?;
+            for (key, value) in object {
+                table.raw_set(
+                    key.as_str(),
+                    from_json_value_inner(lua, value, policy, depth + 1)?,
+                )?;
+            }
+            Value::Table(table)
+        }
+    })
+}
+
+#[cfg(feature = "serde_json")]
+pub(crate) fn to_json_value(value: &Value, policy: NumberPolicy) -> Result<serde_json::Value> {
+    to_json_value_inner(value, policy, &mut FxHashSet::default(), 0)
+}
+
+#[cfg(feature = "serde_json")]
+fn to_json_value_inner(
+    value: &Value,
+    policy: NumberPolicy,
+    visited: &mut FxHashSet<*const c_void>,
+    depth: usize,
+) -> Result<serde_json::Value> {
+    use serde_json::{Map, Number, Value as JsonValue};
+
+    let conversion_error = |from, message: StdString| Error::FromLuaConversionError {
+        from,
+        to: "serde_json::Value".to_string(),
+        message: Some(message),
+    };
+
+    Ok(match value {
+        Value::Nil => JsonValue::Null,
+        value if value.is_null() => JsonValue::Null,
+        Value::Boolean(b) => JsonValue::Bool(*b),
+        Value::Integer(_) | Value::Number(_) => match value.canonicalize_numbers(policy) {
+            #[allow(clippy::useless_conversion)]
+            Value::Integer(i) => JsonValue::Number(i64::from(i).into()),
+            Value::Number(n) => match Number::from_f64(n) {
+                Some(n) => JsonValue::Number(n),
+                None => return Err(conversion_error("number", format!("cannot encode {n}"))),
+            },
+            _ => unreachable!(),
+        },
+        Value::String(s) => JsonValue::String(s.to_str()?.to_owned()),
+        Value::Table(table) => {
+            let ptr = table.to_pointer();
+            if !visited.insert(ptr) {
+                return Err(conversion_error("table", "recursive table detected".to_string()));
+            }
+            if depth >= MAX_DEPTH {
+                return Err(conversion_error("table", "recursion limit exceeded".to_string()));
+            }
+
+            let (pairs, is_array) = table_entries(table)?;
+            let result = if is_array {
+                let array = pairs
+                    .iter()
+                    .map(|(_, value)| to_json_value_inner(value, policy, visited, depth + 1));
+                JsonValue::Array(array.collect::<Result<_>>()?)
+            } else {
+                let mut object = Map::new();
+                for (key, value) in &pairs {
+                    let key = match key {
+                        Value::String(s) => s.to_str()?.to_owned(),
+                        Value::Integer(i) => i.to_string(),
+                        Value::Number(n) if n.is_finite() => n.to_string(),
+                        _ => {
+                            let message = "unsupported object key type".to_string();
+                            return Err(conversion_error(key.type_name(), message));
+                        }
+                    };
+                    object.insert(key, to_json_value_inner(value, policy, visited, depth + 1)?);
+                }
+                JsonValue::Object(object)
+            };
+
+            visited.remove(&ptr);
+            result
+        }
+        value => {
+            return Err(conversion_error(
+                value.type_name(),
+                "unsupported value type".to_string(),
+            ))
+        }
+    })
+}
@@ -0,0 +1,69 @@
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::value::{IntoLua, Nil, Value};
+
+// Name of the registry table with initializers of the lazy globals
+const LAZY_GLOBALS_KEY: &str = "__mlua_lazy_globals";
+
+pub(crate) fn set_lazy_global<F, V>(lua: &Lua, name: &str, init: F) -> Result<()>
+where
+    F: FnOnce(&Lua) -> Result<V> + MaybeSend + 'static,
+    V: IntoLua,
+{
+    let initializers = match lua.named_registry_value::<Option<Table>>(LAZY_GLOBALS_KEY)? {
+        Some(initializers) => initializers,
+        None => {
+            let initializers = lua.create_table()?;
+            install_index(lua, &initializers)?;
+            lua.set_named_registry_value(LAZY_GLOBALS_KEY, &initializers)?;
+            initializers
+        }
+    };
+
+    let mut init = Some(init);
+    let init = lua.create_function_mut(move |lua, ()| {
+        let init = init
+            .take()
+            .ok_or_else(|| Error::runtime("lazy global is already initialized"))?;
+        init(lua)?.into_lua(lua)
+    })?;
+    initializers.raw_set(name, init)?;
+    // Existing value would hide the lazy one
+    lua.globals().raw_set(name, Nil)
+}
+
+// Sets the `__index` metamethod of globals initializing lazy values on the first access.
+//
+// The previous `__index` (if any) is used for other keys.
+fn install_index(lua: &Lua, initializers: &Table) -> Result<()> {
+    let globals = lua.globals();
+    let metatable = match globals.metatable() {
+        Some(metatable) => metatable,
+        None => {
+            let metatable = lua.create_table()?;
+            globals.set_metatable(Some(metatable.clone()));
+            metatable
+        }
+    };
+    let fallback = metatable.raw_get::<Value>("__index")?;
+
+    let initializers = initializers.clone();
+    let index = lua.create_function(move |_, (globals, key): (Table, Value)| {
+        if let Some(init) = initializers.raw_get::<Option<Function>>(&key)? {
+            // Remove first, so recursive access does not run the initializer again
+            initializers.raw_set(&key, Nil)?;
+            let value = init.call::<Value>(())?;
+            globals.raw_set(&key, &value)?;
+            return Ok(value);
+        }
+        match &fallback {
+            Value::Function(func) => func.call((globals, key)),
+            Value::Table(table) => table.get(key),
+            _ => Ok(Nil),
+        }
+    })?;
+    metatable.raw_set("__index", index)
+}
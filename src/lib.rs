@@ -79,62 +79,178 @@
 mod macros;
 
 mod buffer;
+mod callback;
+mod cancel;
+mod capability;
 mod chunk;
+mod command;
 mod conversion;
+mod deterministic;
+#[cfg(feature = "encoding")]
+mod encoding;
 mod error;
+mod error_object;
+mod event;
 mod function;
+mod gc_policy;
+mod hash;
 mod hook;
+mod identity;
+mod imports;
+mod isolate;
+mod json;
+mod lazy;
+#[cfg(feature = "async")]
+mod limiter;
+mod lua_source;
 #[cfg(feature = "luau")]
 mod luau;
+mod memoize;
 mod memory;
+mod merge;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod middleware;
 mod multi;
+mod number_format;
+mod pack;
+mod path;
+#[cfg(feature = "async")]
+mod pool;
+mod random;
+mod ranged;
+mod registry;
+#[cfg(feature = "async")]
+mod scheduler;
 mod scope;
 mod state;
 mod stdlib;
 mod string;
+#[cfg(feature = "async")]
+mod sync;
 mod table;
+#[cfg(feature = "testing")]
+mod testing;
 mod thread;
+mod thread_group;
+#[cfg(feature = "async")]
+mod time;
 mod traits;
 mod types;
 mod userdata;
 mod util;
 mod value;
+mod version;
+mod vfs;
 
+pub mod pattern;
 pub mod prelude;
+pub mod utf8;
 
 pub use bstr::BString;
 pub use ffi::{self, lua_CFunction, lua_State};
 
-pub use crate::chunk::{AsChunk, Chunk, ChunkMode};
-pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result};
+pub use crate::callback::{Callback, Mapper, Predicate, TypedFunction};
+pub use crate::cancel::CancellationToken;
+pub use crate::capability::Capability;
+pub use crate::chunk::{
+    AsChunk, Chunk, ChunkMode, ChunkProvider, IncrementalChunk, PartialChunk, ProvidedChunk,
+};
+pub use crate::command::{
+    Command, CommandArg, CommandArgType, CommandBuffer, CommandDrain, CommandSchema, FromCommand,
+    OverflowPolicy,
+};
+pub use crate::deterministic::DeterministicOptions;
+pub use crate::error::{ConversionErrorInfo, Error, ErrorContext, ExternalError, ExternalResult, Result};
+pub use crate::error_object::{ErrorObject, ErrorObjectOptions};
+pub use crate::event::{EventBus, SubscriptionId};
 pub use crate::function::{Function, FunctionInfo};
+pub use crate::gc_policy::GCAction;
+pub use crate::hash::{HashAlgorithm, HashOptions};
 pub use crate::hook::{Debug, DebugEvent, DebugNames, DebugSource, DebugStack};
-pub use crate::multi::Variadic;
+pub use crate::identity::ValueIdentity;
+pub use crate::isolate::IsolatedGlobals;
+pub use crate::json::JsonOptions;
+pub use crate::lua_source::LuaSourceOptions;
+pub use crate::memoize::MemoizeOptions;
+pub use crate::merge::{MergeResolver, MergeStrategy};
+pub use crate::middleware::{CallInfo, Next};
+pub use crate::multi::{NamedArgs, Variadic};
+pub use crate::number_format::NumberFormat;
+pub use crate::path::PathOptions;
+pub use crate::random::RandomSource;
+pub use crate::ranged::Ranged;
+pub use crate::registry::{Registry, RegistryEntry, RegistryStats};
 pub use crate::scope::Scope;
-pub use crate::state::{GCMode, Lua, LuaOptions};
-pub use crate::stdlib::StdLib;
-pub use crate::string::{BorrowedBytes, BorrowedStr, String};
+pub use crate::state::{
+    DurationFormat, GCMode, IntegerOverflow, Lua, LuaOptions, RefStackUsage, UnwindMode, ValueScope,
+};
+pub use crate::stdlib::{StdLib, UnsafeCapabilities};
+pub use crate::string::{BorrowedBytes, BorrowedStr, CharIndices, Chars, String};
 pub use crate::table::{Table, TablePairs, TableSequence};
 pub use crate::thread::{Thread, ThreadStatus};
-pub use crate::traits::{LuaNativeFn, LuaNativeFnMut, ObjectLike};
+pub use crate::thread_group::ThreadGroup;
+pub use crate::traits::{LuaNativeFn, LuaNativeFnMut, LuaTrait, ObjectLike, ToLuaString};
 pub use crate::types::{
     AppDataRef, AppDataRefMut, Either, Integer, LightUserData, MaybeSend, Number, RegistryKey, VmState,
 };
 pub use crate::userdata::{
-    AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMetatable, UserDataMethods, UserDataRef,
-    UserDataRefMut, UserDataRegistry,
+    AnyUserData, MappedUserDataRef, MappedUserDataRefMut, MetaMethod, UserData, UserDataFields,
+    UserDataMetatable, UserDataMethods, UserDataRef, UserDataRefMut, UserDataRefUpgradable, UserDataRegistry,
+};
+pub use crate::value::{
+    FromLua, FromLuaMulti, FromLuaTable, IntoLua, IntoLuaMulti, MultiValue, Nil, NumberPolicy, Value,
 };
-pub use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
+pub use crate::version::{LuaVersion, VersionInfo};
+pub use crate::vfs::{DirFs, MemoryFs, OverlayFs, Vfs, VfsFileType, VfsMetadata};
 
 #[cfg(not(feature = "luau"))]
-pub use crate::hook::HookTriggers;
+pub use crate::{function::UpvaluePolicy, hook::HookTriggers};
 
 #[cfg(any(feature = "luau", doc))]
 #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
 pub use crate::{buffer::Buffer, chunk::Compiler, function::CoverageInfo, types::Vector};
 
+#[cfg(feature = "luau")]
+#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+pub use crate::luau::ModuleGraph;
+
+#[cfg(feature = "ref-audit")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ref-audit")))]
+pub use crate::state::RefOrigin;
+
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use crate::metrics::{HotString, Metric, MetricKind, MetricsSnapshot};
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub use crate::userdata::MethodCacheStats;
+
+#[cfg(feature = "encoding")]
+#[cfg_attr(docsrs, doc(cfg(feature = "encoding")))]
+pub use crate::encoding::Encoding;
+
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub use crate::testing::{TestOutcome, TestReport, TestResult};
+
 #[cfg(feature = "async")]
-pub use crate::{thread::AsyncThread, traits::LuaNativeAsyncFn};
+pub use crate::{
+    limiter::AsyncLimiter,
+    pool::{LuaPool, PoolOptions},
+    sync::{AsyncChannel, AsyncMutex, AsyncSemaphore},
+    thread::AsyncThread,
+    time::{ManualClock, SleepFuture, TimeDriver},
+    traits::LuaNativeAsyncFn,
+};
+
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use crate::time::TokioTimeDriver;
+
+#[cfg(feature = "async-std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-std")))]
+pub use crate::time::AsyncStdTimeDriver;
 
 #[cfg(feature = "serialize")]
 #[doc(inline)]
@@ -213,6 +329,170 @@ pub use mlua_derive::chunk;
 #[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
 pub use mlua_derive::FromLua;
 
+/// Derive [`IntoLuaMulti`] for a struct.
+///
+/// By default, the struct fields are converted to multiple Lua values in the declaration order, so
+/// a type like `Span(usize, usize)` can be returned from a function as two values. Fields must
+/// implement [`IntoLua`], except the last one, which can implement [`IntoLuaMulti`].
+///
+/// With the `#[mlua(table)]` attribute the struct is converted to a single Lua table instead, where
+/// named fields are stored by their names and tuple struct fields are stored as a sequence.
+///
+/// ```
+/// use mlua::{FromLuaMulti, IntoLuaMulti};
+///
+/// #[derive(IntoLuaMulti, FromLuaMulti)]
+/// struct Span(usize, usize);
+///
+/// #[derive(IntoLuaMulti, FromLuaMulti)]
+/// #[mlua(table)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+/// ```
+///
+/// [`IntoLua`]: crate::IntoLua
+/// [`IntoLuaMulti`]: crate::IntoLuaMulti
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::IntoLuaMulti;
+
+/// Derive [`FromLuaMulti`] for a struct.
+///
+/// This is a counterpart of the [`IntoLuaMulti`] derive macro and supports the same attributes.
+/// When used for function arguments, each field consumes one argument (the last field can consume
+/// the rest of them). Missing values are treated as `nil`.
+///
+/// [`FromLuaMulti`]: crate::FromLuaMulti
+/// [`IntoLuaMulti`]: macro@crate::IntoLuaMulti
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::FromLuaMulti;
+
+/// Derive [`FromLuaTable`] for a struct with named fields.
+///
+/// The struct can then be used with the [`NamedArgs`] wrapper to accept function arguments by
+/// name (as a single table) or by position (in the field declaration order). The struct must
+/// implement [`Default`], which provides values of the missing arguments, and the field types
+/// must implement [`FromLua`].
+///
+/// [`FromLuaTable`]: crate::FromLuaTable
+/// [`NamedArgs`]: crate::NamedArgs
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::FromLuaTable;
+
+/// Derive [`IntoLua`] and [`FromLua`] for a struct with named fields, represented as a Lua table.
+///
+/// Fields are stored under their names. The representation can be customized with field
+/// attributes, similar to `serde` (but without requiring the `serialize` feature):
+///
+/// - `#[lua(rename = "name")]` stores the field under a different key.
+/// - `#[lua(default)]` uses [`Default::default()`] if the key is missing (`nil`).
+/// - `#[lua(skip)]` does not store the field and always uses [`Default::default()`] when converting
+///   from Lua.
+/// - `#[lua(flatten)]` stores fields of the (table) value in the parent table.
+///
+/// ```
+/// use mlua::LuaTable;
+///
+/// #[derive(LuaTable)]
+/// struct Window {
+///     title: String,
+///     #[lua(rename = "isVisible", default)]
+///     visible: bool,
+///     #[lua(flatten)]
+///     size: Size,
+///     #[lua(skip)]
+///     cache: Vec<u8>,
+/// }
+///
+/// #[derive(LuaTable)]
+/// struct Size {
+///     width: u32,
+///     height: u32,
+/// }
+/// ```
+///
+/// [`IntoLua`]: crate::IntoLua
+/// [`FromLua`]: crate::FromLua
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::LuaTable;
+
+/// Allows a trait to be implemented by a Lua table.
+///
+/// The attribute generates an implementation of [`LuaTrait`] for `dyn Trait`, so a Lua table can be
+/// turned into `Box<dyn Trait>` using [`Lua::implement_trait`].
+///
+/// Every trait method is forwarded to the function with the same name in the table, which is
+/// called as a method (with the table passed as the first argument). Arguments are converted
+/// using [`IntoLua`] and the return value using [`FromLuaMulti`].
+/// If a method returns `Result<T, E>` (where `E: From<mlua::Error>`), Lua errors are returned
+/// to the caller, otherwise they cause a panic.
+///
+/// Methods with a default implementation are optional, the default is used when the table does not
+/// have a function with that name.
+///
+/// Generic traits and methods, async methods, associated types and constants are not supported.
+///
+/// ```
+/// use mlua::{Lua, Result};
+///
+/// #[mlua::lua_trait]
+/// trait Shape {
+///     fn area(&self) -> Result<f64>;
+///
+///     fn name(&self) -> String {
+///         "shape".to_string()
+///     }
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     let table = lua.load("{ w = 2, h = 3, area = function(self) return self.w * self.h end }").eval()?;
+///     let shape = lua.implement_trait::<dyn Shape>(table)?;
+///     assert_eq!(shape.area()?, 6.0);
+///     assert_eq!(shape.name(), "shape");
+///     Ok(())
+/// }
+/// ```
+///
+/// [`LuaTrait`]: crate::LuaTrait
+/// [`Lua::implement_trait`]: crate::Lua::implement_trait
+/// [`IntoLua`]: crate::IntoLua
+/// [`FromLuaMulti`]: crate::FromLuaMulti
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::lua_trait;
+
+/// Compiles a Lua script at build time and embeds the resulting bytecode into the binary.
+///
+/// The path is resolved relative to the crate root directory (where `Cargo.toml` is located).
+/// The script is compiled using the Lua version selected for mlua and debug information is
+/// stripped. Syntax errors are reported as compile errors.
+///
+/// The macro returns a value implementing [`AsChunk`] which can be passed to [`Lua::load`].
+///
+/// ```ignore
+/// use mlua::{include_lua, Lua, Result};
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     lua.load(include_lua!("scripts/init.lua")).exec()
+/// }
+/// ```
+///
+/// Note that bytecode is compiled by the vendored Lua build, so using it with a system Lua library
+/// requires a compatible version of it.
+///
+/// [`AsChunk`]: crate::AsChunk
+/// [`Lua::load`]: crate::Lua::load
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use mlua_derive::include_lua;
+
 /// Registers Lua module entrypoint.
 ///
 /// You can register multiple entrypoints as required.
@@ -258,6 +538,50 @@ pub use mlua_derive::FromLua;
 #[cfg_attr(docsrs, doc(cfg(feature = "module")))]
 pub use mlua_derive::lua_module;
 
+/// Registers a Lua plugin entrypoint exported from the host program.
+///
+/// This is an inverse of the [`lua_module`] attribute: instead of building a loadable `cdylib`, the
+/// Lua library is linked into the host program (usually in vendored mode), and the function is
+/// exported as a C function `luaopen_<name>`. It can then be used to open the plugin in Lua states
+/// that were not created by `mlua` (for example, by C code or another library in the same process).
+///
+/// ```ignore
+/// use mlua::{Lua, Result, Table};
+///
+/// #[mlua::lua_plugin]
+/// fn geometry(lua: &Lua) -> Result<Table> {
+///     let exports = lua.create_table()?;
+///     exports.set("distance", lua.create_function(|_, (x, y): (f64, f64)| Ok(x.hypot(y)))?)?;
+///     Ok(exports)
+/// }
+///
+/// // Somewhere in C code (or using `mlua::ffi`), register it for `require`:
+/// // lua_getglobal(L, "package");
+/// // lua_getfield(L, -1, "preload");
+/// // lua_pushcfunction(L, luaopen_geometry);
+/// // lua_setfield(L, -2, "geometry");
+/// ```
+///
+/// The `name` option can be used to change the exported name, in the same way as for
+/// [`lua_module`].
+///
+/// To allow a stock Lua interpreter in the same process to find the entrypoint using `require`
+/// (via `package.loadlib` or `package.cpath` pointing to the executable), the host binary must
+/// export its dynamic symbols (e.g. link with `-rdynamic` on Linux).
+///
+/// Requires `feature = "plugin"`
+///
+/// [`lua_module`]: macro@crate::lua_module
+#[cfg(any(feature = "plugin", docsrs))]
+#[cfg_attr(docsrs, doc(cfg(feature = "plugin")))]
+pub use mlua_derive::lua_plugin;
+
+#[cfg(all(feature = "module", feature = "plugin"))]
+compile_error!("`plugin` feature cannot be used in module mode");
+
+#[cfg(all(feature = "luau", feature = "plugin"))]
+compile_error!("`plugin` feature is not supported by Luau");
+
 #[cfg(all(feature = "module", feature = "send"))]
 compile_error!("`send` feature is not supported in module mode");
 
@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use parking_lot::Mutex;
+
+/// Limits the number of concurrently running (in-flight) futures of async functions.
+///
+/// A limiter is attached to async functions using [`Lua::create_async_function_with_limiter`].
+/// When the limit is reached, further calls wait (suspending the calling coroutine) until one of
+/// the running calls completes. Waiting calls are served in the first-come, first-served order.
+///
+/// Cloned instances share the same limit, so a single limiter can be attached to a group of
+/// functions (or to every async function) to enforce a shared limit.
+///
+/// Requires `feature = "async"`
+///
+/// [`Lua::create_async_function_with_limiter`]: crate::Lua::create_async_function_with_limiter
+#[derive(Clone, Debug)]
+pub struct AsyncLimiter(Arc<Mutex<LimiterState>>);
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: usize,
+    in_flight: usize,
+    next_id: u64,
+    waiters: VecDeque<(u64, Waker)>,
+}
+
+impl AsyncLimiter {
+    /// Creates a new limiter allowing up to `limit` concurrently running futures.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    pub fn new(limit: usize) -> Self {
+        assert!(limit > 0, "concurrency limit must be greater than zero");
+        AsyncLimiter(Arc::new(Mutex::new(LimiterState {
+            limit,
+            in_flight: 0,
+            next_id: 0,
+            waiters: VecDeque::new(),
+        })))
+    }
+
+    /// Returns the maximum number of concurrently running futures.
+    pub fn limit(&self) -> usize {
+        self.0.lock().limit
+    }
+
+    /// Returns the number of currently running futures.
+    pub fn in_flight(&self) -> usize {
+        self.0.lock().in_flight
+    }
+
+    /// Returns the number of calls waiting for a free slot.
+    pub fn waiting(&self) -> usize {
+        self.0.lock().waiters.len()
+    }
+
+    pub(crate) fn acquire(&self) -> Acquire {
+        Acquire {
+            limiter: self.clone(),
+            id: None,
+        }
+    }
+}
+
+impl LimiterState {
+    // Returns a waker of the first waiting call if there is a free slot
+    fn next_waker(&self) -> Option<Waker> {
+        if self.in_flight < self.limit {
+            return self.waiters.front().map(|(_, waker)| waker.clone());
+        }
+        None
+    }
+}
+
+pub(crate) struct Acquire {
+    limiter: AsyncLimiter,
+    // Identifier in the wait queue (if waiting)
+    id: Option<u64>,
+}
+
+impl Future for Acquire {
+    type Output = Permit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Permit> {
+        let limiter = self.limiter.clone();
+        let mut state = limiter.0.lock();
+        let is_first = match self.id {
+            Some(id) => state.waiters.front().map(|(i, _)| *i) == Some(id),
+            None => state.waiters.is_empty(),
+        };
+        if is_first && state.in_flight < state.limit {
+            state.in_flight += 1;
+            let mut next_waker = None;
+            if self.id.take().is_some() {
+                state.waiters.pop_front();
+                next_waker = state.next_waker();
+            }
+            drop(state);
+            // Wake outside of the lock
+            if let Some(waker) = next_waker {
+                waker.wake();
+            }
+            return Poll::Ready(Permit(limiter));
+        }
+
+        match self.id {
+            Some(id) => {
+                if let Some((_, waker)) = state.waiters.iter_mut().find(|(i, _)| *i == id) {
+                    waker.clone_from(cx.waker());
+                }
+            }
+            None => {
+                state.next_id += 1;
+                let id = state.next_id;
+                state.waiters.push_back((id, cx.waker().clone()));
+                self.id = Some(id);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let next_waker = {
+                let mut state = self.limiter.0.lock();
+                state.waiters.retain(|(i, _)| *i != id);
+                state.next_waker()
+            };
+            if let Some(waker) = next_waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub(crate) struct Permit(AsyncLimiter);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let next_waker = {
+            let mut state = self.0 .0.lock();
+            state.in_flight -= 1;
+            state.next_waker()
+        };
+        if let Some(waker) = next_waker {
+            waker.wake();
+        }
+    }
+}
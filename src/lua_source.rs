@@ -0,0 +1,329 @@
+use std::cmp::Ordering;
+use std::fmt::Write as _;
+use std::os::raw::c_void;
+use std::str;
+use std::string::String as StdString;
+
+use rustc_hash::FxHashSet;
+
+use crate::error::{Error, Result};
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::Value;
+
+// Maximum nesting depth of tables
+const MAX_DEPTH: usize = 128;
+
+// Words that cannot be used as bare field names in a table constructor
+const KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// A struct with options to change the output of [`Lua::to_lua_source`].
+///
+/// [`Lua::to_lua_source`]: crate::Lua::to_lua_source
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct LuaSourceOptions {
+    /// If true, tables are printed on multiple lines with each entry indented.
+    /// Otherwise the whole value is printed on a single line.
+    ///
+    /// Default: **false**
+    pub pretty: bool,
+
+    /// Number of spaces used for each indentation level in pretty mode.
+    ///
+    /// Default: **2**
+    pub indent: usize,
+
+    /// If true, encoding an unsupported value (such as function or userdata) will cause an error.
+    /// Otherwise entries with such keys or values are skipped (and replaced by `nil` in the
+    /// sequence part of a table).
+    ///
+    /// Default: **true**
+    pub deny_unsupported_types: bool,
+}
+
+impl Default for LuaSourceOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LuaSourceOptions {
+    /// Returns a new instance of [`LuaSourceOptions`] with default parameters.
+    pub const fn new() -> Self {
+        LuaSourceOptions {
+            pretty: false,
+            indent: 2,
+            deny_unsupported_types: true,
+        }
+    }
+
+    /// Sets [`pretty`] option.
+    ///
+    /// [`pretty`]: #structfield.pretty
+    #[must_use]
+    pub const fn pretty(mut self, enabled: bool) -> Self {
+        self.pretty = enabled;
+        self
+    }
+
+    /// Sets [`indent`] option.
+    ///
+    /// [`indent`]: #structfield.indent
+    #[must_use]
+    pub const fn indent(mut self, width: usize) -> Self {
+        self.indent = width;
+        self
+    }
+
+    /// Sets [`deny_unsupported_types`] option.
+    ///
+    /// [`deny_unsupported_types`]: #structfield.deny_unsupported_types
+    #[must_use]
+    pub const fn deny_unsupported_types(mut self, enabled: bool) -> Self {
+        self.deny_unsupported_types = enabled;
+        self
+    }
+}
+
+pub(crate) fn encode(value: &Value, options: LuaSourceOptions) -> Result<StdString> {
+    let mut encoder = Encoder {
+        out: StdString::new(),
+        options,
+        depth: 0,
+        visited: FxHashSet::default(),
+    };
+    if !encoder.encode_value(value)? {
+        return Err(unsupported_error(value.type_name(), "unsupported value type"));
+    }
+    Ok(encoder.out)
+}
+
+fn unsupported_error(from: &'static str, message: &str) -> Error {
+    Error::FromLuaConversionError {
+        from,
+        to: "Lua source".to_string(),
+        message: Some(message.to_string()),
+    }
+}
+
+struct Encoder {
+    out: StdString,
+    options: LuaSourceOptions,
+    depth: usize,
+    visited: FxHashSet<*const c_void>,
+}
+
+impl Encoder {
+    // Returns `false` if the value is unsupported and was skipped
+    fn encode_value(&mut self, value: &Value) -> Result<bool> {
+        match value {
+            Value::Nil => self.out.push_str("nil"),
+            Value::Boolean(b) => self.out.push_str(if *b { "true" } else { "false" }),
+            Value::Integer(i) => self.encode_integer(*i),
+            Value::Number(n) => self.encode_number(*n),
+            Value::String(s) => self.encode_str(&s.as_bytes()),
+            Value::Table(t) => self.encode_table(t)?,
+            _ if self.options.deny_unsupported_types => {
+                return Err(unsupported_error(value.type_name(), "unsupported value type"))
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn encode_integer(&mut self, i: Integer) {
+        if i == Integer::MIN {
+            // The literal `-9223372036854775808` would be read back as a float
+            write!(self.out, "{} - 1", i + 1).unwrap();
+        } else {
+            write!(self.out, "{i}").unwrap();
+        }
+    }
+
+    fn encode_number(&mut self, n: f64) {
+        if n.is_nan() {
+            self.out.push_str("0/0");
+        } else if n.is_infinite() {
+            self.out.push_str(if n > 0.0 { "1/0" } else { "-1/0" });
+        } else {
+            // `Debug` keeps the fractional part (`1.0`), so the value remains a float
+            write!(self.out, "{n:?}").unwrap();
+        }
+    }
+
+    fn encode_str(&mut self, bytes: &[u8]) {
+        self.out.push('"');
+        match str::from_utf8(bytes) {
+            Ok(s) => {
+                for c in s.chars() {
+                    match c {
+                        c if c.is_ascii() => self.encode_ascii_byte(c as u8),
+                        c => self.out.push(c),
+                    }
+                }
+            }
+            Err(_) => {
+                for &b in bytes {
+                    match b {
+                        0x80.. => write!(self.out, "\\{b:03}").unwrap(),
+                        b => self.encode_ascii_byte(b),
+                    }
+                }
+            }
+        }
+        self.out.push('"');
+    }
+
+    fn encode_ascii_byte(&mut self, b: u8) {
+        match b {
+            b'"' => self.out.push_str("\\\""),
+            b'\\' => self.out.push_str("\\\\"),
+            b'\n' => self.out.push_str("\\n"),
+            b'\r' => self.out.push_str("\\r"),
+            b'\t' => self.out.push_str("\\t"),
+            // Always use three digits, so a following digit is not taken as part of the escape
+            b if b < 0x20 || b == 0x7f => write!(self.out, "\\{b:03}").unwrap(),
+            b => self.out.push(b as char),
+        }
+    }
+
+    fn encode_table(&mut self, table: &Table) -> Result<()> {
+        let ptr = table.to_pointer();
+        if !self.visited.insert(ptr) {
+            return Err(unsupported_error("table", "recursive table detected"));
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(unsupported_error("table", "recursion limit exceeded"));
+        }
+
+        let mut pairs = Vec::new();
+        table.for_each(|key: Value, value: Value| {
+            pairs.push((key, value));
+            Ok(())
+        })?;
+        pairs.sort_by(|(a, _), (b, _)| compare_keys(a, b));
+
+        // Integer keys `1..=n` are printed first without brackets
+        let mut seq_len: Integer = 0;
+        for (key, _) in &pairs {
+            if let Value::Integer(i) = key {
+                if *i == seq_len + 1 {
+                    seq_len += 1;
+                }
+            }
+        }
+        let is_positional = |key: &Value| matches!(key, Value::Integer(i) if *i >= 1 && *i <= seq_len);
+        pairs.sort_by_key(|(key, _)| !is_positional(key));
+
+        self.out.push('{');
+        self.depth += 1;
+        let mut first = true;
+        for (key, value) in &pairs {
+            let positional = is_positional(key);
+            let rollback = self.out.len();
+            self.begin_entry(first);
+            if positional {
+                if !self.encode_value(value)? {
+                    self.out.push_str("nil");
+                }
+            } else {
+                if !self.encode_key(key)? {
+                    self.out.truncate(rollback);
+                    continue;
+                }
+                self.out.push_str(" = ");
+                if !self.encode_value(value)? {
+                    self.out.truncate(rollback);
+                    continue;
+                }
+            }
+            first = false;
+        }
+        self.depth -= 1;
+        if self.options.pretty && !first {
+            self.out.push(',');
+            self.newline();
+        }
+        self.out.push('}');
+
+        self.visited.remove(&ptr);
+        Ok(())
+    }
+
+    fn begin_entry(&mut self, first: bool) {
+        if self.options.pretty {
+            if !first {
+                self.out.push(',');
+            }
+            self.newline();
+        } else if !first {
+            self.out.push_str(", ");
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        let width = self.depth * self.options.indent;
+        self.out.extend(std::iter::repeat(' ').take(width));
+    }
+
+    // Returns `false` if the key is unsupported and was skipped
+    fn encode_key(&mut self, key: &Value) -> Result<bool> {
+        if let Value::String(s) = key {
+            let bytes = s.as_bytes();
+            if is_identifier(&bytes) {
+                // Identifiers are always ASCII
+                self.out.push_str(str::from_utf8(&bytes).unwrap());
+                return Ok(true);
+            }
+        }
+        match key {
+            Value::Boolean(_) | Value::Integer(_) | Value::String(_) => {}
+            Value::Number(n) if !n.is_nan() => {}
+            _ if self.options.deny_unsupported_types => {
+                return Err(unsupported_error(key.type_name(), "unsupported table key type"))
+            }
+            _ => return Ok(false),
+        }
+        self.out.push('[');
+        self.encode_value(key)?;
+        self.out.push(']');
+        Ok(true)
+    }
+}
+
+fn is_identifier(bytes: &[u8]) -> bool {
+    match bytes.first() {
+        Some(b) if b.is_ascii_alphabetic() || *b == b'_' => {}
+        _ => return false,
+    }
+    bytes.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+        && !KEYWORDS.iter().any(|kw| kw.as_bytes() == bytes)
+}
+
+// Orders table keys to produce a stable output:
+// booleans first, then numbers (by value), then strings (bytewise), then everything else.
+fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Boolean(_) => 0,
+            Value::Integer(_) | Value::Number(_) => 1,
+            Value::String(_) => 2,
+            _ => 3,
+        }
+    }
+
+    match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Integer(a), Value::Number(b)) => (*a as f64).total_cmp(b),
+        (Value::Number(a), Value::Integer(b)) => a.total_cmp(&(*b as f64)),
+        (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+        (Value::String(a), Value::String(b)) => a.as_bytes().cmp(&b.as_bytes()),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
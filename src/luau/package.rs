@@ -1,14 +1,16 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::CStr;
 use std::fmt::Write;
-use std::os::raw::c_int;
+use std::os::raw::{c_char, c_int};
 use std::path::{PathBuf, MAIN_SEPARATOR_STR};
 use std::string::String as StdString;
-use std::{env, fs};
+use std::{env, fs, ptr};
 
 use crate::chunk::ChunkMode;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::state::Lua;
 use crate::table::Table;
+use crate::util::{get_internal_metatable, WrappedFailure};
 use crate::value::{IntoLua, Value};
 
 #[cfg(unix)]
@@ -117,15 +119,19 @@ unsafe extern "C-unwind" fn lua_require(state: *mut ffi::lua_State) -> c_int {
     ffi::lua_settop(state, 1);
     let name = ffi::luaL_checkstring(state, 1);
     ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADED")); // _LOADED is at index 2
+    ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADING")); // _LOADING is at index 3
+    let depth = ffi::lua_objlen(state, 3) as c_int;
     if ffi::lua_rawgetfield(state, 2, name) != ffi::LUA_TNIL {
+        record_dependency(state, depth, name);
         return 1; // module is already loaded
     }
     ffi::lua_pop(state, 1); // remove nil
+    check_cyclic_require(state, depth, name);
 
     // load the module
     let err_buf = ffi::lua_newuserdata_t::<StdString>(state);
     err_buf.write(StdString::new());
-    ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADERS")); // _LOADERS is at index 3
+    ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADERS")); // _LOADERS is at index 5
     for i in 1.. {
         if ffi::lua_rawgeti(state, -1, i) == ffi::LUA_TNIL {
             // no more loaders?
@@ -154,8 +160,19 @@ unsafe extern "C-unwind" fn lua_require(state: *mut ffi::lua_State) -> c_int {
     ffi::lua_pushvalue(state, 1); // name is 1st argument to module loader
     ffi::lua_rotate(state, -2, 1); // loader data <-> name
 
+    // Mark the module as being loaded. The loader is called in protected mode to unmark
+    // the module even if it fails.
+    record_dependency(state, depth, name);
+    ffi::lua_pushvalue(state, 1);
+    ffi::lua_rawseti(state, 3, depth + 1);
+
     // stack: ...; loader function; module name; loader data
-    ffi::lua_call(state, 2, 1);
+    let status = ffi::lua_pcall(state, 2, 1, 0);
+    ffi::lua_pushnil(state);
+    ffi::lua_rawseti(state, 3, depth + 1);
+    if status != ffi::LUA_OK {
+        ffi::lua_error(state);
+    }
     // stack: ...; result from loader function
     if ffi::lua_isnil(state, -1) != 0 {
         ffi::lua_pop(state, 1);
@@ -166,6 +183,138 @@ unsafe extern "C-unwind" fn lua_require(state: *mut ffi::lua_State) -> c_int {
     1
 }
 
+// Adds `name` to the module graph as a dependency of the module currently being loaded (if any).
+//
+// The `_LOADING` table (list of modules being loaded) must be at index 3.
+// The graph is stored in the registry as `_MODULE_GRAPH[module] = { [dependency] = true }`.
+unsafe fn record_dependency(state: *mut ffi::lua_State, depth: c_int, name: *const c_char) {
+    ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_MODULE_GRAPH"));
+    ffi::luaL_getsubtable(state, -1, name);
+    ffi::lua_pop(state, 1);
+    if depth > 0 {
+        ffi::lua_rawgeti(state, 3, depth); // parent module name
+        ffi::luaL_getsubtable(state, -2, ffi::lua_tostring(state, -1));
+        ffi::lua_pushboolean(state, 1);
+        ffi::lua_setfield(state, -2, name);
+        ffi::lua_pop(state, 2);
+    }
+    ffi::lua_pop(state, 1);
+}
+
+// Raises `Error::CyclicRequire` if `name` is currently being loaded.
+//
+// The `_LOADING` table (list of modules being loaded) must be at index 3.
+unsafe fn check_cyclic_require(state: *mut ffi::lua_State, depth: c_int, name: *const c_char) {
+    let cname = CStr::from_ptr(name);
+    let mut start = None;
+    for i in 1..=depth {
+        ffi::lua_rawgeti(state, 3, i);
+        let is_same = CStr::from_ptr(ffi::lua_tostring(state, -1)) == cname;
+        ffi::lua_pop(state, 1);
+        if is_same {
+            start = Some(i);
+            break;
+        }
+    }
+    let Some(start) = start else {
+        return;
+    };
+    record_dependency(state, depth, name);
+
+    let mut chain = Vec::with_capacity((depth - start + 2) as usize);
+    for i in start..=depth {
+        ffi::lua_rawgeti(state, 3, i);
+        chain.push(
+            CStr::from_ptr(ffi::lua_tostring(state, -1))
+                .to_string_lossy()
+                .into_owned(),
+        );
+        ffi::lua_pop(state, 1);
+    }
+    chain.push(cname.to_string_lossy().into_owned());
+
+    let ud = WrappedFailure::new_userdata(state);
+    ptr::write(ud, WrappedFailure::Error(Error::CyclicRequire { chain }));
+    get_internal_metatable::<WrappedFailure>(state);
+    ffi::lua_setmetatable(state, -2);
+    ffi::lua_error(state);
+}
+
+// Removes the modules (and their dependencies) from the module graph
+pub(crate) fn forget_modules(lua: &Lua, names: &[StdString]) -> Result<()> {
+    if let Some(graph) = lua.named_registry_value::<Option<Table>>("_MODULE_GRAPH")? {
+        for name in names {
+            graph.raw_set(name.as_str(), Value::Nil)?;
+        }
+    }
+    Ok(())
+}
+
+/// Dependency graph of modules loaded using `require`.
+///
+/// The graph is built incrementally as modules are required and can be used for diagnostics or
+/// to find modules that must be reloaded when one of their dependencies changes.
+///
+/// Returned by [`Lua::module_graph`].
+///
+/// Requires `feature = "luau"`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleGraph {
+    dependencies: BTreeMap<StdString, BTreeSet<StdString>>,
+}
+
+impl ModuleGraph {
+    pub(crate) fn from_registry(lua: &Lua) -> Result<Self> {
+        let mut graph = ModuleGraph::default();
+        if let Some(table) = lua.named_registry_value::<Option<Table>>("_MODULE_GRAPH")? {
+            table.for_each(|name: StdString, deps: Table| {
+                let mut dependencies = BTreeSet::new();
+                deps.for_each(|dep: StdString, _: Value| {
+                    dependencies.insert(dep);
+                    Ok(())
+                })?;
+                graph.dependencies.insert(name, dependencies);
+                Ok(())
+            })?;
+        }
+        Ok(graph)
+    }
+
+    /// Returns an iterator over names of all required modules.
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.dependencies.keys().map(|s| s.as_str())
+    }
+
+    /// Returns an iterator over names of modules directly required by the module `name`.
+    pub fn dependencies(&self, name: &str) -> impl Iterator<Item = &str> {
+        (self.dependencies.get(name).into_iter()).flat_map(|deps| deps.iter().map(|s| s.as_str()))
+    }
+
+    /// Returns an iterator over names of modules that directly require the module `name`.
+    pub fn dependents<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        (self.dependencies.iter())
+            .filter(move |(_, deps)| deps.contains(name))
+            .map(|(module, _)| module.as_str())
+    }
+
+    /// Returns names of all modules that directly or indirectly require the module `name`.
+    ///
+    /// These are the modules that must be reloaded when the module `name` is changed.
+    pub fn transitive_dependents<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        let mut result = Vec::new();
+        let mut queue = vec![name];
+        while let Some(name) = queue.pop() {
+            for module in self.dependents(name) {
+                if !result.contains(&module) {
+                    result.push(module);
+                    queue.push(module);
+                }
+            }
+        }
+        result
+    }
+}
+
 /// Searches for the given `name` in the given `path`.
 ///
 /// `path` is a string containing a sequence of templates separated by semicolons.
@@ -198,6 +347,23 @@ fn lua_loader(lua: &Lua, modname: StdString) -> Result<Value> {
     }?;
     let search_path = package.get::<StdString>("path").unwrap_or_default();
 
+    let vfs = lua.lock().vfs();
+    if let Some(vfs) = vfs {
+        if let Ok(file_path) = crate::vfs::search_path(&*vfs, &modname, &search_path) {
+            return match vfs.read(file_path.as_ref()) {
+                Ok(buf) => lua
+                    .load(&buf)
+                    .set_name(format!("={file_path}"))
+                    .set_mode(ChunkMode::Text)
+                    .set_inherit_environment(false)
+                    .into_function()
+                    .map(Value::Function),
+                Err(err) => format!("cannot open '{file_path}': {err}").into_lua(lua),
+            };
+        }
+        return Ok(Value::Nil);
+    }
+
     if let Some(file_path) = package_searchpath(&modname, &search_path, false) {
         match fs::read(&file_path) {
             Ok(buf) => {
@@ -205,6 +371,7 @@ fn lua_loader(lua: &Lua, modname: StdString) -> Result<Value> {
                     .load(&buf)
                     .set_name(format!("={}", file_path.display()))
                     .set_mode(ChunkMode::Text)
+                    .set_inherit_environment(false)
                     .into_function()
                     .map(Value::Function);
             }
@@ -94,6 +94,22 @@ macro_rules! require_module_feature {
     };
 }
 
+#[cfg(feature = "plugin")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! require_plugin_feature {
+    () => {};
+}
+
+#[cfg(not(feature = "plugin"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! require_plugin_feature {
+    () => {
+        compile_error!("Feature `plugin` must be enabled in the `mlua` crate");
+    };
+}
+
 macro_rules! protect_lua {
     ($state:expr, $nargs:expr, $nresults:expr, $f:expr) => {
         crate::util::protect_lua_closure($state, $nargs, $nresults, $f)
@@ -114,3 +130,96 @@ macro_rules! protect_lua {
         crate::util::protect_lua_call($state, $nargs, do_call)
     }};
 }
+
+/// Registers a generic userdata type for a closed set of type parameters.
+///
+/// For every parameter `P` of `Type<P1, P2, ...>`, the userdata type `Type<P>` is registered
+/// (using its [`UserData`] implementation) under the name `Type_P` (e.g. `MyVec_f32`). Type
+/// parameters must be plain identifiers.
+///
+/// A parameter can be followed by `=> [To1, To2, ...]` to declare conversions to other
+/// instantiations: a method `to_To` (e.g. `to_f64`) is added to `Type<P>`, which converts a clone
+/// of the value using `From<Type<P>> for Type<To>`.
+///
+/// The macro evaluates to `Result<Table>` with a Lua-side factory: the table maps parameter names
+/// to [proxy] objects giving access to static functions (e.g. `MyVec.f32.new()`), and calling it
+/// as `MyVec("f32", ...)` forwards the arguments to the `new` function of the named instantiation.
+///
+/// # Examples
+///
+/// ```
+/// use mlua::{Lua, Result, UserData, UserDataMethods};
+///
+/// #[derive(Clone)]
+/// struct MyVec<T>(Vec<T>);
+///
+/// impl<T: mlua::FromLua + mlua::IntoLua + Clone + mlua::MaybeSend + 'static> UserData for MyVec<T> {
+///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+///         methods.add_function("new", |_, items: Vec<T>| Ok(MyVec(items)));
+///         methods.add_method("get", |_, this, i: usize| Ok(this.0.get(i - 1).cloned()));
+///     }
+/// }
+///
+/// impl From<MyVec<i32>> for MyVec<f64> {
+///     fn from(v: MyVec<i32>) -> Self {
+///         MyVec(v.0.into_iter().map(f64::from).collect())
+///     }
+/// }
+///
+/// fn main() -> Result<()> {
+///     let lua = Lua::new();
+///     let factory = mlua::register_generic!(lua, MyVec<f64, i32 => [f64]>)?;
+///     lua.globals().set("MyVec", factory)?;
+///     lua.load(r#"
+///         local v = MyVec("i32", {1, 2, 3})
+///         assert(v:to_f64():get(2) == 2.0)
+///         assert(MyVec.f64.new({0.5}):get(1) == 0.5)
+///     "#).exec()
+/// }
+/// ```
+///
+/// [`UserData`]: crate::UserData
+/// [proxy]: crate::Lua::create_proxy
+#[macro_export]
+macro_rules! register_generic {
+    ($lua:expr, $ty:ident < $($param:ident $(=> [$($to:ident),* $(,)?])?),+ $(,)? >) => {
+        (|| -> $crate::Result<$crate::Table> {
+            let lua: &$crate::Lua = &$lua;
+            let factory = lua.create_table()?;
+            $(
+                lua.register_userdata_type::<$ty<$param>>(|registry| {
+                    <$ty<$param> as $crate::UserData>::register(registry);
+                    registry.set_type_name(concat!(stringify!($ty), "_", stringify!($param)));
+                    $($(
+                        $crate::UserDataMethods::add_method(
+                            registry,
+                            concat!("to_", stringify!($to)),
+                            |_, this, ()| {
+                                let value = ::std::clone::Clone::clone(this);
+                                Ok(<$ty<$to> as ::std::convert::From<$ty<$param>>>::from(value))
+                            },
+                        );
+                    )*)?
+                })?;
+                factory.raw_set(stringify!($param), lua.create_proxy::<$ty<$param>>()?)?;
+            )+
+
+            let metatable = lua.create_table()?;
+            let call = lua.create_function(
+                |_, (factory, name, args): ($crate::Table, $crate::String, $crate::MultiValue)| {
+                    match factory.raw_get::<::std::option::Option<$crate::AnyUserData>>(&name)? {
+                        Some(proxy) => $crate::ObjectLike::call_function::<$crate::MultiValue>(&proxy, "new", args),
+                        None => Err($crate::Error::runtime(format!(
+                            "unknown type parameter '{}' of '{}'",
+                            name.to_string_lossy(),
+                            stringify!($ty),
+                        ))),
+                    }
+                },
+            )?;
+            metatable.raw_set("__call", call)?;
+            factory.set_metatable(Some(metatable));
+            Ok(factory)
+        })()
+    };
+}
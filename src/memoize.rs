@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use rustc_hash::FxHashMap;
+
+use crate::error::Result;
+use crate::function::Function;
+use crate::hash::{HashAlgorithm, HashOptions};
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::{MultiValue, Nil, Value};
+
+/// Options for [`Function::memoized`].
+///
+/// [`Function::memoized`]: crate::Function::memoized
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct MemoizeOptions {
+    /// Maximum number of cached results.
+    ///
+    /// When the cache is full, the least recently used result is evicted.
+    ///
+    /// Default: **256**
+    pub capacity: usize,
+
+    /// Options used to hash the function arguments.
+    ///
+    /// Default: [`HashOptions::new()`]
+    pub hash_options: HashOptions,
+}
+
+impl Default for MemoizeOptions {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl MemoizeOptions {
+    /// Returns a new instance of [`MemoizeOptions`] with default parameters.
+    pub const fn new() -> Self {
+        MemoizeOptions {
+            capacity: 256,
+            hash_options: HashOptions::new(),
+        }
+    }
+
+    /// Sets [`capacity`] option.
+    ///
+    /// [`capacity`]: #structfield.capacity
+    #[must_use]
+    pub const fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets [`hash_options`] option.
+    ///
+    /// [`hash_options`]: #structfield.hash_options
+    #[must_use]
+    pub const fn hash_options(mut self, options: HashOptions) -> Self {
+        self.hash_options = options;
+        self
+    }
+}
+
+// Tracks the order in which cached results were used
+#[derive(Default)]
+struct LruOrder {
+    tick: u64,
+    // Cache key -> last use tick
+    keys: FxHashMap<Integer, u64>,
+    // Last use tick -> cache key
+    ticks: BTreeMap<u64, Integer>,
+}
+
+impl LruOrder {
+    fn touch(&mut self, key: Integer) {
+        if let Some(tick) = self.keys.insert(key, self.tick) {
+            self.ticks.remove(&tick);
+        }
+        self.ticks.insert(self.tick, key);
+        self.tick += 1;
+    }
+
+    // Returns the least recently used key if there are more than `capacity` keys
+    fn evict(&mut self, capacity: usize) -> Option<Integer> {
+        if self.keys.len() <= capacity {
+            return None;
+        }
+        let (_, key) = self.ticks.pop_first()?;
+        self.keys.remove(&key);
+        Some(key)
+    }
+}
+
+pub(crate) fn memoize(lua: &Lua, func: Function, options: MemoizeOptions) -> Result<Function> {
+    // Cached results are stored in a Lua table as sequences with the `n` field
+    let cache = lua.create_table()?;
+    let order = RefCell::new(LruOrder::default());
+
+    lua.create_function(move |lua, args: MultiValue| {
+        let hash = crate::hash::hash_values(args.iter(), HashAlgorithm::Fnv1a64, options.hash_options)?;
+        let key = hash as Integer;
+
+        if let Some(results) = cache.raw_get::<Option<Table>>(key)? {
+            order.borrow_mut().touch(key);
+            return unpack(&results);
+        }
+
+        // The function can call the memoized function recursively, so no borrows are held here
+        let results = func.call::<MultiValue>(args)?;
+        if options.capacity > 0 {
+            cache.raw_set(key, pack(lua, &results)?)?;
+            let evicted = {
+                let mut order = order.borrow_mut();
+                order.touch(key);
+                order.evict(options.capacity)
+            };
+            if let Some(evicted) = evicted {
+                cache.raw_set(evicted, Nil)?;
+            }
+        }
+        Ok(results)
+    })
+}
+
+fn pack(lua: &Lua, values: &MultiValue) -> Result<Table> {
+    let table = lua.create_table_with_capacity(values.len(), 1)?;
+    for (i, value) in values.iter().enumerate() {
+        table.raw_set(i + 1, value)?;
+    }
+    table.raw_set("n", values.len())?;
+    Ok(table)
+}
+
+fn unpack(table: &Table) -> Result<MultiValue> {
+    let n = table.raw_get::<usize>("n")?;
+    (1..=n).map(|i| table.raw_get::<Value>(i)).collect()
+}
@@ -1,6 +1,7 @@
 use std::alloc::{self, Layout};
 use std::os::raw::c_void;
 use std::ptr;
+use std::string::String as StdString;
 
 pub(crate) static ALLOCATOR: ffi::lua_Alloc = allocator;
 
@@ -15,6 +16,10 @@ pub(crate) struct MemoryState {
     // Indicates that the memory limit was reached on the last allocation.
     #[cfg(feature = "luau")]
     limit_reached: bool,
+    // Memory attributed to each source (chunk name), if tracking is enabled.
+    // Source id `n` refers to `sources[n - 1]`, id `0` means unattributed memory.
+    sources: Option<Vec<(StdString, isize)>>,
+    current_source: u32,
 }
 
 impl MemoryState {
@@ -50,6 +55,47 @@ impl MemoryState {
         prev_limit as usize
     }
 
+    // Enables attribution of allocations to sources.
+    // Must be called before creating a Lua state, as it changes the layout of allocations.
+    #[inline]
+    pub(crate) fn enable_source_tracking(&mut self) {
+        self.sources = Some(Vec::new());
+    }
+
+    #[inline]
+    pub(crate) fn is_tracking_sources(&self) -> bool {
+        self.sources.is_some()
+    }
+
+    pub(crate) fn memory_by_source(&self) -> impl Iterator<Item = (&str, usize)> {
+        let sources = self.sources.as_deref().unwrap_or_default();
+        sources.iter().map(|(name, size)| (name.as_str(), *size as usize))
+    }
+
+    // Sets the source to attribute new allocations to, returning the previous source id.
+    fn set_current_source(&mut self, name: StdString) -> u32 {
+        let Some(sources) = self.sources.as_mut() else {
+            return 0;
+        };
+        let id = match sources.iter().position(|(n, _)| *n == name) {
+            Some(pos) => pos + 1,
+            None => {
+                sources.push((name, 0));
+                sources.len()
+            }
+        };
+        std::mem::replace(&mut self.current_source, id as u32)
+    }
+
+    #[inline]
+    fn attribute(&mut self, source: u32, size: isize) {
+        if let Some(sources) = self.sources.as_mut() {
+            if source > 0 {
+                sources[source as usize - 1].1 += size;
+            }
+        }
+    }
+
     // This function is used primarily for calling `lua_pushcfunction` in lua5.1/jit/luau
     // to bypass the memory limit (if set).
     #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
@@ -80,6 +126,37 @@ impl MemoryState {
     }
 }
 
+/// Attributes allocations to a source until dropped, restoring the previous source afterwards.
+pub(crate) struct MemorySourceGuard {
+    mem_state: *mut MemoryState,
+    prev_source: u32,
+}
+
+impl MemorySourceGuard {
+    // Returns `None` if source tracking is disabled or `source` returns `None`.
+    // The `source` closure is called only when tracking is enabled.
+    pub(crate) unsafe fn new(
+        state: *mut ffi::lua_State,
+        source: impl FnOnce() -> Option<StdString>,
+    ) -> Option<Self> {
+        let mem_state = MemoryState::get(state);
+        if mem_state.is_null() || !(*mem_state).is_tracking_sources() {
+            return None;
+        }
+        let prev_source = (*mem_state).set_current_source(source()?);
+        Some(MemorySourceGuard {
+            mem_state,
+            prev_source,
+        })
+    }
+}
+
+impl Drop for MemorySourceGuard {
+    fn drop(&mut self) {
+        unsafe { (*self.mem_state).current_source = self.prev_source };
+    }
+}
+
 unsafe extern "C-unwind" fn allocator(
     extra: *mut c_void,
     ptr: *mut c_void,
@@ -93,18 +170,29 @@ unsafe extern "C-unwind" fn allocator(
         mem_state.limit_reached = false;
     }
 
+    // When tracking sources, every allocation is prefixed with a header holding the source id
+    let header = if mem_state.is_tracking_sources() {
+        ffi::SYS_MIN_ALIGN
+    } else {
+        0
+    };
+
     if nsize == 0 {
         // Free memory
         if !ptr.is_null() {
-            let layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
-            alloc::dealloc(ptr as *mut u8, layout);
+            let base = (ptr as *mut u8).sub(header);
+            if header > 0 {
+                mem_state.attribute(*(base as *const u32), -(osize as isize));
+            }
+            let layout = Layout::from_size_align_unchecked(osize + header, ffi::SYS_MIN_ALIGN);
+            alloc::dealloc(base, layout);
             mem_state.used_memory -= osize as isize;
         }
         return ptr::null_mut();
     }
 
     // Do not allocate more than isize::MAX
-    if nsize > isize::MAX as usize {
+    if nsize > isize::MAX as usize - header {
         return ptr::null_mut();
     }
 
@@ -126,22 +214,32 @@ unsafe extern "C-unwind" fn allocator(
 
     if ptr.is_null() {
         // Allocate new memory
-        let new_layout = match Layout::from_size_align(nsize, ffi::SYS_MIN_ALIGN) {
+        let new_layout = match Layout::from_size_align(nsize + header, ffi::SYS_MIN_ALIGN) {
             Ok(layout) => layout,
             Err(_) => return ptr::null_mut(),
         };
-        let new_ptr = alloc::alloc(new_layout) as *mut c_void;
+        let new_ptr = alloc::alloc(new_layout);
         if new_ptr.is_null() {
             alloc::handle_alloc_error(new_layout);
         }
-        return new_ptr;
+        if header > 0 {
+            let source = mem_state.current_source;
+            *(new_ptr as *mut u32) = source;
+            mem_state.attribute(source, nsize as isize);
+        }
+        return new_ptr.add(header) as *mut c_void;
     }
 
     // Reallocate memory
-    let old_layout = Layout::from_size_align_unchecked(osize, ffi::SYS_MIN_ALIGN);
-    let new_ptr = alloc::realloc(ptr as *mut u8, old_layout, nsize) as *mut c_void;
+    // The memory stays attributed to the source that originally allocated it
+    let base = (ptr as *mut u8).sub(header);
+    let old_layout = Layout::from_size_align_unchecked(osize + header, ffi::SYS_MIN_ALIGN);
+    let new_ptr = alloc::realloc(base, old_layout, nsize + header);
     if new_ptr.is_null() {
         alloc::handle_alloc_error(old_layout);
     }
-    new_ptr
+    if header > 0 {
+        mem_state.attribute(*(new_ptr as *const u32), mem_diff);
+    }
+    new_ptr.add(header) as *mut c_void
 }
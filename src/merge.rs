@@ -0,0 +1,141 @@
+use std::fmt;
+use std::os::raw::c_void;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::error::Result;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::{MaybeSend, XRc};
+use crate::value::Value;
+
+/// Strategy to resolve conflicting keys in [`Table::merge`].
+///
+/// Keys where both values are tables are always merged recursively, the strategy decides what
+/// happens with other conflicting values.
+///
+/// [`Table::merge`]: crate::Table::merge
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// Values from the other table replace existing values.
+    #[default]
+    Overwrite,
+    /// Existing values are kept, only missing keys are added.
+    KeepExisting,
+    /// Like [`Overwrite`], but arrays (non-empty sequences) in both tables are concatenated
+    /// instead of merged by index.
+    ///
+    /// [`Overwrite`]: #variant.Overwrite
+    ConcatArrays,
+    /// Conflicting values are resolved by a callback, see [`MergeStrategy::custom`].
+    Custom(MergeResolver),
+}
+
+/// A callback resolving conflicting values in [`MergeStrategy::Custom`].
+#[derive(Clone)]
+pub struct MergeResolver(ResolverCallback);
+
+#[cfg(feature = "send")]
+type ResolverCallback = XRc<dyn Fn(&Lua, &Value, Value, Value) -> Result<Value> + Send>;
+
+#[cfg(not(feature = "send"))]
+type ResolverCallback = XRc<dyn Fn(&Lua, &Value, Value, Value) -> Result<Value>>;
+
+impl fmt::Debug for MergeResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MergeResolver({:p})", XRc::as_ptr(&self.0))
+    }
+}
+
+impl MergeStrategy {
+    /// Creates a strategy resolving conflicting values with the provided callback.
+    ///
+    /// The callback receives the key, the existing value and the value from the other table, and
+    /// returns the value to store (returning nil removes the key).
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&Lua, &Value, Value, Value) -> Result<Value> + MaybeSend + 'static,
+    {
+        MergeStrategy::Custom(MergeResolver(XRc::new(f)))
+    }
+}
+
+// Deep merges `source` into `target`
+pub(crate) fn merge(target: &Table, source: &Table, strategy: &MergeStrategy) -> Result<()> {
+    let lua = target.0.lua.upgrade();
+    let mut merger = Merger {
+        lua: &lua,
+        strategy,
+        merging: FxHashSet::default(),
+        copies: FxHashMap::default(),
+    };
+    merger.merge(target, source)
+}
+
+struct Merger<'a> {
+    lua: &'a Lua,
+    strategy: &'a MergeStrategy,
+    // Source tables currently being merged (to skip cyclic references)
+    merging: FxHashSet<*const c_void>,
+    // Copies of source tables stored in the target (to preserve shared and cyclic references)
+    copies: FxHashMap<*const c_void, Table>,
+}
+
+impl Merger<'_> {
+    fn merge(&mut self, target: &Table, source: &Table) -> Result<()> {
+        if matches!(self.strategy, MergeStrategy::ConcatArrays) && is_array(target) && is_array(source) {
+            // The length is taken first, as the target may be the source
+            for i in 1..=source.raw_len() {
+                let value = self.incoming(source.raw_get(i)?)?;
+                target.raw_push(value)?;
+            }
+            return Ok(());
+        }
+
+        // Collect first, as the target may be (or contain) the source
+        let pairs = source.pairs::<Value, Value>().collect::<Result<Vec<_>>>()?;
+        self.merging.insert(source.to_pointer());
+        for (key, value) in pairs {
+            let existing = target.raw_get::<Value>(&key)?;
+            let value = match (existing, value) {
+                (Value::Nil, value) => self.incoming(value)?,
+                (Value::Table(existing), Value::Table(value)) => {
+                    if !self.merging.contains(&value.to_pointer()) {
+                        self.merge(&existing, &value)?;
+                    }
+                    continue;
+                }
+                (existing, value) => match self.strategy {
+                    MergeStrategy::KeepExisting => continue,
+                    MergeStrategy::Custom(resolver) => (resolver.0)(self.lua, &key, existing, value)?,
+                    _ => self.incoming(value)?,
+                },
+            };
+            target.raw_set(key, value)?;
+        }
+        self.merging.remove(&source.to_pointer());
+        Ok(())
+    }
+
+    // Prepares a value from the source to be stored in the target, tables are copied
+    fn incoming(&mut self, value: Value) -> Result<Value> {
+        let Value::Table(table) = value else {
+            return Ok(value);
+        };
+        if let Some(copy) = self.copies.get(&table.to_pointer()) {
+            return Ok(Value::Table(copy.clone()));
+        }
+        let copy = self.lua.create_table()?;
+        copy.set_metatable(table.metatable());
+        self.copies.insert(table.to_pointer(), copy.clone());
+        self.merge(&copy, &table)?;
+        Ok(Value::Table(copy))
+    }
+}
+
+// Checks that the table is a non-empty sequence
+fn is_array(table: &Table) -> bool {
+    let len = table.raw_len();
+    len > 0 && table.pairs::<Value, Value>().count() == len
+}
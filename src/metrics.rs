@@ -0,0 +1,282 @@
+use std::cmp::Reverse;
+use std::fmt::Write as _;
+use std::string::String as StdString;
+
+use bstr::BString;
+use rustc_hash::FxHashMap;
+
+#[cfg(not(feature = "luau"))]
+use {crate::error::Result, crate::memory::MemoryState, std::mem::size_of, std::os::raw::c_int, std::ptr};
+
+/// Kind of a metric in [`MetricsSnapshot`].
+///
+/// Requires `feature = "metrics"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Monotonically increasing value.
+    Counter,
+    /// Value that can go up and down.
+    Gauge,
+}
+
+impl MetricKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+/// A single metric from [`MetricsSnapshot`].
+///
+/// Requires `feature = "metrics"`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Metric {
+    /// Metric name (without prefix), following the Prometheus naming conventions.
+    pub name: &'static str,
+    /// Short description of the metric.
+    pub help: &'static str,
+    /// Metric kind.
+    pub kind: MetricKind,
+    /// Metric value.
+    pub value: u64,
+}
+
+/// Point-in-time metrics of a Lua state, returned by [`Lua::metrics_snapshot`].
+///
+/// Counters are accumulated since the Lua state creation and gauges reflect the current values.
+/// The snapshot can be exported to a metrics system using [`MetricsSnapshot::iter`] or rendered
+/// in the Prometheus text format using [`MetricsSnapshot::to_prometheus`].
+///
+/// Requires `feature = "metrics"`
+///
+/// [`Lua::metrics_snapshot`]: crate::Lua::metrics_snapshot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MetricsSnapshot {
+    /// Number of completed garbage collection cycles (counter).
+    ///
+    /// Always `0` for Luau, which does not support finalizers.
+    pub gc_cycles: u64,
+    /// Memory currently used by the Lua state in bytes (gauge).
+    pub used_memory: usize,
+    /// Number of Rust callbacks invoked from Lua (counter).
+    pub callbacks_invoked: u64,
+    /// Number of errors (including panics) raised by Rust callbacks (counter).
+    pub errors_raised: u64,
+    /// Number of threads (coroutines) created by Rust (counter).
+    pub threads_created: u64,
+    /// Number of threads (coroutines) driving in-progress async calls (gauge).
+    pub active_threads: usize,
+    /// Number of Lua strings created from Rust (counter).
+    ///
+    /// Includes strings created by [`Lua::create_string`] and by converting Rust values to Lua.
+    ///
+    /// [`Lua::create_string`]: crate::Lua::create_string
+    pub strings_created: u64,
+    /// Total size of Lua strings created from Rust in bytes (counter).
+    pub string_bytes: u64,
+}
+
+impl MetricsSnapshot {
+    /// Returns an iterator over all metrics in the snapshot.
+    pub fn iter(&self) -> impl Iterator<Item = Metric> {
+        let metric = |name, help, kind, value| Metric {
+            name,
+            help,
+            kind,
+            value,
+        };
+        [
+            metric(
+                "gc_cycles_total",
+                "Number of completed garbage collection cycles.",
+                MetricKind::Counter,
+                self.gc_cycles,
+            ),
+            metric(
+                "used_memory_bytes",
+                "Memory used by the Lua state in bytes.",
+                MetricKind::Gauge,
+                self.used_memory as u64,
+            ),
+            metric(
+                "callbacks_total",
+                "Number of Rust callbacks invoked from Lua.",
+                MetricKind::Counter,
+                self.callbacks_invoked,
+            ),
+            metric(
+                "errors_total",
+                "Number of errors raised by Rust callbacks.",
+                MetricKind::Counter,
+                self.errors_raised,
+            ),
+            metric(
+                "threads_created_total",
+                "Number of threads created by Rust.",
+                MetricKind::Counter,
+                self.threads_created,
+            ),
+            metric(
+                "active_threads",
+                "Number of threads driving in-progress async calls.",
+                MetricKind::Gauge,
+                self.active_threads as u64,
+            ),
+            metric(
+                "strings_created_total",
+                "Number of Lua strings created by Rust.",
+                MetricKind::Counter,
+                self.strings_created,
+            ),
+            metric(
+                "string_bytes_total",
+                "Size of Lua strings created by Rust in bytes.",
+                MetricKind::Counter,
+                self.string_bytes,
+            ),
+        ]
+        .into_iter()
+    }
+
+    /// Renders the snapshot in the Prometheus text exposition format.
+    ///
+    /// Each metric name is prefixed with `prefix` followed by underscore (if `prefix` is not
+    /// empty).
+    pub fn to_prometheus(&self, prefix: &str) -> StdString {
+        let mut out = StdString::new();
+        for metric in self.iter() {
+            let name = match prefix {
+                "" => metric.name.to_string(),
+                _ => format!("{prefix}_{}", metric.name),
+            };
+            let _ = writeln!(out, "# HELP {name} {}", metric.help);
+            let _ = writeln!(out, "# TYPE {name} {}", metric.kind.as_str());
+            let _ = writeln!(out, "{name} {}", metric.value);
+        }
+        out
+    }
+}
+
+/// A string frequently created from Rust, returned by [`Lua::hot_strings`].
+///
+/// Requires `feature = "metrics"`
+///
+/// [`Lua::hot_strings`]: crate::Lua::hot_strings
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HotString {
+    /// Contents of the string.
+    pub content: BString,
+    /// Number of times the string was created.
+    pub count: u64,
+}
+
+impl HotString {
+    /// Returns the total size of the created copies of the string in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.count * self.content.len() as u64
+    }
+}
+
+// Maximum number of distinct strings tracked for `Lua::hot_strings`
+const HOT_STRINGS_LIMIT: usize = 10_000;
+
+// Metrics counters stored in the Lua state
+#[derive(Default)]
+pub(crate) struct Metrics {
+    // Points to the counter userdata kept in the registry
+    #[cfg(not(feature = "luau"))]
+    pub(crate) gc_cycles: Option<ptr::NonNull<u64>>,
+    pub(crate) callbacks_invoked: u64,
+    pub(crate) errors_raised: u64,
+    pub(crate) threads_created: u64,
+    pub(crate) active_threads: usize,
+    pub(crate) strings_created: u64,
+    pub(crate) string_bytes: u64,
+    // Number of creations per string contents (if enabled)
+    pub(crate) hot_strings: Option<FxHashMap<Box<[u8]>, u64>>,
+}
+
+impl Metrics {
+    pub(crate) fn record_string(&mut self, s: &[u8]) {
+        self.strings_created += 1;
+        self.string_bytes += s.len() as u64;
+        if let Some(hot_strings) = &mut self.hot_strings {
+            if let Some(count) = hot_strings.get_mut(s) {
+                *count += 1;
+            } else if hot_strings.len() < HOT_STRINGS_LIMIT {
+                hot_strings.insert(s.into(), 1);
+            }
+        }
+    }
+
+    // Returns `n` most frequently created strings (in descending order)
+    pub(crate) fn hot_strings(&self, n: usize) -> Vec<HotString> {
+        let Some(hot_strings) = &self.hot_strings else {
+            return Vec::new();
+        };
+        let mut strings = (hot_strings.iter())
+            .map(|(content, &count)| HotString {
+                content: BString::from(&**content),
+                count,
+            })
+            .collect::<Vec<_>>();
+        strings.sort_by_key(|s| Reverse((s.count, s.total_bytes())));
+        strings.truncate(n);
+        strings
+    }
+
+    pub(crate) fn gc_cycles(&self) -> u64 {
+        #[cfg(not(feature = "luau"))]
+        if let Some(counter) = self.gc_cycles {
+            return unsafe { *counter.as_ptr() };
+        }
+        0
+    }
+}
+
+// Sets up counting of completed GC cycles.
+//
+// A sentinel userdata without references is created with a finalizer that increments the counter
+// and creates a new sentinel, so the finalizer is called once per every GC cycle.
+// Returns pointer to the counter (stored in a userdata referenced from the registry).
+#[cfg(not(feature = "luau"))]
+pub(crate) unsafe fn init_gc_counter(state: *mut ffi::lua_State) -> Result<ptr::NonNull<u64>> {
+    unsafe extern "C-unwind" fn sentinel_gc(state: *mut ffi::lua_State) -> c_int {
+        let counter = ffi::lua_touserdata(state, ffi::lua_upvalueindex(1)) as *mut u64;
+        *counter += 1;
+        MemoryState::relax_limit_with(state, || {
+            ffi::lua_newuserdata(state, 0);
+            ffi::lua_pushvalue(state, ffi::lua_upvalueindex(2));
+            ffi::lua_setmetatable(state, -2);
+            ffi::lua_pop(state, 1);
+        });
+        0
+    }
+
+    let counter = protect_lua!(state, 0, 0, |state| {
+        let counter = ffi::lua_newuserdata(state, size_of::<u64>()) as *mut u64;
+        ptr::write(counter, 0);
+        ffi::lua_pushvalue(state, -1);
+        ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX);
+
+        // Sentinel metatable
+        ffi::lua_createtable(state, 0, 1);
+        ffi::lua_pushvalue(state, -2);
+        ffi::lua_pushvalue(state, -2);
+        ffi::lua_pushcclosure(state, sentinel_gc, 2);
+        ffi::lua_setfield(state, -2, cstr!("__gc"));
+
+        // First sentinel
+        ffi::lua_newuserdata(state, 0);
+        ffi::lua_insert(state, -2);
+        ffi::lua_setmetatable(state, -2);
+        ffi::lua_pop(state, 2);
+        counter
+    })?;
+    Ok(ptr::NonNull::new_unchecked(counter))
+}
@@ -0,0 +1,117 @@
+use std::mem;
+use std::os::raw::c_int;
+use std::string::String as StdString;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::state::{Lua, RawLua};
+use crate::types::{CallbackFn, CallbackMiddleware};
+use crate::util::{check_stack, ptr_to_lossy_str};
+use crate::value::{FromLuaMulti, IntoLuaMulti, MultiValue};
+
+/// Information about a Rust function call passed to the middleware set by
+/// [`Lua::set_callback_middleware`].
+///
+/// [`Lua::set_callback_middleware`]: crate::Lua::set_callback_middleware
+pub struct CallInfo<'a> {
+    lua: &'a Lua,
+    name: Option<StdString>,
+    args: &'a MultiValue,
+    started: Instant,
+}
+
+impl CallInfo<'_> {
+    /// Returns the Lua instance calling the function.
+    pub fn lua(&self) -> &Lua {
+        self.lua
+    }
+
+    /// Returns the name of the called function (as seen by the caller), if known.
+    ///
+    /// The name is taken from the debug information, e.g. the global or field name the function
+    /// was called by. Names are not available in Luau.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the number of arguments passed to the function.
+    pub fn arg_count(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Returns the arguments passed to the function.
+    pub fn args(&self) -> &MultiValue {
+        self.args
+    }
+
+    /// Returns the time elapsed since the call started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// The rest of the call chain passed to the middleware set by [`Lua::set_callback_middleware`].
+///
+/// [`Lua::set_callback_middleware`]: crate::Lua::set_callback_middleware
+pub struct Next<'a> {
+    lua: &'a RawLua,
+    func: &'a CallbackFn,
+    args: &'a MultiValue,
+}
+
+impl Next<'_> {
+    /// Calls the function with the original arguments and returns its results.
+    pub fn run(self) -> Result<MultiValue> {
+        unsafe {
+            let nargs = self.args.len() as c_int;
+            check_stack(self.lua.state(), nargs + 1)?;
+            for arg in self.args {
+                self.lua.push_value(arg)?;
+            }
+            let nresults = (self.func)(self.lua, nargs)?;
+            MultiValue::from_stack_multi(nresults, self.lua)
+        }
+    }
+}
+
+// Calls the Rust function (with arguments on the stack) through the middleware
+pub(crate) unsafe fn call_with_middleware(
+    lua: &RawLua,
+    nargs: c_int,
+    func: &CallbackFn,
+    middleware: &CallbackMiddleware,
+) -> Result<c_int> {
+    let started = Instant::now();
+    let name = function_name(lua);
+    let args = MultiValue::from_stack_multi(nargs, lua)?;
+    let info = CallInfo {
+        lua: lua.lua(),
+        name,
+        args: &args,
+        started,
+    };
+    let next = Next {
+        lua,
+        func,
+        args: &args,
+    };
+    let results = middleware(info, next)?;
+    results.push_into_stack_multi(lua)
+}
+
+// Returns name of the running function from the debug information
+unsafe fn function_name(lua: &RawLua) -> Option<StdString> {
+    let state = lua.state();
+    let mut ar: ffi::lua_Debug = mem::zeroed();
+    #[cfg(not(feature = "luau"))]
+    {
+        if ffi::lua_getstack(state, 0, &mut ar) == 0 || ffi::lua_getinfo(state, cstr!("n"), &mut ar) == 0 {
+            return None;
+        }
+    }
+    #[cfg(feature = "luau")]
+    if ffi::lua_getinfo(state, 0, cstr!("n"), &mut ar) == 0 {
+        return None;
+    }
+    ptr_to_lossy_str(ar.name).map(|name| name.into_owned())
+}
@@ -3,10 +3,10 @@ use std::ops::{Deref, DerefMut};
 use std::os::raw::c_int;
 use std::result::Result as StdResult;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::state::{Lua, RawLua};
 use crate::util::check_stack;
-use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil};
+use crate::value::{FromLua, FromLuaMulti, FromLuaTable, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
 
 /// Result is convertible to `MultiValue` following the common Lua idiom of returning the result
 /// on success, or in the case of an error, returning `nil` and an error message.
@@ -139,6 +139,41 @@ impl<T> Variadic<T> {
     }
 }
 
+impl<T> Variadic<T> {
+    /// Splits the values into two at the given index.
+    ///
+    /// Returns a newly allocated `Variadic` containing the values in the range `[at, len)`,
+    /// leaving the values in the range `[0, at)` in `self`. If `at` is greater than the number
+    /// of values, returns an empty `Variadic`.
+    pub fn split_off(&mut self, at: usize) -> Variadic<T> {
+        Variadic(self.0.split_off(at.min(self.0.len())))
+    }
+
+    /// Converts the `Variadic` into a vector of values.
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+
+    /// Returns a slice containing all the values.
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T> From<Vec<T>> for Variadic<T> {
+    #[inline]
+    fn from(values: Vec<T>) -> Self {
+        Variadic(values)
+    }
+}
+
+impl<T> From<Variadic<T>> for Vec<T> {
+    #[inline]
+    fn from(values: Variadic<T>) -> Self {
+        values.0
+    }
+}
+
 impl<T> Default for Variadic<T> {
     fn default() -> Variadic<T> {
         const { Variadic::new() }
@@ -190,6 +225,131 @@ impl<T: FromLua> FromLuaMulti for Variadic<T> {
             .collect::<Result<Vec<T>>>()
             .map(Variadic)
     }
+
+    #[inline]
+    fn from_lua_args(mut args: MultiValue, i: usize, to: Option<&str>, lua: &Lua) -> Result<Self> {
+        (args.drain(..).enumerate())
+            .map(|(j, arg)| T::from_lua_arg(arg, i + j, to, lua))
+            .collect::<Result<Vec<T>>>()
+            .map(Variadic)
+    }
+
+    // Values are converted directly from the stack, without collecting them into `MultiValue`
+    // (this is much faster for primitive types such as numbers)
+    #[inline]
+    unsafe fn from_stack_multi(nvals: c_int, lua: &RawLua) -> Result<Self> {
+        (0..nvals)
+            .map(|j| T::from_stack(-nvals + j, lua))
+            .collect::<Result<Vec<T>>>()
+            .map(Variadic)
+    }
+
+    #[inline]
+    unsafe fn from_stack_args(nargs: c_int, i: usize, to: Option<&str>, lua: &RawLua) -> Result<Self> {
+        (0..nargs)
+            .map(|j| T::from_stack_arg(-nargs + j, i + j as usize, to, lua))
+            .collect::<Result<Vec<T>>>()
+            .map(Variadic)
+    }
+}
+
+/// Wraps arguments that can be passed either by name or by position.
+///
+/// Using this type as a Rust callback argument allows the function to be called from Lua with a
+/// single table of named arguments, e.g. `f{width = 3, height = 4}`, or with positional arguments
+/// in the field declaration order, e.g. `f(3, 4)`. Missing arguments (or `nil` values) are taken
+/// from the [`Default`] implementation of `T`.
+///
+/// A single table argument without a metatable is always treated as a table of named arguments.
+/// Unknown names are rejected.
+///
+/// `T` must implement [`FromLuaTable`], usually using the derive macro.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "macros")]
+/// # fn main() -> mlua::Result<()> {
+/// use mlua::{FromLuaTable, Lua, NamedArgs};
+///
+/// #[derive(Default, FromLuaTable)]
+/// struct Rect {
+///     width: f64,
+///     height: f64,
+///     scale: Option<f64>,
+/// }
+///
+/// let lua = Lua::new();
+/// let area = lua.create_function(|_, NamedArgs(rect): NamedArgs<Rect>| {
+///     Ok(rect.width * rect.height * rect.scale.unwrap_or(1.0))
+/// })?;
+/// lua.globals().set("area", area)?;
+///
+/// assert_eq!(lua.load("area{width = 3, height = 4}").eval::<f64>()?, 12.0);
+/// assert_eq!(lua.load("area(3, 4, 2)").eval::<f64>()?, 24.0);
+/// assert_eq!(lua.load("area{height = 4}").eval::<f64>()?, 0.0);
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "macros"))]
+/// # fn main() {}
+/// ```
+///
+/// [`FromLuaTable`]: crate::FromLuaTable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NamedArgs<T>(pub T);
+
+impl<T> NamedArgs<T> {
+    /// Returns the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for NamedArgs<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for NamedArgs<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T: FromLuaTable> FromLuaMulti for NamedArgs<T> {
+    fn from_lua_multi(mut values: MultiValue, lua: &Lua) -> Result<Self> {
+        if values.len() == 1 {
+            if let Some(Value::Table(table)) = values.front() {
+                if table.metatable().is_none() {
+                    let Some(Value::Table(table)) = values.pop_front() else {
+                        unreachable!()
+                    };
+                    return T::from_lua_table(table, lua).map(NamedArgs);
+                }
+            }
+        }
+        T::from_lua_positional(values, lua).map(NamedArgs)
+    }
+
+    fn from_lua_args(args: MultiValue, i: usize, to: Option<&str>, lua: &Lua) -> Result<Self> {
+        Self::from_lua_multi(args, lua).map_err(|err| match err {
+            Error::BadArgument {
+                to: None,
+                pos,
+                name,
+                cause,
+            } => Error::BadArgument {
+                to: to.map(|s| s.to_string()),
+                pos: pos + i - 1,
+                name,
+                cause,
+            },
+            err => err,
+        })
+    }
 }
 
 macro_rules! impl_tuple {
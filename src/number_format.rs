@@ -0,0 +1,182 @@
+use std::fmt::Write as _;
+use std::string::String as StdString;
+
+use crate::error::Result;
+use crate::function::Function;
+use crate::state::Lua;
+use crate::value::{MultiValue, Value};
+
+/// Formatting of Lua numbers (floats) converted to strings, see [`Lua::set_number_format`].
+///
+/// Lua formats numbers using the C `printf` function with the `LUAI_NUMFFORMAT` format
+/// (`"%.14g"` by default), which depends on the Lua version, build options and the current C
+/// locale. This type provides the same functionality at runtime, with `.` always used as the
+/// decimal point.
+///
+/// Infinite values are formatted as `inf` and `-inf`, and NaN as `nan` (or `-nan`).
+///
+/// [`Lua::set_number_format`]: crate::Lua::set_number_format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NumberFormat {
+    /// Maximum number of significant digits (`1..=17`).
+    ///
+    /// If `None`, the shortest representation that converts back to the same number is used.
+    ///
+    /// Default: **14**
+    pub precision: Option<u8>,
+
+    /// Add `.0` suffix to floats with integral values, e.g. `1.0` instead of `1`.
+    ///
+    /// Default: **true**
+    pub float_suffix: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl NumberFormat {
+    /// Returns a new instance of [`NumberFormat`] matching the default Lua 5.4 formatting.
+    pub const fn new() -> Self {
+        NumberFormat {
+            precision: Some(14),
+            float_suffix: true,
+        }
+    }
+
+    /// Sets [`precision`] option.
+    ///
+    /// The value is clamped to the `1..=17` range.
+    ///
+    /// [`precision`]: #structfield.precision
+    #[must_use]
+    pub const fn precision(mut self, precision: Option<u8>) -> Self {
+        self.precision = match precision {
+            Some(0) => Some(1),
+            Some(p) if p > 17 => Some(17),
+            p => p,
+        };
+        self
+    }
+
+    /// Sets [`float_suffix`] option.
+    ///
+    /// [`float_suffix`]: #structfield.float_suffix
+    #[must_use]
+    pub const fn float_suffix(mut self, enabled: bool) -> Self {
+        self.float_suffix = enabled;
+        self
+    }
+
+    /// Formats a number according to the options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::NumberFormat;
+    /// let format = NumberFormat::new();
+    /// assert_eq!(format.format(0.1 + 0.2), "0.3");
+    /// assert_eq!(format.format(2.0), "2.0");
+    /// assert_eq!(format.format(1e15), "1e+15");
+    ///
+    /// let format = NumberFormat::new().precision(None).float_suffix(false);
+    /// assert_eq!(format.format(0.1 + 0.2), "0.30000000000000004");
+    /// assert_eq!(format.format(2.0), "2");
+    /// ```
+    pub fn format(&self, n: f64) -> StdString {
+        if n.is_nan() {
+            return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+        }
+        if n.is_infinite() {
+            return if n < 0.0 { "-inf" } else { "inf" }.to_string();
+        }
+
+        // Get the significant digits and decimal exponent
+        let sci = match self.precision {
+            Some(p) => format!("{:.*e}", (p.clamp(1, 17) - 1) as usize, n),
+            None => format!("{n:e}"),
+        };
+        let (mantissa, exp) = sci.split_once('e').expect("exponent");
+        let exp: i32 = exp.parse().expect("valid exponent");
+        let (sign, mantissa) = match mantissa.strip_prefix('-') {
+            Some(mantissa) => ("-", mantissa),
+            None => ("", mantissa),
+        };
+        let digits = mantissa.replace('.', "");
+        let digits = match digits.trim_end_matches('0') {
+            "" => "0",
+            digits => digits,
+        };
+
+        // The same rules as `%g` uses to choose between fixed and scientific notation
+        let max_exp = self.precision.map(|p| p.clamp(1, 17) as i32).unwrap_or(17);
+        let mut s = StdString::from(sign);
+        if exp < -4 || exp >= max_exp {
+            s.push_str(&digits[..1]);
+            if digits.len() > 1 {
+                s.push('.');
+                s.push_str(&digits[1..]);
+            }
+            let _ = write!(s, "e{}{:02}", if exp < 0 { '-' } else { '+' }, exp.abs());
+        } else if exp < 0 {
+            s.push_str("0.");
+            s.extend(std::iter::repeat('0').take((-exp - 1) as usize));
+            s.push_str(digits);
+        } else {
+            let int_len = exp as usize + 1;
+            if digits.len() > int_len {
+                s.push_str(&digits[..int_len]);
+                s.push('.');
+                s.push_str(&digits[int_len..]);
+            } else {
+                s.push_str(digits);
+                s.extend(std::iter::repeat('0').take(int_len - digits.len()));
+                if self.float_suffix {
+                    s.push_str(".0");
+                }
+            }
+        }
+        s
+    }
+}
+
+// Registry flag set when the Lua functions are wrapped to use the number format
+const INSTALLED_KEY: &str = "__mlua_number_format_installed";
+
+// Replaces `tostring` and `print` functions with versions that format numbers according to the
+// number format set for the Lua state
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    if lua.named_registry_value::<bool>(INSTALLED_KEY)? {
+        return Ok(());
+    }
+    let globals = lua.globals();
+
+    let tostring = globals.get::<Function>("tostring")?;
+    let new_tostring = lua.create_function(move |lua, value: Value| match value {
+        Value::Number(n) => match lua.lock().number_format() {
+            Some(format) => Ok(Value::String(lua.create_string(format.format(n))?)),
+            None => tostring.call(n),
+        },
+        value => tostring.call(value),
+    })?;
+
+    if let Some(print) = globals.get::<Option<Function>>("print")? {
+        let tostring = new_tostring.clone();
+        let new_print = lua.create_function(move |_, args: MultiValue| {
+            let args = (args.into_iter())
+                .map(|arg| match arg {
+                    Value::Number(_) => tostring.call::<Value>(arg),
+                    arg => Ok(arg),
+                })
+                .collect::<Result<MultiValue>>()?;
+            print.call::<()>(args)
+        })?;
+        globals.set("print", new_print)?;
+    }
+    globals.set("tostring", new_tostring)?;
+
+    lua.set_named_registry_value(INSTALLED_KEY, true)
+}
@@ -0,0 +1,456 @@
+// Implementation of the `string.pack` format engine (Lua 5.4 semantics).
+//
+// Sizes of native types are fixed to the values of a typical 64-bit platform, so the packed data
+// is the same everywhere.
+
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::types::Integer;
+use crate::value::{MultiValue, Value};
+
+// Maximum size for the binary representation of an integer
+const MAX_INT_SIZE: usize = 16;
+// Size of `lua_Integer` (in the format engine)
+const SZINT: usize = 8;
+// Native maximum alignment used by the `!` option
+const NATIVE_ALIGN: usize = 8;
+// Size of `size_t`
+const SIZE_T: usize = 8;
+// Size of `int`
+const SIZE_INT: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Int,
+    Uint,
+    Float,
+    Double,
+    Char,
+    String,
+    Zstr,
+    Padding,
+    PadAlign,
+    Nop,
+}
+
+struct Header {
+    little: bool,
+    max_align: usize,
+}
+
+struct Format<'a> {
+    fmt: &'a [u8],
+    pos: usize,
+    header: Header,
+}
+
+impl<'a> Format<'a> {
+    fn new(fmt: &'a [u8]) -> Self {
+        Format {
+            fmt,
+            pos: 0,
+            header: Header {
+                little: cfg!(target_endian = "little"),
+                max_align: 1,
+            },
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.fmt.len()
+    }
+
+    fn read_num(&mut self, default: Option<usize>) -> Option<usize> {
+        let start = self.pos;
+        let mut n = 0usize;
+        while let Some(c @ b'0'..=b'9') = self.fmt.get(self.pos) {
+            if n > (i32::MAX as usize - 9) / 10 {
+                break;
+            }
+            n = n * 10 + (c - b'0') as usize;
+            self.pos += 1;
+        }
+        if self.pos == start {
+            default
+        } else {
+            Some(n)
+        }
+    }
+
+    fn read_num_limit(&mut self, default: usize) -> Result<usize> {
+        let n = self.read_num(Some(default)).unwrap_or(default);
+        if n > MAX_INT_SIZE || n == 0 {
+            return Err(Error::runtime(format!(
+                "integral size ({n}) out of limits [1,{MAX_INT_SIZE}]"
+            )));
+        }
+        Ok(n)
+    }
+
+    // Reads the next option and returns its kind and size
+    fn option(&mut self) -> Result<(Kind, usize)> {
+        let opt = self.fmt[self.pos];
+        self.pos += 1;
+        let res = match opt {
+            b'b' => (Kind::Int, 1),
+            b'B' => (Kind::Uint, 1),
+            b'h' => (Kind::Int, 2),
+            b'H' => (Kind::Uint, 2),
+            b'l' | b'j' => (Kind::Int, 8),
+            b'L' | b'J' | b'T' => (Kind::Uint, 8),
+            b'f' => (Kind::Float, 4),
+            b'n' | b'd' => (Kind::Double, 8),
+            b'i' => (Kind::Int, self.read_num_limit(SIZE_INT)?),
+            b'I' => (Kind::Uint, self.read_num_limit(SIZE_INT)?),
+            b's' => (Kind::String, self.read_num_limit(SIZE_T)?),
+            b'c' => match self.read_num(None) {
+                Some(size) => (Kind::Char, size),
+                None => return Err(Error::runtime("missing size for format option 'c'")),
+            },
+            b'z' => (Kind::Zstr, 0),
+            b'x' => (Kind::Padding, 1),
+            b'X' => (Kind::PadAlign, 0),
+            b' ' => (Kind::Nop, 0),
+            b'<' => {
+                self.header.little = true;
+                (Kind::Nop, 0)
+            }
+            b'>' => {
+                self.header.little = false;
+                (Kind::Nop, 0)
+            }
+            b'=' => {
+                self.header.little = cfg!(target_endian = "little");
+                (Kind::Nop, 0)
+            }
+            b'!' => {
+                self.header.max_align = self.read_num_limit(NATIVE_ALIGN)?;
+                (Kind::Nop, 0)
+            }
+            _ => {
+                let opt = opt as char;
+                return Err(Error::runtime(format!("invalid format option '{opt}'")));
+            }
+        };
+        Ok(res)
+    }
+
+    // Reads the next option and returns its kind, size and the padding needed to align it
+    // at the `total_size` offset
+    fn details(&mut self, total_size: usize) -> Result<(Kind, usize, usize)> {
+        let (kind, size) = self.option()?;
+        let mut align = size;
+        if kind == Kind::PadAlign {
+            let next = if self.is_done() {
+                None
+            } else {
+                Some(self.option()?)
+            };
+            match next {
+                Some((next_kind, next_size)) if next_kind != Kind::Char && next_size != 0 => {
+                    align = next_size
+                }
+                _ => return Err(Error::runtime("invalid next option for option 'X'")),
+            }
+        }
+        if align <= 1 || kind == Kind::Char {
+            return Ok((kind, size, 0));
+        }
+        let align = align.min(self.header.max_align);
+        if !align.is_power_of_two() {
+            return Err(Error::runtime("format asks for alignment not power of 2"));
+        }
+        let to_align = (align - (total_size & (align - 1))) & (align - 1);
+        Ok((kind, size, to_align))
+    }
+}
+
+fn pack_int(buf: &mut Vec<u8>, n: u64, little: bool, size: usize, negative: bool) {
+    let mut bytes = [if negative { 0xff } else { 0 }; MAX_INT_SIZE];
+    bytes[..SZINT].copy_from_slice(&n.to_le_bytes());
+    let bytes = &mut bytes[..size];
+    if !little {
+        bytes.reverse();
+    }
+    buf.extend_from_slice(bytes);
+}
+
+fn unpack_int(data: &[u8], little: bool, signed: bool) -> Result<i64> {
+    let size = data.len();
+    let byte = |i: usize| if little { data[i] } else { data[size - 1 - i] };
+    let limit = size.min(SZINT);
+    let mut res = 0u64;
+    for i in (0..limit).rev() {
+        res = (res << 8) | byte(i) as u64;
+    }
+    if size < SZINT {
+        if signed {
+            // Sign-extend the value
+            let mask = 1u64 << (size * 8 - 1);
+            res = (res ^ mask).wrapping_sub(mask);
+        }
+    } else if size > SZINT {
+        // Check that the extra bytes are a valid extension of the value
+        let ext = if signed && (res as i64) < 0 { 0xff } else { 0 };
+        if (limit..size).any(|i| byte(i) != ext) {
+            return Err(Error::runtime(format!(
+                "{size}-byte integer does not fit into Lua Integer"
+            )));
+        }
+    }
+    Ok(res as i64)
+}
+
+fn to_integer(lua: &Lua, value: &Value) -> Option<i64> {
+    match *value {
+        #[allow(clippy::useless_conversion)]
+        Value::Integer(i) => Some(i.into()),
+        Value::Number(n) if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 => Some(n as i64),
+        Value::String(_) => {
+            let n = lua.coerce_number(value.clone()).ok().flatten()?;
+            to_integer(lua, &Value::Number(n))
+        }
+        _ => None,
+    }
+}
+
+fn to_number(lua: &Lua, value: &Value) -> Option<f64> {
+    match *value {
+        Value::Integer(i) => Some(i as f64),
+        Value::Number(n) => Some(n),
+        Value::String(_) => lua.coerce_number(value.clone()).ok().flatten(),
+        _ => None,
+    }
+}
+
+fn from_integer(n: i64) -> Value {
+    match Integer::try_from(n) {
+        Ok(i) => Value::Integer(i),
+        Err(_) => Value::Number(n as f64),
+    }
+}
+
+fn bad_argument(to: &str, pos: usize, message: &str) -> Error {
+    Error::BadArgument {
+        to: Some(to.to_string()),
+        pos,
+        name: None,
+        cause: Arc::new(Error::runtime(message)),
+    }
+}
+
+// Packs `values` according to the format string `fmt`.
+// `first_arg` is the position of the first value used in error messages.
+pub(crate) fn pack(lua: &Lua, fmt: &[u8], values: &[Value], first_arg: usize) -> Result<Vec<u8>> {
+    let mut format = Format::new(fmt);
+    let mut buf = Vec::new();
+    let mut index = 0;
+    let mut next_value = |expected: &str| {
+        let pos = first_arg + index;
+        let value = values.get(index).ok_or_else(|| {
+            let msg = format!("{expected} expected, got no value");
+            bad_argument("pack", pos, &msg)
+        })?;
+        index += 1;
+        Ok::<_, Error>((pos, value))
+    };
+
+    while !format.is_done() {
+        let (kind, size, to_align) = format.details(buf.len())?;
+        buf.resize(buf.len() + to_align, 0);
+        let little = format.header.little;
+        match kind {
+            Kind::Int => {
+                let (pos, value) = next_value("number")?;
+                let n = to_integer(lua, value)
+                    .ok_or_else(|| bad_argument("pack", pos, "number has no integer representation"))?;
+                if size < SZINT {
+                    let limit = 1i64 << (size * 8 - 1);
+                    if !(-limit..limit).contains(&n) {
+                        return Err(bad_argument("pack", pos, "integer overflow"));
+                    }
+                }
+                pack_int(&mut buf, n as u64, little, size, n < 0);
+            }
+            Kind::Uint => {
+                let (pos, value) = next_value("number")?;
+                let n = to_integer(lua, value)
+                    .ok_or_else(|| bad_argument("pack", pos, "number has no integer representation"))?;
+                if size < SZINT && (n as u64) >= (1u64 << (size * 8)) {
+                    return Err(bad_argument("pack", pos, "unsigned overflow"));
+                }
+                pack_int(&mut buf, n as u64, little, size, false);
+            }
+            Kind::Float | Kind::Double => {
+                let (pos, value) = next_value("number")?;
+                let n = to_number(lua, value).ok_or_else(|| bad_argument("pack", pos, "number expected"))?;
+                let mut bytes = match kind {
+                    Kind::Float => (n as f32).to_le_bytes().to_vec(),
+                    _ => n.to_le_bytes().to_vec(),
+                };
+                if !little {
+                    bytes.reverse();
+                }
+                buf.extend_from_slice(&bytes);
+            }
+            Kind::Char | Kind::String | Kind::Zstr => {
+                let (pos, value) = next_value("string")?;
+                let s = match value {
+                    Value::String(s) => s.as_bytes().to_vec(),
+                    Value::Integer(_) | Value::Number(_) => value.to_string()?.into_bytes(),
+                    _ => return Err(bad_argument("pack", pos, "string expected")),
+                };
+                match kind {
+                    Kind::Char => {
+                        if s.len() > size {
+                            return Err(bad_argument("pack", pos, "string longer than given size"));
+                        }
+                        buf.extend_from_slice(&s);
+                        buf.resize(buf.len() + size - s.len(), 0);
+                    }
+                    Kind::String => {
+                        if size < SIZE_T && s.len() as u64 >= (1u64 << (size * 8)) {
+                            let msg = "string length does not fit in given size";
+                            return Err(bad_argument("pack", pos, msg));
+                        }
+                        pack_int(&mut buf, s.len() as u64, little, size, false);
+                        buf.extend_from_slice(&s);
+                    }
+                    _ => {
+                        if s.contains(&0) {
+                            return Err(bad_argument("pack", pos, "string contains zeros"));
+                        }
+                        buf.extend_from_slice(&s);
+                        buf.push(0);
+                    }
+                }
+            }
+            Kind::Padding => buf.push(0),
+            Kind::PadAlign | Kind::Nop => {}
+        }
+    }
+    Ok(buf)
+}
+
+// Unpacks values from `data` (starting at the 0-based `pos`) according to the format string `fmt`.
+// Returns the values and the position after the last read byte.
+pub(crate) fn unpack(lua: &Lua, fmt: &[u8], data: &[u8], mut pos: usize) -> Result<(MultiValue, usize)> {
+    let too_short = || Error::runtime("data string too short");
+    if pos > data.len() {
+        return Err(Error::runtime("initial position out of string"));
+    }
+
+    let mut format = Format::new(fmt);
+    let mut values = MultiValue::new();
+    while !format.is_done() {
+        let (kind, size, to_align) = format.details(pos)?;
+        if to_align + size > data.len() - pos {
+            return Err(too_short());
+        }
+        pos += to_align;
+        let little = format.header.little;
+        let bytes = &data[pos..pos + size];
+        match kind {
+            Kind::Int | Kind::Uint => {
+                let n = unpack_int(bytes, little, kind == Kind::Int)?;
+                values.push_back(from_integer(n));
+            }
+            Kind::Float => {
+                let mut b = [0; 4];
+                b.copy_from_slice(bytes);
+                let n = if little {
+                    f32::from_le_bytes(b)
+                } else {
+                    f32::from_be_bytes(b)
+                };
+                values.push_back(Value::Number(n as f64));
+            }
+            Kind::Double => {
+                let mut b = [0; 8];
+                b.copy_from_slice(bytes);
+                let n = if little {
+                    f64::from_le_bytes(b)
+                } else {
+                    f64::from_be_bytes(b)
+                };
+                values.push_back(Value::Number(n));
+            }
+            Kind::Char => values.push_back(Value::String(lua.create_string(bytes)?)),
+            Kind::String => {
+                let len = unpack_int(bytes, little, false)? as u64;
+                if len > (data.len() - pos - size) as u64 {
+                    return Err(too_short());
+                }
+                let len = len as usize;
+                values.push_back(Value::String(
+                    lua.create_string(&data[pos + size..pos + size + len])?,
+                ));
+                pos += len;
+            }
+            Kind::Zstr => {
+                let len = (data[pos..].iter().position(|&b| b == 0))
+                    .ok_or_else(|| Error::runtime("unfinished string for format 'z'"))?;
+                values.push_back(Value::String(lua.create_string(&data[pos..pos + len])?));
+                pos += len + 1;
+            }
+            Kind::Padding | Kind::PadAlign | Kind::Nop => {}
+        }
+        pos += size;
+    }
+    Ok((values, pos))
+}
+
+// Returns the size of a string resulting from `pack` with the format string `fmt`.
+pub(crate) fn pack_size(fmt: &[u8]) -> Result<usize> {
+    let mut format = Format::new(fmt);
+    let mut total = 0usize;
+    while !format.is_done() {
+        let (kind, size, to_align) = format.details(total)?;
+        if matches!(kind, Kind::String | Kind::Zstr) {
+            return Err(Error::runtime("variable-size format in packsize"));
+        }
+        total = (total.checked_add(to_align + size))
+            .filter(|&total| total <= i32::MAX as usize)
+            .ok_or_else(|| Error::runtime("format result too large"))?;
+    }
+    Ok(total)
+}
+
+// Registers `string.pack`, `string.unpack` and `string.packsize` functions
+// if they are not provided by the `string` library
+#[cfg(any(feature = "lua52", feature = "lua51", feature = "luajit"))]
+pub(crate) fn register_polyfill(lua: &Lua) -> Result<()> {
+    use crate::string::String as LuaString;
+    use crate::table::Table;
+
+    let Some(string) = lua.globals().get::<Option<Table>>("string")? else {
+        return Ok(());
+    };
+    if string.contains_key("pack")? {
+        return Ok(());
+    }
+
+    let pack_fn = lua.create_function(|lua, (fmt, values): (LuaString, MultiValue)| {
+        let values = values.into_iter().collect::<Vec<_>>();
+        lua.create_string(pack(lua, &fmt.as_bytes(), &values, 2)?)
+    })?;
+    let unpack_fn = lua.create_function(|lua, (fmt, data, init): (LuaString, LuaString, Option<i64>)| {
+        let data = data.as_bytes();
+        // Negative position is relative to the end of the string
+        let pos = match init.unwrap_or(1) {
+            init if init > 0 => init as usize - 1,
+            0 => 0,
+            init => data.len().saturating_sub(init.unsigned_abs() as usize),
+        };
+        let (mut values, pos) = unpack(lua, &fmt.as_bytes(), &data, pos)?;
+        values.push_back(from_integer(pos as i64 + 1));
+        Ok(values)
+    })?;
+    let packsize_fn = lua.create_function(|_, fmt: LuaString| pack_size(&fmt.as_bytes()))?;
+
+    string.raw_set("pack", pack_fn)?;
+    string.raw_set("unpack", unpack_fn)?;
+    string.raw_set("packsize", packsize_fn)
+}
@@ -0,0 +1,218 @@
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+
+use crate::error::{Error, ErrorContext, Result};
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::{FromLua, IntoLua, Value};
+
+/// A struct with options to change the behavior of [`Table::get_path_with`] and
+/// [`Table::set_path_with`].
+///
+/// [`Table::get_path_with`]: crate::Table::get_path_with
+/// [`Table::set_path_with`]: crate::Table::set_path_with
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct PathOptions {
+    /// If true, missing (nil) intermediate tables are created when setting a value.
+    /// Otherwise setting a value under a missing intermediate table is an error.
+    ///
+    /// Default: **true**
+    pub create_missing: bool,
+
+    /// If true, tables are accessed without invoking metamethods.
+    ///
+    /// Default: **false**
+    pub raw: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl PathOptions {
+    /// Returns a new instance of [`PathOptions`] with default parameters.
+    pub const fn new() -> Self {
+        PathOptions {
+            create_missing: true,
+            raw: false,
+        }
+    }
+
+    /// Sets [`create_missing`] option.
+    ///
+    /// [`create_missing`]: #structfield.create_missing
+    #[must_use]
+    pub const fn create_missing(mut self, enabled: bool) -> Self {
+        self.create_missing = enabled;
+        self
+    }
+
+    /// Sets [`raw`] option.
+    ///
+    /// [`raw`]: #structfield.raw
+    #[must_use]
+    pub const fn raw(mut self, enabled: bool) -> Self {
+        self.raw = enabled;
+        self
+    }
+}
+
+enum Key {
+    Name(StdString),
+    Index(Integer),
+}
+
+// A single path segment with the byte offset in the path where it ends
+struct Segment {
+    key: Key,
+    end: usize,
+}
+
+fn is_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>> {
+    let invalid = |pos: usize, msg: &str| {
+        Error::runtime(format!("invalid path `{path}` at position {}: {msg}", pos + 1))
+    };
+
+    let bytes = path.as_bytes();
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    loop {
+        if bytes.get(pos) == Some(&b'[') {
+            let start = pos;
+            pos += 1;
+            let key = match bytes.get(pos) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let len = bytes[pos + 1..]
+                        .iter()
+                        .position(|&c| c == quote)
+                        .ok_or_else(|| invalid(start, "unterminated string"))?;
+                    let name = path[pos + 1..pos + 1 + len].to_string();
+                    pos += len + 2;
+                    Key::Name(name)
+                }
+                _ => {
+                    let len = bytes[pos..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_digit() || **c == b'-')
+                        .count();
+                    let index = path[pos..pos + len]
+                        .parse::<Integer>()
+                        .map_err(|_| invalid(pos, "expected integer index or quoted key"))?;
+                    pos += len;
+                    Key::Index(index)
+                }
+            };
+            if bytes.get(pos) != Some(&b']') {
+                return Err(invalid(pos, "expected `]`"));
+            }
+            pos += 1;
+            segments.push(Segment { key, end: pos });
+        } else {
+            let len = bytes[pos..].iter().take_while(|&&c| is_name_char(c)).count();
+            if len == 0 {
+                return Err(invalid(pos, "expected key"));
+            }
+            pos += len;
+            let name = path[pos - len..pos].to_string();
+            segments.push(Segment {
+                key: Key::Name(name),
+                end: pos,
+            });
+        }
+
+        match bytes.get(pos) {
+            None => break,
+            Some(b'.') => {
+                pos += 1;
+                if pos == bytes.len() {
+                    return Err(invalid(pos, "expected key"));
+                }
+            }
+            Some(b'[') => {}
+            Some(_) => return Err(invalid(pos, "expected `.` or `[`")),
+        }
+    }
+    Ok(segments)
+}
+
+fn table_get<V: FromLua>(table: &Table, key: &Key, options: PathOptions) -> Result<V> {
+    match (key, options.raw) {
+        (Key::Name(name), false) => table.get(name.as_str()),
+        (Key::Name(name), true) => table.raw_get(name.as_str()),
+        (Key::Index(i), false) => table.get(*i),
+        (Key::Index(i), true) => table.raw_get(*i),
+    }
+}
+
+fn table_set(table: &Table, key: &Key, value: impl IntoLua, options: PathOptions) -> Result<()> {
+    match (key, options.raw) {
+        (Key::Name(name), false) => table.set(name.as_str(), value),
+        (Key::Name(name), true) => table.raw_set(name.as_str(), value),
+        (Key::Index(i), false) => table.set(*i, value),
+        (Key::Index(i), true) => table.raw_set(*i, value),
+    }
+}
+
+// Walks all intermediate segments and returns the innermost table.
+// If an intermediate value is nil (and `create` is false), returns the path prefix to it instead.
+fn walk<'a>(
+    table: &Table,
+    path: &'a str,
+    segments: &[Segment],
+    create: bool,
+    options: PathOptions,
+) -> Result<StdResult<Table, &'a str>> {
+    let mut table = table.clone();
+    for segment in segments {
+        let prefix = &path[..segment.end];
+        let value = table_get::<Value>(&table, &segment.key, options)
+            .with_context(|_| format!("at path `{prefix}`"))?;
+        table = match value {
+            Value::Table(t) => t,
+            Value::Nil if create => {
+                let t = table.0.lua.lock().lua().create_table()?;
+                table_set(&table, &segment.key, &t, options)
+                    .with_context(|_| format!("at path `{prefix}`"))?;
+                t
+            }
+            Value::Nil => return Ok(Err(prefix)),
+            value => {
+                let type_name = value.type_name();
+                let msg = format!("cannot index `{prefix}` (a {type_name} value) at path `{path}`");
+                return Err(Error::runtime(msg));
+            }
+        };
+    }
+    Ok(Ok(table))
+}
+
+pub(crate) fn get_path<V: FromLua>(table: &Table, path: &str, options: PathOptions) -> Result<V> {
+    let segments = parse(path)?;
+    let (last, intermediate) = segments.split_last().expect("path has at least one segment");
+    match walk(table, path, intermediate, false, options)? {
+        Ok(table) => table_get(&table, &last.key, options),
+        // Missing intermediate table is treated as nil value
+        Err(_) => V::from_lua(Value::Nil, table.0.lua.lock().lua()),
+    }
+    .with_context(|_| format!("at path `{path}`"))
+}
+
+pub(crate) fn set_path(table: &Table, path: &str, value: impl IntoLua, options: PathOptions) -> Result<()> {
+    let segments = parse(path)?;
+    let (last, intermediate) = segments.split_last().expect("path has at least one segment");
+    match walk(table, path, intermediate, options.create_missing, options)? {
+        Ok(table) => {
+            table_set(&table, &last.key, value, options).with_context(|_| format!("at path `{path}`"))
+        }
+        Err(prefix) => Err(Error::runtime(format!(
+            "cannot set path `{path}`: `{prefix}` is nil"
+        ))),
+    }
+}
@@ -0,0 +1,590 @@
+//! Utilities for working with Lua patterns outside of the Lua VM.
+//!
+//! Lua patterns can be validated with [`validate`] and translated to regular expressions
+//! (compatible with the [`regex`] crate) with [`to_regex_string`] or [`to_regex`], so host code
+//! can precompile user-supplied patterns and reuse them. [`from_regex`] performs a best-effort
+//! conversion in the opposite direction.
+//!
+//! Lua patterns operate on bytes and use the C locale for character classes, while the
+//! translated regular expressions operate on Unicode characters. The classes (`%a`, `%d`, etc.)
+//! are translated to their ASCII equivalents, so the results match for ASCII subjects.
+//!
+//! [`regex`]: https://docs.rs/regex
+
+use std::fmt::Write as _;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+
+// Maximum number of captures in a Lua pattern (`LUA_MAXCAPTURES`)
+const MAX_CAPTURES: usize = 32;
+
+// Characters with special meaning in Lua patterns
+const LUA_SPECIALS: &str = "^$()%.[]*+-?";
+
+// Characters with special meaning in regular expressions
+const REGEX_SPECIALS: &str = "\\.+*?()|[]{}^$#&-~";
+
+/// Checks that `pattern` is a well-formed Lua pattern.
+///
+/// In addition to the errors raised by Lua itself (e.g. missing `]` or unfinished capture),
+/// this function rejects common mistakes in patterns that Lua silently accepts:
+/// - `^` not at the start or `$` not at the end of the pattern (Lua matches them literally)
+/// - `%b` with identical delimiters (such items never nest)
+/// - more captures than Lua supports
+pub fn validate(pattern: &str) -> Result<()> {
+    Parser::new(pattern, true).parse().map(|_| ())
+}
+
+/// Translates a Lua pattern to an equivalent regular expression.
+///
+/// Position captures (`()`), back-references (`%1`), balanced matches (`%b`) and frontiers
+/// (`%f`) cannot be expressed as regular expressions and result in an error.
+pub fn to_regex_string(pattern: &str) -> Result<StdString> {
+    let mut out = StdString::new();
+    for token in Parser::new(pattern, false).parse()? {
+        let unsupported = |item: &str| {
+            let msg = format!("cannot translate {item} in pattern '{pattern}' to a regular expression");
+            Err(Error::RuntimeError(msg))
+        };
+        match token {
+            Token::StartAnchor => out.push('^'),
+            Token::EndAnchor => out.push('$'),
+            Token::Open => out.push('('),
+            Token::Close => out.push(')'),
+            Token::Item(item, quantifier) => {
+                push_regex_item(&mut out, &item);
+                match quantifier {
+                    Some('-') => out.push_str("*?"),
+                    Some(q) => out.push(q),
+                    None => {}
+                }
+            }
+            Token::Position => return unsupported("position capture '()'"),
+            Token::BackRef(index) => return unsupported(&format!("back-reference '%{index}'")),
+            Token::Balance(open, close) => return unsupported(&format!("'%b{open}{close}'")),
+            Token::Frontier => return unsupported("frontier '%f'"),
+        }
+    }
+    Ok(out)
+}
+
+/// Translates a Lua pattern to a compiled [`Regex`].
+///
+/// See [`to_regex_string`] for limitations.
+///
+/// Requires `feature = "regex"`
+///
+/// [`Regex`]: regex::Regex
+#[cfg(feature = "regex")]
+#[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
+pub fn to_regex(pattern: &str) -> Result<regex::Regex> {
+    regex::Regex::new(&to_regex_string(pattern)?).map_err(Error::external)
+}
+
+/// Translates a regular expression to a Lua pattern (best-effort).
+///
+/// Only the subset of the regular expression syntax that has a Lua pattern equivalent is
+/// supported: literals, `.`, Perl classes (`\d`, `\w`, `\s` and their negations), ASCII classes
+/// in brackets (`[[:alpha:]]`), character sets, anchors at the start and the end, capture groups
+/// and quantifiers applied to single items (including bounded repetitions like `{2,3}`).
+/// Alternation, non-capturing groups and quantified groups result in an error.
+///
+/// Note that `.` in Lua patterns matches any byte (including newlines).
+pub fn from_regex(regex: &str) -> Result<StdString> {
+    RegexTranslator::new(regex).translate()
+}
+
+enum Token {
+    StartAnchor,
+    EndAnchor,
+    Open,
+    Close,
+    Position,
+    Item(Item, Option<char>),
+    BackRef(usize),
+    Balance(char, char),
+    Frontier,
+}
+
+enum Item {
+    Any,
+    Char(char),
+    Class(char),
+    Set(Set),
+}
+
+struct Set {
+    negated: bool,
+    members: Vec<SetMember>,
+}
+
+enum SetMember {
+    Char(char),
+    Range(char, char),
+    Class(char),
+}
+
+// Parser of Lua patterns, following the rules of `lstrlib.c`
+struct Parser<'a> {
+    pattern: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+    strict: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(pattern: &'a str, strict: bool) -> Self {
+        let chars = pattern.chars().collect();
+        Parser {
+            pattern,
+            chars,
+            pos: 0,
+            strict,
+        }
+    }
+
+    fn error<T>(&self, msg: impl std::fmt::Display) -> Result<T> {
+        Err(Error::RuntimeError(format!(
+            "{msg} in pattern '{}'",
+            self.pattern
+        )))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        self.pos += 1;
+        c
+    }
+
+    fn parse(mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        // Open captures (indices) and the total number of captures
+        let mut open = Vec::new();
+        let mut closed = Vec::new();
+        let mut captures = 0;
+
+        if self.chars.first() == Some(&'^') {
+            tokens.push(Token::StartAnchor);
+            self.pos = 1;
+        }
+        while let Some(c) = self.next() {
+            let item = match c {
+                '(' => {
+                    captures += 1;
+                    if captures > MAX_CAPTURES {
+                        return self.error("too many captures");
+                    }
+                    if self.chars.get(self.pos) == Some(&')') {
+                        self.pos += 1;
+                        closed.push(captures);
+                        tokens.push(Token::Position);
+                    } else {
+                        open.push(captures);
+                        tokens.push(Token::Open);
+                    }
+                    continue;
+                }
+                ')' => {
+                    match open.pop() {
+                        Some(index) => closed.push(index),
+                        None => return self.error("invalid pattern capture"),
+                    }
+                    tokens.push(Token::Close);
+                    continue;
+                }
+                '$' if self.pos == self.chars.len() => {
+                    tokens.push(Token::EndAnchor);
+                    continue;
+                }
+                '%' => match self.next() {
+                    None => return self.error("malformed pattern (ends with '%')"),
+                    Some('b') => {
+                        let (Some(open), Some(close)) = (self.next(), self.next()) else {
+                            return self.error("missing arguments to '%b'");
+                        };
+                        if self.strict && open == close {
+                            return self.error(format!("'%b{open}{close}' has identical delimiters"));
+                        }
+                        tokens.push(Token::Balance(open, close));
+                        continue;
+                    }
+                    Some('f') => {
+                        if self.next() != Some('[') {
+                            return self.error("missing '[' after '%f'");
+                        }
+                        self.parse_set()?;
+                        tokens.push(Token::Frontier);
+                        continue;
+                    }
+                    Some(d @ '0'..='9') => {
+                        let index = d as usize - '0' as usize;
+                        if !closed.contains(&index) {
+                            return self.error(format!("invalid capture index %{d}"));
+                        }
+                        tokens.push(Token::BackRef(index));
+                        continue;
+                    }
+                    Some(c) if is_class(c) => Item::Class(c),
+                    Some(c) => Item::Char(c),
+                },
+                '[' => Item::Set(self.parse_set()?),
+                '.' => Item::Any,
+                '^' | '$' if self.strict => {
+                    let position = if c == '^' { "start" } else { "end" };
+                    return self.error(format!(
+                        "'{c}' is an anchor only at the {position} of the pattern (use '%{c}' to match it literally)"
+                    ));
+                }
+                c => Item::Char(c),
+            };
+            let quantifier = match self.chars.get(self.pos) {
+                Some(&q @ ('*' | '+' | '-' | '?')) => {
+                    self.pos += 1;
+                    Some(q)
+                }
+                _ => None,
+            };
+            tokens.push(Token::Item(item, quantifier));
+        }
+        if !open.is_empty() {
+            return self.error("unfinished capture");
+        }
+        Ok(tokens)
+    }
+
+    // Parses a set after the opening `[`
+    fn parse_set(&mut self) -> Result<Set> {
+        let mut set = Set {
+            negated: false,
+            members: Vec::new(),
+        };
+        if self.chars.get(self.pos) == Some(&'^') {
+            set.negated = true;
+            self.pos += 1;
+        }
+        // The first character is always a member (even if it's `]`)
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                None => return self.error("malformed pattern (missing ']')"),
+                Some(']') if !first => return Ok(set),
+                Some(c) => c,
+            };
+            first = false;
+            if c == '%' {
+                match self.next() {
+                    None => return self.error("malformed pattern (missing ']')"),
+                    Some(c) if is_class(c) => set.members.push(SetMember::Class(c)),
+                    Some(c) => set.members.push(SetMember::Char(c)),
+                }
+            } else if self.chars.get(self.pos) == Some(&'-')
+                && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']')
+            {
+                let end = self.chars[self.pos + 1];
+                self.pos += 2;
+                set.members.push(SetMember::Range(c, end));
+            } else {
+                set.members.push(SetMember::Char(c));
+            }
+        }
+    }
+}
+
+fn is_class(c: char) -> bool {
+    class_ranges(c).is_some()
+}
+
+// Returns ASCII ranges of a Lua character class (case-insensitive)
+fn class_ranges(c: char) -> Option<&'static str> {
+    Some(match c.to_ascii_lowercase() {
+        'a' => "A-Za-z",
+        'c' => "\\x00-\\x1F\\x7F",
+        'd' => "0-9",
+        'g' => "\\x21-\\x7E",
+        'l' => "a-z",
+        'p' => "\\x21-\\x2F\\x3A-\\x40\\x5B-\\x60\\x7B-\\x7E",
+        's' => "\\t\\n\\x0B\\x0C\\r ",
+        'u' => "A-Z",
+        'w' => "0-9A-Za-z",
+        'x' => "0-9A-Fa-f",
+        'z' => "\\x00",
+        _ => return None,
+    })
+}
+
+fn push_regex_class(out: &mut StdString, c: char) {
+    let ranges = class_ranges(c).expect("valid class");
+    let negated = if c.is_ascii_uppercase() { "^" } else { "" };
+    let _ = write!(out, "[{negated}{ranges}]");
+}
+
+fn push_regex_escaped(out: &mut StdString, c: char, specials: &str) {
+    if specials.contains(c) {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+fn push_regex_item(out: &mut StdString, item: &Item) {
+    match *item {
+        Item::Any => out.push_str("(?s:.)"),
+        Item::Char(c) => push_regex_escaped(out, c, REGEX_SPECIALS),
+        Item::Class(c) => push_regex_class(out, c),
+        Item::Set(ref set) => {
+            out.push('[');
+            if set.negated {
+                out.push('^');
+            }
+            for member in &set.members {
+                match *member {
+                    SetMember::Char(c) => push_regex_escaped(out, c, REGEX_SPECIALS),
+                    SetMember::Range(start, end) => {
+                        push_regex_escaped(out, start, REGEX_SPECIALS);
+                        out.push('-');
+                        push_regex_escaped(out, end, REGEX_SPECIALS);
+                    }
+                    SetMember::Class(c) => push_regex_class(out, c),
+                }
+            }
+            out.push(']');
+        }
+    }
+}
+
+// Translator of (a subset of) regular expressions to Lua patterns
+struct RegexTranslator<'a> {
+    regex: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> RegexTranslator<'a> {
+    fn new(regex: &'a str) -> Self {
+        let chars = regex.chars().collect();
+        RegexTranslator { regex, chars, pos: 0 }
+    }
+
+    fn error<T>(&self, msg: impl std::fmt::Display) -> Result<T> {
+        let msg = format!("cannot translate regular expression '{}': {msg}", self.regex);
+        Err(Error::RuntimeError(msg))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.pos).copied();
+        self.pos += 1;
+        c
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn translate(mut self) -> Result<StdString> {
+        let mut out = StdString::new();
+        // The last item that can be quantified
+        let mut last_item: Option<StdString> = None;
+        while let Some(c) = self.next() {
+            let item = match c {
+                '^' if self.pos == 1 => {
+                    out.push('^');
+                    continue;
+                }
+                '$' if self.pos == self.chars.len() => {
+                    out.push('$');
+                    continue;
+                }
+                '^' | '$' => return self.error(format!("anchor '{c}' in the middle")),
+                '|' => return self.error("alternation is not supported"),
+                '(' => {
+                    if self.peek() == Some('?') {
+                        // Named groups are translated to regular captures
+                        let rest: StdString = self.chars[self.pos..].iter().take(3).collect();
+                        if rest.starts_with("?P<") || (rest.starts_with("?<") && !rest.starts_with("?<=")) {
+                            while self.next().is_some_and(|c| c != '>') {}
+                        } else {
+                            return self.error("only capture groups are supported");
+                        }
+                    }
+                    out.push('(');
+                    last_item = None;
+                    continue;
+                }
+                ')' => {
+                    out.push(')');
+                    last_item = None;
+                    if matches!(self.peek(), Some('*' | '+' | '?' | '{')) {
+                        return self.error("quantified groups are not supported");
+                    }
+                    continue;
+                }
+                '*' | '+' | '?' | '{' => {
+                    let Some(item) = last_item.take() else {
+                        return self.error(format!("'{c}' without an item to repeat"));
+                    };
+                    self.pos -= 1;
+                    let quantified = self.translate_quantifier(&item)?;
+                    // Remove the previously emitted item
+                    out.truncate(out.len() - item.len());
+                    out.push_str(&quantified);
+                    continue;
+                }
+                '.' => ".".to_string(),
+                '[' => self.translate_set()?,
+                '\\' => self.translate_escape(false)?,
+                c => lua_escaped(c, LUA_SPECIALS),
+            };
+            out.push_str(&item);
+            last_item = Some(item);
+        }
+        Ok(out)
+    }
+
+    // Translates a quantifier (at the current position) applied to `item`
+    fn translate_quantifier(&mut self, item: &str) -> Result<StdString> {
+        let (min, max) = match self.next() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            _ => {
+                let end = match self.chars[self.pos..].iter().position(|&c| c == '}') {
+                    Some(end) => self.pos + end,
+                    None => return self.error("unclosed repetition"),
+                };
+                let spec: StdString = self.chars[self.pos..end].iter().collect();
+                self.pos = end + 1;
+                let parse = |s: &str| s.trim().parse::<usize>().ok();
+                match spec.split_once(',') {
+                    None => match parse(&spec) {
+                        Some(n) => (n, Some(n)),
+                        None => return self.error(format!("invalid repetition '{{{spec}}}'")),
+                    },
+                    Some((min, "")) => match parse(min) {
+                        Some(min) => (min, None),
+                        None => return self.error(format!("invalid repetition '{{{spec}}}'")),
+                    },
+                    Some((min, max)) => match (parse(min), parse(max)) {
+                        (Some(min), Some(max)) if min <= max => (min, Some(max)),
+                        _ => return self.error(format!("invalid repetition '{{{spec}}}'")),
+                    },
+                }
+            }
+        };
+        let lazy = self.peek() == Some('?');
+        if lazy {
+            self.pos += 1;
+        }
+
+        let mut out = item.repeat(min.saturating_sub(1));
+        match (min, max) {
+            (0, None) if lazy => return Ok(format!("{item}-")),
+            (0, None) => return Ok(format!("{item}*")),
+            (_, None) if lazy => out.push_str(&format!("{item}{item}-")),
+            (_, None) => out.push_str(&format!("{item}+")),
+            (_, Some(_)) if lazy => return self.error("lazy bounded repetitions are not supported"),
+            (0, Some(max)) => out = format!("{item}?").repeat(max),
+            (min, Some(max)) => {
+                out.push_str(item);
+                out.push_str(&format!("{item}?").repeat(max - min));
+            }
+        }
+        Ok(out)
+    }
+
+    // Translates an escape sequence (after `\`)
+    fn translate_escape(&mut self, in_set: bool) -> Result<StdString> {
+        let specials = if in_set { "%]-^" } else { LUA_SPECIALS };
+        match self.next() {
+            None => self.error("trailing '\\'"),
+            Some(c @ ('d' | 'D' | 's' | 'S' | 'w' | 'W')) => Ok(format!("%{c}")),
+            Some('t') => Ok("\t".to_string()),
+            Some('n') => Ok("\n".to_string()),
+            Some('r') => Ok("\r".to_string()),
+            Some('f') => Ok("\x0C".to_string()),
+            Some('v') => Ok("\x0B".to_string()),
+            Some(c) if c.is_ascii_punctuation() || c == ' ' => Ok(lua_escaped(c, specials)),
+            Some(c) => self.error(format!("unsupported escape '\\{c}'")),
+        }
+    }
+
+    // Translates a character set (after `[`)
+    fn translate_set(&mut self) -> Result<StdString> {
+        let mut out = StdString::from("[");
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            out.push('^');
+        }
+        let mut first = true;
+        loop {
+            let member = match self.next() {
+                None => return self.error("unclosed character set"),
+                Some(']') if !first => break,
+                Some('[') if self.peek() == Some(':') => {
+                    let end = match self.chars[self.pos..].iter().position(|&c| c == ']') {
+                        Some(end) => self.pos + end,
+                        None => return self.error("unclosed character class"),
+                    };
+                    let name: StdString = self.chars[self.pos..end].iter().collect();
+                    self.pos = end + 1;
+                    match ascii_class(&name) {
+                        Some(class) => class.to_string(),
+                        None => return self.error(format!("unsupported class '[{name}]'")),
+                    }
+                }
+                Some('[') => return self.error("nested character sets are not supported"),
+                Some('\\') => self.translate_escape(true)?,
+                Some(c) => {
+                    if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                        let end = self.chars[self.pos + 1];
+                        self.pos += 2;
+                        if end == '\\' || end == '[' {
+                            return self.error("unsupported range end");
+                        }
+                        format!("{}-{}", lua_escaped(c, "%]^"), lua_escaped(end, "%]"))
+                    } else {
+                        lua_escaped(c, "%]-^")
+                    }
+                }
+            };
+            first = false;
+            out.push_str(&member);
+        }
+        out.push(']');
+        Ok(out)
+    }
+}
+
+// Returns Lua class for the ASCII class name (`:alpha:`, etc.)
+fn ascii_class(name: &str) -> Option<&'static str> {
+    Some(match name {
+        ":alpha:" => "%a",
+        ":^alpha:" => "%A",
+        ":cntrl:" => "%c",
+        ":^cntrl:" => "%C",
+        ":digit:" => "%d",
+        ":^digit:" => "%D",
+        ":graph:" => "%g",
+        ":^graph:" => "%G",
+        ":lower:" => "%l",
+        ":^lower:" => "%L",
+        ":punct:" => "%p",
+        ":^punct:" => "%P",
+        ":space:" => "%s",
+        ":^space:" => "%S",
+        ":upper:" => "%u",
+        ":^upper:" => "%U",
+        ":alnum:" => "%w",
+        ":^alnum:" => "%W",
+        ":xdigit:" => "%x",
+        ":^xdigit:" => "%X",
+        _ => return None,
+    })
+}
+
+fn lua_escaped(c: char, specials: &str) -> StdString {
+    if specials.contains(c) {
+        format!("%{c}")
+    } else {
+        c.to_string()
+    }
+}
@@ -0,0 +1,260 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::chunk::AsChunk;
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::types::MaybeSend;
+use crate::value::{FromLuaMulti, IntoLuaMulti};
+
+#[cfg(feature = "send")]
+type InitFn = Box<dyn Fn() -> Result<Lua> + Send>;
+
+#[cfg(not(feature = "send"))]
+type InitFn = Box<dyn Fn() -> Result<Lua>>;
+
+/// Options for [`LuaPool`].
+///
+/// Requires `feature = "async"`
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct PoolOptions {
+    /// Number of Lua states in the pool.
+    ///
+    /// Default: **4**
+    pub size: usize,
+
+    /// Recycle a state once its memory usage exceeds this number of bytes.
+    ///
+    /// Default: **none**
+    pub max_memory: Option<usize>,
+
+    /// Recycle a state after this number of failed executions.
+    ///
+    /// Default: **none**
+    pub max_errors: Option<usize>,
+
+    /// Recycle a state after this number of executions.
+    ///
+    /// Default: **none**
+    pub max_executions: Option<usize>,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        const { Self::new() }
+    }
+}
+
+impl PoolOptions {
+    /// Returns a new instance of [`PoolOptions`] with default parameters.
+    pub const fn new() -> Self {
+        PoolOptions {
+            size: 4,
+            max_memory: None,
+            max_errors: None,
+            max_executions: None,
+        }
+    }
+
+    /// Sets [`size`] option.
+    ///
+    /// [`size`]: #structfield.size
+    #[must_use]
+    pub const fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets [`max_memory`] option.
+    ///
+    /// [`max_memory`]: #structfield.max_memory
+    #[must_use]
+    pub const fn max_memory(mut self, limit: Option<usize>) -> Self {
+        self.max_memory = limit;
+        self
+    }
+
+    /// Sets [`max_errors`] option.
+    ///
+    /// [`max_errors`]: #structfield.max_errors
+    #[must_use]
+    pub const fn max_errors(mut self, limit: Option<usize>) -> Self {
+        self.max_errors = limit;
+        self
+    }
+
+    /// Sets [`max_executions`] option.
+    ///
+    /// [`max_executions`]: #structfield.max_executions
+    #[must_use]
+    pub const fn max_executions(mut self, limit: Option<usize>) -> Self {
+        self.max_executions = limit;
+        self
+    }
+}
+
+/// A pool of independent Lua states.
+///
+/// Each state is created by the user-provided init function, which typically registers the
+/// required bindings. Chunks are executed on the least loaded state (the state with the lowest
+/// number of running executions), so concurrent executions are spread over all states.
+///
+/// States are recycled (replaced with new ones) after exceeding the limits set in
+/// [`PoolOptions`]. Executions already running on a recycled state are not interrupted.
+///
+/// With `feature = "send"` the pool can be shared between threads (e.g. wrapped in `Arc`).
+///
+/// Requires `feature = "async"`
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, LuaPool, PoolOptions, Result};
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<()> {
+/// let pool = LuaPool::new(PoolOptions::new().size(2), || {
+///     let lua = Lua::new();
+///     lua.globals().set("greeting", "hello")?;
+///     Ok(lua)
+/// })?;
+/// let msg: String = pool.execute("return greeting .. ', ' .. ...", "world").await?;
+/// assert_eq!(msg, "hello, world");
+/// # Ok(())
+/// # }
+/// ```
+pub struct LuaPool {
+    slots: Vec<PoolSlot>,
+    init: Mutex<InitFn>,
+    options: PoolOptions,
+    recycled: AtomicUsize,
+}
+
+struct PoolSlot {
+    // Current state and its generation (incremented on every recycle)
+    state: Mutex<(Lua, usize)>,
+    load: AtomicUsize,
+    errors: AtomicUsize,
+    executions: AtomicUsize,
+}
+
+// Decrements the slot load when the execution is finished (or cancelled)
+struct LoadGuard<'a>(&'a AtomicUsize);
+
+impl Drop for LoadGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl LuaPool {
+    /// Creates a new pool of Lua states using `init` function to create each state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool size is zero.
+    pub fn new<F>(options: PoolOptions, init: F) -> Result<Self>
+    where
+        F: Fn() -> Result<Lua> + MaybeSend + 'static,
+    {
+        assert!(options.size > 0, "pool size must be greater than zero");
+        let slots = (0..options.size)
+            .map(|_| {
+                Ok(PoolSlot {
+                    state: Mutex::new((init()?, 0)),
+                    load: AtomicUsize::new(0),
+                    errors: AtomicUsize::new(0),
+                    executions: AtomicUsize::new(0),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(LuaPool {
+            slots,
+            init: Mutex::new(Box::new(init)),
+            options,
+            recycled: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the number of Lua states in the pool.
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of currently running executions.
+    pub fn load(&self) -> usize {
+        self.slots
+            .iter()
+            .map(|slot| slot.load.load(Ordering::Acquire))
+            .sum()
+    }
+
+    /// Returns the total number of states recycled since the pool creation.
+    pub fn recycled(&self) -> usize {
+        self.recycled.load(Ordering::Relaxed)
+    }
+
+    /// Loads and executes a chunk (source or bytecode) on the least loaded state, passing `args`
+    /// to it and returning the results.
+    ///
+    /// The chunk is executed as an async function, so it can call async Rust functions.
+    pub async fn execute<'a, R>(&self, chunk: impl AsChunk<'a>, args: impl IntoLuaMulti) -> Result<R>
+    where
+        R: FromLuaMulti,
+    {
+        let slot = (self.slots.iter())
+            .min_by_key(|slot| slot.load.load(Ordering::Acquire))
+            .expect("pool is not empty");
+        slot.load.fetch_add(1, Ordering::AcqRel);
+        let guard = LoadGuard(&slot.load);
+
+        let (lua, generation) = slot.state.lock().clone();
+        let result = match lua.load(chunk).into_function() {
+            Ok(func) => func.call_async::<R>(args).await,
+            Err(err) => Err(err),
+        };
+        drop(guard);
+
+        slot.executions.fetch_add(1, Ordering::AcqRel);
+        if result.is_err() {
+            slot.errors.fetch_add(1, Ordering::AcqRel);
+        }
+        if self.needs_recycle(slot, &lua, &result) {
+            // Keep the current state if a new one cannot be created, it will be retried later
+            let _ = self.recycle(slot, generation);
+        }
+        result
+    }
+
+    fn needs_recycle<R>(&self, slot: &PoolSlot, lua: &Lua, result: &Result<R>) -> bool {
+        let exceeds = |limit: Option<usize>, value: usize| limit.is_some_and(|limit| value >= limit);
+        matches!(result, Err(Error::MemoryError(_)))
+            || exceeds(self.options.max_errors, slot.errors.load(Ordering::Acquire))
+            || exceeds(
+                self.options.max_executions,
+                slot.executions.load(Ordering::Acquire),
+            )
+            || exceeds(self.options.max_memory, lua.used_memory())
+    }
+
+    fn recycle(&self, slot: &PoolSlot, generation: usize) -> Result<()> {
+        let new_lua = (self.init.lock())()?;
+        let mut state = slot.state.lock();
+        // The state could be already recycled by another execution
+        if state.1 == generation {
+            *state = (new_lua, generation + 1);
+            slot.errors.store(0, Ordering::Release);
+            slot.executions.store(0, Ordering::Release);
+            self.recycled.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "send"))]
+mod assertions {
+    use super::*;
+
+    static_assertions::assert_impl_all!(LuaPool: Send, Sync);
+}
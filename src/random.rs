@@ -0,0 +1,145 @@
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::value::{MultiValue, Value};
+
+/// A source of random numbers backing `math.random`, see [`Lua::set_random_source`].
+///
+/// This trait is implemented for closures returning `u64`, so any random number generator can be
+/// used, e.g. `move || rng.next_u64()`.
+///
+/// [`Lua::set_random_source`]: crate::Lua::set_random_source
+pub trait RandomSource: MaybeSend + 'static {
+    /// Returns the next random 64-bit value.
+    ///
+    /// All bits of the value must be uniformly distributed.
+    fn next_u64(&mut self) -> u64;
+
+    /// Reseeds the source, called by `math.randomseed(x)`.
+    ///
+    /// The default implementation ignores the seed, which is suitable for sources that must not
+    /// be seeded from Lua (e.g. cryptographically secure generators).
+    fn seed(&mut self, seed: i64) {
+        let _ = seed;
+    }
+}
+
+impl<F: FnMut() -> u64 + MaybeSend + 'static> RandomSource for F {
+    fn next_u64(&mut self) -> u64 {
+        self()
+    }
+}
+
+// Registry flag set when the Lua functions are wrapped to use the random source
+const INSTALLED_KEY: &str = "__mlua_random_source_installed";
+
+// Replaces `math.random` and `math.randomseed` functions with versions that use the random source
+// set for the Lua state
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    if lua.named_registry_value::<bool>(INSTALLED_KEY)? {
+        return Ok(());
+    }
+    let Some(math) = lua.globals().get::<Option<Table>>("math")? else {
+        return Ok(());
+    };
+
+    let random = math.get::<Function>("random")?;
+    let new_random = lua.create_function(move |lua, args: MultiValue| {
+        let args2 = args.clone();
+        let res = lua
+            .lock()
+            .with_random_source(move |source| random_value(source, args2));
+        match res {
+            Some(res) => res,
+            None => random.call::<Value>(args),
+        }
+    })?;
+
+    let randomseed = math.get::<Function>("randomseed")?;
+    let new_randomseed = lua.create_function(move |lua, args: MultiValue| {
+        let seed = match args.front() {
+            #[allow(clippy::useless_conversion)]
+            Some(&Value::Integer(i)) => Some(i.into()),
+            Some(Value::Number(n)) => Some(*n as i64),
+            _ => None,
+        };
+        match lua
+            .lock()
+            .with_random_source(|source| seed.map(|seed| source.seed(seed)))
+        {
+            Some(_) => Ok(MultiValue::new()),
+            None => randomseed.call::<MultiValue>(args),
+        }
+    })?;
+
+    math.set("random", new_random)?;
+    math.set("randomseed", new_randomseed)?;
+    lua.set_named_registry_value(INSTALLED_KEY, true)
+}
+
+// Implements `math.random([m [, n]])` using the random source
+fn random_value(source: &mut dyn RandomSource, args: MultiValue) -> Result<Value> {
+    let ran = source.next_u64();
+    let arg = |i: usize| -> Result<i64> {
+        match args.get(i) {
+            #[allow(clippy::useless_conversion)]
+            Some(&Value::Integer(n)) => Ok(n.into()),
+            Some(Value::Number(n)) if cfg!(any(feature = "lua54", feature = "lua53")) && n.fract() != 0.0 => {
+                Err(bad_argument(i, "number has no integer representation"))
+            }
+            Some(Value::Number(n)) => Ok(n.floor() as i64),
+            Some(value) => Err(bad_argument(
+                i,
+                &format!("number expected, got {}", value.type_name()),
+            )),
+            None => Err(bad_argument(i, "number expected, got no value")),
+        }
+    };
+    let (low, up) = match args.len() {
+        // Float in the range [0, 1)
+        0 => return Ok(Value::Number((ran >> 11) as f64 * (0.5 / (1u64 << 52) as f64))),
+        1 => {
+            let up = arg(0)?;
+            // Lua 5.4 returns a random integer with all bits random
+            #[cfg(feature = "lua54")]
+            if up == 0 {
+                return Ok(Value::Integer(ran as i64));
+            }
+            (1, up)
+        }
+        2 => (arg(0)?, arg(1)?),
+        _ => return Err(Error::runtime("wrong number of arguments")),
+    };
+    if low > up {
+        return Err(bad_argument(args.len() - 1, "interval is empty"));
+    }
+    let n = project(ran, (up as u64).wrapping_sub(low as u64), source);
+    let value = n.wrapping_add(low as u64) as i64;
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    return Ok(Value::Integer(value));
+    #[cfg(not(any(feature = "lua54", feature = "lua53")))]
+    return Ok(Value::Number(value as f64));
+}
+
+// Projects a random integer into the interval [0, n] without bias (as Lua 5.4 does)
+fn project(mut ran: u64, n: u64, source: &mut dyn RandomSource) -> u64 {
+    if n & n.wrapping_add(1) == 0 {
+        // `n + 1` is a power of 2
+        return ran & n;
+    }
+    // Compute the smallest `2^b - 1` not smaller than `n`
+    let lim = u64::MAX >> n.leading_zeros();
+    loop {
+        ran &= lim;
+        if ran <= n {
+            return ran;
+        }
+        ran = source.next_u64();
+    }
+}
+
+fn bad_argument(i: usize, msg: &str) -> Error {
+    Error::runtime(format!("bad argument #{} to 'random' ({msg})", i + 1))
+}
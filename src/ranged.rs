@@ -0,0 +1,131 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::value::{FromLua, IntoLua, Value};
+
+/// An integer argument or value that must be within the `MIN..=MAX` range.
+///
+/// Converting a Lua value to `Ranged` fails with [`Error::OutOfRange`] if the number is outside
+/// of the range, so bindings don't need to repeat the range checks (and error messages) by hand.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Ranged, Result};
+/// # fn main() -> Result<()> {
+/// # let lua = Lua::new();
+/// let set_volume = lua.create_function(|_, volume: Ranged<u8, 0, 100>| Ok(volume.into_inner()))?;
+/// assert_eq!(set_volume.call::<u8>(50)?, 50);
+/// assert!(set_volume.call::<u8>(101).is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Error::OutOfRange`]: crate::Error::OutOfRange
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ranged<T, const MIN: i64, const MAX: i64>(pub T);
+
+impl<T, const MIN: i64, const MAX: i64> Ranged<T, MIN, MAX> {
+    /// Returns the wrapped value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> Deref for Ranged<T, MIN, MAX> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> DerefMut for Ranged<T, MIN, MAX> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Display, const MIN: i64, const MAX: i64> fmt::Display for Ranged<T, MIN, MAX> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: IntoLua, const MIN: i64, const MAX: i64> IntoLua for Ranged<T, MIN, MAX> {
+    #[inline]
+    fn into_lua(self, lua: &Lua) -> Result<Value> {
+        self.0.into_lua(lua)
+    }
+}
+
+impl<T, const MIN: i64, const MAX: i64> FromLua for Ranged<T, MIN, MAX>
+where
+    T: TryFrom<i128>,
+{
+    fn from_lua(value: Value, _: &Lua) -> Result<Self> {
+        let (min, max) = (MIN as i128, MAX as i128);
+        let n = check_range(&value, min, max, None)?;
+        // The range can be wider than `T`
+        T::try_from(n)
+            .map(Ranged)
+            .map_err(|_| out_of_range(format_number(&value), min, max, None))
+    }
+}
+
+// Converts a numeric value to an integer in the `min..=max` range
+pub(crate) fn check_range(value: &Value, min: i128, max: i128, label: Option<&str>) -> Result<i128> {
+    let n = match *value {
+        Value::Integer(i) => i as i128,
+        Value::Number(n) => {
+            // Also rejects NaN
+            if !(min as f64..=max as f64).contains(&n) {
+                return Err(out_of_range(format_number(value), min, max, label));
+            }
+            if n.fract() != 0.0 {
+                return Err(Error::FromLuaConversionError {
+                    from: "number",
+                    to: "integer".to_string(),
+                    message: Some(format!("number {n} has no integer representation")),
+                });
+            }
+            n as i128
+        }
+        _ => {
+            return Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "integer".to_string(),
+                message: Some(format!("expected number, got {}", value.type_name())),
+            })
+        }
+    };
+    if n < min || n > max {
+        return Err(out_of_range(format_number(value), min, max, label));
+    }
+    Ok(n)
+}
+
+fn out_of_range(value: StdString, min: i128, max: i128, label: Option<&str>) -> Error {
+    Error::OutOfRange {
+        value,
+        min,
+        max,
+        label: label.map(|s| s.to_string()),
+    }
+}
+
+fn format_number(value: &Value) -> StdString {
+    match *value {
+        Value::Integer(i) => i.to_string(),
+        Value::Number(n) if n.is_nan() => "nan".to_string(),
+        Value::Number(n) if n.is_infinite() => if n > 0.0 { "inf" } else { "-inf" }.to_string(),
+        Value::Number(n) => format!("{n:?}"),
+        _ => value.type_name().to_string(),
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::os::raw::c_int;
+use std::string::String as StdString;
+
+use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::types::{Integer, RegistryKey};
+use crate::util::{check_stack, StackGuard};
+use crate::value::Value;
+
+/// Handle for auditing the Lua registry entries created by mlua, see [`Lua::registry`].
+///
+/// Only entries created through the `Lua` API are tracked: named values set by
+/// [`Lua::set_named_registry_value`] and slots allocated for [`RegistryKey`]s. This is useful
+/// for long-lived processes to find out which entries keep growing the registry.
+///
+/// [`Lua::registry`]: crate::Lua::registry
+/// [`Lua::set_named_registry_value`]: crate::Lua::set_named_registry_value
+pub struct Registry<'a> {
+    lua: &'a Lua,
+}
+
+/// A registry slot allocated for a [`RegistryKey`], see [`Registry::entries`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryEntry {
+    id: c_int,
+    generation: u64,
+    label: Option<StdString>,
+    dropped: bool,
+}
+
+impl RegistryEntry {
+    /// Returns the underlying Lua reference of the slot (same as [`RegistryKey::id`]).
+    pub fn id(&self) -> c_int {
+        self.id
+    }
+
+    /// Returns the debug label attached with [`Registry::set_label`].
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Returns `true` if the [`RegistryKey`] owning the slot was dropped, but the slot has not been
+    /// reclaimed yet by [`Lua::expire_registry_values`].
+    ///
+    /// [`Lua::expire_registry_values`]: crate::Lua::expire_registry_values
+    pub fn is_dropped(&self) -> bool {
+        self.dropped
+    }
+}
+
+/// Statistics of the registry entries created by mlua, see [`Registry::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RegistryStats {
+    /// Number of named values.
+    pub named_values: usize,
+    /// Number of slots allocated for [`RegistryKey`]s (including dropped keys).
+    pub keys: usize,
+    /// Number of slots with a debug label.
+    pub labeled_keys: usize,
+    /// Number of slots whose [`RegistryKey`] was dropped, but not reclaimed yet.
+    pub dropped_keys: usize,
+    /// Total number of slots reclaimed by [`Lua::expire_registry_values`].
+    ///
+    /// [`Lua::expire_registry_values`]: crate::Lua::expire_registry_values
+    pub expired_keys: usize,
+}
+
+// Tracks the registry entries created by mlua (stored in `ExtraData`)
+#[derive(Default)]
+pub(crate) struct RegistryTracking {
+    names: BTreeSet<StdString>,
+    keys: BTreeMap<c_int, KeyInfo>,
+    generation: u64,
+    expired: usize,
+}
+
+struct KeyInfo {
+    generation: u64,
+    label: Option<StdString>,
+}
+
+impl RegistryTracking {
+    pub(crate) fn set_name(&mut self, name: &str, is_set: bool) {
+        if !is_set {
+            self.names.remove(name);
+        } else if !self.names.contains(name) {
+            self.names.insert(name.to_owned());
+        }
+    }
+
+    // Called when a slot is allocated (or reused) for a new `RegistryKey`
+    pub(crate) fn add_key(&mut self, id: c_int) {
+        self.generation += 1;
+        let info = KeyInfo {
+            generation: self.generation,
+            label: None,
+        };
+        self.keys.insert(id, info);
+    }
+
+    pub(crate) fn remove_key(&mut self, id: c_int) {
+        self.keys.remove(&id);
+    }
+
+    pub(crate) fn expire_keys(&mut self, ids: &[c_int]) {
+        for id in ids {
+            self.keys.remove(id);
+        }
+        self.expired += ids.len();
+    }
+
+    fn is_valid(&self, entry: &RegistryEntry) -> bool {
+        matches!(self.keys.get(&entry.id), Some(info) if info.generation == entry.generation)
+    }
+}
+
+impl<'a> Registry<'a> {
+    pub(crate) fn new(lua: &'a Lua) -> Self {
+        Registry { lua }
+    }
+
+    /// Returns names of the values set by [`Lua::set_named_registry_value`] (in sorted order).
+    ///
+    /// [`Lua::set_named_registry_value`]: crate::Lua::set_named_registry_value
+    pub fn named_values(&self) -> Vec<StdString> {
+        let lua = self.lua.lock();
+        lua.with_registry_tracking(|tracking| tracking.names.iter().cloned().collect())
+    }
+
+    /// Returns a named value.
+    ///
+    /// This is equivalent to [`Lua::named_registry_value`].
+    ///
+    /// [`Lua::named_registry_value`]: crate::Lua::named_registry_value
+    pub fn named_value(&self, name: &str) -> Result<Value> {
+        self.lua.named_registry_value(name)
+    }
+
+    /// Removes a named value.
+    ///
+    /// Returns `true` if the value was set.
+    pub fn remove_named_value(&self, name: &str) -> Result<bool> {
+        let was_set = !self.named_value(name)?.is_nil();
+        self.lua.unset_named_registry_value(name)?;
+        Ok(was_set)
+    }
+
+    /// Returns slots allocated for [`RegistryKey`]s (in the order of their ids).
+    ///
+    /// The returned entries are snapshots. If a slot is reclaimed and reused for another key, the
+    /// old entry is no longer valid and cannot be used to access the new value.
+    pub fn entries(&self) -> Vec<RegistryEntry> {
+        let lua = self.lua.lock();
+        let dropped = lua.registry_unref_ids().into_iter().collect::<HashSet<_>>();
+        lua.with_registry_tracking(|tracking| {
+            (tracking.keys.iter())
+                .map(|(&id, info)| RegistryEntry {
+                    id,
+                    generation: info.generation,
+                    label: info.label.clone(),
+                    dropped: dropped.contains(&id),
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the value stored in the slot.
+    ///
+    /// Returns `None` if the entry is no longer valid.
+    pub fn value(&self, entry: &RegistryEntry) -> Result<Option<Value>> {
+        let lua = self.lua.lock();
+        if !lua.with_registry_tracking(|tracking| tracking.is_valid(entry)) {
+            return Ok(None);
+        }
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, entry.id as Integer);
+            Ok(Some(lua.pop_value()))
+        }
+    }
+
+    /// Removes the value stored in the slot.
+    ///
+    /// If the [`RegistryKey`] owning the slot was dropped, the slot is reclaimed immediately.
+    /// Otherwise the value is replaced with `nil`, but the slot stays allocated until the key is
+    /// dropped, so the key never refers to a value of another key.
+    ///
+    /// Returns `false` if the entry is no longer valid.
+    pub fn remove(&self, entry: &RegistryEntry) -> Result<bool> {
+        let lua = self.lua.lock();
+        if !lua.with_registry_tracking(|tracking| tracking.is_valid(entry)) {
+            return Ok(false);
+        }
+        let state = lua.state();
+        unsafe {
+            if lua.take_registry_unref_id(entry.id) {
+                ffi::luaL_unref(state, ffi::LUA_REGISTRYINDEX, entry.id);
+                lua.with_registry_tracking(|tracking| tracking.expire_keys(&[entry.id]));
+            } else {
+                let _sg = StackGuard::new(state);
+                check_stack(state, 1)?;
+
+                ffi::lua_pushnil(state);
+                ffi::lua_rawseti(state, ffi::LUA_REGISTRYINDEX, entry.id as Integer);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Attaches a debug label to the [`RegistryKey`], reported by [`Registry::entries`].
+    ///
+    /// The label is removed when the slot is reclaimed or reused.
+    pub fn set_label(&self, key: &RegistryKey, label: impl Into<StdString>) -> Result<()> {
+        let lua = self.lua.lock();
+        if !lua.owns_registry_value(key) {
+            return Err(Error::MismatchedRegistryKey);
+        }
+        lua.with_registry_tracking(|tracking| {
+            if let Some(info) = tracking.keys.get_mut(&key.id()) {
+                info.label = Some(label.into());
+            }
+        });
+        Ok(())
+    }
+
+    /// Returns the debug label attached to the [`RegistryKey`].
+    pub fn label(&self, key: &RegistryKey) -> Option<StdString> {
+        let lua = self.lua.lock();
+        if !lua.owns_registry_value(key) {
+            return None;
+        }
+        lua.with_registry_tracking(|tracking| tracking.keys.get(&key.id())?.label.clone())
+    }
+
+    /// Returns statistics of the tracked registry entries.
+    pub fn stats(&self) -> RegistryStats {
+        let lua = self.lua.lock();
+        let dropped_keys = lua.registry_unref_ids().len();
+        lua.with_registry_tracking(|tracking| RegistryStats {
+            named_values: tracking.names.len(),
+            keys: tracking.keys.len(),
+            labeled_keys: tracking.keys.values().filter(|info| info.label.is_some()).count(),
+            dropped_keys,
+            expired_keys: tracking.expired,
+        })
+    }
+}
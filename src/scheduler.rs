@@ -0,0 +1,127 @@
+#[cfg(not(feature = "luau"))]
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Instant;
+
+use crate::error::Result;
+
+#[cfg(feature = "send")]
+pub(crate) type TaskFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type TaskFuture = Pin<Box<dyn Future<Output = Result<()>>>>;
+
+// Creates a cleanup future from the value of a collected userdata
+#[cfg(all(feature = "send", not(feature = "luau")))]
+pub(crate) type AsyncDestructor = Box<dyn Fn(Box<dyn Any>) -> TaskFuture + Send>;
+
+#[cfg(all(not(feature = "send"), not(feature = "luau")))]
+pub(crate) type AsyncDestructor = Box<dyn Fn(Box<dyn Any>) -> TaskFuture>;
+
+// A future registered by `Lua::spawn_task` or `Lua::spawn_thread` (stored in `ExtraData`)
+pub(crate) struct Task {
+    future: TaskFuture,
+    waker: Arc<TaskWaker>,
+    // Cleanup tasks (of async destructors) outlive the Lua state and are completed by `Lua::close`
+    #[cfg(not(feature = "luau"))]
+    cleanup: bool,
+}
+
+// Flag set when the task is woken up and must be polled again
+struct TaskWaker {
+    woken: AtomicBool,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+    }
+}
+
+impl Task {
+    pub(crate) fn new(future: TaskFuture) -> Self {
+        // New tasks are polled for the first time without waiting for a wake up
+        let waker = Arc::new(TaskWaker {
+            woken: AtomicBool::new(true),
+        });
+        Task {
+            future,
+            waker,
+            #[cfg(not(feature = "luau"))]
+            cleanup: false,
+        }
+    }
+
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn new_cleanup(future: TaskFuture) -> Self {
+        Task {
+            cleanup: true,
+            ..Self::new(future)
+        }
+    }
+
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn is_cleanup(&self) -> bool {
+        self.cleanup
+    }
+}
+
+// Polls woken tasks (in the order of registration) until none of them is woken or the deadline
+// passes. At least one task is polled if any is woken.
+//
+// Finished tasks are removed, the first failed task stops polling and its error is returned.
+pub(crate) fn poll_tasks(tasks: &mut Vec<Task>, deadline: Instant) -> Result<()> {
+    let (mut i, mut progress) = (0, false);
+    loop {
+        if i == tasks.len() {
+            if !progress {
+                return Ok(());
+            }
+            (i, progress) = (0, false);
+            continue;
+        }
+
+        let task = &mut tasks[i];
+        if !task.waker.woken.swap(false, Ordering::AcqRel) {
+            i += 1;
+            continue;
+        }
+        progress = true;
+
+        let waker = Waker::from(task.waker.clone());
+        match task.future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(res) => {
+                tasks.remove(i);
+                res?;
+            }
+            Poll::Pending => i += 1,
+        }
+
+        if Instant::now() >= deadline {
+            return Ok(());
+        }
+    }
+}
+
+// Runs the tasks to completion on the executor of the caller.
+//
+// Errors of the tasks are ignored.
+#[cfg(not(feature = "luau"))]
+pub(crate) async fn complete_tasks(mut tasks: Vec<Task>) {
+    std::future::poll_fn(|cx| {
+        tasks.retain_mut(|task| task.future.as_mut().poll(cx).is_pending());
+        match tasks.is_empty() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    })
+    .await
+}
@@ -1,23 +1,85 @@
 use std::cell::RefCell;
+use std::fmt;
 use std::os::raw::c_void;
 use std::rc::Rc;
 use std::result::Result as StdResult;
 use std::string::String as StdString;
+use std::{slice, str};
 
 use rustc_hash::FxHashSet;
 use serde::de::{self, IntoDeserializer};
 
 use crate::error::{Error, Result};
+use crate::state::Lua;
+use crate::string::String;
 use crate::table::{Table, TablePairs, TableSequence};
 use crate::userdata::AnyUserData;
 use crate::value::Value;
 
 /// A struct for deserializing Lua values into Rust values.
+///
+/// By default, strings are copied into the deserialized values. With a [`StringArena`] attached
+/// (see [`Deserializer::with_arena`]), types can borrow `&str` and `&[u8]` directly from Lua
+/// strings for the arena lifetime.
 #[derive(Debug)]
-pub struct Deserializer {
+pub struct Deserializer<'de> {
     value: Value,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    arena: Option<&'de StringArena>,
+}
+
+/// Keeps Lua strings alive while they are borrowed by deserialized values.
+///
+/// Deserializing with an arena allows zero-copy `&str` and `&[u8]` fields (and `Cow` fields
+/// marked with `#[serde(borrow)]`), avoiding an allocation per string when decoding big tables.
+/// Every borrowed string holds a reference in the arena until it's dropped.
+///
+/// See [`LuaSerdeExt::from_value_borrowed`].
+///
+/// [`LuaSerdeExt::from_value_borrowed`]: crate::LuaSerdeExt::from_value_borrowed
+#[derive(Default)]
+pub struct StringArena {
+    strings: RefCell<Vec<String>>,
+    // Every Lua state that owns a kept string
+    luas: RefCell<Vec<Lua>>,
+}
+
+impl StringArena {
+    /// Creates a new empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of strings kept in the arena.
+    pub fn len(&self) -> usize {
+        self.strings.borrow().len()
+    }
+
+    /// Returns `true` if the arena does not keep any strings.
+    pub fn is_empty(&self) -> bool {
+        self.strings.borrow().is_empty()
+    }
+
+    fn keep(&self, s: String) -> &[u8] {
+        let bytes = s.as_bytes();
+        // Lua strings are immutable and never moved by the garbage collector, so the data stays valid
+        // while the string reference (and its Lua state) are kept in the arena
+        let data = unsafe { slice::from_raw_parts(bytes.as_ptr(), bytes.len()) };
+        drop(bytes);
+        let mut luas = self.luas.borrow_mut();
+        if !luas.iter().any(|lua| lua.weak() == s.0.lua) {
+            luas.push(s.0.lua.upgrade());
+        }
+        self.strings.borrow_mut().push(s);
+        data
+    }
+}
+
+impl fmt::Debug for StringArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StringArena").field("len", &self.len()).finish()
+    }
 }
 
 /// A struct with options to change default deserializer behavior.
@@ -93,7 +155,7 @@ impl Options {
     }
 }
 
-impl Deserializer {
+impl<'de> Deserializer<'de> {
     /// Creates a new Lua Deserializer for the `Value`.
     pub fn new(value: Value) -> Self {
         Self::new_with_options(value, Options::default())
@@ -105,19 +167,33 @@ impl Deserializer {
             value,
             options,
             visited: Rc::new(RefCell::new(FxHashSet::default())),
+            arena: None,
         }
     }
 
-    fn from_parts(value: Value, options: Options, visited: Rc<RefCell<FxHashSet<*const c_void>>>) -> Self {
+    /// Attaches a [`StringArena`] to borrow Lua strings during deserialization.
+    #[must_use]
+    pub fn with_arena(mut self, arena: &'de StringArena) -> Self {
+        self.arena = Some(arena);
+        self
+    }
+
+    fn from_parts(
+        value: Value,
+        options: Options,
+        visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+        arena: Option<&'de StringArena>,
+    ) -> Self {
         Deserializer {
             value,
             options,
             visited,
+            arena,
         }
     }
 }
 
-impl<'de> serde::Deserializer<'de> for Deserializer {
+impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
     type Error = Error;
 
     #[inline]
@@ -134,9 +210,18 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
             Value::Number(n) => visitor.visit_f64(n.into()),
             #[cfg(feature = "luau")]
             Value::Vector(_) => self.deserialize_seq(visitor),
-            Value::String(s) => match s.to_str() {
-                Ok(s) => visitor.visit_str(&s),
-                Err(_) => visitor.visit_bytes(&s.as_bytes()),
+            Value::String(s) => match self.arena {
+                Some(arena) => {
+                    let bytes = arena.keep(s);
+                    match str::from_utf8(bytes) {
+                        Ok(s) => visitor.visit_borrowed_str(s),
+                        Err(_) => visitor.visit_borrowed_bytes(bytes),
+                    }
+                }
+                None => match s.to_str() {
+                    Ok(s) => visitor.visit_str(&s),
+                    Err(_) => visitor.visit_bytes(&s.as_bytes()),
+                },
             },
             Value::Table(ref t) if t.raw_len() > 0 || t.is_array() => self.deserialize_seq(visitor),
             Value::Table(_) => self.deserialize_map(visitor),
@@ -224,6 +309,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
             value,
             options: self.options,
             visited: self.visited,
+            arena: self.arena,
         })
     }
 
@@ -240,6 +326,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
                     next: 0,
                     options: self.options,
                     visited: self.visited,
+                    arena: self.arena,
                 };
                 visitor.visit_seq(&mut deserializer)
             }
@@ -251,6 +338,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
                     seq: t.sequence_values(),
                     options: self.options,
                     visited: self.visited,
+                    arena: self.arena,
                 };
                 let seq = visitor.visit_seq(&mut deserializer)?;
                 if deserializer.seq.count() == 0 {
@@ -299,6 +387,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
                     value: None,
                     options: self.options,
                     visited: self.visited,
+                    arena: self.arena,
                     processed: 0,
                 };
                 let map = visitor.visit_map(&mut deserializer)?;
@@ -376,13 +465,14 @@ impl<'de> serde::Deserializer<'de> for Deserializer {
     }
 }
 
-struct SeqDeserializer<'a> {
+struct SeqDeserializer<'a, 'de> {
     seq: TableSequence<'a, Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    arena: Option<&'de StringArena>,
 }
 
-impl<'de> de::SeqAccess<'de> for SeqDeserializer<'_> {
+impl<'de> de::SeqAccess<'de> for SeqDeserializer<'_, 'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -399,7 +489,7 @@ impl<'de> de::SeqAccess<'de> for SeqDeserializer<'_> {
                         continue;
                     }
                     let visited = Rc::clone(&self.visited);
-                    let deserializer = Deserializer::from_parts(value, self.options, visited);
+                    let deserializer = Deserializer::from_parts(value, self.options, visited, self.arena);
                     return seed.deserialize(deserializer).map(Some);
                 }
                 None => return Ok(None),
@@ -416,15 +506,16 @@ impl<'de> de::SeqAccess<'de> for SeqDeserializer<'_> {
 }
 
 #[cfg(feature = "luau")]
-struct VecDeserializer {
+struct VecDeserializer<'de> {
     vec: crate::types::Vector,
     next: usize,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    arena: Option<&'de StringArena>,
 }
 
 #[cfg(feature = "luau")]
-impl<'de> de::SeqAccess<'de> for VecDeserializer {
+impl<'de> de::SeqAccess<'de> for VecDeserializer<'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -435,7 +526,8 @@ impl<'de> de::SeqAccess<'de> for VecDeserializer {
             Some(&n) => {
                 self.next += 1;
                 let visited = Rc::clone(&self.visited);
-                let deserializer = Deserializer::from_parts(Value::Number(n as _), self.options, visited);
+                let value = Value::Number(n as _);
+                let deserializer = Deserializer::from_parts(value, self.options, visited, self.arena);
                 seed.deserialize(deserializer).map(Some)
             }
             None => Ok(None),
@@ -489,16 +581,17 @@ impl Iterator for MapPairs<'_> {
     }
 }
 
-struct MapDeserializer<'a> {
+struct MapDeserializer<'a, 'de> {
     pairs: MapPairs<'a>,
     value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    arena: Option<&'de StringArena>,
     processed: usize,
 }
 
-impl<'a> MapDeserializer<'a> {
-    fn next_key_deserializer(&mut self) -> Result<Option<Deserializer>> {
+impl<'de> MapDeserializer<'_, 'de> {
+    fn next_key_deserializer(&mut self) -> Result<Option<Deserializer<'de>>> {
         loop {
             match self.pairs.next() {
                 Some(item) => {
@@ -513,7 +606,7 @@ impl<'a> MapDeserializer<'a> {
                     self.processed += 1;
                     self.value = Some(value);
                     let visited = Rc::clone(&self.visited);
-                    let key_de = Deserializer::from_parts(key, self.options, visited);
+                    let key_de = Deserializer::from_parts(key, self.options, visited, self.arena);
                     return Ok(Some(key_de));
                 }
                 None => return Ok(None),
@@ -521,18 +614,18 @@ impl<'a> MapDeserializer<'a> {
         }
     }
 
-    fn next_value_deserializer(&mut self) -> Result<Deserializer> {
+    fn next_value_deserializer(&mut self) -> Result<Deserializer<'de>> {
         match self.value.take() {
             Some(value) => {
                 let visited = Rc::clone(&self.visited);
-                Ok(Deserializer::from_parts(value, self.options, visited))
+                Ok(Deserializer::from_parts(value, self.options, visited, self.arena))
             }
             None => Err(de::Error::custom("value is missing")),
         }
     }
 }
 
-impl<'de> de::MapAccess<'de> for MapDeserializer<'_> {
+impl<'de> de::MapAccess<'de> for MapDeserializer<'_, 'de> {
     type Error = Error;
 
     fn next_key_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -564,16 +657,17 @@ impl<'de> de::MapAccess<'de> for MapDeserializer<'_> {
     }
 }
 
-struct EnumDeserializer {
+struct EnumDeserializer<'de> {
     variant: StdString,
     value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    arena: Option<&'de StringArena>,
 }
 
-impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+impl<'de> de::EnumAccess<'de> for EnumDeserializer<'de> {
     type Error = Error;
-    type Variant = VariantDeserializer;
+    type Variant = VariantDeserializer<'de>;
 
     fn variant_seed<T>(self, seed: T) -> Result<(T::Value, Self::Variant)>
     where
@@ -584,18 +678,20 @@ impl<'de> de::EnumAccess<'de> for EnumDeserializer {
             value: self.value,
             options: self.options,
             visited: self.visited,
+            arena: self.arena,
         };
         seed.deserialize(variant).map(|v| (v, variant_access))
     }
 }
 
-struct VariantDeserializer {
+struct VariantDeserializer<'de> {
     value: Option<Value>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    arena: Option<&'de StringArena>,
 }
 
-impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+impl<'de> de::VariantAccess<'de> for VariantDeserializer<'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -613,7 +709,12 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
         T: de::DeserializeSeed<'de>,
     {
         match self.value {
-            Some(value) => seed.deserialize(Deserializer::from_parts(value, self.options, self.visited)),
+            Some(value) => seed.deserialize(Deserializer::from_parts(
+                value,
+                self.options,
+                self.visited,
+                self.arena,
+            )),
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"newtype variant",
@@ -627,7 +728,7 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
     {
         match self.value {
             Some(value) => serde::Deserializer::deserialize_seq(
-                Deserializer::from_parts(value, self.options, self.visited),
+                Deserializer::from_parts(value, self.options, self.visited, self.arena),
                 visitor,
             ),
             None => Err(de::Error::invalid_type(
@@ -643,7 +744,7 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
     {
         match self.value {
             Some(value) => serde::Deserializer::deserialize_map(
-                Deserializer::from_parts(value, self.options, self.visited),
+                Deserializer::from_parts(value, self.options, self.visited, self.arena),
                 visitor,
             ),
             None => Err(de::Error::invalid_type(
@@ -654,6 +755,9 @@ impl<'de> de::VariantAccess<'de> for VariantDeserializer {
     }
 }
 
+// Maximum nesting depth of tables when serializing Lua values
+pub(crate) const MAX_SERIALIZE_DEPTH: usize = 128;
+
 // Adds `ptr` to the `visited` map and removes on drop
 // Used to track recursive tables but allow to traverse same tables multiple times
 pub(crate) struct RecursionGuard {
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+
+use serde::de::{self as serde_de, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::error::{Error, Result};
+use crate::serde::de;
+use crate::serde::LuaSerdeExt;
+use crate::state::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::Integer;
+use crate::value::{NumberPolicy, SerializableValue, Value};
+
+// Creates the `json` module table
+pub(crate) fn create_module(lua: &Lua) -> Result<Table> {
+    let json = lua.create_table()?;
+    json.set("encode", lua.create_function(json_encode)?)?;
+    json.set("decode", lua.create_function(json_decode)?)?;
+    json.set("null", lua.null())?;
+    json.set("array_mt", lua.array_metatable())?;
+    Ok(json)
+}
+
+// Implements `json.encode(value [, options])`
+fn json_encode(lua: &Lua, (value, options): (Value, Option<Table>)) -> Result<String> {
+    let mut pretty = false;
+    let mut de_options = de::Options::new();
+    if let Some(options) = options {
+        pretty = options.get::<Option<bool>>("pretty")?.unwrap_or(false);
+        let sort_keys = options.get::<Option<bool>>("sort_keys")?;
+        de_options = de_options.sort_keys(sort_keys.unwrap_or(false));
+    }
+
+    let value = SerializableValue::new(&value, de_options, None);
+    let result = match pretty {
+        true => serde_json::to_vec_pretty(&value),
+        false => serde_json::to_vec(&value),
+    };
+    lua.create_string(result.map_err(|err| Error::SerializeError(err.to_string()))?)
+}
+
+// Implements `json.decode(s)`
+fn json_decode(lua: &Lua, s: String) -> Result<Value> {
+    let bytes = s.as_bytes();
+    let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+    let value = (LuaValueSeed(lua).deserialize(&mut deserializer))
+        .and_then(|value| deserializer.end().map(|_| value))
+        .map_err(|err| Error::DeserializeError(err.to_string()))?;
+    Ok(value)
+}
+
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+// Builds Lua values directly from the JSON deserializer (without intermediate `serde_json::Value`)
+#[derive(Clone, Copy)]
+struct LuaValueSeed<'a>(&'a Lua);
+
+impl<'de> DeserializeSeed<'de> for LuaValueSeed<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> StdResult<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LuaValueSeed<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> StdResult<Value, E> {
+        Ok(self.0.null())
+    }
+
+    fn visit_bool<E>(self, b: bool) -> StdResult<Value, E> {
+        Ok(Value::Boolean(b))
+    }
+
+    fn visit_i64<E: serde_de::Error>(self, i: i64) -> StdResult<Value, E> {
+        #[allow(clippy::useless_conversion)]
+        match Integer::try_from(i) {
+            Ok(i) => Ok(Value::Integer(i)),
+            Err(_) => self.visit_f64(i as f64),
+        }
+    }
+
+    fn visit_u64<E: serde_de::Error>(self, u: u64) -> StdResult<Value, E> {
+        match i64::try_from(u) {
+            Ok(i) => self.visit_i64(i),
+            Err(_) => self.visit_f64(u as f64),
+        }
+    }
+
+    fn visit_f64<E>(self, n: f64) -> StdResult<Value, E> {
+        Ok(Value::Number(n).canonicalize_numbers(NumberPolicy::PreferInteger))
+    }
+
+    fn visit_str<E: serde_de::Error>(self, s: &str) -> StdResult<Value, E> {
+        self.0.create_string(s).map(Value::String).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> StdResult<Value, A::Error> {
+        let table = (self.0.create_table_with_capacity(seq.size_hint().unwrap_or(0), 0))
+            .map_err(serde_de::Error::custom)?;
+        table.set_metatable(Some(self.0.array_metatable()));
+        let mut i = 1;
+        while let Some(value) = seq.next_element_seed(self)? {
+            table.raw_set(i, value).map_err(serde_de::Error::custom)?;
+            i += 1;
+        }
+        Ok(Value::Table(table))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> StdResult<Value, A::Error> {
+        let mut key = map.next_key::<StdString>()?;
+        // Numbers are passed as maps with a special key if `arbitrary_precision` is enabled
+        if key.as_deref() == Some(NUMBER_TOKEN) {
+            let number = map.next_value::<StdString>()?;
+            return match number.parse::<i64>() {
+                Ok(i) => self.visit_i64(i),
+                Err(_) => self.visit_f64(number.parse().map_err(serde_de::Error::custom)?),
+            };
+        }
+
+        let table = (self.0.create_table_with_capacity(0, map.size_hint().unwrap_or(0)))
+            .map_err(serde_de::Error::custom)?;
+        while let Some(k) = key {
+            let value = map.next_value_seed(self)?;
+            table.raw_set(k, value).map_err(serde_de::Error::custom)?;
+            key = map.next_key()?;
+        }
+        Ok(Value::Table(table))
+    }
+}
@@ -2,10 +2,10 @@
 
 use std::os::raw::c_void;
 
-use serde::de::DeserializeOwned;
+use serde::de::{Deserialize, DeserializeOwned};
 use serde::ser::Serialize;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::private::Sealed;
 use crate::state::Lua;
 use crate::table::Table;
@@ -129,6 +129,59 @@ pub trait LuaSerdeExt: Sealed {
     where
         T: Serialize + ?Sized;
 
+    /// Serializes `T` into an existing table, updating it in place.
+    ///
+    /// `T` must serialize to a table (e.g. a struct, map or sequence). Nested tables present in
+    /// both the target table and the serialized value are updated recursively, so references to
+    /// them held by Lua code remain valid. Keys that are not present in the serialized value are
+    /// removed, unless the [`merge_tables`] option is set.
+    ///
+    /// The metatable of the target table is kept, except that the [`array_metatable`] is attached
+    /// to updated sequences if the [`set_array_metatable`] option is set.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`merge_tables`]: ser::Options::merge_tables
+    /// [`set_array_metatable`]: ser::Options::set_array_metatable
+    /// [`array_metatable`]: #tymethod.array_metatable
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt, SerializeOptions, Table};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct State {
+    ///     score: u32,
+    ///     player: Player,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Player {
+    ///     name: String,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let state: Table = lua.load("{score = 0, player = {name = 'unknown', level = 1}}").eval()?;
+    ///     lua.globals().set("state", &state)?;
+    ///     lua.load("player = state.player").exec()?;
+    ///
+    ///     let new_state = State { score: 10, player: Player { name: "John".into() } };
+    ///     let options = SerializeOptions::new().merge_tables(true);
+    ///     lua.serialize_into(&state, &new_state, options)?;
+    ///
+    ///     lua.load(r#"
+    ///         assert(state.score == 10)
+    ///         assert(player == state.player and player.name == "John" and player.level == 1)
+    ///     "#).exec()
+    /// }
+    /// ```
+    fn serialize_into<T>(&self, table: &Table, t: &T, options: ser::Options) -> Result<()>
+    where
+        T: Serialize + ?Sized;
+
     /// Deserializes a [`Value`] into any serde deserializable object.
     ///
     /// Requires `feature = "serialize"`
@@ -191,6 +244,47 @@ pub trait LuaSerdeExt: Sealed {
     /// ```
     #[allow(clippy::wrong_self_convention)]
     fn from_value_with<T: DeserializeOwned>(&self, value: Value, options: de::Options) -> Result<T>;
+
+    /// Deserializes a [`Value`] into a serde deserializable object that borrows strings.
+    ///
+    /// Unlike [`LuaSerdeExt::from_value`], the target type can contain `&str` and `&[u8]` fields
+    /// pointing directly to Lua strings, without copying them. The strings are kept alive by
+    /// the provided [`StringArena`].
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`Value`]: crate::Value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt};
+    /// use mlua::serde::StringArena;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// struct User<'a> {
+    ///     name: &'a str,
+    ///     tags: Vec<&'a str>,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let val = lua.load(r#"{name = "John Smith", tags = {"admin"}}"#).eval()?;
+    ///     let arena = StringArena::new();
+    ///     let u: User = lua.from_value_borrowed(val, &arena)?;
+    ///
+    ///     assert_eq!(u, User { name: "John Smith", tags: vec!["admin"] });
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    fn from_value_borrowed<'de, T: Deserialize<'de>>(
+        &self,
+        value: Value,
+        arena: &'de StringArena,
+    ) -> Result<T>;
 }
 
 impl LuaSerdeExt for Lua {
@@ -210,14 +304,28 @@ impl LuaSerdeExt for Lua {
     where
         T: Serialize + ?Sized,
     {
-        t.serialize(ser::Serializer::new(self))
+        // Temporary values created during serialization are released at once
+        self.value_scope(|_| t.serialize(ser::Serializer::new(self)))
     }
 
     fn to_value_with<T>(&self, t: &T, options: ser::Options) -> Result<Value>
     where
         T: Serialize + ?Sized,
     {
-        t.serialize(ser::Serializer::new_with_options(self, options))
+        self.value_scope(|_| t.serialize(ser::Serializer::new_with_options(self, options)))
+    }
+
+    fn serialize_into<T>(&self, table: &Table, t: &T, options: ser::Options) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        match self.to_value_with(t, options)? {
+            Value::Table(value) => update_table(self, table, &value, options.merge_tables),
+            value => Err(Error::SerializeError(format!(
+                "cannot serialize {} into a table",
+                value.type_name()
+            ))),
+        }
     }
 
     fn from_value<T>(&self, value: Value) -> Result<T>
@@ -233,6 +341,40 @@ impl LuaSerdeExt for Lua {
     {
         T::deserialize(de::Deserializer::new_with_options(value, options))
     }
+
+    fn from_value_borrowed<'de, T>(&self, value: Value, arena: &'de StringArena) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        T::deserialize(de::Deserializer::new(value).with_arena(arena))
+    }
+}
+
+// Copies contents of the `source` table to the `target` table, reusing nested tables of `target`.
+fn update_table(lua: &Lua, target: &Table, source: &Table, merge: bool) -> Result<()> {
+    if !merge {
+        let mut stale_keys = Vec::new();
+        for pair in target.pairs::<Value, Value>() {
+            let (key, _) = pair?;
+            if source.raw_get::<Value>(&key)?.is_nil() {
+                stale_keys.push(key);
+            }
+        }
+        for key in stale_keys {
+            target.raw_set(key, Value::Nil)?;
+        }
+    }
+    for pair in source.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        match (target.raw_get(&key)?, value) {
+            (Value::Table(nested), Value::Table(value)) => update_table(lua, &nested, &value, merge)?,
+            (_, value) => target.raw_set(key, value)?,
+        }
+    }
+    if source.metatable() == Some(lua.array_metatable()) {
+        target.set_metatable(source.metatable());
+    }
+    Ok(())
 }
 
 // Uses 2 stack spaces and calls checkstack.
@@ -258,9 +400,12 @@ pub(crate) unsafe fn push_array_metatable(state: *mut ffi::lua_State) {
 static ARRAY_METATABLE_REGISTRY_KEY: u8 = 0;
 
 pub mod de;
+#[cfg(feature = "serde_json")]
+pub(crate) mod json;
 pub mod ser;
+pub(crate) mod userdata;
 
 #[doc(inline)]
-pub use de::Deserializer;
+pub use de::{Deserializer, StringArena};
 #[doc(inline)]
 pub use ser::Serializer;
@@ -48,6 +48,15 @@ pub struct Options {
     ///
     /// Default: **false**
     pub detect_serde_json_arbitrary_precision: bool,
+
+    /// If true, [`serialize_into`] keeps the existing keys of the target table (and its nested
+    /// tables) that are not present in the serialized value.
+    /// Otherwise such keys are removed.
+    ///
+    /// Default: **false**
+    ///
+    /// [`serialize_into`]: crate::LuaSerdeExt::serialize_into
+    pub merge_tables: bool,
 }
 
 impl Default for Options {
@@ -64,6 +73,7 @@ impl Options {
             serialize_none_to_null: true,
             serialize_unit_to_null: true,
             detect_serde_json_arbitrary_precision: false,
+            merge_tables: false,
         }
     }
 
@@ -107,6 +117,15 @@ impl Options {
         self.detect_serde_json_arbitrary_precision = enabled;
         self
     }
+
+    /// Sets [`merge_tables`] option.
+    ///
+    /// [`merge_tables`]: #structfield.merge_tables
+    #[must_use]
+    pub const fn merge_tables(mut self, enabled: bool) -> Self {
+        self.merge_tables = enabled;
+        self
+    }
 }
 
 impl<'a> Serializer<'a> {
@@ -490,7 +509,7 @@ impl ser::SerializeMap for SerializeMap<'_> {
     }
 
     fn end(self) -> Result<Value> {
-        Ok(Value::Table(self.table))
+        super::userdata::deserialize_tagged(self.lua, self.table)
     }
 }
 
@@ -525,7 +544,7 @@ impl ser::SerializeStruct for SerializeStruct<'_> {
 
     fn end(self) -> Result<Value> {
         match self.inner {
-            Some(table @ Value::Table(_)) => Ok(table),
+            Some(Value::Table(table)) => super::userdata::deserialize_tagged(self.lua, table),
             Some(value) if self.options.detect_serde_json_arbitrary_precision => {
                 let number_s = value.as_str().expect("not an arbitrary precision number");
                 if number_s.contains(['.', 'e', 'E']) {
@@ -0,0 +1,112 @@
+use std::any::TypeId;
+use std::string::String as StdString;
+
+use rustc_hash::FxHashMap;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::error::Result;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::XRc;
+use crate::userdata::AnyUserData;
+use crate::value::Value;
+
+// Name of the struct wrapping serialized userdata that can be deserialized back
+const TAGGED_STRUCT_NAME: &str = "$mlua::UserData";
+
+// Key of the userdata type name in the serialized (tagged) userdata
+const TYPE_KEY: &str = "$userdata";
+
+// Key of the userdata value in the serialized (tagged) userdata
+const VALUE_KEY: &str = "value";
+
+// Converts userdata to a serializable representation (see `UserDataRegistry::set_serialize`)
+#[cfg(feature = "send")]
+pub(crate) type SerializeHook = XRc<dyn Fn(&AnyUserData) -> Result<Box<dyn erased_serde::Serialize>> + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type SerializeHook = XRc<dyn Fn(&AnyUserData) -> Result<Box<dyn erased_serde::Serialize>>>;
+
+// Creates userdata from a deserialized value (see `UserDataRegistry::set_deserialize`)
+#[cfg(feature = "send")]
+pub(crate) type DeserializeHook = XRc<dyn Fn(&Lua, Value) -> Result<AnyUserData> + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type DeserializeHook = XRc<dyn Fn(&Lua, Value) -> Result<AnyUserData>>;
+
+// Serialization hooks of the registered userdata types (stored in `ExtraData`)
+#[derive(Default)]
+pub(crate) struct UserDataHooks {
+    // Serialization hook and the type name (if the type can be deserialized)
+    serializers: FxHashMap<TypeId, (SerializeHook, Option<StdString>)>,
+    deserializers: FxHashMap<StdString, DeserializeHook>,
+}
+
+impl UserDataHooks {
+    pub(crate) fn register(
+        &mut self,
+        type_id: TypeId,
+        type_name: &str,
+        serialize: Option<SerializeHook>,
+        deserialize: Option<DeserializeHook>,
+    ) {
+        let tag = deserialize.is_some().then(|| type_name.to_string());
+        match serialize {
+            Some(serialize) => self.serializers.insert(type_id, (serialize, tag)),
+            None => self.serializers.remove(&type_id),
+        };
+        match deserialize {
+            Some(deserialize) => self.deserializers.insert(type_name.to_string(), deserialize),
+            None => self.deserializers.remove(type_name),
+        };
+    }
+
+    pub(crate) fn serializer(&self, type_id: TypeId) -> Option<(SerializeHook, Option<StdString>)> {
+        self.serializers.get(&type_id).cloned()
+    }
+}
+
+// Serializes the userdata using its serialization hook.
+//
+// Userdata of types with a deserialization hook are wrapped into a struct with the type name, to
+// be recognized when serializing back to Lua.
+pub(crate) fn serialize_with_hook<S: Serializer>(
+    ud: &AnyUserData,
+    (hook, tag): (SerializeHook, Option<StdString>),
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let value = hook(ud).map_err(serde::ser::Error::custom)?;
+    match tag {
+        Some(type_name) => {
+            let mut state = serializer.serialize_struct(TAGGED_STRUCT_NAME, 2)?;
+            state.serialize_field(TYPE_KEY, &type_name)?;
+            state.serialize_field(VALUE_KEY, &*value)?;
+            state.end()
+        }
+        None => value.serialize(serializer),
+    }
+}
+
+// Creates userdata from the table produced by serializing tagged userdata.
+//
+// Returns the table unchanged if it's not a tagged userdata of a type with deserialization hook.
+pub(crate) fn deserialize_tagged(lua: &Lua, table: Table) -> Result<Value> {
+    if lua
+        .lock()
+        .with_userdata_hooks(|hooks| hooks.deserializers.is_empty())
+    {
+        return Ok(Value::Table(table));
+    }
+    let hook = match table.raw_get::<Value>(TYPE_KEY)? {
+        Value::String(type_name) => {
+            let type_name = type_name.to_string_lossy();
+            lua.lock()
+                .with_userdata_hooks(|hooks| hooks.deserializers.get(&type_name).cloned())
+        }
+        _ => None,
+    };
+    match hook {
+        Some(hook) => hook(lua, table.raw_get(VALUE_KEY)?).map(Value::UserData),
+        None => Ok(Value::Table(table)),
+    }
+}
@@ -1,31 +1,54 @@
 use std::any::TypeId;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::ops::Deref;
-use std::os::raw::c_int;
+use std::os::raw::{c_int, c_void};
 use std::panic::Location;
 use std::result::Result as StdResult;
+use std::string::String as StdString;
+use std::sync::Arc;
 use std::{fmt, mem, ptr};
 
+use crate::cancel::CancellationToken;
+use crate::capability::Capability;
 use crate::chunk::{AsChunk, Chunk};
-use crate::error::{Error, Result};
+use crate::deterministic::DeterministicOptions;
+#[cfg(feature = "encoding")]
+use crate::encoding::Encoding;
+use crate::error::{ConversionErrorInfo, Error, Result};
+use crate::error_object::ErrorObjectOptions;
 use crate::function::Function;
+use crate::gc_policy::GCAction;
 use crate::hook::Debug;
+use crate::identity::{self, ValueIdentity};
+use crate::isolate::IsolatedGlobals;
+use crate::json::JsonOptions;
+use crate::lazy;
+use crate::lua_source::LuaSourceOptions;
 use crate::memory::MemoryState;
+use crate::middleware::{CallInfo, Next};
+use crate::number_format::NumberFormat;
+use crate::random::RandomSource;
+use crate::registry::Registry;
 use crate::scope::Scope;
-use crate::stdlib::StdLib;
+use crate::stdlib::{StdLib, UnsafeCapabilities};
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
+use crate::thread_group::ThreadGroup;
+use crate::traits::{LuaTrait, ToLuaString};
 use crate::types::{
-    AppDataRef, AppDataRefMut, ArcReentrantMutexGuard, Integer, LuaType, MaybeSend, Number, ReentrantMutex,
-    ReentrantMutexGuard, RegistryKey, VmState, XRc, XWeak,
+    AppDataRef, AppDataRefMut, ArcReentrantMutexGuard, Integer, LightUserData, LuaType, MaybeSend, Number,
+    ReentrantMutex, ReentrantMutexGuard, RegistryKey, VmState, XRc, XWeak,
 };
 use crate::userdata::{AnyUserData, UserData, UserDataProxy, UserDataRegistry, UserDataStorage};
 use crate::util::{
     assert_stack, check_stack, protect_lua_closure, push_string, push_table, rawset_field, StackGuard,
 };
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
+use crate::version::VersionInfo;
+use crate::vfs::Vfs;
 
 #[cfg(not(feature = "luau"))]
 use crate::hook::HookTriggers;
@@ -35,17 +58,34 @@ use crate::{buffer::Buffer, chunk::Compiler};
 
 #[cfg(feature = "async")]
 use {
-    crate::types::LightUserData,
+    crate::limiter::AsyncLimiter,
+    crate::scheduler::Task,
+    crate::time::TimeDriver,
     std::future::{self, Future},
+    std::time::{Duration, Instant},
 };
 
+#[cfg(feature = "metrics")]
+use crate::metrics::{HotString, MetricsSnapshot};
+
+#[cfg(feature = "testing")]
+use crate::testing::TestReport;
+
+#[cfg(feature = "serde_json")]
+use crate::value::NumberPolicy;
+
 #[cfg(feature = "serialize")]
 use serde::Serialize;
 
 pub(crate) use extra::ExtraData;
+#[cfg(all(feature = "async", not(feature = "luau")))]
+pub(crate) use raw::userdata_async_destructor;
 pub use raw::RawLua;
 use util::{callback_error_ext, StateGuard};
 
+// Registry key of the (weak) cache used by `Lua::create_shared_userdata`
+const SHARED_USERDATA_KEY: &str = "__mlua_shared_userdata";
+
 /// Top level Lua struct which represents an instance of Lua VM.
 #[derive(Clone)]
 pub struct Lua {
@@ -76,6 +116,79 @@ pub enum GCMode {
     Generational,
 }
 
+/// Mechanism used by Lua to propagate errors, see [`Lua::unwind_mode`].
+///
+/// The mechanism is fixed when building the Lua library. For vendored Luau it can be selected by
+/// setting the `LUA_UNWIND_MODE` env variable to `longjmp` or `cxx` at build time. For system Lua
+/// libraries it cannot be detected and can be declared using the same variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnwindMode {
+    /// `setjmp`/`longjmp` (or LuaJIT internal unwinding).
+    ///
+    /// Foreign frames are skipped without running any cleanup code.
+    Longjmp,
+    /// C++ exceptions (Lua compiled as C++, the default for Luau).
+    CxxExceptions,
+    /// System unwinder (LuaJIT external unwinding, used on Windows).
+    ///
+    /// Interoperates with C++ exceptions and Windows SEH.
+    External,
+}
+
+/// Behavior of converting Rust integers that do not fit into the Lua integer type.
+///
+/// This applies to `u64`, `usize`, `i128` and `u128` values (and `i64`/`u32` values when Lua
+/// integers are 32-bit) that are out of the Lua integer range.
+///
+/// See [`Lua::set_integer_overflow`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IntegerOverflow {
+    /// Convert the value to a Lua number (float), possibly losing precision.
+    ///
+    /// This is the default behavior.
+    #[default]
+    AsFloat,
+    /// Return a conversion error.
+    Error,
+    /// Wrap the value to the Lua integer type (two's complement), so `u64::MAX` becomes `-1`.
+    ///
+    /// When converting back from Lua, negative integers are wrapped to the unsigned types of the
+    /// same size as the Lua integer type.
+    WrapToNegative,
+    /// Convert the value to a Lua string with its decimal representation.
+    ///
+    /// The string can be converted back without loss of precision.
+    LosslessAsString,
+}
+
+/// Representation of [`Duration`] values in Lua.
+///
+/// Converting from Lua always accepts strings with unit suffixes (e.g. `"250ms"`, `"1.5s"`,
+/// `"1h30m"`), while plain numbers are interpreted according to the format.
+///
+/// See [`Lua::set_duration_format`].
+///
+/// [`Duration`]: std::time::Duration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DurationFormat {
+    /// Number of seconds (possibly fractional).
+    ///
+    /// This is the default format.
+    #[default]
+    Seconds,
+    /// Integer number of milliseconds.
+    ///
+    /// Plain numbers are interpreted as milliseconds when converting from Lua.
+    Millis,
+    /// String with unit suffixes, e.g. `"1h30m"` or `"250ms"`.
+    ///
+    /// Plain numbers are interpreted as seconds when converting from Lua.
+    String,
+}
+
 /// Controls Lua interpreter behavior such as Rust panics handling.
 #[derive(Clone, Debug)]
 #[non_exhaustive]
@@ -87,6 +200,8 @@ pub struct LuaOptions {
     ///
     /// If enabled, keeps [`pcall`]/[`xpcall`] unmodified.
     /// Panics are still automatically resumed if returned to the Rust side.
+    /// The string representation of a caught panic includes the location of the Lua code
+    /// that called the panicking function and the Lua stack traceback.
     ///
     /// Default: **true**
     ///
@@ -105,6 +220,38 @@ pub struct LuaOptions {
     #[cfg(feature = "async")]
     #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
     pub thread_pool_size: usize,
+
+    /// Attribute memory allocations to the chunk (source) that made them.
+    ///
+    /// Memory allocated while loading a chunk or calling a Lua function from Rust is attributed
+    /// to the chunk where the function was defined. Use [`Lua::memory_by_source`] to get the
+    /// statistics.
+    ///
+    /// Enabling this option adds a small header to every allocation made by Lua.
+    ///
+    /// Default: **false**
+    pub track_memory_by_source: bool,
+
+    /// Count creations of each distinct string created from Rust.
+    ///
+    /// Use [`Lua::hot_strings`] to find the most frequently created strings, e.g. to locate
+    /// bindings generating excessive garbage. Tracking stops adding new strings after 10000
+    /// distinct ones.
+    ///
+    /// Default: **false**
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub track_hot_strings: bool,
+
+    /// Abort the process if a Rust function called by Lua panics.
+    ///
+    /// By default, panics are caught and propagated through Lua frames as Lua errors, then
+    /// resumed when returned to Rust. This relies on the Lua error propagation mechanism (see
+    /// [`UnwindMode`]) working reliably, which might not be the case when embedding LuaJIT on some
+    /// targets (e.g. MSVC). Enabling this option guarantees that panics never cross Lua frames.
+    ///
+    /// Default: **false**
+    pub abort_on_panic: bool,
 }
 
 impl Default for LuaOptions {
@@ -120,6 +267,10 @@ impl LuaOptions {
             catch_rust_panics: true,
             #[cfg(feature = "async")]
             thread_pool_size: 0,
+            track_memory_by_source: false,
+            #[cfg(feature = "metrics")]
+            track_hot_strings: false,
+            abort_on_panic: false,
         }
     }
 
@@ -142,6 +293,101 @@ impl LuaOptions {
         self.thread_pool_size = size;
         self
     }
+
+    /// Sets [`track_memory_by_source`] option.
+    ///
+    /// [`track_memory_by_source`]: #structfield.track_memory_by_source
+    #[must_use]
+    pub const fn track_memory_by_source(mut self, enabled: bool) -> Self {
+        self.track_memory_by_source = enabled;
+        self
+    }
+
+    /// Sets [`track_hot_strings`] option.
+    ///
+    /// [`track_hot_strings`]: #structfield.track_hot_strings
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    #[must_use]
+    pub const fn track_hot_strings(mut self, enabled: bool) -> Self {
+        self.track_hot_strings = enabled;
+        self
+    }
+
+    /// Sets [`abort_on_panic`] option.
+    ///
+    /// [`abort_on_panic`]: #structfield.abort_on_panic
+    #[must_use]
+    pub const fn abort_on_panic(mut self, enabled: bool) -> Self {
+        self.abort_on_panic = enabled;
+        self
+    }
+}
+
+/// Usage statistics of the auxiliary stack where references to Lua values are stored.
+///
+/// Every handle (such as [`Table`] or [`Function`]) owns a slot on this stack until it is
+/// dropped. Constantly growing number of used slots usually means that handles are leaked.
+///
+/// This struct is created by the [`Lua::ref_stack_usage`] method.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RefStackUsage {
+    /// Number of slots occupied by references (including internal ones).
+    pub used: usize,
+    /// Number of slots that were released and can be reused.
+    pub free: usize,
+    /// Current size of the stack.
+    pub capacity: usize,
+}
+
+/// A scope for temporary Lua values, created by [`Lua::value_scope`].
+///
+/// While the scope is active, references to Lua values (tables, strings, functions, etc.) held
+/// by Rust are allocated from a block of slots reserved in the auxiliary stack. Slots of dropped
+/// values are recycled within the block and the whole block is released at once when the scope
+/// ends.
+pub struct ValueScope<'a> {
+    lua: &'a Lua,
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ValueScope<'_> {
+    /// Returns the number of slots reserved by the scope.
+    pub fn capacity(&self) -> usize {
+        let lua = self.lua.lock();
+        unsafe { (&(*lua.extra.get()).ref_blocks)[self.depth].capacity() }
+    }
+
+    /// Returns the number of reserved slots that are currently in use.
+    pub fn used(&self) -> usize {
+        let lua = self.lua.lock();
+        unsafe { (&(*lua.extra.get()).ref_blocks)[self.depth].used() }
+    }
+}
+
+/// Information about where a reference to a Lua value was created.
+///
+/// This struct is created by the [`Lua::live_refs`] method.
+///
+/// Requires `feature = "ref-audit"`
+#[cfg(feature = "ref-audit")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ref-audit")))]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RefOrigin {
+    /// Type name of the referenced value.
+    pub type_name: &'static str,
+    /// Backtrace captured when the reference was created.
+    pub backtrace: std::sync::Arc<std::backtrace::Backtrace>,
+}
+
+#[cfg(feature = "ref-audit")]
+impl fmt::Display for RefOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} reference created at:\n{}", self.type_name, self.backtrace)
+    }
 }
 
 impl Drop for Lua {
@@ -176,16 +422,27 @@ impl Lua {
     ///
     /// [`StdLib`]: crate::StdLib
     pub fn new() -> Lua {
-        mlua_expect!(
-            Self::new_with(StdLib::ALL_SAFE, LuaOptions::default()),
-            "Cannot create a Lua state"
-        )
+        mlua_expect!(Self::try_new(), "Cannot create a Lua state")
+    }
+
+    /// Creates a new Lua state and loads the **safe** subset of the standard libraries.
+    ///
+    /// Unlike [`Lua::new`], this function never panics and returns an error if the Lua VM cannot
+    /// be created or initialized (e.g. due to memory allocation failure).
+    ///
+    /// [`Lua::new`]: crate::Lua::new
+    pub fn try_new() -> Result<Lua> {
+        Self::new_with(StdLib::ALL_SAFE, LuaOptions::default())
     }
 
     /// Creates a new Lua state and loads all the standard libraries.
     ///
+    /// All [`UnsafeCapabilities`] are enabled in the created state.
+    ///
     /// # Safety
     /// The created Lua state would not have safety guarantees and would allow to load C modules.
+    ///
+    /// [`UnsafeCapabilities`]: crate::UnsafeCapabilities
     pub unsafe fn unsafe_new() -> Lua {
         Self::unsafe_new_with(StdLib::ALL, LuaOptions::default())
     }
@@ -200,31 +457,77 @@ impl Lua {
     ///
     /// See [`StdLib`] documentation for a list of unsafe modules that cannot be loaded.
     ///
+    /// Lua code is still allowed to load binary chunks (see
+    /// [`UnsafeCapabilities::ALLOW_BINARY_CHUNKS`]), use [`Lua::unsafe_new_with_capabilities`]
+    /// to disallow them.
+    ///
+    /// Returns an error if the Lua VM cannot be created or initialized.
+    ///
     /// [`StdLib`]: crate::StdLib
+    /// [`UnsafeCapabilities::ALLOW_BINARY_CHUNKS`]: crate::UnsafeCapabilities::ALLOW_BINARY_CHUNKS
     pub fn new_with(libs: StdLib, options: LuaOptions) -> Result<Lua> {
-        #[cfg(not(feature = "luau"))]
-        if libs.contains(StdLib::DEBUG) {
-            return Err(Error::SafetyError(
-                "The unsafe `debug` module can't be loaded using safe `new_with`".to_string(),
-            ));
-        }
-        #[cfg(feature = "luajit")]
-        if libs.contains(StdLib::FFI) {
-            return Err(Error::SafetyError(
-                "The unsafe `ffi` module can't be loaded using safe `new_with`".to_string(),
-            ));
-        }
+        // SAFETY: the only enabled capability (binary chunks) is allowed in safe mode
+        let caps = UnsafeCapabilities::ALLOW_BINARY_CHUNKS;
+        unsafe { Self::unsafe_new_with_capabilities(libs, options, caps) }
+    }
+
+    /// Creates a new Lua state with only the specified unsafe capabilities enabled and loads the
+    /// specified subset of the standard libraries.
+    ///
+    /// This is a middle ground between [`Lua::new_with`] (no unsafe capabilities) and
+    /// [`Lua::unsafe_new_with`] (all of them), that makes explicit which unsafe features of Lua
+    /// are available to scripts. The capabilities cannot be changed after the state is created.
+    ///
+    /// Returns [`Error::SafetyError`] if `libs` contains an unsafe library that is not allowed
+    /// by `caps`.
+    ///
+    /// # Safety
+    /// The created Lua state has safety guarantees only for the capabilities that are not enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, LuaOptions, Result, StdLib, UnsafeCapabilities};
+    /// # fn main() -> Result<()> {
+    /// let caps = UnsafeCapabilities::ALLOW_DEBUG_LIBRARY;
+    /// let libs = StdLib::ALL_SAFE | StdLib::DEBUG;
+    /// let lua = unsafe { Lua::unsafe_new_with_capabilities(libs, LuaOptions::default(), caps)? };
+    /// assert_eq!(lua.unsafe_capabilities(), caps);
+    /// lua.load("debug.traceback()").exec()?;
+    /// assert!(lua.load("package.loadlib('lib.so', 'f')").exec().is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Error::SafetyError`]: crate::Error::SafetyError
+    pub unsafe fn unsafe_new_with_capabilities(
+        libs: StdLib,
+        options: LuaOptions,
+        caps: UnsafeCapabilities,
+    ) -> Result<Lua> {
+        raw::check_unsafe_libs(libs, caps)?;
 
-        let lua = unsafe { Self::inner_new(libs, options) };
+        let lua = Self::inner_new(libs, options)?;
 
-        if libs.contains(StdLib::PACKAGE) {
-            mlua_expect!(lua.disable_c_modules(), "Error disabling C modules");
+        if libs.contains(StdLib::PACKAGE) && !caps.contains(UnsafeCapabilities::ALLOW_C_FUNCTIONS_FROM_LUA) {
+            lua.disable_c_modules()?;
+        }
+        #[cfg(not(feature = "luau"))]
+        if !caps.contains(UnsafeCapabilities::ALLOW_BINARY_CHUNKS) {
+            lua.disable_binary_chunks()?;
         }
-        unsafe { lua.lock().set_safe() };
+        lua.lock().set_unsafe_capabilities(caps);
 
         Ok(lua)
     }
 
+    /// Returns the set of unsafe capabilities enabled in this Lua state.
+    ///
+    /// See [`Lua::unsafe_new_with_capabilities`] for details.
+    pub fn unsafe_capabilities(&self) -> UnsafeCapabilities {
+        self.lock().unsafe_capabilities()
+    }
+
     /// Creates a new Lua state and loads the specified subset of the standard libraries.
     ///
     /// Use the [`StdLib`] flags to specify the libraries you want to load.
@@ -252,20 +555,33 @@ impl Lua {
             _symbols.push(ffi::luaL_setfuncs as _);
         }
 
-        Self::inner_new(libs, options)
+        mlua_expect!(Self::inner_new(libs, options), "Cannot create a Lua state")
     }
 
     /// Creates a new Lua state with required `libs` and `options`
-    unsafe fn inner_new(libs: StdLib, options: LuaOptions) -> Lua {
+    unsafe fn inner_new(libs: StdLib, options: LuaOptions) -> Result<Lua> {
+        #[cfg(feature = "metrics")]
+        let track_hot_strings = options.track_hot_strings;
         let lua = Lua {
-            raw: RawLua::new(libs, options),
+            raw: RawLua::new(libs, options)?,
             collect_garbage: true,
         };
 
         #[cfg(feature = "luau")]
-        mlua_expect!(lua.configure_luau(), "Error configuring Luau");
+        lua.configure_luau()?;
+
+        #[cfg(any(feature = "lua52", feature = "lua51", feature = "luajit"))]
+        if libs.contains(StdLib::STRING) {
+            crate::pack::register_polyfill(&lua)?;
+        }
 
-        lua
+        // Start tracking after initialization to not count strings created internally
+        #[cfg(feature = "metrics")]
+        if track_hot_strings {
+            (*lua.lock().extra.get()).metrics.hot_strings = Some(Default::default());
+        }
+
+        Ok(lua)
     }
 
     /// Constructs a new Lua instance from an existing raw state.
@@ -310,7 +626,14 @@ impl Lua {
     ///
     /// Use the [`StdLib`] flags to specify the libraries you want to load.
     pub fn load_std_libs(&self, libs: StdLib) -> Result<()> {
-        unsafe { self.lock().load_std_libs(libs) }
+        unsafe { self.lock().load_std_libs(libs)? };
+
+        #[cfg(any(feature = "lua52", feature = "lua51", feature = "luajit"))]
+        if libs.contains(StdLib::STRING) {
+            crate::pack::register_polyfill(self)?;
+        }
+
+        Ok(())
     }
 
     /// Loads module `modname` into an existing Lua state using the specified entrypoint
@@ -332,18 +655,8 @@ impl Lua {
     where
         T: FromLua,
     {
-        let lua = self.lock();
-        let state = lua.state();
-        let loaded = unsafe {
-            let _sg = StackGuard::new(state);
-            check_stack(state, 2)?;
-            protect_lua!(state, 0, 1, fn(state) {
-                ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADED"));
-            })?;
-            Table(lua.pop_ref())
-        };
-
-        let modname = unsafe { lua.create_string(modname)? };
+        let loaded = self.loaded_table()?;
+        let modname = self.create_string(modname)?;
         let value = match loaded.raw_get(&modname)? {
             Value::Nil => {
                 let result = match func.call(&modname)? {
@@ -366,18 +679,83 @@ impl Lua {
     ///
     /// [`package.loaded`]: https://www.lua.org/manual/5.4/manual.html#pdf-package.loaded
     pub fn unload(&self, modname: &str) -> Result<()> {
+        self.loaded_table()?.raw_set(modname, Nil)
+    }
+
+    /// Returns an iterator over the modules in the [`package.loaded`] table.
+    ///
+    /// Yields module names and values sorted by name, including the standard libraries.
+    ///
+    /// [`package.loaded`]: https://www.lua.org/manual/5.4/manual.html#pdf-package.loaded
+    pub fn loaded_modules(&self) -> Result<impl Iterator<Item = (StdString, Value)>> {
+        let mut modules = Vec::new();
+        self.loaded_table()?.for_each(|name: Value, value: Value| {
+            if let Value::String(name) = name {
+                modules.push((name.to_string_lossy(), value));
+            }
+            Ok(())
+        })?;
+        modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(modules.into_iter())
+    }
+
+    /// Unloads module `modname` and clears the cached information about it.
+    ///
+    /// Unlike [`Lua::unload`], it also forgets the module dependencies recorded in the module graph
+    /// (see `Lua::module_graph`) and, if `dependents` is true, unloads all modules that directly or
+    /// indirectly required `modname`, so they are loaded again (with the new version of `modname`)
+    /// on next `require`. The module graph is recorded only in Luau, in other Lua versions
+    /// `dependents` has no effect.
+    ///
+    /// Returns names of the modules that were actually unloaded.
+    #[cfg_attr(not(feature = "luau"), allow(unused_variables))]
+    pub fn unload_module(&self, modname: &str, dependents: bool) -> Result<Vec<StdString>> {
+        #[allow(unused_mut)]
+        let mut names = vec![modname.to_string()];
+        #[cfg(feature = "luau")]
+        if dependents {
+            let graph = self.module_graph()?;
+            names.extend(
+                graph
+                    .transitive_dependents(modname)
+                    .into_iter()
+                    .map(str::to_string),
+            );
+        }
+
+        let loaded = self.loaded_table()?;
+        let mut unloaded = Vec::new();
+        for name in &names {
+            if loaded.raw_get::<Value>(name.as_str())? != Nil {
+                loaded.raw_set(name.as_str(), Nil)?;
+                unloaded.push(name.clone());
+            }
+        }
+        #[cfg(feature = "luau")]
+        crate::luau::forget_modules(self, &names)?;
+        Ok(unloaded)
+    }
+
+    // Returns the environment of the Lua function that called the running Rust callback, if it's
+    // not the global environment
+    fn caller_environment(&self) -> Option<Table> {
+        let lua = self.lock();
+        let caller = unsafe { crate::capability::caller_function(&lua).ok()?? };
+        caller.environment().filter(|env| *env != self.globals())
+    }
+
+    // Returns the `package.loaded` table (stored in the registry)
+    fn loaded_table(&self) -> Result<Table> {
         let lua = self.lock();
         let state = lua.state();
-        let loaded = unsafe {
+        unsafe {
             let _sg = StackGuard::new(state);
             check_stack(state, 2)?;
             protect_lua!(state, 0, 1, fn(state) {
                 ffi::luaL_getsubtable(state, ffi::LUA_REGISTRYINDEX, cstr!("_LOADED"));
             })?;
-            Table(lua.pop_ref())
-        };
-
-        loaded.raw_set(modname, Nil)
+            Ok(Table(lua.pop_ref()))
+        }
     }
 
     // Executes module entrypoint function, which returns only one Value.
@@ -478,6 +856,66 @@ impl Lua {
         }
     }
 
+    /// Sets (or removes) the `readonly` attribute on the given standard libraries.
+    ///
+    /// This allows to fine-tune which libraries can be modified by scripts, for example to keep
+    /// `string` and `math` protected while allowing to patch `buffer`.
+    /// Libraries that are not loaded (or are not tables, such as [`StdLib::VECTOR`]) are ignored.
+    ///
+    /// Making a library writable also disables `safeenv` on the global environment, as Luau may
+    /// otherwise inline calls to builtin functions and ignore any changes made to them.
+    ///
+    /// Note that enabling or disabling [sandbox] mode resets the `readonly` attribute on all
+    /// libraries.
+    ///
+    /// Requires `feature = "luau"`
+    ///
+    /// [sandbox]: #method.sandbox
+    #[cfg(any(feature = "luau", docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn set_std_libs_readonly(&self, libs: StdLib, enabled: bool) -> Result<()> {
+        const LIBS: [(StdLib, &str); 10] = [
+            (StdLib::COROUTINE, ffi::LUA_COLIBNAME),
+            (StdLib::TABLE, ffi::LUA_TABLIBNAME),
+            (StdLib::OS, ffi::LUA_OSLIBNAME),
+            (StdLib::STRING, ffi::LUA_STRLIBNAME),
+            (StdLib::UTF8, ffi::LUA_UTF8LIBNAME),
+            (StdLib::BIT, ffi::LUA_BITLIBNAME),
+            (StdLib::BUFFER, ffi::LUA_BUFFERLIBNAME),
+            (StdLib::MATH, ffi::LUA_MATHLIBNAME),
+            (StdLib::DEBUG, ffi::LUA_DBLIBNAME),
+            (StdLib::PACKAGE, "package"),
+        ];
+
+        let globals = self.globals();
+        for (lib, name) in LIBS {
+            if libs.contains(lib) {
+                if let Some(table) = globals.get::<Option<Table>>(name)? {
+                    table.set_readonly(enabled);
+                }
+            }
+        }
+
+        if !enabled {
+            let lua = self.lock();
+            unsafe { ffi::lua_setsafeenv(lua.main_state, ffi::LUA_GLOBALSINDEX, 0) };
+        }
+        Ok(())
+    }
+
+    /// Returns the dependency graph of modules loaded using `require`.
+    ///
+    /// The graph records which modules were required by each module, including modules that
+    /// were already loaded. Requiring a module that is still being loaded returns
+    /// [`Error::CyclicRequire`] with the full chain of modules instead.
+    ///
+    /// Requires `feature = "luau"`
+    #[cfg(feature = "luau")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn module_graph(&self) -> Result<crate::luau::ModuleGraph> {
+        crate::luau::ModuleGraph::from_registry(self)
+    }
+
     /// Sets a 'hook' function that will periodically be called as Lua code executes.
     ///
     /// When exactly the hook function is called depends on the contents of the `triggers`
@@ -599,28 +1037,6 @@ impl Lua {
     {
         use std::rc::Rc;
 
-        unsafe extern "C-unwind" fn interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
-            if gc >= 0 {
-                // We don't support GC interrupts since they cannot survive Lua exceptions
-                return;
-            }
-            let result = callback_error_ext(state, ptr::null_mut(), move |extra, _| {
-                let interrupt_cb = (*extra).interrupt_callback.clone();
-                let interrupt_cb = mlua_expect!(interrupt_cb, "no interrupt callback set in interrupt_proc");
-                if Rc::strong_count(&interrupt_cb) > 2 {
-                    return Ok(VmState::Continue); // Don't allow recursion
-                }
-                let _guard = StateGuard::new((*extra).raw_lua(), state);
-                interrupt_cb((*extra).lua())
-            });
-            match result {
-                VmState::Continue => {}
-                VmState::Yield => {
-                    ffi::lua_yield(state, 0);
-                }
-            }
-        }
-
         // Set interrupt callback
         let lua = self.lock();
         unsafe {
@@ -637,8 +1053,76 @@ impl Lua {
     pub fn remove_interrupt(&self) {
         let lua = self.lock();
         unsafe {
-            (*lua.extra.get()).interrupt_callback = None;
-            (*ffi::lua_callbacks(lua.main_state)).interrupt = None;
+            let extra = lua.extra.get();
+            (*extra).interrupt_callback = None;
+            if (*extra).auto_yield_interval == 0 {
+                (*ffi::lua_callbacks(lua.main_state)).interrupt = None;
+            }
+        }
+    }
+
+    /// Enables automatic cooperative yielding of running coroutines.
+    ///
+    /// Luau VM periodically checks for interrupts (in practice at function calls and loop
+    /// iterations). With this mode enabled, a running coroutine yields (with no values) back to
+    /// the code that resumed it once every `interval` checks, so long-running scripts can be
+    /// preempted by the host scheduler without modifying them. The coroutine continues where it
+    /// stopped when resumed again.
+    ///
+    /// Only coroutines resumed from Rust (using [`Thread::resume`] or as async threads) are
+    /// preempted, each counting its own interrupt checks. Coroutines resumed from Lua code (using
+    /// `coroutine.resume` or `coroutine.wrap`, e.g. generators) are never interrupted, since their
+    /// caller would receive the automatic yields. Code that cannot yield (the main thread, or when
+    /// running inside a metamethod or a Rust function call) is not interrupted either.
+    ///
+    /// Automatic yields work together with an interrupt set by [`Lua::set_interrupt`], which is
+    /// called first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, ThreadStatus};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.enable_auto_yield(100);
+    ///
+    /// let func = lua.load("local n = 0; for i = 1, 10000 do n += i end; return n");
+    /// let co = lua.create_thread(func.into_function()?)?;
+    /// let mut slices = 0;
+    /// let result = loop {
+    ///     let result = co.resume::<Option<i64>>(())?;
+    ///     if co.status() != ThreadStatus::Resumable {
+    ///         break result;
+    ///     }
+    ///     slices += 1;
+    /// };
+    /// assert_eq!(result, Some(50005000));
+    /// assert!(slices > 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(any(feature = "luau", docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn enable_auto_yield(&self, interval: u32) {
+        let lua = self.lock();
+        unsafe {
+            let extra = lua.extra.get();
+            (*extra).auto_yield_interval = interval.max(1);
+            (*ffi::lua_callbacks(lua.main_state)).interrupt = Some(interrupt_proc);
+        }
+    }
+
+    /// Disables automatic yielding previously enabled by [`Lua::enable_auto_yield`].
+    #[cfg(any(feature = "luau", docsrs))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub fn disable_auto_yield(&self) {
+        let lua = self.lock();
+        unsafe {
+            let extra = lua.extra.get();
+            (*extra).auto_yield_interval = 0;
+            if (*extra).interrupt_callback.is_none() {
+                (*ffi::lua_callbacks(lua.main_state)).interrupt = None;
+            }
         }
     }
 
@@ -653,7 +1137,6 @@ impl Lua {
     {
         use std::ffi::CStr;
         use std::os::raw::{c_char, c_void};
-        use std::string::String as StdString;
 
         unsafe extern "C-unwind" fn warn_proc(ud: *mut c_void, msg: *const c_char, tocont: c_int) {
             let extra = ud as *mut ExtraData;
@@ -710,40 +1193,165 @@ impl Lua {
         }
     }
 
-    /// Gets information about the interpreter runtime stack.
+    /// Sets a callback that is called when a Rust function fails to convert Lua values.
     ///
-    /// This function returns [`Debug`] structure that can be used to get information about the
-    /// function executing at a given level. Level `0` is the current running function, whereas
-    /// level `n+1` is the function that has called level `n` (except for tail calls, which do
-    /// not count in the stack).
+    /// The callback is invoked whenever a Rust function or userdata method called from Lua returns
+    /// a conversion error, most commonly when [`FromLua`]/[`FromLuaMulti`] fails to convert its
+    /// arguments. The provided [`ConversionErrorInfo`] includes the argument position,
+    /// expected and actual types, and the location in Lua code where the function was called.
     ///
-    /// [`Debug`]: crate::hook::Debug
-    pub fn inspect_stack(&self, level: usize) -> Option<Debug> {
+    /// This allows hosts to collect statistics about the most common script-side type errors.
+    /// The error is still raised in Lua as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::{Arc, Mutex};
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let errors2 = errors.clone();
+    /// lua.on_conversion_error(move |info| {
+    ///     errors2.lock().unwrap().push((info.arg_index, info.expected.clone(), info.actual));
+    /// });
+    ///
+    /// let add = lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?;
+    /// assert!(add.call::<i64>((1, "two")).is_err());
+    /// assert_eq!(*errors.lock().unwrap(), [(Some(2), "i64".to_string(), "string")]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`FromLua`]: crate::FromLua
+    /// [`FromLuaMulti`]: crate::FromLuaMulti
+    pub fn on_conversion_error<F>(&self, callback: F)
+    where
+        F: Fn(&ConversionErrorInfo) + MaybeSend + 'static,
+    {
         let lua = self.lock();
-        unsafe {
-            let mut ar: ffi::lua_Debug = mem::zeroed();
-            let level = level as c_int;
-            #[cfg(not(feature = "luau"))]
-            if ffi::lua_getstack(lua.state(), level, &mut ar) == 0 {
-                return None;
-            }
-            #[cfg(feature = "luau")]
-            if ffi::lua_getinfo(lua.state(), level, cstr!(""), &mut ar) == 0 {
-                return None;
-            }
-            Some(Debug::new_owned(lua, level, ar))
-        }
+        unsafe { (*lua.extra.get()).conversion_error_callback = Some(XRc::new(callback)) };
     }
 
-    /// Returns the amount of memory (in bytes) currently used inside this Lua state.
-    pub fn used_memory(&self) -> usize {
+    /// Removes a callback previously set by [`Lua::on_conversion_error`].
+    pub fn remove_conversion_error_callback(&self) {
         let lua = self.lock();
-        unsafe {
-            match MemoryState::get(lua.main_state) {
-                mem_state if !mem_state.is_null() => (*mem_state).used_memory(),
-                _ => {
-                    // Get data from the Lua GC
-                    let used_kbytes = ffi::lua_gc(lua.main_state, ffi::LUA_GCCOUNT, 0);
+        unsafe { (*lua.extra.get()).conversion_error_callback = None };
+    }
+
+    /// Sets a middleware wrapping every call of a Rust function created by this Lua instance.
+    ///
+    /// The middleware receives information about the call ([`CallInfo`]) and the rest of the call
+    /// chain ([`Next`]), which must be run to call the function. This allows implementing
+    /// cross-cutting concerns such as access checks, logging or metrics in one place.
+    ///
+    /// Only the synchronous part of async functions is wrapped. Setting a middleware replaces the
+    /// previous one. Arguments and results are converted to and from [`MultiValue`] when
+    /// a middleware is set, which adds some overhead to every call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::{Arc, Mutex};
+    /// # use mlua::{Error, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let calls = Arc::new(Mutex::new(Vec::new()));
+    /// let calls2 = calls.clone();
+    /// lua.set_callback_middleware(move |info, next| {
+    ///     if info.arg_count() > 2 {
+    ///         return Err(Error::runtime("too many arguments"));
+    ///     }
+    ///     let results = next.run();
+    ///     let name = info.name().map(str::to_string);
+    ///     calls2.lock().unwrap().push((name, info.arg_count(), info.elapsed()));
+    ///     results
+    /// });
+    ///
+    /// lua.globals().set("add", lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?)?;
+    /// assert_eq!(lua.load("return add(1, 2)").eval::<i64>()?, 3);
+    /// assert!(lua.load("add(1, 2, 3)").exec().is_err());
+    /// assert_eq!(calls.lock().unwrap().len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_callback_middleware<F>(&self, middleware: F)
+    where
+        F: Fn(CallInfo, Next) -> Result<MultiValue> + MaybeSend + 'static,
+    {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).callback_middleware = Some(XRc::new(middleware)) };
+    }
+
+    /// Removes a middleware previously set by [`Lua::set_callback_middleware`].
+    pub fn remove_callback_middleware(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).callback_middleware = None };
+    }
+
+    /// Gets information about the interpreter runtime stack.
+    ///
+    /// This function returns [`Debug`] structure that can be used to get information about the
+    /// function executing at a given level. Level `0` is the current running function, whereas
+    /// level `n+1` is the function that has called level `n` (except for tail calls, which do
+    /// not count in the stack).
+    ///
+    /// [`Debug`]: crate::hook::Debug
+    pub fn inspect_stack(&self, level: usize) -> Option<Debug> {
+        let lua = self.lock();
+        unsafe {
+            let mut ar: ffi::lua_Debug = mem::zeroed();
+            let level = level as c_int;
+            #[cfg(not(feature = "luau"))]
+            if ffi::lua_getstack(lua.state(), level, &mut ar) == 0 {
+                return None;
+            }
+            #[cfg(feature = "luau")]
+            if ffi::lua_getinfo(lua.state(), level, cstr!(""), &mut ar) == 0 {
+                return None;
+            }
+            Some(Debug::new_owned(lua, level, ar))
+        }
+    }
+
+    /// Returns the mechanism used by Lua to propagate errors.
+    ///
+    /// See [`UnwindMode`] for details.
+    pub fn unwind_mode() -> UnwindMode {
+        match ffi::LUA_UNWIND_MODE {
+            "cxx" => UnwindMode::CxxExceptions,
+            "external" => UnwindMode::External,
+            _ => UnwindMode::Longjmp,
+        }
+    }
+
+    /// Returns the Lua version and the language features it supports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let info = Lua::version_info();
+    /// let source = if info.bitwise_operators { "return 6 & 3" } else { "return 2" };
+    /// assert_eq!(lua.load(source).eval::<i32>()?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn version_info() -> VersionInfo {
+        VersionInfo::current()
+    }
+
+    /// Returns the amount of memory (in bytes) currently used inside this Lua state.
+    pub fn used_memory(&self) -> usize {
+        let lua = self.lock();
+        unsafe {
+            match MemoryState::get(lua.main_state) {
+                mem_state if !mem_state.is_null() => (*mem_state).used_memory(),
+                _ => {
+                    // Get data from the Lua GC
+                    let used_kbytes = ffi::lua_gc(lua.main_state, ffi::LUA_GCCOUNT, 0);
                     let used_kbytes_rem = ffi::lua_gc(lua.main_state, ffi::LUA_GCCOUNTB, 0);
                     (used_kbytes as usize) * 1024 + (used_kbytes_rem as usize)
                 }
@@ -751,6 +1359,169 @@ impl Lua {
         }
     }
 
+    /// Returns a snapshot of the Lua state metrics (garbage collection, memory, callbacks, errors
+    /// and threads).
+    ///
+    /// The snapshot is cheap to take, so it can be polled periodically to feed a metrics
+    /// exporter, see [`MetricsSnapshot::to_prometheus`].
+    ///
+    /// Requires `feature = "metrics"`
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        let used_memory = self.used_memory();
+        let lua = self.lock();
+        let metrics = unsafe { &(*lua.extra.get()).metrics };
+        MetricsSnapshot {
+            gc_cycles: metrics.gc_cycles(),
+            used_memory,
+            callbacks_invoked: metrics.callbacks_invoked,
+            errors_raised: metrics.errors_raised,
+            threads_created: metrics.threads_created,
+            active_threads: metrics.active_threads,
+            strings_created: metrics.strings_created,
+            string_bytes: metrics.string_bytes,
+        }
+    }
+
+    /// Returns up to `n` strings most frequently created from Rust (in descending order).
+    ///
+    /// Returns an empty vector if the [`track_hot_strings`] option is not enabled.
+    ///
+    /// Requires `feature = "metrics"`
+    ///
+    /// [`track_hot_strings`]: LuaOptions::track_hot_strings
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub fn hot_strings(&self, n: usize) -> Vec<HotString> {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).metrics.hot_strings(n) }
+    }
+
+    /// Returns the amount of memory (in bytes) currently used by each chunk (source).
+    ///
+    /// The keys are chunk names, see [`Chunk::set_name`]. Memory allocated outside of any Lua
+    /// call (for example, when creating values from Rust) is not attributed to any source.
+    ///
+    /// Returns an empty map if the [`track_memory_by_source`] option is not enabled, or the Lua
+    /// state does not use the mlua allocator (e.g. in module mode or with LuaJIT on some
+    /// platforms).
+    ///
+    /// [`track_memory_by_source`]: LuaOptions::track_memory_by_source
+    pub fn memory_by_source(&self) -> HashMap<StdString, usize> {
+        let lua = self.lock();
+        unsafe {
+            match MemoryState::get(lua.main_state) {
+                mem_state if !mem_state.is_null() => (*mem_state)
+                    .memory_by_source()
+                    .map(|(name, size)| (name.to_string(), size))
+                    .collect(),
+                _ => HashMap::new(),
+            }
+        }
+    }
+
+    /// Returns usage statistics of the auxiliary stack where references to Lua values are stored.
+    pub fn ref_stack_usage(&self) -> RefStackUsage {
+        let lua = self.lock();
+        let extra = unsafe { &*lua.extra.get() };
+        // Slots reserved by value scopes are not counted until used
+        let reserved = (extra.ref_blocks.iter())
+            .map(|b| b.capacity() - b.used())
+            .sum::<usize>();
+        RefStackUsage {
+            used: (extra.ref_stack_top as usize).saturating_sub(extra.ref_free.len() + reserved),
+            free: extra.ref_free.len(),
+            capacity: extra.ref_stack_size as usize,
+        }
+    }
+
+    /// Calls the given function with a [`ValueScope`] that holds references to temporary values.
+    ///
+    /// This reduces the bookkeeping for conversion-heavy operations that create and drop many
+    /// values, such as converting large data structures. References created inside the scope
+    /// are allocated from a reserved block of slots of the auxiliary stack, which is released at
+    /// once when the scope ends.
+    ///
+    /// Values may outlive the scope (e.g. returned from the function): their slots remain
+    /// allocated until they are dropped. If the block is exhausted, references are allocated as
+    /// usual.
+    ///
+    /// The Lua state is locked by the current thread until the scope ends.
+    ///
+    /// Scopes can be nested. The scope reserves 256 slots, use
+    /// [`value_scope_with_capacity`] to choose a different size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let list = lua.value_scope(|_| {
+    ///     let list = lua.create_table()?;
+    ///     for i in 1..=1000 {
+    ///         let item = lua.create_table()?;
+    ///         item.set("id", i)?;
+    ///         list.push(item)?;
+    ///     }
+    ///     Ok::<_, mlua::Error>(list)
+    /// })?;
+    /// assert_eq!(list.len()?, 1000);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`value_scope_with_capacity`]: #method.value_scope_with_capacity
+    pub fn value_scope<R>(&self, f: impl FnOnce(&ValueScope) -> R) -> R {
+        self.value_scope_with_capacity(256, f)
+    }
+
+    /// Calls the given function with a [`ValueScope`] that reserves `capacity` slots.
+    ///
+    /// See [`value_scope`] for details.
+    ///
+    /// [`value_scope`]: #method.value_scope
+    pub fn value_scope_with_capacity<R>(&self, capacity: usize, f: impl FnOnce(&ValueScope) -> R) -> R {
+        struct BlockGuard<'a>(&'a RawLua);
+
+        impl Drop for BlockGuard<'_> {
+            fn drop(&mut self) {
+                unsafe { util::ref_block_pop(self.0.extra.get()) };
+            }
+        }
+
+        // Blocks are released in LIFO order, so the state is locked for the whole scope to not
+        // interleave with scopes of other threads
+        let lua = self.lock();
+        let depth = unsafe {
+            util::ref_block_push(lua.extra.get(), capacity);
+            (*lua.extra.get()).ref_blocks.len() - 1
+        };
+        let _guard = BlockGuard(&lua);
+        f(&ValueScope {
+            lua: self,
+            depth,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Returns the creation sites of all references to Lua values that are currently alive.
+    ///
+    /// Calling this method before dropping the last handle to [`Lua`] can be used to find
+    /// references that were leaked. When Lua is dropped, the same report is printed to stderr
+    /// if any references are still alive. Note that this includes references owned by values
+    /// stored inside Lua, such as Rust callbacks, userdata or app data.
+    ///
+    /// Requires `feature = "ref-audit"`
+    #[cfg(feature = "ref-audit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ref-audit")))]
+    pub fn live_refs(&self) -> Vec<RefOrigin> {
+        let lua = self.lock();
+        let extra = unsafe { &*lua.extra.get() };
+        extra.ref_origins.values().cloned().collect()
+    }
+
     /// Sets a memory limit (in bytes) on this Lua state.
     ///
     /// Once an allocation occurs that would pass this memory limit,
@@ -921,6 +1692,297 @@ impl Lua {
         }
     }
 
+    /// Enables (or disables) deterministic iteration order for [`Table::pairs`].
+    ///
+    /// When enabled, the pairs are returned in the same order as by [`Table::sorted_pairs`]:
+    /// booleans, numbers (in ascending order), strings (in lexicographic byte order) and then other
+    /// keys. This makes the iteration order independent of the hash seed, which is useful for
+    /// reproducible outputs and content hashing. Other keys are ordered by their address, so
+    /// tables with non-primitive keys are not iterated in a reproducible order.
+    ///
+    /// It's disabled by default, as sorting requires collecting all table keys upfront.
+    pub fn set_deterministic_pairs(&self, enabled: bool) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).deterministic_pairs = enabled };
+    }
+
+    /// Sets the behavior of converting Rust integers that do not fit into the Lua integer type.
+    ///
+    /// By default, such values are converted to Lua numbers (floats), which can lose precision
+    /// for large values like database ids and hashes.
+    ///
+    /// See [`IntegerOverflow`] for details.
+    pub fn set_integer_overflow(&self, policy: IntegerOverflow) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).integer_overflow = policy };
+    }
+
+    /// Sets the representation of [`Duration`] values in Lua.
+    ///
+    /// See [`DurationFormat`] for details.
+    ///
+    /// [`Duration`]: std::time::Duration
+    pub fn set_duration_format(&self, format: DurationFormat) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).duration_format = format };
+    }
+
+    /// Sets the formatting of Lua numbers (floats) converted to strings by `tostring` and `print`.
+    ///
+    /// This makes the output consistent across platforms, C locales and Lua versions.
+    /// The functions are replaced in the globals table, so this method should be called after
+    /// loading the standard library. Implicit conversions (e.g. string concatenation with `..`)
+    /// are performed by the Lua VM and are not affected.
+    ///
+    /// See [`NumberFormat`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, NumberFormat, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_number_format(NumberFormat::new().precision(Some(3)).float_suffix(false))?;
+    /// assert_eq!(lua.load("return tostring(2 / 3)").eval::<String>()?, "0.667");
+    /// assert_eq!(lua.load("return tostring(2.0)").eval::<String>()?, "2");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_number_format(&self, format: NumberFormat) -> Result<()> {
+        crate::number_format::install(self)?;
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).number_format = Some(format) };
+        Ok(())
+    }
+
+    /// Removes a number format previously set by [`Lua::set_number_format`].
+    ///
+    /// Numbers are formatted by the Lua VM again.
+    pub fn remove_number_format(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).number_format = None };
+    }
+
+    /// Sets a source of random numbers for `math.random`.
+    ///
+    /// The `math.random` and `math.randomseed` functions are replaced in the globals table (so
+    /// this method should be called after loading the standard library) with versions that have
+    /// the same behavior on all Lua versions, but take random numbers from the given source.
+    /// `math.randomseed(x)` calls [`RandomSource::seed`].
+    ///
+    /// This allows seedable determinism, cryptographically secure randomness or capturing the
+    /// random numbers for replays.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let mut state = 0u64;
+    /// lua.set_random_source(move || {
+    ///     // SplitMix64
+    ///     state = state.wrapping_add(0x9e3779b97f4a7c15);
+    ///     let z = (state ^ (state >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    ///     let z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    ///     z ^ (z >> 31)
+    /// })?;
+    /// let n: f64 = lua.load("return math.random(1, 6)").eval()?;
+    /// assert!((1.0..=6.0).contains(&n));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_random_source(&self, source: impl RandomSource) -> Result<()> {
+        crate::random::install(self)?;
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).random_source = Some(Box::new(source)) };
+        Ok(())
+    }
+
+    /// Removes a random source previously set by [`Lua::set_random_source`].
+    ///
+    /// The `math.random` and `math.randomseed` functions will use the Lua built-in generator again.
+    pub fn remove_random_source(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).random_source = None };
+    }
+
+    /// Sets a policy that filters calls to the `collectgarbage` function from Lua.
+    ///
+    /// The policy is called with the `collectgarbage` option (`"collect"` when omitted) and the
+    /// remaining arguments, and decides whether to run the original function, reject the call or
+    /// return custom values instead (see [`GCAction`]). This allows preventing scripts from
+    /// triggering full collections or stopping the collector in latency-sensitive hosts, or
+    /// reporting memory usage from a different source.
+    ///
+    /// The `collectgarbage` function is replaced in the globals table, so this method should be
+    /// called after loading the standard library. It does not affect the Rust-side GC methods
+    /// such as [`Lua::gc_collect`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{GCAction, IntoLuaMulti, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_collectgarbage_policy(|lua, option, _args| match option {
+    ///     "collect" | "stop" => Ok(GCAction::Deny),
+    ///     "count" => Ok(GCAction::Return((lua.used_memory() / 1024).into_lua_multi(lua)?)),
+    ///     _ => Ok(GCAction::Allow),
+    /// })?;
+    ///
+    /// assert!(lua.load("collectgarbage()").exec().is_err());
+    /// assert!(lua.load("collectgarbage('count')").eval::<usize>()? > 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_collectgarbage_policy<F>(&self, policy: F) -> Result<()>
+    where
+        F: Fn(&Lua, &str, &MultiValue) -> Result<GCAction> + MaybeSend + 'static,
+    {
+        crate::gc_policy::install(self)?;
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).collectgarbage_policy = Some(XRc::new(policy)) };
+        Ok(())
+    }
+
+    /// Removes a policy previously set by [`Lua::set_collectgarbage_policy`].
+    ///
+    /// All `collectgarbage` calls are passed to the original function again.
+    pub fn remove_collectgarbage_policy(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).collectgarbage_policy = None };
+    }
+
+    /// Sets a [`CancellationToken`] observed by async functions called from Lua.
+    ///
+    /// Once the token is cancelled, pending and newly started async Rust functions fail with
+    /// [`Error::Cancelled`]. Pure Lua code is not interrupted, scripts can query the token
+    /// themselves if it's passed to them (e.g. as a global variable).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{CancellationToken, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let token = CancellationToken::new();
+    /// lua.set_cancellation_token(token.clone());
+    /// lua.globals().set("token", token.clone())?;
+    ///
+    /// token.cancel();
+    /// assert!(lua.load("return token:cancelled()").eval::<bool>()?);
+    /// assert!(lua.load("token:check()").exec().is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_cancellation_token(&self, token: CancellationToken) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).cancellation_token = Some(token) };
+    }
+
+    /// Returns the [`CancellationToken`] set for the Lua state (if any).
+    pub fn cancellation_token(&self) -> Option<CancellationToken> {
+        self.lock().cancellation_token()
+    }
+
+    /// Removes a token previously set by [`Lua::set_cancellation_token`].
+    pub fn remove_cancellation_token(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).cancellation_token = None };
+    }
+
+    /// Replaces `pcall` and `xpcall` with versions that pass Rust errors to Lua as inspectable
+    /// [`ErrorObject`]s.
+    ///
+    /// By default, an error raised by a Rust callback is seen by Lua code as an opaque value that
+    /// can only be converted to a string. With the replaced functions, error handlers receive an
+    /// object that exposes the error kind, message, cause chain and the Lua traceback (and
+    /// optionally the Rust backtrace), see [`ErrorObject`] for details. Other error values
+    /// (strings or tables raised by Lua code) are passed unchanged.
+    ///
+    /// The functions are replaced in the globals table, so this method should be called after
+    /// loading the standard library. Calling it again only updates the options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Error, ErrorObjectOptions, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.install_error_objects(ErrorObjectOptions::new())?;
+    ///
+    /// let f = lua.create_function(|_, ()| Err::<(), _>(Error::runtime("not found")))?;
+    /// let (kind, message): (String, String) = lua
+    ///     .load("local _, err = pcall(...); return err.kind, err.message")
+    ///     .call(f)?;
+    /// assert_eq!(kind, "runtime_error");
+    /// assert_eq!(message, "runtime error: not found");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ErrorObject`]: crate::ErrorObject
+    pub fn install_error_objects(&self, options: ErrorObjectOptions) -> Result<()> {
+        crate::error_object::install(self)?;
+        let lua = self.lock();
+        unsafe {
+            let backtraces = &mut (*lua.extra.get()).error_backtraces;
+            match (options.capture_backtrace, backtraces.is_some()) {
+                (true, false) => *backtraces = Some(Vec::new()),
+                (false, true) => *backtraces = None,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the `utf8` library on Lua versions without one (Lua 5.1, Lua 5.2 and LuaJIT).
+    ///
+    /// The library is implemented in Rust and is compatible with the Lua 5.3 `utf8` library
+    /// (`char`, `charpattern`, `codepoint`, `len`, `offset` and `codes`), so scripts relying on it
+    /// run on all Lua versions. Does nothing if the `utf8` global is already set.
+    ///
+    /// See also the [`utf8`] module for the equivalent Rust functions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.install_utf8()?;
+    /// lua.globals().set("s", "héllo")?;
+    /// assert_eq!(lua.load("utf8.len(s)").eval::<i64>()?, 5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`utf8`]: crate::utf8
+    pub fn install_utf8(&self) -> Result<()> {
+        crate::utf8::install(self)
+    }
+
+    /// Makes Lua scripts behave deterministically according to the provided options.
+    ///
+    /// This seeds `math.random`, replaces `os.time`, `os.date` and `os.clock` with host-provided
+    /// time sources, and hides memory addresses of Lua values in `tostring`, `print` and error
+    /// messages. Combined with [`Lua::set_deterministic_pairs`], scripts produce identical results
+    /// across runs and machines, which is required for replays and lockstep simulations.
+    ///
+    /// The standard library functions are replaced in the globals table, so this method should be
+    /// called after loading the libraries (and before enabling sandbox mode in Luau).
+    ///
+    /// See [`DeterministicOptions`] for details.
+    pub fn set_deterministic(&self, options: DeterministicOptions) -> Result<()> {
+        crate::deterministic::apply(self, options)
+    }
+
+    pub(crate) fn set_hide_addresses(&self, enabled: bool) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).hide_addresses = enabled };
+    }
+
     /// Sets a default Luau compiler (with custom options).
     ///
     /// This compiler will be used by default to load all Lua chunks
@@ -972,12 +2034,23 @@ impl Lua {
     #[track_caller]
     pub fn load<'a>(&self, chunk: impl AsChunk<'a>) -> Chunk<'a> {
         let caller = Location::caller();
+        let mut env = chunk.environment(self);
+        let mut env_inherited = false;
+        if let Ok(None) = env {
+            if let Some(caller_env) = self.caller_environment() {
+                env = Ok(Some(caller_env));
+                env_inherited = true;
+            }
+        }
         Chunk {
             lua: self.weak(),
             name: chunk.name().unwrap_or_else(|| caller.to_string()),
-            env: chunk.environment(self),
+            env,
             mode: chunk.mode(),
             source: chunk.source(),
+            capabilities: None,
+            import_allowlist: None,
+            env_inherited,
             #[cfg(feature = "luau")]
             compiler: unsafe { (*self.lock().extra.get()).compiler.clone() },
         }
@@ -991,6 +2064,32 @@ impl Lua {
         unsafe { self.lock().create_string(s) }
     }
 
+    /// Creates a Lua string from a UTF-8 `&str`, converting it to the given [`Encoding`].
+    ///
+    /// Returns an error if the string contains characters that cannot be represented in the
+    /// target encoding.
+    ///
+    /// Requires `feature = "encoding"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Encoding, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    ///
+    /// let s = lua.create_string_encoded("café", Encoding::Latin1)?;
+    /// assert_eq!(s.as_bytes(), b"caf\xe9");
+    /// assert!(lua.create_string_encoded("日本", Encoding::Latin1).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "encoding")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "encoding")))]
+    pub fn create_string_encoded(&self, s: &str, encoding: Encoding) -> Result<String> {
+        self.create_string(encoding.encode(s)?)
+    }
+
     /// Create and return a Luau [buffer] object from a byte slice of data.
     ///
     /// Requires `feature = "luau"`
@@ -1054,17 +2153,172 @@ impl Lua {
                 }
             }
 
-            Ok(Table(lua.pop_ref()))
-        }
+            Ok(Table(lua.pop_ref()))
+        }
+    }
+
+    /// Creates a table from an iterator of values, using `1..` as the keys.
+    pub fn create_sequence_from<T, I>(&self, iter: I) -> Result<Table>
+    where
+        T: IntoLua,
+        I: IntoIterator<Item = T>,
+    {
+        unsafe { self.lock().create_sequence_from(iter) }
+    }
+
+    /// Parses a JSON document into a Lua value using the built-in decoder.
+    ///
+    /// JSON `null` is decoded as [`Value::NULL`], arrays and objects are decoded as tables.
+    /// This method does not require `serde`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let value = lua.parse_json(r#"{"name": "mlua", "tags": ["lua", "rust"]}"#)?;
+    /// let table = value.as_table().unwrap();
+    /// assert_eq!(table.get::<String>("name")?, "mlua");
+    /// assert_eq!(table.get::<Table>("tags")?.raw_len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Value::NULL`]: crate::Value::NULL
+    pub fn parse_json(&self, json: impl AsRef<[u8]>) -> Result<Value> {
+        self.parse_json_with(json, JsonOptions::new())
+    }
+
+    /// Parses a JSON document into a Lua value using the built-in decoder with custom options.
+    ///
+    /// See [`Lua::parse_json`] for details.
+    pub fn parse_json_with(&self, json: impl AsRef<[u8]>, options: JsonOptions) -> Result<Value> {
+        crate::json::decode(self, json.as_ref(), options)
+    }
+
+    /// Converts a [`serde_json::Value`] to a Lua value.
+    ///
+    /// This is a direct conversion that is faster than going through the generic `serde` path
+    /// (it does not require the `serialize` feature). JSON `null` is converted to
+    /// [`Value::NULL`], arrays and objects are converted to tables. Numbers are converted according
+    /// to the given [`NumberPolicy`].
+    ///
+    /// Requires `feature = "serde_json"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, NumberPolicy, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let json = serde_json::json!({ "name": "mlua", "version": 1.0 });
+    /// let value = lua.from_json_value(&json, NumberPolicy::PreferInteger)?;
+    /// let table = value.as_table().unwrap();
+    /// assert_eq!(table.get::<String>("name")?, "mlua");
+    /// assert!(table.get::<mlua::Value>("version")?.is_integer());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Value::NULL`]: crate::Value::NULL
+    #[cfg(feature = "serde_json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde_json")))]
+    pub fn from_json_value(&self, value: &serde_json::Value, policy: NumberPolicy) -> Result<Value> {
+        crate::json::from_json_value(self, value, policy)
+    }
+
+    /// Converts a Lua value to a [`serde_json::Value`].
+    ///
+    /// This is the reverse of [`Lua::from_json_value`]. Sequences (and empty tables marked as
+    /// arrays) are converted to JSON arrays, other tables to objects. Numbers are converted
+    /// according to the given [`NumberPolicy`]. Unsupported values (such as functions), recursive
+    /// tables and non-finite numbers cause an error. Metamethods are not invoked.
+    ///
+    /// Requires `feature = "serde_json"`
+    #[cfg(feature = "serde_json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serde_json")))]
+    pub fn to_json_value(&self, value: &Value, policy: NumberPolicy) -> Result<serde_json::Value> {
+        crate::json::to_json_value(value, policy)
+    }
+
+    /// Loads the `json` module, giving scripts access to `json.encode` and `json.decode`.
+    ///
+    /// The module is set as the `json` global and stored in `package.loaded`, so scripts can also
+    /// `require("json")`. It is implemented on top of the [`serde`](crate::serde) integration and
+    /// uses the same conventions: JSON `null` is represented by [`LuaSerdeExt::null`] (available
+    /// as `json.null`), and arrays have the [`LuaSerdeExt::array_metatable`] (available as
+    /// `json.array_mt`), so empty arrays are encoded back as `[]`.
+    ///
+    /// `json.encode(value [, options])` accepts an optional table with the `pretty` and
+    /// `sort_keys` boolean fields.
+    ///
+    /// Requires `feature = "serialize"` and `feature = "serde_json"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.load_std_json()?;
+    ///
+    /// let json: String = lua.load(r#"
+    ///     local data = json.decode('{"tags": [], "name": null}')
+    ///     assert(data.name == json.null)
+    ///     return json.encode(data, {sort_keys = true})
+    /// "#).eval()?;
+    /// assert_eq!(json, r#"{"name":null,"tags":[]}"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`LuaSerdeExt::null`]: crate::LuaSerdeExt::null
+    /// [`LuaSerdeExt::array_metatable`]: crate::LuaSerdeExt::array_metatable
+    #[cfg(all(feature = "serialize", feature = "serde_json"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "serialize", feature = "serde_json"))))]
+    pub fn load_std_json(&self) -> Result<()> {
+        let json = crate::serde::json::create_module(self)?;
+        self.loaded_table()?.raw_set("json", &json)?;
+        self.globals().raw_set("json", json)
     }
 
-    /// Creates a table from an iterator of values, using `1..` as the keys.
-    pub fn create_sequence_from<T, I>(&self, iter: I) -> Result<Table>
-    where
-        T: IntoLua,
-        I: IntoIterator<Item = T>,
-    {
-        unsafe { self.lock().create_sequence_from(iter) }
+    /// Converts a Lua value to Lua source code that evaluates to an equal value.
+    ///
+    /// Tables are printed as table constructors (e.g. `{1, 2, a = 1, [10] = "x"}`), with the
+    /// sequence part first and the remaining keys in a stable order (booleans, numbers, strings),
+    /// so the output is suitable for writing configuration back to editable Lua files.
+    /// Recursive tables are rejected. Metamethods are not invoked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, LuaSourceOptions, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let value: Value = lua.load(r#"{ "x", name = "mlua", [10] = true }"#).eval()?;
+    /// let source = lua.to_lua_source(&value, LuaSourceOptions::new())?;
+    /// assert_eq!(source, r#"{"x", [10] = true, name = "mlua"}"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_lua_source(&self, value: &Value, options: LuaSourceOptions) -> Result<StdString> {
+        crate::lua_source::encode(value, options)
+    }
+
+    /// Creates an implementation of the trait `T` backed by a Lua table.
+    ///
+    /// Each trait method calls the Lua function with the same name from the `table`, passing the
+    /// table itself as the first argument. This allows Lua code to supply behavior consumed by
+    /// generic Rust code, for example a strategy or a plug-in.
+    ///
+    /// The trait must be annotated with the [`lua_trait`] attribute macro (or implement
+    /// [`LuaTrait`] manually). Returns an error if the table lacks any required method.
+    ///
+    /// [`lua_trait`]: crate::lua_trait
+    /// [`LuaTrait`]: crate::LuaTrait
+    pub fn implement_trait<T: LuaTrait + ?Sized>(&self, table: Table) -> Result<Box<T>> {
+        T::from_lua_table(table)
     }
 
     /// Wraps a Rust function or closure, creating a callable Lua function handle to it.
@@ -1118,10 +2372,10 @@ impl Lua {
         A: FromLuaMulti,
         R: IntoLuaMulti,
     {
-        (self.lock()).create_callback(Box::new(move |rawlua, nargs| unsafe {
+        (self.lock()).create_callback(move |rawlua, nargs| unsafe {
             let args = A::from_stack_args(nargs, 1, None, rawlua)?;
             func(rawlua.lua(), args)?.push_into_stack_multi(rawlua)
-        }))
+        })
     }
 
     /// Wraps a Rust mutable closure, creating a callable Lua function handle to it.
@@ -1142,6 +2396,90 @@ impl Lua {
         })
     }
 
+    /// Wraps a Rust function or closure, creating a callable Lua function that requires the given
+    /// capabilities.
+    ///
+    /// Before the function runs, mlua finds the nearest Lua function in the call stack and checks
+    /// that its environment has been granted all the `required` capabilities (using
+    /// [`grant_capabilities`] or [`Chunk::set_capabilities`]). Otherwise the call fails with
+    /// [`Error::PermissionDenied`].
+    ///
+    /// Calls without a Lua caller (for example, directly from Rust or as a coroutine body) and calls
+    /// from Lua functions without an environment are always denied. Note that a tail call
+    /// (`return f()`) may remove the calling function from the stack.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Capability, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let read_file = lua.create_function_with_capabilities([Capability::FS_READ], |_, path: String| {
+    ///     Ok(format!("contents of {path}"))
+    /// })?;
+    /// lua.globals().set("read_file", read_file)?;
+    ///
+    /// assert!(lua.load(r#"read_file("a.txt")"#).exec().is_err());
+    ///
+    /// let contents: String = lua
+    ///     .load(r#"return (read_file("a.txt"))"#)
+    ///     .set_capabilities(["fs"])
+    ///     .eval()?;
+    /// assert_eq!(contents, "contents of a.txt");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`grant_capabilities`]: #method.grant_capabilities
+    pub fn create_function_with_capabilities<F, A, R>(
+        &self,
+        required: impl IntoIterator<Item = impl Into<Capability>>,
+        func: F,
+    ) -> Result<Function>
+    where
+        F: Fn(&Lua, A) -> Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+    {
+        let required: Vec<Capability> = required.into_iter().map(Into::into).collect();
+        (self.lock()).create_callback(move |rawlua, nargs| unsafe {
+            crate::capability::check_caller(rawlua, &required)?;
+            let args = A::from_stack_args(nargs, 1, None, rawlua)?;
+            func(rawlua.lua(), args)?.push_into_stack_multi(rawlua)
+        })
+    }
+
+    /// Grants capabilities to Lua code running in the given environment.
+    ///
+    /// Grants are attached to the environment table and do not keep it alive.
+    ///
+    /// See [`create_function_with_capabilities`] for details.
+    ///
+    /// [`create_function_with_capabilities`]: #method.create_function_with_capabilities
+    pub fn grant_capabilities(
+        &self,
+        env: &Table,
+        caps: impl IntoIterator<Item = impl Into<Capability>>,
+    ) -> Result<()> {
+        crate::capability::grant(self, env, caps.into_iter().map(Into::into))
+    }
+
+    /// Revokes capabilities previously granted to the given environment.
+    ///
+    /// Only exact grants are revoked, eg. revoking `fs.read` does not affect a granted `fs`.
+    pub fn revoke_capabilities(
+        &self,
+        env: &Table,
+        caps: impl IntoIterator<Item = impl Into<Capability>>,
+    ) -> Result<()> {
+        crate::capability::revoke(self, env, caps.into_iter().map(Into::into))
+    }
+
+    /// Returns `true` if the capability is granted to the given environment.
+    pub fn has_capability(&self, env: &Table, cap: impl Into<Capability>) -> Result<bool> {
+        crate::capability::is_granted(self, env, &cap.into())
+    }
+
     /// Wraps a C function, creating a callable Lua function handle to it.
     ///
     /// # Safety
@@ -1213,6 +2551,98 @@ impl Lua {
         }))
     }
 
+    /// Wraps a Rust async function or closure, limiting the number of concurrent calls.
+    ///
+    /// Works like [`Lua::create_async_function`], but at most [`AsyncLimiter::limit`] calls
+    /// can be in-flight at the same time. Further calls suspend the calling coroutine until
+    /// a running call completes. The returned future is not polled until the call is admitted.
+    ///
+    /// The same limiter can be shared between multiple functions to enforce a common limit.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mlua::{AsyncLimiter, Lua, Result};
+    ///
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let limiter = AsyncLimiter::new(10);
+    /// let fetch = lua.create_async_function_with_limiter(
+    ///     |_, url: String| async move { Ok(format!("fetched {url}")) },
+    ///     limiter.clone(),
+    /// )?;
+    /// lua.globals().set("fetch", fetch)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_async_function_with_limiter<F, A, FR, R>(
+        &self,
+        func: F,
+        limiter: AsyncLimiter,
+    ) -> Result<Function>
+    where
+        F: Fn(Lua, A) -> FR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        FR: Future<Output = Result<R>> + MaybeSend + 'static,
+        R: IntoLuaMulti,
+    {
+        self.create_async_function(move |lua, args| {
+            let fut = func(lua, args);
+            let acquire = limiter.acquire();
+            async move {
+                let _permit = acquire.await;
+                fut.await
+            }
+        })
+    }
+
+    /// Creates a table with constructors of synchronization primitives for Lua coroutines.
+    ///
+    /// The table contains `Mutex()`, `Semaphore(permits)` and `Channel([capacity])` functions
+    /// returning [`AsyncMutex`], [`AsyncSemaphore`] and [`AsyncChannel`] userdata respectively.
+    /// Waiting methods (`lock`, `acquire`, `send`, `recv`) are async and must be called from
+    /// a coroutine driven by an async executor (e.g. a function called with
+    /// [`Function::call_async`]).
+    ///
+    /// [`AsyncMutex`]: crate::AsyncMutex
+    /// [`AsyncSemaphore`]: crate::AsyncSemaphore
+    /// [`AsyncChannel`]: crate::AsyncChannel
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.globals().set("sync", lua.create_sync_module()?)?;
+    /// let sum: i64 = lua.load(r#"
+    ///     local ch = sync.Channel()
+    ///     for i = 1, 3 do ch:send(i) end
+    ///     ch:close()
+    ///     local sum = 0
+    ///     local v = ch:recv()
+    ///     while v ~= nil do
+    ///         sum = sum + v
+    ///         v = ch:recv()
+    ///     end
+    ///     return sum
+    /// "#).eval_async().await?;
+    /// assert_eq!(sum, 6);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_sync_module(&self) -> Result<Table> {
+        crate::sync::create_sync_module(self)
+    }
+
     /// Wraps a Lua function into a new thread (or coroutine).
     ///
     /// Equivalent to `coroutine.create`.
@@ -1220,6 +2650,36 @@ impl Lua {
         unsafe { self.lock().create_thread(&func) }
     }
 
+    /// Creates a new [`ThreadGroup`] to supervise a set of coroutines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, ThreadStatus};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let group = lua.create_thread_group()?;
+    ///
+    /// // Run a plugin in its own environment
+    /// let env = lua.create_table()?;
+    /// group.install(&env)?;
+    /// lua.load(r#"
+    ///     worker = coroutine.wrap(function() while true do coroutine.yield() end end)
+    ///     worker()
+    /// "#)
+    /// .set_environment(env)
+    /// .exec()?;
+    ///
+    /// let threads = group.threads()?;
+    /// assert_eq!(threads.len(), 1);
+    /// assert_eq!(threads[0].status(), ThreadStatus::Resumable);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_thread_group(&self) -> Result<ThreadGroup> {
+        ThreadGroup::new(self)
+    }
+
     /// Creates a Lua userdata object from a custom userdata type.
     ///
     /// All userdata instances of the same type `T` shares the same metatable.
@@ -1274,6 +2734,66 @@ impl Lua {
         unsafe { (self.lock()).make_any_userdata(UserDataStorage::new_ser(data)) }
     }
 
+    /// Creates a Lua userdata object from a shared [`Arc`], preserving the object identity.
+    ///
+    /// Pushing the same `Arc` (pointing to the same allocation) multiple times returns the same
+    /// userdata object while it is alive, so shared entities can be compared or used as table
+    /// keys in Lua. The cache holds the userdata objects weakly and does not prevent them from
+    /// being garbage collected, its entries are removed when the objects are collected.
+    ///
+    /// The userdata type is `Arc<T>` (not `T`), so implementations of [`UserData`] for `T` are not
+    /// used. Unless methods are added using [`Lua::register_userdata_type::<Arc<T>>()`], the object
+    /// is opaque to Lua (has no fields or methods) and can only be passed back to Rust, where the
+    /// `Arc` can be borrowed using [`AnyUserData::borrow::<Arc<T>>()`].
+    ///
+    /// [`AnyUserData::borrow::<Arc<T>>()`]: crate::AnyUserData::borrow
+    /// [`Lua::register_userdata_type::<Arc<T>>()`]: Lua::register_userdata_type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let entity = Arc::new("player".to_string());
+    /// lua.globals().set("a", lua.create_shared_userdata(entity.clone())?)?;
+    /// lua.globals().set("b", lua.create_shared_userdata(entity)?)?;
+    /// assert!(lua.load("a == b").eval::<bool>()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_shared_userdata<T>(&self, data: Arc<T>) -> Result<AnyUserData>
+    where
+        T: ?Sized + 'static,
+        Arc<T>: MaybeSend,
+    {
+        let cache = match self.named_registry_value::<Option<Table>>(SHARED_USERDATA_KEY)? {
+            Some(cache) => cache,
+            None => {
+                let cache = self.create_table()?;
+                let mt = self.create_table()?;
+                mt.raw_set("__mode", "v")?;
+                cache.set_metatable(Some(mt));
+                self.set_named_registry_value(SHARED_USERDATA_KEY, &cache)?;
+                cache
+            }
+        };
+
+        let key = LightUserData(Arc::as_ptr(&data) as *const () as *mut c_void);
+        if let Some(ud) = cache.raw_get::<Option<AnyUserData>>(key)? {
+            // The cached object could be destructed and the allocation reused by another `Arc`
+            match ud.borrow::<Arc<T>>() {
+                Ok(cached) if Arc::ptr_eq(&cached, &data) => return Ok(ud),
+                Err(Error::UserDataBorrowError) if ud.is::<Arc<T>>() => return Ok(ud),
+                _ => {}
+            }
+        }
+        let ud = self.create_any_userdata(data)?;
+        cache.raw_set(key, &ud)?;
+        Ok(ud)
+    }
+
     /// Registers a custom Rust type in Lua to use in userdata objects.
     ///
     /// This methods provides a way to add fields or methods to userdata objects of a type `T`.
@@ -1363,56 +2883,221 @@ impl Lua {
             let _sg = StackGuard::new(state);
             assert_stack(state, 2);
 
-            match T::TYPE_ID {
-                ffi::LUA_TBOOLEAN => {
-                    ffi::lua_pushboolean(state, 0);
-                }
-                ffi::LUA_TLIGHTUSERDATA => {
-                    ffi::lua_pushlightuserdata(state, ptr::null_mut());
-                }
-                ffi::LUA_TNUMBER => {
-                    ffi::lua_pushnumber(state, 0.);
-                }
-                #[cfg(feature = "luau")]
-                ffi::LUA_TVECTOR => {
-                    #[cfg(not(feature = "luau-vector4"))]
-                    ffi::lua_pushvector(state, 0., 0., 0.);
-                    #[cfg(feature = "luau-vector4")]
-                    ffi::lua_pushvector(state, 0., 0., 0., 0.);
-                }
-                ffi::LUA_TSTRING => {
-                    ffi::lua_pushstring(state, b"\0" as *const u8 as *const _);
-                }
-                ffi::LUA_TFUNCTION => match self.load("function() end").eval::<Function>() {
-                    Ok(func) => lua.push_ref(&func.0),
-                    Err(_) => return,
-                },
-                ffi::LUA_TTHREAD => {
-                    ffi::lua_newthread(state);
-                }
-                _ => {}
-            }
-            match metatable {
-                Some(metatable) => lua.push_ref(&metatable.0),
-                None => ffi::lua_pushnil(state),
-            }
-            ffi::lua_setmetatable(state, -2);
-        }
+            match T::TYPE_ID {
+                ffi::LUA_TBOOLEAN => {
+                    ffi::lua_pushboolean(state, 0);
+                }
+                ffi::LUA_TLIGHTUSERDATA => {
+                    ffi::lua_pushlightuserdata(state, ptr::null_mut());
+                }
+                ffi::LUA_TNUMBER => {
+                    ffi::lua_pushnumber(state, 0.);
+                }
+                #[cfg(feature = "luau")]
+                ffi::LUA_TVECTOR => {
+                    #[cfg(not(feature = "luau-vector4"))]
+                    ffi::lua_pushvector(state, 0., 0., 0.);
+                    #[cfg(feature = "luau-vector4")]
+                    ffi::lua_pushvector(state, 0., 0., 0., 0.);
+                }
+                ffi::LUA_TSTRING => {
+                    ffi::lua_pushstring(state, b"\0" as *const u8 as *const _);
+                }
+                ffi::LUA_TFUNCTION => match self.load("function() end").eval::<Function>() {
+                    Ok(func) => lua.push_ref(&func.0),
+                    Err(_) => return,
+                },
+                ffi::LUA_TTHREAD => {
+                    ffi::lua_newthread(state);
+                }
+                _ => {}
+            }
+            match metatable {
+                Some(metatable) => lua.push_ref(&metatable.0),
+                None => ffi::lua_pushnil(state),
+            }
+            ffi::lua_setmetatable(state, -2);
+        }
+    }
+
+    /// Returns a handle to the global environment.
+    pub fn globals(&self) -> Table {
+        let lua = self.lock();
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            assert_stack(state, 1);
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, ffi::LUA_RIDX_GLOBALS);
+            #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+            ffi::lua_pushvalue(state, ffi::LUA_GLOBALSINDEX);
+            Table(lua.pop_ref())
+        }
+    }
+
+    /// Sets a global variable whose value is created on the first access.
+    ///
+    /// The `init` function is called once, when the global is read for the first time (by a script
+    /// or by [`Table::get`] on the globals table), and the result is stored as a regular global.
+    /// This is useful to defer building expensive bindings that might not be used.
+    ///
+    /// The deferred initialization is implemented by the `__index` metamethod of the globals
+    /// table. An existing `__index` metamethod is kept and used for other keys, but replacing the
+    /// globals metatable afterwards disables the lazy globals. Lazy globals are not visible to
+    /// raw access or iteration until initialized.
+    ///
+    /// If `init` fails, the error is raised at the access and the global remains unset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.set_lazy_global("config", |lua| {
+    ///     let config = lua.create_table()?;
+    ///     config.set("debug", true)?;
+    ///     Ok(config)
+    /// })?;
+    ///
+    /// assert!(lua.load("config.debug").eval::<bool>()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_lazy_global<F, V>(&self, name: &str, init: F) -> Result<()>
+    where
+        F: FnOnce(&Lua) -> Result<V> + MaybeSend + 'static,
+        V: IntoLua,
+    {
+        lazy::set_lazy_global(self, name, init)
+    }
+
+    /// Runs the closure with an isolated view of the global environment and commits the changes
+    /// to the real globals only if the closure succeeds.
+    ///
+    /// The closure receives [`IsolatedGlobals`], whose [`env`] table should be used as the
+    /// environment of chunks (see [`Chunk::set_environment`]). Reading globals from the
+    /// environment falls through to the real globals, while assignments are recorded separately.
+    /// If the closure returns an error or calls [`IsolatedGlobals::abort`], the recorded changes
+    /// are discarded; otherwise they are applied to the real globals.
+    ///
+    /// Only assignments to global variables are isolated. Modifications of tables reachable from
+    /// globals (e.g. `string.foo = 1`) are applied immediately. Iterating the environment with
+    /// `pairs` does not return any values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.globals().set("limit", 10)?;
+    ///
+    /// // Dry run
+    /// let limit = lua.run_isolated(|iso| {
+    ///     lua.load("limit = limit * 2").set_environment(iso.env().clone()).exec()?;
+    ///     iso.abort();
+    ///     iso.env().get::<i64>("limit")
+    /// })?;
+    /// assert_eq!(limit, 20);
+    /// assert_eq!(lua.globals().get::<i64>("limit")?, 10);
+    ///
+    /// // Changes are rolled back on error
+    /// let res = lua.run_isolated(|iso| {
+    ///     lua.load("limit = 0; error('invalid config')").set_environment(iso.env().clone()).exec()
+    /// });
+    /// assert!(res.is_err());
+    /// assert_eq!(lua.globals().get::<i64>("limit")?, 10);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`env`]: IsolatedGlobals::env
+    /// [`Chunk::set_environment`]: crate::Chunk::set_environment
+    pub fn run_isolated<R>(&self, f: impl FnOnce(&IsolatedGlobals) -> Result<R>) -> Result<R> {
+        crate::isolate::run_isolated(self, f)
+    }
+
+    /// Creates a minimal [busted]-like test module.
+    ///
+    /// The returned table contains the following functions:
+    /// - `describe(name, fn)` groups tests, calling `fn` immediately
+    /// - `it(name, fn)` declares a test
+    /// - `pending(name)` declares a skipped test
+    /// - `before_each(fn)` and `after_each(fn)` declare hooks for the tests in the current group
+    /// - `expect(value)` returns an object with matchers: `to_be`, `to_equal` (deep equality),
+    ///   `to_be_nil`, `to_be_truthy`, `to_be_falsy`, `to_be_a`, `to_be_close_to`,
+    ///   `to_be_greater_than`, `to_be_less_than`, `to_have_length`, `to_contain`, `to_match`,
+    ///   and `to_throw`. Matchers are negated using `expect(value).never`.
+    /// - `run()` runs the declared tests and returns a summary table with `passed`, `failed`,
+    ///   `skipped` counts and the list of `failures` (with `name` and `message` fields).
+    ///
+    /// To run test files from Rust, see [`Lua::run_lua_tests`].
+    ///
+    /// Requires `feature = "testing"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.globals().set("test", lua.create_test_module()?)?;
+    /// let summary: Table = lua
+    ///     .load(r#"
+    ///         test.describe("math", function()
+    ///             test.it("adds", function()
+    ///                 test.expect(1 + 1):to_be(2)
+    ///             end)
+    ///             test.it("fails", function()
+    ///                 test.expect({1, 2}).never:to_equal({1, 2})
+    ///             end)
+    ///         end)
+    ///         return test.run()
+    ///     "#)
+    ///     .eval()?;
+    /// assert_eq!(summary.get::<usize>("passed")?, 1);
+    /// assert_eq!(summary.get::<usize>("failed")?, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [busted]: https://lunarmodules.github.io/busted/
+    #[cfg(feature = "testing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+    pub fn create_test_module(&self) -> Result<Table> {
+        crate::testing::create_test_module(self)
+    }
+
+    /// Runs Lua test files and returns the report.
+    ///
+    /// If `path` is a directory, all `*_spec.lua` and `*_spec.luau` files in it (recursively) are
+    /// run in alphabetical order. Otherwise `path` is run as a single test file.
+    ///
+    /// Each file runs in a separate environment (falling back to the globals) with the test
+    /// module functions (see [`Lua::create_test_module`]) available as globals. Tests are run
+    /// after the file is loaded.
+    ///
+    /// Returns an error only if the test files cannot be found. Test failures (including failures
+    /// to load a file) are recorded in the report.
+    ///
+    /// Requires `feature = "testing"`
+    #[cfg(feature = "testing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+    pub fn run_lua_tests(&self, path: impl AsRef<std::path::Path>) -> Result<TestReport> {
+        crate::testing::run_lua_tests(self, path.as_ref())
     }
 
-    /// Returns a handle to the global environment.
-    pub fn globals(&self) -> Table {
-        let lua = self.lock();
-        let state = lua.state();
-        unsafe {
-            let _sg = StackGuard::new(state);
-            assert_stack(state, 1);
-            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
-            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, ffi::LUA_RIDX_GLOBALS);
-            #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
-            ffi::lua_pushvalue(state, ffi::LUA_GLOBALSINDEX);
-            Table(lua.pop_ref())
-        }
+    /// Asynchronously runs Lua test files and returns the report.
+    ///
+    /// Unlike [`Lua::run_lua_tests`], tests and hooks are run as coroutines, so they can call
+    /// async Rust functions.
+    ///
+    /// Requires `feature = "testing"` and `feature = "async"`
+    #[cfg(all(feature = "testing", feature = "async"))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "testing", feature = "async"))))]
+    pub async fn run_lua_tests_async(&self, path: impl AsRef<std::path::Path>) -> Result<TestReport> {
+        crate::testing::run_lua_tests_async(self, path.as_ref()).await
     }
 
     /// Returns a handle to the active `Thread`. For calls to `Lua` this will be the main Lua
@@ -1429,6 +3114,13 @@ impl Lua {
         }
     }
 
+    /// Returns a copy of the thread-local value of type `T` of the active thread.
+    ///
+    /// This is a shortcut for calling [`Thread::get_local`] on [`Lua::current_thread`].
+    pub fn current_thread_local<T: Clone + 'static>(&self) -> Result<Option<T>> {
+        self.current_thread().get_local()
+    }
+
     /// Calls the given function with a `Scope` parameter, giving the function the ability to create
     /// userdata and callbacks from rust types that are !Send or non-'static.
     ///
@@ -1456,6 +3148,35 @@ impl Lua {
         f(&mut Scope::new(self.lock_arc()))
     }
 
+    /// Converts the items to strings using the `tostring` semantics and concatenates them.
+    ///
+    /// Numbers are formatted as Lua formats them and `__tostring` metamethods are respected, see
+    /// [`ToLuaString`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, ToLuaString, Value};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let point: Value = lua.load(r#"
+    ///     setmetatable({x = 1, y = 2}, {
+    ///         __tostring = function(p) return "(" .. p.x .. ", " .. p.y .. ")" end
+    ///     })
+    /// "#).eval()?;
+    /// let items: [&dyn ToLuaString; 4] = [&"point ", &point, &" at ", &1.5];
+    /// assert_eq!(lua.concat(items)?, "point (1, 2) at 1.5");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn concat<T: ToLuaString>(&self, items: impl IntoIterator<Item = T>) -> Result<String> {
+        let mut buf = Vec::new();
+        for item in items {
+            buf.extend_from_slice(&item.to_lua_string(self)?.as_bytes());
+        }
+        self.create_string(buf)
+    }
+
     /// Attempts to coerce a Lua value into a String in a manner consistent with Lua's internal
     /// behavior.
     ///
@@ -1540,6 +3261,52 @@ impl Lua {
         })
     }
 
+    /// Packs `values` into a binary string according to the format string `fmt`.
+    ///
+    /// The format is the same as for the Lua [`string.pack`] function, so the same layout can be
+    /// shared between Lua scripts and Rust code. The engine is implemented in Rust and works the
+    /// same way in all Lua versions. Sizes of native types are the same as on 64-bit platforms
+    /// (e.g. `l`, `j` and `T` are 8 bytes).
+    ///
+    /// In Lua 5.1, 5.2 and LuaJIT, where `string.pack` is not available, the `string` library is
+    /// extended with `pack`, `unpack` and `packsize` functions using this engine.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let data = lua.string_pack(">I2s1", (513, "hi"))?;
+    /// assert_eq!(data, b"\x02\x01\x02hi");
+    /// let (n, s): (u16, String) = lua.string_unpack(">I2s1", &data)?;
+    /// assert_eq!((n, s.as_str()), (513, "hi"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`string.pack`]: https://www.lua.org/manual/5.4/manual.html#pdf-string.pack
+    pub fn string_pack(&self, fmt: &str, values: impl IntoLuaMulti) -> Result<Vec<u8>> {
+        let values = values.into_lua_multi(self)?.into_iter().collect::<Vec<_>>();
+        crate::pack::pack(self, fmt.as_bytes(), &values, 1)
+    }
+
+    /// Unpacks values from the binary string `data` according to the format string `fmt`.
+    ///
+    /// See [`Lua::string_pack`] for details.
+    pub fn string_unpack<R: FromLuaMulti>(&self, fmt: &str, data: impl AsRef<[u8]>) -> Result<R> {
+        let (values, _) = crate::pack::unpack(self, fmt.as_bytes(), data.as_ref(), 0)?;
+        R::from_lua_multi(values, self)
+    }
+
+    /// Returns the size of a binary string resulting from [`Lua::string_pack`] with the format string
+    /// `fmt`.
+    ///
+    /// The format cannot contain variable-length options `s` or `z`.
+    pub fn string_pack_size(&self, fmt: &str) -> Result<usize> {
+        crate::pack::pack_size(fmt.as_bytes())
+    }
+
     /// Converts a value that implements `IntoLua` into a `Value` instance.
     #[inline]
     pub fn pack(&self, t: impl IntoLua) -> Result<Value> {
@@ -1582,7 +3349,10 @@ impl Lua {
             check_stack(state, 5)?;
 
             lua.push(t)?;
-            rawset_field(state, ffi::LUA_REGISTRYINDEX, name)
+            let is_set = ffi::lua_isnil(state, -1) == 0;
+            rawset_field(state, ffi::LUA_REGISTRYINDEX, name)?;
+            lua.with_registry_tracking(|tracking| tracking.set_name(name, is_set));
+            Ok(())
         }
     }
 
@@ -1644,6 +3414,7 @@ impl Lua {
             if let Some(registry_id) = free_registry_id {
                 // It must be safe to replace the value without triggering memory error
                 ffi::lua_rawseti(state, ffi::LUA_REGISTRYINDEX, registry_id as Integer);
+                lua.with_registry_tracking(|tracking| tracking.add_key(registry_id));
                 return Ok(RegistryKey::new(registry_id, unref_list));
             }
 
@@ -1655,6 +3426,7 @@ impl Lua {
                     ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX)
                 })?
             };
+            lua.with_registry_tracking(|tracking| tracking.add_key(registry_id));
             Ok(RegistryKey::new(registry_id, unref_list))
         }
     }
@@ -1694,9 +3466,11 @@ impl Lua {
             return Err(Error::MismatchedRegistryKey);
         }
 
+        let registry_id = key.take();
         unsafe {
-            ffi::luaL_unref(lua.state(), ffi::LUA_REGISTRYINDEX, key.take());
+            ffi::luaL_unref(lua.state(), ffi::LUA_REGISTRYINDEX, registry_id);
         }
+        lua.with_registry_tracking(|tracking| tracking.remove_key(registry_id));
         Ok(())
     }
 
@@ -1725,6 +3499,7 @@ impl Lua {
                 (Value::Nil, registry_id) => {
                     // Remove the value
                     ffi::luaL_unref(state, ffi::LUA_REGISTRYINDEX, registry_id);
+                    lua.with_registry_tracking(|tracking| tracking.remove_key(registry_id));
                     key.set_id(ffi::LUA_REFNIL);
                 }
                 (value, ffi::LUA_REFNIL) => {
@@ -1758,18 +3533,90 @@ impl Lua {
     /// Unlike normal handle values, [`RegistryKey`]s do not automatically remove themselves on
     /// Drop, but you can call this method to remove any unreachable registry values not
     /// manually removed by [`Lua::remove_registry_value`].
-    pub fn expire_registry_values(&self) {
+    ///
+    /// Returns the number of reclaimed registry slots.
+    pub fn expire_registry_values(&self) -> usize {
         let lua = self.lock();
         let state = lua.state();
         unsafe {
             let mut unref_list = (*lua.extra.get()).registry_unref_list.lock();
             let unref_list = mem::replace(&mut *unref_list, Some(Vec::new()));
-            for id in mlua_expect!(unref_list, "unref list not set") {
+            let unref_list = mlua_expect!(unref_list, "unref list not set");
+            for &id in &unref_list {
                 ffi::luaL_unref(state, ffi::LUA_REGISTRYINDEX, id);
             }
+            lua.with_registry_tracking(|tracking| tracking.expire_keys(&unref_list));
+            unref_list.len()
         }
     }
 
+    /// Returns a handle to inspect and remove the registry entries created by mlua.
+    ///
+    /// See [`Registry`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let key = lua.create_registry_value("cached config")?;
+    /// lua.registry().set_label(&key, "config")?;
+    ///
+    /// drop(key);
+    /// let entry = &lua.registry().entries()[0];
+    /// assert_eq!(entry.label(), Some("config"));
+    /// assert!(entry.is_dropped());
+    ///
+    /// assert_eq!(lua.expire_registry_values(), 1);
+    /// assert!(lua.registry().entries().is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn registry(&self) -> Registry<'_> {
+        Registry::new(self)
+    }
+
+    /// Returns the identity of a Lua object, usable as a key of Rust collections.
+    ///
+    /// The identity does not keep the object alive. Returns `None` for values which are not
+    /// objects (tables, functions, threads and userdata), for example strings or numbers.
+    ///
+    /// See [`ValueIdentity`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use mlua::{Lua, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let mut cache = HashMap::new();
+    ///
+    /// let table = Value::Table(lua.create_table()?);
+    /// let id = lua.value_identity(&table)?.unwrap();
+    /// cache.insert(id, "computed");
+    /// assert_eq!(lua.value_identity(&table)?, Some(id));
+    ///
+    /// drop(table);
+    /// lua.gc_collect()?;
+    /// cache.retain(|id, _| matches!(lua.resolve_identity(id), Ok(Some(_))));
+    /// assert!(cache.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn value_identity(&self, value: &Value) -> Result<Option<ValueIdentity>> {
+        identity::value_identity(self, value)
+    }
+
+    /// Returns the object with the given identity, or `None` if it has been garbage collected.
+    ///
+    /// Objects are detected as collected after a garbage collection cycle, until then an
+    /// unreachable object can still be resolved.
+    pub fn resolve_identity(&self, id: &ValueIdentity) -> Result<Option<Value>> {
+        identity::resolve_identity(self, id)
+    }
+
     /// Sets or replaces an application data object of type `T`.
     ///
     /// Application data could be accessed at any time by using [`Lua::app_data_ref`] or
@@ -1861,6 +3708,207 @@ impl Lua {
         extra.app_data.remove()
     }
 
+    /// Sets a virtual filesystem used to load Lua files.
+    ///
+    /// Once set, the `dofile` and `loadfile` functions and loading Lua modules with `require`
+    /// (using `package.path`) read files from the given [`Vfs`] instead of the real filesystem.
+    /// Binary modules (`package.cpath`) are not affected.
+    ///
+    /// The standard library functions should be loaded before calling this method.
+    pub fn set_vfs(&self, vfs: impl Vfs) -> Result<()> {
+        crate::vfs::install(self)?;
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).vfs = Some(XRc::new(vfs)) };
+        Ok(())
+    }
+
+    /// Removes a virtual filesystem previously set by [`Lua::set_vfs`].
+    ///
+    /// Lua files are loaded from the real filesystem again.
+    pub fn remove_vfs(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).vfs = None };
+    }
+
+    /// Sets a time driver used for async deadlines and sleeps.
+    ///
+    /// mlua is runtime agnostic, so the driver must be provided to use methods such as
+    /// [`Lua::sleep`] or [`Function::call_async_with_deadline`]. See [`TimeDriver`] for the list
+    /// of built-in drivers.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`Function::call_async_with_deadline`]: crate::Function::call_async_with_deadline
+    /// [`TimeDriver`]: crate::TimeDriver
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn set_time_driver(&self, driver: impl TimeDriver) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).time_driver = Some(Box::new(driver)) };
+    }
+
+    /// Removes a time driver previously set by [`Lua::set_time_driver`].
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn remove_time_driver(&self) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).time_driver = None };
+    }
+
+    /// Returns a future that completes after `duration` elapsed according to the time driver.
+    ///
+    /// The returned future resolves to an error if no time driver is set.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// # use mlua::{Lua, ManualClock, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let clock = ManualClock::new();
+    /// lua.set_time_driver(clock.clone());
+    ///
+    /// let sleep = lua.create_async_function(|lua, secs: f64| async move {
+    ///     lua.sleep(Duration::from_secs_f64(secs)).await
+    /// })?;
+    /// let thread = lua.create_thread(sleep)?;
+    /// thread.resume::<()>(1.5)?;
+    ///
+    /// clock.advance(Duration::from_secs(2));
+    /// thread.resume::<()>(())?;
+    /// assert_eq!(thread.status(), mlua::ThreadStatus::Finished);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn sleep(&self, duration: Duration) -> impl Future<Output = Result<()>> {
+        let sleep = (self.lock().time_driver()).map(|driver| driver.sleep_until(driver.now() + duration));
+        async move {
+            sleep?.await;
+            Ok(())
+        }
+    }
+
+    /// Registers a future to be advanced by [`Lua::poll_pending_tasks`].
+    ///
+    /// This allows applications without an async executor (e.g. game loops) to drive async Lua
+    /// code. The future is dropped when it completes or the Lua state is closed.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn spawn_task(&self, future: impl Future<Output = Result<()>> + MaybeSend + 'static) {
+        let lua = self.lock();
+        unsafe { (*lua.extra.get()).pending_tasks.push(Task::new(Box::pin(future))) };
+    }
+
+    /// Registers a [`Thread`] to be resumed by [`Lua::poll_pending_tasks`] until it finishes.
+    ///
+    /// The thread is started with the given arguments and runs as an [`AsyncThread`], so it can
+    /// call async functions. Values yielded or returned by the thread are discarded.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`AsyncThread`]: crate::AsyncThread
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn spawn_thread(&self, thread: Thread, args: impl IntoLuaMulti) -> Result<()> {
+        let args = args.into_lua_multi(self)?;
+        let thread = thread.into_async::<()>(args);
+        self.spawn_task(thread);
+        Ok(())
+    }
+
+    /// Advances tasks registered by [`Lua::spawn_task`] and [`Lua::spawn_thread`] for at most
+    /// `budget` of wall-clock time.
+    ///
+    /// Tasks are polled in the order of registration, only when they were woken up since the last
+    /// poll (newly registered tasks are always polled). Polling stops when no task is woken up
+    /// anymore or the budget is exhausted, but at least one woken task is polled. A task is never
+    /// interrupted, so a single poll can exceed the budget. Tasks registered during polling are
+    /// polled by the next call.
+    ///
+    /// Returns the number of tasks that are still pending. If a task fails, polling stops and the
+    /// error is returned. The failed task is removed, all other tasks remain registered.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// # use mlua::{Lua, ManualClock, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let clock = ManualClock::new();
+    /// lua.set_time_driver(clock.clone());
+    ///
+    /// let sleep = lua.create_async_function(|lua, secs: f64| async move {
+    ///     lua.sleep(Duration::from_secs_f64(secs)).await
+    /// })?;
+    /// let thread = lua.create_thread(sleep)?;
+    /// lua.spawn_thread(thread, 1.0)?;
+    ///
+    /// // Each frame
+    /// assert_eq!(lua.poll_pending_tasks(Duration::from_millis(2))?, 1);
+    /// clock.advance(Duration::from_secs(1));
+    /// assert_eq!(lua.poll_pending_tasks(Duration::from_millis(2))?, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn poll_pending_tasks(&self, budget: Duration) -> Result<usize> {
+        let deadline = Instant::now() + budget;
+        // Tasks are taken out for the duration of polling in case of reentrance
+        let mut tasks = {
+            let lua = self.lock();
+            unsafe { mem::take(&mut (*lua.extra.get()).pending_tasks) }
+        };
+        let res = crate::scheduler::poll_tasks(&mut tasks, deadline);
+
+        let lua = self.lock();
+        let pending_tasks = unsafe { &mut (*lua.extra.get()).pending_tasks };
+        tasks.append(pending_tasks);
+        *pending_tasks = tasks;
+        res.map(|_| pending_tasks.len())
+    }
+
+    /// Closes the Lua state and completes the pending cleanup futures of async destructors.
+    ///
+    /// Closing runs the `__gc` metamethods of all remaining userdata, and the cleanup futures
+    /// scheduled by their async destructors are then awaited by the returned future on the executor
+    /// of the caller. Errors returned by these futures are ignored.
+    ///
+    /// The state is closed only if this is the last [`Lua`] instance referring to it. Otherwise the
+    /// returned future completes immediately and the state is closed when the last instance is
+    /// dropped.
+    ///
+    /// Dropping [`Lua`] without calling this method never blocks, and drops the pending cleanup
+    /// futures without running them.
+    ///
+    /// Requires `feature = "async"`
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "async", not(feature = "luau")))))]
+    pub fn close(self) -> impl Future<Output = ()> {
+        let closed_tasks = XRc::new(parking_lot::Mutex::new(Vec::new()));
+        {
+            let lua = self.lock();
+            unsafe { (*lua.extra.get()).closed_tasks = Some(closed_tasks.clone()) };
+        }
+        drop(self);
+        async move {
+            let tasks = mem::take(&mut *closed_tasks.lock());
+            crate::scheduler::complete_tasks(tasks).await
+        }
+    }
+
     /// Returns an internal `Poll::Pending` constant used for executing async callbacks.
     #[cfg(feature = "async")]
     #[doc(hidden)]
@@ -1898,6 +3946,143 @@ impl Lua {
         Ok(())
     }
 
+    // Prevents Lua code from loading binary chunks
+    #[cfg(not(feature = "luau"))]
+    fn disable_binary_chunks(&self) -> Result<()> {
+        #[cfg(not(feature = "lua51"))]
+        self.load(
+            r#"
+            local load, loadfile, loadstring, error = load, loadfile, loadstring, error
+            _G.load = function(chunk, chunkname, mode, ...)
+                return load(chunk, chunkname, "t", ...)
+            end
+            _G.loadfile = function(filename, mode, ...)
+                return loadfile(filename, "t", ...)
+            end
+            _G.dofile = function(filename)
+                local func, err = loadfile(filename, "t")
+                if not func then error(err, 0) end
+                return func()
+            end
+            if loadstring then
+                _G.loadstring = function(s, chunkname)
+                    return load(s, chunkname, "t")
+                end
+            end
+        "#,
+        )
+        .set_name("=__mlua_disable_binary_chunks")
+        .exec()?;
+
+        #[cfg(feature = "lua51")]
+        {
+            let is_binary = self.create_function(|_, s: Value| {
+                Ok(matches!(s, Value::String(s) if s.as_bytes().first() == Some(&ffi::LUA_SIGNATURE[0])))
+            })?;
+            let read_source = self.create_function(|lua, filename: Option<StdString>| {
+                let source = match &filename {
+                    Some(filename) => std::fs::read(filename),
+                    None => std::io::Read::bytes(std::io::stdin()).collect(),
+                };
+                let name = filename.as_deref().unwrap_or("stdin");
+                let mut source = match source {
+                    Ok(source) => source,
+                    Err(err) => return (Nil, format!("cannot open {name}: {err}")).into_lua_multi(lua),
+                };
+                if source.first() == Some(&ffi::LUA_SIGNATURE[0]) {
+                    return (Nil, "attempt to load a binary chunk").into_lua_multi(lua);
+                }
+                // Skip the first line if it starts with `#` (keeping line numbers)
+                if source.first() == Some(&b'#') {
+                    let eol = source.iter().position(|&b| b == b'\n').unwrap_or(source.len());
+                    source.drain(..eol);
+                }
+                lua.create_string(source)?.into_lua_multi(lua)
+            })?;
+            self.load(
+                r#"
+                local is_binary, read_source = ...
+                local load, loadstring, error, type = load, loadstring, error, type
+                local BINARY_ERROR = "attempt to load a binary chunk"
+                _G.loadstring = function(s, chunkname)
+                    if is_binary(s) then return nil, BINARY_ERROR end
+                    return loadstring(s, chunkname)
+                end
+                _G.load = function(reader, chunkname)
+                    if type(reader) ~= "function" then return load(reader, chunkname) end
+                    local first, pending = reader(), true
+                    if is_binary(first) then return nil, BINARY_ERROR end
+                    return load(function()
+                        if pending then
+                            pending = false
+                            return first
+                        end
+                        return reader()
+                    end, chunkname)
+                end
+                _G.loadfile = function(filename)
+                    local source, err = read_source(filename)
+                    if not source then return nil, err end
+                    return loadstring(source, filename and "@" .. filename or "=stdin")
+                end
+                _G.dofile = function(filename)
+                    local func, err = _G.loadfile(filename)
+                    if not func then error(err, 0) end
+                    return func()
+                end
+            "#,
+            )
+            .set_name("=__mlua_disable_binary_chunks")
+            .call::<()>((is_binary, read_source))?;
+        }
+
+        if self.globals().raw_get::<Option<Table>>("package")?.is_some() {
+            self.disable_binary_modules()?;
+        }
+        Ok(())
+    }
+
+    // Replaces the Lua files searcher with one that does not accept binary chunks
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn disable_binary_modules(&self) -> Result<()> {
+        let globals = self.globals();
+        let package: Table = globals.get("package")?;
+
+        #[cfg(not(feature = "lua51"))]
+        let searchpath: Function = package.get("searchpath")?;
+        #[cfg(feature = "lua51")]
+        let searchpath = self.create_function(|lua, (name, path): (StdString, StdString)| {
+            let name = name.replace('.', std::path::MAIN_SEPARATOR_STR);
+            let mut tried = StdString::new();
+            for template in path.split(';').filter(|t| !t.is_empty()) {
+                let filename = template.replace('?', &name);
+                if std::fs::File::open(&filename).is_ok() {
+                    return filename.into_lua_multi(lua);
+                }
+                tried.push_str(&format!("\n\tno file '{filename}'"));
+            }
+            (Nil, tried).into_lua_multi(lua)
+        })?;
+
+        self.load(
+            r#"
+            local package, searchpath, loadfile, error = ...
+            local searchers = package.searchers or package.loaders
+            searchers[2] = function(name)
+                local filename, err = searchpath(name, package.path)
+                if not filename then return err end
+                local func, err = loadfile(filename)
+                if not func then
+                    error("error loading module '" .. name .. "' from file '" .. filename .. "':\n\t" .. err, 2)
+                end
+                return func, filename
+            end
+        "#,
+        )
+        .set_name("=__mlua_disable_binary_modules")
+        .call((package, searchpath, globals.get::<Function>("loadfile")?, globals.get::<Function>("error")?))
+    }
+
     #[inline(always)]
     pub(crate) fn lock(&self) -> ReentrantMutexGuard<RawLua> {
         self.raw.lock()
@@ -1923,6 +4108,41 @@ impl Lua {
     }
 }
 
+// Luau interrupt handler calling the interrupt callback and performing automatic yields
+#[cfg(feature = "luau")]
+unsafe extern "C-unwind" fn interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
+    if gc >= 0 {
+        // We don't support GC interrupts since they cannot survive Lua exceptions
+        return;
+    }
+    let (result, auto_yield) = callback_error_ext(state, ptr::null_mut(), move |extra, _| {
+        let mut result = VmState::Continue;
+        if let Some(interrupt_cb) = (*extra).interrupt_callback.clone() {
+            if std::rc::Rc::strong_count(&interrupt_cb) > 2 {
+                return Ok((VmState::Continue, false)); // Don't allow recursion
+            }
+            let _guard = StateGuard::new((*extra).raw_lua(), state);
+            result = interrupt_cb((*extra).lua())?;
+        }
+
+        // Only threads resumed from Rust are preempted, Lua coroutines are driven by Lua code
+        let mut auto_yield = false;
+        let interval = (*extra).auto_yield_interval;
+        let counter = (*extra).auto_yield_threads.get_mut(&state);
+        if let Some(counter) = counter.filter(|_| interval > 0) {
+            *counter += 1;
+            if *counter >= interval && ffi::lua_isyieldable(state) != 0 {
+                *counter = 0;
+                auto_yield = true;
+            }
+        }
+        Ok((result, auto_yield))
+    });
+    if matches!(result, VmState::Yield) || auto_yield {
+        ffi::lua_yield(state, 0);
+    }
+}
+
 impl WeakLua {
     #[track_caller]
     #[inline(always)]
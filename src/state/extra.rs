@@ -1,6 +1,6 @@
 use std::any::TypeId;
 use std::cell::UnsafeCell;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
 use std::os::raw::{c_int, c_void};
 use std::ptr;
 use std::rc::Rc;
@@ -11,8 +11,8 @@ use rustc_hash::FxHashMap;
 
 use crate::error::Result;
 use crate::state::RawLua;
-use crate::stdlib::StdLib;
-use crate::types::{AppData, ReentrantMutex, XRc};
+use crate::stdlib::{StdLib, UnsafeCapabilities};
+use crate::types::{AppData, CallbackArena, ReentrantMutex, XRc};
 use crate::util::{get_internal_metatable, push_internal_userdata, TypeKey, WrappedFailure};
 
 #[cfg(any(feature = "luau", doc))]
@@ -21,7 +21,7 @@ use crate::chunk::Compiler;
 #[cfg(feature = "async")]
 use {futures_util::task::noop_waker_ref, std::ptr::NonNull, std::task::Waker};
 
-use super::{Lua, WeakLua};
+use super::{DurationFormat, IntegerOverflow, Lua, WeakLua};
 
 // Unique key to store `ExtraData` in the registry
 static EXTRA_REGISTRY_KEY: u8 = 0;
@@ -41,12 +41,36 @@ pub(crate) struct ExtraData {
 
     // When Lua instance dropped, setting `None` would prevent collecting `RegistryKey`s
     pub(super) registry_unref_list: Arc<Mutex<Option<Vec<c_int>>>>,
+    // Registry entries created by mlua (see `Lua::registry`)
+    pub(super) registry_tracking: crate::registry::RegistryTracking,
+    // Serialization hooks of userdata types
+    #[cfg(feature = "serialize")]
+    pub(super) userdata_hooks: crate::serde::userdata::UserDataHooks,
 
     // Container to store arbitrary data (extensions)
     pub(super) app_data: AppData,
 
-    pub(super) safe: bool,
+    // Unsafe capabilities enabled for Lua code (all of them in states not created as safe)
+    pub(super) unsafe_caps: UnsafeCapabilities,
     pub(super) libs: StdLib,
+    // Iterate tables in a deterministic (sorted) order
+    pub(super) deterministic_pairs: bool,
+    // Hide addresses of Lua values in error messages
+    pub(super) hide_addresses: bool,
+    // Conversion of out of range Rust integers
+    pub(super) integer_overflow: IntegerOverflow,
+    // Representation of `Duration` values
+    pub(super) duration_format: DurationFormat,
+    // Formatting of numbers in `tostring` and `print`
+    pub(super) number_format: Option<crate::number_format::NumberFormat>,
+    // Source of random numbers for `math.random`
+    pub(super) random_source: Option<Box<dyn crate::random::RandomSource>>,
+    // Policy applied to `collectgarbage` calls from Lua
+    pub(super) collectgarbage_policy: Option<crate::types::GCPolicyCallback>,
+    // Token observed by async callbacks
+    pub(super) cancellation_token: Option<crate::cancel::CancellationToken>,
+    // Rust backtraces of callback errors (if capturing is enabled, see `Lua::install_error_objects`)
+    pub(super) error_backtraces: Option<crate::error_object::ErrorBacktraces>,
     // Used in module mode
     pub(super) skip_memory_check: bool,
 
@@ -55,9 +79,19 @@ pub(crate) struct ExtraData {
     pub(super) ref_stack_size: c_int,
     pub(super) ref_stack_top: c_int,
     pub(super) ref_free: Vec<c_int>,
+    // Blocks of reserved slots (see `Lua::value_scope`)
+    pub(super) ref_blocks: Vec<super::util::RefBlock>,
+    // Creation sites of live references
+    #[cfg(feature = "ref-audit")]
+    pub(super) ref_origins: FxHashMap<c_int, super::RefOrigin>,
 
     // Pool of `WrappedFailure` enums in the ref thread (as userdata)
     pub(super) wrapped_failure_pool: Vec<c_int>,
+    // Storage for Rust callbacks
+    pub(crate) callbacks: CallbackArena,
+    // Counters exposed by `Lua::metrics_snapshot`
+    #[cfg(feature = "metrics")]
+    pub(super) metrics: crate::metrics::Metrics,
     // Pool of `Thread`s (coroutines) for async execution
     #[cfg(feature = "async")]
     pub(super) thread_pool: Vec<c_int>,
@@ -68,6 +102,20 @@ pub(crate) struct ExtraData {
     // Waker for polling futures
     #[cfg(feature = "async")]
     pub(super) waker: NonNull<Waker>,
+    // Time source for async deadlines and sleeps
+    #[cfg(feature = "async")]
+    pub(super) time_driver: Option<Box<dyn crate::time::TimeDriver>>,
+    // Futures advanced by `Lua::poll_pending_tasks`
+    #[cfg(feature = "async")]
+    pub(super) pending_tasks: Vec<crate::scheduler::Task>,
+    // Async destructors of userdata types, called by the `__gc` metamethod
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    pub(super) async_destructors: FxHashMap<TypeId, crate::scheduler::AsyncDestructor>,
+    // Receives cleanup tasks still pending when the state is closed by `Lua::close`
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    pub(super) closed_tasks: Option<XRc<Mutex<Vec<crate::scheduler::Task>>>>,
+    // Virtual filesystem used to load Lua files
+    pub(super) vfs: Option<XRc<dyn crate::vfs::Vfs>>,
 
     #[cfg(not(feature = "luau"))]
     pub(super) hook_callback: Option<crate::types::HookCallback>,
@@ -75,8 +123,20 @@ pub(crate) struct ExtraData {
     pub(super) hook_thread: *mut ffi::lua_State,
     #[cfg(feature = "lua54")]
     pub(super) warn_callback: Option<crate::types::WarnCallback>,
+    // Callback to report argument conversion errors in Rust callbacks
+    pub(super) conversion_error_callback: Option<crate::types::ConversionErrorCallback>,
+    // Middleware wrapping calls of Rust functions
+    pub(super) callback_middleware: Option<crate::types::CallbackMiddleware>,
+    // Abort the process instead of propagating panics through Lua frames
+    pub(super) abort_on_panic: bool,
     #[cfg(feature = "luau")]
     pub(super) interrupt_callback: Option<crate::types::InterruptCallback>,
+    // Number of interrupt checks between automatic yields (0 if disabled)
+    #[cfg(feature = "luau")]
+    pub(super) auto_yield_interval: u32,
+    // Threads currently resumed from Rust with their interrupt check counts
+    #[cfg(feature = "luau")]
+    pub(super) auto_yield_threads: FxHashMap<*mut ffi::lua_State, u32>,
 
     #[cfg(feature = "luau")]
     pub(super) sandboxed: bool,
@@ -113,17 +173,14 @@ impl ExtraData {
     #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
     pub(super) const ERROR_TRACEBACK_IDX: c_int = 1;
 
-    pub(super) unsafe fn init(state: *mut ffi::lua_State, owned: bool) -> XRc<UnsafeCell<Self>> {
+    pub(super) unsafe fn init(state: *mut ffi::lua_State, owned: bool) -> Result<XRc<UnsafeCell<Self>>> {
         // Create ref stack thread and place it in the registry to prevent it
         // from being garbage collected.
-        let ref_thread = mlua_expect!(
-            protect_lua!(state, 0, 0, |state| {
-                let thread = ffi::lua_newthread(state);
-                ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX);
-                thread
-            }),
-            "Error while creating ref thread",
-        );
+        let ref_thread = protect_lua!(state, 0, 0, |state| {
+            let thread = ffi::lua_newthread(state);
+            ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX);
+            thread
+        })?;
 
         let wrapped_failure_mt_ptr = {
             get_internal_metatable::<WrappedFailure>(state);
@@ -148,30 +205,64 @@ impl ExtraData {
             registered_userdata_mt: FxHashMap::default(),
             last_checked_userdata_mt: (ptr::null(), None),
             registry_unref_list: Arc::new(Mutex::new(Some(Vec::new()))),
+            registry_tracking: Default::default(),
+            #[cfg(feature = "serialize")]
+            userdata_hooks: Default::default(),
             app_data: AppData::default(),
-            safe: false,
+            unsafe_caps: UnsafeCapabilities::ALL,
             libs: StdLib::NONE,
+            deterministic_pairs: false,
+            hide_addresses: false,
+            integer_overflow: IntegerOverflow::AsFloat,
+            duration_format: DurationFormat::Seconds,
+            number_format: None,
+            random_source: None,
+            collectgarbage_policy: None,
+            cancellation_token: None,
+            error_backtraces: None,
             skip_memory_check: false,
             ref_thread,
             // We need some reserved stack space to move values in and out of the ref stack.
             ref_stack_size: ffi::LUA_MINSTACK - REF_STACK_RESERVE,
             ref_stack_top: ffi::lua_gettop(ref_thread),
             ref_free: Vec::new(),
+            ref_blocks: Vec::new(),
+            #[cfg(feature = "ref-audit")]
+            ref_origins: FxHashMap::default(),
             wrapped_failure_pool: Vec::with_capacity(WRAPPED_FAILURE_POOL_SIZE),
+            callbacks: CallbackArena::default(),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
             #[cfg(feature = "async")]
             thread_pool: Vec::new(),
             wrapped_failure_mt_ptr,
             #[cfg(feature = "async")]
             waker: NonNull::from(noop_waker_ref()),
+            #[cfg(feature = "async")]
+            time_driver: None,
+            #[cfg(feature = "async")]
+            pending_tasks: Vec::new(),
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            async_destructors: FxHashMap::default(),
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            closed_tasks: None,
+            vfs: None,
             #[cfg(not(feature = "luau"))]
             hook_callback: None,
             #[cfg(not(feature = "luau"))]
             hook_thread: ptr::null_mut(),
             #[cfg(feature = "lua54")]
             warn_callback: None,
+            conversion_error_callback: None,
+            callback_middleware: None,
+            abort_on_panic: false,
             #[cfg(feature = "luau")]
             interrupt_callback: None,
             #[cfg(feature = "luau")]
+            auto_yield_interval: 0,
+            #[cfg(feature = "luau")]
+            auto_yield_threads: FxHashMap::default(),
+            #[cfg(feature = "luau")]
             sandboxed: false,
             #[cfg(feature = "luau")]
             compiler: None,
@@ -180,9 +271,14 @@ impl ExtraData {
         }));
 
         // Store it in the registry
-        mlua_expect!(Self::store(&extra, state), "Error while storing extra data");
+        if let Err(err) = Self::store(&extra, state) {
+            // The userdata holding a copy of `extra` could be already created and it would be
+            // collected later. Leak a reference to never drop the partially initialized data.
+            mem::forget(XRc::clone(&extra));
+            return Err(err);
+        }
 
-        extra
+        Ok(extra)
     }
 
     pub(super) unsafe fn set_lua(&mut self, raw: &XRc<ReentrantMutex<RawLua>>) {
@@ -229,6 +325,18 @@ impl ExtraData {
         })
     }
 
+    // Checks if addresses of Lua values should be hidden in error messages
+    pub(crate) unsafe fn hide_addresses(state: *mut ffi::lua_State) -> bool {
+        let extra = Self::get(state);
+        !extra.is_null() && (*extra).hide_addresses
+    }
+
+    // Checks if the process should be aborted when a Rust function called by Lua panics
+    pub(crate) unsafe fn abort_on_panic(state: *mut ffi::lua_State) -> bool {
+        let extra = Self::get(state);
+        !extra.is_null() && (*extra).abort_on_panic
+    }
+
     #[inline(always)]
     pub(super) unsafe fn lua(&self) -> &Lua {
         self.lua.assume_init_ref()
@@ -10,9 +10,10 @@ use std::{mem, ptr};
 use crate::chunk::ChunkMode;
 use crate::error::{Error, Result};
 use crate::function::Function;
-use crate::memory::{MemoryState, ALLOCATOR};
-use crate::state::util::{callback_error_ext, ref_stack_pop, StateGuard};
-use crate::stdlib::StdLib;
+use crate::memory::{MemorySourceGuard, MemoryState, ALLOCATOR};
+use crate::middleware::call_with_middleware;
+use crate::state::util::{callback_error_ext, ref_stack_free, ref_stack_pop, StateGuard};
+use crate::stdlib::{StdLib, UnsafeCapabilities};
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
@@ -30,11 +31,14 @@ use crate::util::{
 use crate::value::{IntoLua, Nil, Value};
 
 use super::extra::ExtraData;
-use super::{Lua, LuaOptions, WeakLua};
+use super::{DurationFormat, IntegerOverflow, Lua, LuaOptions, WeakLua};
 
 #[cfg(not(feature = "luau"))]
 use crate::hook::{Debug, HookTriggers};
 
+#[cfg(feature = "ref-audit")]
+use {super::RefOrigin, rustc_hash::FxHashMap, std::backtrace::Backtrace};
+
 #[cfg(feature = "async")]
 use {
     crate::types::{AsyncCallback, AsyncCallbackUpvalue, AsyncPollUpvalue},
@@ -59,10 +63,30 @@ impl Drop for RawLua {
                 return;
             }
 
+            #[cfg(feature = "ref-audit")]
+            report_live_refs(&(*self.extra.get()).ref_origins);
+
+            // Pending tasks may refer to Lua values, drop them while the state is still alive.
+            // Cleanup tasks of async destructors own their data and can outlive the state.
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            let (cleanup_tasks, _): (Vec<_>, Vec<_>) = mem::take(&mut (*self.extra.get()).pending_tasks)
+                .into_iter()
+                .partition(|task| task.is_cleanup());
+            #[cfg(all(feature = "async", feature = "luau"))]
+            drop(mem::take(&mut (*self.extra.get()).pending_tasks));
+
             let mem_state = MemoryState::get(self.main_state);
 
             ffi::lua_close(self.main_state);
 
+            // Cleanup tasks are handed over to `Lua::close` (if called), otherwise they are dropped
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            if let Some(closed_tasks) = (*self.extra.get()).closed_tasks.take() {
+                let mut closed_tasks = closed_tasks.lock();
+                closed_tasks.extend(cleanup_tasks);
+                closed_tasks.extend(mem::take(&mut (*self.extra.get()).pending_tasks));
+            }
+
             // Deallocate `MemoryState`
             if !mem_state.is_null() {
                 drop(Box::from_raw(mem_state));
@@ -71,6 +95,21 @@ impl Drop for RawLua {
     }
 }
 
+// Prints creation sites of references that are still alive when Lua is dropped
+#[cfg(feature = "ref-audit")]
+fn report_live_refs(origins: &FxHashMap<c_int, RefOrigin>) {
+    if origins.is_empty() {
+        return;
+    }
+    eprintln!(
+        "mlua: {} reference(s) are still alive when dropping Lua:",
+        origins.len()
+    );
+    for origin in origins.values() {
+        eprintln!("{origin}");
+    }
+}
+
 #[cfg(feature = "send")]
 unsafe impl Send for RawLua {}
 
@@ -104,18 +143,39 @@ impl RawLua {
         unsafe { (*self.extra.get()).ref_thread }
     }
 
-    pub(super) unsafe fn new(libs: StdLib, options: LuaOptions) -> XRc<ReentrantMutex<Self>> {
+    pub(super) unsafe fn new(libs: StdLib, options: LuaOptions) -> Result<XRc<ReentrantMutex<Self>>> {
         let mem_state: *mut MemoryState = Box::into_raw(Box::default());
+        if options.track_memory_by_source {
+            (*mem_state).enable_source_tracking();
+        }
         let mut state = ffi::lua_newstate(ALLOCATOR, mem_state as *mut c_void);
         // If state is null then switch to Lua internal allocator
         if state.is_null() {
             drop(Box::from_raw(mem_state));
             state = ffi::luaL_newstate();
         }
-        assert!(!state.is_null(), "Failed to create a Lua VM");
+        if state.is_null() {
+            return Err(Error::MemoryError("failed to create a Lua VM".to_string()));
+        }
 
-        ffi::luaL_requiref(state, cstr!("_G"), ffi::luaopen_base, 1);
-        ffi::lua_pop(state, 1);
+        let rawlua = match Self::try_init_from_ptr(state, true) {
+            Ok(rawlua) => rawlua,
+            Err(err) => {
+                // `RawLua` is not created yet, so we need to close the state manually
+                let mem_state = MemoryState::get(state);
+                ffi::lua_close(state);
+                if !mem_state.is_null() {
+                    drop(Box::from_raw(mem_state));
+                }
+                return Err(err);
+            }
+        };
+        let extra = rawlua.lock().extra.get();
+
+        protect_lua!(state, 0, 0, |state| {
+            ffi::luaL_requiref(state, cstr!("_G"), ffi::luaopen_base, 1);
+            ffi::lua_pop(state, 1);
+        })?;
 
         // Init Luau code generator (jit)
         #[cfg(feature = "luau-jit")]
@@ -123,35 +183,24 @@ impl RawLua {
             ffi::luau_codegen_create(state);
         }
 
-        let rawlua = Self::init_from_ptr(state, true);
-        let extra = rawlua.lock().extra.get();
-
-        mlua_expect!(
-            load_from_std_lib(state, libs),
-            "Error during loading standard libraries"
-        );
+        load_from_std_lib(state, libs)?;
         (*extra).libs |= libs;
 
-        if !options.catch_rust_panics {
-            mlua_expect!(
-                (|| -> Result<()> {
-                    let _sg = StackGuard::new(state);
+        (*extra).abort_on_panic = options.abort_on_panic;
 
-                    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
-                    ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, ffi::LUA_RIDX_GLOBALS);
-                    #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
-                    ffi::lua_pushvalue(state, ffi::LUA_GLOBALSINDEX);
+        if !options.catch_rust_panics {
+            let _sg = StackGuard::new(state);
 
-                    ffi::lua_pushcfunction(state, safe_pcall);
-                    rawset_field(state, -2, "pcall")?;
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, ffi::LUA_RIDX_GLOBALS);
+            #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+            ffi::lua_pushvalue(state, ffi::LUA_GLOBALSINDEX);
 
-                    ffi::lua_pushcfunction(state, safe_xpcall);
-                    rawset_field(state, -2, "xpcall")?;
+            ffi::lua_pushcfunction(state, safe_pcall);
+            rawset_field(state, -2, "pcall")?;
 
-                    Ok(())
-                })(),
-                "Error during applying option `catch_rust_panics`"
-            )
+            ffi::lua_pushcfunction(state, safe_xpcall);
+            rawset_field(state, -2, "xpcall")?;
         }
 
         #[cfg(feature = "async")]
@@ -159,47 +208,55 @@ impl RawLua {
             (*extra).thread_pool.reserve_exact(options.thread_pool_size);
         }
 
-        rawlua
+        Ok(rawlua)
     }
 
     pub(super) unsafe fn init_from_ptr(state: *mut ffi::lua_State, owned: bool) -> XRc<ReentrantMutex<Self>> {
         assert!(!state.is_null(), "Lua state is NULL");
+        mlua_expect!(
+            Self::try_init_from_ptr(state, owned),
+            "Error during Lua initialization"
+        )
+    }
+
+    unsafe fn try_init_from_ptr(
+        state: *mut ffi::lua_State,
+        owned: bool,
+    ) -> Result<XRc<ReentrantMutex<Self>>> {
         if let Some(lua) = Self::try_from_ptr(state) {
-            return lua;
+            return Ok(lua);
         }
 
         let main_state = get_main_state(state).unwrap_or(state);
         let main_state_top = ffi::lua_gettop(main_state);
 
-        mlua_expect!(
-            (|state| {
-                init_error_registry(state)?;
+        init_error_registry(main_state)?;
 
-                // Create the internal metatables and store them in the registry
-                // to prevent from being garbage collected.
+        // Create the internal metatables and store them in the registry
+        // to prevent from being garbage collected.
 
-                init_internal_metatable::<XRc<UnsafeCell<ExtraData>>>(state, None)?;
-                init_internal_metatable::<Callback>(state, None)?;
-                init_internal_metatable::<CallbackUpvalue>(state, None)?;
-                #[cfg(feature = "async")]
-                {
-                    init_internal_metatable::<AsyncCallback>(state, None)?;
-                    init_internal_metatable::<AsyncCallbackUpvalue>(state, None)?;
-                    init_internal_metatable::<AsyncPollUpvalue>(state, None)?;
-                    init_internal_metatable::<Option<Waker>>(state, None)?;
-                }
-
-                // Init serde metatables
-                #[cfg(feature = "serialize")]
-                crate::serde::init_metatables(state)?;
+        init_internal_metatable::<XRc<UnsafeCell<ExtraData>>>(main_state, None)?;
+        init_internal_metatable::<Callback>(main_state, None)?;
+        init_internal_metatable::<CallbackUpvalue>(main_state, None)?;
+        #[cfg(feature = "async")]
+        {
+            init_internal_metatable::<AsyncCallback>(main_state, None)?;
+            init_internal_metatable::<AsyncCallbackUpvalue>(main_state, None)?;
+            init_internal_metatable::<AsyncPollUpvalue>(main_state, None)?;
+            init_internal_metatable::<Option<Waker>>(main_state, None)?;
+        }
 
-                Ok::<_, Error>(())
-            })(main_state),
-            "Error during Lua initialization",
-        );
+        // Init serde metatables
+        #[cfg(feature = "serialize")]
+        crate::serde::init_metatables(main_state)?;
 
         // Init ExtraData
-        let extra = ExtraData::init(main_state, owned);
+        let extra = ExtraData::init(main_state, owned)?;
+
+        #[cfg(all(feature = "metrics", not(feature = "luau")))]
+        {
+            (*extra.get()).metrics.gc_cycles = Some(crate::metrics::init_gc_counter(main_state)?);
+        }
 
         // Register `DestructedUserdata` type
         get_destructed_userdata_metatable(main_state);
@@ -224,7 +281,7 @@ impl RawLua {
         }));
         (*extra.get()).set_lua(&rawlua);
 
-        rawlua
+        Ok(rawlua)
     }
 
     unsafe fn try_from_ptr(state: *mut ffi::lua_State) -> Option<XRc<ReentrantMutex<Self>>> {
@@ -234,10 +291,16 @@ impl RawLua {
         }
     }
 
-    /// Marks the Lua state as safe.
+    /// Returns the set of unsafe capabilities enabled in the Lua state.
+    #[inline(always)]
+    pub(crate) fn unsafe_capabilities(&self) -> UnsafeCapabilities {
+        unsafe { (*self.extra.get()).unsafe_caps }
+    }
+
+    /// Restricts the Lua state to the given set of unsafe capabilities.
     #[inline(always)]
-    pub(super) unsafe fn set_safe(&self) {
-        (*self.extra.get()).safe = true;
+    pub(super) unsafe fn set_unsafe_capabilities(&self, caps: UnsafeCapabilities) {
+        (*self.extra.get()).unsafe_caps = caps;
     }
 
     /// Loads the specified subset of the standard libraries into an existing Lua state.
@@ -246,33 +309,130 @@ impl RawLua {
     ///
     /// [`StdLib`]: crate::StdLib
     pub(super) unsafe fn load_std_libs(&self, libs: StdLib) -> Result<()> {
-        let is_safe = (*self.extra.get()).safe;
-
-        #[cfg(not(feature = "luau"))]
-        if is_safe && libs.contains(StdLib::DEBUG) {
-            return Err(Error::SafetyError(
-                "the unsafe `debug` module can't be loaded in safe mode".to_string(),
-            ));
-        }
-        #[cfg(feature = "luajit")]
-        if is_safe && libs.contains(StdLib::FFI) {
-            return Err(Error::SafetyError(
-                "the unsafe `ffi` module can't be loaded in safe mode".to_string(),
-            ));
-        }
+        let caps = self.unsafe_capabilities();
+        check_unsafe_libs(libs, caps)?;
 
         let res = load_from_std_lib(self.main_state, libs);
 
-        // If `package` library loaded into a safe lua state then disable C modules
+        // If `package` library loaded into a restricted lua state then disable unsafe loaders
         let curr_libs = (*self.extra.get()).libs;
-        if is_safe && (curr_libs ^ (curr_libs | libs)).contains(StdLib::PACKAGE) {
+        let package_loaded = (curr_libs ^ (curr_libs | libs)).contains(StdLib::PACKAGE);
+        if package_loaded && !caps.contains(UnsafeCapabilities::ALLOW_C_FUNCTIONS_FROM_LUA) {
             mlua_expect!(self.lua().disable_c_modules(), "Error during disabling C modules");
         }
+        #[cfg(not(feature = "luau"))]
+        if package_loaded && !caps.contains(UnsafeCapabilities::ALLOW_BINARY_CHUNKS) {
+            mlua_expect!(
+                self.lua().disable_binary_modules(),
+                "Error during disabling binary modules"
+            );
+        }
         unsafe { (*self.extra.get()).libs |= libs };
 
         res
     }
 
+    /// Resumes the thread from Rust, which makes it subject to automatic yields (if enabled).
+    #[cfg(feature = "luau")]
+    pub(crate) unsafe fn resume_thread(
+        &self,
+        thread_state: *mut ffi::lua_State,
+        nargs: c_int,
+        nresults: &mut c_int,
+    ) -> c_int {
+        let extra = self.extra.get();
+        let auto_yield = (*extra).auto_yield_interval > 0;
+        if auto_yield {
+            (*extra).auto_yield_threads.insert(thread_state, 0);
+        }
+        let ret = ffi::lua_resume(thread_state, self.state(), nargs, nresults);
+        if auto_yield {
+            (*extra).auto_yield_threads.remove(&thread_state);
+        }
+        ret
+    }
+
+    /// See [`Lua::set_deterministic_pairs`]
+    #[inline]
+    pub(crate) fn deterministic_pairs(&self) -> bool {
+        unsafe { (*self.extra.get()).deterministic_pairs }
+    }
+
+    /// See [`Lua::set_integer_overflow`]
+    #[inline]
+    pub(crate) fn integer_overflow(&self) -> IntegerOverflow {
+        unsafe { (*self.extra.get()).integer_overflow }
+    }
+
+    /// See [`Lua::set_duration_format`]
+    #[inline]
+    pub(crate) fn duration_format(&self) -> DurationFormat {
+        unsafe { (*self.extra.get()).duration_format }
+    }
+
+    /// See [`Lua::set_number_format`]
+    #[inline]
+    pub(crate) fn number_format(&self) -> Option<crate::number_format::NumberFormat> {
+        unsafe { (*self.extra.get()).number_format }
+    }
+
+    /// Calls the function with the random source set for the Lua state (if any).
+    ///
+    /// See [`Lua::set_random_source`]
+    pub(crate) fn with_random_source<R>(
+        &self,
+        f: impl FnOnce(&mut dyn crate::random::RandomSource) -> R,
+    ) -> Option<R> {
+        // The source is taken out for the duration of the call in case of reentrance
+        let mut source = unsafe { (*self.extra.get()).random_source.take()? };
+        let res = f(&mut *source);
+        unsafe { (*self.extra.get()).random_source.get_or_insert(source) };
+        Some(res)
+    }
+
+    /// Returns the policy applied to `collectgarbage` calls (if any).
+    ///
+    /// See [`Lua::set_collectgarbage_policy`]
+    pub(crate) fn collectgarbage_policy(&self) -> Option<crate::types::GCPolicyCallback> {
+        unsafe { (*self.extra.get()).collectgarbage_policy.clone() }
+    }
+
+    /// Returns the cancellation token set for the Lua state (if any).
+    ///
+    /// See [`Lua::set_cancellation_token`]
+    pub(crate) fn cancellation_token(&self) -> Option<crate::cancel::CancellationToken> {
+        unsafe { (*self.extra.get()).cancellation_token.clone() }
+    }
+
+    /// Returns the Rust backtrace captured when the error was raised into Lua.
+    ///
+    /// See [`Lua::install_error_objects`]
+    pub(crate) fn error_backtrace(&self, error: &Error) -> Option<Arc<std::backtrace::Backtrace>> {
+        let backtraces = unsafe { (*self.extra.get()).error_backtraces.as_ref()? };
+        crate::error_object::find_backtrace(backtraces, error)
+    }
+
+    /// Updates counters reported by [`Lua::metrics_snapshot`].
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub(crate) fn update_metrics(&self, f: impl FnOnce(&mut crate::metrics::Metrics)) {
+        unsafe { f(&mut (*self.extra.get()).metrics) }
+    }
+
+    /// Returns the virtual filesystem set by [`Lua::set_vfs`].
+    pub(crate) fn vfs(&self) -> Option<XRc<dyn crate::vfs::Vfs>> {
+        unsafe { (*self.extra.get()).vfs.clone() }
+    }
+
+    /// Returns the time driver set by [`Lua::set_time_driver`].
+    #[cfg(feature = "async")]
+    pub(crate) fn time_driver(&self) -> Result<&dyn crate::time::TimeDriver> {
+        match unsafe { &(*self.extra.get()).time_driver } {
+            Some(driver) => Ok(driver.as_ref()),
+            None => Err(Error::runtime("time driver is not set")),
+        }
+    }
+
     /// See [`Lua::try_set_app_data`]
     #[inline]
     pub(crate) fn try_set_app_data<T: MaybeSend + 'static>(&self, data: T) -> StdResult<Option<T>, T> {
@@ -303,6 +463,45 @@ impl RawLua {
         Arc::ptr_eq(&key.unref_list, registry_unref_list)
     }
 
+    /// Calls the function with the registry entries tracked for [`Lua::registry`].
+    pub(crate) fn with_registry_tracking<R>(
+        &self,
+        f: impl FnOnce(&mut crate::registry::RegistryTracking) -> R,
+    ) -> R {
+        unsafe { f(&mut (*self.extra.get()).registry_tracking) }
+    }
+
+    /// Calls the function with the serialization hooks of userdata types.
+    #[cfg(feature = "serialize")]
+    pub(crate) fn with_userdata_hooks<R>(
+        &self,
+        f: impl FnOnce(&mut crate::serde::userdata::UserDataHooks) -> R,
+    ) -> R {
+        unsafe { f(&mut (*self.extra.get()).userdata_hooks) }
+    }
+
+    /// Returns ids of the dropped `RegistryKey`s waiting to be expired.
+    pub(crate) fn registry_unref_ids(&self) -> Vec<c_int> {
+        let registry_unref_list = unsafe { &(*self.extra.get()).registry_unref_list };
+        registry_unref_list.lock().clone().unwrap_or_default()
+    }
+
+    /// Removes the id from the list of dropped `RegistryKey`s, returning `true` if it was there.
+    pub(crate) fn take_registry_unref_id(&self, id: c_int) -> bool {
+        let registry_unref_list = unsafe { &(*self.extra.get()).registry_unref_list };
+        let mut unref_list = registry_unref_list.lock();
+        match unref_list
+            .as_mut()
+            .and_then(|list| list.iter().position(|&x| x == id))
+        {
+            Some(i) => {
+                unref_list.as_mut().unwrap().swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub(crate) fn load_chunk(
         &self,
         name: Option<&CStr>,
@@ -314,6 +513,8 @@ impl RawLua {
         unsafe {
             let _sg = StackGuard::new(state);
             check_stack(state, 2)?;
+            let _source_guard =
+                MemorySourceGuard::new(state, || name.map(|n| n.to_string_lossy().into_owned()));
 
             let mode_str = match mode {
                 Some(ChunkMode::Binary) => cstr!("b"),
@@ -402,6 +603,9 @@ impl RawLua {
 
     /// See [`Lua::create_string`]
     pub(crate) unsafe fn create_string(&self, s: impl AsRef<[u8]>) -> Result<String> {
+        #[cfg(feature = "metrics")]
+        self.update_metrics(|metrics| metrics.record_string(s.as_ref()));
+
         let state = self.state();
         if self.unlikely_memory_error() {
             push_string(self.ref_thread(), s.as_ref(), false)?;
@@ -471,6 +675,10 @@ impl RawLua {
         };
         let thread = Thread(self.pop_ref(), thread_state);
         ffi::lua_xpush(self.ref_thread(), thread_state, func.0.index);
+        #[cfg(feature = "metrics")]
+        {
+            (*self.extra.get()).metrics.threads_created += 1;
+        }
         Ok(thread)
     }
 
@@ -489,7 +697,7 @@ impl RawLua {
                 ffi::lua_replace(thread_state, ffi::LUA_GLOBALSINDEX);
             }
 
-            return Ok(Thread(ValueRef::new(self, index), thread_state));
+            return Ok(Thread(self.new_ref(index), thread_state));
         }
 
         self.create_thread(func)
@@ -514,6 +722,8 @@ impl RawLua {
             #[cfg(feature = "luau")]
             ffi::lua_resetthread(thread_state);
             extra.thread_pool.push(thread.0.index);
+            #[cfg(feature = "ref-audit")]
+            extra.ref_origins.remove(&thread.0.index);
             thread.0.drop = false; // Prevent thread from being garbage collected
             return true;
         }
@@ -632,7 +842,7 @@ impl RawLua {
                 let failure_mt_ptr = (*self.extra.get()).wrapped_failure_mt_ptr;
                 match get_internal_userdata::<WrappedFailure>(state, idx, failure_mt_ptr).as_mut() {
                     Some(WrappedFailure::Error(err)) => Value::Error(Box::new(err.clone())),
-                    Some(WrappedFailure::Panic(panic)) => {
+                    Some(WrappedFailure::Panic(panic, _)) => {
                         if let Some(panic) = panic.take() {
                             resume_unwind(panic);
                         }
@@ -690,28 +900,47 @@ impl RawLua {
     pub(crate) unsafe fn pop_ref(&self) -> ValueRef {
         ffi::lua_xmove(self.state(), self.ref_thread(), 1);
         let index = ref_stack_pop(self.extra.get());
-        ValueRef::new(self, index)
+        self.new_ref(index)
     }
 
     // Same as `pop_ref` but assumes the value is already on the reference thread
     #[inline]
     pub(crate) unsafe fn pop_ref_thread(&self) -> ValueRef {
         let index = ref_stack_pop(self.extra.get());
-        ValueRef::new(self, index)
+        self.new_ref(index)
     }
 
     #[inline]
     pub(crate) unsafe fn clone_ref(&self, vref: &ValueRef) -> ValueRef {
         ffi::lua_pushvalue(self.ref_thread(), vref.index);
         let index = ref_stack_pop(self.extra.get());
-        ValueRef::new(self, index)
+        self.new_ref(index)
     }
 
     pub(crate) unsafe fn drop_ref(&self, vref: &ValueRef) {
+        ref_stack_free(self.extra.get(), vref.index);
+        #[cfg(feature = "ref-audit")]
+        (*self.extra.get()).ref_origins.remove(&vref.index);
+    }
+
+    #[inline]
+    unsafe fn new_ref(&self, index: c_int) -> ValueRef {
+        #[cfg(feature = "ref-audit")]
+        self.track_ref(index);
+        ValueRef::new(self, index)
+    }
+
+    // Records where the reference was created
+    #[cfg(feature = "ref-audit")]
+    #[inline(never)]
+    unsafe fn track_ref(&self, index: c_int) {
         let ref_thread = self.ref_thread();
-        ffi::lua_pushnil(ref_thread);
-        ffi::lua_replace(ref_thread, vref.index);
-        (*self.extra.get()).ref_free.push(vref.index);
+        let type_name = CStr::from_ptr(ffi::lua_typename(ref_thread, ffi::lua_type(ref_thread, index)));
+        let origin = RefOrigin {
+            type_name: type_name.to_str().unwrap_or("unknown"),
+            backtrace: Arc::new(Backtrace::force_capture()),
+        };
+        (*self.extra.get()).ref_origins.insert(index, origin);
     }
 
     #[inline]
@@ -829,9 +1058,24 @@ impl RawLua {
     }
 
     pub(crate) unsafe fn push_userdata_metatable<T>(&self, mut registry: UserDataRegistry<T>) -> Result<()> {
+        #[cfg(debug_assertions)]
+        registry.validate()?;
+
+        // Async destructors are supported only for shared types
+        #[cfg(all(feature = "async", not(feature = "luau")))]
+        let async_destructor = registry.async_destructor.take().zip(registry.type_id());
+
+        // Serialization hooks of shared types replace hooks of the previous registration
+        #[cfg(feature = "serialize")]
+        if let Some(type_id) = registry.type_id() {
+            let (serialize, deserialize) = (registry.serialize_hook.take(), registry.deserialize_hook.take());
+            let type_name = short_type_name::<T>();
+            self.with_userdata_hooks(|hooks| hooks.register(type_id, &type_name, serialize, deserialize));
+        }
+
         let state = self.state();
         let _sg = StackGuard::with_top(state, ffi::lua_gettop(state) + 1);
-        check_stack(state, 13)?;
+        check_stack(state, 15)?;
 
         // Prepare metatable, add meta methods first and then meta fields
         let metatable_nrec = registry.meta_methods.len() + registry.meta_fields.len();
@@ -853,8 +1097,11 @@ impl RawLua {
             push_field(self)?;
             rawset_field(state, -2, MetaMethod::validate(&k)?)?;
         }
-        // Set `__name/__type` if not provided
-        if !has_name {
+        // Set `__name/__type` if not provided (or overridden)
+        if let Some(type_name) = registry.type_name {
+            push_string(state, type_name.as_bytes(), !self.unlikely_memory_error())?;
+            rawset_field(state, -2, MetaMethod::Type.name())?;
+        } else if !has_name {
             let type_name = short_type_name::<T>();
             push_string(state, type_name.as_bytes(), !self.unlikely_memory_error())?;
             rawset_field(state, -2, MetaMethod::Type.name())?;
@@ -863,6 +1110,10 @@ impl RawLua {
 
         let mut extra_tables_count = 0;
 
+        // Tracks whether `__index` table (if any) was created by us and cannot be modified
+        // afterwards, which makes its lookups safe to cache.
+        let mut index_is_internal = false;
+
         let fields_nrec = registry.fields.len();
         if fields_nrec > 0 {
             // If `__index` is a table then update it in-place
@@ -873,6 +1124,7 @@ impl RawLua {
                         // Create a new table
                         ffi::lua_pop(state, 1);
                         push_table(state, 0, fields_nrec, true)?;
+                        index_is_internal = true;
                     }
                     for (k, push_field) in mem::take(&mut registry.fields) {
                         push_field(self)?;
@@ -953,6 +1205,7 @@ impl RawLua {
                 ffi::LUA_TNIL => {
                     // Set the new table as `__index`
                     rawset_field(state, metatable_index, "__index")?;
+                    index_is_internal = true;
                 }
                 _ => {
                     methods_index = Some(ffi::lua_absindex(state, -1));
@@ -975,9 +1228,18 @@ impl RawLua {
             field_getters_index,
             field_setters_index,
             methods_index,
+            index_is_internal,
             extra_init,
         )?;
 
+        // Replace the default destructor to schedule the async one
+        #[cfg(all(feature = "async", not(feature = "luau")))]
+        if let Some(((destructor, gc), type_id)) = async_destructor {
+            ffi::lua_pushcfunction(state, gc);
+            rawset_field(state, metatable_index, "__gc")?;
+            (*self.extra.get()).async_destructors.insert(type_id, destructor);
+        }
+
         // Pop extra tables to get metatable on top of the stack
         ffi::lua_pop(state, extra_tables_count);
 
@@ -1056,8 +1318,11 @@ impl RawLua {
         Ok(type_id)
     }
 
-    // Creates a Function out of a Callback containing a 'static Fn.
-    pub(crate) fn create_callback(&self, func: Callback) -> Result<Function> {
+    // Creates a Function out of a 'static Fn.
+    pub(crate) fn create_callback<F>(&self, func: F) -> Result<Function>
+    where
+        F: Fn(&RawLua, c_int) -> Result<c_int> + MaybeSend + 'static,
+    {
         unsafe extern "C-unwind" fn call_callback(state: *mut ffi::lua_State) -> c_int {
             let upvalue = get_userdata::<CallbackUpvalue>(state, ffi::lua_upvalueindex(1));
             callback_error_ext(state, (*upvalue).extra.get(), |extra, nargs| {
@@ -1065,8 +1330,15 @@ impl RawLua {
                 // The lock must be already held as the callback is executed
                 let rawlua = (*extra).raw_lua();
                 let _guard = StateGuard::new(rawlua, state);
-                match (*upvalue).data {
-                    Some(ref func) => func(rawlua, nargs),
+                #[cfg(feature = "metrics")]
+                {
+                    (*extra).metrics.callbacks_invoked += 1;
+                }
+                match (*upvalue).slot.and_then(|slot| (*extra).callbacks.get(slot)) {
+                    Some(func) => match (*extra).callback_middleware.clone() {
+                        Some(middleware) => call_with_middleware(rawlua, nargs, &*func, &middleware),
+                        None => (*func)(rawlua, nargs),
+                    },
                     None => Err(Error::CallbackDestructed),
                 }
             })
@@ -1077,10 +1349,10 @@ impl RawLua {
             let _sg = StackGuard::new(state);
             check_stack(state, 4)?;
 
-            let func = Some(func);
+            let slot = Some((*self.extra.get()).callbacks.insert(func));
             let extra = XRc::clone(&self.extra);
             let protect = !self.unlikely_memory_error();
-            push_internal_userdata(state, CallbackUpvalue { data: func, extra }, protect)?;
+            push_internal_userdata(state, CallbackUpvalue { slot, extra }, protect)?;
             if protect {
                 protect_lua!(state, 1, 1, fn(state) {
                     ffi::lua_pushcclosure(state, call_callback, 1);
@@ -1114,9 +1386,16 @@ impl RawLua {
                 // The lock must be already held as the callback is executed
                 let rawlua = (*extra).raw_lua();
                 let _guard = StateGuard::new(rawlua, state);
+                #[cfg(feature = "metrics")]
+                {
+                    (*extra).metrics.callbacks_invoked += 1;
+                }
 
                 let func = &*(*upvalue).data;
-                let fut = func(rawlua, nargs);
+                let mut fut = func(rawlua, nargs);
+                if let Some(token) = rawlua.cancellation_token() {
+                    fut = token.guard(fut);
+                }
                 let extra = XRc::clone(&(*upvalue).extra);
                 let protect = !rawlua.unlikely_memory_error();
                 push_internal_userdata(state, AsyncPollUpvalue { data: fut, extra }, protect)?;
@@ -1247,7 +1526,49 @@ impl RawLua {
     }
 }
 
+// Destructor of userdata with an async destructor, schedules the cleanup future
+#[cfg(all(feature = "async", not(feature = "luau")))]
+pub(crate) unsafe extern "C-unwind" fn userdata_async_destructor<T: 'static>(
+    state: *mut ffi::lua_State,
+) -> c_int {
+    let data = crate::util::take_userdata::<UserDataStorage<T>>(state);
+    let extra = ExtraData::get(state);
+    if extra.is_null() {
+        return 0;
+    }
+    // Borrowed values cannot be moved out and are dropped as usual
+    let (Some(destructor), Ok(data)) = (
+        (*extra).async_destructors.get(&TypeId::of::<T>()),
+        data.into_inner(),
+    ) else {
+        return 0;
+    };
+    let future = destructor(Box::new(data));
+    (*extra)
+        .pending_tasks
+        .push(crate::scheduler::Task::new_cleanup(future));
+    0
+}
+
 // Uses 3 stack spaces
+// Checks that the unsafe libraries in `libs` are allowed by the capabilities
+#[cfg_attr(feature = "luau", allow(unused_variables))]
+pub(super) fn check_unsafe_libs(libs: StdLib, caps: UnsafeCapabilities) -> Result<()> {
+    #[cfg(not(feature = "luau"))]
+    if libs.contains(StdLib::DEBUG) && !caps.contains(UnsafeCapabilities::ALLOW_DEBUG_LIBRARY) {
+        return Err(Error::SafetyError(
+            "the unsafe `debug` module can't be loaded without `ALLOW_DEBUG_LIBRARY` capability".to_string(),
+        ));
+    }
+    #[cfg(feature = "luajit")]
+    if libs.contains(StdLib::FFI) && !caps.contains(UnsafeCapabilities::ALLOW_FFI_LIBRARY) {
+        return Err(Error::SafetyError(
+            "the unsafe `ffi` module can't be loaded without `ALLOW_FFI_LIBRARY` capability".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 unsafe fn load_from_std_lib(state: *mut ffi::lua_State, libs: StdLib) -> Result<()> {
     #[inline(always)]
     pub unsafe fn requiref(
@@ -1344,6 +1665,14 @@ unsafe fn load_from_std_lib(state: *mut ffi::lua_State, libs: StdLib) -> Result<
         ffi::lua_pop(state, 1);
     }
 
+    #[cfg(feature = "luau")]
+    if libs.contains(StdLib::VECTOR) {
+        protect_lua!(state, 0, 0, |state| {
+            ffi::lua_pushcfunction(state, crate::luau::lua_vector);
+            ffi::lua_setglobal(state, cstr!("vector"));
+        })?;
+    }
+
     if libs.contains(StdLib::MATH) {
         requiref(state, ffi::LUA_MATHLIBNAME, ffi::luaopen_math, 1)?;
         ffi::lua_pop(state, 1);
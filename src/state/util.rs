@@ -3,8 +3,9 @@ use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::ptr;
 use std::sync::Arc;
 
-use crate::error::{Error, Result};
+use crate::error::{ConversionErrorInfo, Error, Result};
 use crate::state::{ExtraData, RawLua};
+use crate::types::ConversionErrorCallback;
 use crate::util::{self, get_internal_metatable, WrappedFailure};
 
 const WRAPPED_FAILURE_POOL_SIZE: usize = 64;
@@ -75,9 +76,7 @@ where
                     ffi::lua_rawcheckstack(state, 2);
                     ffi::lua_pushvalue(ref_thread, index);
                     ffi::lua_xmove(ref_thread, state, 1);
-                    ffi::lua_pushnil(ref_thread);
-                    ffi::lua_replace(ref_thread, index);
-                    (*extra).ref_free.push(index);
+                    ref_stack_free(extra, index);
                     ffi::lua_touserdata(state, -1) as *mut WrappedFailure
                 }
             }
@@ -100,9 +99,7 @@ where
                     if (*extra).wrapped_failure_pool.len() < WRAPPED_FAILURE_POOL_SIZE {
                         (*extra).wrapped_failure_pool.push(index);
                     } else {
-                        ffi::lua_pushnil(ref_thread);
-                        ffi::lua_replace(ref_thread, index);
-                        (*extra).ref_free.push(index);
+                        ref_stack_free(extra, index);
                     }
                 }
             }
@@ -113,13 +110,24 @@ where
     // to store a wrapped failure (error or panic) *before* we proceed.
     let prealloc_failure = PreallocatedFailure::reserve(state, extra);
 
-    match catch_unwind(AssertUnwindSafe(|| f(extra, nargs))) {
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let res = f(extra, nargs);
+        if let (Err(err), Some(callback)) = (&res, &(*extra).conversion_error_callback) {
+            report_conversion_error(state, err, callback.clone());
+        }
+        res
+    }));
+    match res {
         Ok(Ok(r)) => {
             // Return unused `WrappedFailure` to the pool
             prealloc_failure.release(state, extra);
             r
         }
         Ok(Err(err)) => {
+            #[cfg(feature = "metrics")]
+            {
+                (*extra).metrics.errors_raised += 1;
+            }
             let wrapped_error = prealloc_failure.r#use(state, extra);
 
             // Build `CallbackError` with traceback
@@ -132,6 +140,9 @@ where
                 "<not enough stack space for traceback>".to_string()
             };
             let cause = Arc::new(err);
+            if let Some(backtraces) = &mut (*extra).error_backtraces {
+                crate::error_object::capture_backtrace(backtraces, &cause);
+            }
             ptr::write(
                 wrapped_error,
                 WrappedFailure::Error(Error::CallbackError { traceback, cause }),
@@ -142,8 +153,16 @@ where
             ffi::lua_error(state)
         }
         Err(p) => {
+            if (*extra).abort_on_panic {
+                std::process::abort();
+            }
+            #[cfg(feature = "metrics")]
+            {
+                (*extra).metrics.errors_raised += 1;
+            }
             let wrapped_panic = prealloc_failure.r#use(state, extra);
-            ptr::write(wrapped_panic, WrappedFailure::Panic(Some(p)));
+            let context = util::panic_context(state);
+            ptr::write(wrapped_panic, WrappedFailure::Panic(Some(p), context));
             get_internal_metatable::<WrappedFailure>(state);
             ffi::lua_setmetatable(state, -2);
             ffi::lua_error(state)
@@ -151,30 +170,159 @@ where
     }
 }
 
+// Calls the conversion error callback if the error was caused by a failed conversion
+unsafe fn report_conversion_error(
+    state: *mut ffi::lua_State,
+    err: &Error,
+    callback: ConversionErrorCallback,
+) {
+    if let Some(mut info) = ConversionErrorInfo::from_error(err) {
+        info.location = util::caller_location(state);
+        callback(&info);
+    }
+}
+
+// A block of reference slots reserved by `Lua::value_scope`
+//
+// References created while the block is active are allocated from it. Released slots are
+// recycled within the block without clearing them, and the whole block is released at once
+// when the scope ends.
+pub(crate) struct RefBlock {
+    start: c_int,
+    end: c_int,
+    next: c_int,
+    free: Vec<c_int>,
+}
+
+impl RefBlock {
+    #[inline]
+    fn contains(&self, index: c_int) -> bool {
+        index >= self.start && index < self.end
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+
+    pub(crate) fn used(&self) -> usize {
+        (self.next - self.start) as usize - self.free.len()
+    }
+}
+
 pub(super) unsafe fn ref_stack_pop(extra: *mut ExtraData) -> c_int {
     let extra = &mut *extra;
+    if let Some(block) = extra.ref_blocks.last_mut() {
+        let index = match block.free.pop() {
+            Some(index) => Some(index),
+            None if block.next < block.end => {
+                block.next += 1;
+                Some(block.next - 1)
+            }
+            None => None,
+        };
+        if let Some(index) = index {
+            ffi::lua_replace(extra.ref_thread, index);
+            return index;
+        }
+    }
+
     if let Some(free) = extra.ref_free.pop() {
         ffi::lua_replace(extra.ref_thread, free);
         return free;
     }
 
+    if !ref_stack_reserve(extra, 1) {
+        // Pop item on top of the stack to avoid stack leaking and successfully run destructors
+        // during unwinding.
+        ffi::lua_pop(extra.ref_thread, 1);
+        let top = extra.ref_stack_top;
+        // It is a user error to create enough references to exhaust the Lua max stack size for
+        // the ref thread.
+        panic!("cannot create a Lua reference, out of auxiliary stack space (used {top} slots)");
+    }
+    extra.ref_stack_top += 1;
+    extra.ref_stack_top
+}
+
+// Makes sure the ref stack has space for `n` more slots
+unsafe fn ref_stack_reserve(extra: &mut ExtraData, n: c_int) -> bool {
     // Try to grow max stack size
-    if extra.ref_stack_top >= extra.ref_stack_size {
-        let mut inc = extra.ref_stack_size; // Try to double stack size
-        while inc > 0 && ffi::lua_checkstack(extra.ref_thread, inc) == 0 {
+    while extra.ref_stack_top + n > extra.ref_stack_size {
+        let mut inc = extra.ref_stack_size.max(n); // Try to double stack size
+        let top = extra.ref_stack_top;
+        while inc > 0 && ffi::lua_checkstack(extra.ref_thread, extra.ref_stack_size + inc - top) == 0 {
             inc /= 2;
         }
         if inc == 0 {
-            // Pop item on top of the stack to avoid stack leaking and successfully run destructors
-            // during unwinding.
-            ffi::lua_pop(extra.ref_thread, 1);
-            let top = extra.ref_stack_top;
-            // It is a user error to create enough references to exhaust the Lua max stack size for
-            // the ref thread.
-            panic!("cannot create a Lua reference, out of auxiliary stack space (used {top} slots)");
+            return false;
         }
         extra.ref_stack_size += inc;
     }
-    extra.ref_stack_top += 1;
-    extra.ref_stack_top
+    true
+}
+
+// Releases a slot in the ref stack
+pub(super) unsafe fn ref_stack_free(extra: *mut ExtraData, index: c_int) {
+    let extra = &mut *extra;
+    // Only the innermost block recycles its slots, slots of outer blocks are released as usual
+    // (and treated as used when the block is released)
+    if let Some(block) = extra.ref_blocks.last_mut().filter(|b| b.contains(index)) {
+        // The slot is cleared when reused or when the block is released
+        block.free.push(index);
+        return;
+    }
+    ffi::lua_pushnil(extra.ref_thread);
+    ffi::lua_replace(extra.ref_thread, index);
+    extra.ref_free.push(index);
+}
+
+// Reserves a new block of `capacity` slots in the ref stack
+pub(super) unsafe fn ref_block_push(extra: *mut ExtraData, capacity: usize) {
+    let extra = &mut *extra;
+    let capacity = capacity.min(c_int::MAX as usize / 2) as c_int;
+    let capacity = if ref_stack_reserve(extra, capacity) {
+        capacity
+    } else {
+        0
+    };
+    let start = extra.ref_stack_top + 1;
+    ffi::lua_settop(extra.ref_thread, extra.ref_stack_top + capacity);
+    extra.ref_stack_top += capacity;
+    extra.ref_blocks.push(RefBlock {
+        start,
+        end: start + capacity,
+        next: start,
+        free: Vec::new(),
+    });
+}
+
+// Releases the most recently reserved block of slots.
+//
+// Slots that are still in use become regular ref stack slots.
+pub(super) unsafe fn ref_block_pop(extra: *mut ExtraData) {
+    let extra = &mut *extra;
+    let Some(mut block) = extra.ref_blocks.pop() else {
+        return;
+    };
+    let ref_thread = extra.ref_thread;
+    block.free.sort_unstable();
+    let is_free = |index: c_int| index >= block.next || block.free.binary_search(&index).is_ok();
+
+    // Truncate the stack if the block is on top of it
+    let mut top = extra.ref_stack_top;
+    if block.end - 1 == top {
+        top = (block.start..block.end)
+            .rev()
+            .find(|&i| !is_free(i))
+            .unwrap_or(block.start - 1);
+        ffi::lua_settop(ref_thread, top);
+        extra.ref_stack_top = top;
+    }
+    for index in (block.start..=top.min(block.end - 1)).filter(|&i| is_free(i)) {
+        if index < block.next {
+            ffi::lua_pushnil(ref_thread);
+            ffi::lua_replace(ref_thread, index);
+        }
+        extra.ref_free.push(index);
+    }
 }
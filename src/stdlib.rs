@@ -44,10 +44,22 @@ impl StdLib {
     pub const PACKAGE: StdLib = StdLib(1 << 8);
 
     /// [`buffer`](https://luau-lang.org/library#buffer-library) library
+    ///
+    /// Requires `feature = "luau"`
     #[cfg(any(feature = "luau", doc))]
     #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
     pub const BUFFER: StdLib = StdLib(1 << 9);
 
+    /// [`vector`](https://luau-lang.org/typecheck#vector) builtin constructor
+    ///
+    /// Included in [`StdLib::ALL`] and [`StdLib::ALL_SAFE`]. Custom sets of libraries must include
+    /// it explicitly to have the `vector` global installed.
+    ///
+    /// Requires `feature = "luau"`
+    #[cfg(any(feature = "luau", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    pub const VECTOR: StdLib = StdLib(1 << 10);
+
     /// [`jit`](http://luajit.org/ext_jit.html) library
     ///
     /// Requires `feature = "luajit"`
@@ -118,3 +130,85 @@ impl BitXorAssign for StdLib {
         *self = StdLib(self.0 ^ rhs.0)
     }
 }
+
+/// Flags describing the set of unsafe capabilities enabled in a Lua state.
+///
+/// A state created by [`Lua::new`] or [`Lua::new_with`] only allows binary chunks, and
+/// [`Lua::unsafe_new`] enables all of them. Use [`Lua::unsafe_new_with_capabilities`] to enable
+/// only the ones you need.
+///
+/// [`Lua::new`]: crate::Lua::new
+/// [`Lua::new_with`]: crate::Lua::new_with
+/// [`Lua::unsafe_new`]: crate::Lua::unsafe_new
+/// [`Lua::unsafe_new_with_capabilities`]: crate::Lua::unsafe_new_with_capabilities
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
+pub struct UnsafeCapabilities(u32);
+
+impl UnsafeCapabilities {
+    /// Allows Lua code to load precompiled (binary) chunks using `load`, `loadfile`, `dofile`
+    /// and `require`.
+    ///
+    /// Malformed bytecode can crash the Lua VM. Binary chunks loaded from Rust using
+    /// [`Lua::load`] are not affected by this flag.
+    ///
+    /// Has no effect in Luau, which cannot load bytecode from Lua code.
+    ///
+    /// Lua 5.1 `loadfile` has no way to reject binary chunks, so without this flag `loadfile`,
+    /// `dofile` and the Lua files searcher of `require` are reimplemented on top of [`std::fs`]
+    /// (and stdin). They follow the original behavior, but error messages for unreadable or
+    /// missing files come from Rust and may differ from the ones of the built-in functions.
+    ///
+    /// [`Lua::load`]: crate::Lua::load
+    pub const ALLOW_BINARY_CHUNKS: UnsafeCapabilities = UnsafeCapabilities(1);
+
+    /// Allows loading the [`debug`](https://www.lua.org/manual/5.4/manual.html#6.10) library.
+    ///
+    /// The Luau `debug` library is safe and does not require this flag.
+    pub const ALLOW_DEBUG_LIBRARY: UnsafeCapabilities = UnsafeCapabilities(1 << 1);
+
+    /// Allows loading the LuaJIT [`ffi`](http://luajit.org/ext_ffi.html) library.
+    ///
+    /// Requires `feature = "luajit"`
+    #[cfg(any(feature = "luajit", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "luajit")))]
+    pub const ALLOW_FFI_LIBRARY: UnsafeCapabilities = UnsafeCapabilities(1 << 2);
+
+    /// Allows Lua code to load C functions from shared libraries, using `package.loadlib` or
+    /// `require` of C modules.
+    pub const ALLOW_C_FUNCTIONS_FROM_LUA: UnsafeCapabilities = UnsafeCapabilities(1 << 3);
+
+    /// No unsafe capabilities
+    pub const NONE: UnsafeCapabilities = UnsafeCapabilities(0);
+    /// All unsafe capabilities
+    pub const ALL: UnsafeCapabilities = UnsafeCapabilities(u32::MAX);
+
+    pub fn contains(self, caps: Self) -> bool {
+        (self & caps).0 == caps.0
+    }
+}
+
+impl BitAnd for UnsafeCapabilities {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        UnsafeCapabilities(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for UnsafeCapabilities {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = UnsafeCapabilities(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for UnsafeCapabilities {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        UnsafeCapabilities(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for UnsafeCapabilities {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = UnsafeCapabilities(self.0 | rhs.0)
+    }
+}
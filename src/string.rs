@@ -74,6 +74,32 @@ impl String {
         StdString::from_utf8_lossy(&self.as_bytes()).into_owned()
     }
 
+    /// Converts this string from the given [`Encoding`] to a UTF-8 [`StdString`].
+    ///
+    /// Returns an error if the string is not valid in the source encoding.
+    ///
+    /// Requires `feature = "encoding"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Encoding, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    ///
+    /// let s = lua.create_string(b"\x93\xfa\x96\x7b")?;
+    /// assert_eq!(s.decode(Encoding::ShiftJis)?, "日本");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`StdString`]: std::string::String
+    #[cfg(feature = "encoding")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "encoding")))]
+    pub fn decode(&self, encoding: crate::Encoding) -> Result<StdString> {
+        encoding.decode(&self.as_bytes())
+    }
+
     /// Get the bytes that make up this string.
     ///
     /// The returned slice will not contain the terminating nul byte, but will contain any nul
@@ -97,6 +123,49 @@ impl String {
         BorrowedBytes(&bytes[..bytes.len() - 1], guard)
     }
 
+    /// Returns an iterator over the characters of this string.
+    ///
+    /// Unlike [`to_str`], the string is validated lazily, so this does not scan the whole string
+    /// upfront. The iterator yields an error when it reaches an invalid UTF-8 sequence and stops
+    /// after it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    ///
+    /// let s = lua.create_string("héllo")?;
+    /// assert_eq!(s.chars().collect::<Result<String>>()?, "héllo");
+    ///
+    /// let s = lua.create_string(b"ab\xff")?;
+    /// let mut chars = s.chars();
+    /// assert_eq!(chars.next().unwrap()?, 'a');
+    /// assert_eq!(chars.next().unwrap()?, 'b');
+    /// assert!(chars.next().unwrap().is_err());
+    /// assert!(chars.next().is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`to_str`]: #method.to_str
+    pub fn chars(&self) -> Chars<'_> {
+        Chars(self.char_indices())
+    }
+
+    /// Returns an iterator over the characters of this string and their byte positions.
+    ///
+    /// See [`chars`] for details about validation.
+    ///
+    /// [`chars`]: #method.chars
+    pub fn char_indices(&self) -> CharIndices<'_> {
+        CharIndices {
+            bytes: self.as_bytes(),
+            pos: 0,
+        }
+    }
+
     /// Get the bytes that make up this string, including the trailing nul byte.
     pub fn as_bytes_with_nul(&self) -> BorrowedBytes {
         let (bytes, guard) = unsafe { self.to_slice() };
@@ -312,6 +381,51 @@ impl<'a> IntoIterator for BorrowedBytes<'a> {
     }
 }
 
+/// An iterator over the characters of a [`String`] and their byte positions.
+///
+/// This struct is created by the [`String::char_indices`] method.
+pub struct CharIndices<'a> {
+    bytes: BorrowedBytes<'a>,
+    pos: usize,
+}
+
+impl Iterator for CharIndices<'_> {
+    type Item = Result<(usize, char)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.pos;
+        let rest = self.bytes.get(pos..).filter(|rest| !rest.is_empty())?;
+        match crate::utf8::decode(rest) {
+            Some((c, width)) => {
+                self.pos += width;
+                Some(Ok((pos, c)))
+            }
+            None => {
+                // Stop after the invalid sequence
+                self.pos = self.bytes.len();
+                Some(Err(Error::FromLuaConversionError {
+                    from: "string",
+                    to: "char".to_string(),
+                    message: Some(format!("invalid UTF-8 sequence at byte {pos}")),
+                }))
+            }
+        }
+    }
+}
+
+/// An iterator over the characters of a [`String`].
+///
+/// This struct is created by the [`String::chars`] method.
+pub struct Chars<'a>(CharIndices<'a>);
+
+impl Iterator for Chars<'_> {
+    type Item = Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.0.next()?.map(|(_, c)| c))
+    }
+}
+
 impl LuaType for String {
     const TYPE_ID: c_int = ffi::LUA_TSTRING;
 }
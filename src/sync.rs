@@ -0,0 +1,557 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::{Lua, WeakLua};
+use crate::table::Table;
+use crate::types::XRc;
+use crate::userdata::{UserData, UserDataMethods};
+use crate::util::Waiters;
+use crate::value::{MultiValue, Value};
+
+//
+// Semaphore
+//
+
+struct SemaphoreState {
+    permits: usize,
+    // Number of permits requested by each waiting task
+    requests: VecDeque<(u64, usize)>,
+    waiters: Waiters,
+}
+
+impl SemaphoreState {
+    // Returns a waker of the first waiting task if it can acquire permits
+    fn next_waker(&self) -> Option<Waker> {
+        match self.requests.front() {
+            Some(&(_, n)) if n <= self.permits => self.waiters.first(),
+            _ => None,
+        }
+    }
+
+    fn try_acquire(&mut self, n: usize) -> bool {
+        if self.waiters.is_empty() && n <= self.permits {
+            self.permits -= n;
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(Clone)]
+struct Semaphore(Arc<Mutex<SemaphoreState>>);
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Semaphore(Arc::new(Mutex::new(SemaphoreState {
+            permits,
+            requests: VecDeque::new(),
+            waiters: Waiters::default(),
+        })))
+    }
+
+    fn acquire(&self, n: usize) -> Acquire {
+        Acquire {
+            sem: self.clone(),
+            n,
+            id: None,
+        }
+    }
+
+    fn release(&self, n: usize) -> Result<()> {
+        let next_waker = {
+            let mut state = self.0.lock();
+            state.permits =
+                (state.permits.checked_add(n)).ok_or_else(|| Error::runtime("too many permits released"))?;
+            state.next_waker()
+        };
+        if let Some(waker) = next_waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn permits(&self) -> usize {
+        self.0.lock().permits
+    }
+}
+
+// Releases the acquired permits on drop (including when the holding future is cancelled)
+struct Permit {
+    sem: Semaphore,
+    n: usize,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let _ = self.sem.release(self.n);
+    }
+}
+
+// Waits for the permits in the first-come, first-served order
+struct Acquire {
+    sem: Semaphore,
+    n: usize,
+    // Identifier in the wait queue (if waiting)
+    id: Option<u64>,
+}
+
+impl Future for Acquire {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let sem = self.sem.clone();
+        let mut state = sem.0.lock();
+        if state.waiters.is_first(self.id) && self.n <= state.permits {
+            state.permits -= self.n;
+            let id = self.id.take();
+            state.waiters.remove(id);
+            state.requests.retain(|(i, _)| Some(*i) != id);
+            let next_waker = state.next_waker();
+            drop(state);
+            if let Some(waker) = next_waker {
+                waker.wake();
+            }
+            return Poll::Ready(());
+        }
+        if self.id.is_none() {
+            let mut id = None;
+            state.waiters.register(&mut id, cx.waker());
+            state.requests.push_back((id.unwrap(), self.n));
+            self.id = id;
+        } else {
+            let mut id = self.id;
+            state.waiters.register(&mut id, cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if self.id.is_some() {
+            let next_waker = {
+                let mut state = self.sem.0.lock();
+                state.waiters.remove(self.id);
+                state.requests.retain(|(i, _)| Some(*i) != self.id);
+                state.next_waker()
+            };
+            if let Some(waker) = next_waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// An asynchronous mutex for Lua coroutines.
+///
+/// Unlike the standard mutexes, it does not protect any data and is not tied to a scope: Lua code
+/// calls `mutex:lock()` (waiting until the mutex is available) and `mutex:unlock()` explicitly.
+/// Waiting coroutines are suspended and resumed by the async executor in the first-come,
+/// first-served order.
+///
+/// Available Lua methods:
+/// - `lock()` waits until the mutex is locked by the caller
+/// - `try_lock()` locks the mutex if it's available and returns `true` on success
+/// - `unlock()` unlocks the mutex (raises an error if it's not locked)
+/// - `is_locked()` returns `true` if the mutex is locked
+/// - `with(func, ...)` calls `func(...)` while holding the lock and returns its results
+///
+/// Cloned instances share the same lock.
+///
+/// Requires `feature = "async"`
+#[derive(Clone)]
+pub struct AsyncMutex(Semaphore);
+
+impl AsyncMutex {
+    /// Creates a new unlocked mutex.
+    pub fn new() -> Self {
+        AsyncMutex(Semaphore::new(1))
+    }
+
+    /// Returns `true` if the mutex is locked.
+    pub fn is_locked(&self) -> bool {
+        self.0.permits() == 0
+    }
+
+    fn unlock(&self) -> Result<()> {
+        if !self.is_locked() {
+            return Err(Error::runtime("mutex is not locked"));
+        }
+        self.0.release(1)
+    }
+}
+
+impl Default for AsyncMutex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for AsyncMutex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncMutex")
+            .field("locked", &self.is_locked())
+            .finish()
+    }
+}
+
+impl UserData for AsyncMutex {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("lock", |_, this, ()| {
+            let acquire = this.0.acquire(1);
+            async move {
+                acquire.await;
+                Ok(())
+            }
+        });
+        methods.add_method("try_lock", |_, this, ()| Ok(this.0 .0.lock().try_acquire(1)));
+        methods.add_method("unlock", |_, this, ()| this.unlock());
+        methods.add_method("is_locked", |_, this, ()| Ok(this.is_locked()));
+        methods.add_async_method("with", |_, this, (func, args): (Function, MultiValue)| {
+            let mutex = this.clone();
+            async move {
+                mutex.0.acquire(1).await;
+                let _permit = Permit { sem: mutex.0, n: 1 };
+                func.call_async::<MultiValue>(args).await
+            }
+        });
+    }
+}
+
+/// An asynchronous counting semaphore for Lua coroutines.
+///
+/// Available Lua methods:
+/// - `acquire([n])` waits until `n` (default 1) permits are available and takes them
+/// - `try_acquire([n])` takes `n` permits if available and returns `true` on success
+/// - `release([n])` returns `n` permits to the semaphore
+/// - `available()` returns the number of available permits
+///
+/// Waiting coroutines are served in the first-come, first-served order. Cloned instances share
+/// the same permits.
+///
+/// Requires `feature = "async"`
+#[derive(Clone)]
+pub struct AsyncSemaphore(Semaphore);
+
+impl AsyncSemaphore {
+    /// Creates a new semaphore with the given number of permits.
+    pub fn new(permits: usize) -> Self {
+        AsyncSemaphore(Semaphore::new(permits))
+    }
+
+    /// Returns the number of available permits.
+    pub fn available(&self) -> usize {
+        self.0.permits()
+    }
+}
+
+impl fmt::Debug for AsyncSemaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncSemaphore")
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+impl UserData for AsyncSemaphore {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("acquire", |_, this, n: Option<usize>| {
+            let acquire = this.0.acquire(n.unwrap_or(1));
+            async move {
+                acquire.await;
+                Ok(())
+            }
+        });
+        methods.add_method("try_acquire", |_, this, n: Option<usize>| {
+            Ok(this.0 .0.lock().try_acquire(n.unwrap_or(1)))
+        });
+        methods.add_method("release", |_, this, n: Option<usize>| {
+            this.0.release(n.unwrap_or(1))
+        });
+        methods.add_method("available", |_, this, ()| Ok(this.available()));
+    }
+}
+
+//
+// Channel
+//
+
+struct ChannelState {
+    // Values can only be passed within the Lua state that created the channel
+    lua: WeakLua,
+    queue: VecDeque<Value>,
+    capacity: Option<usize>,
+    closed: bool,
+    senders: Waiters,
+    receivers: Waiters,
+}
+
+impl ChannelState {
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|cap| self.queue.len() >= cap)
+    }
+}
+
+/// An asynchronous FIFO channel for passing Lua values between coroutines.
+///
+/// Available Lua methods:
+/// - `send(value)` waits until there is space in the channel and sends the value
+///   (raises an error if the channel is closed)
+/// - `try_send(value)` sends the value if there is space and returns `true` on success
+/// - `recv()` waits for a value and returns it, or returns `nil` if the channel is closed and empty
+/// - `try_recv()` returns the next value if available (`nil` otherwise)
+/// - `close()` closes the channel, the remaining values still can be received
+/// - `is_closed()` returns `true` if the channel is closed
+/// - `len()` returns the number of values in the channel
+///
+/// Cloned instances share the same channel. The channel is bound to the Lua state it was created
+/// for, using it from another Lua state raises an error.
+///
+/// Requires `feature = "async"`
+#[derive(Clone)]
+pub struct AsyncChannel(XRc<Mutex<ChannelState>>);
+
+impl AsyncChannel {
+    /// Creates a new channel for the given Lua state holding up to `capacity` values (unbounded if
+    /// `None`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(lua: &Lua, capacity: Option<usize>) -> Self {
+        assert!(capacity != Some(0), "channel capacity must be greater than zero");
+        AsyncChannel(XRc::new(Mutex::new(ChannelState {
+            lua: lua.weak(),
+            queue: VecDeque::new(),
+            capacity,
+            closed: false,
+            senders: Waiters::default(),
+            receivers: Waiters::default(),
+        })))
+    }
+
+    /// Returns the number of values in the channel.
+    pub fn len(&self) -> usize {
+        self.0.lock().queue.len()
+    }
+
+    /// Returns `true` if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().queue.is_empty()
+    }
+
+    /// Closes the channel.
+    ///
+    /// Sending to a closed channel fails, while the remaining values can still be received.
+    pub fn close(&self) {
+        let wakers = {
+            let mut state = self.0.lock();
+            state.closed = true;
+            let mut wakers = state.senders.all();
+            wakers.extend(state.receivers.all());
+            wakers
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().closed
+    }
+
+    fn check_lua(&self, lua: &Lua) -> Result<()> {
+        if self.0.lock().lua != lua.weak() {
+            return Err(Error::runtime("channel used from a different Lua state"));
+        }
+        Ok(())
+    }
+
+    fn try_send(&self, value: Value) -> Result<StdResult<(), Value>> {
+        let mut state = self.0.lock();
+        if state.closed {
+            return Err(Error::runtime("channel is closed"));
+        }
+        if !state.senders.is_empty() || state.is_full() {
+            return Ok(Err(value));
+        }
+        state.queue.push_back(value);
+        let waker = state.receivers.first();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(Ok(()))
+    }
+
+    fn try_recv(&self) -> Option<Value> {
+        let mut state = self.0.lock();
+        if !state.receivers.is_empty() {
+            return None;
+        }
+        let value = state.queue.pop_front();
+        let waker = value.as_ref().and_then(|_| state.senders.first());
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        value
+    }
+
+    async fn send(&self, value: Value) -> Result<()> {
+        let mut guard = WaiterGuard::new(self, true);
+        let mut value = Some(value);
+        poll_fn(|cx| {
+            let mut state = self.0.lock();
+            if state.closed {
+                state.senders.remove(guard.id.take());
+                return Poll::Ready(Err(Error::runtime("channel is closed")));
+            }
+            if state.senders.is_first(guard.id) && !state.is_full() {
+                state.senders.remove(guard.id.take());
+                state.queue.push_back(value.take().unwrap());
+                let mut wakers = Vec::from_iter(state.receivers.first());
+                if !state.is_full() {
+                    wakers.extend(state.senders.first());
+                }
+                drop(state);
+                wakers.into_iter().for_each(Waker::wake);
+                return Poll::Ready(Ok(()));
+            }
+            state.senders.register(&mut guard.id, cx.waker());
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn recv(&self) -> Option<Value> {
+        let mut guard = WaiterGuard::new(self, false);
+        poll_fn(|cx| {
+            let mut state = self.0.lock();
+            // After closing the remaining values are handed out without waiting for the turn
+            if state.receivers.is_first(guard.id) || state.closed {
+                if let Some(value) = state.queue.pop_front() {
+                    state.receivers.remove(guard.id.take());
+                    let mut wakers = Vec::from_iter(state.senders.first());
+                    if !state.queue.is_empty() {
+                        wakers.extend(state.receivers.first());
+                    }
+                    drop(state);
+                    wakers.into_iter().for_each(Waker::wake);
+                    return Poll::Ready(Some(value));
+                }
+            }
+            if state.closed {
+                state.receivers.remove(guard.id.take());
+                return Poll::Ready(None);
+            }
+            state.receivers.register(&mut guard.id, cx.waker());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+// Removes a cancelled waiter from the channel queue, passing the wake up to the next one
+struct WaiterGuard<'a> {
+    channel: &'a AsyncChannel,
+    sender: bool,
+    id: Option<u64>,
+}
+
+impl<'a> WaiterGuard<'a> {
+    fn new(channel: &'a AsyncChannel, sender: bool) -> Self {
+        WaiterGuard {
+            channel,
+            sender,
+            id: None,
+        }
+    }
+}
+
+impl Drop for WaiterGuard<'_> {
+    fn drop(&mut self) {
+        if self.id.is_some() {
+            let waker = {
+                let mut state = self.channel.0.lock();
+                let waiters = if self.sender {
+                    &mut state.senders
+                } else {
+                    &mut state.receivers
+                };
+                waiters.remove(self.id);
+                waiters.first()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for AsyncChannel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.0.lock();
+        f.debug_struct("AsyncChannel")
+            .field("len", &state.queue.len())
+            .field("capacity", &state.capacity)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl UserData for AsyncChannel {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_async_method("send", |lua, this, value: Value| {
+            let channel = this.clone();
+            async move {
+                channel.check_lua(&lua)?;
+                channel.send(value).await
+            }
+        });
+        methods.add_method("try_send", |lua, this, value: Value| {
+            this.check_lua(lua)?;
+            Ok(this.try_send(value)?.is_ok())
+        });
+        methods.add_async_method("recv", |lua, this, ()| {
+            let channel = this.clone();
+            async move {
+                channel.check_lua(&lua)?;
+                Ok(channel.recv().await)
+            }
+        });
+        methods.add_method("try_recv", |lua, this, ()| {
+            this.check_lua(lua)?;
+            Ok(this.try_recv())
+        });
+        methods.add_method("close", |_, this, ()| {
+            this.close();
+            Ok(())
+        });
+        methods.add_method("is_closed", |_, this, ()| Ok(this.is_closed()));
+        methods.add_method("len", |_, this, ()| Ok(this.len()));
+    }
+}
+
+pub(crate) fn create_sync_module(lua: &Lua) -> Result<Table> {
+    let module = lua.create_table()?;
+    module.raw_set("Mutex", lua.create_function(|_, ()| Ok(AsyncMutex::new()))?)?;
+    let semaphore = lua.create_function(|_, permits: usize| Ok(AsyncSemaphore::new(permits)))?;
+    module.raw_set("Semaphore", semaphore)?;
+    let channel = lua.create_function(|lua, capacity: Option<usize>| {
+        if capacity == Some(0) {
+            return Err(Error::runtime("channel capacity must be greater than zero"));
+        }
+        Ok(AsyncChannel::new(lua, capacity))
+    })?;
+    module.raw_set("Channel", channel)?;
+    Ok(module)
+}
@@ -1,8 +1,9 @@
+use std::cmp::Ordering;
 use std::collections::HashSet;
-use std::fmt;
 use std::marker::PhantomData;
 use std::os::raw::{c_int, c_void};
 use std::string::String as StdString;
+use std::{fmt, vec};
 
 #[cfg(feature = "serialize")]
 use {
@@ -13,6 +14,9 @@ use {
 
 use crate::error::{Error, Result};
 use crate::function::Function;
+use crate::json::JsonOptions;
+use crate::merge::MergeStrategy;
+use crate::path::PathOptions;
 use crate::state::{LuaGuard, RawLua};
 use crate::traits::ObjectLike;
 use crate::types::{Integer, LuaType, ValueRef};
@@ -624,10 +628,41 @@ impl Table {
     /// [`Result`]: crate::Result
     /// [Lua manual]: http://www.lua.org/manual/5.4/manual.html#pdf-next
     pub fn pairs<K: FromLua, V: FromLua>(&self) -> TablePairs<K, V> {
+        let guard = self.0.lua.lock();
+        let sorted = guard.deterministic_pairs();
+        TablePairs {
+            guard,
+            table: self,
+            key: Some(Nil),
+            sorted,
+            sorted_keys: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the pairs of the table in a deterministic order.
+    ///
+    /// Keys are sorted by type: booleans, numbers (in ascending order), strings (in lexicographic
+    /// byte order) and then other keys. The order of boolean, number and string keys does not
+    /// depend on the hash seed, so it can be used for reproducible outputs and content hashing.
+    ///
+    /// Other keys (tables, functions, userdata, etc.) are ordered by their address, which is
+    /// **not** deterministic and can differ between runs. Tables with such keys do not have
+    /// a reproducible order.
+    ///
+    /// All keys are collected upfront when the iteration starts. Keys removed from the table during
+    /// iteration are skipped.
+    ///
+    /// See also [`Lua::set_deterministic_pairs`] to make this order default for [`Table::pairs`].
+    ///
+    /// [`Lua::set_deterministic_pairs`]: crate::Lua::set_deterministic_pairs
+    pub fn sorted_pairs<K: FromLua, V: FromLua>(&self) -> TablePairs<'_, K, V> {
         TablePairs {
             guard: self.0.lua.lock(),
             table: self,
             key: Some(Nil),
+            sorted: true,
+            sorted_keys: None,
             _phantom: PhantomData,
         }
     }
@@ -660,6 +695,136 @@ impl Table {
         Ok(())
     }
 
+    /// Retains only the pairs specified by the predicate, without invoking metamethods.
+    ///
+    /// Removes all pairs `(k, v)` for which `f(k, v)` returns `false`. The table is modified in
+    /// place in a single traversal. The predicate must not add new keys to the table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table, Value};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let t: Table = lua.load("{ a = 1, b = 2, c = 3, 4 }").eval()?;
+    /// t.retain(|_: Value, v: i64| Ok(v % 2 == 0))?;
+    /// assert_eq!(t.get::<Option<i64>>("a")?, None);
+    /// assert_eq!(t.get::<i64>("b")?, 2);
+    /// assert_eq!(t.get::<i64>(1)?, 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn retain<K, V>(&self, mut f: impl FnMut(K, V) -> Result<bool>) -> Result<()>
+    where
+        K: FromLua,
+        V: FromLua,
+    {
+        #[cfg(feature = "luau")]
+        self.check_readonly_write()?;
+
+        let lua = self.0.lua.lock();
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 5)?;
+
+            lua.push_ref(&self.0);
+            ffi::lua_pushnil(state);
+            while ffi::lua_next(state, -2) != 0 {
+                let k = K::from_stack(-2, &lua)?;
+                let v = V::from_stack(-1, &lua)?;
+                ffi::lua_pop(state, 1);
+                if !f(k, v)? {
+                    // Clearing existing fields during traversal is allowed
+                    ffi::lua_pushvalue(state, -1);
+                    ffi::lua_pushnil(state);
+                    ffi::lua_rawset(state, -4);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sorts the sequence part of the table in place, like Lua's `table.sort`.
+    ///
+    /// Elements `t[1]` to `t[n]` (where `n` is the raw length of the table) are sorted using the
+    /// given `comparator` function, which must return `true` when its first argument should come
+    /// before the second. If `comparator` is `None`, the Lua `<` operator is used instead (it might
+    /// invoke the `__lt` metamethod).
+    ///
+    /// Unlike `table.sort`, the sort is stable and does not fail or loop forever if the comparator
+    /// is inconsistent. Elements are read and written without invoking metamethods.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Function, Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let t: Table = lua.load("{ 3, 1, 2 }").eval()?;
+    /// t.sort(None)?;
+    /// assert_eq!(t.sequence_values().collect::<Result<Vec<i64>>>()?, [1, 2, 3]);
+    ///
+    /// let desc: Function = lua.load("function(a, b) return a > b end").eval()?;
+    /// t.sort(Some(desc))?;
+    /// assert_eq!(t.sequence_values().collect::<Result<Vec<i64>>>()?, [3, 2, 1]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort(&self, comparator: Option<Function>) -> Result<()> {
+        #[cfg(feature = "luau")]
+        self.check_readonly_write()?;
+
+        let values = self.read_sequence(|lua| unsafe { Value::from_stack(-1, lua) })?;
+        let order = match comparator {
+            Some(f) => try_merge_sort(values.len(), |a, b| f.call((&values[a], &values[b]))),
+            None => {
+                let lua = self.0.lua.lock();
+                try_merge_sort(values.len(), |a, b| unsafe {
+                    less_than(&lua, &values[a], &values[b])
+                })
+            }
+        }?;
+
+        let mut values = values.into_iter().map(Some).collect::<Vec<_>>();
+        self.write_sequence(order.into_iter().map(|i| values[i].take().unwrap()))
+    }
+
+    /// Sorts the sequence part of the table in place using a Rust comparison function.
+    ///
+    /// Each element `t[1]` to `t[n]` (where `n` is the raw length of the table) is converted to `K`
+    /// exactly once, then the elements are sorted by their keys entirely in Rust without calling
+    /// back into Lua. This is much faster than [`Table::sort`] with a Lua comparator for large
+    /// tables.
+    ///
+    /// The sort is stable. Elements are read and written without invoking metamethods.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let t: Table = lua.load(r#"{ "banana", "Apple", "cherry" }"#).eval()?;
+    /// t.sort_by_rust(|a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase()))?;
+    /// assert_eq!(t.sequence_values().collect::<Result<Vec<String>>>()?, ["Apple", "banana", "cherry"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort_by_rust<K, F>(&self, mut compare: F) -> Result<()>
+    where
+        K: FromLua,
+        F: FnMut(&K, &K) -> Ordering,
+    {
+        #[cfg(feature = "luau")]
+        self.check_readonly_write()?;
+
+        let mut entries =
+            self.read_sequence(|lua| unsafe { Ok((K::from_stack(-1, lua)?, Value::from_stack(-1, lua)?)) })?;
+        entries.sort_by(|(a, _), (b, _)| compare(a, b));
+        self.write_sequence(entries.into_iter().map(|(_, value)| value))
+    }
+
     /// Returns an iterator over all values in the sequence part of the table.
     ///
     /// The iterator will yield all values `t[1]`, `t[2]` and so on, until a `nil` value is
@@ -701,6 +866,119 @@ impl Table {
         }
     }
 
+    /// Encodes the table to a compact JSON string using the built-in encoder.
+    ///
+    /// Tables with only a sequence part are encoded as JSON arrays, other tables as JSON objects.
+    /// [`Value::NULL`] is encoded as `null`. Recursive tables are rejected. Metamethods are not
+    /// invoked.
+    ///
+    /// This method does not require `serde` and is usually faster than going through an
+    /// intermediate `serde_json::Value`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{JsonOptions, Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let t: Table = lua.load(r#"{ a = 1, b = { 1.5, "x" } }"#).eval()?;
+    /// let json = t.to_json_string(JsonOptions::new().sort_keys(true))?;
+    /// assert_eq!(json, r#"{"a":1,"b":[1.5,"x"]}"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Value::NULL`]: crate::Value::NULL
+    pub fn to_json_string(&self, options: JsonOptions) -> Result<StdString> {
+        crate::json::encode_table(self, options)
+    }
+
+    /// Gets the value at a dotted `path`, e.g. `a.b[3].c`.
+    ///
+    /// Path segments are separated by dots, integer indices and keys that are not valid
+    /// identifiers can be written in brackets, e.g. `items[1]` or `a["b.c"]`.
+    ///
+    /// If an intermediate value is nil, the result is converted from nil, so `Option<V>` can be
+    /// used to read optional values. Indexing a non-table value is an error.
+    ///
+    /// This might invoke the `__index` metamethod. See [`get_path_with`] to change the behavior.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let config = lua.create_table()?;
+    /// config.set_path("server.ports[1]", 8080)?;
+    /// assert_eq!(config.get_path::<u16>("server.ports[1]")?, 8080);
+    /// assert_eq!(config.get_path::<Option<String>>("server.host")?, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`get_path_with`]: #method.get_path_with
+    pub fn get_path<V: FromLua>(&self, path: &str) -> Result<V> {
+        crate::path::get_path(self, path, PathOptions::new())
+    }
+
+    /// Gets the value at a dotted `path` using the provided [`PathOptions`].
+    ///
+    /// See [`get_path`] for the path syntax.
+    ///
+    /// [`get_path`]: #method.get_path
+    pub fn get_path_with<V: FromLua>(&self, path: &str, options: PathOptions) -> Result<V> {
+        crate::path::get_path(self, path, options)
+    }
+
+    /// Sets the value at a dotted `path`, e.g. `a.b[3].c`.
+    ///
+    /// Missing intermediate tables are created. Indexing a non-table value is an error.
+    ///
+    /// This might invoke the `__index` and `__newindex` metamethods. See [`set_path_with`] to
+    /// change the behavior.
+    ///
+    /// [`set_path_with`]: #method.set_path_with
+    pub fn set_path(&self, path: &str, value: impl IntoLua) -> Result<()> {
+        crate::path::set_path(self, path, value, PathOptions::new())
+    }
+
+    /// Sets the value at a dotted `path` using the provided [`PathOptions`].
+    ///
+    /// See [`get_path`] for the path syntax.
+    ///
+    /// [`get_path`]: #method.get_path
+    pub fn set_path_with(&self, path: &str, value: impl IntoLua, options: PathOptions) -> Result<()> {
+        crate::path::set_path(self, path, value, options)
+    }
+
+    /// Deep merges the `other` table into this table using the provided [`MergeStrategy`].
+    ///
+    /// Nested tables present in both tables are merged recursively, other conflicting values are
+    /// resolved by the strategy. Tables added from `other` are copied (keeping their metatables),
+    /// so this table never shares nested tables with `other`. Cyclic references are handled.
+    ///
+    /// Tables are accessed without invoking metamethods, existing metatables are not changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, MergeStrategy, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let config: Table = lua.load("{ server = { host = 'localhost', port = 80 }, tags = {'a'} }").eval()?;
+    /// let user: Table = lua.load("{ server = { port = 8080 }, tags = {'b'} }").eval()?;
+    /// config.merge(&user, MergeStrategy::ConcatArrays)?;
+    /// assert_eq!(config.get_path::<String>("server.host")?, "localhost");
+    /// assert_eq!(config.get_path::<u16>("server.port")?, 8080);
+    /// assert_eq!(config.get::<Vec<String>>("tags")?, ["a", "b"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge(&self, other: &Table, strategy: MergeStrategy) -> Result<()> {
+        crate::merge::merge(self, other, &strategy)
+    }
+
     #[cfg(feature = "serialize")]
     pub(crate) fn for_each_value<V>(&self, mut f: impl FnMut(V) -> Result<()>) -> Result<()>
     where
@@ -723,6 +1001,50 @@ impl Table {
         Ok(())
     }
 
+    // Reads elements `1..=raw_len` without invoking metamethods.
+    // The closure is called with each element on top of the stack.
+    fn read_sequence<T>(&self, mut f: impl FnMut(&RawLua) -> Result<T>) -> Result<Vec<T>> {
+        let lua = self.0.lua.lock();
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 4)?;
+
+            lua.push_ref(&self.0);
+            let len = ffi::lua_rawlen(state, -1);
+            let mut items = Vec::with_capacity(len);
+            for i in 1..=len {
+                ffi::lua_rawgeti(state, -1, i as _);
+                items.push(f(&lua)?);
+                ffi::lua_pop(state, 1);
+            }
+            Ok(items)
+        }
+    }
+
+    // Writes values to positions starting from 1 without invoking metamethods
+    fn write_sequence(&self, values: impl IntoIterator<Item = Value>) -> Result<()> {
+        let lua = self.0.lua.lock();
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 5)?;
+
+            lua.push_ref(&self.0);
+            for (i, value) in (1..).zip(values) {
+                ffi::lua_pushvalue(state, -1);
+                lua.push_value(&value)?;
+                if lua.unlikely_memory_error() {
+                    ffi::lua_rawseti(state, -2, i);
+                    ffi::lua_pop(state, 1);
+                } else {
+                    protect_lua!(state, 2, 0, |state| ffi::lua_rawseti(state, -2, i))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Sets element value at position `idx` without invoking metamethods.
     #[doc(hidden)]
     pub fn raw_seti(&self, idx: usize, value: impl IntoLua) -> Result<()> {
@@ -801,6 +1123,60 @@ impl Table {
     }
 }
 
+// Evaluates `a < b` using Lua semantics (might invoke the `__lt` metamethod)
+unsafe fn less_than(lua: &RawLua, a: &Value, b: &Value) -> Result<bool> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => return Ok(a < b),
+        (Value::Number(a), Value::Number(b)) => return Ok(a < b),
+        _ => {}
+    }
+
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 4)?;
+
+    lua.push_value(a)?;
+    lua.push_value(b)?;
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    let res = protect_lua!(state, 2, 0, |state| ffi::lua_compare(
+        state,
+        -2,
+        -1,
+        ffi::LUA_OPLT
+    ))?;
+    #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
+    let res = protect_lua!(state, 2, 0, |state| ffi::lua_lessthan(state, -2, -1))?;
+    Ok(res != 0)
+}
+
+// Stable bottom-up merge sort over element indices.
+// Unlike `slice::sort_by`, it accepts a fallible and possibly inconsistent comparator.
+fn try_merge_sort(len: usize, mut less: impl FnMut(usize, usize) -> Result<bool>) -> Result<Vec<usize>> {
+    let mut src = (0..len).collect::<Vec<_>>();
+    let mut dst = vec![0; len];
+    let mut width = 1;
+    while width < len {
+        for start in (0..len).step_by(2 * width) {
+            let mid = (start + width).min(len);
+            let end = (start + 2 * width).min(len);
+            let (mut i, mut j) = (start, mid);
+            for slot in &mut dst[start..end] {
+                // Take from the right run only if strictly less to keep the sort stable
+                if j < end && (i == mid || less(src[j], src[i])?) {
+                    *slot = src[j];
+                    j += 1;
+                } else {
+                    *slot = src[i];
+                    i += 1;
+                }
+            }
+        }
+        std::mem::swap(&mut src, &mut dst);
+        width *= 2;
+    }
+    Ok(src)
+}
+
 impl fmt::Debug for Table {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         if fmt.alternate() {
@@ -879,6 +1255,20 @@ impl ObjectLike for Table {
         self.get(key)
     }
 
+    fn get_multi<R: FromLuaMulti>(&self, key: impl IntoLua) -> Result<R> {
+        let lua = self.0.lua.upgrade();
+        let key = key.into_lua(&lua)?;
+        // `__index` is consulted only for missing keys
+        if self.raw_get::<Value>(&key)?.is_nil() {
+            if let Some(Value::Function(index)) =
+                self.metatable().map(|mt| mt.raw_get("__index")).transpose()?
+            {
+                return index.call((self, key));
+            }
+        }
+        R::from_lua_multi(self.get::<Value>(key)?.into_lua_multi(&lua)?, &lua)
+    }
+
     #[inline]
     fn set(&self, key: impl IntoLua, value: impl IntoLua) -> Result<()> {
         self.set(key, value)
@@ -993,7 +1383,7 @@ impl<'a> Serialize for SerializableTable<'a> {
     where
         S: Serializer,
     {
-        use crate::serde::de::{check_value_for_skip, MapPairs, RecursionGuard};
+        use crate::serde::de::{check_value_for_skip, MapPairs, RecursionGuard, MAX_SERIALIZE_DEPTH};
         use crate::value::SerializableValue;
 
         let convert_result = |res: Result<()>, serialize_err: Option<S::Error>| match res {
@@ -1005,6 +1395,10 @@ impl<'a> Serialize for SerializableTable<'a> {
 
         let options = self.options;
         let visited = &self.visited;
+        // `visited` holds the tables being serialized, so its size is the current nesting depth
+        if visited.borrow().len() >= MAX_SERIALIZE_DEPTH {
+            return Err(serde::ser::Error::custom("recursion limit exceeded"));
+        }
         let _guard = RecursionGuard::new(self.table, visited);
 
         // Array
@@ -1076,9 +1470,45 @@ pub struct TablePairs<'a, K, V> {
     guard: LuaGuard,
     table: &'a Table,
     key: Option<Value>,
+    // Iterate in a deterministic order, using the keys collected on the first iteration
+    sorted: bool,
+    sorted_keys: Option<vec::IntoIter<Value>>,
     _phantom: PhantomData<(K, V)>,
 }
 
+impl<K, V> TablePairs<'_, K, V>
+where
+    K: FromLua,
+    V: FromLua,
+{
+    fn next_sorted(&mut self) -> Option<Result<(K, V)>> {
+        if self.sorted_keys.is_none() {
+            let mut keys = Vec::new();
+            if let Err(err) = (self.table).for_each::<Value, Value>(|key, _| {
+                keys.push(key);
+                Ok(())
+            }) {
+                self.sorted = false;
+                self.key = None;
+                return Some(Err(err));
+            }
+            keys.sort_by(|a, b| a.sort_cmp(b));
+            self.sorted_keys = Some(keys.into_iter());
+        }
+
+        let lua = self.guard.lua();
+        for key in self.sorted_keys.as_mut()? {
+            let value = match self.table.raw_get::<Value>(&key) {
+                Ok(Value::Nil) => continue, // The key was removed
+                Ok(value) => value,
+                Err(err) => return Some(Err(err)),
+            };
+            return Some(K::from_lua(key, lua).and_then(|k| Ok((k, V::from_lua(value, lua)?))));
+        }
+        None
+    }
+}
+
 impl<'a, K, V> Iterator for TablePairs<'a, K, V>
 where
     K: FromLua,
@@ -1087,6 +1517,10 @@ where
     type Item = Result<(K, V)>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.sorted {
+            return self.next_sorted();
+        }
+
         if let Some(prev_key) = self.key.take() {
             let lua: &RawLua = &self.guard;
             let state = lua.state();
@@ -0,0 +1,597 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::types::{Number, XRc};
+use crate::userdata::{UserData, UserDataFields, UserDataMethods};
+use crate::value::{MultiValue, Value};
+
+/// Outcome of a single Lua test.
+///
+/// Requires `feature = "testing"`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestOutcome {
+    /// The test passed.
+    Passed,
+    /// The test failed with the given message.
+    Failed(StdString),
+    /// The test was declared with `pending` and not run.
+    Skipped,
+}
+
+/// Result of a single Lua test, see [`TestReport`].
+///
+/// Requires `feature = "testing"`
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TestResult {
+    /// Full test name, including the names of the enclosing `describe` blocks.
+    pub name: StdString,
+    /// Path to the file containing the test (if run from a file).
+    pub file: Option<PathBuf>,
+    /// Test outcome.
+    pub outcome: TestOutcome,
+    /// Time spent running the test (including `before_each`/`after_each` hooks).
+    pub duration: Duration,
+}
+
+/// Report produced by [`Lua::run_lua_tests`].
+///
+/// The report implements [`Display`] to print a human readable summary.
+///
+/// Requires `feature = "testing"`
+///
+/// [`Lua::run_lua_tests`]: crate::Lua::run_lua_tests
+/// [`Display`]: std::fmt::Display
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct TestReport {
+    /// Results of all tests in the order they were run.
+    ///
+    /// Files that failed to load are reported as failed tests with the file name.
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    /// Returns an iterator over the passed tests.
+    pub fn passed(&self) -> impl Iterator<Item = &TestResult> {
+        (self.results.iter()).filter(|r| r.outcome == TestOutcome::Passed)
+    }
+
+    /// Returns an iterator over the failed tests.
+    pub fn failed(&self) -> impl Iterator<Item = &TestResult> {
+        (self.results.iter()).filter(|r| matches!(r.outcome, TestOutcome::Failed(_)))
+    }
+
+    /// Returns an iterator over the skipped tests.
+    pub fn skipped(&self) -> impl Iterator<Item = &TestResult> {
+        (self.results.iter()).filter(|r| r.outcome == TestOutcome::Skipped)
+    }
+
+    /// Returns `true` if no test failed.
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for result in self.failed() {
+            if let Some(file) = &result.file {
+                write!(fmt, "{}: ", file.display())?;
+            }
+            if let TestOutcome::Failed(msg) = &result.outcome {
+                writeln!(fmt, "{} FAILED\n  {}", result.name, msg.replace('\n', "\n  "))?;
+            }
+        }
+        write!(
+            fmt,
+            "{} passed, {} failed, {} skipped",
+            self.passed().count(),
+            self.failed().count(),
+            self.skipped().count()
+        )
+    }
+}
+
+// Tests collected by the `describe`/`it` functions
+#[derive(Default)]
+struct Suite {
+    blocks: Vec<Block>,
+    tests: Vec<PendingTest>,
+}
+
+// A `describe` block
+#[derive(Default)]
+struct Block {
+    name: StdString,
+    before_each: Vec<Function>,
+    after_each: Vec<Function>,
+}
+
+struct PendingTest {
+    name: StdString,
+    // `None` for pending tests
+    func: Option<Function>,
+    before_each: Vec<Function>,
+    after_each: Vec<Function>,
+}
+
+impl Suite {
+    fn full_name(&self, name: &str) -> StdString {
+        let mut names: Vec<&str> = (self.blocks.iter().skip(1)).map(|b| b.name.as_str()).collect();
+        names.push(name);
+        names.join(" ")
+    }
+
+    fn add_test(&mut self, name: &str, func: Option<Function>) {
+        let before_each = (self.blocks.iter()).flat_map(|b| b.before_each.clone()).collect();
+        let after_each = (self.blocks.iter().rev())
+            .flat_map(|b| b.after_each.clone())
+            .collect();
+        self.tests.push(PendingTest {
+            name: self.full_name(name),
+            func,
+            before_each,
+            after_each,
+        });
+    }
+}
+
+type SharedSuite = XRc<Mutex<Suite>>;
+
+fn new_suite() -> SharedSuite {
+    // The root block holds top level hooks
+    XRc::new(Mutex::new(Suite {
+        blocks: vec![Block::default()],
+        tests: Vec::new(),
+    }))
+}
+
+fn create_module(lua: &Lua, suite: &SharedSuite) -> Result<Table> {
+    let module = lua.create_table()?;
+
+    let describe = {
+        let suite = suite.clone();
+        lua.create_function(move |_, (name, func): (StdString, Function)| {
+            (suite.lock().blocks).push(Block {
+                name,
+                ..Default::default()
+            });
+            let res = func.call::<()>(());
+            suite.lock().blocks.pop();
+            res
+        })?
+    };
+    module.raw_set("describe", describe)?;
+
+    let it = {
+        let suite = suite.clone();
+        lua.create_function(move |_, (name, func): (StdString, Function)| {
+            suite.lock().add_test(&name, Some(func));
+            Ok(())
+        })?
+    };
+    module.raw_set("it", it)?;
+
+    let pending = {
+        let suite = suite.clone();
+        lua.create_function(move |_, (name, _): (StdString, Option<Function>)| {
+            suite.lock().add_test(&name, None);
+            Ok(())
+        })?
+    };
+    module.raw_set("pending", pending)?;
+
+    let before_each = {
+        let suite = suite.clone();
+        lua.create_function(move |_, func: Function| {
+            let mut suite = suite.lock();
+            suite.blocks.last_mut().unwrap().before_each.push(func);
+            Ok(())
+        })?
+    };
+    module.raw_set("before_each", before_each)?;
+
+    let after_each = {
+        let suite = suite.clone();
+        lua.create_function(move |_, func: Function| {
+            let mut suite = suite.lock();
+            suite.blocks.last_mut().unwrap().after_each.push(func);
+            Ok(())
+        })?
+    };
+    module.raw_set("after_each", after_each)?;
+
+    module.raw_set("expect", lua.create_function(|_, value| Ok(Expect::new(value)))?)?;
+
+    let run = {
+        let suite = suite.clone();
+        lua.create_function(move |lua, ()| {
+            let tests = mem::take(&mut suite.lock().tests);
+            let results = tests.into_iter().map(|test| run_test(test, None)).collect();
+            report_to_table(lua, &TestReport { results })
+        })?
+    };
+    module.raw_set("run", run)?;
+
+    Ok(module)
+}
+
+fn report_to_table(lua: &Lua, report: &TestReport) -> Result<Table> {
+    let failures = lua.create_table()?;
+    for result in report.failed() {
+        if let TestOutcome::Failed(msg) = &result.outcome {
+            let failure = lua.create_table()?;
+            failure.raw_set("name", &*result.name)?;
+            failure.raw_set("message", &**msg)?;
+            failures.raw_push(failure)?;
+        }
+    }
+    let table = lua.create_table()?;
+    table.raw_set("passed", report.passed().count())?;
+    table.raw_set("failed", report.failed().count())?;
+    table.raw_set("skipped", report.skipped().count())?;
+    table.raw_set("failures", failures)?;
+    Ok(table)
+}
+
+// Returns the original error message, without the traceback
+fn failure_message(err: &Error) -> StdString {
+    match err {
+        Error::CallbackError { cause, .. } => failure_message(cause),
+        Error::RuntimeError(msg) => msg.clone(),
+        err => err.to_string(),
+    }
+}
+
+fn finish_test(
+    name: StdString,
+    file: Option<PathBuf>,
+    start: Instant,
+    res: Result<()>,
+    after_res: Result<()>,
+) -> TestResult {
+    let outcome = match res.and(after_res) {
+        Ok(()) => TestOutcome::Passed,
+        Err(err) => TestOutcome::Failed(failure_message(&err)),
+    };
+    TestResult {
+        name,
+        file,
+        outcome,
+        duration: start.elapsed(),
+    }
+}
+
+fn skipped_test(name: StdString, file: Option<PathBuf>) -> TestResult {
+    TestResult {
+        name,
+        file,
+        outcome: TestOutcome::Skipped,
+        duration: Duration::ZERO,
+    }
+}
+
+fn run_test(test: PendingTest, file: Option<PathBuf>) -> TestResult {
+    let Some(func) = test.func else {
+        return skipped_test(test.name, file);
+    };
+    let start = Instant::now();
+    let res = (test.before_each.iter())
+        .try_for_each(|hook| hook.call::<()>(()))
+        .and_then(|_| func.call::<()>(()));
+    let mut after_res = Ok(());
+    for hook in &test.after_each {
+        after_res = after_res.and(hook.call::<()>(()));
+    }
+    finish_test(test.name, file, start, res, after_res)
+}
+
+#[cfg(feature = "async")]
+async fn run_test_async(test: PendingTest, file: Option<PathBuf>) -> TestResult {
+    let Some(func) = test.func else {
+        return skipped_test(test.name, file);
+    };
+    let start = Instant::now();
+    let mut res = Ok(());
+    for hook in &test.before_each {
+        res = res.and(hook.call_async::<()>(()).await);
+        if res.is_err() {
+            break;
+        }
+    }
+    if res.is_ok() {
+        res = func.call_async::<()>(()).await;
+    }
+    let mut after_res = Ok(());
+    for hook in &test.after_each {
+        after_res = after_res.and(hook.call_async::<()>(()).await);
+    }
+    finish_test(test.name, file, start, res, after_res)
+}
+
+// Returns the test files (`*_spec.lua` or `*_spec.luau`) in the directory, sorted by path
+fn find_test_files(path: &Path) -> Result<Vec<PathBuf>> {
+    fn visit(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                visit(&path, files)?;
+            } else if (path.file_name().and_then(|name| name.to_str()))
+                .is_some_and(|name| name.ends_with("_spec.lua") || name.ends_with("_spec.luau"))
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    visit(path, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+// Loads the test file in a new environment with the test module functions and collects its tests.
+//
+// Returns a failed result if the file cannot be loaded.
+fn collect_tests(lua: &Lua, file: &Path) -> Result<StdResult<Vec<PendingTest>, TestResult>> {
+    let suite = new_suite();
+    let env = lua.create_table()?;
+    for pair in create_module(lua, &suite)?.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        env.raw_set(key, value)?;
+    }
+    let globals = lua.globals();
+    env.set_metatable(Some(lua.create_table_from([("__index", &globals)])?));
+
+    let start = Instant::now();
+    let res = lua.load(file).set_environment(env).exec();
+    match res {
+        Ok(()) => Ok(Ok(mem::take(&mut suite.lock().tests))),
+        Err(err) => {
+            let name = file.display().to_string();
+            Ok(Err(finish_test(name, Some(file.into()), start, Err(err), Ok(()))))
+        }
+    }
+}
+
+pub(crate) fn run_lua_tests(lua: &Lua, path: &Path) -> Result<TestReport> {
+    let mut report = TestReport::default();
+    for file in find_test_files(path)? {
+        match collect_tests(lua, &file)? {
+            Ok(tests) => {
+                let results = tests.into_iter().map(|test| run_test(test, Some(file.clone())));
+                report.results.extend(results);
+            }
+            Err(result) => report.results.push(result),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(feature = "async")]
+pub(crate) async fn run_lua_tests_async(lua: &Lua, path: &Path) -> Result<TestReport> {
+    let mut report = TestReport::default();
+    for file in find_test_files(path)? {
+        match collect_tests(lua, &file)? {
+            Ok(tests) => {
+                for test in tests {
+                    report
+                        .results
+                        .push(run_test_async(test, Some(file.clone())).await);
+                }
+            }
+            Err(result) => report.results.push(result),
+        }
+    }
+    Ok(report)
+}
+
+pub(crate) fn create_test_module(lua: &Lua) -> Result<Table> {
+    create_module(lua, &new_suite())
+}
+
+// Value wrapper returned by `expect`
+struct Expect {
+    value: Value,
+    negate: bool,
+}
+
+impl Expect {
+    fn new(value: Value) -> Self {
+        Expect { value, negate: false }
+    }
+
+    fn check(&self, pass: bool, what: impl fmt::Display) -> Result<()> {
+        if pass != self.negate {
+            return Ok(());
+        }
+        let not = if self.negate { "not " } else { "" };
+        Err(Error::runtime(format!("expected {:#?} {not}{what}", self.value)))
+    }
+
+    fn number(&self) -> Result<Number> {
+        match self.value {
+            Value::Integer(i) => Ok(i as Number),
+            Value::Number(n) => Ok(n),
+            _ => Err(Error::runtime(format!(
+                "expected a number, got {:#?}",
+                self.value
+            ))),
+        }
+    }
+}
+
+// Lua type name of the value (as returned by `type` function)
+fn lua_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Integer(_) => "number",
+        Value::LightUserData(_) => "userdata",
+        Value::Error(_) => "userdata",
+        value => value.type_name(),
+    }
+}
+
+// Compares values recursively, ignoring metatables
+fn deep_equals(a: &Value, b: &Value, visited: &mut HashSet<(usize, usize)>) -> Result<bool> {
+    let (Value::Table(ta), Value::Table(tb)) = (a, b) else {
+        return a.equals(b);
+    };
+    if ta == tb || !visited.insert((ta.to_pointer() as usize, tb.to_pointer() as usize)) {
+        return Ok(true);
+    }
+    let mut count = 0;
+    for pair in ta.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        if !deep_equals(&value, &tb.raw_get(key)?, visited)? {
+            return Ok(false);
+        }
+        count += 1;
+    }
+    Ok(tb.pairs::<Value, Value>().count() == count)
+}
+
+impl UserData for Expect {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("never", |_, this| {
+            Ok(Expect {
+                value: this.value.clone(),
+                negate: !this.negate,
+            })
+        });
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("to_be", |_, this, expected: Value| {
+            let pass = this.value.equals(&expected)?;
+            this.check(pass, format_args!("to be {expected:#?}"))
+        });
+
+        methods.add_method("to_equal", |_, this, expected: Value| {
+            let pass = deep_equals(&this.value, &expected, &mut HashSet::new())?;
+            this.check(pass, format_args!("to equal {expected:#?}"))
+        });
+
+        methods.add_method("to_be_nil", |_, this, ()| {
+            this.check(this.value.is_nil(), "to be nil")
+        });
+
+        methods.add_method("to_be_truthy", |_, this, ()| {
+            let pass = !matches!(this.value, Value::Nil | Value::Boolean(false));
+            this.check(pass, "to be truthy")
+        });
+
+        methods.add_method("to_be_falsy", |_, this, ()| {
+            let pass = matches!(this.value, Value::Nil | Value::Boolean(false));
+            this.check(pass, "to be falsy")
+        });
+
+        methods.add_method("to_be_a", |_, this, type_name: StdString| {
+            let pass = lua_type_name(&this.value) == type_name;
+            this.check(pass, format_args!("to be a {type_name}"))
+        });
+
+        methods.add_method(
+            "to_be_close_to",
+            |_, this, (expected, eps): (Number, Option<Number>)| {
+                let pass = (this.number()? - expected).abs() <= eps.unwrap_or(1e-9);
+                this.check(pass, format_args!("to be close to {expected}"))
+            },
+        );
+
+        methods.add_method("to_be_greater_than", |_, this, expected: Number| {
+            let pass = this.number()? > expected;
+            this.check(pass, format_args!("to be greater than {expected}"))
+        });
+
+        methods.add_method("to_be_less_than", |_, this, expected: Number| {
+            let pass = this.number()? < expected;
+            this.check(pass, format_args!("to be less than {expected}"))
+        });
+
+        methods.add_method("to_have_length", |_, this, expected: usize| {
+            let len = match &this.value {
+                Value::String(s) => s.as_bytes().len(),
+                Value::Table(t) => t.raw_len(),
+                value => {
+                    return Err(Error::runtime(format!(
+                        "expected a string or table, got {value:#?}"
+                    )))
+                }
+            };
+            this.check(
+                len == expected,
+                format_args!("to have length {expected} (got {len})"),
+            )
+        });
+
+        methods.add_method("to_contain", |_, this, expected: Value| {
+            let pass = match (&this.value, &expected) {
+                (Value::String(s), Value::String(sub)) => {
+                    let (s, sub) = (s.as_bytes(), sub.as_bytes());
+                    sub.is_empty() || s.windows(sub.len()).any(|w| w == &*sub)
+                }
+                (Value::Table(t), _) => {
+                    let mut found = false;
+                    for pair in t.pairs::<Value, Value>() {
+                        if pair?.1.equals(&expected)? {
+                            found = true;
+                            break;
+                        }
+                    }
+                    found
+                }
+                (value, _) => {
+                    return Err(Error::runtime(format!(
+                        "expected a string or table, got {value:#?}"
+                    )))
+                }
+            };
+            this.check(pass, format_args!("to contain {expected:#?}"))
+        });
+
+        methods.add_method("to_match", |lua, this, pattern: StdString| {
+            let string_match = lua.globals().get::<Table>("string")?.get::<Function>("match")?;
+            let pass = match &this.value {
+                Value::String(_) => !string_match.call::<Value>((&this.value, &*pattern))?.is_nil(),
+                _ => false,
+            };
+            this.check(pass, format_args!("to match {pattern:?}"))
+        });
+
+        methods.add_method("to_throw", |_, this, expected: Option<StdString>| {
+            let Value::Function(func) = &this.value else {
+                return Err(Error::runtime(format!(
+                    "expected a function, got {:#?}",
+                    this.value
+                )));
+            };
+            let (pass, what) = match (func.call::<MultiValue>(()), expected) {
+                (Ok(_), None) => (false, "to throw an error".to_string()),
+                (Ok(_), Some(msg)) => (false, format!("to throw an error containing {msg:?}")),
+                (Err(_), None) => (true, "to throw an error".to_string()),
+                (Err(err), Some(msg)) => {
+                    let err = failure_message(&err);
+                    let what = format!("to throw an error containing {msg:?} (got {err:?})");
+                    (err.contains(&msg), what)
+                }
+            };
+            this.check(pass, what)
+        });
+    }
+}
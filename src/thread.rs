@@ -4,18 +4,18 @@ use crate::error::{Error, Result};
 #[allow(unused)]
 use crate::state::Lua;
 use crate::state::RawLua;
-use crate::types::{LuaType, ValueRef, VmState};
-use crate::util::{check_stack, error_traceback_thread, pop_error, StackGuard};
+use crate::table::Table;
+use crate::types::{AppData, LuaType, MaybeSend, ValueRef, VmState};
+use crate::userdata::AnyUserData;
+use crate::util::{check_stack, error_traceback_thread, pop_error, stack_level_exists, StackGuard};
 use crate::value::{FromLuaMulti, IntoLuaMulti};
 
 #[cfg(not(feature = "luau"))]
-use crate::{
-    hook::{Debug, HookTriggers},
-    types::MaybeSend,
-};
+use crate::hook::{Debug, HookTriggers};
 
 #[cfg(feature = "async")]
 use {
+    crate::value::MultiValue,
     futures_util::stream::Stream,
     std::{
         future::Future,
@@ -33,7 +33,7 @@ pub enum ThreadStatus {
     ///
     /// If a thread is in this state, it can be resumed by calling [`Thread::resume`].
     Resumable,
-    /// The thread is currently running.
+    /// The thread is currently running or has resumed another thread.
     Running,
     /// The thread has finished executing.
     Finished,
@@ -62,6 +62,10 @@ unsafe impl Sync for Thread {}
 pub struct AsyncThread<A, R> {
     thread: Thread,
     init_args: Option<A>,
+    // Arguments for the next resume after `coroutine.yield`
+    next_args: Option<MultiValue>,
+    // The thread is waiting for an async function to complete
+    pending: bool,
     ret: PhantomData<R>,
     recycle: bool,
 }
@@ -150,7 +154,10 @@ impl Thread {
         }
 
         let mut nresults = 0;
+        #[cfg(not(feature = "luau"))]
         let ret = ffi::lua_resume(thread_state, state, nargs, &mut nresults as *mut c_int);
+        #[cfg(feature = "luau")]
+        let ret = lua.resume_thread(thread_state, nargs, &mut nresults);
         if ret != ffi::LUA_OK && ret != ffi::LUA_YIELD {
             if ret == ffi::LUA_ERRMEM {
                 // Don't call error handler for memory errors
@@ -179,6 +186,9 @@ impl Thread {
         let status = unsafe { ffi::lua_status(thread_state) };
         if status != ffi::LUA_OK && status != ffi::LUA_YIELD {
             ThreadStatus::Error
+        } else if status == ffi::LUA_OK && unsafe { stack_level_exists(thread_state, 0) } {
+            // The thread has resumed another coroutine and waits for it to finish ("normal")
+            ThreadStatus::Running
         } else if status == ffi::LUA_YIELD || unsafe { ffi::lua_gettop(thread_state) > 0 } {
             ThreadStatus::Resumable
         } else {
@@ -226,16 +236,7 @@ impl Thread {
 
         let thread_state = self.state();
         unsafe {
-            #[cfg(all(feature = "lua54", not(feature = "vendored")))]
-            let status = ffi::lua_resetthread(thread_state);
-            #[cfg(all(feature = "lua54", feature = "vendored"))]
-            let status = ffi::lua_closethread(thread_state, lua.state());
-            #[cfg(feature = "lua54")]
-            if status != ffi::LUA_OK {
-                return Err(pop_error(thread_state, status));
-            }
-            #[cfg(feature = "luau")]
-            ffi::lua_resetthread(thread_state);
+            self.close_inner(&lua)?;
 
             // Push function to the top of the thread stack
             ffi::lua_xpush(lua.ref_thread(), thread_state, func.0.index);
@@ -251,6 +252,44 @@ impl Thread {
         }
     }
 
+    /// Closes the thread, releasing its call stack.
+    ///
+    /// In Lua 5.4 all pending to-be-closed variables are closed. Returns an error in case of
+    /// either the original error that stopped the thread or errors in closing methods.
+    ///
+    /// The thread status becomes [`ThreadStatus::Finished`]. It can be started again using
+    /// [`Thread::reset`]. Returns an error if the thread is running or has resumed another
+    /// thread.
+    ///
+    /// Requires `feature = "lua54"` OR `feature = "luau"`.
+    #[cfg(any(feature = "lua54", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "luau"))))]
+    pub fn close(&self) -> Result<()> {
+        let lua = self.0.lua.lock();
+        if self.status_inner(&lua) == ThreadStatus::Running {
+            return Err(Error::runtime("cannot close a running thread"));
+        }
+        unsafe { self.close_inner(&lua) }
+    }
+
+    #[cfg(any(feature = "lua54", feature = "luau"))]
+    unsafe fn close_inner(&self, lua: &RawLua) -> Result<()> {
+        let thread_state = self.state();
+        #[cfg(all(feature = "lua54", not(feature = "vendored")))]
+        let status = ffi::lua_resetthread(thread_state);
+        #[cfg(all(feature = "lua54", feature = "vendored"))]
+        let status = ffi::lua_closethread(thread_state, lua.state());
+        #[cfg(feature = "lua54")]
+        if status != ffi::LUA_OK {
+            return Err(pop_error(thread_state, status));
+        }
+        #[cfg(feature = "luau")]
+        ffi::lua_resetthread(thread_state);
+        #[cfg(not(all(feature = "lua54", feature = "vendored")))]
+        let _ = lua;
+        Ok(())
+    }
+
     /// Converts Thread to an AsyncThread which implements [`Future`] and [`Stream`] traits.
     ///
     /// `args` are passed as arguments to the thread function for first call.
@@ -302,9 +341,13 @@ impl Thread {
     where
         R: FromLuaMulti,
     {
+        #[cfg(feature = "metrics")]
+        self.0.lua.lock().update_metrics(|m| m.active_threads += 1);
         AsyncThread {
             thread: self,
             init_args: Some(args),
+            next_args: None,
+            pending: false,
             ret: PhantomData,
             recycle: false,
         }
@@ -355,6 +398,77 @@ impl Thread {
         }
     }
 
+    /// Sets a thread-local value of type `T`, replacing the previous one.
+    ///
+    /// Thread-local values travel with the thread (coroutine) across yields and resumes and can be
+    /// read from Rust callbacks called by the thread using [`Lua::current_thread_local`].
+    /// Each thread has its own storage with at most one value per type, which is dropped when
+    /// the thread is garbage collected.
+    ///
+    /// Returns the previous value of type `T`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Thread};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// #[derive(Clone)]
+    /// struct RequestId(u64);
+    ///
+    /// let request_id = lua.create_function(|lua, ()| {
+    ///     Ok(lua.current_thread_local::<RequestId>()?.map(|id| id.0))
+    /// })?;
+    /// lua.globals().set("request_id", request_id)?;
+    ///
+    /// let thread: Thread = lua.load("coroutine.create(function() coroutine.yield(); return request_id() end)").eval()?;
+    /// thread.set_local(RequestId(42))?;
+    /// thread.resume::<()>(())?;
+    /// assert_eq!(thread.resume::<u64>(())?, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_local<T: MaybeSend + 'static>(&self, value: T) -> Result<Option<T>> {
+        let locals = self.locals(true)?.expect("thread locals must be created");
+        let data = locals.borrow::<AppData>()?;
+        Ok(data.insert(value))
+    }
+
+    /// Returns a copy of the thread-local value of type `T` set by [`Thread::set_local`].
+    pub fn get_local<T: Clone + 'static>(&self) -> Result<Option<T>> {
+        match self.locals(false)? {
+            Some(locals) => {
+                let data = locals.borrow::<AppData>()?;
+                let value = data.borrow::<T>(None).map(|value| value.clone());
+                Ok(value)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Removes the thread-local value of type `T` and returns it.
+    pub fn remove_local<T: 'static>(&self) -> Result<Option<T>> {
+        match self.locals(false)? {
+            Some(locals) => Ok(locals.borrow::<AppData>()?.remove()),
+            None => Ok(None),
+        }
+    }
+
+    // Returns userdata with the thread-local storage, creating it if requested
+    fn locals(&self, create: bool) -> Result<Option<AnyUserData>> {
+        let lua = self.0.lua.lock();
+        let locals_map = unsafe { thread_locals_map(&lua)? };
+        match locals_map.raw_get::<Option<AnyUserData>>(self)? {
+            Some(locals) => Ok(Some(locals)),
+            None if create => {
+                let locals = lua.lua().create_any_userdata(AppData::default())?;
+                locals_map.raw_set(self, &locals)?;
+                Ok(Some(locals))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Converts this thread to a generic C pointer.
     ///
     /// There is no way to convert the pointer back to its original value.
@@ -366,6 +480,31 @@ impl Thread {
     }
 }
 
+// Returns a table (with weak keys) that maps threads to their local storage.
+// Uses 3 stack spaces and calls checkstack.
+unsafe fn thread_locals_map(lua: &RawLua) -> Result<Table> {
+    let state = lua.state();
+    let _sg = StackGuard::new(state);
+    check_stack(state, 3)?;
+
+    let locals_key = &THREAD_LOCALS_REGISTRY_KEY as *const u8 as *const c_void;
+    if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, locals_key) != ffi::LUA_TTABLE {
+        ffi::lua_pop(state, 1);
+        protect_lua!(state, 0, 1, |state| {
+            ffi::lua_createtable(state, 0, 0);
+            ffi::lua_createtable(state, 0, 1);
+            ffi::lua_pushstring(state, cstr!("k"));
+            ffi::lua_setfield(state, -2, cstr!("__mode"));
+            ffi::lua_setmetatable(state, -2);
+            ffi::lua_pushvalue(state, -1);
+            ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, locals_key);
+        })?;
+    }
+    Ok(Table(lua.pop_ref()))
+}
+
+static THREAD_LOCALS_REGISTRY_KEY: u8 = 0;
+
 impl PartialEq for Thread {
     fn eq(&self, other: &Self) -> bool {
         self.0 == other.0
@@ -378,6 +517,44 @@ impl LuaType for Thread {
 
 #[cfg(feature = "async")]
 impl<A, R> AsyncThread<A, R> {
+    /// Sets arguments for the next resumption of the thread.
+    ///
+    /// When the thread is suspended by `coroutine.yield`, the arguments are returned by `yield`
+    /// on the Lua side, which allows request/response style communication with the thread.
+    /// By default (or if this method is not called before the next poll), no arguments are passed.
+    ///
+    /// Arguments are not consumed while the thread is waiting for an async function, they are
+    /// passed after the next `yield` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, Thread};
+    /// use futures_util::stream::TryStreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let thread: Thread = lua.load(r#"
+    ///     coroutine.create(function(request)
+    ///         while request do
+    ///             request = coroutine.yield(request * 2)
+    ///         end
+    ///     end)
+    /// "#).eval()?;
+    ///
+    /// let mut stream = thread.into_async::<i64>(1);
+    /// assert_eq!(stream.try_next().await?, Some(2));
+    /// stream.resume_with(21)?;
+    /// assert_eq!(stream.try_next().await?, Some(42));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume_with(&mut self, args: impl IntoLuaMulti) -> Result<()> {
+        let lua = self.thread.0.lua.upgrade();
+        self.next_args = Some(args.into_lua_multi(&lua)?);
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn set_recyclable(&mut self, recyclable: bool) {
         self.recycle = recyclable;
@@ -385,9 +562,30 @@ impl<A, R> AsyncThread<A, R> {
 }
 
 #[cfg(feature = "async")]
-#[cfg(any(feature = "lua54", feature = "luau"))]
+impl<A: IntoLuaMulti, R> AsyncThread<A, R> {
+    // Resumes the thread with the initial or next arguments, returns the number of results
+    unsafe fn resume_next(&mut self, lua: &RawLua) -> Result<c_int> {
+        let nresults = if let Some(args) = self.init_args.take() {
+            self.thread.resume_inner(lua, args)?
+        } else if let Some(args) = (!self.pending).then(|| self.next_args.take()).flatten() {
+            self.thread.resume_inner(lua, args)?
+        } else {
+            self.thread.resume_inner(lua, ())?
+        };
+        self.pending = nresults == 1 && is_poll_pending(self.thread.state());
+        Ok(nresults)
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg(any(feature = "lua54", feature = "luau", feature = "metrics"))]
 impl<A, R> Drop for AsyncThread<A, R> {
     fn drop(&mut self) {
+        #[cfg(feature = "metrics")]
+        if let Some(lua) = self.thread.0.lua.try_lock() {
+            lua.update_metrics(|m| m.active_threads = m.active_threads.saturating_sub(1));
+        }
+        #[cfg(any(feature = "lua54", feature = "luau"))]
         if self.recycle {
             if let Some(lua) = self.thread.0.lua.try_lock() {
                 unsafe {
@@ -426,13 +624,8 @@ impl<A: IntoLuaMulti, R: FromLuaMulti> Stream for AsyncThread<A, R> {
 
             // This is safe as we are not moving the whole struct
             let this = self.get_unchecked_mut();
-            let nresults = if let Some(args) = this.init_args.take() {
-                this.thread.resume_inner(&lua, args)?
-            } else {
-                this.thread.resume_inner(&lua, ())?
-            };
-
-            if nresults == 1 && is_poll_pending(thread_state) {
+            let nresults = this.resume_next(&lua)?;
+            if this.pending {
                 return Poll::Pending;
             }
 
@@ -464,13 +657,8 @@ impl<A: IntoLuaMulti, R: FromLuaMulti> Future for AsyncThread<A, R> {
 
             // This is safe as we are not moving the whole struct
             let this = self.get_unchecked_mut();
-            let nresults = if let Some(args) = this.init_args.take() {
-                this.thread.resume_inner(&lua, args)?
-            } else {
-                this.thread.resume_inner(&lua, ())?
-            };
-
-            if nresults == 1 && is_poll_pending(thread_state) {
+            let nresults = this.resume_next(&lua)?;
+            if this.pending {
                 return Poll::Pending;
             }
 
@@ -0,0 +1,142 @@
+use crate::error::Result;
+use crate::function::Function;
+use crate::state::Lua;
+use crate::table::Table;
+use crate::thread::Thread;
+use crate::value::Value;
+
+#[cfg(any(feature = "lua54", feature = "luau"))]
+use crate::thread::ThreadStatus;
+
+// Replacement of the `coroutine` library registering created threads in the group
+const COROUTINE_SHIM: &str = r#"
+local coroutine, threads, pairs, error = ...
+local create, resume = coroutine.create, coroutine.resume
+local shim = {}
+for k, v in pairs(coroutine) do
+    shim[k] = v
+end
+function shim.create(f)
+    local co = create(f)
+    threads[co] = true
+    return co
+end
+local function check(ok, ...)
+    if not ok then
+        error((...), 0)
+    end
+    return ...
+end
+function shim.wrap(f)
+    local co = shim.create(f)
+    return function(...)
+        return check(resume(co, ...))
+    end
+end
+return shim
+"#;
+
+/// A group of Lua threads (coroutines) supervised together.
+///
+/// Threads are added to the group explicitly using [`ThreadGroup::add`], or automatically when
+/// created by scripts using the `coroutine` library installed by [`ThreadGroup::install`]. This
+/// allows to enumerate the coroutines started by a plugin and to close them when it's unloaded.
+///
+/// The group does not keep the threads alive, collected threads are removed from it.
+///
+/// Created by [`Lua::create_thread_group`].
+///
+/// [`Lua::create_thread_group`]: crate::Lua::create_thread_group
+#[derive(Clone, Debug)]
+pub struct ThreadGroup {
+    // Weak table with threads as keys
+    threads: Table,
+}
+
+impl ThreadGroup {
+    pub(crate) fn new(lua: &Lua) -> Result<Self> {
+        let threads = lua.create_table()?;
+        let mt = lua.create_table()?;
+        mt.raw_set("__mode", "k")?;
+        threads.set_metatable(Some(mt));
+        Ok(ThreadGroup { threads })
+    }
+
+    /// Installs the `coroutine` library tracking created threads into the environment table.
+    ///
+    /// The library is a copy of the global `coroutine` table, where `coroutine.create` and
+    /// `coroutine.wrap` add the new threads to this group. Chunks running in the environment
+    /// (e.g. a plugin) use it instead of the global library.
+    pub fn install(&self, env: &Table) -> Result<()> {
+        let lua = self.threads.0.lua.lock().lua().clone();
+        let globals = lua.globals();
+        let coroutine = globals.raw_get::<Table>("coroutine")?;
+        let shim = lua
+            .load(COROUTINE_SHIM)
+            .set_name("=__mlua_coroutine_shim")
+            .call::<Table>((
+                coroutine,
+                &self.threads,
+                globals.raw_get::<Function>("pairs")?,
+                globals.raw_get::<Function>("error")?,
+            ))?;
+        env.raw_set("coroutine", shim)
+    }
+
+    /// Adds the thread to the group.
+    pub fn add(&self, thread: &Thread) -> Result<()> {
+        self.threads.raw_set(thread, true)
+    }
+
+    /// Returns `true` if the thread belongs to the group.
+    pub fn contains(&self, thread: &Thread) -> Result<bool> {
+        self.threads.raw_get(thread)
+    }
+
+    /// Returns the threads of the group that have not been garbage collected yet.
+    ///
+    /// The threads are returned in an unspecified order, including the finished ones. Use
+    /// [`Thread::status`] to query their status.
+    pub fn threads(&self) -> Result<Vec<Thread>> {
+        let mut threads = Vec::new();
+        self.threads.for_each(|key: Value, _: Value| {
+            if let Value::Thread(thread) = key {
+                threads.push(thread);
+            }
+            Ok(())
+        })?;
+        Ok(threads)
+    }
+
+    /// Closes all suspended (or failed) threads of the group and removes them from the group.
+    ///
+    /// In Lua 5.4 the pending to-be-closed variables of suspended threads are closed, the first
+    /// error raised by their closing methods is returned after closing all threads. Running
+    /// threads (including the ones waiting for a resumed coroutine) are left in the group intact.
+    ///
+    /// Returns the number of closed suspended threads.
+    ///
+    /// Requires `feature = "lua54"` OR `feature = "luau"`.
+    #[cfg(any(feature = "lua54", feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "lua54", feature = "luau"))))]
+    pub fn close_all(&self) -> Result<usize> {
+        let (mut closed, mut result) = (0, Ok(()));
+        for thread in self.threads()? {
+            match thread.status() {
+                ThreadStatus::Resumable => {
+                    closed += 1;
+                    if let Err(err) = thread.close() {
+                        result = result.and(Err(err));
+                    }
+                }
+                // The original error was already reported when the thread failed
+                ThreadStatus::Error => {
+                    let _ = thread.close();
+                }
+                ThreadStatus::Running | ThreadStatus::Finished => continue,
+            }
+            self.threads.raw_set(&thread, Value::Nil)?;
+        }
+        result.map(|_| closed)
+    }
+}
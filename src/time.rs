@@ -0,0 +1,187 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::types::MaybeSend;
+
+/// A future returned by [`TimeDriver::sleep_until`].
+#[cfg(feature = "send")]
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A future returned by [`TimeDriver::sleep_until`].
+#[cfg(not(feature = "send"))]
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+/// A source of time used by the async subsystem for deadlines and sleeps.
+///
+/// mlua does not depend on any particular async runtime, so the time driver must be provided by
+/// the application using [`Lua::set_time_driver`]. Built-in drivers are [`TokioTimeDriver`]
+/// (requires `feature = "tokio"`), [`AsyncStdTimeDriver`] (requires `feature = "async-std"`) and
+/// [`ManualClock`] for deterministic tests in simulated time.
+///
+/// Requires `feature = "async"`
+///
+/// [`Lua::set_time_driver`]: crate::Lua::set_time_driver
+pub trait TimeDriver: MaybeSend + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Returns a future that completes at the given `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> SleepFuture;
+}
+
+/// A [`TimeDriver`] based on the [tokio] runtime timers.
+///
+/// Requires `feature = "tokio"`
+///
+/// [tokio]: https://docs.rs/tokio
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioTimeDriver;
+
+#[cfg(feature = "tokio")]
+impl TimeDriver for TokioTimeDriver {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> SleepFuture {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
+
+/// A [`TimeDriver`] based on the [async-std] runtime timers.
+///
+/// Requires `feature = "async-std"`
+///
+/// [async-std]: https://docs.rs/async-std
+#[cfg(feature = "async-std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-std")))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsyncStdTimeDriver;
+
+#[cfg(feature = "async-std")]
+impl TimeDriver for AsyncStdTimeDriver {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> SleepFuture {
+        let duration = deadline.saturating_duration_since(Instant::now());
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+/// A [`TimeDriver`] with manually controlled time.
+///
+/// The clock does not move on its own, it must be advanced using [`ManualClock::advance`].
+/// Sleeping futures are woken up when the clock passes their deadline.
+///
+/// Cloned instances share the same time, so one instance can be passed to
+/// [`Lua::set_time_driver`] while another one is kept to drive the time.
+///
+/// Requires `feature = "async"`
+///
+/// [`Lua::set_time_driver`]: crate::Lua::set_time_driver
+#[derive(Clone, Debug)]
+pub struct ManualClock(Arc<Mutex<ManualClockState>>);
+
+#[derive(Debug)]
+struct ManualClockState {
+    now: Instant,
+    next_id: u64,
+    sleepers: Vec<(u64, Instant, Waker)>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Creates a new manual clock starting at the current (real) time.
+    pub fn new() -> Self {
+        ManualClock(Arc::new(Mutex::new(ManualClockState {
+            now: Instant::now(),
+            next_id: 0,
+            sleepers: Vec::new(),
+        })))
+    }
+
+    /// Returns the current clock time.
+    pub fn now(&self) -> Instant {
+        self.0.lock().now
+    }
+
+    /// Advances the clock by `duration`, waking up all sleepers whose deadline has passed.
+    pub fn advance(&self, duration: Duration) {
+        let wakers = {
+            let mut state = self.0.lock();
+            state.now += duration;
+            let now = state.now;
+            let (ready, pending) = state
+                .sleepers
+                .drain(..)
+                .partition(|(_, deadline, _)| *deadline <= now);
+            state.sleepers = pending;
+            ready
+        };
+        // Wake outside of the lock
+        for (_, _, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl TimeDriver for ManualClock {
+    fn now(&self) -> Instant {
+        ManualClock::now(self)
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> SleepFuture {
+        let id = {
+            let mut state = self.0.lock();
+            state.next_id += 1;
+            state.next_id
+        };
+        Box::pin(ManualSleep {
+            clock: self.clone(),
+            id,
+            deadline,
+        })
+    }
+}
+
+struct ManualSleep {
+    clock: ManualClock,
+    id: u64,
+    deadline: Instant,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let mut state = self.clock.0.lock();
+        if state.now >= self.deadline {
+            return Poll::Ready(());
+        }
+        match state.sleepers.iter_mut().find(|(id, ..)| *id == self.id) {
+            Some((_, _, waker)) => waker.clone_from(cx.waker()),
+            None => state.sleepers.push((self.id, self.deadline, cx.waker().clone())),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        self.clock.0.lock().sleepers.retain(|(id, ..)| *id != self.id);
+    }
+}
@@ -2,6 +2,9 @@ use std::string::String as StdString;
 
 use crate::error::Result;
 use crate::private::Sealed;
+use crate::state::Lua;
+use crate::string::String;
+use crate::table::Table;
 use crate::types::MaybeSend;
 use crate::util::short_type_name;
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti};
@@ -21,8 +24,18 @@ impl<T> ShortTypeName for T {}
 /// A trait for types that can be used as Lua objects (usually table and userdata).
 pub trait ObjectLike: Sealed {
     /// Gets the value associated to `key` from the object, assuming it has `__index` metamethod.
+    ///
+    /// As with the Lua indexing operator, only the first value returned by the `__index`
+    /// metamethod is used. See [`ObjectLike::get_multi`] to get all of them.
     fn get<V: FromLua>(&self, key: impl IntoLua) -> Result<V>;
 
+    /// Gets all values associated to `key` from the object.
+    ///
+    /// If the object has `__index` metamethod which is a function (and the key is not present in
+    /// the object itself), the metamethod is called directly and all returned values are
+    /// converted to `R`. Otherwise this method behaves like [`ObjectLike::get`].
+    fn get_multi<R: FromLuaMulti>(&self, key: impl IntoLua) -> Result<R>;
+
     /// Sets the value associated to `key` in the object, assuming it has `__newindex` metamethod.
     fn set(&self, key: impl IntoLua, value: impl IntoLua) -> Result<()>;
 
@@ -88,6 +101,41 @@ pub trait ObjectLike: Sealed {
     fn to_string(&self) -> Result<StdString>;
 }
 
+/// Trait for converting values to Lua strings using the `tostring` semantics.
+///
+/// Unlike formatting values in Rust, the conversion follows Lua rules: numbers are formatted as
+/// Lua formats them and the `__tostring` (and `__name`) metamethods of tables and userdata are
+/// respected. It is used by helper APIs such as [`Lua::concat`].
+///
+/// The trait can be implemented for custom types, e.g. to convert them to Lua values first.
+///
+/// [`Lua::concat`]: crate::Lua::concat
+pub trait ToLuaString {
+    /// Converts the value to a Lua string.
+    fn to_lua_string(&self, lua: &Lua) -> Result<String>;
+}
+
+impl<T: ToLuaString + ?Sized> ToLuaString for &T {
+    #[inline]
+    fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+        (**self).to_lua_string(lua)
+    }
+}
+
+/// A trait object type that can be implemented by a Lua table.
+///
+/// This trait is usually implemented using the [`lua_trait`] attribute macro, which generates a
+/// hidden Rust type forwarding every trait method to the Lua function with the same name.
+///
+/// See [`Lua::implement_trait`] for more details.
+///
+/// [`lua_trait`]: crate::lua_trait
+/// [`Lua::implement_trait`]: crate::Lua::implement_trait
+pub trait LuaTrait {
+    /// Creates an implementation of the trait backed by the given Lua table.
+    fn from_lua_table(table: Table) -> Result<Box<Self>>;
+}
+
 /// A trait for types that can be used as Lua functions.
 pub trait LuaNativeFn<A: FromLuaMulti> {
     type Output: IntoLuaMulti;
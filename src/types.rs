@@ -1,5 +1,7 @@
 use std::cell::UnsafeCell;
+use std::mem::{self, MaybeUninit};
 use std::os::raw::{c_int, c_void};
+use std::ptr::{self, NonNull};
 use std::rc::Rc;
 
 use crate::error::Result;
@@ -51,14 +53,180 @@ pub(crate) type Callback = Box<dyn Fn(&RawLua, c_int) -> Result<c_int> + Send +
 #[cfg(not(feature = "send"))]
 pub(crate) type Callback = Box<dyn Fn(&RawLua, c_int) -> Result<c_int> + 'static>;
 
+pub(crate) type CallbackFn = dyn Fn(&RawLua, c_int) -> Result<c_int>;
+
 pub(crate) type ScopedCallback<'s> = Box<dyn Fn(&RawLua, c_int) -> Result<c_int> + 's>;
 
+#[cfg(feature = "async")]
 pub(crate) struct Upvalue<T> {
     pub(crate) data: T,
     pub(crate) extra: XRc<UnsafeCell<ExtraData>>,
 }
 
-pub(crate) type CallbackUpvalue = Upvalue<Option<Callback>>;
+// Upvalue of Rust callbacks, holds the slot of the callback in the state `CallbackArena`.
+// The slot is freed when the upvalue is garbage collected.
+pub(crate) struct CallbackUpvalue {
+    pub(crate) slot: Option<usize>,
+    pub(crate) extra: XRc<UnsafeCell<ExtraData>>,
+}
+
+impl Drop for CallbackUpvalue {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            let callback = unsafe { (*self.extra.get()).callbacks.remove(slot) };
+            drop(callback);
+        }
+    }
+}
+
+// Number of callback slots allocated at once by `CallbackArena`
+const CALLBACK_CHUNK_SIZE: usize = 64;
+
+// Inline storage of a callback slot, closures that do not fit into it are boxed
+type CallbackData = MaybeUninit<[usize; 4]>;
+
+struct CallbackSlot {
+    data: CallbackData,
+    // Returns the callback stored in `data`, `None` if the slot is free
+    get: Option<unsafe fn(*const CallbackData) -> *const CallbackFn>,
+    drop: unsafe fn(*mut CallbackData),
+}
+
+// Storage for all callbacks of a Lua state.
+//
+// Callbacks are addressed by stable slot indices. Slots are allocated in chunks that are never
+// moved or freed before the arena, and freed slots are reused by new callbacks. Small closures are
+// stored inside the slots, so creating them does not allocate unless a new chunk is needed.
+#[derive(Default)]
+pub(crate) struct CallbackArena {
+    chunks: Vec<NonNull<CallbackSlot>>,
+    len: usize,
+    free: Vec<usize>,
+}
+
+// A callback removed from `CallbackArena`, dropped when this value is dropped
+pub(crate) struct RemovedCallback {
+    data: CallbackData,
+    drop: unsafe fn(*mut CallbackData),
+}
+
+// Only `MaybeSend` callbacks are stored in the arena
+#[cfg(feature = "send")]
+unsafe impl Send for CallbackArena {}
+#[cfg(feature = "send")]
+unsafe impl Send for RemovedCallback {}
+
+impl CallbackArena {
+    pub(crate) fn insert<F>(&mut self, func: F) -> usize
+    where
+        F: Fn(&RawLua, c_int) -> Result<c_int> + MaybeSend + 'static,
+    {
+        if mem::size_of::<F>() > mem::size_of::<CallbackData>()
+            || mem::align_of::<F>() > mem::align_of::<CallbackData>()
+        {
+            return self.insert_inline(Box::new(func));
+        }
+        self.insert_inline(func)
+    }
+
+    fn insert_inline<F>(&mut self, func: F) -> usize
+    where
+        F: Fn(&RawLua, c_int) -> Result<c_int> + MaybeSend + 'static,
+    {
+        unsafe fn get_callback<F: Fn(&RawLua, c_int) -> Result<c_int> + 'static>(
+            data: *const CallbackData,
+        ) -> *const CallbackFn {
+            data as *const F as *const CallbackFn
+        }
+
+        unsafe fn drop_callback<F>(data: *mut CallbackData) {
+            ptr::drop_in_place(data as *mut F)
+        }
+
+        assert!(mem::size_of::<F>() <= mem::size_of::<CallbackData>());
+        assert!(mem::align_of::<F>() <= mem::align_of::<CallbackData>());
+
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None => {
+                if self.len == self.chunks.len() * CALLBACK_CHUNK_SIZE {
+                    let chunk = (0..CALLBACK_CHUNK_SIZE)
+                        .map(|_| CallbackSlot {
+                            data: MaybeUninit::uninit(),
+                            get: None,
+                            drop: drop_callback::<()>,
+                        })
+                        .collect::<Box<[_]>>();
+                    let chunk = Box::into_raw(chunk) as *mut CallbackSlot;
+                    self.chunks.push(unsafe { NonNull::new_unchecked(chunk) });
+                }
+                self.len += 1;
+                self.len - 1
+            }
+        };
+        unsafe {
+            let slot_ptr = self.slot_ptr(slot);
+            ptr::write((*slot_ptr).data.as_mut_ptr() as *mut F, func);
+            (*slot_ptr).get = Some(get_callback::<F>);
+            (*slot_ptr).drop = drop_callback::<F>;
+        }
+        slot
+    }
+
+    // Returns a pointer to the callback, which remains valid until the slot is freed
+    // (even if new callbacks are inserted).
+    pub(crate) fn get(&self, slot: usize) -> Option<*const CallbackFn> {
+        if slot >= self.len {
+            return None;
+        }
+        unsafe {
+            let slot_ptr = self.slot_ptr(slot);
+            let get = (*slot_ptr).get?;
+            Some(get(&(*slot_ptr).data))
+        }
+    }
+
+    pub(crate) fn remove(&mut self, slot: usize) -> Option<RemovedCallback> {
+        if slot >= self.len {
+            return None;
+        }
+        unsafe {
+            let slot_ptr = self.slot_ptr(slot);
+            (*slot_ptr).get.take()?;
+            self.free.push(slot);
+            Some(RemovedCallback {
+                data: ptr::read(&(*slot_ptr).data),
+                drop: (*slot_ptr).drop,
+            })
+        }
+    }
+
+    #[inline]
+    unsafe fn slot_ptr(&self, slot: usize) -> *mut CallbackSlot {
+        let chunk = self.chunks[slot / CALLBACK_CHUNK_SIZE];
+        chunk.as_ptr().add(slot % CALLBACK_CHUNK_SIZE)
+    }
+}
+
+impl Drop for CallbackArena {
+    fn drop(&mut self) {
+        unsafe {
+            for slot in 0..self.len {
+                drop(self.remove(slot));
+            }
+            for chunk in self.chunks.drain(..) {
+                let chunk = ptr::slice_from_raw_parts_mut(chunk.as_ptr(), CALLBACK_CHUNK_SIZE);
+                drop(Box::from_raw(chunk));
+            }
+        }
+    }
+}
+
+impl Drop for RemovedCallback {
+    fn drop(&mut self) {
+        unsafe { (self.drop)(&mut self.data) }
+    }
+}
 
 #[cfg(all(feature = "async", feature = "send"))]
 pub(crate) type AsyncCallback =
@@ -101,6 +269,35 @@ pub(crate) type WarnCallback = Box<dyn Fn(&Lua, &str, bool) -> Result<()> + Send
 #[cfg(all(not(feature = "send"), feature = "lua54"))]
 pub(crate) type WarnCallback = Box<dyn Fn(&Lua, &str, bool) -> Result<()>>;
 
+#[cfg(feature = "send")]
+pub(crate) type ClockCallback<T> = Box<dyn Fn() -> T + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type ClockCallback<T> = Box<dyn Fn() -> T>;
+
+#[cfg(feature = "send")]
+pub(crate) type ConversionErrorCallback = XRc<dyn Fn(&crate::error::ConversionErrorInfo) + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type ConversionErrorCallback = XRc<dyn Fn(&crate::error::ConversionErrorInfo)>;
+
+#[cfg(feature = "send")]
+pub(crate) type CallbackMiddleware = XRc<
+    dyn Fn(crate::middleware::CallInfo, crate::middleware::Next) -> Result<crate::value::MultiValue> + Send,
+>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type CallbackMiddleware =
+    XRc<dyn Fn(crate::middleware::CallInfo, crate::middleware::Next) -> Result<crate::value::MultiValue>>;
+
+#[cfg(feature = "send")]
+pub(crate) type GCPolicyCallback =
+    XRc<dyn Fn(&Lua, &str, &crate::value::MultiValue) -> Result<crate::gc_policy::GCAction> + Send>;
+
+#[cfg(not(feature = "send"))]
+pub(crate) type GCPolicyCallback =
+    XRc<dyn Fn(&Lua, &str, &crate::value::MultiValue) -> Result<crate::gc_policy::GCAction>>;
+
 /// A trait that adds `Send` requirement if `send` feature is enabled.
 #[cfg(feature = "send")]
 pub trait MaybeSend: Send {}
@@ -16,16 +16,17 @@ use {
 
 use crate::error::{Error, Result};
 use crate::function::Function;
+use crate::scope::Scope;
 use crate::state::Lua;
 use crate::string::String;
 use crate::table::{Table, TablePairs};
-use crate::types::{MaybeSend, SubtypeId, ValueRef};
+use crate::types::{Either, MaybeSend, SubtypeId, ValueRef};
 use crate::util::{check_stack, get_userdata, take_userdata, StackGuard};
-use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Value};
+use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Nil, Value};
 
 // Re-export for convenience
 pub(crate) use cell::UserDataStorage;
-pub use cell::{UserDataRef, UserDataRefMut};
+pub use cell::{MappedUserDataRef, MappedUserDataRefMut, UserDataRef, UserDataRefMut, UserDataRefUpgradable};
 pub(crate) use registry::UserDataProxy;
 pub use registry::UserDataRegistry;
 
@@ -446,6 +447,111 @@ pub trait UserDataMethods<T> {
         A: FromLuaMulti,
         FR: Future<Output = Result<R>> + MaybeSend + 'static,
         R: IntoLuaMulti;
+
+    /// Adds the `__tostring` metamethod that uses the [`Display`] implementation of `T`.
+    ///
+    /// [`Display`]: std::fmt::Display
+    fn add_display_tostring(&mut self)
+    where
+        T: fmt::Display,
+    {
+        self.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.to_string()));
+    }
+
+    /// Adds the `__tostring` metamethod that uses the [`Debug`] implementation of `T`.
+    ///
+    /// [`Debug`]: std::fmt::Debug
+    fn add_debug_tostring(&mut self)
+    where
+        T: fmt::Debug,
+    {
+        self.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(format!("{this:?}")));
+    }
+
+    /// Adds the `__eq` metamethod that uses the [`PartialEq`] implementation of `T`.
+    ///
+    /// Userdata of a different type is never equal to `T`.
+    fn add_partial_eq(&mut self)
+    where
+        T: PartialEq + 'static,
+    {
+        self.add_meta_method(MetaMethod::Eq, |_, this, other: AnyUserData| {
+            match other.borrow_scoped::<T, _>(|other| this == other) {
+                Ok(eq) => Ok(eq),
+                Err(Error::UserDataTypeMismatch) => Ok(false),
+                Err(err) => Err(err),
+            }
+        });
+    }
+
+    /// Adds a binary metamethod (such as `__add` or `__mul`) between `T` and values of type `P`.
+    ///
+    /// Lua calls the metamethod if either operand has it, so the userdata can be on either side
+    /// of the operator. Each operand is passed to the function as [`Either::Right`] if it's a
+    /// userdata of type `T`, otherwise it's converted to `P` and passed as [`Either::Left`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Either, Lua, MetaMethod, Result, UserData, UserDataMethods};
+    /// # fn main() -> Result<()> {
+    /// #[derive(Clone, Copy)]
+    /// struct Vec2(f64, f64);
+    ///
+    /// impl UserData for Vec2 {
+    ///     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+    ///         methods.add_method("x", |_, v, ()| Ok(v.0));
+    ///         methods.add_meta_binop(MetaMethod::Mul, |_, lhs: Either<f64, &Vec2>, rhs| {
+    ///             Ok(match (lhs, rhs) {
+    ///                 (Either::Right(v), Either::Left(k)) | (Either::Left(k), Either::Right(v)) => {
+    ///                     Vec2(v.0 * k, v.1 * k)
+    ///                 }
+    ///                 (Either::Right(a), Either::Right(b)) => Vec2(a.0 * b.0, a.1 * b.1),
+    ///                 (Either::Left(_), Either::Left(_)) => unreachable!(),
+    ///             })
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// let lua = Lua::new();
+    /// lua.globals().set("v", Vec2(1.0, 2.0))?;
+    /// assert_eq!(lua.load("(v * 3):x() + (2 * v):x() + (v * v):x()").eval::<f64>()?, 6.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn add_meta_binop<P, F, R>(&mut self, name: impl ToString, function: F)
+    where
+        T: 'static,
+        P: FromLua,
+        F: Fn(&Lua, Either<P, &T>, Either<P, &T>) -> Result<R> + MaybeSend + 'static,
+        R: IntoLuaMulti,
+    {
+        self.add_meta_function(name, move |lua, (lhs, rhs): (Value, Value)| {
+            // The same userdata on both sides is borrowed once
+            let same = matches!((&lhs, &rhs), (Value::UserData(a), Value::UserData(b)) if a == b);
+            let (mut lhs_ud, mut rhs_ud) = (None, None);
+            let lhs = match binop_operand::<P, T>(lhs, lua)? {
+                Either::Left(value) => Either::Left(value),
+                Either::Right(ud) => Either::Right(&**lhs_ud.insert(ud)),
+            };
+            let rhs = match (&lhs, same) {
+                (Either::Right(this), true) => Either::Right(*this),
+                _ => match binop_operand::<P, T>(rhs, lua)? {
+                    Either::Left(value) => Either::Left(value),
+                    Either::Right(ud) => Either::Right(&**rhs_ud.insert(ud)),
+                },
+            };
+            function(lua, lhs, rhs)
+        });
+    }
+}
+
+// Converts an operand of a binary metamethod to either a value of type `P` or the userdata `T`
+fn binop_operand<P: FromLua, T: 'static>(value: Value, lua: &Lua) -> Result<Either<P, UserDataRef<T>>> {
+    match value {
+        Value::UserData(ud) if ud.is::<T>() => ud.borrow::<T>().map(Either::Right),
+        value => P::from_lua(value, lua).map(Either::Left),
+    }
 }
 
 /// Field registry for [`UserData`] implementors.
@@ -477,6 +583,20 @@ pub trait UserDataFields<T> {
         M: Fn(&Lua, &T) -> Result<R> + MaybeSend + 'static,
         R: IntoLua;
 
+    /// Add a regular field getter as a method which accepts a `&T` as the parameter, caching the
+    /// returned value.
+    ///
+    /// The getter is called on the first access only, the returned value is stored in a named user
+    /// value of the userdata instance and returned by subsequent accesses. This is useful for
+    /// expensive derived properties. Use [`AnyUserData::invalidate_field`] to discard the stored
+    /// value, so it is computed again on the next access. `nil` values are not cached.
+    ///
+    /// [`AnyUserData::invalidate_field`]: crate::AnyUserData::invalidate_field
+    fn add_field_method_get_cached<M, R>(&mut self, name: impl ToString, method: M)
+    where
+        M: Fn(&Lua, &T) -> Result<R> + MaybeSend + 'static,
+        R: IntoLua;
+
     /// Add a regular field setter as a method which accepts a `&mut T` as the first parameter.
     ///
     /// Regular field setters are implemented by overriding the `__newindex` metamethod and setting
@@ -490,6 +610,43 @@ pub trait UserDataFields<T> {
         M: FnMut(&Lua, &mut T, A) -> Result<()> + MaybeSend + 'static,
         A: FromLua;
 
+    /// Add a regular field with both getter and setter methods.
+    ///
+    /// This is equivalent to calling [`add_field_method_get`] and [`add_field_method_set`] with the
+    /// same name, but ensures that both accessors use the same value type `V`. Errors raised by
+    /// either accessor (such as a conversion error of the assigned value or a borrow error) refer
+    /// to the field by the same name.
+    ///
+    /// [`add_field_method_get`]: #tymethod.add_field_method_get
+    /// [`add_field_method_set`]: #tymethod.add_field_method_set
+    fn add_field_method_get_set<G, S, V>(&mut self, name: impl ToString, getter: G, setter: S)
+    where
+        G: Fn(&Lua, &T) -> Result<V> + MaybeSend + 'static,
+        S: FnMut(&Lua, &mut T, V) -> Result<()> + MaybeSend + 'static,
+        V: IntoLua + FromLua;
+
+    /// Add a regular field with getter and setter methods, calling `notify` after each successful
+    /// assignment.
+    ///
+    /// The `notify` hook receives the userdata after the setter returned and the mutable borrow
+    /// was released, so it can read the updated field (e.g. to refresh a view bound to the data).
+    /// Errors returned by the hook are propagated to the assigning script.
+    ///
+    /// See [`add_field_method_get_set`] for details.
+    ///
+    /// [`add_field_method_get_set`]: #tymethod.add_field_method_get_set
+    fn add_field_method_get_set_notify<G, S, N, V>(
+        &mut self,
+        name: impl ToString,
+        getter: G,
+        setter: S,
+        notify: N,
+    ) where
+        G: Fn(&Lua, &T) -> Result<V> + MaybeSend + 'static,
+        S: FnMut(&Lua, &mut T, V) -> Result<()> + MaybeSend + 'static,
+        N: Fn(&Lua, AnyUserData) -> Result<()> + MaybeSend + 'static,
+        V: IntoLua + FromLua;
+
     /// Add a regular field getter as a function which accepts a generic [`AnyUserData`] of type `T`
     /// argument.
     ///
@@ -514,6 +671,32 @@ pub trait UserDataFields<T> {
         F: FnMut(&Lua, AnyUserData, A) -> Result<()> + MaybeSend + 'static,
         A: FromLua;
 
+    /// Add a catch-all field getter as a method which accepts a `&T` and the field name.
+    ///
+    /// The getter is called for string keys when no regular field or method with the given name
+    /// is found, which makes it suitable for proxy-like userdata with dynamic set of fields.
+    /// Indexing by non-string keys returns `nil`.
+    ///
+    /// The getter is implemented as the `__index` metamethod and replaces the one set using
+    /// `add_meta_method` (if any).
+    fn add_dynamic_field_get<M, R>(&mut self, method: M)
+    where
+        M: Fn(&Lua, &T, &str) -> Result<R> + MaybeSend + 'static,
+        R: IntoLua;
+
+    /// Add a catch-all field setter as a method which accepts a `&mut T`, the field name and
+    /// the value.
+    ///
+    /// The setter is called for string keys when no regular field setter with the given name is
+    /// found. Setting a field using a non-string key raises an error.
+    ///
+    /// The setter is implemented as the `__newindex` metamethod and replaces the one set using
+    /// `add_meta_method` (if any).
+    fn add_dynamic_field_set<M, A>(&mut self, method: M)
+    where
+        M: FnMut(&Lua, &mut T, &str, A) -> Result<()> + MaybeSend + 'static,
+        A: FromLua;
+
     /// Add a metatable field.
     ///
     /// This will initialize the metatable field with `value` on `UserData` creation.
@@ -682,6 +865,21 @@ impl AnyUserData {
         self.inspect(|ud| ud.try_borrow_owned_mut())
     }
 
+    /// Borrow this userdata immutably if it is of type `T`, with an option to upgrade the borrow
+    /// to mutable later.
+    ///
+    /// This allows to check the userdata state and then modify it without releasing the borrow
+    /// in between. Only one upgradable borrow can exist at a time.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `UserDataBorrowError` if the userdata is already mutably or upgradably borrowed.
+    /// Returns a `UserDataTypeMismatch` if the userdata is not of type `T` or if it's scoped.
+    #[inline]
+    pub fn borrow_upgradable<T: 'static>(&self) -> Result<UserDataRefUpgradable<T>> {
+        self.inspect(|ud| ud.try_borrow_owned_upgradable())
+    }
+
     /// Borrow this userdata mutably if it is of type `T`, passing the borrowed value
     /// to the closure.
     ///
@@ -885,6 +1083,78 @@ impl AnyUserData {
         }
     }
 
+    /// Discards the value cached by a field getter added with
+    /// [`UserDataFields::add_field_method_get_cached`].
+    ///
+    /// The getter is called again on the next access to the field.
+    pub fn invalidate_field(&self, name: &str) -> Result<()> {
+        self.set_named_user_value(&cached_field_key(name), Nil)
+    }
+
+    /// Returns the callback stored in a slot added with [`UserDataRegistry::add_callback_slot`].
+    ///
+    /// Returns `None` if no callback is set.
+    pub fn callback_slot(&self, name: &str) -> Result<Option<Function>> {
+        self.named_user_value(&callback_slot_key(name))
+    }
+
+    /// Sets (or removes) the callback stored in a slot added with
+    /// [`UserDataRegistry::add_callback_slot`].
+    pub fn set_callback_slot(&self, name: &str, func: Option<Function>) -> Result<()> {
+        self.set_named_user_value(&callback_slot_key(name), func)
+    }
+
+    /// Calls the callback stored in a slot added with [`UserDataRegistry::add_callback_slot`].
+    ///
+    /// Returns `None` without calling anything if no callback is set.
+    ///
+    /// The userdata is not borrowed during the call, so the callback can freely access it. Make
+    /// sure to release borrows of the userdata (e.g. from [`AnyUserData::borrow`]) before calling
+    /// this method.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{AnyUserData, Lua, Result, UserData, UserDataMethods, UserDataRegistry};
+    /// # fn main() -> Result<()> {
+    /// struct Counter(i64);
+    ///
+    /// impl UserData for Counter {
+    ///     fn register(registry: &mut UserDataRegistry<Self>) {
+    ///         registry.add_callback_slot("on_change");
+    ///         registry.add_function("incr", |_, ud: AnyUserData| {
+    ///             let value = {
+    ///                 let mut this = ud.borrow_mut::<Counter>()?;
+    ///                 this.0 += 1;
+    ///                 this.0
+    ///             };
+    ///             ud.call_callback_slot::<()>("on_change", value)?;
+    ///             Ok(())
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// let lua = Lua::new();
+    /// lua.globals().set("counter", Counter(0))?;
+    /// lua.load(r#"
+    ///     counter.on_change = function(value) last = value end
+    ///     counter:incr()
+    ///     assert(last == 1)
+    /// "#).exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_callback_slot<R: FromLuaMulti>(
+        &self,
+        name: &str,
+        args: impl IntoLuaMulti,
+    ) -> Result<Option<R>> {
+        match self.callback_slot(name)? {
+            Some(func) => func.call(args).map(Some),
+            None => Ok(None),
+        }
+    }
+
     #[doc(hidden)]
     #[deprecated(since = "0.9.0", note = "please use `named_user_value` instead")]
     pub fn get_named_user_value<V: FromLua>(&self, name: &str) -> Result<V> {
@@ -989,7 +1259,12 @@ impl AnyUserData {
         let lua = self.0.lua.lock();
         let is_serializable = || unsafe {
             // Userdata must be registered and not destructed
-            let _ = lua.get_userdata_ref_type_id(&self.0)?;
+            let type_id = lua.get_userdata_ref_type_id(&self.0)?;
+            if let Some(type_id) = type_id {
+                if lua.with_userdata_hooks(|hooks| hooks.serializer(type_id).is_some()) {
+                    return Ok(true);
+                }
+            }
             let ud = &*get_userdata::<UserDataStorage<()>>(lua.ref_thread(), self.0.index);
             Ok::<_, Error>((*ud).is_serializable())
         };
@@ -1069,6 +1344,44 @@ impl UserDataMetatable {
     pub fn pairs<V: FromLua>(&self) -> UserDataMetatablePairs<V> {
         UserDataMetatablePairs(self.0.pairs())
     }
+
+    /// Returns statistics of the method lookup cache used by the `__index` metamethod.
+    ///
+    /// Methods resolved by the generated `__index` metamethod are cached per metatable, so repeated
+    /// `obj:method()` calls on the same type skip the lookups in the field getters and methods
+    /// tables. Field getters are never cached.
+    ///
+    /// The statistics are collected only when the `metrics` feature is enabled, as counting adds
+    /// a table update to every cached lookup.
+    ///
+    /// Requires `feature = "metrics"`
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+    pub fn method_cache_stats(&self) -> Result<MethodCacheStats> {
+        match self.0.raw_get::<Option<Table>>("__mlua_method_cache")? {
+            Some(stats) => Ok(MethodCacheStats {
+                hits: stats.raw_get(1)?,
+                misses: stats.raw_get(2)?,
+            }),
+            None => Ok(MethodCacheStats::default()),
+        }
+    }
+}
+
+/// Statistics of the userdata method lookup cache.
+///
+/// This struct is created by the [`UserDataMetatable::method_cache_stats`] method.
+///
+/// [`UserDataMetatable::method_cache_stats`]: crate::UserDataMetatable::method_cache_stats
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MethodCacheStats {
+    /// Number of lookups served from the cache.
+    pub hits: u64,
+    /// Number of lookups that were resolved from the methods table and then cached.
+    pub misses: u64,
 }
 
 /// An iterator over the pairs of a [`UserData`] metatable.
@@ -1110,17 +1423,33 @@ impl Serialize for AnyUserData {
     {
         let lua = self.0.lua.lock();
         unsafe {
-            let _ = lua
+            let type_id = lua
                 .get_userdata_ref_type_id(&self.0)
                 .map_err(ser::Error::custom)?;
+            let hook = type_id.and_then(|type_id| lua.with_userdata_hooks(|hooks| hooks.serializer(type_id)));
+            if let Some(hook) = hook {
+                return crate::serde::userdata::serialize_with_hook(self, hook, serializer);
+            }
             let ud = &*get_userdata::<UserDataStorage<()>>(lua.ref_thread(), self.0.index);
             ud.serialize(serializer)
         }
     }
 }
 
+// Returns name of the user value storing a value of the cached field
+pub(crate) fn cached_field_key(name: &str) -> StdString {
+    format!("__mlua_cached_field:{name}")
+}
+
+// Returns name of the user value storing a callback of the callback slot
+pub(crate) fn callback_slot_key(name: &str) -> StdString {
+    format!("__mlua_callback_slot:{name}")
+}
+
 pub(crate) struct WrappedUserdata<F: FnOnce(&Lua) -> Result<AnyUserData>>(F);
 
+type BoxedUserdataCtor<'a> = Box<dyn FnOnce(&Lua) -> Result<AnyUserData> + 'a>;
+
 impl AnyUserData {
     /// Wraps any Rust type, returning an opaque type that implements [`IntoLua`] trait.
     ///
@@ -1128,6 +1457,62 @@ impl AnyUserData {
     pub fn wrap<T: MaybeSend + 'static>(data: T) -> impl IntoLua {
         WrappedUserdata(move |lua| lua.create_any_userdata(data))
     }
+
+    /// Wraps a reference to any Rust type, returning an opaque type that implements [`IntoLua`]
+    /// trait.
+    ///
+    /// This function uses [`Scope::create_any_userdata_ref()`] under the hood, so the userdata
+    /// expires on scope drop and cannot be mutated from Lua. It allows exposing a read-only view
+    /// of (large) host data to a script call without cloning it. The value must be converted using
+    /// the same Lua instance the scope belongs to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{AnyUserData, Function, Lua, Result, UserDataFields};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// struct Config {
+    ///     name: String,
+    /// }
+    ///
+    /// lua.register_userdata_type::<Config>(|reg| {
+    ///     reg.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+    /// })?;
+    ///
+    /// let config = Config { name: "mlua".to_string() };
+    /// let get_name: Function = lua.load("function(config) return config.name end").eval()?;
+    /// let name = lua.scope(|scope| get_name.call::<String>(AnyUserData::wrap_ref(scope, &config)))?;
+    /// assert_eq!(name, "mlua");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Scope::create_any_userdata_ref()`]: crate::Scope::create_any_userdata_ref
+    pub fn wrap_ref<'scope, 'env, T: 'static>(
+        scope: &'scope Scope<'scope, 'env>,
+        data: &'env T,
+    ) -> impl IntoLua + 'scope {
+        // Boxing erases the `'env` lifetime from the returned type
+        let f: BoxedUserdataCtor<'scope> = Box::new(move |_| scope.create_any_userdata_ref(data));
+        WrappedUserdata(f)
+    }
+
+    /// Wraps a mutable reference to any Rust type, returning an opaque type that implements
+    /// [`IntoLua`] trait.
+    ///
+    /// This is a version of [`AnyUserData::wrap_ref`] that uses
+    /// [`Scope::create_any_userdata_ref_mut()`] under the hood, allowing the data to be mutated
+    /// from Lua.
+    ///
+    /// [`Scope::create_any_userdata_ref_mut()`]: crate::Scope::create_any_userdata_ref_mut
+    pub fn wrap_ref_mut<'scope, 'env, T: 'static>(
+        scope: &'scope Scope<'scope, 'env>,
+        data: &'env mut T,
+    ) -> impl IntoLua + 'scope {
+        let f: BoxedUserdataCtor<'scope> = Box::new(move |_| scope.create_any_userdata_ref_mut(data));
+        WrappedUserdata(f)
+    }
 }
 
 impl<F> IntoLua for WrappedUserdata<F>
@@ -1,8 +1,10 @@
 use std::any::{type_name, TypeId};
 use std::cell::{RefCell, UnsafeCell};
-use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::os::raw::c_int;
+use std::ptr::{self, NonNull};
+use std::result::Result as StdResult;
+use std::{fmt, mem};
 
 #[cfg(feature = "serialize")]
 use serde::ser::{Serialize, Serializer};
@@ -71,6 +73,12 @@ impl<T> UserDataVariant<T> {
         UserDataRefMut::try_from(self.clone())
     }
 
+    // Immutably borrows the wrapped value with an option to upgrade the borrow to mutable.
+    #[inline(always)]
+    fn try_borrow_owned_upgradable(&self) -> Result<UserDataRefUpgradable<T>> {
+        UserDataRefUpgradable::try_from(self.clone())
+    }
+
     // Returns the wrapped value.
     //
     // This method checks that we have exclusive access to the value.
@@ -158,6 +166,37 @@ impl<T> Deref for UserDataRef<T> {
     }
 }
 
+impl<T> UserDataRef<T> {
+    /// Makes a new [`MappedUserDataRef`] for a component of the borrowed data.
+    ///
+    /// The userdata remains borrowed until the returned reference is dropped.
+    ///
+    /// This is an associated function that needs to be used as `UserDataRef::map(...)`,
+    /// so it does not interfere with methods of the same name on the userdata.
+    #[inline]
+    pub fn map<U: ?Sized, F>(this: Self, f: F) -> MappedUserDataRef<T, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let ptr = NonNull::from(f(&this));
+        MappedUserDataRef { inner: this, ptr }
+    }
+
+    /// Tries to make a new [`MappedUserDataRef`] for a component of the borrowed data.
+    ///
+    /// Returns the original reference if the closure returns `None`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(this: Self, f: F) -> StdResult<MappedUserDataRef<T, U>, Self>
+    where
+        F: FnOnce(&T) -> Option<&U>,
+    {
+        match f(&this).map(NonNull::from) {
+            Some(ptr) => Ok(MappedUserDataRef { inner: this, ptr }),
+            None => Err(this),
+        }
+    }
+}
+
 impl<T> Drop for UserDataRef<T> {
     #[inline]
     fn drop(&mut self) {
@@ -226,6 +265,37 @@ impl<T> DerefMut for UserDataRefMut<T> {
     }
 }
 
+impl<T> UserDataRefMut<T> {
+    /// Makes a new [`MappedUserDataRefMut`] for a component of the borrowed data.
+    ///
+    /// The userdata remains mutably borrowed until the returned reference is dropped.
+    ///
+    /// This is an associated function that needs to be used as `UserDataRefMut::map_mut(...)`,
+    /// so it does not interfere with methods of the same name on the userdata.
+    #[inline]
+    pub fn map_mut<U: ?Sized, F>(mut this: Self, f: F) -> MappedUserDataRefMut<T, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let ptr = NonNull::from(f(&mut this));
+        MappedUserDataRefMut { inner: this, ptr }
+    }
+
+    /// Tries to make a new [`MappedUserDataRefMut`] for a component of the borrowed data.
+    ///
+    /// Returns the original reference if the closure returns `None`.
+    #[inline]
+    pub fn try_map_mut<U: ?Sized, F>(mut this: Self, f: F) -> StdResult<MappedUserDataRefMut<T, U>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        match f(&mut this).map(NonNull::from) {
+            Some(ptr) => Ok(MappedUserDataRefMut { inner: this, ptr }),
+            None => Err(this),
+        }
+    }
+}
+
 impl<T> Drop for UserDataRefMut<T> {
     #[inline]
     fn drop(&mut self) {
@@ -273,6 +343,216 @@ impl<T: 'static> FromLua for UserDataRefMut<T> {
     }
 }
 
+/// A wrapper type for a [`UserData`] value that provides read access and can be upgraded to
+/// [`UserDataRefMut`] without releasing the borrow.
+///
+/// Only one upgradable borrow can exist at a time, but it can coexist with other immutable
+/// borrows. Mutable borrows are not allowed while the upgradable borrow is alive.
+///
+/// Created by [`AnyUserData::borrow_upgradable`].
+///
+/// [`UserData`]: crate::UserData
+pub struct UserDataRefUpgradable<T>(UserDataVariant<T>);
+
+impl<T> UserDataRefUpgradable<T> {
+    /// Tries to upgrade the borrow to mutable.
+    ///
+    /// The upgrade succeeds only if there are no other immutable borrows of the userdata.
+    /// On failure the original (still valid) borrow is returned.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `UserDataRefUpgradable::try_upgrade(...)`, so it does not interfere with methods of the
+    /// same name on the userdata.
+    pub fn try_upgrade(this: Self) -> StdResult<UserDataRefMut<T>, Self> {
+        if !unsafe { this.0.raw_lock().try_upgrade() } {
+            return Err(this);
+        }
+        let this = mem::ManuallyDrop::new(this);
+        // SAFETY: the variant is moved out of the wrapper that is not dropped
+        Ok(UserDataRefMut(unsafe { ptr::read(&this.0) }))
+    }
+}
+
+impl<T> Deref for UserDataRefUpgradable<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.0.as_ptr() }
+    }
+}
+
+impl<T> Drop for UserDataRefUpgradable<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.0.raw_lock().unlock_upgradable() };
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for UserDataRefUpgradable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for UserDataRefUpgradable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T> TryFrom<UserDataVariant<T>> for UserDataRefUpgradable<T> {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(variant: UserDataVariant<T>) -> Result<Self> {
+        if !variant.raw_lock().try_lock_upgradable() {
+            return Err(Error::UserDataBorrowError);
+        }
+        Ok(UserDataRefUpgradable(variant))
+    }
+}
+
+/// A read-only reference to a component of a borrowed userdata value.
+///
+/// Created by [`UserDataRef::map`].
+pub struct MappedUserDataRef<T, U: ?Sized> {
+    inner: UserDataRef<T>,
+    ptr: NonNull<U>,
+}
+
+// SAFETY: `MappedUserDataRef` provides shared access to `U` and owns `UserDataRef<T>`
+unsafe impl<T, U: ?Sized + Sync> Send for MappedUserDataRef<T, U> where UserDataRef<T>: Send {}
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedUserDataRef<T, U> where UserDataRef<T>: Sync {}
+
+impl<T, U: ?Sized> MappedUserDataRef<T, U> {
+    /// Makes a new [`MappedUserDataRef`] for a component of the borrowed data.
+    #[inline]
+    pub fn map<V: ?Sized, F>(this: Self, f: F) -> MappedUserDataRef<T, V>
+    where
+        F: FnOnce(&U) -> &V,
+    {
+        let ptr = NonNull::from(f(&this));
+        MappedUserDataRef {
+            inner: this.inner,
+            ptr,
+        }
+    }
+
+    /// Tries to make a new [`MappedUserDataRef`] for a component of the borrowed data.
+    ///
+    /// Returns the original reference if the closure returns `None`.
+    #[inline]
+    pub fn try_map<V: ?Sized, F>(this: Self, f: F) -> StdResult<MappedUserDataRef<T, V>, Self>
+    where
+        F: FnOnce(&U) -> Option<&V>,
+    {
+        match f(&this).map(NonNull::from) {
+            Some(ptr) => Ok(MappedUserDataRef {
+                inner: this.inner,
+                ptr,
+            }),
+            None => Err(this),
+        }
+    }
+}
+
+impl<T, U: ?Sized> Deref for MappedUserDataRef<T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: the userdata is kept borrowed by `inner`
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized + fmt::Debug> fmt::Debug for MappedUserDataRef<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T, U: ?Sized + fmt::Display> fmt::Display for MappedUserDataRef<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A mutable reference to a component of a mutably borrowed userdata value.
+///
+/// Created by [`UserDataRefMut::map_mut`].
+pub struct MappedUserDataRefMut<T, U: ?Sized> {
+    inner: UserDataRefMut<T>,
+    ptr: NonNull<U>,
+}
+
+// SAFETY: `MappedUserDataRefMut` provides exclusive access to `U` and owns `UserDataRefMut<T>`
+unsafe impl<T, U: ?Sized + Send> Send for MappedUserDataRefMut<T, U> where UserDataRefMut<T>: Send {}
+unsafe impl<T, U: ?Sized + Sync> Sync for MappedUserDataRefMut<T, U> where UserDataRefMut<T>: Sync {}
+
+impl<T, U: ?Sized> MappedUserDataRefMut<T, U> {
+    /// Makes a new [`MappedUserDataRefMut`] for a component of the borrowed data.
+    #[inline]
+    pub fn map_mut<V: ?Sized, F>(mut this: Self, f: F) -> MappedUserDataRefMut<T, V>
+    where
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        let ptr = NonNull::from(f(&mut this));
+        MappedUserDataRefMut {
+            inner: this.inner,
+            ptr,
+        }
+    }
+
+    /// Tries to make a new [`MappedUserDataRefMut`] for a component of the borrowed data.
+    ///
+    /// Returns the original reference if the closure returns `None`.
+    #[inline]
+    pub fn try_map_mut<V: ?Sized, F>(mut this: Self, f: F) -> StdResult<MappedUserDataRefMut<T, V>, Self>
+    where
+        F: FnOnce(&mut U) -> Option<&mut V>,
+    {
+        match f(&mut this).map(NonNull::from) {
+            Some(ptr) => Ok(MappedUserDataRefMut {
+                inner: this.inner,
+                ptr,
+            }),
+            None => Err(this),
+        }
+    }
+}
+
+impl<T, U: ?Sized> Deref for MappedUserDataRefMut<T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: the userdata is kept mutably borrowed by `inner`
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized> DerefMut for MappedUserDataRefMut<T, U> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: the userdata is kept mutably borrowed by `inner`
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T, U: ?Sized + fmt::Debug> fmt::Debug for MappedUserDataRefMut<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T, U: ?Sized + fmt::Display> fmt::Display for MappedUserDataRefMut<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
 /// A type that provides read access to a userdata value (borrowing the value).
 pub(crate) struct UserDataBorrowRef<'a, T>(&'a UserDataVariant<T>);
 
@@ -438,6 +718,14 @@ impl<T: 'static> UserDataStorage<T> {
         }
     }
 
+    #[inline(always)]
+    pub(crate) fn try_borrow_owned_upgradable(&self) -> Result<UserDataRefUpgradable<T>> {
+        match self {
+            Self::Owned(data) => data.try_borrow_owned_upgradable(),
+            Self::Scoped(_) => Err(Error::UserDataTypeMismatch),
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn into_inner(self) -> Result<T> {
         match self {
@@ -494,6 +782,12 @@ mod assertions {
     #[cfg(feature = "send")]
     static_assertions::assert_not_impl_all!(UserDataRefMut<std::rc::Rc<()>>: Send, Sync);
     #[cfg(feature = "send")]
+    static_assertions::assert_impl_all!(UserDataRefUpgradable<()>: Send, Sync);
+    #[cfg(feature = "send")]
+    static_assertions::assert_impl_all!(MappedUserDataRef<(), ()>: Send, Sync);
+    #[cfg(feature = "send")]
+    static_assertions::assert_impl_all!(MappedUserDataRefMut<(), ()>: Send, Sync);
+    #[cfg(feature = "send")]
     static_assertions::assert_impl_all!(UserDataBorrowRef<'_, ()>: Send, Sync);
     #[cfg(feature = "send")]
     static_assertions::assert_impl_all!(UserDataBorrowMut<'_, ()>: Send, Sync);
@@ -503,6 +797,12 @@ mod assertions {
     #[cfg(not(feature = "send"))]
     static_assertions::assert_not_impl_all!(UserDataRefMut<()>: Send, Sync);
     #[cfg(not(feature = "send"))]
+    static_assertions::assert_not_impl_all!(UserDataRefUpgradable<()>: Send, Sync);
+    #[cfg(not(feature = "send"))]
+    static_assertions::assert_not_impl_all!(MappedUserDataRef<(), ()>: Send, Sync);
+    #[cfg(not(feature = "send"))]
+    static_assertions::assert_not_impl_all!(MappedUserDataRefMut<(), ()>: Send, Sync);
+    #[cfg(not(feature = "send"))]
     static_assertions::assert_not_impl_all!(UserDataBorrowRef<'_, ()>: Send, Sync);
     #[cfg(not(feature = "send"))]
     static_assertions::assert_not_impl_all!(UserDataBorrowMut<'_, ()>: Send, Sync);
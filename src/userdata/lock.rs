@@ -3,9 +3,14 @@ pub(crate) trait UserDataLock {
 
     fn try_lock_shared(&self) -> bool;
     fn try_lock_exclusive(&self) -> bool;
+    // Shared lock that can be later upgraded to exclusive (only one is allowed at a time)
+    fn try_lock_upgradable(&self) -> bool;
 
     unsafe fn unlock_shared(&self);
     unsafe fn unlock_exclusive(&self);
+    unsafe fn unlock_upgradable(&self);
+    // Upgrades the upgradable lock to exclusive (fails if there are other shared locks)
+    unsafe fn try_upgrade(&self) -> bool;
 }
 
 pub(crate) use lock_impl::RawLock;
@@ -14,11 +19,13 @@ pub(crate) use lock_impl::RawLock;
 mod lock_impl {
     use std::cell::Cell;
 
-    // Positive values represent the number of read references.
+    // Positive values represent the number of read references (plus `UPGRADABLE` flag if one
+    // of them is upgradable).
     // Negative values represent the number of write references (only one allowed).
     pub(crate) type RawLock = Cell<isize>;
 
     const UNUSED: isize = 0;
+    const UPGRADABLE: isize = 1 << (isize::BITS - 2);
 
     impl super::UserDataLock for RawLock {
         #[allow(clippy::declare_interior_mutable_const)]
@@ -44,6 +51,16 @@ mod lock_impl {
             true
         }
 
+        #[inline(always)]
+        fn try_lock_upgradable(&self) -> bool {
+            let flag = self.get();
+            if flag < UNUSED || flag & UPGRADABLE != 0 {
+                return false;
+            }
+            self.set(flag | UPGRADABLE);
+            true
+        }
+
         #[inline(always)]
         unsafe fn unlock_shared(&self) {
             let flag = self.get();
@@ -57,6 +74,22 @@ mod lock_impl {
             debug_assert!(flag < UNUSED);
             self.set(flag + 1);
         }
+
+        #[inline(always)]
+        unsafe fn unlock_upgradable(&self) {
+            let flag = self.get();
+            debug_assert!(flag & UPGRADABLE != 0);
+            self.set(flag & !UPGRADABLE);
+        }
+
+        #[inline(always)]
+        unsafe fn try_upgrade(&self) -> bool {
+            if self.get() != UPGRADABLE {
+                return false;
+            }
+            self.set(UNUSED - 1);
+            true
+        }
     }
 }
 
@@ -80,6 +113,12 @@ mod lock_impl {
             RawLock::try_lock(self)
         }
 
+        // Shared locks are exclusive, so the upgradable lock is already exclusive
+        #[inline(always)]
+        fn try_lock_upgradable(&self) -> bool {
+            RawLock::try_lock(self)
+        }
+
         #[inline(always)]
         unsafe fn unlock_shared(&self) {
             RawLock::unlock(self)
@@ -89,5 +128,15 @@ mod lock_impl {
         unsafe fn unlock_exclusive(&self) {
             RawLock::unlock(self)
         }
+
+        #[inline(always)]
+        unsafe fn unlock_upgradable(&self) {
+            RawLock::unlock(self)
+        }
+
+        #[inline(always)]
+        unsafe fn try_upgrade(&self) -> bool {
+            true
+        }
     }
 }
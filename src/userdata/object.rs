@@ -3,7 +3,7 @@ use std::string::String as StdString;
 use crate::error::{Error, Result};
 use crate::table::Table;
 use crate::traits::ObjectLike;
-use crate::userdata::AnyUserData;
+use crate::userdata::{AnyUserData, MetaMethod};
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Value};
 use crate::Function;
 
@@ -18,6 +18,16 @@ impl ObjectLike for AnyUserData {
         Table(self.0.copy()).get_protected(key)
     }
 
+    fn get_multi<R: FromLuaMulti>(&self, key: impl IntoLua) -> Result<R> {
+        match self.get_raw_metatable()?.raw_get(MetaMethod::Index.name())? {
+            Value::Function(index) => index.call((self, key)),
+            _ => {
+                let lua = self.0.lua.upgrade();
+                R::from_lua_multi(self.get::<Value>(key)?.into_lua_multi(&lua)?, &lua)
+            }
+        }
+    }
+
     #[inline]
     fn set(&self, key: impl IntoLua, value: impl IntoLua) -> Result<()> {
         // `lua_settable` method used under the hood can work with any Lua value
@@ -7,10 +7,14 @@ use std::os::raw::c_void;
 use std::string::String as StdString;
 
 use crate::error::{Error, Result};
+use crate::function::Function;
 use crate::state::{Lua, RawLua};
 use crate::types::{Callback, MaybeSend};
-use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMethods, UserDataStorage};
-use crate::util::{get_userdata, short_type_name};
+use crate::userdata::{
+    cached_field_key, callback_slot_key, AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMethods,
+    UserDataStorage,
+};
+use crate::util::{check_stack, get_userdata, short_type_name};
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Value};
 
 #[cfg(feature = "async")]
@@ -20,6 +24,17 @@ use {
     std::future::{self, Future},
 };
 
+#[cfg(all(feature = "async", not(feature = "luau")))]
+use {crate::scheduler::AsyncDestructor, std::any::Any};
+
+#[cfg(feature = "serialize")]
+use {
+    crate::serde::userdata::{DeserializeHook, SerializeHook},
+    crate::serde::LuaSerdeExt,
+    crate::types::XRc,
+    serde::{de::DeserializeOwned, Serialize},
+};
+
 type StaticFieldCallback = Box<dyn FnOnce(&RawLua) -> Result<()> + 'static>;
 
 #[derive(Clone, Copy)]
@@ -44,10 +59,45 @@ pub struct UserDataRegistry<T> {
     #[cfg(feature = "async")]
     pub(crate) async_meta_methods: Vec<(String, AsyncCallback)>,
 
+    // Registered metamethods and meta fields, used for validation
+    pub(crate) meta_checks: Vec<MetaCheck>,
+
+    // Type name overriding the `__name/__type` meta field (see `set_type_name`)
+    pub(crate) type_name: Option<String>,
+
+    // Async destructor and the `__gc` metamethod scheduling it
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    pub(crate) async_destructor: Option<(AsyncDestructor, ffi::lua_CFunction)>,
+
+    // Hooks used by the serde integration
+    #[cfg(feature = "serialize")]
+    pub(crate) serialize_hook: Option<SerializeHook>,
+    #[cfg(feature = "serialize")]
+    pub(crate) deserialize_hook: Option<DeserializeHook>,
+
     pub(crate) type_id: UserDataTypeId,
     _type: PhantomData<T>,
 }
 
+// Registration details of a metamethod (or meta field)
+pub(crate) struct MetaCheck {
+    name: StdString,
+    kind: &'static str,
+    // Type name of the arguments (excluding `self`), for metamethods accepting `self`
+    args: Option<&'static str>,
+    // Type name of the return value, for metamethods
+    ret: Option<&'static str>,
+}
+
+// Comparison metamethods, always called with an argument in addition to `self`
+const COMPARISON_METAMETHODS: &[&str] = &["__eq", "__lt", "__le"];
+
+// Return types that cannot be converted to a string
+const NON_STRING_TYPES: &[&str] = &[
+    "()", "bool", "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "i128", "u128", "isize", "usize",
+    "f32", "f64",
+];
+
 impl<T> UserDataRegistry<T> {
     #[inline]
     pub(crate) fn new(type_id: TypeId) -> Self {
@@ -62,6 +112,14 @@ impl<T> UserDataRegistry<T> {
             meta_methods: Vec::new(),
             #[cfg(feature = "async")]
             async_meta_methods: Vec::new(),
+            meta_checks: Vec::new(),
+            type_name: None,
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            async_destructor: None,
+            #[cfg(feature = "serialize")]
+            serialize_hook: None,
+            #[cfg(feature = "serialize")]
+            deserialize_hook: None,
             type_id: UserDataTypeId::Shared(type_id),
             _type: PhantomData,
         }
@@ -80,6 +138,14 @@ impl<T> UserDataRegistry<T> {
             meta_methods: Vec::new(),
             #[cfg(feature = "async")]
             async_meta_methods: Vec::new(),
+            meta_checks: Vec::new(),
+            type_name: None,
+            #[cfg(all(feature = "async", not(feature = "luau")))]
+            async_destructor: None,
+            #[cfg(feature = "serialize")]
+            serialize_hook: None,
+            #[cfg(feature = "serialize")]
+            deserialize_hook: None,
             type_id: UserDataTypeId::Unique(ud_ptr as usize),
             _type: PhantomData,
         }
@@ -93,6 +159,105 @@ impl<T> UserDataRegistry<T> {
         }
     }
 
+    /// Checks the registered metamethods for common mistakes.
+    ///
+    /// The following mistakes are detected:
+    /// - restricted metamethods (such as `__gc`)
+    /// - metamethods registered more than once (e.g. `__index` as both a meta field and a
+    ///   metamethod), where the last registration silently replaces the previous ones
+    /// - comparison metamethods (`__eq`, `__lt`, `__le`) that do not accept an argument in
+    ///   addition to `self`
+    /// - `__tostring` metamethod that does not return a string
+    ///
+    /// In debug builds, this check is performed automatically when the userdata type is
+    /// registered.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |message: StdString| {
+            let type_name = short_type_name::<T>();
+            Err(Error::runtime(format!(
+                "invalid userdata `{type_name}` registration: {message}"
+            )))
+        };
+
+        let mut seen: Vec<(&str, &str)> = Vec::with_capacity(self.meta_checks.len());
+        for check in &self.meta_checks {
+            let name = MetaMethod::validate(&check.name)?;
+            if let Some((_, prev_kind)) = seen.iter().find(|(n, _)| *n == name) {
+                let message = format!(
+                    "metamethod `{name}` is registered more than once (as {prev_kind} and {})",
+                    check.kind
+                );
+                return invalid(message);
+            }
+            seen.push((name, check.kind));
+
+            if check.args == Some("()") && COMPARISON_METAMETHODS.contains(&name) {
+                return invalid(format!("{} `{name}` must accept an argument", check.kind));
+            }
+            if let Some(ret) = check.ret.filter(|_| name == MetaMethod::ToString.name()) {
+                if NON_STRING_TYPES.contains(&ret) {
+                    let message = format!("{} `{name}` must return a string, not `{ret}`", check.kind);
+                    return invalid(message);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the type name of the userdata, as reported by the `__name` (`__type` in Luau) meta
+    /// field.
+    ///
+    /// By default the name is the Rust type name without the module path. The name set here
+    /// takes precedence over the [`MetaMethod::Type`] meta field.
+    ///
+    /// [`MetaMethod::Type`]: crate::MetaMethod::Type
+    pub fn set_type_name(&mut self, name: impl ToString) {
+        self.type_name = Some(name.to_string());
+    }
+
+    /// Adds a field storing a Lua callback on the userdata instance.
+    ///
+    /// Scripts can assign a function (or `nil`) to the field, e.g. `obj.on_change = function() end`,
+    /// and read it back. The function is stored in a named user value of the instance, so it is
+    /// collected together with the userdata and never outlives the Lua state (unlike a
+    /// [`RegistryKey`] stored in the Rust struct).
+    ///
+    /// Use [`AnyUserData::call_callback_slot`] to invoke the callback from Rust.
+    ///
+    /// [`RegistryKey`]: crate::RegistryKey
+    pub fn add_callback_slot(&mut self, name: impl ToString) {
+        let name = name.to_string();
+        let key = callback_slot_key(&name);
+        let getter = self.box_function(&name, move |_, ud: AnyUserData| {
+            ud.named_user_value::<Value>(&key)
+        });
+        self.field_getters.push((name.clone(), getter));
+
+        let key = callback_slot_key(&name);
+        let setter = self.box_function(&name, move |_, (ud, func): (AnyUserData, Option<Function>)| {
+            ud.set_named_user_value(&key, func)
+        });
+        self.field_setters.push((name, setter));
+    }
+
+    fn track_meta<A, R>(&mut self, name: &str, kind: &'static str, with_self: bool) {
+        self.meta_checks.push(MetaCheck {
+            name: name.to_string(),
+            kind,
+            args: with_self.then(std::any::type_name::<A>),
+            ret: Some(std::any::type_name::<R>()),
+        });
+    }
+
+    fn track_meta_field(&mut self, name: &str) {
+        self.meta_checks.push(MetaCheck {
+            name: name.to_string(),
+            kind: "meta field",
+            args: None,
+            ret: None,
+        });
+    }
+
     fn box_method<M, A, R>(&self, name: &str, method: M) -> Callback
     where
         M: Fn(&Lua, &T, A) -> Result<R> + MaybeSend + 'static,
@@ -347,6 +512,93 @@ impl<T> UserDataRegistry<T> {
     }
 }
 
+impl<T: 'static> UserDataRegistry<T> {
+    /// Sets an async destructor, called when the userdata is garbage collected.
+    ///
+    /// The destructor receives the userdata value and returns a future performing the cleanup
+    /// (e.g. flushing network buffers or closing remote resources). The future is not awaited by
+    /// the garbage collector, it is scheduled to be run by [`Lua::poll_pending_tasks`] instead.
+    /// Errors returned by these futures are ignored.
+    ///
+    /// # Limitations
+    ///
+    /// Completion of the cleanup is **not** guaranteed when the Lua state is dropped: dropping
+    /// [`Lua`] never blocks, so the cleanup futures still pending at that moment (including the
+    /// ones scheduled by closing the state) are dropped without being polled. Use
+    /// [`Lua::close`] to close the state and await all pending cleanup futures instead.
+    ///
+    /// The destructor is not called for userdata taken with [`AnyUserData::take`], scoped
+    /// userdata or values that are borrowed when collected.
+    ///
+    /// Requires `feature = "async"`
+    ///
+    /// [`Lua::poll_pending_tasks`]: crate::Lua::poll_pending_tasks
+    /// [`Lua::close`]: crate::Lua::close
+    #[cfg(all(feature = "async", not(feature = "luau")))]
+    #[cfg_attr(docsrs, doc(cfg(all(feature = "async", not(feature = "luau")))))]
+    pub fn add_async_destructor<F, FR>(&mut self, destructor: F)
+    where
+        F: Fn(T) -> FR + MaybeSend + 'static,
+        FR: Future<Output = Result<()>> + MaybeSend + 'static,
+    {
+        let destructor: AsyncDestructor = Box::new(move |data: Box<dyn Any>| {
+            let data = data.downcast::<T>().expect("unexpected userdata type");
+            Box::pin(destructor(*data))
+        });
+        let gc = crate::state::userdata_async_destructor::<T>;
+        self.async_destructor = Some((destructor, gc));
+    }
+
+    /// Sets a function to serialize the userdata value, for types not implementing
+    /// [`Serialize`].
+    ///
+    /// The function converts the value to a serializable representation, which is used by the serde
+    /// integration (e.g. when serializing a [`Value`] or deserializing it using
+    /// [`LuaSerdeExt::from_value`]) in place of the userdata.
+    ///
+    /// If a deserialization function is also set with [`UserDataRegistry::set_deserialize`], the
+    /// representation is wrapped into a struct `{ "$userdata": <type name>, "value": ... }`,
+    /// recognized by [`LuaSerdeExt::to_value`] to recreate the userdata.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`Serialize`]: serde::Serialize
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    pub fn set_serialize<F, R>(&mut self, f: F)
+    where
+        F: Fn(&T) -> R + MaybeSend + 'static,
+        R: Serialize + 'static,
+    {
+        self.serialize_hook = Some(XRc::new(move |ud: &AnyUserData| {
+            let repr = ud.borrow_scoped::<T, R>(&f)?;
+            Ok(Box::new(repr) as Box<dyn erased_serde::Serialize>)
+        }));
+    }
+
+    /// Sets a function to create the userdata value from its deserialized representation.
+    ///
+    /// [`LuaSerdeExt::to_value`] recreates userdata from structs (or maps) produced by serializing
+    /// userdata with [`UserDataRegistry::set_serialize`] hook, allowing to restore snapshots of
+    /// Lua state containing userdata. The type is identified by its name (without the module
+    /// path).
+    ///
+    /// Requires `feature = "serialize"`
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    pub fn set_deserialize<F, D>(&mut self, f: F)
+    where
+        T: MaybeSend,
+        F: Fn(D) -> Result<T> + MaybeSend + 'static,
+        D: DeserializeOwned,
+    {
+        self.deserialize_hook = Some(XRc::new(move |lua: &Lua, value: Value| {
+            let data = f(lua.from_value(value)?)?;
+            lua.create_any_userdata(data)
+        }));
+    }
+}
+
 // Returns function name for the type `T`, without the module path
 fn get_function_name<T>(name: &str) -> StdString {
     format!("{}.{name}", short_type_name::<T>())
@@ -374,6 +626,36 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
         self.field_getters.push((name, callback));
     }
 
+    fn add_field_method_get_cached<M, R>(&mut self, name: impl ToString, method: M)
+    where
+        M: Fn(&Lua, &T) -> Result<R> + MaybeSend + 'static,
+        R: IntoLua,
+    {
+        let name = name.to_string();
+        let key = cached_field_key(&name);
+        let getter = self.box_method(&name, move |lua, data, ()| method(lua, data));
+        let callback: Callback = Box::new(move |rawlua, nargs| unsafe {
+            // Let the getter report invalid arguments
+            let ud = match nargs {
+                0 => return getter(rawlua, nargs),
+                _ => match AnyUserData::from_stack(-nargs, rawlua) {
+                    Ok(ud) => ud,
+                    Err(_) => return getter(rawlua, nargs),
+                },
+            };
+            let cached = ud.named_user_value::<Value>(&key)?;
+            if !cached.is_nil() {
+                check_stack(rawlua.state(), 1)?;
+                rawlua.push_value(&cached)?;
+                return Ok(1);
+            }
+            let nresults = getter(rawlua, nargs)?;
+            ud.set_named_user_value(&key, rawlua.stack_value(-1, None))?;
+            Ok(nresults)
+        });
+        self.field_getters.push((name, callback));
+    }
+
     fn add_field_method_set<M, A>(&mut self, name: impl ToString, method: M)
     where
         M: FnMut(&Lua, &mut T, A) -> Result<()> + MaybeSend + 'static,
@@ -384,6 +666,49 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
         self.field_setters.push((name, callback));
     }
 
+    fn add_field_method_get_set<G, S, V>(&mut self, name: impl ToString, getter: G, setter: S)
+    where
+        G: Fn(&Lua, &T) -> Result<V> + MaybeSend + 'static,
+        S: FnMut(&Lua, &mut T, V) -> Result<()> + MaybeSend + 'static,
+        V: IntoLua + FromLua,
+    {
+        let name = name.to_string();
+        self.add_field_method_get(&name, getter);
+        self.add_field_method_set(name, setter);
+    }
+
+    fn add_field_method_get_set_notify<G, S, N, V>(
+        &mut self,
+        name: impl ToString,
+        getter: G,
+        setter: S,
+        notify: N,
+    ) where
+        G: Fn(&Lua, &T) -> Result<V> + MaybeSend + 'static,
+        S: FnMut(&Lua, &mut T, V) -> Result<()> + MaybeSend + 'static,
+        N: Fn(&Lua, AnyUserData) -> Result<()> + MaybeSend + 'static,
+        V: IntoLua + FromLua,
+    {
+        let name = name.to_string();
+        self.add_field_method_get(&name, getter);
+        let setter = self.box_method_mut(&name, setter);
+        let callback: Callback = Box::new(move |rawlua, nargs| unsafe {
+            // Let the setter report invalid arguments
+            let ud = match nargs {
+                0 => return setter(rawlua, nargs),
+                _ => match AnyUserData::from_stack(-nargs, rawlua) {
+                    Ok(ud) => ud,
+                    Err(_) => return setter(rawlua, nargs),
+                },
+            };
+            let nresults = setter(rawlua, nargs)?;
+            // The userdata is not borrowed anymore
+            notify(rawlua.lua(), ud)?;
+            Ok(nresults)
+        });
+        self.field_setters.push((name, callback));
+    }
+
     fn add_field_function_get<F, R>(&mut self, name: impl ToString, function: F)
     where
         F: Fn(&Lua, AnyUserData) -> Result<R> + MaybeSend + 'static,
@@ -404,11 +729,43 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
         self.field_setters.push((name, callback));
     }
 
+    fn add_dynamic_field_get<M, R>(&mut self, method: M)
+    where
+        M: Fn(&Lua, &T, &str) -> Result<R> + MaybeSend + 'static,
+        R: IntoLua,
+    {
+        let name = MetaMethod::Index.name();
+        self.track_meta::<Value, R>(name, "dynamic field getter", true);
+        let callback = self.box_method(name, move |lua, data, key: Value| match key {
+            Value::String(key) => method(lua, data, &key.to_str()?)?.into_lua(lua),
+            _ => Ok(Value::Nil),
+        });
+        self.meta_methods.push((name.to_string(), callback));
+    }
+
+    fn add_dynamic_field_set<M, A>(&mut self, mut method: M)
+    where
+        M: FnMut(&Lua, &mut T, &str, A) -> Result<()> + MaybeSend + 'static,
+        A: FromLua,
+    {
+        let name = MetaMethod::NewIndex.name();
+        self.track_meta::<(Value, A), ()>(name, "dynamic field setter", true);
+        let callback = self.box_method_mut(name, move |lua, data, (key, value): (Value, A)| match key {
+            Value::String(key) => method(lua, data, &key.to_str()?, value),
+            _ => Err(Error::runtime(format!(
+                "field name must be a string, got {}",
+                key.type_name()
+            ))),
+        });
+        self.meta_methods.push((name.to_string(), callback));
+    }
+
     fn add_meta_field<V>(&mut self, name: impl ToString, value: V)
     where
         V: IntoLua + 'static,
     {
         let name = name.to_string();
+        self.track_meta_field(&name);
         self.meta_fields.push((
             name.clone(),
             Box::new(move |rawlua| unsafe {
@@ -423,6 +780,7 @@ impl<T> UserDataFields<T> for UserDataRegistry<T> {
         R: IntoLua,
     {
         let name = name.to_string();
+        self.track_meta_field(&name);
         self.meta_fields.push((
             name.clone(),
             Box::new(move |rawlua| unsafe {
@@ -526,6 +884,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "meta method", true);
         let callback = self.box_method(&name, method);
         self.meta_methods.push((name, callback));
     }
@@ -537,6 +896,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "meta method", true);
         let callback = self.box_method_mut(&name, method);
         self.meta_methods.push((name, callback));
     }
@@ -551,6 +911,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "async meta method", true);
         let callback = self.box_async_method(&name, method);
         self.async_meta_methods.push((name, callback));
     }
@@ -565,6 +926,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "async meta method", true);
         let callback = self.box_async_method_mut(&name, method);
         self.async_meta_methods.push((name, callback));
     }
@@ -576,6 +938,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "meta function", false);
         let callback = self.box_function(&name, function);
         self.meta_methods.push((name, callback));
     }
@@ -587,6 +950,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "meta function", false);
         let callback = self.box_function_mut(&name, function);
         self.meta_methods.push((name, callback));
     }
@@ -600,6 +964,7 @@ impl<T> UserDataMethods<T> for UserDataRegistry<T> {
         R: IntoLuaMulti,
     {
         let name = name.to_string();
+        self.track_meta::<A, R>(&name, "async meta function", false);
         let callback = self.box_async_function(&name, function);
         self.async_meta_methods.push((name, callback));
     }
@@ -622,6 +987,7 @@ macro_rules! lua_userdata_impl {
                 #[cfg(feature = "async")]
                 registry.async_methods.extend(orig_registry.async_methods);
                 registry.meta_methods.extend(orig_registry.meta_methods);
+                registry.meta_checks.extend(orig_registry.meta_checks);
                 #[cfg(feature = "async")]
                 registry
                     .async_meta_methods
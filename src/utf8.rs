@@ -0,0 +1,254 @@
+//! UTF-8 utilities equivalent to the Lua 5.3+ `utf8` library.
+//!
+//! The functions operate on byte slices (Lua strings are not required to be valid UTF-8) and
+//! use 0-based byte positions. Decoding is strict: overlong encodings, surrogates and code
+//! points above `U+10FFFF` are rejected.
+//!
+//! The Lua-visible `utf8` table can be registered on Lua versions without one (Lua 5.1, Lua 5.2
+//! and LuaJIT) using [`Lua::install_utf8`].
+//!
+//! [`Lua::install_utf8`]: crate::Lua::install_utf8
+
+use std::str;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::string::String;
+use crate::types::Integer;
+use crate::value::{MultiValue, Value};
+
+// Pattern matching exactly one UTF-8 byte sequence (`utf8.charpattern`)
+#[cfg(not(any(feature = "lua51", feature = "luajit")))]
+const CHAR_PATTERN: &[u8] = b"[\0-\x7F\xC2-\xF4][\x80-\xBF]*";
+
+// Lua 5.1 patterns cannot contain `\0`, `%z` matches it instead
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+const CHAR_PATTERN: &[u8] = b"[%z\x01-\x7F\xC2-\xF4][\x80-\xBF]*";
+
+/// Decodes the UTF-8 character at the beginning of `bytes`.
+///
+/// Returns the character and its length in bytes, or `None` if `bytes` does not start with a
+/// valid UTF-8 sequence.
+pub fn decode(bytes: &[u8]) -> Option<(char, usize)> {
+    let width = match *bytes.first()? {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return None,
+    };
+    let s = str::from_utf8(bytes.get(..width)?).ok()?;
+    s.chars().next().map(|c| (c, width))
+}
+
+/// Returns the number of UTF-8 characters in `bytes`.
+///
+/// If `bytes` contains an invalid sequence, returns the position of its first byte as an error.
+pub fn len(bytes: &[u8]) -> std::result::Result<usize, usize> {
+    let (mut pos, mut n) = (0, 0);
+    while pos < bytes.len() {
+        let (_, width) = decode(&bytes[pos..]).ok_or(pos)?;
+        pos += width;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// Returns the position where the `n`-th character (counting from position `i`) starts.
+///
+/// This is the 0-based equivalent of `utf8.offset(s, n, i + 1)`: positive `n` counts forward
+/// (`n = 1` is the character at `i`), negative `n` counts backward from `i` and `n = 0` returns the
+/// start of the character containing byte `i`. Position `bytes.len()` (the end of the string) is
+/// a valid result. Returns `None` if there are not enough characters or `i` is out of bounds.
+pub fn offset(bytes: &[u8], mut n: isize, i: usize) -> Option<usize> {
+    let is_cont = |pos: usize| bytes.get(pos).is_some_and(|b| b & 0xC0 == 0x80);
+    let mut pos = i;
+    if pos > bytes.len() {
+        return None;
+    }
+    if n == 0 {
+        while pos > 0 && is_cont(pos) {
+            pos -= 1;
+        }
+        return Some(pos);
+    }
+    if is_cont(pos) {
+        return None;
+    }
+    if n < 0 {
+        while n < 0 && pos > 0 {
+            pos -= 1;
+            while pos > 0 && is_cont(pos) {
+                pos -= 1;
+            }
+            n += 1;
+        }
+    } else {
+        n -= 1;
+        while n > 0 && pos < bytes.len() {
+            pos += 1;
+            while is_cont(pos) {
+                pos += 1;
+            }
+            n -= 1;
+        }
+    }
+    (n == 0).then_some(pos)
+}
+
+// Registry flag set when the `utf8` table is registered
+const INSTALLED_KEY: &str = "__mlua_utf8_installed";
+
+// Registers the `utf8` global table implemented in Rust, unless the table already exists
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    if lua.named_registry_value::<bool>(INSTALLED_KEY)? {
+        return Ok(());
+    }
+    let globals = lua.globals();
+    if globals.contains_key("utf8")? {
+        return Ok(());
+    }
+
+    let utf8 = lua.create_table()?;
+    utf8.set("charpattern", lua.create_string(CHAR_PATTERN)?)?;
+    utf8.set("char", lua.create_function(utf8_char)?)?;
+    utf8.set("codepoint", lua.create_function(utf8_codepoint)?)?;
+    utf8.set("len", lua.create_function(utf8_len)?)?;
+    utf8.set("offset", lua.create_function(utf8_offset)?)?;
+    utf8.set("codes", lua.create_function(utf8_codes)?)?;
+    globals.set("utf8", utf8)?;
+    lua.set_named_registry_value(INSTALLED_KEY, true)
+}
+
+// Implements `utf8.char(...)`
+fn utf8_char(lua: &Lua, codes: MultiValue) -> Result<String> {
+    let mut s = std::string::String::new();
+    for (i, code) in codes.iter().enumerate() {
+        let code = integer_arg(code, i, "char")?;
+        let c = u32::try_from(code).ok().and_then(char::from_u32);
+        s.push(c.ok_or_else(|| bad_argument(i, "char", "value out of range"))?);
+    }
+    lua.create_string(s)
+}
+
+// Implements `utf8.codepoint(s [, i [, j]])`
+fn utf8_codepoint(_: &Lua, (s, i, j): (String, Option<Integer>, Option<Integer>)) -> Result<MultiValue> {
+    let bytes = s.as_bytes();
+    let len = bytes.len() as i64;
+    let posi = relative_position(i.map_or(1, i64::from), len);
+    let pose = relative_position(j.map_or(posi, i64::from), len);
+    if posi < 1 {
+        return Err(bad_argument(1, "codepoint", "out of bounds"));
+    }
+    if pose > len {
+        return Err(bad_argument(2, "codepoint", "out of bounds"));
+    }
+
+    let mut codes = MultiValue::new();
+    let mut pos = posi as usize - 1;
+    while pos < pose as usize {
+        let (c, width) = decode(&bytes[pos..]).ok_or_else(|| Error::runtime("invalid UTF-8 code"))?;
+        codes.push_back(Value::Integer(c as Integer));
+        pos += width;
+    }
+    Ok(codes)
+}
+
+// Implements `utf8.len(s [, i [, j]])`
+fn utf8_len(_: &Lua, (s, i, j): (String, Option<Integer>, Option<Integer>)) -> Result<MultiValue> {
+    let bytes = s.as_bytes();
+    let len = bytes.len() as i64;
+    let posi = relative_position(i.map_or(1, i64::from), len);
+    let posj = relative_position(j.map_or(-1, i64::from), len);
+    if posi < 1 || posi - 1 > len {
+        return Err(bad_argument(1, "len", "initial position out of bounds"));
+    }
+    if posj > len {
+        return Err(bad_argument(2, "len", "final position out of bounds"));
+    }
+
+    let (mut pos, mut n) = (posi as usize - 1, 0);
+    while (pos as i64) < posj {
+        match decode(&bytes[pos..]) {
+            Some((_, width)) => pos += width,
+            None => {
+                return Ok(MultiValue::from_iter([
+                    Value::Nil,
+                    Value::Integer(pos as Integer + 1),
+                ]))
+            }
+        }
+        n += 1;
+    }
+    Ok(MultiValue::from_iter([Value::Integer(n)]))
+}
+
+// Implements `utf8.offset(s, n [, i])`
+fn utf8_offset(_: &Lua, (s, n, i): (String, Integer, Option<Integer>)) -> Result<Option<Integer>> {
+    let bytes = s.as_bytes();
+    let len = bytes.len() as i64;
+    let default_i = if n >= 0 { 1 } else { len + 1 };
+    let posi = relative_position(i.map_or(default_i, i64::from), len);
+    if posi < 1 || posi - 1 > len {
+        return Err(bad_argument(2, "offset", "position out of bounds"));
+    }
+    let pos = posi as usize - 1;
+    if n != 0 && bytes.get(pos).is_some_and(|b| b & 0xC0 == 0x80) {
+        return Err(Error::runtime("initial position is a continuation byte"));
+    }
+    Ok(offset(&bytes, n as isize, pos).map(|pos| pos as Integer + 1))
+}
+
+// Implements `utf8.codes(s)`
+fn utf8_codes(lua: &Lua, s: String) -> Result<(Function, String, Integer)> {
+    let iter = lua.create_function(|_, (s, i): (String, Integer)| {
+        let bytes = s.as_bytes();
+        // Skip the character at the previous position
+        #[allow(clippy::useless_conversion)]
+        let mut pos = i64::from(i) - 1;
+        if pos < 0 {
+            pos = 0;
+        } else if (pos as usize) < bytes.len() {
+            pos += 1;
+            while bytes.get(pos as usize).is_some_and(|b| b & 0xC0 == 0x80) {
+                pos += 1;
+            }
+        }
+        let pos = pos as usize;
+        if pos >= bytes.len() {
+            return Ok((None, None));
+        }
+        let (c, _) = decode(&bytes[pos..]).ok_or_else(|| Error::runtime("invalid UTF-8 code"))?;
+        Ok((Some(pos as Integer + 1), Some(c as Integer)))
+    })?;
+    Ok((iter, s, 0))
+}
+
+// Converts a relative (negative) string position to the absolute one
+fn relative_position(pos: i64, len: i64) -> i64 {
+    if pos >= 0 {
+        pos
+    } else if -pos > len {
+        0
+    } else {
+        len + pos + 1
+    }
+}
+
+fn integer_arg(value: &Value, i: usize, name: &str) -> Result<i64> {
+    match *value {
+        #[allow(clippy::useless_conversion)]
+        Value::Integer(n) => Ok(n.into()),
+        Value::Number(n) if n.fract() == 0.0 => Ok(n as i64),
+        _ => Err(bad_argument(
+            i,
+            name,
+            &format!("number expected, got {}", value.type_name()),
+        )),
+    }
+}
+
+fn bad_argument(i: usize, name: &str, msg: &str) -> Error {
+    Error::runtime(format!("bad argument #{} to '{name}' ({msg})", i + 1))
+}
@@ -1,13 +1,14 @@
 use std::any::Any;
 use std::fmt::Write as _;
-use std::mem::MaybeUninit;
-use std::os::raw::{c_int, c_void};
+use std::mem::{self, MaybeUninit};
+use std::os::raw::{c_char, c_int, c_void};
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::ptr;
 use std::sync::Arc;
 
 use crate::error::{Error, Result};
 use crate::memory::MemoryState;
+use crate::state::ExtraData;
 use crate::util::{
     check_stack, get_internal_metatable, get_internal_userdata, init_internal_metatable,
     push_internal_userdata, push_string, push_table, rawset_field, to_string, TypeKey,
@@ -19,7 +20,8 @@ static WRAPPED_FAILURE_TYPE_KEY: u8 = 0;
 pub(crate) enum WrappedFailure {
     None,
     Error(Error),
-    Panic(Option<Box<dyn Any + Send + 'static>>),
+    // Panic payload and the Lua context (location and traceback) where it happened
+    Panic(Option<Box<dyn Any + Send + 'static>>, String),
 }
 
 impl TypeKey for WrappedFailure {
@@ -97,7 +99,11 @@ where
         }
         Err(p) => {
             ffi::lua_settop(state, 1);
-            ptr::write(ud, WrappedFailure::Panic(Some(p)));
+            if ExtraData::abort_on_panic(state) {
+                std::process::abort();
+            }
+            let context = panic_context(state);
+            ptr::write(ud, WrappedFailure::Panic(Some(p), context));
             get_internal_metatable::<WrappedFailure>(state);
             ffi::lua_setmetatable(state, -2);
             ffi::lua_error(state)
@@ -105,6 +111,52 @@ where
     }
 }
 
+// Returns the Lua context of a Rust panic raised in a callback: the location of the calling Lua code
+// (if any) and the call stack traceback.
+// Uses 2 stack spaces, does not call checkstack (but checks the space needed for traceback).
+pub(crate) unsafe fn panic_context(state: *mut ffi::lua_State) -> String {
+    if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) == 0 {
+        return "<not enough stack space for traceback>".to_string();
+    }
+
+    let mut context = String::new();
+    // Find the innermost Lua function in the call stack (skipping `pcall` and other C functions)
+    if let Some(location) = caller_location(state) {
+        let _ = writeln!(context, "panicked at {location}");
+    }
+    ffi::luaL_traceback(state, state, ptr::null(), 0);
+    context.push_str(&to_string(state, -1));
+    ffi::lua_pop(state, 1);
+    context
+}
+
+// Returns location (`source:line`) of the innermost Lua function in the call stack
+pub(crate) unsafe fn caller_location(state: *mut ffi::lua_State) -> Option<String> {
+    if ffi::lua_checkstack(state, 1) == 0 {
+        return None;
+    }
+    let mut level = 1;
+    while stack_level_exists(state, level) {
+        ffi::luaL_where(state, level);
+        let location = to_string(state, -1);
+        ffi::lua_pop(state, 1);
+        let location = location.trim_end_matches([':', ' ']);
+        if !location.is_empty() {
+            return Some(location.to_string());
+        }
+        level += 1;
+    }
+    None
+}
+
+pub(crate) unsafe fn stack_level_exists(state: *mut ffi::lua_State, level: c_int) -> bool {
+    let mut ar: ffi::lua_Debug = mem::zeroed();
+    #[cfg(not(feature = "luau"))]
+    return ffi::lua_getstack(state, level, &mut ar) != 0;
+    #[cfg(feature = "luau")]
+    return ffi::lua_getinfo(state, level, cstr!(""), &mut ar) != 0;
+}
+
 // Pops an error off of the stack and returns it. The specific behavior depends on the type of the
 // error at the top of the stack:
 //   1) If the error is actually a panic, this will continue the panic.
@@ -122,7 +174,7 @@ pub(crate) unsafe fn pop_error(state: *mut ffi::lua_State, err_code: c_int) -> E
             ffi::lua_pop(state, 1);
             err.clone()
         }
-        Some(WrappedFailure::Panic(panic)) => {
+        Some(WrappedFailure::Panic(panic, _)) => {
             if let Some(p) = panic.take() {
                 resume_unwind(p);
             } else {
@@ -275,7 +327,7 @@ pub(crate) unsafe extern "C-unwind" fn error_traceback(state: *mut ffi::lua_Stat
     }
 
     if get_internal_userdata::<WrappedFailure>(state, -1, ptr::null()).is_null() {
-        let s = ffi::luaL_tolstring(state, -1, ptr::null_mut());
+        let s = error_to_string(state);
         if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
             ffi::luaL_traceback(state, state, s, 0);
             ffi::lua_remove(state, -2);
@@ -285,13 +337,34 @@ pub(crate) unsafe extern "C-unwind" fn error_traceback(state: *mut ffi::lua_Stat
     1
 }
 
+// Converts the error object on top of the stack to a string and pushes it onto the stack.
+// Hides the object address if requested (see `Lua::set_deterministic`).
+unsafe fn error_to_string(state: *mut ffi::lua_State) -> *const c_char {
+    let has_address = matches!(
+        ffi::lua_type(state, -1),
+        ffi::LUA_TTABLE
+            | ffi::LUA_TFUNCTION
+            | ffi::LUA_TTHREAD
+            | ffi::LUA_TUSERDATA
+            | ffi::LUA_TLIGHTUSERDATA
+    );
+    if has_address && ExtraData::hide_addresses(state) {
+        if ffi::luaL_getmetafield(state, -1, cstr!("__tostring")) == ffi::LUA_TNIL {
+            let type_name = ffi::luaL_typename(state, -1);
+            return ffi::lua_pushfstring(state, cstr!("(error object is a %s value)"), type_name);
+        }
+        ffi::lua_pop(state, 1);
+    }
+    ffi::luaL_tolstring(state, -1, ptr::null_mut())
+}
+
 // A variant of `error_traceback` that can safely inspect another (yielded) thread stack
 pub(crate) unsafe fn error_traceback_thread(state: *mut ffi::lua_State, thread: *mut ffi::lua_State) {
     // Move error object to the main thread to safely call `__tostring` metamethod if present
     ffi::lua_xmove(thread, state, 1);
 
     if get_internal_userdata::<WrappedFailure>(state, -1, ptr::null()).is_null() {
-        let s = ffi::luaL_tolstring(state, -1, ptr::null_mut());
+        let s = error_to_string(state);
         if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
             ffi::luaL_traceback(state, thread, s, 0);
             ffi::lua_remove(state, -2);
@@ -325,7 +398,7 @@ pub(crate) unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<(
                     let _ = write!(&mut (*err_buf), "{error}");
                     Ok(err_buf)
                 }
-                Some(WrappedFailure::Panic(Some(ref panic))) => {
+                Some(WrappedFailure::Panic(Some(ref panic), ref context)) => {
                     let err_buf_key = &ERROR_PRINT_BUFFER_KEY as *const u8 as *const c_void;
                     ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, err_buf_key);
                     let err_buf = ffi::lua_touserdata(state, -1) as *mut String;
@@ -339,9 +412,12 @@ pub(crate) unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<(
                     } else {
                         let _ = write!(&mut (*err_buf), "<panic>");
                     };
+                    if !context.is_empty() {
+                        let _ = write!(&mut (*err_buf), "\n{context}");
+                    }
                     Ok(err_buf)
                 }
-                Some(WrappedFailure::Panic(None)) => Err(Error::PreviouslyResumedPanic),
+                Some(WrappedFailure::Panic(None, _)) => Err(Error::PreviouslyResumedPanic),
                 _ => {
                     // I'm not sure whether this is possible to trigger without bugs in mlua?
                     Err(Error::UserDataTypeMismatch)
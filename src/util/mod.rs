@@ -6,8 +6,8 @@ use std::{ptr, slice, str};
 use crate::error::{Error, Result};
 
 pub(crate) use error::{
-    error_traceback, error_traceback_thread, init_error_registry, pop_error, protect_lua_call,
-    protect_lua_closure, WrappedFailure,
+    caller_location, error_traceback, error_traceback_thread, init_error_registry, panic_context, pop_error,
+    protect_lua_call, protect_lua_closure, stack_level_exists, WrappedFailure,
 };
 pub(crate) use short_names::short_type_name;
 pub(crate) use types::TypeKey;
@@ -16,6 +16,8 @@ pub(crate) use userdata::{
     init_internal_metatable, init_userdata_metatable, push_internal_userdata, take_userdata,
     DESTRUCTED_USERDATA_METATABLE,
 };
+#[cfg(feature = "async")]
+pub(crate) use waiters::Waiters;
 
 #[cfg(not(feature = "luau"))]
 pub(crate) use userdata::push_uninit_userdata;
@@ -155,7 +157,7 @@ pub(crate) unsafe extern "C-unwind" fn safe_pcall(state: *mut ffi::lua_State) ->
         ffi::lua_gettop(state)
     } else {
         let wf_ud = get_internal_userdata::<WrappedFailure>(state, -1, ptr::null());
-        if let Some(WrappedFailure::Panic(_)) = wf_ud.as_ref() {
+        if let Some(WrappedFailure::Panic(..)) = wf_ud.as_ref() {
             ffi::lua_error(state);
         }
         ffi::lua_pushboolean(state, 0);
@@ -170,7 +172,7 @@ pub(crate) unsafe extern "C-unwind" fn safe_xpcall(state: *mut ffi::lua_State) -
         ffi::luaL_checkstack(state, 2, ptr::null());
 
         let wf_ud = get_internal_userdata::<WrappedFailure>(state, -1, ptr::null());
-        if let Some(WrappedFailure::Panic(_)) = wf_ud.as_ref() {
+        if let Some(WrappedFailure::Panic(..)) = wf_ud.as_ref() {
             1
         } else {
             ffi::lua_pushvalue(state, ffi::lua_upvalueindex(1));
@@ -199,7 +201,7 @@ pub(crate) unsafe extern "C-unwind" fn safe_xpcall(state: *mut ffi::lua_State) -
         ffi::lua_gettop(state) - 1
     } else {
         let wf_ud = get_internal_userdata::<WrappedFailure>(state, -1, ptr::null());
-        if let Some(WrappedFailure::Panic(_)) = wf_ud.as_ref() {
+        if let Some(WrappedFailure::Panic(..)) = wf_ud.as_ref() {
             ffi::lua_error(state);
         }
         ffi::lua_pushboolean(state, 0);
@@ -306,3 +308,5 @@ mod error;
 mod short_names;
 mod types;
 mod userdata;
+#[cfg(feature = "async")]
+mod waiters;
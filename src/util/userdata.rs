@@ -149,14 +149,22 @@ pub(crate) unsafe fn get_destructed_userdata_metatable(state: *mut ffi::lua_Stat
 // access. The function also, if given a `field_getters` or `methods` tables, will create an
 // `__index` metamethod (capturing previous one) to lookup in `field_getters` first, then `methods`
 // and falling back to the captured `__index` if no matches found.
+// Methods resolved by the generated `__index` are stored in a per-metatable lookup cache. If
+// `cache_index` is set, values found in the captured `__index` table are cached as well (it must be
+// used only when the table is owned by mlua and never changed).
+// With `feature = "metrics"` the cache hit statistics are stored in the metatable under the
+// `__mlua_method_cache` key.
+// Field getters and the captured `__index` function are tail called, so all values returned by
+// them are propagated to the caller.
 // The same is also applicable for `__newindex` metamethod and `field_setters` table.
-// Internally uses 9 stack spaces and does not call checkstack.
+// Internally uses 11 stack spaces and does not call checkstack.
 pub(crate) unsafe fn init_userdata_metatable(
     state: *mut ffi::lua_State,
     metatable: c_int,
     field_getters: Option<c_int>,
     field_setters: Option<c_int>,
     methods: Option<c_int>,
+    cache_index: bool,
     extra_init: Option<fn(*mut ffi::lua_State) -> Result<()>>,
 ) -> Result<()> {
     ffi::lua_pushvalue(state, metatable);
@@ -176,13 +184,16 @@ pub(crate) unsafe fn init_userdata_metatable(
                         ffi::lua_pushnil(state);
                     }
                 }
+                ffi::lua_pushboolean(state, cache_index as c_int);
+                ffi::lua_pushboolean(state, cfg!(feature = "metrics") as c_int);
 
-                // Generate `__index`
-                protect_lua!(state, 4, 1, fn(state) ffi::lua_call(state, 3, 1))?;
+                // Generate `__index` (and the cache statistics table)
+                protect_lua!(state, 6, 2, fn(state) ffi::lua_call(state, 5, 2))?;
             }
             _ => mlua_panic!("improper __index type {}", index_type),
         }
 
+        rawset_field(state, -3, "__mlua_method_cache")?;
         rawset_field(state, -2, "__index")?;
     }
 
@@ -242,19 +253,50 @@ unsafe fn init_userdata_metatable_index(state: *mut ffi::lua_State) -> Result<()
     let code = cstr!(
         r#"
             local error, isfunction, istable = ...
-            return function (__index, field_getters, methods)
+            return function (__index, field_getters, methods, cache_index, track_stats)
+                -- Method lookup cache and its statistics (hits, misses) if tracked
+                local cache, stats = {}, track_stats and {0, 0} or nil
+
                 -- Common case: has field getters and index is a table
                 if field_getters ~= nil and methods == nil and istable(__index) then
+                    if not cache_index then
+                        return function (self, key)
+                            local field_getter = field_getters[key]
+                            if field_getter ~= nil then
+                                return field_getter(self)
+                            end
+                            return __index[key]
+                        end, stats
+                    end
+
                     return function (self, key)
+                        local value = cache[key]
+                        if value ~= nil then
+                            if stats then stats[1] = stats[1] + 1 end
+                            return value
+                        end
+
                         local field_getter = field_getters[key]
                         if field_getter ~= nil then
                             return field_getter(self)
                         end
-                        return __index[key]
-                    end
+
+                        value = __index[key]
+                        if value ~= nil then
+                            if stats then stats[2] = stats[2] + 1 end
+                            cache[key] = value
+                        end
+                        return value
+                    end, stats
                 end
 
                 return function (self, key)
+                    local method = cache[key]
+                    if method ~= nil then
+                        if stats then stats[1] = stats[1] + 1 end
+                        return method
+                    end
+
                     if field_getters ~= nil then
                         local field_getter = field_getters[key]
                         if field_getter ~= nil then
@@ -263,8 +305,10 @@ unsafe fn init_userdata_metatable_index(state: *mut ffi::lua_State) -> Result<()
                     end
 
                     if methods ~= nil then
-                        local method = methods[key]
+                        method = methods[key]
                         if method ~= nil then
+                            if stats then stats[2] = stats[2] + 1 end
+                            cache[key] = method
                             return method
                         end
                     end
@@ -276,7 +320,7 @@ unsafe fn init_userdata_metatable_index(state: *mut ffi::lua_State) -> Result<()
                     else
                         return __index[key]
                     end
-                end
+                end, stats
             end
     "#
     );
@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+use std::task::Waker;
+
+// FIFO queue of tasks waiting for a resource or an event.
+//
+// Each task is identified by an id assigned on the first registration, which the task keeps to
+// update its waker and to remove itself from the queue when it's done (or cancelled).
+#[derive(Default)]
+pub(crate) struct Waiters {
+    next_id: u64,
+    queue: VecDeque<(u64, Waker)>,
+}
+
+impl Waiters {
+    // Adds the task to the queue or updates its waker
+    pub(crate) fn register(&mut self, id: &mut Option<u64>, waker: &Waker) {
+        match *id {
+            Some(id) => {
+                if let Some((_, w)) = self.queue.iter_mut().find(|(i, _)| *i == id) {
+                    w.clone_from(waker);
+                    return;
+                }
+                self.queue.push_back((id, waker.clone()));
+            }
+            None => {
+                self.next_id += 1;
+                self.queue.push_back((self.next_id, waker.clone()));
+                *id = Some(self.next_id);
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, id: Option<u64>) {
+        if let Some(id) = id {
+            self.queue.retain(|(i, _)| *i != id);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub(crate) fn is_first(&self, id: Option<u64>) -> bool {
+        match id {
+            Some(id) => self.queue.front().map(|(i, _)| *i) == Some(id),
+            None => self.queue.is_empty(),
+        }
+    }
+
+    pub(crate) fn first(&self) -> Option<Waker> {
+        self.queue.front().map(|(_, waker)| waker.clone())
+    }
+
+    pub(crate) fn all(&self) -> Vec<Waker> {
+        self.queue.iter().map(|(_, waker)| waker.clone()).collect()
+    }
+
+    // Removes all tasks from the queue, returning their wakers
+    pub(crate) fn take_all(&mut self) -> Vec<Waker> {
+        self.queue.drain(..).map(|(_, waker)| waker).collect()
+    }
+}
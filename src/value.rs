@@ -10,6 +10,7 @@ use num_traits::FromPrimitive;
 
 use crate::error::{Error, Result};
 use crate::function::Function;
+use crate::hash::{HashAlgorithm, HashOptions};
 use crate::state::{Lua, RawLua};
 use crate::string::{BorrowedStr, String};
 use crate::table::Table;
@@ -73,6 +74,43 @@ pub enum Value {
 
 pub use self::Value::Nil;
 
+/// Policy for choosing between [`Value::Integer`] and [`Value::Number`] representation of numbers,
+/// see [`Value::canonicalize_numbers`].
+///
+/// Lua versions differ in how they represent numbers (Lua 5.1 and LuaJIT have no integer subtype,
+/// while Lua 5.3+ keep `1` and `1.0` apart), so host code branching on the variant can behave
+/// differently across versions. Canonicalizing numbers first makes such decisions consistent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NumberPolicy {
+    /// Represent numbers with an exact integer value as [`Value::Integer`].
+    ///
+    /// Floats are converted only if they are in the [`Integer`] range, `-0.0` becomes `0`.
+    #[default]
+    PreferInteger,
+    /// Represent numbers as [`Value::Number`].
+    ///
+    /// Integers are converted only if they can be represented as a float without loss of
+    /// precision.
+    PreferFloat,
+}
+
+macro_rules! try_into_int {
+    ($name:ident, $t:ty) => {
+        #[doc = concat!("Converts the value to `", stringify!($t), "`, checking that it fits into the type.")]
+        ///
+        /// Accepts Lua integers and floats with an integral value. If the number is out of range,
+        /// returns [`Error::OutOfRange`] carrying the original value and the `label` (e.g. name of
+        /// an argument), which is used in the error message.
+        ///
+        /// [`Error::OutOfRange`]: crate::Error::OutOfRange
+        pub fn $name(&self, label: &str) -> Result<$t> {
+            let n = crate::ranged::check_range(self, <$t>::MIN as i128, <$t>::MAX as i128, Some(label))?;
+            Ok(n as $t)
+        }
+    };
+}
+
 impl Value {
     /// A special value (lightuserdata) to represent null value.
     ///
@@ -142,6 +180,38 @@ impl Value {
         }
     }
 
+    /// Computes a stable structural hash of the value using the given `algorithm`.
+    ///
+    /// Supported values are `nil`, booleans, numbers, strings and tables (recursively).
+    /// Tables are hashed by their content, so two distinct tables with equal keys and values have
+    /// the same hash regardless of insertion or iteration order. Numbers with an integer
+    /// representation have the same hash as the corresponding integers. Cycles are supported.
+    ///
+    /// Returns an error if the value contains a function, thread or userdata.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{HashAlgorithm, Lua, Result, Value};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let a: Value = lua.load("{ x = 1, y = { 'a', 'b' } }").eval()?;
+    /// let b: Value = lua.load("{ y = { 'a', 'b' }, x = 1.0 }").eval()?;
+    /// assert_eq!(a.hash(HashAlgorithm::Fnv1a64)?, b.hash(HashAlgorithm::Fnv1a64)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hash(&self, algorithm: HashAlgorithm) -> Result<u64> {
+        crate::hash::hash_value(self, algorithm, HashOptions::new())
+    }
+
+    /// Computes a stable structural hash of the value using the given `algorithm` and `options`.
+    ///
+    /// See [`Value::hash`] for details.
+    pub fn hash_with(&self, algorithm: HashAlgorithm, options: HashOptions) -> Result<u64> {
+        crate::hash::hash_value(self, algorithm, options)
+    }
+
     /// Converts the value to a string.
     ///
     /// If the value has a metatable with a `__tostring` method, then it will be called to get the
@@ -290,6 +360,15 @@ impl Value {
         self.as_integer().and_then(|i| usize::try_from(i).ok())
     }
 
+    try_into_int!(try_into_i8, i8);
+    try_into_int!(try_into_i16, i16);
+    try_into_int!(try_into_i32, i32);
+    try_into_int!(try_into_i64, i64);
+    try_into_int!(try_into_u8, u8);
+    try_into_int!(try_into_u16, u16);
+    try_into_int!(try_into_u32, u32);
+    try_into_int!(try_into_u64, u64);
+
     /// Returns `true` if the value is a Lua [`Number`].
     #[inline]
     pub fn is_number(&self) -> bool {
@@ -323,6 +402,46 @@ impl Value {
         self.as_number()
     }
 
+    /// Cast the value to [`Number`] without loss of precision.
+    ///
+    /// If the value is a Lua [`Number`], returns it. If the value is a Lua [`Integer`] that can be
+    /// represented as a float exactly, returns it as a float. Otherwise returns `None`.
+    pub fn as_number_lossless(&self) -> Option<Number> {
+        match *self {
+            Value::Integer(i) => integer_to_number(i),
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Converts numeric value to the representation chosen by the `policy`.
+    ///
+    /// Numbers that cannot be converted exactly keep their representation, non-numeric values
+    /// are returned unchanged. Tables are not traversed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mlua::{NumberPolicy, Value};
+    ///
+    /// let value = Value::Number(2.0).canonicalize_numbers(NumberPolicy::PreferInteger);
+    /// assert!(matches!(value, Value::Integer(2)));
+    ///
+    /// let value = Value::Integer(2).canonicalize_numbers(NumberPolicy::PreferFloat);
+    /// assert!(matches!(value, Value::Number(n) if n == 2.0));
+    /// ```
+    pub fn canonicalize_numbers(&self, policy: NumberPolicy) -> Value {
+        match (policy, self) {
+            (NumberPolicy::PreferInteger, &Value::Number(n)) => number_to_integer(n)
+                .map(Value::Integer)
+                .unwrap_or(Value::Number(n)),
+            (NumberPolicy::PreferFloat, &Value::Integer(i)) => integer_to_number(i)
+                .map(Value::Number)
+                .unwrap_or(Value::Integer(i)),
+            _ => self.clone(),
+        }
+    }
+
     /// Returns `true` if the value is a Lua [`String`].
     #[inline]
     pub fn is_string(&self) -> bool {
@@ -550,6 +669,20 @@ impl Value {
     }
 }
 
+// Upper (exclusive) bound of the `Integer` range as a float
+const INTEGER_BOUND: Number = -(Integer::MIN as Number);
+
+// Converts a float to `Integer` if it has an exact integer value in the `Integer` range
+fn number_to_integer(n: Number) -> Option<Integer> {
+    (n.fract() == 0.0 && (-INTEGER_BOUND..INTEGER_BOUND).contains(&n)).then_some(n as Integer)
+}
+
+// Converts an integer to `Number` if it can be represented exactly
+fn integer_to_number(i: Integer) -> Option<Number> {
+    let n = i as Number;
+    (n < INTEGER_BOUND && n as Integer == i).then_some(n)
+}
+
 impl fmt::Debug for Value {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         if fmt.alternate() {
@@ -809,6 +942,49 @@ impl MultiValue {
         MultiValue(VecDeque::with_capacity(capacity))
     }
 
+    /// Removes the first `n` values and converts them to `A`.
+    ///
+    /// The remaining values are kept, so functions accepting a few fixed arguments followed by
+    /// variadic arguments of mixed types can parse them step by step without copying. Missing
+    /// values are treated as `nil`, and conversion errors report the argument positions starting
+    /// from 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, MultiValue, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let format = lua.create_function(|lua, mut args: MultiValue| {
+    ///     let (sep, count): (String, usize) = args.split_first_n(2, lua)?;
+    ///     let items = (args.rest(0).take(count))
+    ///         .map(|v| v.to_string())
+    ///         .collect::<Result<Vec<_>>>()?;
+    ///     Ok(items.join(&sep))
+    /// })?;
+    /// let s: String = format.call((", ", 2, 1, "a", true))?;
+    /// assert_eq!(s, "1, a");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn split_first_n<A: FromLuaMulti>(&mut self, n: usize, lua: &Lua) -> Result<A> {
+        let rest = self.0.split_off(n.min(self.0.len()));
+        let first = MultiValue(mem::replace(&mut self.0, rest));
+        A::from_lua_args(first, 1, None, lua)
+    }
+
+    /// Returns an iterator over the values starting at the `start` index (0-based).
+    ///
+    /// If `start` is greater than the number of values, the iterator is empty.
+    pub fn rest(&self, start: usize) -> vec_deque::Iter<'_, Value> {
+        self.0.range(start.min(self.0.len())..)
+    }
+
+    /// Converts the `MultiValue` into a vector of values.
+    pub fn into_vec(self) -> Vec<Value> {
+        self.into()
+    }
+
     #[inline]
     pub(crate) fn from_lua_iter<T: IntoLua>(lua: &Lua, iter: impl IntoIterator<Item = T>) -> Result<Self> {
         let iter = iter.into_iter();
@@ -820,6 +996,20 @@ impl MultiValue {
     }
 }
 
+impl From<Vec<Value>> for MultiValue {
+    #[inline]
+    fn from(values: Vec<Value>) -> Self {
+        MultiValue(VecDeque::from(values))
+    }
+}
+
+impl From<MultiValue> for Vec<Value> {
+    #[inline]
+    fn from(mut values: MultiValue) -> Self {
+        Vec::from(mem::take(&mut values.0))
+    }
+}
+
 impl FromIterator<Value> for MultiValue {
     #[inline]
     fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
@@ -920,11 +1110,33 @@ pub trait FromLuaMulti: Sized {
     #[doc(hidden)]
     #[inline]
     unsafe fn from_stack_args(nargs: c_int, i: usize, to: Option<&str>, lua: &RawLua) -> Result<Self> {
-        let _ = (i, to);
-        Self::from_stack_multi(nargs, lua)
+        let mut args = MultiValue::with_capacity(nargs as usize);
+        for idx in 0..nargs {
+            args.push_back(lua.stack_value(-nargs + idx, None));
+        }
+        if nargs > 0 {
+            // It's safe to clear the stack as all references moved to ref thread
+            ffi::lua_pop(lua.state(), nargs);
+        }
+        Self::from_lua_args(args, i, to, lua.lua())
     }
 }
 
+/// Trait for types that can be created from a table of named arguments or positional arguments.
+///
+/// This trait is used by the [`NamedArgs`] wrapper and usually implemented using the
+/// [`FromLuaTable`] derive macro.
+///
+/// [`NamedArgs`]: crate::NamedArgs
+/// [`FromLuaTable`]: macro@crate::FromLuaTable
+pub trait FromLuaTable: Sized {
+    /// Performs the conversion from a table of named arguments.
+    fn from_lua_table(table: Table, lua: &Lua) -> Result<Self>;
+
+    /// Performs the conversion from positional arguments.
+    fn from_lua_positional(args: MultiValue, lua: &Lua) -> Result<Self>;
+}
+
 #[cfg(test)]
 mod assertions {
     use super::*;
@@ -0,0 +1,121 @@
+use std::fmt;
+
+/// Lua implementation and version used by `mlua`.
+///
+/// See [`VersionInfo`] for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum LuaVersion {
+    /// Lua 5.1
+    Lua51,
+    /// Lua 5.2
+    Lua52,
+    /// Lua 5.3
+    Lua53,
+    /// Lua 5.4
+    Lua54,
+    /// LuaJIT (Lua 5.1 compatible, with some Lua 5.2 extensions)
+    LuaJIT,
+    /// Roblox Luau (derived from Lua 5.1)
+    Luau,
+}
+
+impl fmt::Display for LuaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LuaVersion::Lua51 => "Lua 5.1",
+            LuaVersion::Lua52 => "Lua 5.2",
+            LuaVersion::Lua53 => "Lua 5.3",
+            LuaVersion::Lua54 => "Lua 5.4",
+            LuaVersion::LuaJIT => "LuaJIT",
+            LuaVersion::Luau => "Luau",
+        })
+    }
+}
+
+/// Language and library features of the Lua version used by `mlua`.
+///
+/// Allows version-generic code to check for the features it needs instead of parsing the
+/// `_VERSION` global. The information describes what the Lua version supports, not whether the
+/// corresponding standard library was loaded into a particular state.
+///
+/// Returned by [`Lua::version_info`].
+///
+/// [`Lua::version_info`]: crate::Lua::version_info
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct VersionInfo {
+    /// The Lua implementation and version.
+    pub version: LuaVersion,
+    /// Numbers have a separate integer subtype (Lua 5.3+).
+    pub integers: bool,
+    /// Native bitwise operators (`&`, `|`, `~`, `<<`, `>>`) are available (Lua 5.3+).
+    pub bitwise_operators: bool,
+    /// A bitwise operations library is available (`bit32` in Lua 5.2 and Luau, `bit` in LuaJIT).
+    pub bit_library: bool,
+    /// Floor division operator `//` is available (Lua 5.3+, Luau).
+    pub floor_division: bool,
+    /// `goto` statement and labels are supported (Lua 5.2+, LuaJIT).
+    pub goto: bool,
+    /// `continue` statement is supported (Luau).
+    pub continue_statement: bool,
+    /// Variable attributes `<const>` and `<close>` (to-be-closed variables) are supported (Lua 5.4).
+    pub to_be_closed: bool,
+    /// Environment is represented by the `_ENV` upvalue instead of `setfenv`/`getfenv` (Lua 5.2+).
+    pub lexical_env: bool,
+    /// The `utf8` standard library is available (Lua 5.3+, Luau).
+    ///
+    /// On other versions it can be installed using [`Lua::install_utf8`].
+    ///
+    /// [`Lua::install_utf8`]: crate::Lua::install_utf8
+    pub native_utf8: bool,
+    /// `pcall` and metamethods can yield across coroutine boundaries (Lua 5.2+, LuaJIT, Luau).
+    pub yieldable_pcall: bool,
+    /// Functions can be dumped to bytecode with [`Function::dump`] and `string.dump` (all versions
+    /// except Luau).
+    ///
+    /// [`Function::dump`]: crate::Function::dump
+    pub bytecode_dump: bool,
+}
+
+impl VersionInfo {
+    pub(crate) const fn current() -> Self {
+        VersionInfo {
+            version: if cfg!(feature = "lua54") {
+                LuaVersion::Lua54
+            } else if cfg!(feature = "lua53") {
+                LuaVersion::Lua53
+            } else if cfg!(feature = "lua52") {
+                LuaVersion::Lua52
+            } else if cfg!(feature = "luajit") {
+                LuaVersion::LuaJIT
+            } else if cfg!(feature = "luau") {
+                LuaVersion::Luau
+            } else {
+                LuaVersion::Lua51
+            },
+            integers: cfg!(any(feature = "lua54", feature = "lua53")),
+            bitwise_operators: cfg!(any(feature = "lua54", feature = "lua53")),
+            bit_library: cfg!(any(feature = "lua52", feature = "luajit", feature = "luau")),
+            floor_division: cfg!(any(feature = "lua54", feature = "lua53", feature = "luau")),
+            goto: cfg!(any(
+                feature = "lua54",
+                feature = "lua53",
+                feature = "lua52",
+                feature = "luajit"
+            )),
+            continue_statement: cfg!(feature = "luau"),
+            to_be_closed: cfg!(feature = "lua54"),
+            lexical_env: cfg!(any(feature = "lua54", feature = "lua53", feature = "lua52")),
+            native_utf8: cfg!(any(feature = "lua54", feature = "lua53", feature = "luau")),
+            yieldable_pcall: cfg!(any(
+                feature = "lua54",
+                feature = "lua53",
+                feature = "lua52",
+                feature = "luajit",
+                feature = "luau"
+            )),
+            bytecode_dump: cfg!(not(feature = "luau")),
+        }
+    }
+}
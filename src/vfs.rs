@@ -0,0 +1,463 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::result::Result as StdResult;
+use std::string::String as StdString;
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::state::Lua;
+use crate::stdlib::UnsafeCapabilities;
+use crate::table::Table;
+use crate::types::MaybeSend;
+use crate::value::{FromLuaMulti, IntoLuaMulti, MultiValue, Nil};
+
+/// A virtual filesystem used to load Lua files.
+///
+/// When installed using [`Lua::set_vfs`], the filesystem backs the `dofile` and `loadfile`
+/// functions and loading Lua modules with `require`.
+///
+/// Paths are always relative to the filesystem root. Built-in filesystems are [`DirFs`]
+/// (a directory on disk), [`MemoryFs`] (in-memory files, eg. embedded assets) and [`OverlayFs`]
+/// (a stack of filesystems). Other sources, such as zip archives, can be supported by implementing
+/// this trait.
+///
+/// [`Lua::set_vfs`]: crate::Lua::set_vfs
+pub trait Vfs: MaybeSend + 'static {
+    /// Opens a file for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Reads the whole file content.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.open(path)?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Returns metadata of a file or directory.
+    fn stat(&self, path: &Path) -> io::Result<VfsMetadata>;
+
+    /// Returns paths of the entries in a directory.
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+}
+
+/// Type of an entry in a [`Vfs`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VfsFileType {
+    File,
+    Dir,
+}
+
+/// Metadata of an entry in a [`Vfs`], returned by [`Vfs::stat`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct VfsMetadata {
+    /// Entry type.
+    pub file_type: VfsFileType,
+    /// File size in bytes (`0` for directories).
+    pub len: u64,
+}
+
+impl VfsMetadata {
+    /// Returns metadata of a file with the given size.
+    pub const fn file(len: u64) -> Self {
+        VfsMetadata {
+            file_type: VfsFileType::File,
+            len,
+        }
+    }
+
+    /// Returns metadata of a directory.
+    pub const fn dir() -> Self {
+        VfsMetadata {
+            file_type: VfsFileType::Dir,
+            len: 0,
+        }
+    }
+
+    /// Returns `true` if the entry is a file.
+    pub fn is_file(&self) -> bool {
+        self.file_type == VfsFileType::File
+    }
+
+    /// Returns `true` if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.file_type == VfsFileType::Dir
+    }
+}
+
+// Converts path to a relative path without `.` components.
+// Returns error for paths escaping the root.
+fn normalize(path: &Path) -> io::Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => {
+                let msg = format!("path '{}' is outside of the filesystem root", path.display());
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, msg));
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("'{}' not found", path.display()))
+}
+
+/// A [`Vfs`] backed by a directory on disk.
+///
+/// Paths outside of the directory cannot be accessed, including through symbolic links pointing
+/// outside of it.
+#[derive(Clone, Debug)]
+pub struct DirFs {
+    root: PathBuf,
+}
+
+impl DirFs {
+    /// Creates a new filesystem rooted at the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirFs { root: root.into() }
+    }
+
+    // Returns the path on disk, resolving symbolic links.
+    // Returns error for paths outside of the root directory.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        let root = self.root.canonicalize()?;
+        let resolved = root.join(normalize(path)?).canonicalize()?;
+        if !resolved.starts_with(&root) {
+            let msg = format!("path '{}' is outside of the filesystem root", path.display());
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, msg));
+        }
+        Ok(resolved)
+    }
+}
+
+impl Vfs for DirFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(fs::File::open(self.resolve(path)?)?))
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let metadata = fs::metadata(self.resolve(path)?)?;
+        match metadata.is_dir() {
+            true => Ok(VfsMetadata::dir()),
+            false => Ok(VfsMetadata::file(metadata.len())),
+        }
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let path = normalize(path)?;
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(self.resolve(&path)?)? {
+            entries.push(path.join(entry?.file_name()));
+        }
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+/// An in-memory [`Vfs`].
+///
+/// Directories are implied by the file paths. This is useful for scripts embedded into the binary
+/// (eg. using `include_bytes!` or the `include_dir` crate).
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, MemoryFs, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let vfs = MemoryFs::new()
+///     .with_file("lib/greet.lua", "return function(name) return 'Hello, ' .. name end".as_bytes());
+/// lua.set_vfs(vfs)?;
+///
+/// lua.load("package.path = 'lib/?.lua'").exec()?;
+/// let greeting: String = lua.load("return require('greet')('world')").eval()?;
+/// assert_eq!(greeting, "Hello, world");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, Cow<'static, [u8]>>,
+}
+
+impl MemoryFs {
+    /// Creates a new empty filesystem.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file to the filesystem, replacing the existing one.
+    ///
+    /// Paths escaping the root (using `..`) are ignored.
+    pub fn insert(&mut self, path: impl AsRef<Path>, data: impl Into<Cow<'static, [u8]>>) {
+        if let Ok(path) = normalize(path.as_ref()) {
+            self.files.insert(path, data.into());
+        }
+    }
+
+    /// Adds a file to the filesystem and returns it (builder style).
+    #[must_use]
+    pub fn with_file(mut self, path: impl AsRef<Path>, data: impl Into<Cow<'static, [u8]>>) -> Self {
+        self.insert(path, data);
+        self
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self.files.get(&normalize(path)?) {
+            Some(data) => Ok(Box::new(&**data)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<VfsMetadata> {
+        let normalized = normalize(path)?;
+        if let Some(data) = self.files.get(&normalized) {
+            return Ok(VfsMetadata::file(data.len() as u64));
+        }
+        match self.files.keys().any(|file| file.starts_with(&normalized)) {
+            true => Ok(VfsMetadata::dir()),
+            false => Err(not_found(path)),
+        }
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir = normalize(path)?;
+        let entries: BTreeSet<PathBuf> = (self.files.keys())
+            .filter_map(|file| file.strip_prefix(&dir).ok())
+            .filter_map(|rest| rest.components().next())
+            .map(|name| dir.join(name))
+            .collect();
+        if entries.is_empty() && self.stat(path)?.is_file() {
+            let msg = format!("'{}' is not a directory", path.display());
+            return Err(io::Error::other(msg));
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+/// A [`Vfs`] combining several filesystems.
+///
+/// Files are looked up in the layers in the order they were added, so earlier layers override
+/// the later ones. Directory listings are merged.
+#[derive(Default)]
+pub struct OverlayFs {
+    layers: Vec<Box<dyn Vfs>>,
+}
+
+impl OverlayFs {
+    /// Creates a new filesystem without layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a new layer with lower priority than the existing ones.
+    pub fn push(&mut self, layer: impl Vfs) {
+        self.layers.push(Box::new(layer));
+    }
+
+    /// Adds a new layer and returns the filesystem (builder style).
+    #[must_use]
+    pub fn with_layer(mut self, layer: impl Vfs) -> Self {
+        self.push(layer);
+        self
+    }
+
+    // Returns the first layer containing the path
+    fn find(&self, path: &Path) -> io::Result<&dyn Vfs> {
+        for layer in &self.layers {
+            match layer.stat(path) {
+                Ok(_) => return Ok(layer.as_ref()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(not_found(path))
+    }
+}
+
+impl Vfs for OverlayFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        self.find(path)?.open(path)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<VfsMetadata> {
+        self.find(path)?.stat(path)
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = BTreeSet::new();
+        let mut found = false;
+        for layer in &self.layers {
+            if let Ok(list) = layer.list(path) {
+                entries.extend(list);
+                found = true;
+            }
+        }
+        match found {
+            true => Ok(entries.into_iter().collect()),
+            false => Err(not_found(path)),
+        }
+    }
+}
+
+// Registry flag set when the Lua functions are wrapped to use the virtual filesystem
+const INSTALLED_KEY: &str = "__mlua_vfs_installed";
+
+// Loads a Lua file from the virtual filesystem, returning error message on failure
+fn load_file(
+    lua: &Lua,
+    vfs: &dyn Vfs,
+    path: &str,
+    mode: Option<&str>,
+    env: Option<Table>,
+) -> Result<StdResult<Function, StdString>> {
+    let source = match vfs.read(Path::new(path)) {
+        Ok(source) => source,
+        Err(err) => return Ok(Err(format!("cannot open {path}: {err}"))),
+    };
+    // Like in standard Lua, files are loaded in the global environment (unless `env` is given).
+    // Loaded modules are shared, so they must not be bound to the environment of the first caller.
+    let mut chunk = lua
+        .load(source)
+        .set_name(format!("@{path}"))
+        .set_inherit_environment(false);
+    let mode = match lua
+        .unsafe_capabilities()
+        .contains(UnsafeCapabilities::ALLOW_BINARY_CHUNKS)
+    {
+        true => mode,
+        false => Some("t"),
+    };
+    match mode {
+        Some("t") => chunk = chunk.set_mode(ChunkMode::Text),
+        Some("b") => chunk = chunk.set_mode(ChunkMode::Binary),
+        _ => {}
+    }
+    if let Some(env) = env {
+        chunk = chunk.set_environment(env);
+    }
+    match chunk.into_function() {
+        Ok(func) => Ok(Ok(func)),
+        Err(Error::SyntaxError { message, .. }) => Ok(Err(message)),
+        Err(err) => Err(err),
+    }
+}
+
+// Searches for a module in `package.path` using the virtual filesystem.
+//
+// Returns the file path or the list of tried paths.
+pub(crate) fn search_path(
+    vfs: &dyn Vfs,
+    name: &str,
+    search_path: &str,
+) -> StdResult<StdString, Vec<StdString>> {
+    let name = name.replace('.', "/");
+    let mut tried = Vec::new();
+    for template in search_path.split(';').filter(|t| !t.is_empty()) {
+        let path = template.replace('?', &name);
+        if vfs.stat(Path::new(&path)).is_ok_and(|m| m.is_file()) {
+            return Ok(path);
+        }
+        tried.push(path);
+    }
+    Err(tried)
+}
+
+// Loads a Lua module using the virtual filesystem (module searcher)
+#[cfg(not(feature = "luau"))]
+fn search_module(lua: &Lua, vfs: &dyn Vfs, name: &str) -> Result<MultiValue> {
+    let package = lua.globals().get::<Table>("package")?;
+    let path = package.get::<StdString>("path").unwrap_or_default();
+    match search_path(vfs, name, &path) {
+        Ok(path) => match load_file(lua, vfs, &path, None, None)? {
+            Ok(func) => (func, path).into_lua_multi(lua),
+            Err(msg) => Err(Error::runtime(format!(
+                "error loading module '{name}' from file '{path}':\n\t{msg}"
+            ))),
+        },
+        Err(tried) => {
+            let tried = tried.iter().map(|path| format!("no file '{path}'"));
+            #[cfg(feature = "lua54")]
+            let msg = tried.collect::<Vec<_>>().join("\n\t");
+            #[cfg(not(feature = "lua54"))]
+            let msg = tried.map(|s| format!("\n\t{s}")).collect::<StdString>();
+            msg.into_lua_multi(lua)
+        }
+    }
+}
+
+// Wraps `dofile`, `loadfile` and the Lua modules searcher to use the virtual filesystem (if set)
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    if lua.named_registry_value::<bool>(INSTALLED_KEY)? {
+        return Ok(());
+    }
+    let globals = lua.globals();
+
+    if let Some(loadfile) = globals.raw_get::<Option<Function>>("loadfile")? {
+        let func = lua.create_function(move |lua, args: MultiValue| {
+            let Some(vfs) = lua.lock().vfs() else {
+                return loadfile.call::<MultiValue>(args);
+            };
+            let (path, mode, env) =
+                <(Option<StdString>, Option<StdString>, Option<Table>)>::from_lua_multi(args, lua)?;
+            let Some(path) = path else {
+                return (Nil, "cannot read stdin using virtual filesystem").into_lua_multi(lua);
+            };
+            match load_file(lua, &*vfs, &path, mode.as_deref(), env)? {
+                Ok(func) => func.into_lua_multi(lua),
+                Err(msg) => (Nil, msg).into_lua_multi(lua),
+            }
+        })?;
+        globals.raw_set("loadfile", func)?;
+    }
+
+    if let Some(dofile) = globals.raw_get::<Option<Function>>("dofile")? {
+        let func = lua.create_function(move |lua, args: MultiValue| {
+            let Some(vfs) = lua.lock().vfs() else {
+                return dofile.call::<MultiValue>(args);
+            };
+            let path = <Option<StdString>>::from_lua_multi(args, lua)?;
+            let Some(path) = path else {
+                return Err(Error::runtime("cannot read stdin using virtual filesystem"));
+            };
+            match load_file(lua, &*vfs, &path, None, None)? {
+                Ok(func) => func.call(()),
+                Err(msg) => Err(Error::runtime(msg)),
+            }
+        })?;
+        globals.raw_set("dofile", func)?;
+    }
+
+    // Replace the Lua files searcher (the second one, after `package.preload`)
+    #[cfg(not(feature = "luau"))]
+    if let Some(package) = globals.raw_get::<Option<Table>>("package")? {
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        let searchers = package.raw_get::<Option<Table>>("loaders")?;
+        #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+        let searchers = package.raw_get::<Option<Table>>("searchers")?;
+        if let Some(searchers) = searchers {
+            if let Some(searcher) = searchers.raw_get::<Option<Function>>(2)? {
+                let func = lua.create_function(move |lua, name: StdString| {
+                    let vfs = lua.lock().vfs();
+                    match vfs {
+                        Some(vfs) => search_module(lua, &*vfs, &name),
+                        None => searcher.call::<MultiValue>(name),
+                    }
+                })?;
+                searchers.raw_set(2, func)?;
+            }
+        }
+    }
+
+    lua.set_named_registry_value(INSTALLED_KEY, true)
+}
@@ -8,8 +8,9 @@ use futures_util::stream::TryStreamExt;
 use tokio::sync::Mutex;
 
 use mlua::{
-    Error, Function, Lua, LuaOptions, MultiValue, ObjectLike, Result, StdLib, Table, UserData,
-    UserDataMethods, Value,
+    AsyncChannel, AsyncLimiter, CancellationToken, Error, EventBus, Function, Lua, LuaOptions, LuaPool,
+    ManualClock, MultiValue, ObjectLike, PoolOptions, Result, StdLib, Table, ThreadStatus, TypedFunction,
+    UserData, UserDataMethods, Value,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -36,6 +37,44 @@ async fn test_async_function() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_call_with_handler() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(move |_lua, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(n)
+    })?;
+    lua.globals().set("sleep", sleep)?;
+    let handler: Function = lua
+        .load("function(err) return 'handled: ' .. tostring(err) end")
+        .eval()?;
+
+    let func: Function = lua.load("function(a, b) return a + b end").eval()?;
+    let res = func
+        .call_async_with_handler::<i64>((1, 2), handler.clone())
+        .await?;
+    assert_eq!(res, 3);
+
+    // Yielding across `xpcall` is not supported by Lua 5.1
+    #[cfg(not(feature = "lua51"))]
+    {
+        let func: Function = lua.load("function(n) return sleep(n), nil end").eval()?;
+        let res = func
+            .call_async_with_handler::<(u64, Value)>(10, handler.clone())
+            .await?;
+        assert_eq!(res, (10, Value::Nil));
+
+        let func: Function = lua.load("function(n) sleep(n); error('boom', 0) end").eval()?;
+        match func.call_async_with_handler::<()>(10, handler).await {
+            Err(Error::RuntimeError(msg)) => assert_eq!(msg, "handled: boom"),
+            r => panic!("expected RuntimeError, got {r:?}"),
+        }
+    }
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_function_wrap() -> Result<()> {
     let lua = Lua::new();
@@ -51,6 +90,20 @@ async fn test_async_function_wrap() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_typed_function() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(move |_lua, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(format!("elapsed:{}ms", n))
+    })?;
+    let sleep = TypedFunction::<u64, StdString>::from(sleep);
+    assert_eq!(sleep.call_async(10).await?, "elapsed:10ms");
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_function_wrap_raw() -> Result<()> {
     let lua = Lua::new();
@@ -91,6 +144,121 @@ async fn test_async_sleep() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_time_driver() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(|lua, ms: u64| async move {
+        lua.sleep(Duration::from_millis(ms)).await?;
+        Ok("done")
+    })?;
+
+    // No time driver is set
+    match sleep.call_async::<()>(10).await {
+        Err(Error::CallbackError { cause, .. }) => assert!(cause.to_string().contains("time driver")),
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    // Manual clock
+    let clock = ManualClock::new();
+    lua.set_time_driver(clock.clone());
+    let thread = lua.create_thread(sleep.clone())?;
+    thread.resume::<()>(100)?;
+    clock.advance(Duration::from_millis(50));
+    thread.resume::<()>(())?;
+    assert_eq!(thread.status(), ThreadStatus::Resumable);
+    clock.advance(Duration::from_millis(50));
+    assert_eq!(thread.resume::<String>(())?, "done");
+
+    let deadline = clock.now() + Duration::from_millis(100);
+    let call = sleep.call_async_with_deadline::<String>(deadline, 200);
+    let clock2 = clock.clone();
+    let (res, _) = tokio::join!(call, async move {
+        tokio::task::yield_now().await;
+        clock2.advance(Duration::from_millis(150));
+    });
+    match res {
+        Err(Error::RuntimeError(msg)) => assert_eq!(msg, "deadline exceeded"),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Tokio timers
+    #[cfg(feature = "tokio")]
+    {
+        lua.set_time_driver(mlua::TokioTimeDriver);
+        let deadline = std::time::Instant::now() + Duration::from_millis(500);
+        let res = sleep.call_async_with_deadline::<String>(deadline, 10).await?;
+        assert_eq!(res, "done");
+        let deadline = std::time::Instant::now() + Duration::from_millis(10);
+        assert!(sleep
+            .call_async_with_deadline::<String>(deadline, 500)
+            .await
+            .is_err());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_function_limiter() -> Result<()> {
+    let lua = Lua::new();
+    let clock = ManualClock::new();
+    lua.set_time_driver(clock.clone());
+
+    let limiter = AsyncLimiter::new(2);
+    let sleep = lua.create_async_function_with_limiter(
+        |lua, ms: u64| async move {
+            lua.sleep(Duration::from_millis(ms)).await?;
+            Ok(ms)
+        },
+        limiter.clone(),
+    )?;
+
+    let threads = [10, 20, 30]
+        .into_iter()
+        .map(|ms| Ok((lua.create_thread(sleep.clone())?, ms)))
+        .collect::<Result<Vec<_>>>()?;
+    for (thread, ms) in &threads {
+        thread.resume::<()>(*ms)?;
+    }
+    assert_eq!(limiter.in_flight(), 2);
+    assert_eq!(limiter.waiting(), 1);
+
+    // Complete the first call, the third one should be admitted
+    clock.advance(Duration::from_millis(10));
+    assert_eq!(threads[0].0.resume::<u64>(())?, 10);
+    assert_eq!(limiter.in_flight(), 1);
+    threads[2].0.resume::<()>(())?;
+    assert_eq!(limiter.in_flight(), 2);
+    assert_eq!(limiter.waiting(), 0);
+
+    clock.advance(Duration::from_millis(30));
+    assert_eq!(threads[1].0.resume::<u64>(())?, 20);
+    assert_eq!(threads[2].0.resume::<u64>(())?, 30);
+    assert_eq!(limiter.in_flight(), 0);
+
+    // Many concurrent calls
+    let limiter = AsyncLimiter::new(3);
+    let max_seen = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let (limiter2, max_seen2) = (limiter.clone(), max_seen.clone());
+    let work = lua.create_async_function_with_limiter(
+        move |_, ()| {
+            let (limiter, max_seen) = (limiter2.clone(), max_seen2.clone());
+            async move {
+                max_seen.fetch_max(limiter.in_flight(), std::sync::atomic::Ordering::Relaxed);
+                sleep_ms(5).await;
+                Ok(())
+            }
+        },
+        limiter,
+    )?;
+    let calls = (0..10).map(|_| work.call_async::<()>(()));
+    futures_util::future::try_join_all(calls).await?;
+    assert_eq!(max_seen.load(std::sync::atomic::Ordering::Relaxed), 3);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_call() -> Result<()> {
     let lua = Lua::new();
@@ -298,6 +466,57 @@ async fn test_async_thread_stream() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_async_thread_stream_resume_with() -> Result<()> {
+    let lua = Lua::new();
+
+    let sleep = lua.create_async_function(|_, n: i64| async move {
+        sleep_ms(1).await;
+        Ok(n)
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    let thread = lua.create_thread(
+        lua.load(
+            r#"
+            function(request)
+                local log = {}
+                while request ~= "stop" do
+                    -- Async calls in between do not consume resume arguments
+                    local n = sleep(#log)
+                    table.insert(log, request .. n)
+                    request = coroutine.yield(#log)
+                end
+                return table.concat(log, ",")
+            end
+            "#,
+        )
+        .eval()?,
+    )?;
+
+    let mut stream = thread.into_async::<Value>("a");
+    assert_eq!(stream.try_next().await?, Some(Value::Integer(1)));
+    stream.resume_with("b")?;
+    assert_eq!(stream.try_next().await?, Some(Value::Integer(2)));
+    stream.resume_with("c")?;
+    assert_eq!(stream.try_next().await?, Some(Value::Integer(3)));
+    stream.resume_with("stop")?;
+    let log = stream.try_next().await?.unwrap();
+    assert_eq!(log.to_string()?, "a0,b1,c2");
+    assert!(stream.try_next().await?.is_none());
+
+    // Resuming without arguments passes nothing to `yield`
+    let thread = lua.create_thread(
+        lua.load("function() return select('#', coroutine.yield(-1)) end")
+            .eval()?,
+    )?;
+    let mut stream = thread.into_async::<i64>(());
+    assert_eq!(stream.try_next().await?, Some(-1));
+    assert_eq!(stream.try_next().await?, Some(0));
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_async_thread() -> Result<()> {
     let lua = Lua::new();
@@ -547,3 +766,409 @@ async fn test_async_terminate() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_async_pool() -> Result<()> {
+    let next_id = Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let init = {
+        let next_id = next_id.clone();
+        move || {
+            let lua = Lua::new();
+            let id = next_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            lua.globals().set("id", id)?;
+            lua.globals().set("calls", 0)?;
+            let sleep = lua.create_async_function(|_, ms: u64| async move {
+                sleep_ms(ms).await;
+                Ok(())
+            })?;
+            lua.globals().set("sleep", sleep)?;
+            Ok(lua)
+        }
+    };
+
+    // Concurrent executions are spread over all states
+    let pool = LuaPool::new(PoolOptions::new().size(2), init.clone())?;
+    assert_eq!(pool.size(), 2);
+    let futs = (0..4).map(|_| pool.execute::<u32>("calls = calls + 1; sleep(20); return id", ()));
+    let mut ids = futures_util::future::try_join_all(futs).await?;
+    ids.sort();
+    assert_eq!(ids, vec![0, 0, 1, 1]);
+    assert_eq!(pool.load(), 0);
+
+    // Recycle after the number of executions
+    next_id.store(0, std::sync::atomic::Ordering::Relaxed);
+    let pool = LuaPool::new(PoolOptions::new().size(1).max_executions(Some(2)), init.clone())?;
+    for expected in [(0, 1), (0, 2), (1, 1)] {
+        let res = pool
+            .execute::<(u32, u32)>("calls = calls + 1; return id, calls", ())
+            .await?;
+        assert_eq!(res, expected);
+    }
+    assert_eq!(pool.recycled(), 1);
+
+    // Recycle after errors
+    let pool = LuaPool::new(PoolOptions::new().size(1).max_errors(Some(1)), init)?;
+    pool.execute::<()>("polluted = true", ()).await?;
+    assert!(pool.execute::<()>("error('boom')", ()).await.is_err());
+    assert_eq!(pool.recycled(), 1);
+    assert_eq!(pool.execute::<Option<bool>>("return polluted", ()).await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_event_bus() -> Result<()> {
+    let lua = Lua::new();
+    let bus = EventBus::new();
+
+    bus.on_async(&lua, "tick", |lua, n: u64| async move {
+        sleep_ms(n).await;
+        lua.globals().set("ticked", n)
+    })?;
+    lua.globals().set("bus", bus.clone())?;
+
+    // Sync emit cannot call async handlers
+    assert_eq!(bus.emit(&lua, "tick", 1)?.len(), 1);
+
+    let errors = bus.emit_async(&lua, "tick", 10).await?;
+    assert!(errors.is_empty());
+    assert_eq!(lua.globals().get::<u64>("ticked")?, 10);
+
+    lua.load("assert(bus:emit_async('tick', 20))")
+        .exec_async()
+        .await?;
+    assert_eq!(lua.globals().get::<u64>("ticked")?, 20);
+
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn test_async_metrics() -> Result<()> {
+    let lua = Lua::new();
+
+    let f = lua.create_async_function(|lua, ()| async move {
+        sleep_ms(1).await;
+        Ok(lua.metrics_snapshot().active_threads)
+    })?;
+    assert_eq!(f.call_async::<usize>(()).await?, 1);
+    assert_eq!(lua.metrics_snapshot().active_threads, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_sync_primitives() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("sync", lua.create_sync_module()?)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, ms: u64| async move {
+            sleep_ms(ms).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Mutex serializes critical sections
+    let worker: Function = lua
+        .load(
+            r#"
+            mutex = sync.Mutex()
+            log = {}
+            return function(name)
+                mutex:lock()
+                table.insert(log, name .. ":start")
+                sleep(5)
+                table.insert(log, name .. ":end")
+                mutex:unlock()
+            end
+        "#,
+        )
+        .eval()?;
+    let (a, b) = tokio::join!(worker.call_async::<()>("a"), worker.call_async::<()>("b"));
+    a?;
+    b?;
+    let log = lua.load("return table.concat(log, ',')").eval::<String>()?;
+    assert_eq!(log, "a:start,a:end,b:start,b:end");
+    let locked: bool = lua.load("return mutex:try_lock() and mutex:is_locked()").eval()?;
+    assert!(locked);
+    lua.load("mutex:unlock()").exec()?;
+    let err = lua.load("mutex:unlock()").exec().unwrap_err().to_string();
+    assert!(err.contains("mutex is not locked"), "{err}");
+
+    // `with` releases the lock on error
+    let res = lua
+        .load("return mutex:with(function(x) return x * 2 end, 21)")
+        .eval_async::<i64>()
+        .await?;
+    assert_eq!(res, 42);
+    assert!(lua
+        .load("return mutex:with(error, 'boom')")
+        .exec_async()
+        .await
+        .is_err());
+    assert!(!lua.load("return mutex:is_locked()").eval::<bool>()?);
+
+    // `with` releases the lock when the future is dropped
+    let fut = lua.load("return mutex:with(sleep, 100)").exec_async();
+    assert!(tokio::time::timeout(Duration::from_millis(10), fut)
+        .await
+        .is_err());
+    // The pending future is owned by the coroutine and dropped by the GC
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert!(!lua.load("return mutex:is_locked()").eval::<bool>()?);
+
+    // Semaphore
+    let ok: bool = lua
+        .load(
+            r#"
+            local sem = sync.Semaphore(2)
+            assert(sem:try_acquire(2) and not sem:try_acquire())
+            sem:release()
+            assert(sem:available() == 1)
+            sem:acquire()
+            assert(sem:available() == 0)
+            for _ = 1, 3 do sem:release(2^62) end
+            assert(not pcall(sem.release, sem, 2^62), "permits must not overflow")
+            return sem:available() == 3 * 2^62
+        "#,
+        )
+        .eval_async()
+        .await?;
+    assert!(ok);
+
+    // Bounded channel between producer and consumer
+    let (producer, consumer): (Function, Function) = lua
+        .load(
+            r#"
+            local ch = sync.Channel(1)
+            local function producer()
+                for i = 1, 5 do ch:send(i) end
+                ch:close()
+                return ch:try_send(6)
+            end
+            local function consumer()
+                local sum = 0
+                local v = ch:recv()
+                while v ~= nil do
+                    sum = sum + v
+                    v = ch:recv()
+                end
+                return sum
+            end
+            return producer, consumer
+        "#,
+        )
+        .eval()?;
+    let (sent, sum) = tokio::join!(producer.call_async::<bool>(()), consumer.call_async::<i64>(()));
+    assert!(sent.unwrap_err().to_string().contains("channel is closed"));
+    assert_eq!(sum?, 15);
+    assert!(lua.load("sync.Channel(0)").exec().is_err());
+
+    // Channel cannot pass values between Lua states
+    let channel = AsyncChannel::new(&lua, None);
+    lua.globals().set("ch", channel.clone())?;
+    lua.load("ch:try_send('hello')").exec()?;
+    let lua2 = Lua::new();
+    lua2.globals().set("ch", channel)?;
+    let err = lua2.load("ch:try_recv()").exec().unwrap_err().to_string();
+    assert!(err.contains("channel used from a different Lua state"), "{err}");
+    assert!(lua2.load("ch:send(1)").exec_async().await.is_err());
+    assert_eq!(lua.load("ch:try_recv()").eval::<String>()?, "hello");
+
+    Ok(())
+}
+
+#[test]
+fn test_async_poll_pending_tasks() -> Result<()> {
+    let lua = Lua::new();
+    let clock = ManualClock::new();
+    lua.set_time_driver(clock.clone());
+
+    let sleep =
+        lua.create_async_function(|lua, ms: u64| async move { lua.sleep(Duration::from_millis(ms)).await })?;
+    lua.globals().set("sleep", sleep)?;
+    assert_eq!(lua.poll_pending_tasks(Duration::ZERO)?, 0);
+
+    let thread = lua.create_thread(lua.load("function(n) sleep(n); done = n end").eval()?)?;
+    lua.spawn_thread(thread, 100)?;
+    let lua2 = lua.clone();
+    lua.spawn_task(async move {
+        lua2.sleep(Duration::from_millis(50)).await?;
+        lua2.globals().set("task_done", true)
+    });
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(10))?, 2);
+    // Nothing was woken up
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(10))?, 2);
+
+    clock.advance(Duration::from_millis(50));
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(10))?, 1);
+    assert!(lua.globals().get::<bool>("task_done")?);
+    clock.advance(Duration::from_millis(50));
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(10))?, 0);
+    assert_eq!(lua.globals().get::<u64>("done")?, 100);
+
+    // Budget is respected by busy tasks
+    let busy = lua.create_thread(
+        lua.load("function() while true do coroutine.yield() end end")
+            .eval()?,
+    )?;
+    lua.spawn_thread(busy, ())?;
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(5))?, 1);
+
+    // Failed task is removed
+    let failing = lua.create_thread(lua.load("function() sleep(10); error('boom') end").eval()?)?;
+    lua.spawn_thread(failing, ())?;
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(5))?, 2);
+    clock.advance(Duration::from_millis(10));
+    let mut failed = false;
+    for _ in 0..10 {
+        match lua.poll_pending_tasks(Duration::from_millis(5)) {
+            Ok(n) => assert_eq!(n, if failed { 1 } else { 2 }),
+            Err(err) => {
+                assert!(err.to_string().contains("boom"));
+                failed = true;
+            }
+        }
+    }
+    assert!(failed);
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[tokio::test]
+async fn test_async_userdata_destructor() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Connection {
+        flushed: Arc<AtomicUsize>,
+        // Completes the flush from another thread (if set)
+        remote: bool,
+    }
+
+    let new_lua = || -> Result<Lua> {
+        let lua = Lua::new();
+        lua.register_userdata_type::<Connection>(|reg| {
+            reg.add_async_destructor(|conn| async move {
+                if conn.remote {
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    std::thread::spawn(move || {
+                        std::thread::sleep(Duration::from_millis(20));
+                        tx.send(()).unwrap();
+                    });
+                    rx.await.unwrap();
+                }
+                conn.flushed.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            });
+        })?;
+        Ok(lua)
+    };
+
+    let lua = new_lua()?;
+    let flushed = Arc::new(AtomicUsize::new(0));
+    let conn = lua.create_any_userdata(Connection {
+        flushed: flushed.clone(),
+        remote: false,
+    })?;
+    drop(conn);
+    lua.gc_collect()?;
+    // The cleanup is scheduled, but not run by the garbage collector
+    assert_eq!(flushed.load(Ordering::SeqCst), 0);
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(10))?, 0);
+    assert_eq!(flushed.load(Ordering::SeqCst), 1);
+
+    // Taken values are not passed to the destructor
+    let conn = lua.create_any_userdata(Connection {
+        flushed: flushed.clone(),
+        remote: false,
+    })?;
+    drop(conn.take::<Connection>()?);
+    lua.gc_collect()?;
+    assert_eq!(lua.poll_pending_tasks(Duration::from_millis(10))?, 0);
+    assert_eq!(flushed.load(Ordering::SeqCst), 1);
+
+    // Pending cleanups are dropped without running when Lua is dropped
+    let conn = lua.create_any_userdata(Connection {
+        flushed: flushed.clone(),
+        remote: true,
+    })?;
+    lua.globals().set("conn", conn)?;
+    drop(lua);
+    assert_eq!(flushed.load(Ordering::SeqCst), 1);
+
+    // Pending cleanups are completed by `Lua::close`
+    let lua = new_lua()?;
+    let conn = lua.create_any_userdata(Connection {
+        flushed: flushed.clone(),
+        remote: true,
+    })?;
+    lua.globals().set("conn", conn)?;
+    let close = lua.close();
+    assert_eq!(flushed.load(Ordering::SeqCst), 1);
+    close.await;
+    assert_eq!(flushed.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_async_cancellation_token() -> Result<()> {
+    let lua = Lua::new();
+    let token = CancellationToken::new();
+    lua.set_cancellation_token(token.clone());
+    lua.globals().set("token", token.clone())?;
+
+    let sleep = lua.create_async_function(|_, n: u64| async move {
+        sleep_ms(n).await;
+        Ok(n)
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    // Not cancelled yet
+    let res: u64 = lua
+        .load("assert(not token:cancelled()); token:check(); return sleep(10)")
+        .eval_async()
+        .await?;
+    assert_eq!(res, 10);
+
+    // Pending async function is interrupted
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        sleep_ms(20).await;
+        canceller.cancel();
+    });
+    let res = lua.load("return sleep(10000)").exec_async().await;
+    match res {
+        Err(Error::CallbackError { ref cause, .. }) if matches!(**cause, Error::Cancelled) => {}
+        res => panic!("expected Cancelled error, got {res:?}"),
+    }
+
+    // Script can observe the token
+    assert!(lua.load("return token:cancelled()").eval::<bool>()?);
+    let res = lua.load("token:check()").exec();
+    match res {
+        Err(Error::CallbackError { ref cause, .. }) if matches!(**cause, Error::Cancelled) => {}
+        res => panic!("expected Cancelled error, got {res:?}"),
+    }
+
+    // Without the token async functions work again
+    lua.remove_cancellation_token();
+    assert!(lua.cancellation_token().is_none());
+    let res: u64 = lua.load("return sleep(10)").eval_async().await?;
+    assert_eq!(res, 10);
+
+    // Waiting for another token
+    let other = CancellationToken::new();
+    lua.globals().set("other", other.clone())?;
+    tokio::spawn(async move {
+        sleep_ms(20).await;
+        other.cancel();
+    });
+    lua.load("other:wait()").exec_async().await?;
+    assert!(lua.load("return other:cancelled()").eval::<bool>()?);
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::{fs, io};
 
-use mlua::{Lua, Result};
+use mlua::{ChunkMode, ChunkProvider, Error, IncrementalChunk, Integer, Lua, Result, Table};
 
 #[test]
 fn test_chunk_path() -> Result<()> {
@@ -68,3 +73,203 @@ fn test_chunk_macro() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg(feature = "macros")]
+fn test_include_lua() -> Result<()> {
+    use mlua::AsChunk;
+
+    let lua = Lua::new();
+
+    let chunk = lua.load(mlua::include_lua!("tests/scripts/include.lua"));
+    let (sum, s): (i32, String) = chunk.call((1, 2))?;
+    assert_eq!(sum, 3);
+    assert_eq!(s, "included");
+
+    // The script is embedded as bytecode
+    let chunk = mlua::include_lua!("tests/scripts/include.lua");
+    assert_eq!(chunk.mode(), Some(mlua::ChunkMode::Binary));
+    assert!(!chunk.source()?.starts_with(b"local"));
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_shared_sources() -> Result<()> {
+    let lua = Lua::new();
+
+    let source: Arc<str> = Arc::from("return 1");
+    assert_eq!(lua.load(&source).eval::<i32>()?, 1);
+    assert_eq!(lua.load(source).eval::<i32>()?, 1);
+    let source: Rc<[u8]> = Rc::from(&b"return 2"[..]);
+    assert_eq!(lua.load(&source).eval::<i32>()?, 2);
+    assert_eq!(lua.load(source).eval::<i32>()?, 2);
+    let source: Box<str> = Box::from("return 3");
+    assert_eq!(lua.load(source).eval::<i32>()?, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_provider() -> Result<()> {
+    struct Assets {
+        sources: HashMap<&'static str, Vec<u8>>,
+        loaded: Cell<usize>,
+    }
+
+    impl ChunkProvider for Assets {
+        fn source(&self, name: &str) -> io::Result<Cow<'_, [u8]>> {
+            self.loaded.set(self.loaded.get() + 1);
+            let source = self.sources.get(name).ok_or(io::ErrorKind::NotFound)?;
+            Ok(Cow::Borrowed(source))
+        }
+
+        fn chunk_name(&self, name: &str) -> String {
+            format!("=assets/{name}")
+        }
+
+        fn mode(&self, _name: &str) -> Option<ChunkMode> {
+            Some(ChunkMode::Text)
+        }
+
+        fn environment(&self, name: &str, lua: &Lua) -> Result<Option<Table>> {
+            Ok(Some(lua.create_table_from([("asset", name)])?))
+        }
+    }
+
+    let lua = Lua::new();
+    let assets = Assets {
+        sources: HashMap::from([
+            ("main.lua", b"return asset".to_vec()),
+            ("bad.lua", b"local x; x()".to_vec()),
+        ]),
+        loaded: Cell::new(0),
+    };
+
+    // Source is retrieved only when loading
+    let chunk = assets.chunk("main.lua");
+    assert_eq!(assets.loaded.get(), 0);
+    assert_eq!(lua.load(chunk).eval::<String>()?, "main.lua");
+    assert_eq!(assets.loaded.get(), 1);
+
+    let err = lua.load(assets.chunk("bad.lua")).exec().unwrap_err().to_string();
+    assert!(err.contains("assets/bad.lua:1: attempt to call"), "{err}");
+
+    match lua.load(assets.chunk("missing.lua")).exec() {
+        Err(Error::ExternalError(err)) => assert!(err.to_string().contains("not found"), "{err}"),
+        r => panic!("expected ExternalError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_import_allowlist() -> Result<()> {
+    let lua = Lua::new();
+    let allowlist = ["string", "table", "mymod", "counter"];
+
+    lua.globals().set("counter", 0)?;
+    let mymod = lua.create_function(|lua, _: String| lua.create_table_from([("value", 42)]))?;
+    lua.load_from_function::<mlua::Value>("mymod", mymod)?;
+    let load = |source: &str| lua.load(source.to_string()).set_import_allowlist(allowlist);
+    let rejected = |source: &str| match load(source).exec() {
+        Err(Error::ImportNotAllowed { name, line }) => (name, line),
+        r => panic!("expected ImportNotAllowed error, got {r:?}"),
+    };
+
+    // Locals, parameters, fields and table keys are not imports
+    let value: Integer = load(
+        r##"
+        local os, t = {}, { print = 1, ["io"] = 2 }
+        local function f(a, ...) return a + select("#", ...) end
+        local obj = {}
+        function obj:get() return self.value end
+        for i, v in ipairs({1, 2}) do counter = counter + i + v end
+        repeat local done = true until done
+        obj.value = require("mymod").value
+        return obj:get() + t.print + f(1, 2, 3) + #string.rep("x", 2) + #table.concat({"ab"})
+        "##,
+    )
+    .set_import_allowlist(["ipairs", "select"])
+    .eval()?;
+    assert_eq!(value, 42 + 1 + 3 + 2 + 2);
+    assert_eq!(lua.globals().get::<Integer>("counter")?, 6);
+
+    // The chunk is rejected before execution
+    assert_eq!(
+        rejected("counter = 100\nlocal x = os.time()"),
+        ("os".into(), Some(2))
+    );
+    assert_eq!(lua.globals().get::<Integer>("counter")?, 6);
+    assert_eq!(rejected("local print = print"), ("print".into(), Some(1)));
+    assert_eq!(rejected("local function f() x = 1 end"), ("x".into(), Some(1)));
+    assert_eq!(
+        rejected("do local io = {} end\nreturn io"),
+        ("io".into(), Some(2))
+    );
+    assert_eq!(rejected("return require('other')"), ("other".into(), Some(1)));
+    assert_eq!(
+        rejected("local m = 'mymod'\nreturn require(m)"),
+        ("require".into(), Some(2))
+    );
+
+    // Syntax errors are reported by the compiler
+    match load("local x = ").exec() {
+        Err(Error::SyntaxError { incomplete_input, .. }) => assert!(incomplete_input),
+        r => panic!("expected SyntaxError, got {r:?}"),
+    }
+
+    // Runtime enforcement for chunks that cannot be scanned
+    let source = "return string.len('abc'), os ~= nil";
+    #[cfg(not(feature = "luau"))]
+    let bytecode = lua.load(source).into_function()?.dump(false);
+    #[cfg(feature = "luau")]
+    let bytecode = mlua::Compiler::new().compile(source)?;
+    let err = lua
+        .load(bytecode)
+        .set_import_allowlist(allowlist)
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("import of `os` is not allowed"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_chunk_incremental() -> Result<()> {
+    let lua = Lua::new();
+
+    let partial = match lua.load("function f()").try_into_function_incremental()? {
+        IncrementalChunk::Incomplete(partial) => partial,
+        IncrementalChunk::Complete(_) => panic!("expected incomplete chunk"),
+    };
+    let partial = match partial.append("return 1")? {
+        IncrementalChunk::Incomplete(partial) => partial,
+        IncrementalChunk::Complete(_) => panic!("expected incomplete chunk"),
+    };
+    assert_eq!(partial.source(), b"function f()\nreturn 1");
+    match partial.append("end")? {
+        IncrementalChunk::Complete(func) => func.call::<()>(())?,
+        IncrementalChunk::Incomplete(_) => panic!("expected complete chunk"),
+    }
+    assert_eq!(lua.load("f()").eval::<Integer>()?, 1);
+
+    // Expressions return their value
+    let partial = match lua.load("1 +").try_into_function_incremental()? {
+        IncrementalChunk::Incomplete(partial) => partial,
+        IncrementalChunk::Complete(_) => panic!("expected incomplete chunk"),
+    };
+    match partial.append("2")? {
+        IncrementalChunk::Complete(func) => assert_eq!(func.call::<Integer>(())?, 3),
+        IncrementalChunk::Incomplete(_) => panic!("expected complete chunk"),
+    }
+
+    // Errors that cannot be fixed by more input
+    match lua.load("local 1 = 2").try_into_function_incremental() {
+        Err(Error::SyntaxError { incomplete_input, .. }) => assert!(!incomplete_input),
+        Err(err) => panic!("expected SyntaxError, got {err:?}"),
+        Ok(_) => panic!("expected SyntaxError"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use mlua::{
+    Command, CommandArg, CommandArgType, CommandBuffer, CommandSchema, Error, FromCommand, Integer, Lua,
+    OverflowPolicy, Result,
+};
+
+#[derive(Debug, PartialEq)]
+enum Draw {
+    Line(f64, f64, f64, f64),
+    Text(String, Integer),
+}
+
+impl FromCommand for Draw {
+    fn from_command(command: Command) -> Result<Self> {
+        let args = command.args();
+        match command.name() {
+            "line" => Ok(Draw::Line(
+                args[0].as_number().unwrap(),
+                args[1].as_number().unwrap(),
+                args[2].as_number().unwrap(),
+                args[3].as_number().unwrap(),
+            )),
+            "text" => Ok(Draw::Text(
+                args[0].as_str().unwrap().to_string(),
+                args[1].as_integer().unwrap(),
+            )),
+            name => Err(Error::runtime(format!("unsupported command {name}"))),
+        }
+    }
+}
+
+fn schema() -> CommandSchema {
+    CommandSchema::new()
+        .command("line", [CommandArgType::Number; 4])
+        .command("text", [CommandArgType::String, CommandArgType::Integer])
+        .command("flag", [CommandArgType::Boolean])
+}
+
+#[test]
+fn test_command_buffer() -> Result<()> {
+    let lua = Lua::new();
+    let buffer = CommandBuffer::new(schema());
+    lua.globals().set("draw", buffer.clone())?;
+
+    lua.load(
+        r#"
+        for i = 1, 3 do
+            draw:push("line", 0, 0, i, i * 0.5)
+        end
+        draw:push("text", "hello", 2.0)
+        assert(draw:len() == 4)
+    "#,
+    )
+    .exec()?;
+
+    let commands = buffer.drain_as::<Draw>().collect::<Result<Vec<_>>>()?;
+    assert_eq!(commands.len(), 4);
+    assert_eq!(commands[2], Draw::Line(0.0, 0.0, 3.0, 1.5));
+    assert_eq!(commands[3], Draw::Text("hello".into(), 2));
+    assert!(buffer.is_empty());
+
+    // Untyped access
+    lua.load(r#"draw:push("flag", true)"#).exec()?;
+    let command = buffer.drain().next().unwrap();
+    assert_eq!((command.id(), command.name()), (2, "flag"));
+    assert_eq!(command.into_args(), [CommandArg::Boolean(true)]);
+
+    // Validation errors
+    let err = |code: &str| lua.load(code).exec().unwrap_err().to_string();
+    assert!(err(r#"draw:push("circle", 1)"#).contains("unknown command 'circle'"));
+    assert!(err(r#"draw:push("line", 1, 2)"#).contains("command 'line' expects 4 arguments, got 2"));
+    let msg = err(r#"draw:push("text", "x", 1.5)"#);
+    assert!(msg.contains("bad argument #2 to `command 'text'`"), "{msg}");
+    assert!(err(r#"draw:push("flag", 1)"#).contains("error converting Lua integer to boolean"));
+    assert!(buffer.is_empty());
+
+    lua.load(r#"draw:push("flag", false); draw:clear()"#).exec()?;
+    assert!(buffer.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_command_buffer_overflow() -> Result<()> {
+    let lua = Lua::new();
+
+    let buffer = CommandBuffer::new(schema()).with_capacity(2, OverflowPolicy::Error);
+    lua.globals().set("buf", buffer.clone())?;
+    lua.load(r#"buf:push("flag", true); buf:push("flag", true)"#)
+        .exec()?;
+    let err = lua.load(r#"buf:push("flag", true)"#).exec().unwrap_err();
+    assert!(err.to_string().contains("command buffer is full"));
+    assert_eq!(buffer.len(), 2);
+
+    let buffer = CommandBuffer::new(schema()).with_capacity(2, OverflowPolicy::DropNewest);
+    lua.globals().set("buf", buffer.clone())?;
+    let pushed: Vec<bool> = lua
+        .load(r#"return {buf:push("text", "a", 1), buf:push("text", "b", 2), buf:push("text", "c", 3)}"#)
+        .eval()?;
+    assert_eq!(pushed, [true, true, false]);
+    assert_eq!(buffer.dropped(), 1);
+    let texts: Vec<_> = buffer.drain_as::<Draw>().collect::<Result<_>>()?;
+    assert_eq!(texts, [Draw::Text("a".into(), 1), Draw::Text("b".into(), 2)]);
+
+    let buffer = CommandBuffer::new(schema()).with_capacity(2, OverflowPolicy::DropOldest);
+    lua.globals().set("buf", buffer.clone())?;
+    lua.load(r#"for i = 1, 5 do buf:push("text", "t", i) end"#)
+        .exec()?;
+    assert_eq!(buffer.dropped(), 3);
+    let texts: Vec<_> = buffer.drain_as::<Draw>().collect::<Result<_>>()?;
+    assert_eq!(texts, [Draw::Text("t".into(), 4), Draw::Text("t".into(), 5)]);
+
+    Ok(())
+}
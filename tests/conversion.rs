@@ -1,12 +1,14 @@
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::{CStr, CString};
+use std::string::String as StdString;
+use std::time::Duration;
 
 use bstr::BString;
 use maplit::{btreemap, btreeset, hashmap, hashset};
 use mlua::{
-    AnyUserData, Either, Error, Function, IntoLua, Lua, RegistryKey, Result, Table, Thread, UserDataRef,
-    Value,
+    AnyUserData, DurationFormat, Either, Error, Function, IntegerOverflow, IntoLua, Lua, NumberFormat,
+    Ranged, RegistryKey, Result, Table, Thread, UserDataRef, Value,
 };
 
 #[test]
@@ -202,6 +204,43 @@ fn test_integer_from_lua() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_integer_overflow() -> Result<()> {
+    let lua = Lua::new();
+    let id = u64::MAX - 1;
+
+    // Default: convert to float
+    assert!(matches!(lua.pack(id)?, Value::Number(_)));
+
+    lua.set_integer_overflow(IntegerOverflow::Error);
+    match lua.pack(id) {
+        Err(Error::ToLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "out of range");
+        }
+        r => panic!("expected Error::ToLuaConversionError, got {r:?}"),
+    }
+    assert!(lua.globals().set("id", id).is_err());
+    // Values in range are not affected
+    assert_eq!(lua.pack(42u64)?, Value::Integer(42));
+
+    lua.set_integer_overflow(IntegerOverflow::WrapToNegative);
+    assert_eq!(lua.pack(id)?, Value::Integer(-2));
+    lua.globals().set("id", id)?;
+    assert_eq!(lua.load("id").eval::<i64>()?, -2);
+    #[cfg(not(feature = "luau"))]
+    assert_eq!(lua.globals().get::<u64>("id")?, id);
+    assert!(lua.unpack::<u8>(Value::Integer(-2)).is_err());
+
+    lua.set_integer_overflow(IntegerOverflow::LosslessAsString);
+    lua.globals().set("id", id)?;
+    assert_eq!(lua.load("id").eval::<String>()?, "18446744073709551614");
+    assert_eq!(lua.globals().get::<u64>("id")?, id);
+    let f = lua.create_function(|_, id: u64| Ok(id))?;
+    assert_eq!(f.call::<u64>(id)?, id);
+
+    Ok(())
+}
+
 #[test]
 fn test_float_from_lua() -> Result<()> {
     let lua = Lua::new();
@@ -312,6 +351,24 @@ fn test_conv_cow() -> Result<()> {
     let s2: String = lua.globals().get("s")?;
     assert_eq!(s, s2);
 
+    let bytes: Cow<[u8]> = Cow::Owned(vec![0xff, 0, b'a']);
+    lua.globals().set("b", bytes.clone())?;
+    let b2: BString = lua.globals().get("b")?;
+    assert_eq!(b2, *bytes);
+
+    // Values are pushed directly into the stack
+    let f = lua
+        .load("return function(...) return select('#', ...), type(select(1, ...)), ... end")
+        .eval::<Function>()?;
+    let t = lua.create_table()?;
+    let (n, ty, s3, b3, t2): (usize, StdString, StdString, BString, Table) =
+        f.call((Cow::Borrowed("x"), Cow::Borrowed(b"\xfe".as_slice()), t.clone()))?;
+    assert_eq!(
+        (n, ty.as_str(), s3.as_str(), b3.as_slice()),
+        (3, "string", "x", b"\xfe".as_slice())
+    );
+    assert_eq!(t2, t);
+
     Ok(())
 }
 
@@ -379,6 +436,32 @@ fn test_bstring_from_lua() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "bytes")]
+#[test]
+fn test_bytes_conversion() -> Result<()> {
+    use bytes::{Bytes, BytesMut};
+
+    let lua = Lua::new();
+
+    let data = Bytes::from_static(b"\x00\xffhello");
+    let s = lua.pack(data.clone())?;
+    assert_eq!(s.as_string().unwrap().as_bytes(), &data[..]);
+    assert_eq!(lua.unpack::<Bytes>(s)?, data);
+    assert_eq!(lua.unpack::<Bytes>(Value::Integer(123))?, "123");
+
+    let mut data = BytesMut::from(&b"abc"[..]);
+    data.extend_from_slice(b"\x00def");
+    let f = lua.create_function(|_, (a, b): (Bytes, BytesMut)| {
+        let mut res = BytesMut::from(&a[..]);
+        res.extend_from_slice(&b);
+        Ok(res)
+    })?;
+    let res = f.call::<BytesMut>(("xyz", data))?;
+    assert_eq!(res, &b"xyzabc\x00def"[..]);
+
+    Ok(())
+}
+
 #[cfg(feature = "luau")]
 #[test]
 fn test_bstring_from_lua_buffer() -> Result<()> {
@@ -496,3 +579,251 @@ fn test_either_from_lua() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_duration_conversion() -> Result<()> {
+    let lua = Lua::new();
+
+    // Default format is seconds
+    assert_eq!(lua.pack(Duration::from_millis(1500))?, Value::Number(1.5));
+    assert_eq!(lua.unpack::<Duration>(Value::Integer(2))?, Duration::from_secs(2));
+    assert_eq!(
+        lua.unpack::<Duration>(Value::Number(0.25))?,
+        Duration::from_millis(250)
+    );
+
+    // Strings with unit suffixes
+    for (s, expected) in [
+        ("250ms", Duration::from_millis(250)),
+        ("2h", Duration::from_secs(7200)),
+        ("1h 30m", Duration::from_secs(5400)),
+        ("1.5s", Duration::from_millis(1500)),
+        ("10us", Duration::from_micros(10)),
+        ("1d", Duration::from_secs(86400)),
+        ("3", Duration::from_secs(3)),
+    ] {
+        let value = Value::String(lua.create_string(s)?);
+        assert_eq!(lua.unpack::<Duration>(value)?, expected, "parsing {s}");
+    }
+    for s in ["", "-1s", "5 parsecs", "1.2.3s"] {
+        let value = Value::String(lua.create_string(s)?);
+        assert!(lua.unpack::<Duration>(value).is_err(), "parsing {s}");
+    }
+    assert!(lua.unpack::<Duration>(Value::Number(-1.0)).is_err());
+
+    lua.set_duration_format(DurationFormat::Millis);
+    assert_eq!(lua.pack(Duration::from_secs(2))?, Value::Integer(2000));
+    assert_eq!(
+        lua.unpack::<Duration>(Value::Integer(250))?,
+        Duration::from_millis(250)
+    );
+
+    lua.set_duration_format(DurationFormat::String);
+    let f = lua.create_function(|_, d: Duration| Ok(d * 2))?;
+    assert_eq!(f.call::<StdString>("45m")?, "1h30m");
+    assert_eq!(f.call::<StdString>(0.5)?, "1s");
+    assert_eq!(f.call::<Duration>("1s250ms")?, Duration::from_millis(2500));
+    assert_eq!(f.call::<StdString>("0s")?, "0s");
+
+    Ok(())
+}
+
+#[test]
+fn test_number_format() -> Result<()> {
+    let format = NumberFormat::new();
+    assert_eq!(format.format(1.5), "1.5");
+    assert_eq!(format.format(-3.0), "-3.0");
+    assert_eq!(format.format(0.0001), "0.0001");
+    assert_eq!(format.format(0.00001), "1e-05");
+    assert_eq!(format.format(123456789012345.0), "1.2345678901234e+14");
+    assert_eq!(format.format(f64::INFINITY), "inf");
+    assert_eq!(format.format(f64::NEG_INFINITY), "-inf");
+    let format = NumberFormat::new().precision(None);
+    assert_eq!(format.format(1.0 / 3.0), "0.3333333333333333");
+    assert_eq!(format.format(1e100), "1e+100");
+
+    let lua = Lua::new();
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    {
+        let (s1, s2) = lua
+            .load("return tostring(1/3), tostring(10 / 2)")
+            .eval::<(StdString, StdString)>()?;
+        assert_eq!((s1.as_str(), s2.as_str()), ("0.33333333333333", "5.0"));
+    }
+
+    lua.set_number_format(NumberFormat::new().precision(Some(4)).float_suffix(false))?;
+    let (s1, s2, s3) = lua
+        .load("return tostring(1/3), tostring(10 / 2), tostring('x')")
+        .eval::<(StdString, StdString, StdString)>()?;
+    assert_eq!((s1.as_str(), s2.as_str(), s3.as_str()), ("0.3333", "5", "x"));
+
+    // Calling twice does not wrap functions again
+    lua.set_number_format(NumberFormat::new().precision(Some(2)))?;
+    assert_eq!(lua.load("return tostring(2/3)").eval::<StdString>()?, "0.67");
+
+    lua.remove_number_format();
+    let s = lua.load("return tostring(1/3)").eval::<StdString>()?;
+    assert!(s.starts_with("0.33333333333"), "{s}");
+
+    Ok(())
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_derive_lua_table() -> Result<()> {
+    use mlua::LuaTable;
+
+    #[derive(Debug, Default, PartialEq, LuaTable)]
+    struct Size {
+        width: u32,
+        height: u32,
+    }
+
+    #[derive(Debug, PartialEq, LuaTable)]
+    struct Window {
+        title: StdString,
+        #[lua(rename = "isVisible", default)]
+        visible: bool,
+        #[lua(flatten)]
+        size: Size,
+        #[lua(skip)]
+        cache: Vec<u8>,
+        min_size: Option<Size>,
+    }
+
+    #[derive(Debug, PartialEq, LuaTable)]
+    struct Wrapper<T> {
+        inner: T,
+    }
+
+    let lua = Lua::new();
+
+    let window = Window {
+        title: "main".into(),
+        visible: true,
+        size: Size {
+            width: 640,
+            height: 480,
+        },
+        cache: vec![1, 2, 3],
+        min_size: None,
+    };
+    let table = lua.convert::<Table>(window)?;
+    assert_eq!(table.get::<StdString>("title")?, "main");
+    assert!(table.get::<bool>("isVisible")?);
+    assert_eq!(table.get::<Value>("visible")?, Value::Nil);
+    assert_eq!(table.get::<u32>("width")?, 640);
+    assert_eq!(table.get::<u32>("height")?, 480);
+    assert_eq!(table.get::<Value>("cache")?, Value::Nil);
+    assert_eq!(table.get::<Value>("size")?, Value::Nil);
+
+    let window = (lua.load(
+        "{title = 'main', isVisible = true, width = 10, height = 20, min_size = {width = 1, height = 2}}",
+    ))
+    .eval::<Window>()?;
+    assert_eq!(
+        window,
+        Window {
+            title: "main".into(),
+            visible: true,
+            size: Size {
+                width: 10,
+                height: 20
+            },
+            cache: Vec::new(),
+            min_size: Some(Size { width: 1, height: 2 }),
+        }
+    );
+    let window = lua
+        .load("{title = 'x', width = 1, height = 2}")
+        .eval::<Window>()?;
+    assert!(!window.visible);
+    assert_eq!(window.min_size, None);
+
+    // Round trip
+    let value = lua.convert::<Wrapper<Size>>(Wrapper {
+        inner: Size { width: 3, height: 4 },
+    })?;
+    assert_eq!(value.inner, Size { width: 3, height: 4 });
+
+    // Errors
+    let err = (lua.load("{title = 'x', width = 'abc', height = 1}"))
+        .eval::<Window>()
+        .unwrap_err();
+    assert!(err.to_string().contains("field `width`"), "{err}");
+    let err = lua.load("{title = 'x', width = 1}").eval::<Window>().unwrap_err();
+    assert!(err.to_string().contains("field `height`"), "{err}");
+    let err = lua.load("{width = 1, height = 1}").eval::<Window>().unwrap_err();
+    assert!(err.to_string().contains("field `title`"), "{err}");
+    let err = (lua.load("{title = 'x', width = 1, height = 1, min_size = {width = 1}}"))
+        .eval::<Window>()
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("field `min_size`\nfield `height`"),
+        "{err}"
+    );
+    let err = lua.load("123").eval::<Window>().unwrap_err();
+    assert!(err.to_string().contains("expected table"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_ranged_conversion() -> Result<()> {
+    let lua = Lua::new();
+
+    // Value helpers
+    assert_eq!(Value::Integer(255).try_into_u8("byte")?, 255);
+    assert_eq!(Value::Number(-3.0).try_into_i8("offset")?, -3);
+    match Value::Integer(70000).try_into_u16("port") {
+        Err(Error::OutOfRange {
+            value,
+            min,
+            max,
+            label,
+        }) => {
+            assert_eq!(value, "70000");
+            assert_eq!((min, max), (0, 65535));
+            assert_eq!(label.as_deref(), Some("port"));
+        }
+        r => panic!("expected OutOfRange error, got {r:?}"),
+    }
+    let err = Value::Integer(-1).try_into_u32("count").unwrap_err();
+    assert_eq!(err.to_string(), "count -1 is out of range [0, 4294967295]");
+    assert!(matches!(
+        Value::Number(f64::NAN).try_into_i64("n"),
+        Err(Error::OutOfRange { .. })
+    ));
+    assert!(matches!(
+        Value::Number(1.5).try_into_u64("n"),
+        Err(Error::FromLuaConversionError { .. })
+    ));
+    assert!(matches!(
+        Value::Boolean(true).try_into_i32("n"),
+        Err(Error::FromLuaConversionError { .. })
+    ));
+
+    // `Ranged` extractor
+    let f = lua.create_function(|_, (volume, pan): (Ranged<u8, 0, 100>, Ranged<i32, -10, 10>)| {
+        Ok(*volume as i32 + pan.into_inner())
+    })?;
+    assert_eq!(f.call::<i32>((100, -10))?, 90);
+    assert_eq!(f.call::<i32>((50.0, 2))?, 52);
+    match f.call::<i32>((50, 11)) {
+        Err(Error::CallbackError { cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { pos: 2, cause, .. } => {
+                assert_eq!(cause.to_string(), "value 11 is out of range [-10, 10]")
+            }
+            err => panic!("expected BadArgument error, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    // Range is wider than the target type
+    assert!(matches!(
+        lua.unpack::<Ranged<u8, 0, 1000>>(Value::Integer(300)),
+        Err(Error::OutOfRange { .. })
+    ));
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use std::io;
 
-use mlua::{Error, ErrorContext, Lua, Result};
+use mlua::{
+    ConversionErrorInfo, Error, ErrorContext, ErrorObject, ErrorObjectOptions, Lua, Result, UserData,
+    UserDataMethods, UserDataRef,
+};
 
 #[test]
 fn test_error_context() -> Result<()> {
@@ -72,3 +75,204 @@ fn test_error_chain() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_error_push_context() -> Result<()> {
+    let lua = Lua::new();
+
+    let func = lua.create_function(|_, ()| {
+        Err::<(), _>(Error::external(io::Error::other("other")))
+            .push_context("while reading file")
+            .push_context("while loading config")
+    })?;
+    lua.globals().set("func", &func)?;
+
+    let msg = lua
+        .load("local _, err = pcall(func); return tostring(err)")
+        .eval::<String>()?;
+    let (outer, inner) = (msg.find("while loading config"), msg.find("while reading file"));
+    assert!(outer.is_some() && outer < inner, "{msg}");
+    assert!(msg.contains("other"));
+
+    let err = func.call::<()>(()).err().unwrap();
+    let Error::CallbackError { cause, .. } = &err else {
+        unreachable!()
+    };
+    assert!(err
+        .to_string()
+        .contains("while loading config\nwhile reading file\n"));
+    assert!(cause.downcast_ref::<io::Error>().is_none());
+    let Error::WithContext { cause, .. } = cause.as_ref() else {
+        unreachable!()
+    };
+    assert!(cause.downcast_ref::<io::Error>().is_some());
+
+    Ok(())
+}
+
+#[cfg(feature = "anyhow")]
+#[test]
+fn test_error_anyhow() -> Result<()> {
+    use anyhow::Context;
+
+    let lua = Lua::new();
+
+    let func = lua.create_function(|_, ()| -> Result<()> {
+        let res: anyhow::Result<()> = Err(io::Error::new(io::ErrorKind::NotFound, "not found").into());
+        Ok(res.context("failed to open config")?)
+    })?;
+    lua.globals().set("func", &func)?;
+
+    let msg = lua
+        .load("local _, err = pcall(func); return tostring(err)")
+        .eval::<String>()?;
+    assert!(msg.contains("failed to open config: not found"), "{msg}");
+
+    let err = func.call::<()>(()).err().unwrap();
+    let Error::CallbackError { cause, .. } = &err else {
+        unreachable!()
+    };
+    let io_err = cause.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(io_err.kind(), io::ErrorKind::NotFound);
+
+    // mlua errors are passed through
+    let err = Error::from(anyhow::Error::from(Error::runtime("boom")));
+    assert!(matches!(err, Error::RuntimeError(ref msg) if msg == "boom"));
+
+    Ok(())
+}
+
+#[test]
+fn test_conversion_error_callback() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+
+    struct Point;
+
+    impl UserData for Point {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("move_by", |_, _, (_dx, _dy): (f64, f64)| Ok(()));
+        }
+    }
+
+    let lua = Lua::new();
+    let errors = Arc::new(Mutex::new(Vec::<ConversionErrorInfo>::new()));
+    let errors2 = errors.clone();
+    lua.on_conversion_error(move |info| errors2.lock().unwrap().push(info.clone()));
+
+    let globals = lua.globals();
+    globals.set("len", lua.create_function(|_, s: String| Ok(s.len()))?)?;
+    globals.set(
+        "fail",
+        lua.create_function(|_, ()| Err::<(), _>(Error::runtime("fail")))?,
+    )?;
+    globals.set("point", Point)?;
+
+    lua.load(
+        r#"
+        pcall(len, {})
+        pcall(point.move_by, point, 1, "x")
+        pcall(fail)
+        len("ok")
+    "#,
+    )
+    .set_name("script")
+    .exec()?;
+
+    let errors = std::mem::take(&mut *errors.lock().unwrap());
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].function, None);
+    assert_eq!(errors[0].arg_index, Some(1));
+    assert_eq!(
+        (errors[0].actual, errors[0].expected.as_str()),
+        ("table", "String")
+    );
+    assert_eq!(errors[0].location.as_deref(), Some("[string \"script\"]:2"));
+    assert_eq!(errors[1].function.as_deref(), Some("Point.move_by"));
+    assert_eq!(errors[1].arg_index, Some(3));
+    assert_eq!((errors[1].actual, errors[1].expected.as_str()), ("string", "f64"));
+    assert_eq!(errors[1].location.as_deref(), Some("[string \"script\"]:3"));
+
+    lua.remove_conversion_error_callback();
+    assert!(lua.load("len(nil)").exec().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_error_objects() -> Result<()> {
+    let lua = Lua::new();
+    lua.install_error_objects(ErrorObjectOptions::new())?;
+
+    let globals = lua.globals();
+    globals.set("add", lua.create_function(|_, (a, b): (i32, i32)| Ok(a + b))?)?;
+    globals.set(
+        "fail",
+        lua.create_function(|_, ()| Err::<(), _>(Error::runtime("boom")).context("while failing"))?,
+    )?;
+
+    lua.load(
+        r##"
+        local ok, err = pcall(add, 1, "x")
+        assert(not ok)
+        assert(err.kind == "bad_argument", err.kind)
+        assert(err.cause.kind == "from_lua_conversion_error")
+        assert(#err.chain == 2)
+        assert(err.traceback:find("stack traceback"))
+        assert(err.backtrace == nil)
+        assert(tostring(err):find("bad argument #2"))
+
+        local ok, err = pcall(fail)
+        assert(err.kind == "with_context")
+        assert(err.message:find("while failing"))
+        assert(err.cause.kind == "runtime_error" and err.cause.message == "runtime error: boom")
+
+        -- Handlers receive error objects
+        local ok, kind = xpcall(fail, function(err) return err.kind end)
+        assert(not ok and kind == "with_context")
+
+        -- Lua errors are passed unchanged
+        local ok, err = pcall(error, "plain")
+        assert(err == "plain")
+        local t = {}
+        local ok, err = pcall(error, t)
+        assert(err == t)
+        assert(pcall(add, 1, 2))
+        assert(select("#", pcall(function() return 1, 2, 3 end)) == 4)
+    "##,
+    )
+    .exec()?;
+
+    // The original error is available in Rust
+    let err = lua
+        .load("local _, err = pcall(fail); return err")
+        .eval::<UserDataRef<ErrorObject>>()?;
+    assert!(matches!(err.error(), Error::CallbackError { .. }));
+    assert!(err.backtrace().is_none());
+
+    // Rust backtraces
+    lua.install_error_objects(ErrorObjectOptions::new().capture_backtrace(true))?;
+    let err = lua
+        .load("local _, err = pcall(fail); assert(type(err.backtrace) == 'string'); return err")
+        .eval::<UserDataRef<ErrorObject>>()?;
+    assert!(err.backtrace().is_some());
+
+    // Yielding across the wrappers
+    #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+    {
+        let value = lua
+            .load(
+                r#"
+                local co = coroutine.wrap(function()
+                    local ok, value = pcall(coroutine.yield, 1)
+                    return value
+                end)
+                co()
+                return co(2)
+            "#,
+            )
+            .eval::<i32>()?;
+        assert_eq!(value, 2);
+    }
+
+    Ok(())
+}
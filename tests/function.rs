@@ -1,4 +1,10 @@
-use mlua::{Error, Function, Lua, Result, String, Table};
+use std::string::String as StdString;
+use std::sync::{Arc, Mutex};
+
+use mlua::{
+    Callback, Error, ErrorContext, Function, Lua, Mapper, MemoizeOptions, MultiValue, Predicate, Result,
+    String, Table, TypedFunction, Value,
+};
 
 #[test]
 fn test_function() -> Result<()> {
@@ -53,6 +59,79 @@ fn test_bind() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_memoized() -> Result<()> {
+    let lua = Lua::new();
+
+    let globals = lua.globals();
+    lua.load(
+        r#"
+        calls = 0
+        function area(rect, scale)
+            calls = calls + 1
+            if rect.w < 0 then error("negative width") end
+            return rect.w * rect.h * (scale or 1), "m2", nil
+        end
+    "#,
+    )
+    .exec()?;
+    let area = globals.get::<Function>("area")?;
+    let area = area.memoized(MemoizeOptions::new().capacity(2))?;
+    globals.set("area", &area)?;
+
+    // Structurally equal arguments hit the cache, results (including nils) are preserved
+    let res = lua
+        .load("{ area({w = 2, h = 3}), select('#', area({h = 3, w = 2})) }")
+        .eval::<Table>()?;
+    assert_eq!(res.get::<i64>(1)?, 6);
+    assert_eq!(res.get::<i64>(2)?, 3);
+    assert_eq!(
+        area.call::<(i64, String)>((lua.load("{w = 2, h = 3}").eval::<Table>()?,))?
+            .0,
+        6
+    );
+    assert_eq!(globals.get::<u32>("calls")?, 1);
+    assert_eq!(
+        area.call::<i64>((lua.load("{w = 2, h = 3}").eval::<Table>()?, 2))?,
+        12
+    );
+    assert_eq!(globals.get::<u32>("calls")?, 2);
+
+    // Least recently used results are evicted
+    lua.load("area({w = 1, h = 1}); area({w = 2, h = 3}, 2)").exec()?;
+    assert_eq!(globals.get::<u32>("calls")?, 3);
+    lua.load("area({w = 2, h = 3})").exec()?;
+    assert_eq!(globals.get::<u32>("calls")?, 4);
+
+    // Errors are not cached
+    for _ in 0..2 {
+        assert!(lua.load("area({w = -1, h = 1})").exec().is_err());
+    }
+    assert_eq!(globals.get::<u32>("calls")?, 6);
+
+    // Unhashable arguments
+    assert!(lua.load("area({w = 1, h = print})").exec().is_err());
+
+    // Recursive calls
+    lua.load(
+        r#"
+        fib_calls = 0
+        fib = function(n)
+            fib_calls = fib_calls + 1
+            if n < 2 then return n end
+            return fib(n - 1) + fib(n - 2)
+        end
+    "#,
+    )
+    .exec()?;
+    let fib = globals.get::<Function>("fib")?.memoized(MemoizeOptions::new())?;
+    globals.set("fib", &fib)?;
+    assert_eq!(fib.call::<i64>(50)?, 12586269025);
+    assert_eq!(globals.get::<u32>("fib_calls")?, 51);
+
+    Ok(())
+}
+
 #[test]
 fn test_rust_function() -> Result<()> {
     let lua = Lua::new();
@@ -269,6 +348,62 @@ fn test_function_deep_clone() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_function_deep_clone_to() -> Result<()> {
+    use mlua::UpvaluePolicy;
+
+    let lua1 = Lua::new();
+    let lua2 = Lua::new();
+
+    // No upvalues (except `_ENV`)
+    lua1.globals().set("x", 1)?;
+    lua2.globals().set("x", 2)?;
+    let func: Function = lua1.load("function(a) return a + x end").eval()?;
+    let func2 = func.deep_clone_to(&lua2, UpvaluePolicy::Error)?;
+    assert_eq!(func.call::<i64>(10)?, 11);
+    assert_eq!(func2.call::<i64>(10)?, 12);
+
+    let counter: Function = lua1
+        .load("local n, step = 0, 1; return function() n = n + step; return n end")
+        .eval()?;
+    counter.call::<()>(())?;
+    match counter.deep_clone_to(&lua2, UpvaluePolicy::Error) {
+        Err(Error::RuntimeError(msg)) => assert!(msg.contains("upvalue 'n'")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    lua2.globals().set("n", 100)?;
+    lua2.globals().set("step", 10)?;
+    let counter2 = counter.deep_clone_to(&lua2, UpvaluePolicy::Globals)?;
+    assert_eq!(counter2.call::<i64>(())?, 110);
+    assert_eq!(counter.call::<i64>(())?, 2);
+
+    #[cfg(feature = "serialize")]
+    {
+        let counter3 = counter.deep_clone_to(&lua2, UpvaluePolicy::Copy)?;
+        assert_eq!(counter3.call::<i64>(())?, 3);
+        assert_eq!(counter3.call::<i64>(())?, 4);
+        assert_eq!(counter.call::<i64>(())?, 3);
+
+        let config: Function = lua1
+            .load("local cfg = { name = 'test', tags = {'a', 'b'} }; return function() return cfg.name .. #cfg.tags end")
+            .eval()?;
+        let config2 = config.deep_clone_to(&lua2, UpvaluePolicy::Copy)?;
+        assert_eq!(config2.call::<String>(())?, "test2");
+
+        // Functions cannot be copied
+        let wrapper: Function = lua1.load("local f = print; return function() f() end").eval()?;
+        assert!(wrapper.deep_clone_to(&lua2, UpvaluePolicy::Copy).is_err());
+    }
+
+    // Rust functions cannot be cloned
+    let rust_func = lua1.create_function(|_, ()| Ok(()))?;
+    assert!(rust_func.deep_clone_to(&lua2, UpvaluePolicy::Globals).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_function_wrap() -> Result<()> {
     let lua = Lua::new();
@@ -369,3 +504,265 @@ fn test_function_wrap_raw() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_function_recreate_callbacks() -> Result<()> {
+    let lua = Lua::new();
+
+    for round in 0..3 {
+        let funcs = (0..1000)
+            .map(|i| lua.create_function(move |_, x: i64| Ok(x + i + round)))
+            .collect::<Result<Vec<_>>>()?;
+        for (i, f) in funcs.iter().enumerate() {
+            assert_eq!(f.call::<i64>(1)?, 1 + i as i64 + round);
+        }
+        drop(funcs);
+        lua.gc_collect()?;
+        lua.gc_collect()?;
+    }
+
+    // Callbacks created while other callback is running
+    let make = lua.create_function(|lua, n: i64| {
+        let funcs = (0..100)
+            .map(|i| lua.create_function(move |_, ()| Ok(n * i)))
+            .collect::<Result<Vec<_>>>()?;
+        funcs[99].call::<i64>(())
+    })?;
+    assert_eq!(make.call::<i64>(2)?, 198);
+
+    // Captured values of small and large closures are dropped when collected
+    let counter = Arc::new(());
+    let funcs = (0..100)
+        .map(|i| {
+            let (counter, data) = (counter.clone(), [i; 16]);
+            match i % 2 {
+                0 => lua.create_function(move |_, ()| Ok(i + Arc::strong_count(&counter) as i64)),
+                _ => lua.create_function(move |_, ()| {
+                    Ok(data.iter().sum::<i64>() + Arc::strong_count(&counter) as i64)
+                }),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(funcs[10].call::<i64>(())?, 10 + 101);
+    assert_eq!(funcs[11].call::<i64>(())?, 11 * 16 + 101);
+    drop(funcs);
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(Arc::strong_count(&counter), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_function_call_with_handler() -> Result<()> {
+    let lua = Lua::new();
+
+    let func: Function = lua
+        .load(
+            r#"
+            function(kind, x)
+                if kind == "lua" then
+                    error("bad " .. x)
+                elseif kind == "rust" then
+                    fail(x)
+                end
+                return x, x * 2
+            end
+        "#,
+        )
+        .eval()?;
+    lua.globals().set(
+        "fail",
+        lua.create_function(|_, x: i64| Err::<(), _>(Error::runtime(format!("failed {x}"))))?,
+    )?;
+
+    // Lua handler
+    let handler: Function = lua
+        .load("function(err) return 'handled: ' .. tostring(err) end")
+        .eval()?;
+    let res = func.call_with_handler::<(i64, i64)>(("ok", 2), handler.clone())?;
+    assert_eq!(res, (2, 4));
+    match func.call_with_handler::<()>(("lua", 1), handler.clone()) {
+        Err(Error::RuntimeError(msg)) => {
+            assert!(msg.starts_with("handled: "));
+            assert!(msg.ends_with("bad 1"));
+        }
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+    match func.call_with_handler::<()>(("rust", 2), handler) {
+        Err(Error::RuntimeError(msg)) => assert!(msg.contains("failed 2")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+
+    // Rust handler receives Rust errors and can wrap them
+    let handler = lua.create_function(|_, err: Error| Ok(Error::external(format!("wrapped: {err}"))))?;
+    match func.call_with_handler::<()>(("rust", 3), handler) {
+        Err(Error::ExternalError(err)) => {
+            assert!(err.to_string().starts_with("wrapped: runtime error: failed 3"))
+        }
+        r => panic!("expected ExternalError, got {r:?}"),
+    }
+
+    // The handler is called before the stack is unwound
+    let handler = lua.create_function(|lua, err: Error| {
+        let depth = (0..)
+            .take_while(|&level| lua.inspect_stack(level).is_some())
+            .count();
+        Ok(err.context(format!("depth {depth}")))
+    })?;
+    match func.call_with_handler::<()>(("lua", 4), handler) {
+        Err(Error::WithContext { context, .. }) => assert!(context != "depth 0" && context != "depth 1"),
+        r => panic!("expected WithContext, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_function_callback_middleware() -> Result<()> {
+    let lua = Lua::new();
+    let calls = Arc::new(Mutex::new(Vec::new()));
+
+    let calls2 = calls.clone();
+    lua.set_callback_middleware(move |info, next| {
+        calls2
+            .lock()
+            .unwrap()
+            .push((info.name().map(str::to_string), info.arg_count()));
+        if info.args().front() == Some(&Value::Boolean(false)) {
+            return Err(Error::runtime("denied"));
+        }
+        let mut results = next.run()?;
+        results.push_back(Value::Boolean(true));
+        Ok(results)
+    });
+
+    let sum = lua.create_function(|_, (a, b): (i64, i64)| Ok(a + b))?;
+    lua.globals().set("sum", &sum)?;
+    let t = lua.create_table()?;
+    t.set("check", lua.create_function(|_, _: bool| Ok("checked"))?)?;
+    lua.globals().set("t", t)?;
+
+    // Tail calls have no name in LuaJIT
+    let (res, extra): (i64, bool) = lua
+        .load("local res, extra = sum(1, 2); return res, extra")
+        .eval()?;
+    assert_eq!((res, extra), (3, true));
+    assert_eq!(
+        lua.load("local res = t.check(true); return res")
+            .eval::<String>()?,
+        "checked"
+    );
+    let err = lua.load("t.check(false)").exec().unwrap_err().to_string();
+    assert!(err.contains("denied"), "{err}");
+    // Calls from Rust have no name
+    assert_eq!(sum.call::<MultiValue>((2, 3))?.len(), 2);
+    // Errors of the function are propagated
+    assert!(lua.load("sum('a', 1)").exec().is_err());
+
+    let log = calls.lock().unwrap().clone();
+    assert_eq!(log.iter().map(|(_, n)| *n).collect::<Vec<_>>(), [2, 1, 1, 2, 2]);
+    #[cfg(not(feature = "luau"))]
+    assert_eq!(
+        log.into_iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        [Some("sum"), Some("check"), Some("check"), None, Some("sum")].map(|s| s.map(str::to_string))
+    );
+
+    lua.remove_callback_middleware();
+    assert_eq!(lua.load("return select('#', sum(1, 2))").eval::<i64>()?, 1);
+    assert_eq!(calls.lock().unwrap().len(), 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_function_wrappers() -> Result<()> {
+    type RunArgs = (Callback<(i64, StdString)>, Predicate<i64>, Mapper<i64, StdString>);
+
+    let lua = Lua::new();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen2 = seen.clone();
+    let run = lua.create_function(move |_, (cb, pred, mapper): RunArgs| {
+        for i in 1..=4 {
+            if pred.test(i)? {
+                cb.call((i, mapper.map(i)?))?;
+            }
+        }
+        Ok(())
+    })?;
+    lua.globals().set("run", run)?;
+    lua.globals().set(
+        "seen",
+        lua.create_function(move |_, (i, s): (i64, StdString)| {
+            seen2.lock().unwrap().push((i, s));
+            Ok(())
+        })?,
+    )?;
+    lua.load(
+        r#"
+        run(
+            function(i, s) seen(i, s); return "ignored" end,
+            function(i) if i % 2 == 0 then return i end end,
+            function(i) return "n" .. i end
+        )
+    "#,
+    )
+    .exec()?;
+    assert_eq!(*seen.lock().unwrap(), vec![(2, "n2".into()), (4, "n4".into())]);
+
+    // Wrong types are rejected with a conversion error
+    let res = lua.load("run(1, 2, 3)").exec();
+    match res {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { cause, .. } => {
+                assert!(matches!(cause.as_ref(), Error::FromLuaConversionError { .. }))
+            }
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        res => panic!("expected CallbackError, got {res:?}"),
+    }
+
+    // Round trip
+    let f: Function = lua.load("function(x) return x * 2 end").eval()?;
+    let mapper = Mapper::<i64, i64>::from(f.clone());
+    assert_eq!(mapper.map(21)?, 42);
+    assert_eq!(mapper.clone().into_function(), f);
+    let back: Mapper<i64, i64> = lua.unpack(lua.pack(mapper.clone())?)?;
+    assert_eq!(back, mapper);
+
+    Ok(())
+}
+
+#[test]
+fn test_typed_function() -> Result<()> {
+    struct Handlers {
+        add: TypedFunction<(i64, i64), i64>,
+        split: TypedFunction<StdString, (StdString, Option<StdString>)>,
+    }
+
+    let lua = Lua::new();
+    let handlers = Handlers {
+        add: lua.load("function(a, b) return a + b end").eval()?,
+        split: lua
+            .load("function(s) return s:match('([^=]*)=?(.*)') end")
+            .eval()?,
+    };
+    assert_eq!(handlers.add.call((1, 2))?, 3);
+    assert_eq!(handlers.split.call("a=b".into())?, ("a".into(), Some("b".into())));
+
+    // Results are checked on each call
+    let bad: TypedFunction<(), i64> = lua.load("function() return 'x' end").eval()?;
+    assert!(matches!(bad.call(()), Err(Error::FromLuaConversionError { .. })));
+
+    // Conversion from non-function values fails early
+    let res = lua.load("123").eval::<TypedFunction<(), ()>>();
+    assert!(matches!(res, Err(Error::FromLuaConversionError { .. })));
+
+    // Passing back to Lua
+    lua.globals().set("add", handlers.add.clone())?;
+    assert_eq!(lua.load("add(2, 3)").eval::<i64>()?, 5);
+    assert_eq!(handlers.add.function(), &lua.globals().get::<Function>("add")?);
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use mlua::{Error, JsonOptions, Lua, Result, Table, Value};
+
+#[test]
+fn test_json_encode() -> Result<()> {
+    let lua = Lua::new();
+
+    let t: Table = lua
+        .load(
+            r#"
+        {
+            int = 1,
+            num = 2.5,
+            str = "hello \"world\"\n",
+            bool = true,
+            arr = { 1, 2, 3 },
+            obj = { a = {} },
+        }
+    "#,
+        )
+        .eval()?;
+    t.set("null", Value::NULL)?;
+    let json = t.to_json_string(JsonOptions::new().sort_keys(true))?;
+    assert_eq!(
+        json,
+        r#"{"arr":[1,2,3],"bool":true,"int":1,"null":null,"num":2.5,"obj":{"a":{}},"str":"hello \"world\"\n"}"#
+    );
+
+    // Empty tables
+    let t = lua.create_table()?;
+    assert_eq!(t.to_json_string(JsonOptions::new())?, "{}");
+    let opts = JsonOptions::new().encode_empty_tables_as_array(true);
+    assert_eq!(t.to_json_string(opts)?, "[]");
+
+    // Floats
+    let t: Table = lua.load("{ 1.0, 2, 0.5 }").eval()?;
+    let opts = JsonOptions::new().explicit_floats(true);
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    assert_eq!(t.to_json_string(opts)?, "[1.0,2,0.5]");
+    #[cfg(not(any(feature = "lua54", feature = "lua53")))]
+    assert_eq!(t.to_json_string(opts)?, "[1,2,0.5]");
+
+    // Unsupported types
+    let t: Table = lua.load("{ a = print, b = 1 }").eval()?;
+    match t.to_json_string(JsonOptions::new()) {
+        Err(Error::FromLuaConversionError { .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    let opts = JsonOptions::new().deny_unsupported_types(false);
+    assert_eq!(t.to_json_string(opts)?, r#"{"b":1}"#);
+
+    // Recursive tables
+    let t: Table = lua.load("local t = {}; t.t = t; return t").eval()?;
+    assert!(t.to_json_string(JsonOptions::new()).is_err());
+
+    // Deeply nested tables
+    let t: Table = lua
+        .load("local t = {} for i = 1, 200000 do t = {t} end return t")
+        .eval()?;
+    match t.to_json_string(JsonOptions::new()) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "recursion limit exceeded")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    let t: Table = lua
+        .load("local t = {} for i = 1, 100 do t = {t} end return t")
+        .eval()?;
+    assert!(t.to_json_string(JsonOptions::new()).is_ok());
+
+    // Keys that encode to the same object key
+    let t: Table = lua.load(r#"{ [1] = "a", ["1"] = "b" }"#).eval()?;
+    match t.to_json_string(JsonOptions::new()) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "duplicate object key '1'")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_json_decode() -> Result<()> {
+    let lua = Lua::new();
+
+    let value = lua.parse_json(r#" {"a": [1, 2.5, -3e2, null, true], "b": "\u00e9\ud83d\ude00\t"} "#)?;
+    let t = value.as_table().unwrap();
+    let arr: Table = t.get("a")?;
+    assert_eq!(arr.raw_len(), 5);
+    assert_eq!(arr.get::<Value>(1)?, Value::Integer(1));
+    assert_eq!(arr.get::<Value>(2)?, Value::Number(2.5));
+    assert_eq!(arr.get::<f64>(3)?, -300.0);
+    assert!(arr.get::<Value>(4)?.is_null());
+    assert!(arr.get::<bool>(5)?);
+    assert_eq!(t.get::<String>("b")?, "é😀\t");
+
+    let opts = JsonOptions::new().decode_integers(false);
+    assert_eq!(lua.parse_json_with("10", opts)?, Value::Number(10.0));
+
+    // Errors
+    for json in [
+        "",
+        "[1,]",
+        "{\"a\" 1}",
+        "01",
+        "\"abc",
+        "[1] 2",
+        "tru",
+        "\"\\ud800\"",
+        "\"\\u+041\"",
+        "\"\\u-041\"",
+        "\"\\u00 1\"",
+    ] {
+        assert!(lua.parse_json(json).is_err(), "expected error for `{json}`");
+    }
+    let deep = "[".repeat(1000) + &"]".repeat(1000);
+    assert!(lua.parse_json(deep).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_json_roundtrip() -> Result<()> {
+    let lua = Lua::new();
+
+    let json = r#"{"list":[{"id":1,"tags":["x","y"]},{"id":2,"tags":[]}],"name":"test","ratio":0.25}"#;
+    let value = lua.parse_json(json)?;
+    let opts = JsonOptions::new()
+        .sort_keys(true)
+        .encode_empty_tables_as_array(true);
+    assert_eq!(value.as_table().unwrap().to_json_string(opts)?, json);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn test_serde_json_value() -> Result<()> {
+    use mlua::NumberPolicy;
+    use serde_json::json;
+
+    let lua = Lua::new();
+
+    let json = json!({
+        "int": 1,
+        "float": 2.5,
+        "whole": 3.0,
+        "big": u64::MAX,
+        "str": "hello",
+        "bool": true,
+        "null": null,
+        "arr": [1, "two", [3]],
+        "empty_arr": [],
+        "obj": { "nested": { "a": 1 } },
+    });
+
+    let value = lua.from_json_value(&json, NumberPolicy::PreferInteger)?;
+    let t = value.as_table().unwrap();
+    assert_eq!(t.get::<Value>("int")?, Value::Integer(1));
+    assert_eq!(t.get::<Value>("float")?, Value::Number(2.5));
+    assert_eq!(t.get::<Value>("whole")?, Value::Integer(3));
+    assert_eq!(t.get::<f64>("big")?, u64::MAX as f64);
+    assert_eq!(t.get::<String>("str")?, "hello");
+    assert!(t.get::<bool>("bool")?);
+    assert!(t.get::<Value>("null")?.is_null());
+    assert_eq!(t.get::<Table>("arr")?.raw_len(), 3);
+    assert_eq!(t.get_path::<i64>("obj.nested.a")?, 1);
+
+    let value = lua.from_json_value(&json, NumberPolicy::PreferFloat)?;
+    let t = value.as_table().unwrap();
+    assert_eq!(t.get::<Value>("int")?, Value::Number(1.0));
+    assert_eq!(t.get::<Value>("whole")?, Value::Number(3.0));
+
+    // Roundtrip
+    let value = lua.from_json_value(&json, NumberPolicy::PreferInteger)?;
+    let mut expected = json.clone();
+    expected["whole"] = json!(3);
+    expected["big"] = json!(u64::MAX as f64);
+    let back = lua.to_json_value(&value, NumberPolicy::PreferInteger)?;
+    #[cfg(feature = "serialize")]
+    assert_eq!(back, expected);
+    #[cfg(not(feature = "serialize"))]
+    {
+        // Empty arrays cannot be distinguished from empty objects
+        expected["empty_arr"] = json!({});
+        assert_eq!(back, expected);
+    }
+
+    // Number policy when converting to JSON
+    let value = lua.load("{ 1, 2.0, 2.5 }").eval::<Value>()?;
+    assert_eq!(
+        lua.to_json_value(&value, NumberPolicy::PreferInteger)?,
+        json!([1, 2, 2.5])
+    );
+    assert_eq!(
+        lua.to_json_value(&value, NumberPolicy::PreferFloat)?,
+        json!([1.0, 2.0, 2.5])
+    );
+
+    // Mixed and numeric keys become objects
+    let value = lua.load("{ 1, 2, x = 3, [10] = 4 }").eval::<Value>()?;
+    assert_eq!(
+        lua.to_json_value(&value, NumberPolicy::PreferInteger)?,
+        json!({ "1": 1, "2": 2, "x": 3, "10": 4 })
+    );
+
+    // Errors
+    let value = lua
+        .load("(function() local t = {}; t.t = t; return t end)()")
+        .eval::<Value>()?;
+    match lua.to_json_value(&value, NumberPolicy::PreferInteger) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "recursive table detected")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    let value = lua
+        .load("local t = {} for i = 1, 200000 do t = {t} end return t")
+        .eval::<Value>()?;
+    match lua.to_json_value(&value, NumberPolicy::PreferInteger) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "recursion limit exceeded")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    let value = lua.load("{ f = print }").eval::<Value>()?;
+    assert!(lua.to_json_value(&value, NumberPolicy::PreferInteger).is_err());
+    let value = lua.load("{ 0/0 }").eval::<Value>()?;
+    assert!(lua.to_json_value(&value, NumberPolicy::PreferInteger).is_err());
+
+    Ok(())
+}
@@ -95,6 +95,76 @@ fn test_require() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_require_graph() -> Result<()> {
+    if cfg!(target_arch = "wasm32") {
+        return Ok(());
+    }
+
+    let lua = Lua::new();
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let modules = [
+        ("app", "require('util'); require('config'); return {}"),
+        ("util", "require('config'); require('string'); return {}"),
+        ("config", "return {}"),
+        ("cycle_a", "return require('cycle_b')"),
+        ("cycle_b", "return require('cycle_c')"),
+        ("cycle_c", "return require('cycle_a')"),
+    ];
+    for (name, source) in modules {
+        fs::write(temp_dir.path().join(format!("{name}.luau")), source)?;
+    }
+    lua.globals()
+        .get::<Table>("package")?
+        .set("path", temp_dir.path().join("?.luau").to_string_lossy())?;
+
+    lua.load("require('app')").exec()?;
+    let graph = lua.module_graph()?;
+    assert_eq!(
+        graph.modules().collect::<Vec<_>>(),
+        ["app", "config", "string", "util"]
+    );
+    assert_eq!(graph.dependencies("app").collect::<Vec<_>>(), ["config", "util"]);
+    assert_eq!(
+        graph.dependencies("util").collect::<Vec<_>>(),
+        ["config", "string"]
+    );
+    assert_eq!(graph.dependencies("config").count(), 0);
+    assert_eq!(graph.dependents("config").collect::<Vec<_>>(), ["app", "util"]);
+    let mut dependents = graph.transitive_dependents("string");
+    dependents.sort();
+    assert_eq!(dependents, ["app", "util"]);
+
+    // Unloading a module forgets its dependencies and unloads modules that require it
+    let mut unloaded = lua.unload_module("config", true)?;
+    unloaded.sort();
+    assert_eq!(unloaded, ["app", "config", "util"]);
+    let graph = lua.module_graph()?;
+    assert_eq!(graph.modules().collect::<Vec<_>>(), ["string"]);
+    lua.load("require('util')").exec()?;
+    let graph = lua.module_graph()?;
+    assert_eq!(graph.modules().collect::<Vec<_>>(), ["config", "string", "util"]);
+
+    match lua.load("require('cycle_a')").exec() {
+        Err(Error::CyclicRequire { chain }) => {
+            assert_eq!(chain, ["cycle_a", "cycle_b", "cycle_c", "cycle_a"]);
+        }
+        r => panic!("expected CyclicRequire error, got {r:?}"),
+    }
+    // The failed modules must not be considered as loading anymore
+    match lua.load("require('cycle_b')").exec() {
+        Err(Error::CyclicRequire { chain }) => {
+            assert_eq!(chain, ["cycle_b", "cycle_c", "cycle_a", "cycle_b"]);
+        }
+        r => panic!("expected CyclicRequire error, got {r:?}"),
+    }
+    let graph = lua.module_graph()?;
+    assert_eq!(graph.dependencies("cycle_c").collect::<Vec<_>>(), ["cycle_a"]);
+
+    Ok(())
+}
+
 #[cfg(not(feature = "luau-vector4"))]
 #[test]
 fn test_vectors() -> Result<()> {
@@ -297,6 +367,30 @@ fn test_sandbox_nolibs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_granular_stdlib() -> Result<()> {
+    let lua = Lua::new_with(StdLib::BUFFER, LuaOptions::default())?;
+    assert!(lua.globals().get::<Option<Table>>("buffer")?.is_some());
+    assert_eq!(lua.globals().get::<Value>("vector")?, Value::Nil);
+    assert_eq!(lua.globals().get::<Value>("bit32")?, Value::Nil);
+
+    lua.load_std_libs(StdLib::VECTOR | StdLib::MATH)?;
+    let v: Vector = lua.load("vector(1, 2, 3)").eval()?;
+    assert_eq!((v.x(), v.y(), v.z()), (1.0, 2.0, 3.0));
+
+    // Toggle readonly on individual libraries
+    lua.sandbox(true)?;
+    let err = lua.load("buffer.custom = 1").exec().unwrap_err();
+    assert!(err.to_string().contains("attempt to modify a readonly table"));
+    lua.set_std_libs_readonly(StdLib::BUFFER, false)?;
+    lua.load("buffer.custom = 1").exec()?;
+    assert!(lua.load("math.custom = 1").exec().is_err());
+    lua.set_std_libs_readonly(StdLib::BUFFER, true)?;
+    assert!(lua.load("buffer.custom = 2").exec().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_sandbox_threads() -> Result<()> {
     let lua = Lua::new();
@@ -392,6 +486,112 @@ fn test_interrupts() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_auto_yield() -> Result<()> {
+    let lua = Lua::new();
+    lua.enable_auto_yield(10);
+
+    let func = lua
+        .load(
+            r#"
+        local n = 0
+        for i = 1, 1000 do n += i end
+        return n
+    "#,
+        )
+        .into_function()?;
+
+    // Coroutines are preempted
+    let co = lua.create_thread(func.clone())?;
+    let mut slices = 0;
+    let result = loop {
+        let result = co.resume::<Option<i64>>(())?;
+        if co.status() == ThreadStatus::Finished {
+            break result;
+        }
+        slices += 1;
+    };
+    assert_eq!(result, Some(500500));
+    assert!(slices >= 10);
+
+    // The main thread and non-yieldable code are not interrupted
+    assert_eq!(func.call::<i64>(())?, 500500);
+    let co = lua.create_thread(lua.create_function(move |_, ()| func.call::<i64>(()))?)?;
+    assert_eq!(co.resume::<i64>(())?, 500500);
+
+    // Lua-level generators are not preempted, only the coroutine resumed from Rust
+    let generator = lua
+        .load(
+            r#"
+        local function range(n)
+            return coroutine.wrap(function()
+                for i = 1, n do coroutine.yield(i) end
+            end)
+        end
+        local sum = 0
+        for i in range(1000) do sum += i end
+        return sum
+    "#,
+        )
+        .into_function()?;
+    let co = lua.create_thread(generator)?;
+    let mut slices = 0;
+    let result = loop {
+        let result = co.resume::<Option<i64>>(())?;
+        if co.status() == ThreadStatus::Finished {
+            break result;
+        }
+        slices += 1;
+    };
+    assert_eq!(result, Some(500500));
+    assert!(slices > 1);
+
+    // Each coroutine has its own counter
+    let co1 = lua.create_thread(lua.load("for i = 1, 100 do end").into_function()?)?;
+    let co2 = lua.create_thread(lua.load("for i = 1, 100 do end").into_function()?)?;
+    let (mut slices1, mut slices2) = (0, 0);
+    while co1.status() == ThreadStatus::Resumable || co2.status() == ThreadStatus::Resumable {
+        if co1.status() == ThreadStatus::Resumable {
+            co1.resume::<()>(())?;
+            slices1 += 1;
+        }
+        if co2.status() == ThreadStatus::Resumable {
+            co2.resume::<()>(())?;
+            slices2 += 1;
+        }
+    }
+    assert_eq!(slices1, slices2);
+
+    // Works together with the interrupt callback
+    let interrupts = Arc::new(AtomicU64::new(0));
+    let interrupts2 = interrupts.clone();
+    lua.set_interrupt(move |_| {
+        interrupts2.fetch_add(1, Ordering::Relaxed);
+        Ok(VmState::Continue)
+    });
+    let co = lua.create_thread(lua.load("for i = 1, 100 do end").into_function()?)?;
+    let mut slices = 0;
+    while co.status() == ThreadStatus::Resumable {
+        co.resume::<()>(())?;
+        slices += 1;
+    }
+    assert!(slices > 1);
+    assert!(interrupts.load(Ordering::Relaxed) > 0);
+
+    // Removing the interrupt keeps automatic yields
+    lua.remove_interrupt();
+    let co = lua.create_thread(lua.load("for i = 1, 100 do end").into_function()?)?;
+    co.resume::<()>(())?;
+    assert_eq!(co.status(), ThreadStatus::Resumable);
+
+    lua.disable_auto_yield();
+    let co = lua.create_thread(lua.load("for i = 1, 100 do end").into_function()?)?;
+    co.resume::<()>(())?;
+    assert_eq!(co.status(), ThreadStatus::Finished);
+
+    Ok(())
+}
+
 #[test]
 fn test_coverage() -> Result<()> {
     let lua = Lua::new();
@@ -470,3 +670,42 @@ fn test_fflags() {
     // We cannot really on any particular feature flag to be present
     assert!(Lua::set_fflag("UnknownFlag", true).is_err());
 }
+
+#[test]
+fn test_import_allowlist() -> Result<()> {
+    let lua = Lua::new();
+    let allowlist = ["string", "ipairs", "setmetatable"];
+
+    // Names in type annotations are not imports
+    let source = r#"
+        type Point<T = number> = { x: T, y: T, tag: Tag? }
+        export type Tag = "a" | "b"
+        local function sum<T>(points: { Point<T> }, ...: number): (number, string)
+            local total: number = 0
+            for _, p: Point in ipairs(points) do
+                if p.x < 0 then continue end
+                total += p.x :: number
+            end
+            return total, `total: {string.format("%d", total)}`
+        end
+        local obj = setmetatable({}, { __index = { n = if #string.rep("x", 2) > 1 then 1 else 0 } })
+        return sum({ { x = 1, y = 2 }, { x = -1, y = 0 }, { x = 2, y = 0 } }) + obj.n
+    "#;
+    let total: f64 = lua.load(source).set_import_allowlist(allowlist).eval()?;
+    assert_eq!(total, 4.0);
+
+    // Interpolated strings and compiled chunks are scanned too
+    let chunk = lua
+        .load("local x = `{string.rep('a', 2)} {os.time()}`")
+        .set_compiler(Compiler::new().set_optimization_level(2))
+        .set_import_allowlist(allowlist);
+    match chunk.exec() {
+        Err(Error::ImportNotAllowed { name, line }) => {
+            assert_eq!(name, "os");
+            assert_eq!(line, Some(1));
+        }
+        r => panic!("expected ImportNotAllowed error, got {r:?}"),
+    }
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use mlua::{Error, GCMode, Lua, Result, UserData};
+use mlua::{
+    Error, GCAction, GCMode, IntoLuaMulti, Lua, LuaOptions, MultiValue, Result, StdLib, Table, UserData,
+};
 
 #[test]
 fn test_memory_limit() -> Result<()> {
@@ -57,6 +59,51 @@ fn test_memory_limit_thread() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_memory_by_source() -> Result<()> {
+    let lua = Lua::new();
+    lua.load("x = {}").set_name("plugin").exec()?;
+    assert!(lua.memory_by_source().is_empty());
+
+    let options = LuaOptions::new().track_memory_by_source(true);
+    let lua = Lua::new_with(StdLib::ALL_SAFE, options)?;
+    if lua.set_memory_limit(0).is_err() {
+        // The mlua allocator is not used
+        return Ok(());
+    }
+
+    let plugin_a: Table = lua
+        .load(
+            r#"
+        local data = {}
+        return {
+            grow = function() for i = 1, 10000 do data[#data + 1] = "item" .. i end end,
+            clear = function() data = {} end,
+        }
+    "#,
+        )
+        .set_name("plugin_a")
+        .eval()?;
+    lua.load("small = {1, 2, 3}").set_name("plugin_b").exec()?;
+
+    let before = lua.memory_by_source();
+    plugin_a.get::<mlua::Function>("grow")?.call::<()>(())?;
+    let after = lua.memory_by_source();
+    assert!(after["plugin_a"] > before["plugin_a"] + 100_000);
+    // Memory of `plugin_b` can only be released (by GC)
+    assert!(after["plugin_b"] <= before["plugin_b"]);
+
+    plugin_a.get::<mlua::Function>("clear")?.call::<()>(())?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert!(lua.memory_by_source()["plugin_a"] < after["plugin_a"]);
+
+    let total: usize = lua.memory_by_source().values().sum();
+    assert!(total <= lua.used_memory());
+
+    Ok(())
+}
+
 #[test]
 fn test_gc_control() -> Result<()> {
     let lua = Lua::new();
@@ -94,6 +141,68 @@ fn test_gc_control() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_collectgarbage_policy() -> Result<()> {
+    let lua = Lua::new();
+
+    let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let calls2 = calls.clone();
+    lua.set_collectgarbage_policy(move |lua, option, args| {
+        calls2.lock().unwrap().push((option.to_string(), args.len()));
+        match option {
+            "collect" => Ok(GCAction::Deny),
+            "stop" => Ok(GCAction::Return(MultiValue::new())),
+            "count" => Ok(GCAction::Return(42.5.into_lua_multi(lua)?)),
+            _ => Ok(GCAction::Allow),
+        }
+    })?;
+
+    match lua.load("collectgarbage()").exec() {
+        Err(Error::CallbackError { cause, .. }) => match cause.as_ref() {
+            Error::RuntimeError(msg) => assert!(msg.contains("'collect' is not allowed")),
+            err => panic!("expected RuntimeError, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+    assert!(lua.load("collectgarbage('collect')").exec().is_err());
+    assert_eq!(lua.load("collectgarbage('count')").eval::<f64>()?, 42.5);
+
+    // Ignored call does not stop the collector
+    lua.load("collectgarbage('stop')").exec()?;
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52", feature = "luau"))]
+    assert!(lua.gc_is_running());
+
+    // Allowed calls are passed to the original function
+    lua.load("collectgarbage('step', 0)").exec()?;
+    assert!(lua.load("collectgarbage('invalid')").exec().is_err());
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        [
+            ("collect".to_string(), 0),
+            ("collect".to_string(), 0),
+            ("count".to_string(), 0),
+            ("stop".to_string(), 0),
+            ("step".to_string(), 1),
+            ("invalid".to_string(), 0),
+        ]
+    );
+
+    // Rust-side GC methods are not affected
+    lua.gc_collect()?;
+
+    lua.remove_collectgarbage_policy();
+    lua.load("collectgarbage()").exec()?;
+    assert!(lua.load("collectgarbage('count')").eval::<f64>()? > 0.0);
+    assert_eq!(calls.lock().unwrap().len(), 6);
+
+    // No base library
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::default())?;
+    lua.set_collectgarbage_policy(|_, _, _| Ok(GCAction::Deny))?;
+
+    Ok(())
+}
+
 #[cfg(any(feature = "lua53", feature = "lua52"))]
 #[test]
 fn test_gc_error() {
@@ -58,3 +58,213 @@ fn test_result_conversions() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_derive_multi() -> Result<()> {
+    use mlua::{FromLuaMulti, Table, Variadic};
+
+    #[derive(Debug, PartialEq, IntoLuaMulti, FromLuaMulti)]
+    struct Span(usize, usize);
+
+    #[derive(IntoLuaMulti, FromLuaMulti)]
+    struct Call {
+        name: std::string::String,
+        args: Variadic<i64>,
+    }
+
+    #[derive(Debug, PartialEq, IntoLuaMulti, FromLuaMulti)]
+    #[mlua(table)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    #[derive(Debug, PartialEq, IntoLuaMulti, FromLuaMulti)]
+    #[mlua(table)]
+    struct Pair<T>(T, T);
+
+    let lua = Lua::new();
+
+    // Multiple values
+    let shift = lua.create_function(|_, span: Span| Ok(Span(span.0 + 10, span.1 + 10)))?;
+    let (start, end) = shift.call::<(usize, usize)>((1, 3))?;
+    assert_eq!((start, end), (11, 13));
+    assert_eq!(shift.call::<Span>(Span(1, 2))?, Span(11, 12));
+    let err = shift.call::<Span>((1, "a")).unwrap_err().to_string();
+    assert!(err.contains("bad argument #2"), "{err}");
+
+    let call = lua.load("return ...").into_function()?;
+    let res = call.call::<Call>(("sum", 1, 2, 3))?;
+    assert_eq!(res.name, "sum");
+    assert_eq!(res.args[..], [1, 2, 3]);
+    let (name, a, _, c) = call.call::<(std::string::String, i64, i64, i64)>(res)?;
+    assert_eq!((name.as_str(), a, c), ("sum", 1, 3));
+
+    // Table representation
+    let point = Point { x: 1.0, y: 2.0 }.into_lua_multi(&lua)?;
+    assert_eq!(point.len(), 1);
+    let table = point[0].as_table().unwrap();
+    assert_eq!(table.get::<f64>("y")?, 2.0);
+    let point = lua.load("return {x = 3, y = 4}").eval::<Point>()?;
+    assert_eq!(point, Point { x: 3.0, y: 4.0 });
+    let err = lua.load("return 1").eval::<Point>().unwrap_err().to_string();
+    assert!(err.contains("expected table"), "{err}");
+
+    let pair = call.call::<Table>(Pair("a", "b"))?;
+    assert_eq!(
+        pair.sequence_values::<std::string::String>()
+            .collect::<Result<Vec<_>>>()?,
+        ["a", "b"]
+    );
+    assert_eq!(lua.load("return {5, 6}").eval::<Pair<i32>>()?, Pair(5, 6));
+
+    Ok(())
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_named_args() -> Result<()> {
+    use mlua::{FromLuaTable, NamedArgs};
+
+    #[derive(FromLuaTable)]
+    struct Options {
+        width: u32,
+        height: u32,
+        title: Option<std::string::String>,
+    }
+
+    impl Default for Options {
+        fn default() -> Self {
+            Options {
+                width: 800,
+                height: 600,
+                title: None,
+            }
+        }
+    }
+
+    #[derive(Default, FromLuaTable)]
+    struct Wrapper<T> {
+        value: T,
+    }
+
+    let lua = Lua::new();
+    let describe = lua.create_function(|_, NamedArgs(opts): NamedArgs<Options>| {
+        let title = opts.title.unwrap_or_else(|| "untitled".into());
+        Ok(format!("{title}: {}x{}", opts.width, opts.height))
+    })?;
+    lua.globals().set("describe", describe)?;
+
+    let describe = |code: &str| lua.load(code).eval::<std::string::String>();
+    assert_eq!(describe("describe{width = 3, height = 4}")?, "untitled: 3x4");
+    assert_eq!(describe("describe{title = 'main'}")?, "main: 800x600");
+    assert_eq!(describe("describe(3, 4, 'pos')")?, "pos: 3x4");
+    assert_eq!(describe("describe(nil, 4)")?, "untitled: 800x4");
+    assert_eq!(describe("describe()")?, "untitled: 800x600");
+    assert_eq!(describe("describe{}")?, "untitled: 800x600");
+
+    let err = describe("describe{width = 'wide'}").unwrap_err().to_string();
+    assert!(err.contains("bad argument `width`"), "{err}");
+    let err = describe("describe{depth = 1}").unwrap_err().to_string();
+    assert!(
+        err.contains("bad argument `depth`") && err.contains("unknown argument"),
+        "{err}"
+    );
+    let err = describe("describe(1, {})").unwrap_err().to_string();
+    assert!(err.contains("bad argument `height`"), "{err}");
+
+    let unwrap = lua.create_function(|_, args: NamedArgs<Wrapper<i64>>| Ok(args.into_inner().value))?;
+    assert_eq!(unwrap.call::<i64>(5)?, 5);
+    assert_eq!(unwrap.call::<i64>(lua.create_table_from([("value", 7)])?)?, 7);
+
+    Ok(())
+}
+
+#[test]
+fn test_multi_value_split() -> Result<()> {
+    use mlua::{MultiValue, Variadic};
+
+    let lua = Lua::new();
+
+    let mut args = MultiValue::from(vec![Value::Integer(1), Value::Boolean(true), Value::Nil]);
+    let (a, b): (i64, bool) = args.split_first_n(2, &lua)?;
+    assert_eq!((a, b), (1, true));
+    assert_eq!(args.len(), 1);
+    assert_eq!(args.rest(0).count(), 1);
+    assert_eq!(args.rest(5).count(), 0);
+
+    // Splitting more values than available treats the missing ones as nil
+    let (c, d): (Option<i64>, Option<i64>) = args.split_first_n(5, &lua)?;
+    assert_eq!((c, d), (None, None));
+    assert!(args.is_empty());
+
+    // Conversion errors report the argument position
+    let mut args = MultiValue::from(vec![Value::Integer(1), Value::Boolean(true)]);
+    let err = args.split_first_n::<(i64, i64)>(2, &lua).unwrap_err();
+    assert!(err.to_string().contains("bad argument #2"), "{err}");
+
+    // Fixed argument followed by mixed varargs
+    let f = lua.create_function(|lua, mut args: MultiValue| {
+        let name: std::string::String = args.split_first_n(1, lua)?;
+        let types = args.rest(0).map(|v| v.type_name()).collect::<Vec<_>>();
+        Ok(format!("{name}: {}", types.join(",")))
+    })?;
+    assert_eq!(
+        f.call::<std::string::String>(("x", 1, "s", false))?,
+        "x: integer,string,boolean"
+    );
+
+    let values: Vec<Value> = lua.load("return 1, 2").eval::<MultiValue>()?.into_vec();
+    assert_eq!(values, [Value::Integer(1), Value::Integer(2)]);
+
+    let mut v = Variadic::from(vec![1, 2, 3, 4]);
+    let tail = v.split_off(1);
+    assert_eq!(v.into_vec(), [1]);
+    assert_eq!(Vec::from(tail), [2, 3, 4]);
+    assert!(Variadic::from(vec![1]).split_off(3).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_variadic_numbers() -> Result<()> {
+    use mlua::Variadic;
+
+    let lua = Lua::new();
+
+    // Polygon area (shoelace formula) from flat list of coordinates
+    let area = lua.create_function(|_, (scale, coords): (f64, Variadic<f64>)| {
+        let points = coords.as_slice().chunks_exact(2).collect::<Vec<_>>();
+        let sum: f64 = (0..points.len())
+            .map(|i| {
+                let (p, q) = (points[i], points[(i + 1) % points.len()]);
+                p[0] * q[1] - q[0] * p[1]
+            })
+            .sum();
+        Ok(scale * sum.abs() / 2.0)
+    })?;
+    lua.globals().set("area", area)?;
+    let result: f64 = lua.load("return area(2, 0, 0, 4, 0, 4, 3)").eval()?;
+    assert_eq!(result, 12.0);
+
+    let ints = lua.create_function(|_, args: Variadic<i64>| Ok(args.into_vec()))?;
+    lua.globals().set("ints", ints)?;
+    assert_eq!(lua.load("return ints(1, 2, 3)").eval::<Vec<i64>>()?, [1, 2, 3]);
+    assert_eq!(lua.load("return ints()").eval::<Vec<i64>>()?, Vec::<i64>::new());
+
+    // Conversion errors report the argument position
+    let err = lua.load("area(1, 2, 'x')").exec().unwrap_err().to_string();
+    assert!(err.contains("bad argument #3"), "{err}");
+    let err = lua.load("ints(1, {})").exec().unwrap_err().to_string();
+    assert!(err.contains("bad argument #2"), "{err}");
+
+    // Values returned from Lua
+    let values = lua.load("return 1.5, 2, 3").eval::<Variadic<f64>>()?;
+    assert_eq!(values.as_slice(), [1.5, 2.0, 3.0]);
+    let (first, rest) = lua.load("return 'a', 1, 2").eval::<(String, Variadic<i64>)>()?;
+    assert_eq!(first, "a");
+    assert_eq!(rest.as_slice(), [1, 2]);
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use mlua::{pattern, Lua, Result};
+
+#[test]
+fn test_pattern_validate() -> Result<()> {
+    pattern::validate("^(%w+)=(%d+)$")?;
+    pattern::validate("%b()[]]%f[%w]()")?;
+    pattern::validate("(a)%1[%^%$]")?;
+
+    let lua = Lua::new();
+    let string_match = lua.load("return string.match(...)").into_function()?;
+    for (pat, subject, msg) in [
+        ("abc%", "abc", "malformed pattern (ends with '%')"),
+        ("[a-z", "x", "malformed pattern (missing ']')"),
+        ("(abc", "abc", "unfinished capture"),
+        ("abc)", "abc", "invalid pattern capture"),
+        ("%b(", "x", "missing arguments to '%b'"),
+        ("%fa", "x", "missing '[' after '%f'"),
+        ("(a)%2", "a", "invalid capture index %2"),
+    ] {
+        let err = pattern::validate(pat).unwrap_err().to_string();
+        assert!(err.contains(msg), "{pat}: {err}");
+        // Lua rejects the same patterns
+        assert!(string_match.call::<()>((subject, pat)).is_err(), "{pat}");
+    }
+
+    // Patterns accepted by Lua, but likely mistakes
+    for (pat, msg) in [
+        ("a^b", "'^' is an anchor only at the start"),
+        ("a$b", "'$' is an anchor only at the end"),
+        ("%b''", "'%b''' has identical delimiters"),
+        (&"()".repeat(33), "too many captures"),
+    ] {
+        let err = pattern::validate(pat).unwrap_err().to_string();
+        assert!(err.contains(msg), "{pat}: {err}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_pattern_to_regex_string() -> Result<()> {
+    assert_eq!(
+        pattern::to_regex_string("^(%w+)=(%d+)$")?,
+        "^([0-9A-Za-z]+)=([0-9]+)$"
+    );
+    assert_eq!(pattern::to_regex_string("a.-b")?, "a(?s:.)*?b");
+    assert_eq!(
+        pattern::to_regex_string("[^%s%]]")?,
+        "[^[\\t\\n\\x0B\\x0C\\r ]\\]]"
+    );
+    assert_eq!(pattern::to_regex_string("%$%D?a^")?, "\\$[^0-9]?a\\^");
+
+    for pat in ["()", "(a)%1", "%b()", "%f[%w]"] {
+        assert!(pattern::to_regex_string(pat).is_err(), "{pat}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_pattern_from_regex() -> Result<()> {
+    for (regex, pat) in [
+        (r"^\d+$", "^%d+$"),
+        (r"(\w+)\s*=\s*(\w+)", "(%w+)%s*=%s*(%w+)"),
+        (r"a{2,3}b{2}c{1,}", "aaa?bbc+"),
+        (r"x+?y*?", "xx-y-"),
+        (r"[a-z\-]", "[a-z%-]"),
+        (r"[[:alpha:]_]+", "[%a_]+"),
+        (r"\.\$%", "%.%$%%"),
+        (r"(?P<num>\d)", "(%d)"),
+    ] {
+        assert_eq!(pattern::from_regex(regex)?, pat, "{regex}");
+    }
+
+    for regex in ["a|b", "(ab)+", "(?:ab)", r"\bword", "a^", "[[a]]"] {
+        assert!(pattern::from_regex(regex).is_err(), "{regex}");
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn test_pattern_to_regex() -> Result<()> {
+    use mlua::Variadic;
+
+    let lua = Lua::new();
+    let string_match = lua.load("return string.match(...)").into_function()?;
+
+    for (pat, subject) in [
+        ("^(%w+)=(%d+)$", "key=42"),
+        ("%s*(%S+)%s*", "  hello  "),
+        ("(%a+)-(%a-)x", "abc-defx"),
+        ("[%d%.]+", "v1.25b"),
+        ("[^%s]+$", "a b c"),
+        ("a.-b", "a\nxb b"),
+        ("%$%d+", "cost $15"),
+        ("[]]", "x]"),
+        ("[a%-z]+", "-a-z"),
+        ("%p+", "hi!?"),
+        ("%x+", "0xFFg"),
+        ("^abc", "xabc"),
+    ] {
+        let expected = string_match.call::<Variadic<Option<String>>>((subject, pat))?;
+        let regex = pattern::to_regex(pat)?;
+        let actual: Vec<_> = match regex.captures(subject) {
+            Some(caps) if caps.len() > 1 => caps
+                .iter()
+                .skip(1)
+                .map(|m| m.map(|m| m.as_str().into()))
+                .collect(),
+            Some(caps) => vec![Some(caps[0].to_string())],
+            None => vec![None],
+        };
+        assert_eq!(*expected, actual, "{pat}");
+    }
+
+    Ok(())
+}
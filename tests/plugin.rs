@@ -0,0 +1,60 @@
+#![cfg(feature = "plugin")]
+
+use mlua::{ffi, Lua, Result, Table};
+
+#[mlua::lua_plugin]
+fn geometry(lua: &Lua) -> Result<Table> {
+    let exports = lua.create_table()?;
+    exports.set(
+        "distance",
+        lua.create_function(|_, (x, y): (f64, f64)| Ok(x.hypot(y)))?,
+    )?;
+    Ok(exports)
+}
+
+#[mlua::lua_plugin(name = "geometry_alt")]
+fn geometry2(lua: &Lua) -> Result<Table> {
+    let exports = geometry(lua)?;
+    exports.set("alt", true)?;
+    Ok(exports)
+}
+
+#[test]
+fn test_plugin_foreign_state() -> Result<()> {
+    unsafe {
+        // Create a state outside of mlua and register the exported entrypoints as preloaders
+        let state = ffi::luaL_newstate();
+        ffi::luaL_openlibs(state);
+        ffi::lua_getglobal(state, c"package".as_ptr());
+        ffi::lua_getfield(state, -1, c"preload".as_ptr());
+        ffi::lua_pushcfunction(state, luaopen_geometry);
+        ffi::lua_setfield(state, -2, c"geometry".as_ptr());
+        ffi::lua_pushcfunction(state, luaopen_geometry_alt);
+        ffi::lua_setfield(state, -2, c"geometry_alt".as_ptr());
+        ffi::lua_pop(state, 2);
+
+        let lua = Lua::init_from_ptr(state);
+        let distance: f64 = lua.load("return require('geometry').distance(3, 4)").eval()?;
+        assert_eq!(distance, 5.0);
+        assert!(lua.load("return require('geometry_alt').alt").eval::<bool>()?);
+
+        let err = lua.load("require('geometry').distance('a')").exec().unwrap_err();
+        assert!(err.to_string().contains("bad argument"), "{err}");
+
+        drop(lua);
+        ffi::lua_close(state);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_plugin_mlua_state() -> Result<()> {
+    let lua = Lua::new();
+    let open = unsafe { lua.create_c_function(luaopen_geometry)? };
+    let geometry: Table = open.call("geometry")?;
+    let distance: f64 = geometry.get::<mlua::Function>("distance")?.call((6, 8))?;
+    assert_eq!(distance, 10.0);
+
+    Ok(())
+}
@@ -422,6 +422,53 @@ fn test_scope_any_userdata_ref() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_scope_wrap_any_userdata_ref() -> Result<()> {
+    let lua = Lua::new();
+
+    struct Inventory {
+        items: Vec<StdString>,
+    }
+
+    lua.register_userdata_type::<Inventory>(|reg| {
+        reg.add_method("count", |_, this, ()| Ok(this.items.len()));
+        reg.add_method("get", |_, this, i: usize| Ok(this.items.get(i - 1).cloned()));
+        reg.add_method_mut("add", |_, this, item: StdString| {
+            this.items.push(item);
+            Ok(())
+        });
+    })?;
+
+    let mut inventory = Inventory {
+        items: vec!["sword".into(), "shield".into()],
+    };
+    let describe: Function = lua
+        .load("function(inv) _G.inv = inv; return inv:count() .. ' ' .. inv:get(2) end")
+        .eval()?;
+    let add: Function = lua.load("function(inv, item) inv:add(item) end").eval()?;
+
+    let desc = lua.scope(|scope| describe.call::<StdString>(AnyUserData::wrap_ref(scope, &inventory)))?;
+    assert_eq!(desc, "2 shield");
+
+    // Read-only reference cannot be mutated
+    lua.scope(|scope| {
+        let res = add.call::<()>((AnyUserData::wrap_ref(scope, &inventory), "bow"));
+        assert!(res.is_err());
+        Ok(())
+    })?;
+
+    lua.scope(|scope| add.call::<()>((AnyUserData::wrap_ref_mut(scope, &mut inventory), "bow")))?;
+    assert_eq!(inventory.items, ["sword", "shield", "bow"]);
+
+    // Userdata expires after the scope
+    match lua.load("inv:count()").exec() {
+        Err(Error::CallbackError { ref cause, .. }) => assert!(matches!(**cause, Error::UserDataDestructed)),
+        r => panic!("expected UserDataDestructed error, got {r:?}"),
+    }
+
+    Ok(())
+}
+
 fn modify_userdata(lua: &Lua, ud: AnyUserData) -> Result<()> {
     let f: Function = lua
         .load(
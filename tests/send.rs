@@ -33,3 +33,32 @@ fn test_userdata_multithread_access() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_value_scope_multithread() -> Result<()> {
+    let lua = Lua::new();
+    let usage = lua.ref_stack_usage();
+
+    // Scopes on different threads must not release each other's blocks
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for i in 0..50 {
+                    lua.value_scope_with_capacity(8, |scope| {
+                        let tables = (0..i % 12)
+                            .map(|_| lua.create_table())
+                            .collect::<Result<Vec<_>>>()?;
+                        std::thread::yield_now();
+                        assert_eq!(scope.capacity(), 8);
+                        assert_eq!(scope.used(), tables.len().min(8));
+                        Ok::<_, Error>(())
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(lua.ref_stack_usage().used, usage.used);
+
+    Ok(())
+}
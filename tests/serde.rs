@@ -4,8 +4,8 @@ use std::collections::HashMap;
 use std::error::Error as StdError;
 
 use mlua::{
-    DeserializeOptions, Error, ExternalResult, Lua, LuaSerdeExt, Result as LuaResult, SerializeOptions,
-    UserData, Value,
+    AnyUserData, DeserializeOptions, Error, ExternalResult, Lua, LuaSerdeExt, Result as LuaResult,
+    SerializeOptions, Table, UserData, UserDataFields, Value,
 };
 use serde::{Deserialize, Serialize};
 
@@ -377,6 +377,71 @@ fn test_to_value_with_options() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_serialize_into() -> Result<(), Box<dyn StdError>> {
+    #[derive(Serialize)]
+    struct State {
+        score: u32,
+        player: Player,
+        items: Vec<&'static str>,
+    }
+
+    #[derive(Serialize)]
+    struct Player {
+        name: &'static str,
+    }
+
+    let lua = Lua::new();
+    let state = lua.create_table()?;
+    lua.globals().set("state", &state)?;
+    lua.load(
+        r#"
+        state.player = {name = "unknown", level = 1}
+        state.items = {"sword", "shield", "potion"}
+        state.extra = true
+        player, items = state.player, state.items
+    "#,
+    )
+    .exec()?;
+
+    let new_state = State {
+        score: 10,
+        player: Player { name: "John" },
+        items: vec!["bow"],
+    };
+
+    // Merge keeps the existing keys
+    let options = SerializeOptions::new().merge_tables(true);
+    lua.serialize_into(&state, &new_state, options)?;
+    lua.load(
+        r#"
+        assert(state.score == 10 and state.extra == true)
+        assert(player == state.player and player.name == "John" and player.level == 1)
+        assert(items == state.items and items[1] == "bow" and items[3] == "potion")
+    "#,
+    )
+    .exec()?;
+
+    // Without merge the stale keys are removed
+    lua.serialize_into(&state, &new_state, SerializeOptions::new())?;
+    lua.load(
+        r#"
+        assert(state.score == 10 and state.extra == nil)
+        assert(player == state.player and player.name == "John" and player.level == nil)
+        assert(items == state.items and #items == 1 and getmetatable(items) ~= nil)
+    "#,
+    )
+    .exec()?;
+
+    // Non-table values cannot be serialized into a table
+    match lua.serialize_into(&state, &123, SerializeOptions::new()) {
+        Err(Error::SerializeError(msg)) => assert_eq!(msg, "cannot serialize integer into a table"),
+        r => panic!("expected SerializeError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_from_value_nested_tables() -> Result<(), Box<dyn StdError>> {
     let lua = Lua::new();
@@ -602,6 +667,81 @@ fn test_from_value_with_options() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_from_value_borrowed() -> Result<(), Box<dyn StdError>> {
+    use std::borrow::Cow;
+
+    use mlua::serde::{Deserializer, StringArena};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Record<'a> {
+        name: &'a str,
+        data: &'a [u8],
+        #[serde(borrow)]
+        note: Cow<'a, str>,
+        tags: HashMap<&'a str, Vec<&'a str>>,
+        kind: Kind<'a>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Kind<'a> {
+        Named(&'a str),
+    }
+
+    let lua = Lua::new();
+    let value = lua
+        .load(
+            r#"{
+                name = "record",
+                data = "\255\0",
+                note = "borrowed",
+                tags = { colors = {"red", "green"} },
+                kind = { Named = "x" },
+            }"#,
+        )
+        .eval()?;
+
+    let arena = StringArena::new();
+    let record: Record = lua.from_value_borrowed(value, &arena)?;
+    assert_eq!(record.name, "record");
+    assert_eq!(record.data, b"\xff\x00");
+    assert!(matches!(record.note, Cow::Borrowed("borrowed")));
+    assert_eq!(record.tags["colors"], ["red", "green"]);
+    assert_eq!(record.kind, Kind::Named("x"));
+    assert!(!arena.is_empty());
+
+    // Borrowed data stays valid after the source table is gone
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(record.tags["colors"], ["red", "green"]);
+
+    // Options can be combined with the arena
+    let value = lua.load(r#"{name = "x", f = function() end}"#).eval()?;
+    let options = DeserializeOptions::new().deny_unsupported_types(false);
+    let map: HashMap<&str, &str> =
+        HashMap::deserialize(Deserializer::new_with_options(value, options).with_arena(&arena))?;
+    assert_eq!(map, HashMap::from([("name", "x")]));
+
+    // Borrowing requires the arena
+    let value: Value = lua.load(r#""string""#).eval()?;
+    assert!(lua.from_value::<Box<str>>(value.clone()).is_ok());
+    assert!(<&str>::deserialize(Deserializer::new(value)).is_err());
+
+    // Strings from different states keep their own state alive
+    let arena = StringArena::new();
+    let lua1 = Lua::new();
+    let lua2 = Lua::new();
+    let s1: &str = lua1.from_value_borrowed(Value::String(lua1.create_string("first")?), &arena)?;
+    let s2: &str = lua2.from_value_borrowed(Value::String(lua2.create_string("second")?), &arena)?;
+    drop(lua1);
+    drop(lua2);
+    assert_eq!(s1, "first");
+    assert_eq!(s2, "second");
+    assert_eq!(arena.len(), 2);
+
+    Ok(())
+}
+
 #[test]
 fn test_from_value_userdata() -> Result<(), Box<dyn StdError>> {
     let lua = Lua::new();
@@ -661,6 +801,84 @@ fn test_from_value_userdata() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_userdata_serde_hooks() -> Result<(), Box<dyn StdError>> {
+    // A foreign type without serde support
+    struct Endpoint {
+        host: String,
+        port: u16,
+    }
+
+    struct Counter(i64);
+
+    let lua = Lua::new();
+    lua.register_userdata_type::<Endpoint>(|reg| {
+        reg.add_field_method_get("host", |_, this| Ok(this.host.clone()));
+        reg.set_serialize(|this| (this.host.clone(), this.port));
+        reg.set_deserialize(|(host, port)| Ok(Endpoint { host, port }));
+    })?;
+    // Serialization only
+    lua.register_userdata_type::<Counter>(|reg| reg.set_serialize(|this| this.0))?;
+
+    let endpoint = lua.create_any_userdata(Endpoint {
+        host: "localhost".into(),
+        port: 8080,
+    })?;
+    lua.globals().set("endpoint", endpoint)?;
+    lua.globals()
+        .set("counter", lua.create_any_userdata(Counter(5))?)?;
+
+    let json = serde_json::to_value(
+        lua.load("{endpoint = endpoint, counter = counter}")
+            .eval::<Value>()?,
+    )?;
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "endpoint": {"$userdata": "Endpoint", "value": ["localhost", 8080]},
+            "counter": 5,
+        })
+    );
+    let counter = lua.from_value::<i64>(lua.globals().get("counter")?)?;
+    assert_eq!(counter, 5);
+
+    // Restore the snapshot into a new state
+    let lua2 = Lua::new();
+    lua2.register_userdata_type::<Endpoint>(|reg| {
+        reg.add_field_method_get("host", |_, this| Ok(this.host.clone()));
+        reg.set_deserialize(|(host, port)| Ok(Endpoint { host, port }));
+    })?;
+    // `serde_json` is built with `arbitrary_precision` feature
+    let options = SerializeOptions::new().detect_serde_json_arbitrary_precision(true);
+    let snapshot = lua2.to_value_with(&json, options)?;
+    lua2.globals().set("snapshot", snapshot)?;
+    lua2.load(
+        r#"
+        assert(type(snapshot.endpoint) == "userdata")
+        assert(snapshot.endpoint.host == "localhost")
+        assert(snapshot.counter == 5)
+    "#,
+    )
+    .exec()?;
+    let endpoint = lua2
+        .globals()
+        .get::<Table>("snapshot")?
+        .get::<AnyUserData>("endpoint")?;
+    assert_eq!(endpoint.borrow::<Endpoint>()?.port, 8080);
+
+    // Userdata is restored when serializing values between states directly
+    let value = lua2.to_value(&lua.globals().get::<Value>("endpoint")?)?;
+    assert!(value.as_userdata().unwrap().is::<Endpoint>());
+
+    // Unknown types are left as tables
+    let lua3 = Lua::new();
+    let value = lua3.to_value_with(&json, options)?;
+    let endpoint = value.as_table().unwrap().get::<Table>("endpoint")?;
+    assert_eq!(endpoint.get::<String>("$userdata")?, "Endpoint");
+
+    Ok(())
+}
+
 #[test]
 fn test_from_value_sorted() -> Result<(), Box<dyn StdError>> {
     let lua = Lua::new();
@@ -743,3 +961,59 @@ fn test_buffer_from_value() -> LuaResult<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn test_std_json_module() -> LuaResult<()> {
+    let lua = Lua::new();
+    lua.load_std_json()?;
+
+    lua.load(
+        r#"
+        assert(require("json") == json)
+
+        local data = json.decode('{"a": [1, 2.5, "x", null], "b": {}, "c": [], "d": true}')
+        assert(data.a[1] == 1 and data.a[2] == 2.5 and data.a[3] == "x")
+        assert(data.a[4] == json.null)
+        assert(next(data.b) == nil and data.d == true)
+        assert(json.decode("-5") == -5 and json.decode("1e2") == 100)
+        assert(json.decode("18446744073709551615") == 2^64)
+
+        assert(json.encode(data.c) == "[]")
+        assert(json.encode(setmetatable({}, json.array_mt)) == "[]")
+        assert(json.encode(data, {sort_keys = true}) == '{"a":[1,2.5,"x",null],"b":{},"c":[],"d":true}')
+        assert(json.encode({1, 2}, {pretty = true}) == "[\n  1,\n  2\n]")
+    "#,
+    )
+    .exec()?;
+
+    // Errors
+    let res = lua.load("json.decode('{invalid')").exec();
+    match res {
+        Err(Error::CallbackError { ref cause, .. }) => {
+            assert!(matches!(cause.as_ref(), Error::DeserializeError(_)))
+        }
+        res => panic!("expected DeserializeError, got {res:?}"),
+    }
+    let res = lua.load("json.decode('[1] [2]')").exec();
+    assert!(res.is_err(), "trailing input must be rejected");
+    let res = lua.load("json.encode({f = print})").exec();
+    match res {
+        Err(Error::CallbackError { ref cause, .. }) => {
+            assert!(matches!(cause.as_ref(), Error::SerializeError(_)))
+        }
+        res => panic!("expected SerializeError, got {res:?}"),
+    }
+    let res = lua
+        .load("local t = {} for i = 1, 200000 do t = {t} end return json.encode(t)")
+        .exec();
+    match res {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::SerializeError(msg) => assert_eq!(msg, "recursion limit exceeded"),
+            err => panic!("expected SerializeError, got {err:?}"),
+        },
+        res => panic!("expected SerializeError, got {res:?}"),
+    }
+
+    Ok(())
+}
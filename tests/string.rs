@@ -1,7 +1,8 @@
 use std::borrow::Cow;
 use std::collections::HashSet;
+use std::string::String as StdString;
 
-use mlua::{Lua, Result, String};
+use mlua::{Error, Lua, Result, String, Table, ToLuaString, UserData, UserDataMethods, Value};
 
 #[test]
 fn test_string_compare() {
@@ -103,3 +104,273 @@ fn test_string_pointer() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_string_pack() -> Result<()> {
+    let lua = Lua::new();
+
+    let data = lua.string_pack("<i4>Hzs1!4d", (-2, 0x0102, "ab", "xyz", 1.5))?;
+    assert_eq!(&data[..12], b"\xfe\xff\xff\xff\x01\x02ab\0\x03xy");
+    let (i, h, z, s, d): (i32, u16, std::string::String, std::string::String, f64) =
+        lua.string_unpack("<i4>Hzs1!4d", &data)?;
+    assert_eq!((i, h, z.as_str(), s.as_str(), d), (-2, 0x0102, "ab", "xyz", 1.5));
+    assert_eq!(lua.string_pack_size("i4i8")?, 12);
+    assert_eq!(lua.string_pack_size("!8i4i8")?, 16);
+
+    // Errors
+    assert!(lua.string_pack("i4", "abc").is_err());
+    assert!(lua.string_pack("i1", 200).is_err());
+    assert!(lua.string_pack("q", 1).is_err());
+    assert!(lua.string_unpack::<i32>("i4", b"\x01\x02").is_err());
+    assert!(lua.string_pack_size("s").is_err());
+
+    // Rust engine must be compatible with the `string` library functions
+    let data2 = lua
+        .load(r#"string.pack("<i4>Hzs1!4d", -2, 0x0102, "ab", "xyz", 1.5)"#)
+        .eval::<String>()?;
+    assert_eq!(data2.as_bytes(), &data[..]);
+    lua.globals().set("data", lua.create_string(&data)?)?;
+    lua.load(
+        r#"
+        local i, h, z, s, d, next = string.unpack("<i4>Hzs1!4d", data)
+        assert(i == -2 and h == 0x0102 and z == "ab" and s == "xyz" and d == 1.5)
+        assert(next == #data + 1)
+        assert(string.packsize("i4i8") == 12)
+        assert(string.unpack("B", "\1\2\3", -1) == 3)
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_string_chars() -> Result<()> {
+    let lua = Lua::new();
+
+    let s = lua.create_string("h€llo")?;
+    assert_eq!(s.chars().collect::<Result<StdString>>()?, "h€llo");
+    let indices = s
+        .char_indices()
+        .map(|r| r.map(|(i, _)| i))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(indices, [0, 1, 4, 5, 6]);
+
+    // Invalid sequences are reported lazily
+    let s = lua.create_string(b"ab\xe2\x82cd")?;
+    let mut iter = s.char_indices();
+    assert_eq!(iter.next().unwrap()?, (0, 'a'));
+    assert_eq!(iter.next().unwrap()?, (1, 'b'));
+    let err = iter.next().unwrap().unwrap_err();
+    assert!(err.to_string().contains("invalid UTF-8 sequence at byte 2"));
+    assert!(iter.next().is_none());
+
+    assert_eq!(lua.create_string("")?.chars().count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_utf8_helpers() {
+    use mlua::utf8;
+
+    let s = "h\u{e9}llo\u{20ac}\u{1d11e}".as_bytes();
+    assert_eq!(utf8::decode(s), Some(('h', 1)));
+    assert_eq!(utf8::decode(&s[1..]), Some(('\u{e9}', 2)));
+    assert_eq!(utf8::decode(&s[2..]), None);
+    assert_eq!(utf8::decode(b"\xed\xa0\x80"), None); // surrogate
+    assert_eq!(utf8::decode(b"\xc0\x80"), None); // overlong
+    assert_eq!(utf8::decode(b""), None);
+
+    assert_eq!(utf8::len(s), Ok(7));
+    assert_eq!(utf8::len(b"ab\xffcd"), Err(2));
+
+    assert_eq!(utf8::offset(s, 3, 0), Some(3));
+    assert_eq!(utf8::offset(s, -1, s.len()), Some(9));
+    assert_eq!(utf8::offset(s, 0, 2), Some(1));
+    assert_eq!(utf8::offset(s, 8, 0), Some(s.len()));
+    assert_eq!(utf8::offset(s, 9, 0), None);
+    assert_eq!(utf8::offset(s, 1, 2), None);
+    assert_eq!(utf8::offset(s, 1, s.len() + 1), None);
+}
+
+#[test]
+fn test_utf8_library() -> Result<()> {
+    let lua = Lua::new();
+    let native = lua.globals().get::<mlua::Value>("utf8")?;
+    lua.globals().set("utf8", mlua::Nil)?;
+    lua.install_utf8()?;
+    let polyfill = lua.globals().get::<mlua::Table>("utf8")?;
+
+    // Does nothing if the library exists
+    lua.globals().set("utf8", lua.create_table()?)?;
+    lua.install_utf8()?;
+
+    let check = lua
+        .load(
+            r##"
+        local utf8, s, bad, expected_chars = ...
+        assert(utf8.len(s) == 7)
+        local n, pos = utf8.len(s, 3)
+        assert(n == nil and pos == 3)
+        assert(utf8.len(s, 4, 5) == 2)
+        assert(utf8.len("") == 0)
+        n, pos = utf8.len(bad)
+        assert(n == nil and pos == 3)
+
+        local codes = {utf8.codepoint(s, 1, -1)}
+        assert(#codes == 7 and codes[2] == 233 and codes[6] == 8364 and codes[7] == 119070)
+        assert(utf8.codepoint(s) == 104)
+        assert(select("#", utf8.codepoint(s, 3, 2)) == 0)
+        assert(not pcall(utf8.codepoint, bad, 1, -1))
+        assert(not pcall(utf8.codepoint, s, 20))
+
+        assert(utf8.offset(s, 3) == 4)
+        assert(utf8.offset(s, -1) == 10)
+        assert(utf8.offset(s, 0, 3) == 2)
+        assert(utf8.offset(s, 8) == 14)
+        assert(utf8.offset(s, 9) == nil)
+        assert(not pcall(utf8.offset, s, 1, 3))
+
+        assert(utf8.char(104, 233, 108, 108, 111, 8364, 119070) == s)
+        assert(utf8.char() == "")
+        assert(not pcall(utf8.char, -1))
+
+        local positions, chars = {}, {}
+        for p, c in utf8.codes(s) do
+            positions[#positions + 1] = p
+            chars[#chars + 1] = c
+        end
+        assert(table.concat(positions, ",") == "1,2,4,5,6,7,10")
+        local unpack = unpack or table.unpack
+        assert(utf8.char(unpack(chars)) == s)
+        assert(not pcall(function() for _ in utf8.codes(bad) do end end))
+
+        local matched = {}
+        for c in s:gmatch(utf8.charpattern) do
+            matched[#matched + 1] = c
+        end
+        assert(#matched == 7 and matched[2] == expected_chars[1] and matched[7] == expected_chars[2])
+    "##,
+        )
+        .into_function()?;
+
+    let s = "h\u{e9}llo\u{20ac}\u{1d11e}";
+    let bad = lua.create_string(b"ab\xffcd")?;
+    let expected = vec!["\u{e9}", "\u{1d11e}"];
+    check.call::<()>((polyfill, s, &bad, expected.clone()))?;
+    // Compare with the native library
+    if let mlua::Value::Table(native) = native {
+        check.call::<()>((native, s, &bad, expected))?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_to_lua_string() -> Result<()> {
+    let lua = Lua::new();
+
+    // Lua formatting of numbers
+    assert_eq!(1.5.to_lua_string(&lua)?, "1.5");
+    assert_eq!(true.to_lua_string(&lua)?, "true");
+    assert_eq!(Value::Nil.to_lua_string(&lua)?, "nil");
+    #[cfg(any(feature = "lua54", feature = "lua53"))]
+    assert_eq!(2.0.to_lua_string(&lua)?, "2.0");
+    assert_eq!(42.to_lua_string(&lua)?, "42");
+
+    // `__tostring` and `__name` metamethods
+    let t: Table = lua
+        .load(r#"setmetatable({}, {__tostring = function() return "custom" end})"#)
+        .eval()?;
+    assert_eq!(t.to_lua_string(&lua)?, "custom");
+
+    struct Point(i32, i32);
+    impl UserData for Point {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_meta_method("__tostring", |_, this, ()| {
+                Ok(format!("({}, {})", this.0, this.1))
+            });
+        }
+    }
+    let ud = lua.create_userdata(Point(1, 2))?;
+    assert_eq!(ud.to_lua_string(&lua)?, "(1, 2)");
+
+    // Errors from `__tostring` are propagated
+    let t: Table = lua
+        .load(r#"setmetatable({}, {__tostring = function() error("boom") end})"#)
+        .eval()?;
+    assert!(t.to_lua_string(&lua).unwrap_err().to_string().contains("boom"));
+
+    // Custom implementation
+    struct Celsius(f64);
+    impl ToLuaString for Celsius {
+        fn to_lua_string(&self, lua: &Lua) -> Result<String> {
+            lua.create_string(format!("{}C", self.0))
+        }
+    }
+
+    let items: [&dyn ToLuaString; 6] = [
+        &"point=",
+        &ud,
+        &", t=",
+        &Celsius(21.5),
+        &", ",
+        &Value::Boolean(false),
+    ];
+    assert_eq!(lua.concat(items)?, "point=(1, 2), t=21.5C, false");
+    assert_eq!(lua.concat([1, 2, 3])?, "123");
+    assert_eq!(lua.concat(Vec::<Value>::new())?, "");
+    assert_eq!(lua.concat([Error::runtime("failed")])?, "runtime error: failed");
+
+    Ok(())
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn test_string_encoding() -> Result<()> {
+    use mlua::Encoding;
+
+    let lua = Lua::new();
+
+    // Round trips
+    for (text, encoding, bytes) in [
+        ("héllo", Encoding::Utf8, "héllo".as_bytes()),
+        ("hé", Encoding::Utf16Le, &b"h\0\xe9\0"[..]),
+        ("hé", Encoding::Utf16Be, b"\0h\0\xe9"),
+        ("\u{80}é", Encoding::Latin1, b"\x80\xe9"),
+        ("€é", Encoding::Windows1252, b"\x80\xe9"),
+        ("日本", Encoding::ShiftJis, b"\x93\xfa\x96\x7b"),
+        ("한", Encoding::for_label("euc-kr").unwrap(), b"\xc7\xd1"),
+    ] {
+        let s = lua.create_string_encoded(text, encoding)?;
+        assert_eq!(s.as_bytes(), bytes, "encoding {text:?} to {encoding}");
+        assert_eq!(s.decode(encoding)?, text, "decoding {text:?} from {encoding}");
+    }
+
+    // Labels
+    assert_eq!(Encoding::for_label("latin1"), Some(Encoding::Latin1));
+    assert_eq!(Encoding::for_label("Shift_JIS"), Some(Encoding::ShiftJis));
+    assert_eq!(Encoding::for_label("utf-16le"), Some(Encoding::Utf16Le));
+    assert_eq!(Encoding::for_label("unknown"), None);
+
+    // Unmappable characters
+    match lua.create_string_encoded("a日", Encoding::Latin1) {
+        Err(Error::ToLuaConversionError { message, .. }) => {
+            assert!(message.unwrap().contains("'日'"))
+        }
+        res => panic!("expected ToLuaConversionError, got {res:?}"),
+    }
+    assert!(lua.create_string_encoded("€", Encoding::ShiftJis).is_err());
+
+    // Malformed input
+    let s = lua.create_string(b"\x93")?;
+    assert!(matches!(
+        s.decode(Encoding::ShiftJis),
+        Err(Error::FromLuaConversionError { .. })
+    ));
+    assert!(lua.create_string(b"\xff")?.decode(Encoding::Utf8).is_err());
+    assert!(lua.create_string(b"a")?.decode(Encoding::Utf16Le).is_err());
+
+    Ok(())
+}
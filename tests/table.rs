@@ -1,4 +1,4 @@
-use mlua::{Error, Lua, Nil, ObjectLike, Result, Table, Value};
+use mlua::{Error, Lua, MergeStrategy, Nil, ObjectLike, PathOptions, Result, Table, Value};
 
 #[test]
 fn test_globals_set_get() -> Result<()> {
@@ -127,7 +127,7 @@ fn test_table_push_pop() -> Result<()> {
             .clone()
             .sequence_values::<i64>()
             .collect::<Result<Vec<_>>>()?,
-        vec![]
+        Vec::<i64>::new()
     );
     assert_eq!(table2.pop::<i64>()?, 345);
     assert_eq!(table2.pop::<i64>()?, 234);
@@ -186,6 +186,180 @@ fn test_table_clear() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_table_retain() -> Result<()> {
+    let lua = Lua::new();
+
+    // Check readonly error
+    #[cfg(feature = "luau")]
+    {
+        let t = lua.create_table()?;
+        t.set_readonly(true);
+        assert!(matches!(
+            t.retain(|_: Value, _: Value| Ok(false)),
+            Err(Error::RuntimeError(err)) if err.contains("attempt to modify a readonly table")
+        ));
+    }
+
+    let t = lua
+        .load(
+            r#"
+        setmetatable({1, 2, 3, 4, a = 5, b = 6, c = "x"}, {
+            __index = function() error("index error") end,
+            __newindex = function() error("newindex error") end,
+            __pairs = function() error("pairs error") end,
+        })
+    "#,
+        )
+        .eval::<Table>()?;
+    let mut visited = 0;
+    t.retain(|_: Value, v: Value| {
+        visited += 1;
+        Ok(matches!(v, Value::Integer(n) if n % 2 == 0))
+    })?;
+    assert_eq!(visited, 7);
+    assert_eq!(t.raw_get::<Option<i64>>(1)?, None);
+    assert_eq!(t.raw_get::<i64>(2)?, 2);
+    assert_eq!(t.raw_get::<i64>(4)?, 4);
+    assert_eq!(t.raw_get::<Option<i64>>("a")?, None);
+    assert_eq!(t.raw_get::<i64>("b")?, 6);
+    assert_eq!(t.raw_get::<Value>("c")?, Value::Nil);
+    let mut remaining = 0;
+    t.for_each(|_: Value, _: Value| {
+        remaining += 1;
+        Ok(())
+    })?;
+    assert_eq!(remaining, 3);
+
+    // Remove everything
+    t.retain(|_: Value, _: Value| Ok(false))?;
+    assert!(t.is_empty());
+
+    // Errors stop the traversal
+    let t = lua.create_sequence_from([1, 2, 3])?;
+    let err = t
+        .retain(|k: i64, _: i64| {
+            if k == 2 {
+                Err(Error::runtime("stop"))
+            } else {
+                Ok(true)
+            }
+        })
+        .unwrap_err();
+    assert!(err.to_string().contains("stop"));
+    assert_eq!(t.raw_len(), 3);
+
+    // Conversion errors
+    let t = lua.load(r#"{1, 2, "x"}"#).eval::<Table>()?;
+    assert!(t.retain(|_: i64, _: i64| Ok(true)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_table_sort() -> Result<()> {
+    let lua = Lua::new();
+
+    let seq = |t: &Table| t.sequence_values().collect::<Result<Vec<Value>>>();
+
+    // Default `<` comparison
+    let t = lua.load("{5, 3.5, 1, 4, 2}").eval::<Table>()?;
+    t.sort(None)?;
+    assert_eq!(
+        t.sequence_values().collect::<Result<Vec<f64>>>()?,
+        [1.0, 2.0, 3.5, 4.0, 5.0]
+    );
+
+    let t = lua.load(r#"{"b", "c", "a"}"#).eval::<Table>()?;
+    t.sort(None)?;
+    assert_eq!(
+        t.sequence_values().collect::<Result<Vec<String>>>()?,
+        ["a", "b", "c"]
+    );
+
+    // `__lt` metamethod
+    let t = lua
+        .load(
+            r#"
+        local mt = { __lt = function(a, b) return a.n < b.n end }
+        local function obj(n) return setmetatable({ n = n }, mt) end
+        return { obj(3), obj(1), obj(2) }
+    "#,
+        )
+        .eval::<Table>()?;
+    t.sort(None)?;
+    let ns = seq(&t)?
+        .iter()
+        .map(|v| v.as_table().unwrap().get("n"))
+        .collect::<Result<Vec<i64>>>()?;
+    assert_eq!(ns, [1, 2, 3]);
+
+    // Incomparable values
+    let t = lua.load(r#"{1, "x", 2}"#).eval::<Table>()?;
+    assert!(t.sort(None).is_err());
+    assert_eq!(t.raw_get::<String>(2)?, "x");
+
+    // Custom comparator, the sort is stable
+    let t = lua
+        .load(r#"{ {k = 2, v = "a"}, {k = 1, v = "b"}, {k = 2, v = "c"}, {k = 1, v = "d"} }"#)
+        .eval::<Table>()?;
+    let by_key: mlua::Function = lua.load("function(a, b) return a.k < b.k end").eval()?;
+    t.sort(Some(by_key))?;
+    let vs = seq(&t)?
+        .iter()
+        .map(|v| v.as_table().unwrap().get("v"))
+        .collect::<Result<Vec<String>>>()?;
+    assert_eq!(vs, ["b", "d", "a", "c"]);
+
+    // Inconsistent comparator does not fail
+    let t = lua.create_sequence_from(1..=100)?;
+    let random: mlua::Function = lua.load("function() return math.random() < 0.5 end").eval()?;
+    t.sort(Some(random))?;
+    let mut values = t.sequence_values().collect::<Result<Vec<i64>>>()?;
+    values.sort();
+    assert_eq!(values, (1..=100).collect::<Vec<_>>());
+
+    // Comparator errors
+    let failing: mlua::Function = lua.load("function() error('compare error') end").eval()?;
+    let err = t.sort(Some(failing)).unwrap_err();
+    assert!(err.to_string().contains("compare error"));
+
+    // Rust comparator
+    let t = lua
+        .load(r#"{"banana", "Apple", "cherry", "apple"}"#)
+        .eval::<Table>()?;
+    t.sort_by_rust(|a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase()))?;
+    assert_eq!(
+        t.sequence_values().collect::<Result<Vec<String>>>()?,
+        ["Apple", "apple", "banana", "cherry"]
+    );
+
+    let t = lua.load("{ {3}, {1}, {2} }").eval::<Table>()?;
+    t.sort_by_rust(|a: &Vec<i64>, b: &Vec<i64>| b.cmp(a))?;
+    assert_eq!(
+        t.sequence_values().collect::<Result<Vec<Vec<i64>>>>()?,
+        [[3], [2], [1]]
+    );
+
+    // Conversion errors leave the table untouched
+    let t = lua.load(r#"{2, "x", 1}"#).eval::<Table>()?;
+    assert!(t.sort_by_rust(|a: &i64, b: &i64| a.cmp(b)).is_err());
+    assert_eq!(t.raw_get::<i64>(1)?, 2);
+
+    // Check readonly error
+    #[cfg(feature = "luau")]
+    {
+        let t = lua.create_sequence_from([2, 1])?;
+        t.set_readonly(true);
+        assert!(matches!(
+            t.sort(None),
+            Err(Error::RuntimeError(err)) if err.contains("attempt to modify a readonly table")
+        ));
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_table_sequence_from() -> Result<()> {
     let lua = Lua::new();
@@ -235,6 +409,54 @@ fn test_table_pairs() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_table_sorted_pairs() -> Result<()> {
+    let lua = Lua::new();
+
+    let table = lua
+        .load(
+            r#"
+    {
+        zeta = 1, alpha = 2, [10] = 3, [2] = 4, [-1.5] = 5, [true] = 6, beta = 7, ["10"] = 8,
+    }
+    "#,
+        )
+        .eval::<Table>()?;
+
+    let expected = vec![
+        Value::Boolean(true),
+        Value::Number(-1.5),
+        Value::Integer(2),
+        Value::Integer(10),
+        lua.pack("10")?,
+        lua.pack("alpha")?,
+        lua.pack("beta")?,
+        lua.pack("zeta")?,
+    ];
+    let keys = |pairs: mlua::TablePairs<Value, Value>| {
+        pairs.map(|kv| kv.map(|(k, _)| k)).collect::<Result<Vec<_>>>()
+    };
+    assert_eq!(keys(table.sorted_pairs())?, expected);
+
+    // Default order can be changed per state
+    lua.set_deterministic_pairs(true);
+    assert_eq!(keys(table.pairs())?, expected);
+    lua.set_deterministic_pairs(false);
+
+    // Removed keys are skipped
+    let mut values = Vec::new();
+    for kv in table.sorted_pairs::<Value, i32>() {
+        let (k, v) = kv?;
+        if k == Value::Integer(2) {
+            table.raw_set(10, Nil)?;
+        }
+        values.push(v);
+    }
+    assert_eq!(values, vec![6, 5, 4, 8, 2, 7, 1]);
+
+    Ok(())
+}
+
 #[test]
 fn test_table_for_each() -> Result<()> {
     let lua = Lua::new();
@@ -398,6 +620,138 @@ fn test_table_error() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_table_path() -> Result<()> {
+    let lua = Lua::new();
+
+    let t: Table = lua
+        .load(r#"{ a = { b = { 10, 20, { c = "x" } } }, ["d.e"] = 1, n = true }"#)
+        .eval()?;
+    assert_eq!(t.get_path::<i32>("a.b[2]")?, 20);
+    assert_eq!(t.get_path::<String>("a.b[3].c")?, "x");
+    assert_eq!(t.get_path::<i32>(r#"["d.e"]"#)?, 1);
+    assert_eq!(t.get_path::<Option<i32>>("a.x.y")?, None);
+
+    // Errors
+    let err = t.get_path::<i32>("n.x").unwrap_err().to_string();
+    assert!(
+        err.contains("cannot index `n` (a boolean value) at path `n.x`"),
+        "{err}"
+    );
+    let err = t.get_path::<i32>("a.b[3].c").unwrap_err().to_string();
+    assert!(err.contains("at path `a.b[3].c`"), "{err}");
+    for path in ["", "a.", "a..b", "a[x]", "a[1", r#"a["b]"#, "a b"] {
+        let err = t.get_path::<Value>(path).unwrap_err().to_string();
+        assert!(err.contains("invalid path"), "{path}: {err}");
+    }
+
+    // Set
+    t.set_path("a.b[3].c", "y")?;
+    t.set_path("x.y[1].z", true)?;
+    assert_eq!(t.get_path::<String>("a.b[3].c")?, "y");
+    assert!(t.get_path::<bool>("x.y[1].z")?);
+    assert!(t.get_path::<Table>("x.y")?.raw_get::<Table>(1).is_ok());
+
+    let options = PathOptions::new().create_missing(false);
+    let err = t.set_path_with("p.q.r", 1, options).unwrap_err().to_string();
+    assert!(err.contains("cannot set path `p.q.r`: `p` is nil"), "{err}");
+    assert_eq!(t.get::<Value>("p")?, Nil);
+    let err = t.set_path("n.x", 1).unwrap_err().to_string();
+    assert!(err.contains("cannot index `n` (a boolean value)"), "{err}");
+
+    // Raw access
+    let proxy: Table = lua
+        .load(r#"setmetatable({}, { __index = { a = { b = 1 } } })"#)
+        .eval()?;
+    assert_eq!(proxy.get_path::<i32>("a.b")?, 1);
+    let raw = PathOptions::new().raw(true);
+    assert_eq!(proxy.get_path_with::<Option<i32>>("a.b", raw)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_table_merge() -> Result<()> {
+    let lua = Lua::new();
+    let layers = |lua: &Lua| -> Result<(Table, Table)> {
+        lua.load(
+            r#"
+            local defaults = { name = "app", opts = { debug = false, level = 1 }, tags = { "a", "b" } }
+            local user = { opts = { debug = true, extra = { x = 1 } }, tags = { "c" }, name = 42 }
+            return defaults, user
+        "#,
+        )
+        .eval()
+    };
+
+    // Overwrite
+    let (defaults, user) = layers(&lua)?;
+    defaults.merge(&user, MergeStrategy::Overwrite)?;
+    assert_eq!(defaults.get::<i32>("name")?, 42);
+    assert!(defaults.get_path::<bool>("opts.debug")?);
+    assert_eq!(defaults.get_path::<i32>("opts.level")?, 1);
+    assert_eq!(defaults.get::<Vec<String>>("tags")?, ["c", "b"]);
+    // Added tables are copied
+    let extra = defaults.get_path::<Table>("opts.extra")?;
+    assert!(extra != user.get_path::<Table>("opts.extra")?);
+    assert_eq!(extra.get::<i32>("x")?, 1);
+
+    // Keep existing
+    let (defaults, user) = layers(&lua)?;
+    defaults.merge(&user, MergeStrategy::KeepExisting)?;
+    assert_eq!(defaults.get::<String>("name")?, "app");
+    assert!(!defaults.get_path::<bool>("opts.debug")?);
+    assert_eq!(defaults.get_path::<i32>("opts.extra.x")?, 1);
+    assert_eq!(defaults.get::<Vec<String>>("tags")?, ["a", "b"]);
+
+    // Concat arrays
+    let (defaults, user) = layers(&lua)?;
+    defaults.merge(&user, MergeStrategy::ConcatArrays)?;
+    assert_eq!(defaults.get::<Vec<String>>("tags")?, ["a", "b", "c"]);
+    assert_eq!(defaults.get::<i32>("name")?, 42);
+
+    // Custom resolver
+    let (defaults, user) = layers(&lua)?;
+    let strategy = MergeStrategy::custom(|lua, key, existing, incoming| match key.to_string()?.as_str() {
+        "name" => {
+            let name = format!("{}-{}", existing.to_string()?, incoming.to_string()?);
+            lua.create_string(name).map(Value::String)
+        }
+        _ => Ok(incoming),
+    });
+    defaults.merge(&user, strategy)?;
+    assert_eq!(defaults.get::<String>("name")?, "app-42");
+    assert!(defaults.get_path::<bool>("opts.debug")?);
+
+    // Cyclic tables
+    let (a, b): (Table, Table) = lua
+        .load(
+            r#"
+            local a, b = { v = 1 }, { w = 2 }
+            a.self, b.self, b.list = a, b, { b }
+            return a, b
+        "#,
+        )
+        .eval()?;
+    a.merge(&b, MergeStrategy::Overwrite)?;
+    assert_eq!(a.get_path::<i32>("self.w")?, 2);
+    assert_eq!(a.get_path::<i32>("self.v")?, 1);
+    let list = a.get::<Table>("list")?;
+    let copy = list.get::<Table>(1)?;
+    assert_eq!(copy.get::<Table>("self")?, copy);
+    assert_eq!(copy.get::<i32>("w")?, 2);
+    a.merge(&a.clone(), MergeStrategy::ConcatArrays)?;
+
+    // Metamethods are not invoked
+    let t: Table = lua
+        .load("setmetatable({}, { __newindex = function() error('newindex') end })")
+        .eval()?;
+    t.merge(&user, MergeStrategy::Overwrite)?;
+    assert_eq!(t.raw_get::<i32>("name")?, 42);
+
+    Ok(())
+}
+
 #[test]
 fn test_table_object_like() -> Result<()> {
     let lua = Lua::new();
@@ -0,0 +1,136 @@
+#![cfg(feature = "testing")]
+
+use std::fs;
+
+use mlua::{Lua, Result, Table, TestOutcome};
+
+#[test]
+fn test_test_module() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("t", lua.create_test_module()?)?;
+
+    let summary: Table = lua
+        .load(
+            r#"
+            local log = {}
+            t.before_each(function() table.insert(log, "before") end)
+            t.describe("matchers", function()
+                t.after_each(function() table.insert(log, "after") end)
+                t.it("passes", function()
+                    t.expect(1):to_be(1.0)
+                    t.expect({a = {1, 2}}):to_equal({a = {1, 2}})
+                    t.expect({a = 1}).never:to_be({a = 1})
+                    t.expect(nil):to_be_nil()
+                    t.expect(0):to_be_truthy()
+                    t.expect(false):to_be_falsy()
+                    t.expect(1):to_be_a("number")
+                    t.expect(0.1 + 0.2):to_be_close_to(0.3)
+                    t.expect(2):to_be_greater_than(1)
+                    t.expect(2):to_be_less_than(3)
+                    t.expect("abc"):to_have_length(3)
+                    t.expect({1, 2}):to_contain(2)
+                    t.expect("hello world"):to_contain("o w")
+                    t.expect("key=42"):to_match("^%w+=%d+$")
+                    t.expect(function() error("boom") end):to_throw("boom")
+                    t.expect(function() end).never:to_throw()
+                end)
+                t.it("fails", function()
+                    t.expect({1, 2}):to_equal({1, 3})
+                end)
+                t.pending("later")
+            end)
+            local summary = t.run()
+            summary.log = table.concat(log, ",")
+            return summary
+        "#,
+        )
+        .eval()?;
+
+    assert_eq!(summary.get::<usize>("passed")?, 1);
+    assert_eq!(summary.get::<usize>("failed")?, 1);
+    assert_eq!(summary.get::<usize>("skipped")?, 1);
+    assert_eq!(summary.get::<String>("log")?, "before,after,before,after");
+    let failure: Table = summary.get::<Table>("failures")?.get(1)?;
+    assert_eq!(failure.get::<String>("name")?, "matchers fails");
+    let message = failure.get::<String>("message")?;
+    assert!(
+        message.starts_with("expected {") && message.contains("to equal {"),
+        "{message}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_run_lua_tests() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("nested"))?;
+    fs::write(
+        temp_dir.path().join("a_spec.lua"),
+        r#"
+        describe("strings", function()
+            it("upper", function() expect(("abc"):upper()):to_be("ABC") end)
+            it("len", function() expect("abc"):to_have_length(4) end)
+        end)
+    "#,
+    )?;
+    fs::write(
+        temp_dir.path().join("nested/b_spec.lua"),
+        r#"it("works", function() expect(answer()):to_be(42) end)"#,
+    )?;
+    fs::write(temp_dir.path().join("nested/c_spec.lua"), "syntax error")?;
+    fs::write(temp_dir.path().join("helper.lua"), "error('not a test')")?;
+
+    let lua = Lua::new();
+    lua.globals()
+        .set("answer", lua.create_function(|_, ()| Ok(42))?)?;
+
+    let report = lua.run_lua_tests(temp_dir.path())?;
+    assert!(!report.is_success());
+    let names: Vec<_> = report.results.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names[..3], ["strings upper", "strings len", "works"]);
+    assert_eq!(report.passed().count(), 2);
+    assert_eq!(report.failed().count(), 2);
+    assert!(report.results[0].file.as_ref().unwrap().ends_with("a_spec.lua"));
+    match &report.results[1].outcome {
+        TestOutcome::Failed(msg) => assert_eq!(msg, r#"expected "abc" to have length 4 (got 3)"#),
+        outcome => panic!("unexpected outcome {outcome:?}"),
+    }
+    assert!(report.results[3].name.ends_with("c_spec.lua"));
+    assert!(report.to_string().ends_with("2 passed, 2 failed, 0 skipped"));
+
+    // Test functions are not leaked to globals
+    assert!(lua.globals().get::<Option<mlua::Function>>("it")?.is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_run_lua_tests_async() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("async_spec.lua");
+    fs::write(
+        &file,
+        r#"
+        it("awaits", function() expect(fetch(41)):to_be(42) end)
+    "#,
+    )?;
+
+    let lua = Lua::new();
+    let fetch = lua.create_async_function(|_, n: i64| async move {
+        tokio::task::yield_now().await;
+        Ok(n + 1)
+    })?;
+    lua.globals().set("fetch", fetch)?;
+
+    let report = lua.run_lua_tests_async(&file).await?;
+    assert!(report.is_success(), "{report}");
+    assert_eq!(report.passed().count(), 1);
+
+    // Sync runner cannot call async functions
+    let report = lua.run_lua_tests(&file)?;
+    assert_eq!(report.failed().count(), 1);
+
+    Ok(())
+}
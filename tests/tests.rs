@@ -8,8 +8,8 @@ use std::sync::Arc;
 use std::{error, f32, f64, fmt};
 
 use mlua::{
-    ChunkMode, Error, ExternalError, Function, Lua, LuaOptions, Nil, Result, StdLib, String, Table, UserData,
-    Value, Variadic,
+    Capability, ChunkMode, DeterministicOptions, Error, EventBus, ExternalError, Function, Lua, LuaOptions,
+    LuaVersion, Nil, Result, StdLib, String, Table, UnwindMode, UserData, Value, Variadic,
 };
 
 #[cfg(not(feature = "luau"))]
@@ -66,6 +66,112 @@ fn test_safety() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_unsafe_capabilities() -> Result<()> {
+    use mlua::UnsafeCapabilities;
+
+    let dir = tempfile::tempdir().unwrap();
+    let dump = |lua: &Lua| -> Result<()> {
+        let bytecode = lua.load("return 42").into_function()?.dump(false);
+        std::fs::write(dir.path().join("binmod.luac"), bytecode).unwrap();
+        std::fs::write(dir.path().join("textmod.lua"), "return 'text'").unwrap();
+        let path = dir.path().join("?.luac;").display().to_string()
+            + &dir.path().join("?.lua").display().to_string();
+        lua.globals().get::<Table>("package")?.set("path", path)?;
+        Ok(())
+    };
+
+    // LuaJIT reports "wrong mode" instead of "binary chunk"
+    let is_mode_error = |msg: &str| msg.contains("binary chunk") || msg.contains("wrong mode");
+
+    // Safe state allows binary chunks only
+    let lua = Lua::new();
+    assert_eq!(lua.unsafe_capabilities(), UnsafeCapabilities::ALLOW_BINARY_CHUNKS);
+    dump(&lua)?;
+    let bytecode = lua.load("return 1").into_function()?.dump(false);
+    assert_eq!(
+        lua.load("return (loadstring or load)(...)()")
+            .call::<i32>(lua.create_string(&bytecode)?)?,
+        1
+    );
+    assert_eq!(lua.load(r#"require "binmod""#).eval::<i32>()?, 42);
+
+    // No unsafe capabilities
+    let caps = UnsafeCapabilities::NONE;
+    let lua = unsafe { Lua::unsafe_new_with_capabilities(StdLib::ALL_SAFE, LuaOptions::default(), caps)? };
+    assert_eq!(lua.unsafe_capabilities(), caps);
+    dump(&lua)?;
+    let (func, err) = lua
+        .load("return (loadstring or load)(...)")
+        .call::<(Value, StdString)>(lua.create_string(&bytecode)?)?;
+    assert!(func.is_nil());
+    assert!(is_mode_error(&err), "{err}");
+    match lua.load(r#"require "binmod""#).exec() {
+        Err(Error::RuntimeError(msg)) => assert!(is_mode_error(&msg), "{msg}"),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+    assert_eq!(lua.load(r#"require "textmod""#).eval::<StdString>()?, "text");
+    assert_eq!(lua.load("(loadstring or load)('return 2')()").eval::<i32>()?, 2);
+    // Binary chunks can still be loaded from Rust
+    assert_eq!(lua.load(&bytecode).eval::<i32>()?, 1);
+
+    // Only binary chunks allowed (same as the safe state)
+    let caps = UnsafeCapabilities::ALLOW_BINARY_CHUNKS;
+    let lua = unsafe { Lua::unsafe_new_with_capabilities(StdLib::ALL_SAFE, LuaOptions::default(), caps)? };
+    assert_eq!(lua.unsafe_capabilities(), caps);
+    assert!(lua.load("package.loadlib('nonexistent', 'f')").exec().is_err());
+    match lua.load_std_libs(StdLib::DEBUG) {
+        Err(Error::SafetyError(_)) => {}
+        r => panic!("expected SafetyError, got {r:?}"),
+    }
+    match unsafe { Lua::unsafe_new_with_capabilities(StdLib::DEBUG, LuaOptions::default(), caps) } {
+        Err(Error::SafetyError(_)) => {}
+        r => panic!("expected SafetyError, got {r:?}"),
+    }
+
+    // Debug library and C functions allowed
+    let caps = UnsafeCapabilities::ALLOW_DEBUG_LIBRARY | UnsafeCapabilities::ALLOW_C_FUNCTIONS_FROM_LUA;
+    let lua = unsafe { Lua::unsafe_new_with_capabilities(StdLib::NONE, LuaOptions::default(), caps)? };
+    lua.load_std_libs(StdLib::DEBUG | StdLib::PACKAGE)?;
+    assert!(lua
+        .unsafe_capabilities()
+        .contains(UnsafeCapabilities::ALLOW_DEBUG_LIBRARY));
+    assert!(!lua
+        .unsafe_capabilities()
+        .contains(UnsafeCapabilities::ALLOW_BINARY_CHUNKS));
+    assert_eq!(lua.load("type(debug.getinfo)").eval::<StdString>()?, "function");
+    assert!(lua
+        .load("return package.loadlib('nonexistent', 'f')")
+        .eval::<Value>()?
+        .is_nil());
+    dump(&lua)?;
+    assert!(lua.load(r#"require "binmod""#).exec().is_err());
+
+    // All capabilities
+    let lua = unsafe { Lua::unsafe_new() };
+    assert_eq!(lua.unsafe_capabilities(), UnsafeCapabilities::ALL);
+    assert_eq!(
+        lua.load("return (loadstring or load)(...)()")
+            .call::<i32>(lua.create_string(&bytecode)?)?,
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_try_new() -> Result<()> {
+    let lua = Lua::try_new()?;
+    assert_eq!(lua.load("1 + 1").eval::<i32>()?, 2);
+
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::default())?;
+    assert!(lua.globals().get::<Option<Value>>("string")?.is_none());
+    assert_eq!(lua.load("type(print)").eval::<StdString>()?, "function");
+
+    Ok(())
+}
+
 #[test]
 fn test_load() -> Result<()> {
     let lua = Lua::new();
@@ -428,13 +534,18 @@ fn test_panic() -> Result<()> {
         lua.load(
             r#"
             local _, err = pcall(rust_panic_function)
-            error(tostring(err))
+            error(tostring(err), 0)
         "#,
         )
+        .set_name("@panic.lua")
         .exec()
     }) {
         Ok(Ok(_)) => panic!("no error was detected"),
-        Ok(Err(Error::RuntimeError(_))) => {}
+        Ok(Err(Error::RuntimeError(msg))) => {
+            // Lua context is attached to the panic message
+            assert!(msg.starts_with("rust panic\npanicked at panic.lua:2\n"), "{msg}");
+            assert!(msg.contains("stack traceback:"), "{msg}");
+        }
         Ok(Err(e)) => panic!("expected RuntimeError, got {:?}", e),
         Err(_) => panic!("panic was detected"),
     }
@@ -484,6 +595,57 @@ fn test_panic() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_version_info() -> Result<()> {
+    let lua = Lua::new();
+    let info = Lua::version_info();
+
+    // LuaJIT reports itself as Lua 5.1
+    let version = lua.globals().get::<StdString>("_VERSION")?;
+    if info.version != LuaVersion::LuaJIT {
+        assert!(version.starts_with(&info.version.to_string()), "{version}");
+    }
+
+    let compiles = |source: &str| lua.load(source).into_function().is_ok();
+    assert_eq!(compiles("goto skip; ::skip::"), info.goto);
+    assert_eq!(compiles("return 5 // 2"), info.floor_division);
+    assert_eq!(compiles("return 6 & 3"), info.bitwise_operators);
+    assert_eq!(compiles("local x <close> = nil"), info.to_be_closed);
+    assert_eq!(compiles("for i = 1, 2 do continue end"), info.continue_statement);
+
+    let has_global = |name: &str| lua.globals().contains_key(name).unwrap();
+    assert_eq!(has_global("utf8"), info.native_utf8);
+    assert_eq!(has_global("bit32") || has_global("bit"), info.bit_library);
+    assert_eq!(has_global("setfenv"), !info.lexical_env);
+    assert_eq!(lua.load("math.type ~= nil").eval::<bool>()?, info.integers);
+
+    Ok(())
+}
+
+#[test]
+fn test_unwind_mode() -> Result<()> {
+    let expected = if cfg!(feature = "luau") {
+        UnwindMode::CxxExceptions
+    } else if cfg!(all(feature = "luajit", windows)) {
+        UnwindMode::External
+    } else {
+        UnwindMode::Longjmp
+    };
+    if std::env::var_os("LUA_UNWIND_MODE").is_none() {
+        assert_eq!(Lua::unwind_mode(), expected);
+    }
+
+    // Errors are still propagated when panics abort the process
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::new().abort_on_panic(true))?;
+    let f = lua.create_function(|_, ()| Err::<(), _>(Error::runtime("failed")))?;
+    lua.globals().set("f", f)?;
+    let (ok, err): (bool, Value) = lua.load("return pcall(f)").eval()?;
+    assert!(!ok);
+    assert!(err.to_string()?.contains("failed"), "{err:?}");
+
+    Ok(())
+}
+
 #[test]
 fn test_num_conversion() -> Result<()> {
     let lua = Lua::new();
@@ -749,6 +911,83 @@ fn test_replace_registry_value() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_registry_audit() -> Result<()> {
+    let lua = Lua::new();
+    let registry = lua.registry();
+
+    // Named values
+    lua.set_named_registry_value("app.config", "value")?;
+    assert!(registry.named_values().contains(&"app.config".to_string()));
+    assert_eq!(
+        registry.named_value("app.config")?,
+        Value::String(lua.create_string("value")?)
+    );
+    assert!(registry.remove_named_value("app.config")?);
+    assert!(!registry.remove_named_value("app.config")?);
+    assert!(!registry.named_values().contains(&"app.config".to_string()));
+
+    // Registry keys
+    let named_values = registry.stats().named_values;
+    let key1 = lua.create_registry_value("value1")?;
+    let key2 = lua.create_registry_value("value2")?;
+    let nil_key = lua.create_registry_value(Nil)?;
+    registry.set_label(&key1, "first")?;
+    assert_eq!(registry.label(&key1).as_deref(), Some("first"));
+    assert_eq!(registry.label(&key2), None);
+    assert_eq!(registry.label(&nil_key), None);
+    assert!(matches!(
+        Lua::new().registry().set_label(&key1, "other"),
+        Err(Error::MismatchedRegistryKey)
+    ));
+
+    let entries = registry.entries();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].id(), key1.id());
+    assert_eq!(entries[0].label(), Some("first"));
+    assert!(!entries[0].is_dropped());
+    assert_eq!(
+        registry.value(&entries[1])?,
+        Some(Value::String(lua.create_string("value2")?))
+    );
+
+    // Removing a live entry keeps the slot reserved for the key
+    assert!(registry.remove(&entries[1])?);
+    assert_eq!(lua.registry_value::<Value>(&key2)?, Nil);
+    assert_eq!(registry.entries().len(), 2);
+
+    drop(key1);
+    drop(key2);
+    let stats = registry.stats();
+    assert_eq!(stats.named_values, named_values);
+    assert_eq!(stats.keys, 2);
+    assert_eq!(stats.labeled_keys, 1);
+    assert_eq!(stats.dropped_keys, 2);
+    assert!(registry.entries().iter().all(|entry| entry.is_dropped()));
+
+    // Removing a dropped entry reclaims the slot
+    assert!(registry.remove(&entries[0])?);
+    assert_eq!(lua.expire_registry_values(), 1);
+    assert_eq!(lua.expire_registry_values(), 0);
+    let stats = registry.stats();
+    assert_eq!((stats.keys, stats.dropped_keys, stats.expired_keys), (0, 0, 2));
+
+    // Stale entries cannot access reused slots
+    let key3 = lua.create_registry_value("value3")?;
+    assert!(entries.iter().any(|entry| entry.id() == key3.id()));
+    for entry in &entries {
+        assert_eq!(registry.value(entry)?, None);
+        assert!(!registry.remove(entry)?);
+    }
+    assert_eq!(lua.registry_value::<String>(&key3)?, "value3");
+    assert_eq!(registry.entries()[0].label(), None);
+
+    lua.remove_registry_value(key3)?;
+    assert!(registry.entries().is_empty());
+
+    Ok(())
+}
+
 #[test]
 fn test_lua_registry_hash() -> Result<()> {
     let lua = Lua::new();
@@ -964,6 +1203,361 @@ fn test_ref_stack_exhaustion() {
     }
 }
 
+#[test]
+fn test_ref_stack_usage() -> Result<()> {
+    let lua = Lua::new();
+
+    let usage = lua.ref_stack_usage();
+    let tables = (0..100).map(|_| lua.create_table()).collect::<Result<Vec<_>>>()?;
+    let usage2 = lua.ref_stack_usage();
+    assert_eq!(usage2.used, usage.used + 100);
+    assert!(usage2.capacity >= usage2.used);
+
+    drop(tables);
+    let usage3 = lua.ref_stack_usage();
+    assert_eq!(usage3.used, usage.used);
+    assert_eq!(usage3.free, usage2.free + 100);
+
+    // Released slots are reused
+    let _table = lua.create_table()?;
+    assert_eq!(lua.ref_stack_usage().free, usage3.free - 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_value_scope() -> Result<()> {
+    let lua = Lua::new();
+
+    let usage = lua.ref_stack_usage();
+    let kept = lua.value_scope(|scope| {
+        assert_eq!(scope.capacity(), 256);
+        let kept = lua.create_table()?;
+        for i in 0..1000 {
+            let t = lua.create_table()?;
+            t.set("i", i)?;
+            kept.set(i, t)?;
+        }
+        assert_eq!(scope.used(), 1);
+        let other = lua.create_table()?;
+        assert_eq!(scope.used(), 2);
+        assert_eq!(lua.ref_stack_usage().used, usage.used + 2);
+        drop(other);
+        Ok::<_, Error>(kept)
+    })?;
+
+    // Values created in the scope remain valid
+    assert_eq!(kept.get::<Table>(999)?.get::<i32>("i")?, 999);
+    assert_eq!(lua.ref_stack_usage().used, usage.used + 1);
+    drop(kept);
+    assert_eq!(lua.ref_stack_usage().used, usage.used);
+
+    // Nested scopes and falling back when the block is exhausted
+    lua.value_scope_with_capacity(2, |outer| {
+        let tables = (0..5).map(|_| lua.create_table()).collect::<Result<Vec<_>>>()?;
+        assert_eq!(outer.used(), 2);
+        lua.value_scope(|inner| {
+            let s = lua.create_string("inner")?;
+            assert_eq!(inner.used(), 1);
+            assert_eq!(outer.used(), 2);
+            tables[4].set("s", s)
+        })?;
+        for (i, t) in tables.iter().enumerate() {
+            t.set("i", i)?;
+        }
+        assert_eq!(tables[4].get::<String>("s")?, "inner");
+        Ok::<_, Error>(())
+    })?;
+    assert_eq!(lua.ref_stack_usage().used, usage.used);
+
+    // Dropping values of an outer scope inside a nested one
+    lua.value_scope(|_| {
+        let outer = lua.create_table()?;
+        lua.value_scope(|_| {
+            let inner = lua.create_table()?;
+            drop(outer);
+            inner.set("x", 1)
+        })?;
+        lua.create_table()?.set("y", 2)
+    })?;
+    assert_eq!(lua.ref_stack_usage().used, usage.used);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "ref-audit")]
+fn test_live_refs() -> Result<()> {
+    let lua = Lua::new();
+
+    let count = |type_name| {
+        lua.live_refs()
+            .iter()
+            .filter(|r| r.type_name == type_name)
+            .count()
+    };
+    let (tables, funcs) = (count("table"), count("function"));
+
+    let table = lua.create_table()?;
+    let func = lua.create_function(|_, ()| Ok(()))?;
+    assert_eq!(count("table"), tables + 1);
+    assert_eq!(count("function"), funcs + 1);
+    let origin = lua
+        .live_refs()
+        .into_iter()
+        .find(|r| r.type_name == "table")
+        .unwrap();
+    assert!(origin.to_string().starts_with("table reference created at:"));
+
+    drop(table);
+    assert_eq!(count("table"), tables);
+    assert_eq!(count("function"), funcs + 1);
+
+    drop(func);
+    assert_eq!(count("function"), funcs);
+
+    Ok(())
+}
+
+#[test]
+fn test_capabilities() -> Result<()> {
+    let lua = Lua::new();
+
+    let read = lua.create_function_with_capabilities([Capability::FS_READ], |_, path: StdString| {
+        Ok(format!("read {path}"))
+    })?;
+    let spawn = lua.create_function_with_capabilities(["process", "net"], |_, ()| Ok(true))?;
+    lua.globals().set("read", read.clone())?;
+    lua.globals().set("spawn", spawn)?;
+
+    let assert_denied = |res: Result<()>, cap: &str| match res {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::PermissionDenied { capability } => assert_eq!(capability, cap),
+            err => panic!("expected PermissionDenied, got {err:?}"),
+        },
+        r => panic!("expected CallbackError, got {r:?}"),
+    };
+
+    // Nothing is granted by default
+    assert_denied(lua.load("read('a')").exec(), "fs.read");
+    assert_denied(read.call(""), "fs.read");
+    // Indirect calls are checked against the nearest Lua caller
+    let (ok, err): (bool, StdString) = lua
+        .load("local ok, err = pcall(read, 'a'); return ok, tostring(err)")
+        .eval()?;
+    assert!(!ok && err.contains("permission denied: missing capability `fs.read`"));
+    #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+    assert_denied(lua.load("coroutine.wrap(read)('a')").exec(), "fs.read");
+
+    // Per-chunk grants
+    let res: StdString = lua.load("return (read('a'))").set_capabilities(["fs"]).eval()?;
+    assert_eq!(res, "read a");
+    assert_denied(lua.load("spawn()").set_capabilities(["process"]).exec(), "net");
+    assert!(lua
+        .load("return (spawn())")
+        .set_capabilities(["*"])
+        .eval::<bool>()?);
+    // Functions defined in a chunk keep its grants, assignments go to globals
+    lua.load("function reader(p) return (read(p)) end")
+        .set_capabilities([Capability::FS_READ])
+        .exec()?;
+    let res: StdString = lua.load("return (reader('b'))").eval()?;
+    assert_eq!(res, "read b");
+    assert_denied(lua.load("read('a')").exec(), "fs.read");
+
+    // Per-environment grants
+    let env = lua.create_table()?;
+    env.set("read", lua.globals().get::<Function>("read")?)?;
+    assert!(!lua.has_capability(&env, "fs.read")?);
+    lua.grant_capabilities(&env, ["fs.read"])?;
+    assert!(lua.has_capability(&env, "fs.read")?);
+    assert!(!lua.has_capability(&env, "fs")?);
+    assert!(!lua.has_capability(&env, "fs.readx")?);
+    let res: StdString = lua
+        .load("return (read('c'))")
+        .set_environment(env.clone())
+        .eval()?;
+    assert_eq!(res, "read c");
+    lua.revoke_capabilities(&env, ["fs.read"])?;
+    assert_denied(lua.load("read('c')").set_environment(env).exec(), "fs.read");
+
+    assert!(Capability::new("fs").grants(&Capability::FS_WRITE));
+    assert!(!Capability::FS_READ.grants(&Capability::new("fs")));
+    assert!(Capability::ALL.grants(&Capability::NET));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "metrics")]
+fn test_metrics_snapshot() -> Result<()> {
+    let lua = Lua::new();
+    let before = lua.metrics_snapshot();
+
+    let f = lua.create_function(|_, fail: bool| match fail {
+        true => Err(Error::runtime("failed")),
+        false => Ok(()),
+    })?;
+    f.call::<()>(false)?;
+    assert!(f.call::<()>(true).is_err());
+    lua.load("pcall(...)").call::<()>((&f, true))?;
+    lua.create_thread(f)?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+
+    let after = lua.metrics_snapshot();
+    assert_eq!(after.callbacks_invoked - before.callbacks_invoked, 3);
+    assert_eq!(after.errors_raised - before.errors_raised, 2);
+    assert_eq!(after.threads_created - before.threads_created, 1);
+    assert_eq!(after.active_threads, 0);
+    assert!(after.used_memory > 0);
+    #[cfg(not(feature = "luau"))]
+    assert!(after.gc_cycles >= before.gc_cycles + 2);
+
+    let text = after.to_prometheus("lua");
+    assert!(text.contains("# TYPE lua_callbacks_total counter\n"));
+    assert!(text.contains(&format!("\nlua_errors_total {}\n", after.errors_raised)));
+    assert_eq!(after.iter().count(), text.lines().count() / 3);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "metrics")]
+fn test_metrics_strings() -> Result<()> {
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::new().track_hot_strings(true))?;
+    let before = lua.metrics_snapshot();
+
+    for _ in 0..3 {
+        lua.create_string("hot")?;
+    }
+    lua.create_string("warm")?;
+    let f = lua.create_function(|_, ()| Ok("hot"))?;
+    f.call::<()>(())?;
+
+    let after = lua.metrics_snapshot();
+    assert_eq!(after.strings_created - before.strings_created, 5);
+    assert_eq!(after.string_bytes - before.string_bytes, 16);
+    let text = after.to_prometheus("lua");
+    assert!(text.contains(&format!("\nlua_string_bytes_total {}\n", after.string_bytes)));
+
+    let hot = lua.hot_strings(2);
+    assert_eq!(hot.len(), 2);
+    assert_eq!((hot[0].content.as_slice(), hot[0].count), (&b"hot"[..], 4));
+    assert_eq!(hot[0].total_bytes(), 12);
+    assert_eq!((hot[1].content.as_slice(), hot[1].count), (&b"warm"[..], 1));
+
+    // Tracking is disabled by default
+    assert!(Lua::new().hot_strings(10).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic_mode() -> Result<()> {
+    let run = || -> Result<(Vec<f64>, StdString, StdString)> {
+        let lua = Lua::new();
+        lua.set_deterministic(
+            DeterministicOptions::new()
+                .random_seed(Some(42))
+                .time(|| 1_000_000)
+                .clock(|| 1.5),
+        )?;
+        let randoms = lua
+            .load("return {math.random(), math.random(), math.random()}")
+            .eval()?;
+        let (time, clock, date): (i64, f64, StdString) = lua
+            .load("return os.time(), os.clock(), os.date('!%Y-%m-%d')")
+            .eval()?;
+        assert_eq!((time, clock, date.as_str()), (1_000_000, 1.5, "1970-01-12"));
+        let strings = lua
+            .load("local t, f = {}, print; return tostring(t) .. ' ' .. tostring(f) .. ' ' .. tostring(t)")
+            .eval()?;
+        let err = lua.load("error({})").exec().unwrap_err().to_string();
+        Ok((randoms, strings, err))
+    };
+
+    let (randoms, strings, err) = run()?;
+    assert_eq!(run()?, (randoms, strings.clone(), err.clone()));
+    assert_eq!(strings, "table: #1 function: #2 table: #1");
+    assert!(
+        err.starts_with("runtime error: (error object is a table value)"),
+        "{err}"
+    );
+
+    // Values with `__tostring` are not affected
+    let lua = Lua::new();
+    lua.set_deterministic(DeterministicOptions::new())?;
+    let s: StdString = lua
+        .load("return tostring(setmetatable({}, {__tostring = function() return 'custom' end}))")
+        .eval()?;
+    assert_eq!(s, "custom");
+    // `os.time` with a table argument uses the original implementation
+    assert!(
+        lua.load("return os.time({year = 2020, month = 1, day = 1})")
+            .eval::<i64>()?
+            > 0
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_random_source() -> Result<()> {
+    use mlua::RandomSource;
+
+    // Sequential counter, optionally reseeded from Lua
+    struct Counter(u64);
+
+    impl RandomSource for Counter {
+        fn next_u64(&mut self) -> u64 {
+            self.0 += 1;
+            self.0
+        }
+
+        fn seed(&mut self, seed: i64) {
+            self.0 = seed as u64;
+        }
+    }
+
+    let lua = Lua::new();
+    lua.set_random_source(Counter(0))?;
+
+    let values: Vec<f64> = lua
+        .load("return {math.random(10), math.random(10), math.random(5, 7), math.random(3, 3)}")
+        .eval()?;
+    assert_eq!(values, [2.0, 3.0, 5.0, 3.0]);
+    let f: f64 = lua.load("return math.random()").eval()?;
+    assert!((0.0..1.0).contains(&f));
+
+    // Reseeding
+    let (a, b): (f64, f64) = lua
+        .load(
+            "math.randomseed(0); local a = math.random(100); math.randomseed(0); return a, math.random(100)",
+        )
+        .eval()?;
+    assert_eq!((a, b), (2.0, 2.0));
+
+    let err = lua.load("math.random(5, 1)").exec().unwrap_err().to_string();
+    assert!(err.contains("interval is empty"), "{err}");
+    let err = lua.load("math.random('x')").exec().unwrap_err().to_string();
+    assert!(err.contains("number expected, got string"), "{err}");
+
+    // Deterministic mode seeds the random source
+    lua.set_random_source(|| u64::MAX)?;
+    assert_eq!(lua.load("return math.random(1, 4)").eval::<f64>()?, 4.0);
+    lua.set_deterministic(DeterministicOptions::new().random_seed(Some(7)))?;
+
+    // Built-in generator is used again after removing the source
+    lua.remove_random_source();
+    let n: f64 = lua
+        .load("math.randomseed(1); return math.random(1000000)")
+        .eval()?;
+    assert!((1.0..=1000000.0).contains(&n));
+
+    Ok(())
+}
+
 #[test]
 fn test_large_args() -> Result<()> {
     let lua = Lua::new();
@@ -1047,6 +1641,66 @@ fn test_chunk_env() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_chunk_env_inheritance() -> Result<()> {
+    let lua = Lua::new();
+
+    let run = lua.create_function(|lua, code: StdString| lua.load(code).eval::<Value>())?;
+    let run_global = lua.create_function(|lua, code: StdString| {
+        lua.load(code).set_inherit_environment(false).eval::<Value>()
+    })?;
+    let read = lua.create_function_with_capabilities(["fs.read"], |_, ()| Ok("data"))?;
+    lua.globals().set("run", run)?;
+    lua.globals().set("run_global", run_global)?;
+    lua.globals().set("read", read)?;
+    lua.globals().set("secret", 42)?;
+
+    let env = lua.create_table()?;
+    for name in ["run", "run_global", "read"] {
+        env.set(name, lua.globals().get::<Function>(name)?)?;
+    }
+    env.set("secret", 1)?;
+
+    // Top-level chunks and chunks loaded from global code use globals
+    assert_eq!(lua.load("return (run('return secret'))").eval::<i64>()?, 42);
+
+    // Chunks loaded from a callback run in the caller environment
+    let sandboxed = |code: &str| lua.load(code.to_string()).set_environment(env.clone());
+    assert_eq!(sandboxed("return (run('return secret'))").eval::<i64>()?, 1);
+    sandboxed("run('leaked = true')").exec()?;
+    assert!(env.get::<bool>("leaked")?);
+    assert_eq!(lua.globals().get::<Option<bool>>("leaked")?, None);
+
+    // Opt out
+    assert_eq!(
+        sandboxed("return (run_global('return secret'))").eval::<i64>()?,
+        42
+    );
+
+    // Capabilities are inherited, including for chunks with extra grants
+    let err = sandboxed("return (run('return read()'))")
+        .eval::<StdString>()
+        .unwrap_err();
+    assert!(err.to_string().contains("missing capability `fs.read`"));
+    lua.grant_capabilities(&env, ["fs.read"])?;
+    assert_eq!(
+        sandboxed("return (run('return read()'))").eval::<StdString>()?,
+        "data"
+    );
+    let f = lua.create_function(|lua, ()| {
+        lua.load("return read(), secret")
+            .set_capabilities(["net"])
+            .eval::<(StdString, i64)>()
+    })?;
+    env.set("f", f)?;
+    assert_eq!(
+        sandboxed("local r, s = f(); return r, s").eval::<(StdString, i64)>()?,
+        ("data".into(), 1)
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_context_thread() -> Result<()> {
     let lua = Lua::new();
@@ -1138,6 +1792,164 @@ fn test_load_from_function() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_loaded_modules() -> Result<()> {
+    let lua = Lua::new();
+
+    let func = lua.create_function(|_, modname: StdString| Ok(format!("{modname} loaded")))?;
+    let _: Value = lua.load_from_function("my_module", func)?;
+    let modules = lua.loaded_modules()?.collect::<Vec<_>>();
+    let names = modules.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert!(names.contains(&"my_module") && names.contains(&"string"));
+    assert!(names.windows(2).all(|w| w[0] <= w[1]));
+    let (_, value) = modules.iter().find(|(name, _)| name == "my_module").unwrap();
+    assert_eq!(value.to_string()?, "my_module loaded");
+
+    assert_eq!(lua.unload_module("my_module", true)?, ["my_module"]);
+    assert!(lua.unload_module("my_module", true)?.is_empty());
+    assert!(lua.loaded_modules()?.all(|(name, _)| name != "my_module"));
+
+    Ok(())
+}
+
+#[test]
+fn test_run_isolated() -> Result<()> {
+    let lua = Lua::new();
+    let globals = lua.globals();
+    globals.set("a", 1)?;
+    globals.set("b", 2)?;
+
+    let script = r#"
+        a = a + 10
+        b = nil
+        c = "new"
+        function get_a() return a end
+        assert(_G.a == 11 and _G.b == nil)
+    "#;
+
+    // Rolled back on error
+    let res = lua.run_isolated(|iso| {
+        lua.load(script).set_environment(iso.env().clone()).exec()?;
+        Err::<(), _>(Error::runtime("rejected"))
+    });
+    assert!(matches!(res, Err(Error::RuntimeError(msg)) if msg == "rejected"));
+    assert_eq!(globals.get::<i64>("a")?, 1);
+    assert_eq!(globals.get::<i64>("b")?, 2);
+    assert_eq!(globals.get::<Value>("c")?, Nil);
+
+    // Rolled back on abort
+    let a = lua.run_isolated(|iso| {
+        lua.load(script).set_environment(iso.env().clone()).exec()?;
+        iso.abort();
+        iso.env().get::<i64>("a")
+    })?;
+    assert_eq!(a, 11);
+    assert_eq!(globals.get::<i64>("a")?, 1);
+
+    // Committed
+    lua.run_isolated(|iso| lua.load(script).set_environment(iso.env().clone()).exec())?;
+    assert_eq!(globals.get::<i64>("a")?, 11);
+    assert_eq!(globals.get::<Value>("b")?, Nil);
+    assert_eq!(globals.get::<StdString>("c")?, "new");
+
+    // Functions defined in the isolated environment use real globals after commit
+    globals.set("a", 100)?;
+    assert_eq!(globals.get::<Function>("get_a")?.call::<i64>(())?, 100);
+
+    Ok(())
+}
+
+#[test]
+fn test_lazy_global() -> Result<()> {
+    let lua = Lua::new();
+    let calls = Arc::new(AtomicU32::new(0));
+
+    let calls2 = calls.clone();
+    lua.set_lazy_global("heavy", move |lua| {
+        calls2.fetch_add(1, Ordering::Relaxed);
+        lua.create_table_from([("answer", 42)])
+    })?;
+    assert_eq!(calls.load(Ordering::Relaxed), 0);
+    assert_eq!(lua.globals().raw_get::<Value>("heavy")?, Nil);
+
+    // Initialized once, on the first access
+    assert_eq!(lua.load("heavy.answer + heavy.answer").eval::<i64>()?, 84);
+    assert_eq!(lua.globals().get::<Table>("heavy")?.get::<i64>("answer")?, 42);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    assert!(lua.globals().raw_get::<Option<Table>>("heavy")?.is_some());
+
+    // Existing values are replaced
+    lua.globals().set("replaced", 1)?;
+    lua.set_lazy_global("replaced", |_| Ok("lazy"))?;
+    assert_eq!(lua.load("replaced").eval::<StdString>()?, "lazy");
+
+    // Errors are propagated and the global remains unset
+    lua.set_lazy_global("broken", |_| Err::<Value, _>(Error::runtime("init failed")))?;
+    let err = lua.load("return broken").exec().unwrap_err();
+    assert!(err.to_string().contains("init failed"));
+    assert_eq!(lua.load("broken").eval::<Value>()?, Nil);
+
+    // An existing `__index` metamethod of globals is kept
+    let lua = Lua::new();
+    lua.load(r#"setmetatable(_G, {__index = function(_, k) return "fallback " .. k end})"#)
+        .exec()?;
+    lua.set_lazy_global("lazy", |_| Ok(1))?;
+    assert_eq!(lua.load("lazy").eval::<i64>()?, 1);
+    assert_eq!(lua.load("undefined").eval::<StdString>()?, "fallback undefined");
+
+    Ok(())
+}
+
+#[test]
+fn test_event_bus() -> Result<()> {
+    let lua = Lua::new();
+    let bus = EventBus::new();
+
+    let total = Arc::new(AtomicU32::new(0));
+    let total2 = total.clone();
+    let id = bus.on(&lua, "add", move |_, n: u32| {
+        total2.fetch_add(n, Ordering::Relaxed);
+        Ok(())
+    })?;
+    bus.on(&lua, "add", |_, n: u32| match n {
+        13 => Err(Error::runtime("unlucky")),
+        _ => Ok(()),
+    })?;
+    assert_eq!(bus.handler_count("add"), 2);
+
+    lua.globals().set("bus", bus.clone())?;
+    lua.load(
+        r#"
+        seen = {}
+        bus:on("add", function(n) table.insert(seen, n) end)
+        bus:once("add", function(n) table.insert(seen, "once " .. n) end)
+        assert(bus:emit("add", 1) == true)
+        local ok, errors = bus:emit("add", 13)
+        assert(ok == false and #errors == 1)
+        assert(tostring(errors[1]):find("unlucky"))
+        assert(bus:emit("unknown") == true)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(total.load(Ordering::Relaxed), 14);
+    let seen = lua.globals().get::<Vec<Value>>("seen")?;
+    assert_eq!(seen.len(), 3);
+    assert_eq!(seen[1].to_string()?, "once 1");
+
+    // Emit from Rust, errors are isolated per handler
+    let errors = bus.emit(&lua, "add", 13)?;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(total.load(Ordering::Relaxed), 27);
+
+    assert!(bus.off(id));
+    assert!(!bus.off(id));
+    bus.emit(&lua, "add", 100)?;
+    assert_eq!(total.load(Ordering::Relaxed), 27);
+    assert_eq!(bus.handler_count("add"), 2);
+
+    Ok(())
+}
+
 #[test]
 fn test_inspect_stack() -> Result<()> {
     let lua = Lua::new();
@@ -1323,3 +2135,69 @@ fn test_exec_raw() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "macros")]
+#[test]
+fn test_implement_trait() -> Result<()> {
+    #[mlua::lua_trait]
+    trait Strategy {
+        fn score(&self, a: i64, b: i64) -> Result<i64>;
+        fn label(&self) -> StdString;
+        fn weight(&self, factor: f64) -> f64 {
+            factor * 2.0
+        }
+    }
+
+    fn run(strategy: &dyn Strategy) -> Result<i64> {
+        strategy.score(3, 4)
+    }
+
+    let lua = Lua::new();
+
+    let table: Table = lua
+        .load(
+            r#"
+        {
+            bonus = 10,
+            score = function(self, a, b) return a * b + self.bonus end,
+            label = function() return "mul" end,
+        }
+    "#,
+        )
+        .eval()?;
+    let strategy = lua.implement_trait::<dyn Strategy>(table.clone())?;
+    assert_eq!(run(&*strategy)?, 22);
+    assert_eq!(strategy.label(), "mul");
+    assert_eq!(strategy.weight(1.5), 3.0);
+
+    // Override the default method
+    table.set(
+        "weight",
+        lua.load("function(self, f) return f end").eval::<Function>()?,
+    )?;
+    assert_eq!(strategy.weight(1.5), 1.5);
+
+    // Errors are propagated through `Result`
+    table.set(
+        "score",
+        lua.load("function() error('boom') end").eval::<Function>()?,
+    )?;
+    match strategy.score(1, 2) {
+        Err(Error::RuntimeError(msg)) => assert!(msg.contains("boom")),
+        r => panic!("expected RuntimeError, got {r:?}"),
+    }
+    table.set("label", lua.load("function() return {} end").eval::<Function>()?)?;
+    assert!(catch_unwind(AssertUnwindSafe(|| strategy.label())).is_err());
+
+    // Missing required method
+    let table = lua.create_table()?;
+    table.set("label", lua.load("function() return '' end").eval::<Function>()?)?;
+    match lua.implement_trait::<dyn Strategy>(table) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "missing method `score`")
+        }
+        r => panic!("expected FromLuaConversionError, got {:?}", r.map(|_| ())),
+    }
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::panic::catch_unwind;
+use std::sync::Arc;
 
 use mlua::{Error, Function, Lua, Result, Thread, ThreadStatus};
 
@@ -219,6 +220,51 @@ fn test_coroutine_panic() {
     }
 }
 
+#[test]
+fn test_thread_locals() -> Result<()> {
+    let lua = Lua::new();
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct RequestId(u32);
+
+    let request_id =
+        lua.create_function(|lua, ()| Ok(lua.current_thread_local::<RequestId>()?.map(|id| id.0)))?;
+    lua.globals().set("request_id", request_id)?;
+
+    let func = lua
+        .load("function() local a = request_id(); coroutine.yield(a); return request_id() end")
+        .eval::<Function>()?;
+    let thread1 = lua.create_thread(func.clone())?;
+    let thread2 = lua.create_thread(func)?;
+    assert_eq!(thread1.set_local(RequestId(1))?, None);
+    assert_eq!(thread1.set_local(RequestId(10))?, Some(RequestId(1)));
+    thread2.set_local(RequestId(2))?;
+
+    // Values travel with the threads across yields
+    assert_eq!(thread1.resume::<u32>(())?, 10);
+    assert_eq!(thread2.resume::<u32>(())?, 2);
+    assert_eq!(thread2.resume::<u32>(())?, 2);
+    assert_eq!(thread1.resume::<u32>(())?, 10);
+    assert_eq!(lua.current_thread_local::<RequestId>()?, None);
+    assert_eq!(thread1.get_local::<RequestId>()?, Some(RequestId(10)));
+    assert_eq!(thread1.get_local::<u32>()?, None);
+
+    assert_eq!(thread1.remove_local::<RequestId>()?, Some(RequestId(10)));
+    assert_eq!(thread1.get_local::<RequestId>()?, None);
+
+    // Values are dropped when the thread is collected
+    let data = Arc::new(());
+    let thread3 = lua.create_thread(lua.create_function(|_, ()| Ok(()))?)?;
+    thread3.set_local(data.clone())?;
+    assert_eq!(Arc::strong_count(&data), 2);
+    drop(thread3);
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(Arc::strong_count(&data), 1);
+
+    Ok(())
+}
+
 #[test]
 fn test_thread_pointer() -> Result<()> {
     let lua = Lua::new();
@@ -231,3 +277,152 @@ fn test_thread_pointer() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_thread_group() -> Result<()> {
+    let lua = Lua::new();
+    let group = lua.create_thread_group()?;
+
+    let env = lua.create_table()?;
+    for name in ["assert", "error", "pcall"] {
+        env.set(name, lua.globals().get::<Function>(name)?)?;
+    }
+    group.install(&env)?;
+    let threads = lua
+        .load(
+            r#"
+        local co = coroutine.create(function() coroutine.yield(1) end)
+        coroutine.resume(co)
+        local wrapped = coroutine.wrap(function(a) local b = coroutine.yield(a + 1); return b * 2 end)
+        assert(wrapped(1) == 2)
+        assert(wrapped(3) == 6)
+        local failing = coroutine.wrap(function() error({code = 1}) end)
+        local ok, err = pcall(failing)
+        assert(not ok and err.code == 1)
+        assert(coroutine.status(co) == "suspended")
+        return co
+    "#,
+        )
+        .set_environment(env)
+        .call::<Thread>(())?;
+    assert!(group.contains(&threads)?);
+
+    let statuses = group.threads()?.iter().map(|t| t.status()).collect::<Vec<_>>();
+    assert_eq!(statuses.len(), 3);
+    assert_eq!(
+        statuses.iter().filter(|s| **s == ThreadStatus::Resumable).count(),
+        1
+    );
+    assert_eq!(
+        statuses.iter().filter(|s| **s == ThreadStatus::Finished).count(),
+        1
+    );
+    assert_eq!(statuses.iter().filter(|s| **s == ThreadStatus::Error).count(), 1);
+
+    // Threads are not kept alive
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(group.threads()?.len(), 1);
+
+    // Threads created from Rust
+    let thread = lua.create_thread(lua.load("coroutine.yield()").into_function()?)?;
+    assert!(!group.contains(&thread)?);
+    group.add(&thread)?;
+    thread.resume::<()>(())?;
+    assert_eq!(group.threads()?.len(), 2);
+
+    #[cfg(any(feature = "lua54", feature = "luau"))]
+    {
+        assert_eq!(group.close_all()?, 2);
+        assert_eq!(thread.status(), ThreadStatus::Finished);
+        assert!(group.threads()?.is_empty());
+        assert!(!group.contains(&thread)?);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "lua54")]
+#[test]
+fn test_thread_group_close() -> Result<()> {
+    let lua = Lua::new();
+    let group = lua.create_thread_group()?;
+    group.install(&lua.globals())?;
+
+    lua.load(
+        r#"
+        closed = 0
+        local function worker(fail)
+            local guard <close> = setmetatable({}, {__close = function()
+                closed = closed + 1
+                if fail then error("close failed") end
+            end})
+            coroutine.yield()
+        end
+        for i = 1, 3 do
+            local co = coroutine.create(worker)
+            coroutine.resume(co, i == 2)
+        end
+    "#,
+    )
+    .exec()?;
+
+    let err = group.close_all().unwrap_err();
+    assert!(err.to_string().contains("close failed"));
+    assert_eq!(lua.globals().get::<i64>("closed")?, 3);
+    assert!(group.threads()?.is_empty());
+
+    // Closing a single thread
+    let thread = lua.create_thread(lua.load("coroutine.yield()").into_function()?)?;
+    thread.resume::<()>(())?;
+    assert_eq!(thread.status(), ThreadStatus::Resumable);
+    thread.close()?;
+    assert_eq!(thread.status(), ThreadStatus::Finished);
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua54", feature = "luau"))]
+#[test]
+fn test_thread_group_close_nested() -> Result<()> {
+    let lua = Lua::new();
+    let group = lua.create_thread_group()?;
+    group.install(&lua.globals())?;
+
+    let (group2, outer) = (group.clone(), lua.create_table()?);
+    let outer2 = outer.clone();
+    let close_all = lua.create_function(move |_, ()| {
+        // The outer thread has resumed the current one
+        let outer = outer2.get::<Thread>("co")?;
+        assert_eq!(outer.status(), ThreadStatus::Running);
+        assert!(outer.close().is_err());
+        group2.close_all()
+    })?;
+    lua.globals().set("close_all", close_all)?;
+    lua.globals().set("outer", outer)?;
+
+    let result = lua
+        .load(
+            r#"
+        local co = coroutine.create(function()
+            local inner = coroutine.create(function()
+                return close_all()
+            end)
+            local ok, closed = coroutine.resume(inner)
+            assert(ok, closed)
+            collectgarbage()
+            coroutine.yield(closed)
+            return "done"
+        end)
+        outer.co = co
+        local _, closed = coroutine.resume(co)
+        collectgarbage()
+        local _, res = coroutine.resume(co)
+        return closed, res
+    "#,
+        )
+        .eval::<(usize, String)>()?;
+    assert_eq!(result, (0, "done".to_string()));
+
+    Ok(())
+}
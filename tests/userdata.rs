@@ -6,8 +6,9 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicI64, Ordering};
 
 use mlua::{
-    AnyUserData, Error, ExternalError, Function, Lua, MetaMethod, Nil, ObjectLike, Result, String, UserData,
-    UserDataFields, UserDataMethods, UserDataRef, Value, Variadic,
+    AnyUserData, Error, ExternalError, Function, Lua, MappedUserDataRef, MappedUserDataRefMut, MetaMethod,
+    Nil, ObjectLike, Result, String, UserData, UserDataFields, UserDataMethods, UserDataRef, UserDataRefMut,
+    UserDataRefUpgradable, UserDataRegistry, Value, Variadic,
 };
 
 #[test]
@@ -206,6 +207,146 @@ fn test_metamethods() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_derived_metamethods() -> Result<()> {
+    #[derive(Debug, PartialEq)]
+    struct Point(i32, i32);
+
+    impl std::fmt::Display for Point {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "({}, {})", self.0, self.1)
+        }
+    }
+
+    impl UserData for Point {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_display_tostring();
+            methods.add_partial_eq();
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Other(i32);
+
+    impl UserData for Other {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_debug_tostring();
+            methods.add_partial_eq();
+        }
+    }
+
+    let lua = Lua::new();
+    let globals = lua.globals();
+    globals.set("p1", Point(1, 2))?;
+    globals.set("p2", Point(1, 2))?;
+    globals.set("p3", Point(2, 1))?;
+    globals.set("o", Other(1))?;
+    lua.load(
+        r#"
+        assert(tostring(p1) == "(1, 2)")
+        assert(tostring(o) == "Other(1)")
+        assert(p1 == p2)
+        assert(p1 ~= p3)
+        assert(p1 == p1)
+    "#,
+    )
+    .exec()?;
+    // Both operands have `__eq` but of different types
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    lua.load("assert(p1 ~= o and o ~= p1)").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_index_multi() -> Result<()> {
+    struct MyUserData;
+
+    impl UserData for MyUserData {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field("name", "ud");
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("method", |_, _, ()| Ok(()));
+            methods.add_meta_method(MetaMethod::Index, |_, _, key: StdString| {
+                Ok(Variadic::from_iter([key.clone(), key.to_uppercase()]))
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(MyUserData)?;
+
+    // Fallback `__index` function
+    let (a, b): (StdString, StdString) = ud.get_multi("key")?;
+    assert_eq!((a.as_str(), b.as_str()), ("key", "KEY"));
+    assert_eq!(ud.get::<StdString>("key")?, "key");
+    // Fields and methods
+    assert_eq!(ud.get_multi::<Variadic<StdString>>("name")?.len(), 1);
+    assert!(matches!(ud.get_multi::<Value>("method")?, Value::Function(_)));
+
+    // Tables with `__index` function
+    let table: mlua::Table = lua
+        .load("setmetatable({x = 1}, {__index = function(_, key) return key, 2 end})")
+        .eval()?;
+    assert_eq!(table.get_multi::<(StdString, i32)>("y")?, ("y".to_string(), 2));
+    assert_eq!(table.get_multi::<(i32, Option<i32>)>("x")?, (1, None));
+
+    Ok(())
+}
+
+#[test]
+fn test_registry_validate() -> Result<()> {
+    struct MyUserData;
+
+    let lua = Lua::new();
+    let validate = |f: fn(&mut UserDataRegistry<MyUserData>)| {
+        let mut result = None;
+        lua.register_userdata_type::<MyUserData>(|reg| {
+            f(reg);
+            result = Some(reg.validate());
+        })
+        .unwrap_or_default();
+        result.unwrap()
+    };
+
+    validate(|reg| {
+        reg.add_meta_method(MetaMethod::Eq, |_, _, _other: AnyUserData| Ok(true));
+        reg.add_meta_method(MetaMethod::ToString, |_, _, ()| Ok("MyUserData"));
+        reg.add_field_method_get("x", |_, _| Ok(1));
+    })?;
+
+    // `__index` registered twice
+    let err = validate(|reg| {
+        reg.add_meta_field(MetaMethod::Index, "fallback");
+        reg.add_meta_method(MetaMethod::Index, |_, _, _key: String| Ok(()));
+    })
+    .unwrap_err();
+    assert!(err.to_string().contains("`__index` is registered more than once"));
+
+    // `__eq` without the other operand
+    let err = validate(|reg| reg.add_meta_method(MetaMethod::Eq, |_, _, ()| Ok(true))).unwrap_err();
+    assert!(err.to_string().contains("`__eq` must accept an argument"));
+
+    // `__tostring` returning a number
+    let err = validate(|reg| reg.add_meta_method(MetaMethod::ToString, |_, _, ()| Ok(1i32))).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("`__tostring` must return a string, not `i32`"));
+
+    // Registration is validated automatically in debug builds
+    #[cfg(debug_assertions)]
+    {
+        let result = lua.register_userdata_type::<MyUserData>(|reg| {
+            reg.add_meta_method(MetaMethod::Eq, |_, _, ()| Ok(true));
+        });
+        assert!(result.is_err());
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "lua54")]
 #[test]
 fn test_metamethod_close() -> Result<()> {
@@ -557,6 +698,68 @@ fn test_fields() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_dynamic_fields() -> Result<()> {
+    struct Row {
+        id: i64,
+        columns: HashMap<StdString, i64>,
+    }
+
+    impl UserData for Row {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("id", |_, this| Ok(this.id));
+            fields.add_dynamic_field_get(|_, this, name| Ok(this.columns.get(name).copied()));
+            fields.add_dynamic_field_set(|_, this, name, value: i64| {
+                if !this.columns.contains_key(name) {
+                    return Err(format!("unknown column '{name}'").into_lua_err());
+                }
+                this.columns.insert(name.to_string(), value);
+                Ok(())
+            });
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method("count", |_, this, ()| Ok(this.columns.len()));
+        }
+    }
+
+    let lua = Lua::new();
+    let row = Row {
+        id: 1,
+        columns: HashMap::from([("a".into(), 10), ("b".into(), 20)]),
+    };
+    lua.globals().set("row", row)?;
+    lua.load(
+        r#"
+        assert(row.id == 1)
+        assert(row:count() == 2)
+        assert(row.a == 10 and row.b == 20)
+        assert(row.c == nil)
+        assert(row[1] == nil)
+        row.a = 15
+        assert(row.a == 15)
+    "#,
+    )
+    .exec()?;
+
+    match lua.load("row.c = 1").exec() {
+        Err(Error::CallbackError { ref cause, .. }) => {
+            assert!(cause.to_string().contains("unknown column 'c'"))
+        }
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+    match lua.load("row[1] = 1").exec() {
+        Err(Error::CallbackError { ref cause, .. }) => {
+            assert!(cause
+                .to_string()
+                .contains("field name must be a string, got integer"))
+        }
+        r => panic!("expected CallbackError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_metatable() -> Result<()> {
     #[derive(Copy, Clone)]
@@ -635,6 +838,53 @@ fn test_metatable() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_method_cache() -> Result<()> {
+    struct MyUserData(i64);
+
+    impl UserData for MyUserData {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("value", |_, this| Ok(this.0));
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_method_mut("inc", |_, this, ()| {
+                this.0 += 1;
+                Ok(())
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(MyUserData(0))?;
+    lua.globals().set("ud", &ud)?;
+    lua.load(
+        r#"
+        for _ = 1, 10 do
+            ud:inc()
+        end
+        assert(ud.value == 10)
+        assert(ud.unknown == nil)
+    "#,
+    )
+    .exec()?;
+
+    #[cfg(feature = "metrics")]
+    {
+        let stats = ud.metatable()?.method_cache_stats()?;
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 9);
+    }
+
+    // Field getters must not be cached
+    ud.borrow_mut::<MyUserData>()?.0 = 100;
+    lua.load("assert(ud.value == 100)").exec()?;
+    #[cfg(feature = "metrics")]
+    assert_eq!(ud.metatable()?.method_cache_stats()?.hits, 9);
+
+    Ok(())
+}
+
 #[test]
 fn test_userdata_proxy() -> Result<()> {
     struct MyUserData(i64);
@@ -816,6 +1066,99 @@ fn test_userdata_method_errors() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_userdata_ref_map() -> Result<()> {
+    struct Player {
+        name: StdString,
+        scores: Vec<i64>,
+    }
+
+    impl UserData for Player {}
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(Player {
+        name: "alice".into(),
+        scores: vec![1, 2],
+    })?;
+
+    let name = UserDataRef::map(ud.borrow::<Player>()?, |p| p.name.as_str());
+    assert_eq!(&*name, "alice");
+    // The userdata remains borrowed while the mapped reference is alive
+    assert!(matches!(
+        ud.borrow_mut::<Player>(),
+        Err(Error::UserDataBorrowMutError)
+    ));
+    drop(name);
+    let first = MappedUserDataRef::map(UserDataRef::map(ud.borrow::<Player>()?, |p| &p.scores), |s| &s[0]);
+    assert_eq!(*first, 1);
+    drop(first);
+
+    let scores = UserDataRefMut::map_mut(ud.borrow_mut::<Player>()?, |p| &mut p.scores);
+    let mut last = MappedUserDataRefMut::map_mut(scores, |s| s.last_mut().unwrap());
+    *last += 10;
+    assert!(matches!(ud.borrow::<Player>(), Err(Error::UserDataBorrowError)));
+    drop(last);
+    assert_eq!(ud.borrow::<Player>()?.scores, [1, 12]);
+
+    // `try_map` returns the original reference on failure
+    let player = match UserDataRef::try_map(ud.borrow::<Player>()?, |p| p.scores.get(5)) {
+        Ok(_) => panic!("expected mapping to fail"),
+        Err(player) => player,
+    };
+    assert_eq!(player.name, "alice");
+    let second = UserDataRef::try_map(player, |p| p.scores.get(1)).ok().unwrap();
+    assert_eq!(*second, 12);
+    drop(second);
+
+    let name = UserDataRefMut::try_map_mut(ud.borrow_mut::<Player>()?, |p| Some(&mut p.name));
+    name.ok().unwrap().push_str(" smith");
+    assert_eq!(ud.borrow::<Player>()?.name, "alice smith");
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_borrow_upgradable() -> Result<()> {
+    #[derive(Debug)]
+    struct Counter(i64);
+    impl UserData for Counter {}
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(Counter(1))?;
+
+    let counter = ud.borrow_upgradable::<Counter>()?;
+    assert_eq!(counter.0, 1);
+    // Only one upgradable borrow is allowed, and no mutable borrows
+    assert!(matches!(
+        ud.borrow_upgradable::<Counter>(),
+        Err(Error::UserDataBorrowError)
+    ));
+    assert!(matches!(
+        ud.borrow_mut::<Counter>(),
+        Err(Error::UserDataBorrowMutError)
+    ));
+
+    #[cfg(not(feature = "send"))]
+    let counter = {
+        // Upgrade fails while other immutable borrows exist
+        let other = ud.borrow::<Counter>()?;
+        let counter = UserDataRefUpgradable::try_upgrade(counter).unwrap_err();
+        assert_eq!(counter.0, other.0);
+        counter
+    };
+
+    let mut counter = UserDataRefUpgradable::try_upgrade(counter).ok().unwrap();
+    counter.0 += 1;
+    assert!(matches!(ud.borrow::<Counter>(), Err(Error::UserDataBorrowError)));
+    drop(counter);
+
+    assert_eq!(ud.borrow::<Counter>()?.0, 2);
+    drop(ud.borrow_upgradable::<Counter>()?);
+    assert_eq!(ud.borrow_mut::<Counter>()?.0, 2);
+
+    Ok(())
+}
+
 #[test]
 fn test_userdata_pointer() -> Result<()> {
     let lua = Lua::new();
@@ -881,3 +1224,419 @@ fn test_nested_userdata_gc() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_shared_userdata() -> Result<()> {
+    struct Entity {
+        name: StdString,
+    }
+
+    let lua = Lua::new();
+    lua.register_userdata_type::<Arc<Entity>>(|reg| {
+        reg.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+    })?;
+
+    let player = Arc::new(Entity {
+        name: "player".into(),
+    });
+    let other = Arc::new(Entity {
+        name: "player".into(),
+    });
+    let globals = lua.globals();
+    globals.set("a", lua.create_shared_userdata(player.clone())?)?;
+    globals.set("b", lua.create_shared_userdata(player.clone())?)?;
+    globals.set("c", lua.create_shared_userdata(other.clone())?)?;
+    lua.load(
+        r#"
+        assert(a == b and rawequal(a, b))
+        assert(a ~= c)
+        assert(a.name == "player")
+        local seen = {[a] = true}
+        assert(seen[b] and not seen[c])
+    "#,
+    )
+    .exec()?;
+
+    // Borrowing returns the original `Arc`
+    let entity = globals.get::<AnyUserData>("b")?.borrow::<Arc<Entity>>()?.clone();
+    assert!(Arc::ptr_eq(&entity, &player));
+    drop(entity);
+    assert_eq!(Arc::strong_count(&player), 2);
+
+    // The cache does not keep the objects alive
+    globals.raw_remove("a")?;
+    globals.raw_remove("b")?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(Arc::strong_count(&player), 1);
+    let ud = lua.create_shared_userdata(player.clone())?;
+    assert_eq!(ud.get::<StdString>("name")?, "player");
+
+    // Destructed objects are replaced
+    let taken = ud.take::<Arc<Entity>>()?;
+    assert!(Arc::ptr_eq(&taken, &player));
+    let ud2 = lua.create_shared_userdata(player.clone())?;
+    assert_ne!(ud, ud2);
+    assert_eq!(ud2, lua.create_shared_userdata(player.clone())?);
+
+    // Cache entries are removed when the userdata objects are collected
+    let count_cached = || -> Result<usize> {
+        let cache = lua.named_registry_value::<mlua::Table>("__mlua_shared_userdata")?;
+        Ok(cache.pairs::<Value, Value>().count())
+    };
+    assert_eq!(count_cached()?, 2);
+    drop((ud, ud2));
+    for i in 0..10 {
+        globals.set("tmp", lua.create_shared_userdata(Arc::new(i))?)?;
+    }
+    globals.raw_remove("tmp")?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(count_cached()?, 1); // `c`
+    globals.raw_remove("c")?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert_eq!(count_cached()?, 0);
+    assert_eq!(Arc::strong_count(&other), 1);
+
+    // Types without registered methods are converted to opaque userdata
+    let opaque = lua.create_shared_userdata(Arc::new(1))?;
+    assert!(opaque.is::<Arc<i32>>());
+    globals.set("opaque", &opaque)?;
+    assert!(lua.load("return opaque.value").exec().is_err());
+    assert_eq!(**opaque.borrow::<Arc<i32>>()?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_cached_fields() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Mesh {
+        vertices: Vec<f64>,
+        computed: Arc<AtomicUsize>,
+    }
+
+    impl UserData for Mesh {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get_cached("sum", |_, this| {
+                this.computed.fetch_add(1, Ordering::Relaxed);
+                Ok(this.vertices.iter().sum::<f64>())
+            });
+            fields.add_field_method_get_cached("none", |_, this| {
+                this.computed.fetch_add(1, Ordering::Relaxed);
+                Ok(Nil)
+            });
+            fields.add_field_function_set("vertices", |_, ud, vertices: Vec<f64>| {
+                ud.borrow_mut::<Mesh>()?.vertices = vertices;
+                ud.invalidate_field("sum")
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    let computed = Arc::new(AtomicUsize::new(0));
+    let mesh = lua.create_userdata(Mesh {
+        vertices: vec![1.0, 2.0, 3.0],
+        computed: computed.clone(),
+    })?;
+    lua.globals().set("mesh", &mesh)?;
+
+    assert_eq!(lua.load("mesh.sum + mesh.sum").eval::<f64>()?, 12.0);
+    assert_eq!(computed.load(Ordering::Relaxed), 1);
+
+    lua.load("mesh.vertices = {10, 20}").exec()?;
+    assert_eq!(lua.load("mesh.sum").eval::<f64>()?, 30.0);
+    assert_eq!(lua.load("mesh.sum").eval::<f64>()?, 30.0);
+    assert_eq!(computed.load(Ordering::Relaxed), 2);
+
+    mesh.invalidate_field("sum")?;
+    assert_eq!(lua.load("mesh.sum").eval::<f64>()?, 30.0);
+    assert_eq!(computed.load(Ordering::Relaxed), 3);
+
+    // Nil values are not cached
+    lua.load("assert(mesh.none == nil and mesh.none == nil)").exec()?;
+    assert_eq!(computed.load(Ordering::Relaxed), 5);
+
+    // Each instance has its own cache
+    let mesh2 = lua.create_userdata(Mesh {
+        vertices: vec![5.0],
+        computed: computed.clone(),
+    })?;
+    assert_eq!(mesh2.get::<f64>("sum")?, 5.0);
+    assert_eq!(mesh.get::<f64>("sum")?, 30.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_field_get_set() -> Result<()> {
+    struct Label {
+        text: StdString,
+        width: u32,
+        changes: Vec<StdString>,
+    }
+
+    impl UserData for Label {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get_set(
+                "width",
+                |_, this| Ok(this.width),
+                |_, this, width| {
+                    this.width = width;
+                    Ok(())
+                },
+            );
+            fields.add_field_method_get_set_notify(
+                "text",
+                |_, this| Ok(this.text.clone()),
+                |_, this, text| {
+                    if text == "forbidden" {
+                        return Err(Error::runtime("forbidden text"));
+                    }
+                    this.text = text;
+                    Ok(())
+                },
+                |_, ud| {
+                    // The userdata can be borrowed mutably again
+                    let mut this = ud.borrow_mut::<Label>()?;
+                    let text = this.text.clone();
+                    this.changes.push(text);
+                    Ok(())
+                },
+            );
+        }
+    }
+
+    let lua = Lua::new();
+    let label = lua.create_userdata(Label {
+        text: "a".into(),
+        width: 10,
+        changes: Vec::new(),
+    })?;
+    lua.globals().set("label", &label)?;
+
+    lua.load(
+        r#"
+        assert(label.width == 10 and label.text == "a")
+        label.width = 20
+        label.text = "b"
+        label.text = "c"
+        assert(label.width == 20 and label.text == "c")
+    "#,
+    )
+    .exec()?;
+    assert_eq!(label.borrow::<Label>()?.changes, vec!["b", "c"]);
+
+    // Failed assignments don't fire the hook
+    assert!(lua.load("label.text = 'forbidden'").exec().is_err());
+    assert_eq!(label.borrow::<Label>()?.changes, vec!["b", "c"]);
+
+    // Both accessors report errors using the field name
+    match lua.load("label.width = 'wide'").exec() {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { to, .. } => assert_eq!(to.as_deref(), Some("Label.width")),
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        res => panic!("expected CallbackError, got {res:?}"),
+    }
+    let _borrow = label.borrow_mut::<Label>()?;
+    match lua.load("return label.width").exec() {
+        Err(Error::CallbackError { ref cause, .. }) => match cause.as_ref() {
+            Error::BadArgument { to, .. } => assert_eq!(to.as_deref(), Some("Label.width")),
+            err => panic!("expected BadArgument, got {err:?}"),
+        },
+        res => panic!("expected CallbackError, got {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_callback_slots() -> Result<()> {
+    struct Button(i64);
+
+    impl UserData for Button {
+        fn register(registry: &mut UserDataRegistry<Self>) {
+            registry.add_callback_slot("on_click");
+            registry.add_function("click", |_, ud: AnyUserData| {
+                let n = {
+                    let mut this = ud.borrow_mut::<Button>()?;
+                    this.0 += 1;
+                    this.0
+                };
+                Ok(ud
+                    .call_callback_slot::<Option<StdString>>("on_click", n)?
+                    .flatten())
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals()
+        .set("Button", lua.create_function(|_, ()| Ok(Button(0)))?)?;
+    let button = lua.create_userdata(Button(0))?;
+    lua.globals().set("button", &button)?;
+
+    // No callback set
+    assert_eq!(button.call_method::<Option<StdString>>("click", ())?, None);
+    assert!(button.callback_slot("on_click")?.is_none());
+
+    lua.load(
+        r#"
+        button.on_click = function(n) return "clicked " .. n end
+        assert(type(button.on_click) == "function")
+    "#,
+    )
+    .exec()?;
+    assert_eq!(button.call_method::<StdString>("click", ())?, "clicked 2");
+    assert_eq!(
+        button.call_callback_slot::<StdString>("on_click", 10)?.as_deref(),
+        Some("clicked 10")
+    );
+
+    // Only functions are accepted
+    let err = lua.load("button.on_click = 123").exec().unwrap_err();
+    assert!(err.to_string().contains("on_click"));
+
+    // Setting from Rust and clearing
+    let func = lua.create_function(|_, n: i64| Ok(format!("rust {n}")))?;
+    button.set_callback_slot("on_click", Some(func))?;
+    assert_eq!(button.call_method::<StdString>("click", ())?, "rust 3");
+    lua.load("button.on_click = nil").exec()?;
+    assert!(button.callback_slot("on_click")?.is_none());
+
+    // Callback is collected together with the userdata
+    lua.load(
+        r#"
+        weak = setmetatable({}, {__mode = "k"})
+        local b = Button()
+        local f = function() end
+        b.on_click = f
+        weak[f] = true
+    "#,
+    )
+    .exec()?;
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert!(lua.load("next(weak) == nil").eval::<bool>()?);
+
+    Ok(())
+}
+
+#[test]
+fn test_register_generic() -> Result<()> {
+    #[derive(Clone)]
+    struct Pair<T>(T, T);
+
+    impl<
+            T: mlua::FromLua + mlua::IntoLua + Clone + mlua::MaybeSend + std::ops::Add<Output = T> + 'static,
+        > UserData for Pair<T>
+    {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_function("new", |_, (a, b): (T, T)| Ok(Pair(a, b)));
+            methods.add_method("sum", |_, this, ()| Ok(this.0.clone() + this.1.clone()));
+        }
+    }
+
+    impl From<Pair<i32>> for Pair<f64> {
+        fn from(p: Pair<i32>) -> Self {
+            Pair(p.0.into(), p.1.into())
+        }
+    }
+
+    impl From<Pair<f32>> for Pair<f64> {
+        fn from(p: Pair<f32>) -> Self {
+            Pair(p.0.into(), p.1.into())
+        }
+    }
+
+    let lua = Lua::new();
+    let factory = mlua::register_generic!(lua, Pair<f32 => [f64], f64, i32 => [f64]>)?;
+    lua.globals().set("Pair", &factory)?;
+
+    // Each instantiation is named after its parameter
+    let ud = lua.load("Pair.i32.new(1, 2)").eval::<AnyUserData>()?;
+    assert!(ud.is::<Pair<i32>>());
+    let type_name = ud.metatable()?.get::<StdString>(MetaMethod::Type.name())?;
+    assert_eq!(type_name, "Pair_i32");
+    let ud = lua.load(r#"Pair("f32", 0.5, 0.25)"#).eval::<AnyUserData>()?;
+    assert!(ud.is::<Pair<f32>>());
+    assert_eq!(
+        ud.metatable()?.get::<StdString>(MetaMethod::Type.name())?,
+        "Pair_f32"
+    );
+
+    // Declared conversions
+    lua.load(
+        r#"
+        local p = Pair("i32", 1, 2)
+        assert(p:sum() == 3)
+        local q = p:to_f64()
+        assert(q:sum() == 3.0)
+        assert(Pair.f32.new(0.5, 0.25):to_f64():sum() == 0.75)
+        assert(Pair.f64.new(1, 2).to_f64 == nil)
+    "#,
+    )
+    .exec()?;
+    let ud = lua.load(r#"Pair("i32", 1, 2):to_f64()"#).eval::<AnyUserData>()?;
+    assert!(ud.is::<Pair<f64>>());
+
+    // Unknown parameter
+    let err = lua.load(r#"Pair("u8", 1, 2)"#).exec().unwrap_err();
+    assert!(err.to_string().contains("unknown type parameter 'u8' of 'Pair'"));
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_meta_binop() -> Result<()> {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Meters(f64);
+
+    fn value(x: mlua::Either<f64, &Meters>) -> f64 {
+        match x {
+            mlua::Either::Left(n) => n,
+            mlua::Either::Right(m) => m.0,
+        }
+    }
+
+    impl UserData for Meters {
+        fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+            fields.add_field_method_get("value", |_, this| Ok(this.0));
+        }
+
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_meta_binop(MetaMethod::Sub, |_, lhs: mlua::Either<f64, &Meters>, rhs| {
+                Ok(Meters(value(lhs) - value(rhs)))
+            });
+            methods.add_meta_binop(MetaMethod::Lt, |_, lhs: mlua::Either<f64, &Meters>, rhs| {
+                Ok(value(lhs) < value(rhs))
+            });
+        }
+    }
+
+    let lua = Lua::new();
+    lua.globals().set("m", Meters(10.0))?;
+
+    // Operand order is preserved
+    assert_eq!(lua.load("(m - 3).value").eval::<f64>()?, 7.0);
+    assert_eq!(lua.load("(3 - m).value").eval::<f64>()?, -7.0);
+    assert_eq!(lua.load("(m - m).value").eval::<f64>()?, 0.0);
+    assert_eq!(lua.load("m - '4'").eval::<UserDataRef<Meters>>()?.0, 6.0);
+
+    // Comparison of the userdata with itself
+    assert!(!lua.load("m < m").eval::<bool>()?);
+
+    // Invalid operands
+    let err = lua.load("return m - {}").exec().unwrap_err();
+    assert!(err.to_string().contains("table"), "{err}");
+
+    // Userdata must not be mutably borrowed
+    let ud = lua.globals().get::<AnyUserData>("m")?;
+    let _borrow = ud.borrow_mut::<Meters>()?;
+    assert!(lua.load("return m - 1").exec().is_err());
+
+    Ok(())
+}
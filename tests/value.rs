@@ -3,7 +3,10 @@ use std::os::raw::c_void;
 use std::ptr;
 use std::string::String as StdString;
 
-use mlua::{Error, LightUserData, Lua, MultiValue, Result, UserData, UserDataMethods, Value};
+use mlua::{
+    Error, HashAlgorithm, HashOptions, Integer, LightUserData, Lua, LuaSourceOptions, MultiValue,
+    NumberPolicy, Result, Table, UserData, UserDataMethods, Value,
+};
 
 #[test]
 fn test_value_eq() -> Result<()> {
@@ -143,6 +146,79 @@ fn test_value_to_string() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_value_hash() -> Result<()> {
+    let lua = Lua::new();
+    let hash = |code: &str| lua.load(code).eval::<Value>()?.hash(HashAlgorithm::Fnv1a64);
+
+    // Stable across runs and platforms
+    assert_eq!(hash("nil")?, 0xaf63bd4c8601b7df);
+    assert_eq!(hash("'abc'")?, hash("'abc'")?);
+    assert_ne!(hash("'abc'")?, hash("'abd'")?);
+    assert_eq!(hash("1")?, hash("1.0")?);
+    assert_ne!(hash("1")?, hash("'1'")?);
+    assert_ne!(hash("true")?, hash("false")?);
+
+    // Tables are hashed structurally
+    let a = hash("{ x = 1, y = { 'a', 'b' }, [true] = 0.5 }")?;
+    let b = hash("local t = { [true] = 0.5 }; t.y = { 'a', 'b' }; t.x = 1; return t")?;
+    assert_eq!(a, b);
+    assert_ne!(a, hash("{ x = 1, y = { 'b', 'a' }, [true] = 0.5 }")?);
+    assert_ne!(hash("{}")?, hash("{ {} }")?);
+
+    // Cycles
+    let c1 = hash("local t = { a = 1 }; t.self = t; return t")?;
+    let c2 = hash("local t = { a = 1 }; t.self = t; return t")?;
+    assert_eq!(c1, c2);
+    assert_ne!(c1, hash("local t = { a = 2 }; t.self = t; return t")?);
+
+    // Unsupported values
+    match hash("{ f = print }") {
+        Err(Error::FromLuaConversionError { from: "function", .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    // Metamethods
+    struct MyUserData(i64);
+    impl UserData for MyUserData {
+        fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+            methods.add_meta_method("__hash", |_, this, ()| Ok(this.0));
+        }
+    }
+    let ud = Value::UserData(lua.create_userdata(MyUserData(42))?);
+    assert!(ud.hash(HashAlgorithm::Fnv1a64).is_err());
+    let options = HashOptions::new().use_metamethods(true);
+    assert_eq!(ud.hash_with(HashAlgorithm::Fnv1a64, options)?, hash("42")?);
+    let t: Value = lua
+        .load("setmetatable({ a = 1 }, { __hash = function() return 'key' end })")
+        .eval()?;
+    assert_eq!(t.hash(HashAlgorithm::Fnv1a64)?, hash("{ a = 1 }")?);
+    assert_eq!(t.hash_with(HashAlgorithm::Fnv1a64, options)?, hash("'key'")?);
+
+    // Recursive metamethods are detected
+    let recursive: Vec<Value> = lua
+        .load(
+            r#"
+        local a, b = {}, {}
+        setmetatable(a, { __hash = function() return b end })
+        setmetatable(b, { __hash = function() return a end })
+        local fresh = {}
+        fresh.__hash = function() return setmetatable({}, fresh) end
+        return setmetatable({}, { __hash = function(self) return self end }), a, setmetatable({}, fresh)
+    "#,
+        )
+        .eval()?;
+    for value in recursive {
+        assert!(value.hash(HashAlgorithm::Fnv1a64).is_ok());
+        match value.hash_with(HashAlgorithm::Fnv1a64, options) {
+            Err(Error::FromLuaConversionError { .. }) => {}
+            r => panic!("expected FromLuaConversionError, got {r:?}"),
+        }
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_debug_format() -> Result<()> {
     let lua = Lua::new();
@@ -222,3 +298,196 @@ fn test_value_conversions() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_value_number_canonicalization() -> Result<()> {
+    let lua = Lua::new();
+
+    assert_eq!(Value::Integer(3).as_number_lossless(), Some(3.0));
+    assert_eq!(Value::Number(3.5).as_number_lossless(), Some(3.5));
+    assert_eq!(
+        Value::Integer(Integer::MIN).as_number_lossless(),
+        Some(Integer::MIN as f64)
+    );
+    assert_eq!(Value::Boolean(true).as_number_lossless(), None);
+    #[cfg(not(feature = "luau"))]
+    {
+        assert_eq!(Value::Integer(Integer::MAX).as_number_lossless(), None);
+        assert_eq!(Value::Integer((1 << 53) + 1).as_number_lossless(), None);
+    }
+
+    let prefer_int = |v: Value| v.canonicalize_numbers(NumberPolicy::PreferInteger);
+    let prefer_float = |v: Value| v.canonicalize_numbers(NumberPolicy::PreferFloat);
+
+    // The same value produced by Lua is canonicalized consistently across Lua versions
+    for value in [lua.load("2^2").eval::<Value>()?, lua.load("4").eval::<Value>()?] {
+        assert_eq!(prefer_int(value.clone()).as_integer(), Some(4));
+        assert_eq!(prefer_float(value).as_number(), Some(4.0));
+    }
+    assert!(matches!(prefer_int(Value::Number(-0.0)), Value::Integer(0)));
+    assert!(matches!(prefer_int(Value::Number(1.5)), Value::Number(n) if n == 1.5));
+    assert!(matches!(prefer_int(Value::Number(f64::NAN)), Value::Number(n) if n.is_nan()));
+    assert!(matches!(
+        prefer_int(Value::Number(f64::INFINITY)),
+        Value::Number(_)
+    ));
+    assert!(matches!(prefer_int(Value::Number(1e300)), Value::Number(_)));
+    #[cfg(not(feature = "luau"))]
+    assert!(matches!(
+        prefer_float(Value::Integer(Integer::MAX)),
+        Value::Integer(Integer::MAX)
+    ));
+    assert!(matches!(prefer_float(Value::Boolean(true)), Value::Boolean(true)));
+
+    Ok(())
+}
+
+#[test]
+fn test_value_identity() -> Result<()> {
+    let lua = Lua::new();
+
+    // Non-objects have no identity
+    assert_eq!(lua.value_identity(&Value::Nil)?, None);
+    assert_eq!(lua.value_identity(&Value::Integer(1))?, None);
+    assert_eq!(
+        lua.value_identity(&Value::String(lua.create_string("abc")?))?,
+        None
+    );
+
+    let t1 = Value::Table(lua.create_table()?);
+    let t2 = Value::Table(lua.create_table()?);
+    let f = Value::Function(lua.create_function(|_, ()| Ok(()))?);
+    let id1 = lua.value_identity(&t1)?.unwrap();
+    let id2 = lua.value_identity(&t2)?.unwrap();
+    let id3 = lua.value_identity(&f)?.unwrap();
+    assert_ne!(id1, id2);
+    assert_ne!(id1, id3);
+    assert_eq!(id1.to_pointer(), t1.to_pointer());
+    assert!(id1.generation() < id2.generation());
+
+    // Identity is stable and does not depend on the handle
+    let t1_copy: Value = lua.load("return ...").call(&t1)?;
+    assert_eq!(lua.value_identity(&t1_copy)?, Some(id1));
+
+    let mut map = HashMap::new();
+    map.insert(id1, "t1");
+    map.insert(id2, "t2");
+    map.insert(id3, "f");
+    assert_eq!(map[&lua.value_identity(&t2)?.unwrap()], "t2");
+
+    // Resolving objects
+    let resolved = lua.resolve_identity(&id1)?.unwrap();
+    assert_eq!(resolved, t1);
+
+    // Collected objects are detected
+    drop((t1, t1_copy, resolved));
+    lua.gc_collect()?;
+    lua.gc_collect()?;
+    assert!(lua.resolve_identity(&id1)?.is_none());
+    assert!(lua.resolve_identity(&id2)?.is_some());
+
+    // A new object never reuses the identity
+    let t3 = Value::Table(lua.create_table()?);
+    let id4 = lua.value_identity(&t3)?.unwrap();
+    assert!(id4.generation() > id3.generation());
+    assert_ne!(id4, id1);
+
+    Ok(())
+}
+
+#[test]
+fn test_value_to_lua_source() -> Result<()> {
+    let lua = Lua::new();
+
+    let value: Value = lua
+        .load(
+            r#"
+        {
+            "a", "b\n\"c\"",
+            name = "mlua",
+            ["key with spaces"] = 1.5,
+            ["end"] = false,
+            [10] = { x = 1, y = {} },
+            [true] = 0/0,
+        }
+    "#,
+        )
+        .eval()?;
+
+    let compact = lua.to_lua_source(&value, LuaSourceOptions::new())?;
+    assert_eq!(
+        compact,
+        r#"{"a", "b\n\"c\"", [true] = 0/0, [10] = {x = 1, y = {}}, ["end"] = false, ["key with spaces"] = 1.5, name = "mlua"}"#
+    );
+
+    let pretty = lua.to_lua_source(&value, LuaSourceOptions::new().pretty(true))?;
+    let expected = r#"{
+  "a",
+  "b\n\"c\"",
+  [true] = 0/0,
+  [10] = {
+    x = 1,
+    y = {},
+  },
+  ["end"] = false,
+  ["key with spaces"] = 1.5,
+  name = "mlua",
+}"#;
+    assert_eq!(pretty, expected);
+
+    // Output can be loaded back
+    let loaded: Value = lua.load(format!("return {pretty}")).eval()?;
+    let loaded = loaded.as_table().unwrap();
+    assert_eq!(loaded.get::<String>(2)?, "b\n\"c\"");
+    assert_eq!(loaded.get::<Value>("key with spaces")?, Value::Number(1.5));
+    assert_eq!(loaded.get::<Table>(10)?.get::<i32>("x")?, 1);
+
+    // Scalars and special numbers
+    let source = |v: Value| lua.to_lua_source(&v, LuaSourceOptions::new());
+    assert_eq!(source(Value::Nil)?, "nil");
+    assert_eq!(source(Value::Number(1.0))?, "1.0");
+    assert_eq!(source(Value::Number(f64::NEG_INFINITY))?, "-1/0");
+    assert_eq!(
+        source(Value::String(lua.create_string(b"\x01\xff9")?))?,
+        r#""\001\2559""#
+    );
+    let min = lua.load(&source(Value::Integer(Integer::MIN))?).eval::<Value>();
+    assert_eq!(min?, Value::Integer(Integer::MIN));
+
+    // Unsupported values
+    let t = lua.create_table()?;
+    t.set(1, "a")?;
+    t.set(2, lua.create_function(|_, ()| Ok(()))?)?;
+    t.set("f", lua.create_function(|_, ()| Ok(()))?)?;
+    match source(Value::Table(t.clone())) {
+        Err(Error::FromLuaConversionError { .. }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+    let options = LuaSourceOptions::new().deny_unsupported_types(false);
+    assert_eq!(
+        lua.to_lua_source(&Value::Table(t.clone()), options)?,
+        r#"{"a", nil}"#
+    );
+
+    // Recursive tables
+    t.set("self", &t)?;
+    match lua.to_lua_source(&Value::Table(t), options) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "recursive table detected")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    // Deeply nested tables
+    let value: Value = lua
+        .load("local t = {} for i = 1, 200000 do t = {t} end return t")
+        .eval()?;
+    match lua.to_lua_source(&value, options) {
+        Err(Error::FromLuaConversionError { message, .. }) => {
+            assert_eq!(message.unwrap(), "recursion limit exceeded")
+        }
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    Ok(())
+}
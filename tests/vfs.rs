@@ -0,0 +1,168 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use mlua::{DirFs, Lua, MemoryFs, OverlayFs, Result, Vfs};
+
+#[test]
+fn test_memory_fs() -> Result<()> {
+    let vfs = MemoryFs::new()
+        .with_file("/lib/a.lua", b"return 1".as_slice())
+        .with_file("lib/sub/b.lua", b"return 2".to_vec());
+
+    assert!(vfs.stat(Path::new("lib")).unwrap().is_dir());
+    assert_eq!(vfs.stat(Path::new("./lib/a.lua")).unwrap().len, 8);
+    assert!(vfs.stat(Path::new("lib/c.lua")).is_err());
+    assert!(vfs.read(Path::new("lib/../lib/a.lua")).is_err());
+    assert_eq!(
+        vfs.list(Path::new("lib")).unwrap(),
+        [PathBuf::from("lib/a.lua"), PathBuf::from("lib/sub")]
+    );
+    assert_eq!(vfs.read(Path::new("lib/sub/b.lua")).unwrap(), b"return 2");
+
+    Ok(())
+}
+
+#[test]
+fn test_overlay_fs() -> Result<()> {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("a.lua"), "return 'disk'")?;
+    fs::write(temp_dir.path().join("b.lua"), "return 'disk'")?;
+
+    let vfs = OverlayFs::new()
+        .with_layer(MemoryFs::new().with_file("a.lua", b"return 'memory'".as_slice()))
+        .with_layer(DirFs::new(temp_dir.path()));
+
+    assert_eq!(vfs.read(Path::new("a.lua")).unwrap(), b"return 'memory'");
+    assert_eq!(vfs.read(Path::new("b.lua")).unwrap(), b"return 'disk'");
+    assert!(vfs.read(Path::new("../a.lua")).is_err());
+    assert_eq!(
+        vfs.list(Path::new("")).unwrap(),
+        [PathBuf::from("a.lua"), PathBuf::from("b.lua")]
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dir_fs_symlinks() -> Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(outside.path().join("secret.lua"), "return 'secret'")?;
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir(root.path().join("lib"))?;
+    fs::write(root.path().join("lib/a.lua"), "return 'a'")?;
+    symlink(outside.path().join("secret.lua"), root.path().join("secret.lua"))?;
+    symlink(outside.path(), root.path().join("outside"))?;
+    symlink(root.path().join("lib/a.lua"), root.path().join("a.lua"))?;
+
+    let vfs = DirFs::new(root.path());
+    // Links inside of the root are followed
+    assert_eq!(vfs.read(Path::new("a.lua")).unwrap(), b"return 'a'");
+    // Links pointing outside of the root are rejected
+    let err = vfs.read(Path::new("secret.lua")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(vfs.read(Path::new("outside/secret.lua")).is_err());
+    assert!(vfs.stat(Path::new("outside")).is_err());
+    assert!(vfs.list(Path::new("outside")).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_vfs_require() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryFs::new()
+        .with_file(
+            "mods/foo.lua",
+            b"return { name = 'foo', bar = require('foo.bar') }".as_slice(),
+        )
+        .with_file("mods/foo/bar.lua", b"return 'bar'".as_slice())
+        .with_file("mods/broken.lua", b"return +".as_slice());
+    lua.set_vfs(vfs)?;
+    lua.load("package.path = 'mods/?.lua'").exec()?;
+
+    let (name, bar): (String, String) = lua
+        .load("local m = require('foo'); return m.name, m.bar")
+        .eval()?;
+    assert_eq!((name.as_str(), bar.as_str()), ("foo", "bar"));
+
+    let err = lua.load("require('missing')").exec().unwrap_err().to_string();
+    #[cfg(not(feature = "luau"))]
+    assert!(err.contains("no file 'mods/missing.lua'"), "{err}");
+    #[cfg(feature = "luau")]
+    assert!(err.contains("module 'missing' not found"), "{err}");
+    assert!(lua.load("require('broken')").exec().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_vfs_require_from_sandbox() -> Result<()> {
+    let lua = Lua::new();
+    let vfs = MemoryFs::new().with_file(
+        "mods/shared.lua",
+        b"return function() return secret end".as_slice(),
+    );
+    lua.set_vfs(vfs)?;
+    lua.load("package.path = 'mods/?.lua'; secret = 'trusted'")
+        .exec()?;
+
+    // The sandbox requires the module first
+    let env = lua.create_table()?;
+    env.set("require", lua.globals().get::<mlua::Function>("require")?)?;
+    env.set("secret", "sandbox")?;
+    let value: String = lua
+        .load("return require('shared')()")
+        .set_environment(env)
+        .eval()?;
+    assert_eq!(value, "trusted");
+
+    // Trusted code gets the module bound to the global environment
+    let value: String = lua.load("return require('shared')()").eval()?;
+    assert_eq!(value, "trusted");
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_vfs_dofile_loadfile() -> Result<()> {
+    let lua = Lua::new();
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("disk.lua"), "return 'disk'")?;
+    let disk_path = temp_dir.path().join("disk.lua");
+    lua.globals().set("disk_path", disk_path.to_str().unwrap())?;
+
+    let vfs = MemoryFs::new()
+        .with_file("main.lua", b"return 1, 2".as_slice())
+        .with_file("args.lua", b"return ...".as_slice())
+        .with_file("env.lua", b"return value".as_slice())
+        .with_file("broken.lua", b"return +".as_slice());
+    lua.set_vfs(vfs)?;
+
+    let (a, b): (i64, i64) = lua.load("return dofile('main.lua')").eval()?;
+    assert_eq!((a, b), (1, 2));
+    assert_eq!(lua.load("return loadfile('args.lua')(3)").eval::<i64>()?, 3);
+    #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+    assert_eq!(
+        lua.load("return loadfile('env.lua', 't', { value = 42 })()")
+            .eval::<i64>()?,
+        42
+    );
+
+    let (f, err): (Option<mlua::Function>, String) = lua.load("return loadfile('broken.lua')").eval()?;
+    assert!(f.is_none() && err.contains("broken.lua"), "{err}");
+    let (f, err): (Option<mlua::Function>, String) = lua.load("return loadfile(disk_path)").eval()?;
+    assert!(f.is_none() && err.contains("cannot open"), "{err}");
+    assert!(lua.load("dofile('missing.lua')").exec().is_err());
+
+    // Real filesystem is used after removing vfs
+    lua.remove_vfs();
+    assert_eq!(lua.load("return dofile(disk_path)").eval::<String>()?, "disk");
+    assert!(lua.load("return dofile('main.lua')").exec().is_err());
+
+    Ok(())
+}